@@ -0,0 +1,6 @@
+-- Optional page-title templates per Confluence target.
+-- Placeholders: {server}, {label}, {code}, {date}, plus {before}/{after} for diffs.
+-- NULL keeps the built-in default title.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS snapshot_title_template TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS family_title_template TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS diff_title_template TEXT;
@@ -0,0 +1,16 @@
+-- Publish mode per Confluence target: 'overwrite' updates the same root page on every
+-- publish, 'versioned' creates a new dated page per snapshot under an index page.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS publish_mode TEXT NOT NULL DEFAULT 'overwrite';
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS version_title_template TEXT;
+
+-- Every versioned publish, used to generate the index page linking historical snapshots.
+CREATE TABLE IF NOT EXISTS publication_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    akeneo_server_id UUID NOT NULL REFERENCES akeneo_server(id) ON DELETE CASCADE,
+    snapshot_id UUID NOT NULL REFERENCES snapshot(id) ON DELETE CASCADE,
+    page_id TEXT NOT NULL,
+    page_title TEXT NOT NULL,
+    web_url TEXT NOT NULL,
+    published_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (akeneo_server_id, page_id)
+);
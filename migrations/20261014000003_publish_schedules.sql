@@ -0,0 +1,14 @@
+-- Cron schedules per Akeneo server, run by the built-in scheduler.
+-- action: 'snapshot' fetches the live model from Akeneo and publishes it,
+--         'diff' publishes the server's most recent diff.
+CREATE TABLE IF NOT EXISTS publish_schedule (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    akeneo_server_id UUID NOT NULL REFERENCES akeneo_server(id) ON DELETE CASCADE,
+    cron TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('snapshot', 'diff')),
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    last_run_at TIMESTAMPTZ,
+    last_status TEXT,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
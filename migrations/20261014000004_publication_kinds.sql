@@ -0,0 +1,11 @@
+-- Record every publish in publication_history, not only versioned ones, so listings can
+-- show whether a snapshot or diff is published. 'version' rows are still the only ones
+-- shown on the versioned index page.
+ALTER TABLE publication_history
+    ADD COLUMN IF NOT EXISTS kind TEXT NOT NULL DEFAULT 'version'
+        CHECK (kind IN ('version', 'overwrite', 'diff'));
+ALTER TABLE publication_history
+    ADD COLUMN IF NOT EXISTS diff_id UUID REFERENCES diff(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS publication_history_snapshot_id_idx ON publication_history (snapshot_id);
+CREATE INDEX IF NOT EXISTS publication_history_diff_id_idx ON publication_history (diff_id);
@@ -0,0 +1,10 @@
+-- Draft-first publishing: publishes can be staged as '[Draft] ' pages under a staging
+-- parent page and promoted to the live location after review.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS draft_publishing BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS staging_parent_page TEXT;
+
+ALTER TABLE publication_history DROP CONSTRAINT IF EXISTS publication_history_kind_check;
+ALTER TABLE publication_history
+    ADD CONSTRAINT publication_history_kind_check
+        CHECK (kind IN ('version', 'overwrite', 'diff', 'draft'));
+ALTER TABLE publication_history ADD COLUMN IF NOT EXISTS promoted_at TIMESTAMPTZ;
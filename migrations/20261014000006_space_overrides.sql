@@ -0,0 +1,3 @@
+-- Per-request space overrides: spaces a publish may be redirected to with ?space_key=,
+-- in addition to the configured space_key.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS allowed_space_keys TEXT[] NOT NULL DEFAULT '{}';
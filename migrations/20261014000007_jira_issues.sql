@@ -0,0 +1,11 @@
+-- Jira issues raised for diffs with breaking changes (removed or renamed attributes and
+-- families). Jira Cloud shares the Atlassian site and credentials of the Confluence target.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS jira_project_key TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS jira_issue_type TEXT NOT NULL DEFAULT 'Task';
+
+-- One issue per diff, so republishing a diff does not raise it again.
+CREATE TABLE IF NOT EXISTS diff_jira_issue (
+    diff_id UUID PRIMARY KEY REFERENCES diff(id) ON DELETE CASCADE,
+    issue_key TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
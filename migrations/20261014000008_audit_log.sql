@@ -0,0 +1,19 @@
+-- Who published what: one row per publish, promotion, withdrawal and scheduled run,
+-- exported through GET /api/audit and pruned after AUDIT_RETENTION_DAYS.
+CREATE TABLE IF NOT EXISTS audit_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- API key name, webhook source, 'admin', 'scheduler' or the client IP
+    actor TEXT NOT NULL,
+    -- Route (e.g. 'GET /api/snapshot/{id}') or 'scheduler:<action>'
+    endpoint TEXT NOT NULL,
+    -- The snapshot, diff, publication or server the operation was on
+    target_id UUID,
+    parameters JSONB NOT NULL DEFAULT '{}',
+    -- 'ok', 'error', or 'skipped' (a scheduled run with nothing to publish)
+    result TEXT NOT NULL,
+    error TEXT,
+    page_urls TEXT[] NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_created_at_idx ON audit_log (created_at);
@@ -0,0 +1,4 @@
+-- Threshold alerts a diff raised when it was published (e.g. most attributes removed,
+-- usually a broken export), as a JSON array of `diff::DiffAlert`. Empty for snapshots and
+-- for diffs within their thresholds.
+ALTER TABLE publication_history ADD COLUMN IF NOT EXISTS alerts JSONB NOT NULL DEFAULT '[]'::jsonb;
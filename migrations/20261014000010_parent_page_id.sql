@@ -0,0 +1,4 @@
+-- Parent page by ID: survives renames of the parent page and needs no title search.
+-- When set it takes precedence over parent_page, which stays as the fallback.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS parent_page_id TEXT
+    CHECK (parent_page_id ~ '^[0-9]+$');
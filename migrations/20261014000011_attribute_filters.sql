@@ -0,0 +1,7 @@
+-- Attribute filters applied to snapshot pages (the attributes section, family attribute
+-- tables and attribute options), e.g. to leave technical attributes undocumented. Each
+-- rule is `group:<group code>`, `prefix:<code prefix>` or `regex:<pattern>` (matched
+-- against the attribute code). An attribute is shown when it matches an include rule (or
+-- there are none) and no exclude rule.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS attribute_include TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS attribute_exclude TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,14 @@
+-- Akeneo event receiver (POST /api/webhooks/akeneo/{id}). `webhook_secret` is the shared
+-- secret requests are signed with, a literal or an `env:` / `vault:` reference; NULL turns
+-- the receiver off for the server. `webhook_nonce` keeps the signature of every accepted
+-- request for the timestamp tolerance, so a replayed request is rejected.
+ALTER TABLE akeneo_server ADD COLUMN IF NOT EXISTS webhook_secret TEXT;
+
+CREATE TABLE IF NOT EXISTS webhook_nonce (
+    akeneo_server_id UUID NOT NULL REFERENCES akeneo_server(id) ON DELETE CASCADE,
+    nonce TEXT NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (akeneo_server_id, nonce)
+);
+
+CREATE INDEX IF NOT EXISTS webhook_nonce_received_at ON webhook_nonce (received_at);
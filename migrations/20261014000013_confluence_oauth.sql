@@ -0,0 +1,16 @@
+-- OAuth 2.0 (3LO) as an alternative to username + API token. An OAuth target routes its
+-- requests through api.atlassian.com/ex/confluence/{cloud_id} with an access token
+-- refreshed from oauth_refresh_token, which Atlassian rotates on every refresh (the new
+-- one is written back here). oauth_client_secret and oauth_refresh_token are encrypted
+-- like api_token, or may be secret references. username / api_token stay in use for Jira.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS auth_type TEXT NOT NULL DEFAULT 'api_token'
+    CHECK (auth_type IN ('api_token', 'oauth'));
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS oauth_client_id TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS oauth_client_secret TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS oauth_refresh_token TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS cloud_id TEXT;
+ALTER TABLE confluence_config ADD CONSTRAINT confluence_config_oauth_check CHECK (
+    auth_type <> 'oauth'
+    OR (oauth_client_id IS NOT NULL AND oauth_client_secret IS NOT NULL
+        AND oauth_refresh_token IS NOT NULL AND cloud_id IS NOT NULL)
+);
@@ -0,0 +1,6 @@
+-- TLS settings for sites whose certificate the built-in roots do not cover, typically
+-- Data Center behind an internal CA. ca_certificate is a PEM bundle or the path of a PEM
+-- file, trusted in addition to the built-in roots. danger_accept_invalid_certs skips
+-- certificate verification altogether and is meant for test instances only.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS ca_certificate TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS danger_accept_invalid_certs BOOLEAN NOT NULL DEFAULT false;
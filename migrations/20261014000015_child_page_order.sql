@@ -0,0 +1,5 @@
+-- Order of the family pages under a snapshot's root page, applied after every publish:
+-- by family label (default), family code or page title, or 'none' to leave them in
+-- creation order.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS child_page_order TEXT NOT NULL DEFAULT 'label'
+    CHECK (child_page_order IN ('none', 'label', 'code', 'title'));
@@ -0,0 +1,5 @@
+-- Intermediate index pages between a snapshot's root page and its family pages, for
+-- targets with large family sets: by the first letter of the label, by the attribute
+-- group of the family's attribute_as_label, or by the code prefix before the first '_'.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS family_grouping TEXT NOT NULL DEFAULT 'none'
+    CHECK (family_grouping IN ('none', 'first_letter', 'label_attribute_group', 'prefix'));
@@ -0,0 +1,6 @@
+-- Copies of every live publish (page bodies and a JSON report) kept in S3-compatible
+-- object storage for compliance. A target without a bucket is not archived; the keys
+-- written for a publish are recorded with it.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS archive_bucket TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS archive_prefix TEXT NOT NULL DEFAULT '';
+ALTER TABLE publication_history ADD COLUMN IF NOT EXISTS archive_keys TEXT[];
@@ -0,0 +1,10 @@
+-- Git repository every live snapshot publish of a target is committed to (JSON and/or
+-- Markdown), for model history in code review tooling. No URL: not mirrored. The token
+-- is stored like the Confluence API token (encrypted, or an env:/vault: reference).
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS git_mirror_url TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS git_mirror_branch TEXT NOT NULL DEFAULT 'main';
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS git_mirror_username TEXT NOT NULL DEFAULT 'x-access-token';
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS git_mirror_token TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS git_mirror_path TEXT NOT NULL DEFAULT '';
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS git_mirror_format TEXT NOT NULL DEFAULT 'json'
+    CHECK (git_mirror_format IN ('json', 'markdown', 'both'));
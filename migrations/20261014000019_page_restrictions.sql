@@ -0,0 +1,8 @@
+-- Confluence page restrictions set on every generated page after it is created or
+-- updated, so the pages are read-only (or hidden) for everyone else. Groups by name,
+-- users by Atlassian account ID; an operation with no groups and no users is left
+-- unrestricted.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS view_restriction_groups TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS view_restriction_users TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS edit_restriction_groups TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS edit_restriction_users TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,8 @@
+-- Snapshot data may be stored zstd-compressed (`storage.snapshot_codec = "zstd"`): the
+-- JSON then lives in data_zstd and data is NULL. Every row has exactly one of the two;
+-- POST /api/admin/migrate-snapshot-storage converts existing rows.
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS data_zstd BYTEA;
+ALTER TABLE snapshot ALTER COLUMN data DROP NOT NULL;
+ALTER TABLE snapshot DROP CONSTRAINT IF EXISTS snapshot_data_stored;
+ALTER TABLE snapshot ADD CONSTRAINT snapshot_data_stored
+    CHECK ((data IS NULL) <> (data_zstd IS NULL));
@@ -0,0 +1,9 @@
+-- Snapshot lifecycle for the retention job (`[snapshot_retention]`). Archived snapshots
+-- are left out of listings but still open and publish by ID; soft-deleted ones
+-- (deleted_at set) are treated as gone. POST /api/admin/snapshots/{id}/restore brings
+-- either back and sets restored_at, which exempts the snapshot from later runs.
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS archived BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS restored_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS snapshot_server_completed_at
+    ON snapshot (akeneo_server_id, completed_at);
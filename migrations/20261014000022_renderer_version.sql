@@ -0,0 +1,5 @@
+-- The renderer version (`renderer::RENDERER_VERSION`) that produced each publication, so
+-- pages published by an older renderer can be refreshed by `POST /api/republish`.
+-- Publications recorded before versioning count as version 0.
+ALTER TABLE publication_history
+    ADD COLUMN IF NOT EXISTS renderer_version INTEGER NOT NULL DEFAULT 0;
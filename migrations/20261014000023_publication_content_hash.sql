@@ -0,0 +1,4 @@
+-- SHA-256 of the rendered page tree a snapshot publication published (see
+-- `publish::content_hash`), so `if_changed=true` can skip publishing identical content.
+-- Diff publications and publications recorded before hashing have none.
+ALTER TABLE publication_history ADD COLUMN IF NOT EXISTS content_hash TEXT;
@@ -0,0 +1,5 @@
+-- Locale (e.g. 'de-DE') and IANA time zone (e.g. 'Europe/Berlin') that counts and
+-- timestamps on a target's pages are formatted for (see `render_options::DisplayFormat`).
+-- Unset keeps ungrouped counts and UTC timestamps.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS display_locale TEXT;
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS display_timezone TEXT;
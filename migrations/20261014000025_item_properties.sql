@@ -0,0 +1,8 @@
+-- Fields the diff added / removed tables of a target show when no columns are configured
+-- (see `render_options::ItemProperties`): `item_priority_fields` first, in order, and never
+-- `item_skip_fields`. Unset keeps the built-in lists; an empty array lists none.
+-- `item_columns` sets the columns of one category's tables, e.g.
+-- '{"families": ["code", "label", "attribute_as_label"]}'.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS item_priority_fields TEXT[];
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS item_skip_fields TEXT[];
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS item_columns JSONB NOT NULL DEFAULT '{}'::jsonb;
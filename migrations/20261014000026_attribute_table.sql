@@ -0,0 +1,5 @@
+-- Attributes table layout of a target's snapshot pages: `extended` adds attribute
+-- constraints to the attributes table and the family pages (see
+-- `TableOptions.extended_attributes`). Unset keeps the `ATTRIBUTE_TABLE` default.
+ALTER TABLE confluence_config ADD COLUMN IF NOT EXISTS attribute_table TEXT
+    CHECK (attribute_table IN ('basic', 'extended'));
@@ -0,0 +1,379 @@
+use axum::{
+    extract::State,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::crypto;
+use crate::db;
+use crate::extract::Query;
+use crate::jobs::JobStatus;
+use crate::metrics;
+use crate::{AppState, ErrorResponse};
+
+/// Check the `Authorization: Bearer <token>` header against the configured `ADMIN_TOKEN`.
+/// Admin endpoints are disabled entirely when no token is configured.
+pub fn require_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let Some(expected) = state.admin_token.as_deref() else {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(
+                "Admin endpoints are disabled (ADMIN_TOKEN is not set)",
+            )),
+        ));
+    };
+
+    let provided = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => Ok(()),
+        _ => {
+            warn!("Rejected admin request with missing or invalid token");
+            Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::new("Missing or invalid admin token")),
+            ))
+        }
+    }
+}
+
+/// Compare two byte strings without short-circuiting on the first mismatch.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Runtime state exposed to on-call operators.
+#[derive(Serialize)]
+struct DebugState<'a> {
+    /// The startup configuration in effect, without the database URL.
+    config: &'a AppConfig,
+    active_jobs: Vec<JobStatus>,
+    render_cache: RenderCacheState,
+    confluence_clients: ClientCacheState,
+    confluence_configs: ConfigCacheState,
+    scheduler: SchedulerState,
+    db_pool: DbPoolState,
+    confluence_http: ConfluenceHttpState,
+    /// Request budget per Confluence site (see `confluence::PublishCoordinator`).
+    confluence_sites: Vec<ConfluenceSiteState>,
+}
+
+#[derive(Serialize)]
+struct RenderCacheState {
+    entries: usize,
+    max_entries: usize,
+    hits: u64,
+    misses: u64,
+    hit_rate: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct ClientCacheState {
+    servers: usize,
+    entries: usize,
+    ttl_secs: u64,
+}
+
+#[derive(Serialize)]
+struct ConfigCacheState {
+    entries: usize,
+    ttl_secs: u64,
+}
+
+#[derive(Serialize)]
+struct SchedulerState {
+    next_prerender_at: Option<DateTime<Utc>>,
+    /// The next run of every `publish_schedule` row (`None` when disabled or invalid).
+    schedules: Vec<ScheduledRunState>,
+}
+
+#[derive(Serialize)]
+struct ScheduledRunState {
+    id: Uuid,
+    server_name: String,
+    action: String,
+    cron: String,
+    running: bool,
+    next_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct ConfluenceSiteState {
+    base_url: String,
+    paused_until: Option<DateTime<Utc>>,
+    tokens: Option<f64>,
+    waiting: usize,
+    in_flight: Option<usize>,
+    breaker: Option<BreakerState>,
+}
+
+#[derive(Serialize)]
+struct BreakerState {
+    state: &'static str,
+    consecutive_failures: u64,
+    opens_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct DbPoolState {
+    size: u32,
+    idle: usize,
+    max_connections: u32,
+}
+
+#[derive(Serialize)]
+struct ConfluenceHttpState {
+    requests: u64,
+    connections_opened: u64,
+    http2_responses: u64,
+}
+
+/// GET /api/debug/state
+///
+/// Returns a JSON snapshot of runtime state (startup configuration, active jobs, cache
+/// statistics, scheduled run times, pool statistics, Confluence request budgets) for
+/// incident diagnosis. Requires the admin token.
+pub async fn handle_debug_state(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+
+    let cache = state.render_cache.stats();
+    let lookups = cache.hits + cache.misses;
+    let http = metrics::confluence_counters();
+    let clients = state.clients.stats();
+    let configs = state.confluence_configs.stats();
+    // A database outage is a likely reason to look here; report the rest regardless
+    let schedules = state.scheduler.statuses(&state).await.unwrap_or_else(|e| {
+        warn!("Debug state: failed to read the schedules: {:#}", e);
+        Vec::new()
+    });
+
+    let debug_state = DebugState {
+        config: &state.config,
+        active_jobs: state.jobs.active(),
+        render_cache: RenderCacheState {
+            entries: cache.entries,
+            max_entries: cache.max_entries,
+            hits: cache.hits,
+            misses: cache.misses,
+            hit_rate: (lookups > 0).then(|| cache.hits as f64 / lookups as f64),
+        },
+        confluence_clients: ClientCacheState {
+            servers: clients.servers,
+            entries: clients.entries,
+            ttl_secs: clients.ttl_secs,
+        },
+        confluence_configs: ConfigCacheState {
+            entries: configs.entries,
+            ttl_secs: configs.ttl_secs,
+        },
+        scheduler: SchedulerState {
+            next_prerender_at: state.render_cache.next_prerender_at(),
+            schedules: schedules
+                .into_iter()
+                .map(|s| ScheduledRunState {
+                    id: s.id,
+                    server_name: s.server_name,
+                    action: s.action,
+                    cron: s.cron,
+                    running: s.running,
+                    next_run_at: s.next_run_at,
+                })
+                .collect(),
+        },
+        db_pool: DbPoolState {
+            size: state.pool.size(),
+            idle: state.pool.num_idle(),
+            max_connections: state.pool.options().get_max_connections(),
+        },
+        confluence_http: ConfluenceHttpState {
+            requests: http.requests,
+            connections_opened: http.connections_opened,
+            http2_responses: http.http2_responses,
+        },
+        confluence_sites: state
+            .coordinator
+            .stats()
+            .into_iter()
+            .map(|site| ConfluenceSiteState {
+                base_url: site.base_url,
+                paused_until: site.paused_until,
+                tokens: site.tokens,
+                waiting: site.waiting,
+                in_flight: site.in_flight,
+                breaker: site.breaker.map(|breaker| BreakerState {
+                    state: breaker.state,
+                    consecutive_failures: breaker.consecutive_failures,
+                    opens_at: breaker.opens_at,
+                }),
+            })
+            .collect(),
+    };
+
+    (StatusCode::OK, Json(debug_state)).into_response()
+}
+
+/// Outcome of a token re-encryption run.
+#[derive(Serialize)]
+struct ReencryptResponse {
+    status: &'static str,
+    /// Tokens that were plaintext or used a retired key and are now encrypted.
+    reencrypted: usize,
+    /// Tokens already encrypted with the current key.
+    unchanged: usize,
+}
+
+/// POST /api/admin/reencrypt-tokens (admin)
+///
+/// Encrypts every Confluence API token with the current `TOKEN_ENCRYPTION_KEY`: legacy
+/// plaintext tokens are encrypted and tokens under a retired key are re-wrapped.
+/// Safe to run repeatedly.
+pub async fn handle_reencrypt_tokens(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+
+    let Some(cipher) = crypto::cipher() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("TOKEN_ENCRYPTION_KEY is not set")),
+        )
+            .into_response();
+    };
+
+    match db::reencrypt_confluence_tokens(&state.pool, cipher).await {
+        Ok((reencrypted, unchanged)) => {
+            info!(
+                "Re-encrypted {} Confluence API token(s), {} already current",
+                reencrypted, unchanged
+            );
+            Json(ReencryptResponse {
+                status: "ok",
+                reencrypted,
+                unchanged,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            error!("Token re-encryption failed: {:#}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MigrateStorageParams {
+    /// Convert at most this many snapshots (default: all of them).
+    limit: Option<usize>,
+}
+
+/// Outcome of a snapshot storage migration.
+#[derive(Serialize)]
+struct MigrateStorageResponse {
+    status: &'static str,
+    /// The configured codec the snapshots were converted to.
+    codec: &'static str,
+    migrated: usize,
+    /// Snapshots still stored with the other codec.
+    remaining: i64,
+}
+
+/// POST /api/admin/migrate-snapshot-storage (admin)
+///
+/// Converts stored snapshots to the configured `storage.snapshot_codec`: compresses JSONB
+/// data for `zstd`, or decompresses it again for `json`. `?limit=` bounds one call; safe
+/// to run repeatedly.
+pub async fn handle_migrate_snapshot_storage(
+    State(state): State<AppState>,
+    Query(params): Query<MigrateStorageParams>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+
+    let storage = &state.config.storage;
+    match db::migrate_snapshot_storage(&state.pool, storage, params.limit).await {
+        Ok(migration) => {
+            info!(
+                "Migrated {} snapshot(s) to {} storage, {} remaining",
+                migration.migrated,
+                storage.snapshot_codec.name(),
+                migration.remaining
+            );
+            Json(MigrateStorageResponse {
+                status: "ok",
+                codec: storage.snapshot_codec.name(),
+                migrated: migration.migrated,
+                remaining: migration.remaining,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            error!("Snapshot storage migration failed: {:#}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct InvalidateParams {
+    /// Only this Akeneo server's target (default: every target).
+    server: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+struct InvalidateResponse {
+    status: &'static str,
+    /// Cached targets that were dropped.
+    invalidated: usize,
+}
+
+/// POST /api/admin/confluence-config-cache/invalidate (admin)
+///
+/// Drops cached `confluence_config` rows (of one server with `?server=`), so an edited
+/// target takes effect on the next publish instead of when its entry expires.
+pub async fn handle_invalidate_config_cache(
+    State(state): State<AppState>,
+    Query(params): Query<InvalidateParams>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+
+    let invalidated = state.confluence_configs.invalidate(params.server);
+    match params.server {
+        Some(server_id) => info!("Invalidated cached Confluence config of server {}", server_id),
+        None => info!("Invalidated {} cached Confluence config(s)", invalidated),
+    }
+    Json(InvalidateResponse {
+        status: "ok",
+        invalidated,
+    })
+    .into_response()
+}
@@ -0,0 +1,291 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use reqwest::header::ACCEPT;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::db::{self, DbAkeneoServer};
+use crate::AppState;
+
+/// Page size for Akeneo list endpoints (the API maximum).
+const PAGE_LIMIT: &str = "100";
+
+/// Attribute types whose options are fetched into `attribute_options`.
+const OPTION_ATTRIBUTE_TYPES: [&str; 2] = ["pim_catalog_simpleselect", "pim_catalog_multiselect"];
+
+/// Attribute type whose measurement families are fetched into `measurement_families`.
+const METRIC_ATTRIBUTE_TYPE: &str = "pim_catalog_metric";
+
+/// Pull the live data model from an Akeneo server and store it as a new snapshot.
+/// Returns the new snapshot's ID. The server's client secret and password may be secret
+/// references (`env:` / `vault:`); the data is stored with the `[storage]` codec.
+pub async fn fetch_and_store_snapshot(
+    state: &AppState,
+    akeneo_server_id: Uuid,
+    label: Option<&str>,
+) -> Result<Uuid> {
+    let (pool, secrets) = (&state.pool, &state.secrets);
+    let mut server = db::fetch_akeneo_server(pool, akeneo_server_id).await?;
+    server.client_secret = secrets
+        .resolve(&server.client_secret)
+        .await
+        .context("Failed to resolve Akeneo client secret")?;
+    server.password = secrets
+        .resolve(&server.password)
+        .await
+        .context("Failed to resolve Akeneo password")?;
+    info!("Fetching live model from Akeneo server '{}'", server.name);
+
+    let started_at = Utc::now();
+    let client = AkeneoClient::connect(&server).await?;
+    let data = client.fetch_model().await?;
+    let completed_at = Utc::now();
+
+    let storage = &state.config.storage;
+    let snapshot_id =
+        db::insert_snapshot(pool, storage, server.id, label, started_at, completed_at, &data)
+            .await?;
+    info!(
+        "Stored snapshot {} for Akeneo server '{}' ({}s)",
+        snapshot_id,
+        server.name,
+        (completed_at - started_at).num_seconds()
+    );
+
+    Ok(snapshot_id)
+}
+
+/// Akeneo PIM REST API client, authenticated with the server's API connection
+/// (client ID/secret plus the connection's username/password).
+pub struct AkeneoClient {
+    client: Client,
+    base_url: String,
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// One page of an Akeneo list endpoint (`_embedded.items` + `_links.next`).
+#[derive(Deserialize)]
+struct ListPage {
+    #[serde(rename = "_embedded")]
+    embedded: Embedded,
+    #[serde(rename = "_links", default)]
+    links: ListLinks,
+}
+
+#[derive(Deserialize)]
+struct Embedded {
+    items: Vec<Value>,
+}
+
+#[derive(Deserialize, Default)]
+struct ListLinks {
+    next: Option<Link>,
+}
+
+#[derive(Deserialize)]
+struct Link {
+    href: String,
+}
+
+impl AkeneoClient {
+    /// Request an access token for the server and build an authenticated client.
+    pub async fn connect(server: &DbAkeneoServer) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .context("Failed to build Akeneo HTTP client")?;
+        let base_url = server.base_url.trim_end_matches('/').to_string();
+
+        let resp = client
+            .post(format!("{}/api/oauth/v1/token", base_url))
+            .basic_auth(&server.client_id, Some(&server.client_secret))
+            .json(&serde_json::json!({
+                "grant_type": "password",
+                "username": &server.username,
+                "password": &server.password,
+            }))
+            .send()
+            .await
+            .context("Failed to request Akeneo access token")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("Akeneo authentication failed (HTTP {}): {}", status, body);
+        }
+
+        let token: TokenResponse = resp
+            .json()
+            .await
+            .context("Failed to parse Akeneo token response")?;
+
+        Ok(Self {
+            client,
+            base_url,
+            access_token: token.access_token,
+        })
+    }
+
+    /// Fetch the live data model in the shape stored in `snapshot.data`:
+    /// `channels`, `families`, `attributes`, `categories` (each sorted by code),
+    /// `attribute_options` keyed by attribute code (select attributes only) and
+    /// `measurement_families` (when there are metric attributes).
+    pub async fn fetch_model(&self) -> Result<Value> {
+        let channels = self.fetch_all("channels").await?;
+        let families = self.fetch_all("families").await?;
+        let attributes = self.fetch_all("attributes").await?;
+        let categories = self.fetch_all("categories").await?;
+
+        let mut attribute_options = Map::new();
+        for attribute in &attributes {
+            let is_select = attribute
+                .get("type")
+                .and_then(Value::as_str)
+                .is_some_and(|t| OPTION_ATTRIBUTE_TYPES.contains(&t));
+            let Some(code) = attribute
+                .get("code")
+                .and_then(Value::as_str)
+                .filter(|_| is_select)
+            else {
+                continue;
+            };
+
+            let options = self
+                .fetch_pages(&format!("attributes/{}/options", code))
+                .await?;
+            attribute_options.insert(code.to_string(), Value::Array(options));
+        }
+
+        let has_metrics = attributes
+            .iter()
+            .any(|a| a.get("type").and_then(Value::as_str) == Some(METRIC_ATTRIBUTE_TYPE));
+        // Only used to label metric units, so a server that denies the endpoint still
+        // gets its snapshot
+        let measurement_families = if has_metrics {
+            self.fetch_measurement_families().await.unwrap_or_else(|e| {
+                warn!("Skipping Akeneo measurement families: {:#}", e);
+                Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+
+        info!(
+            "Fetched Akeneo model: {} channels, {} families, {} attributes, {} categories, {} option lists, {} measurement families",
+            channels.len(),
+            families.len(),
+            attributes.len(),
+            categories.len(),
+            attribute_options.len(),
+            measurement_families.len()
+        );
+
+        Ok(serde_json::json!({
+            "channels": channels,
+            "families": families,
+            "attributes": attributes,
+            "categories": categories,
+            "attribute_options": attribute_options,
+            "measurement_families": measurement_families,
+        }))
+    }
+
+    /// Fetch the measurement families, sorted by code. Unlike the other resources the
+    /// endpoint returns a plain array rather than pages.
+    async fn fetch_measurement_families(&self) -> Result<Vec<Value>> {
+        let resp = self
+            .client
+            .get(format!("{}/api/rest/v1/measurement-families", self.base_url))
+            .bearer_auth(&self.access_token)
+            .header(ACCEPT, "application/json")
+            .send()
+            .await
+            .context("Failed to fetch Akeneo measurement families")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!(
+                "Akeneo measurement-families request failed (HTTP {}): {}",
+                status,
+                body
+            );
+        }
+
+        let mut items: Vec<Value> = resp
+            .json()
+            .await
+            .context("Failed to parse Akeneo measurement-families response")?;
+        let code = |v: &Value| v.get("code").and_then(Value::as_str).unwrap_or("").to_string();
+        items.sort_by_key(code);
+        Ok(items)
+    }
+
+    /// Fetch every item of a list endpoint, sorted by code.
+    async fn fetch_all(&self, resource: &str) -> Result<Vec<Value>> {
+        let mut items = self.fetch_pages(resource).await?;
+        items.sort_by(|a, b| {
+            let code = |v: &Value| v.get("code").and_then(Value::as_str).unwrap_or("").to_string();
+            code(a).cmp(&code(b))
+        });
+        Ok(items)
+    }
+
+    /// Follow `_links.next` through all pages of a list endpoint, in API order.
+    /// Per-item `_links` are dropped; they are API navigation, not model data.
+    async fn fetch_pages(&self, resource: &str) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut request = self
+            .client
+            .get(format!("{}/api/rest/v1/{}", self.base_url, resource))
+            .query(&[("limit", PAGE_LIMIT)]);
+
+        loop {
+            let resp = request
+                .bearer_auth(&self.access_token)
+                .header(ACCEPT, "application/json")
+                .send()
+                .await
+                .with_context(|| format!("Failed to fetch Akeneo {}", resource))?;
+
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let body = resp.text().await.unwrap_or_default();
+                bail!(
+                    "Akeneo {} request failed (HTTP {}): {}",
+                    resource,
+                    status,
+                    body
+                );
+            }
+
+            let page: ListPage = resp
+                .json()
+                .await
+                .with_context(|| format!("Failed to parse Akeneo {} response", resource))?;
+
+            items.extend(page.embedded.items.into_iter().map(|mut item| {
+                if let Some(obj) = item.as_object_mut() {
+                    obj.remove("_links");
+                }
+                item
+            }));
+
+            match page.links.next {
+                Some(next) => request = self.client.get(next.href),
+                None => break,
+            }
+        }
+
+        Ok(items)
+    }
+}
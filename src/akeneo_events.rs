@@ -0,0 +1,195 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::extract::Path;
+use crate::{admin, akeneo, audit, db, hooks, AppState, ErrorResponse};
+
+/// Header carrying the HMAC-SHA256 of `<timestamp>.<body>`, as hex (`sha256=` optional).
+const SIGNATURE_HEADER: &str = "x-akeneo-request-signature";
+
+/// Header carrying the Unix time (seconds) the request was signed at.
+const TIMESTAMP_HEADER: &str = "x-akeneo-request-timestamp";
+
+/// How far a request's timestamp may be from now; nonces are kept this long too.
+const TIMESTAMP_TOLERANCE_SECS: i64 = 300;
+
+/// Audit actor of the snapshots the receiver fetches.
+const ACTOR: &str = "webhook:akeneo";
+
+/// JSON response returned once an event is accepted.
+#[derive(Serialize)]
+struct EventAccepted {
+    /// `fetching`, or `already_fetching` when a fetch of the server was still running.
+    status: &'static str,
+    akeneo_server_id: Uuid,
+}
+
+/// POST /api/webhooks/akeneo/:id
+///
+/// Receives Akeneo events for a server and answers 202 after starting a fetch of its live
+/// model into a new snapshot (as `POST /api/server/{id}/snapshot` does). The request
+/// must be signed with the server's `webhook_secret`: an HMAC-SHA256 of
+/// `<X-Akeneo-Request-Timestamp>.<body>` in `X-Akeneo-Request-Signature`, with a timestamp
+/// within `TIMESTAMP_TOLERANCE_SECS` of now, and not seen before. Anything else is 401.
+pub async fn handle_akeneo_event(
+    State(state): State<AppState>,
+    Path(server_id): Path<Uuid>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let verified = match verify(&state, server_id, &headers, &body).await {
+        Ok(signature) => record_nonce(&state.pool, server_id, &signature).await,
+        Err(rejection) => Err(rejection),
+    };
+    if let Err(rejection) = verified {
+        return rejected(server_id, rejection);
+    }
+
+    // Akeneo sends events in bursts; one fetch picks up all the changes made so far
+    let fetching = state
+        .jobs
+        .active()
+        .iter()
+        .any(|job| job.kind == "fetch" && job.target_id == server_id);
+    if !fetching {
+        info!("Akeneo event for server {}: fetching a snapshot", server_id);
+        tokio::spawn(fetch_snapshot(state, server_id));
+    }
+    let accepted = EventAccepted {
+        status: if fetching { "already_fetching" } else { "fetching" },
+        akeneo_server_id: server_id,
+    };
+    (StatusCode::ACCEPTED, Json(accepted)).into_response()
+}
+
+enum Rejection {
+    Unauthorized(&'static str),
+    Internal(anyhow::Error),
+}
+
+/// 401 for an unauthorized event, 500 when it could not be checked.
+fn rejected(server_id: Uuid, rejection: Rejection) -> Response {
+    match rejection {
+        Rejection::Unauthorized(reason) => {
+            warn!("Rejected Akeneo event for server {}: {}", server_id, reason);
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::new(format!("Unauthorized event: {}", reason))),
+            )
+                .into_response()
+        }
+        Rejection::Internal(e) => internal_error(server_id, e),
+    }
+}
+
+/// Check a request's timestamp and signature; returns the signature, the request's nonce.
+async fn verify(
+    state: &AppState,
+    server_id: Uuid,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<String, Rejection> {
+    let timestamp =
+        check_timestamp(headers, Utc::now().timestamp()).map_err(Rejection::Unauthorized)?;
+
+    // An unknown server and one without a secret look the same to the caller
+    let secret = db::fetch_webhook_secret(&state.pool, server_id)
+        .await
+        .map_err(Rejection::Internal)?
+        .ok_or(Rejection::Unauthorized("no webhook secret configured"))?;
+    let secret = state.secrets.resolve(&secret).await.map_err(Rejection::Internal)?;
+
+    check_signature(headers, timestamp, body, secret.as_bytes()).map_err(Rejection::Unauthorized)
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim)
+}
+
+/// The request's timestamp, if it is within `TIMESTAMP_TOLERANCE_SECS` of `now`.
+fn check_timestamp(headers: &HeaderMap, now: i64) -> Result<i64, &'static str> {
+    let timestamp = header(headers, TIMESTAMP_HEADER)
+        .ok_or("missing timestamp")?
+        .parse::<i64>()
+        .map_err(|_| "malformed timestamp")?;
+    if (now - timestamp).abs() > TIMESTAMP_TOLERANCE_SECS {
+        return Err("timestamp outside the tolerance");
+    }
+    Ok(timestamp)
+}
+
+/// The request's signature, if it is the HMAC of `<timestamp>.<body>` with `secret`.
+fn check_signature(
+    headers: &HeaderMap,
+    timestamp: i64,
+    body: &[u8],
+    secret: &[u8],
+) -> Result<String, &'static str> {
+    let signature = header(headers, SIGNATURE_HEADER)
+        .ok_or("missing signature")?
+        .trim_start_matches("sha256=")
+        .to_ascii_lowercase();
+
+    let mut signed = format!("{}.", timestamp).into_bytes();
+    signed.extend_from_slice(body);
+    let expected = hooks::sign(secret, &signed);
+    let expected = expected.trim_start_matches("sha256=");
+    if !admin::constant_time_eq(expected.as_bytes(), signature.as_bytes()) {
+        return Err("invalid signature");
+    }
+    Ok(signature)
+}
+
+/// Record the request's nonce; a nonce the server already sent is a replay.
+async fn record_nonce(pool: &PgPool, server_id: Uuid, nonce: &str) -> Result<(), Rejection> {
+    let expired_before = Utc::now() - Duration::seconds(TIMESTAMP_TOLERANCE_SECS);
+    match db::record_webhook_nonce(pool, server_id, nonce, expired_before).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Rejection::Unauthorized("already received")),
+        Err(e) => Err(Rejection::Internal(e)),
+    }
+}
+
+/// Fetch and store a snapshot of the server, recorded in the audit log.
+async fn fetch_snapshot(state: AppState, server_id: Uuid) {
+    let fetched = {
+        let _job = state.jobs.start("fetch", server_id);
+        akeneo::fetch_and_store_snapshot(&state, server_id, None).await
+    };
+    if let Err(e) = &fetched {
+        error!("Failed to fetch a snapshot of server {} after an event: {:#}", server_id, e);
+    }
+    let entry = audit::Entry {
+        actor: ACTOR,
+        endpoint: "POST /api/webhooks/akeneo/{id}",
+        target_id: *fetched.as_ref().unwrap_or(&server_id),
+        parameters: serde_json::json!({ "server_id": server_id }),
+    };
+    let outcome = match &fetched {
+        Ok(_) => audit::Outcome::Ok(Vec::new()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, entry, outcome).await;
+}
+
+fn internal_error(server_id: Uuid, e: anyhow::Error) -> Response {
+    error!("Failed to receive an Akeneo event for server {}: {:#}", server_id, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(format!("{:#}", e))),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests;
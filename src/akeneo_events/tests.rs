@@ -0,0 +1,101 @@
+//! The 401 paths of the Akeneo event receiver: a wrong signature, a stale timestamp and a
+//! replayed nonce.
+
+use axum::http::HeaderValue;
+
+use super::*;
+
+const SECRET: &[u8] = b"webhook-secret";
+const BODY: &[u8] = br#"{"events": [{"action": "product.updated"}]}"#;
+const NOW: i64 = 1_791_964_800;
+
+/// Headers of a request signed with `secret` at `timestamp`.
+fn signed_headers(secret: &[u8], timestamp: i64) -> HeaderMap {
+    let mut signed = format!("{}.", timestamp).into_bytes();
+    signed.extend_from_slice(BODY);
+    let mut headers = HeaderMap::new();
+    headers.insert(TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+    headers.insert(
+        SIGNATURE_HEADER,
+        HeaderValue::try_from(hooks::sign(secret, &signed)).unwrap(),
+    );
+    headers
+}
+
+fn assert_unauthorized(result: Result<impl std::fmt::Debug, Rejection>, reason: &str) {
+    match result {
+        Err(Rejection::Unauthorized(rejected)) => {
+            assert_eq!(rejected, reason);
+            let response = super::rejected(Uuid::nil(), Rejection::Unauthorized(rejected));
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        Err(Rejection::Internal(e)) => panic!("expected 401 ({}), got an error: {:#}", reason, e),
+        Ok(value) => panic!("expected 401 ({}), got {:?}", reason, value),
+    }
+}
+
+#[test]
+fn accepts_a_signed_request() {
+    let headers = signed_headers(SECRET, NOW);
+    let timestamp = check_timestamp(&headers, NOW).unwrap();
+    let signature = check_signature(&headers, timestamp, BODY, SECRET).unwrap();
+    assert!(!signature.starts_with("sha256="));
+}
+
+#[test]
+fn rejects_a_bad_signature() {
+    let headers = signed_headers(b"another-secret", NOW);
+    let result = check_signature(&headers, NOW, BODY, SECRET).map_err(Rejection::Unauthorized);
+    assert_unauthorized(result, "invalid signature");
+
+    // A signature of another body or timestamp does not carry over
+    let headers = signed_headers(SECRET, NOW);
+    let result = check_signature(&headers, NOW, b"{}", SECRET).map_err(Rejection::Unauthorized);
+    assert_unauthorized(result, "invalid signature");
+    let result =
+        check_signature(&headers, NOW + 1, BODY, SECRET).map_err(Rejection::Unauthorized);
+    assert_unauthorized(result, "invalid signature");
+}
+
+#[test]
+fn rejects_a_stale_timestamp() {
+    let stale = NOW - TIMESTAMP_TOLERANCE_SECS - 1;
+    let headers = signed_headers(SECRET, stale);
+    let result = check_timestamp(&headers, NOW).map_err(Rejection::Unauthorized);
+    assert_unauthorized(result, "timestamp outside the tolerance");
+
+    let early = NOW + TIMESTAMP_TOLERANCE_SECS + 1;
+    let headers = signed_headers(SECRET, early);
+    let result = check_timestamp(&headers, NOW).map_err(Rejection::Unauthorized);
+    assert_unauthorized(result, "timestamp outside the tolerance");
+
+    let edge = NOW - TIMESTAMP_TOLERANCE_SECS;
+    assert_eq!(check_timestamp(&signed_headers(SECRET, edge), NOW), Ok(edge));
+}
+
+#[tokio::test]
+#[ignore = "needs a migrated database in DATABASE_URL"]
+async fn rejects_a_replayed_nonce() {
+    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL");
+    let pool = PgPool::connect(&url).await.unwrap();
+    let name = format!("webhook-test-{}", Uuid::new_v4());
+    let server = db::NewAkeneoServer {
+        name: &name,
+        base_url: "https://pim.example.com",
+        client_id: "client",
+        client_secret: "secret",
+        username: "user",
+        password: "password",
+        webhook_secret: Some("webhook-secret"),
+    };
+    let server_id = db::insert_akeneo_server(&pool, &server).await.unwrap();
+
+    let first = record_nonce(&pool, server_id, "nonce").await;
+    let replayed = record_nonce(&pool, server_id, "nonce").await;
+    let other = record_nonce(&pool, server_id, "other-nonce").await;
+    db::delete_akeneo_server(&pool, server_id).await.unwrap();
+
+    assert!(first.is_ok());
+    assert_unauthorized(replayed, "already received");
+    assert!(other.is_ok());
+}
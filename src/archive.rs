@@ -0,0 +1,435 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::db::{self, SnapshotRow};
+use crate::renderer::{self, escape_html, SnapshotPageTree};
+use crate::titles::{
+    option_page_title, TitleContext, TitleTemplates, CATEGORY_TREE_TITLE_SUFFIX,
+    COVERAGE_TITLE_SUFFIX, HEALTH_TITLE_SUFFIX, LABELS_TITLE_SUFFIX,
+};
+use crate::AppState;
+
+const MACRO_OPEN: &str = "<ac:structured-macro";
+const MACRO_CLOSE: &str = "</ac:structured-macro>";
+const RICH_BODY_OPEN: &str = "<ac:rich-text-body>";
+const RICH_BODY_CLOSE: &str = "</ac:rich-text-body>";
+const PLAIN_BODY_OPEN: &str = "<ac:plain-text-body><![CDATA[";
+const PLAIN_BODY_CLOSE: &str = "]]></ac:plain-text-body>";
+const PAGE_LINK_OPEN: &str = "<ac:link><ri:page ri:content-title=\"";
+const PAGE_LINK_CLOSE: &str = "</ac:link>";
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 80rem; \
+       padding: 0 1rem; color: #172b4d; line-height: 1.4; }
+nav { margin-bottom: 1rem; }
+table { border-collapse: collapse; width: 100%; margin: 0.5rem 0 1.5rem; }
+th, td { text-align: left; vertical-align: top; padding: 0.3rem 0.5rem; \
+         border: 1px solid #dfe1e6; }
+th { background: #f4f5f7; }
+pre { background: #f4f5f7; padding: 0.75rem; overflow-x: auto; }
+details { margin: 0.5rem 0; }
+summary { cursor: pointer; color: #0052cc; }
+.panel { border-left: 4px solid #0065ff; background: #deebff; padding: 0.5rem 1rem; \
+         margin: 1rem 0; }
+.panel.note { border-color: #ffab00; background: #fffae6; }
+.panel.warning { border-color: #ff5630; background: #ffebe6; }
+.panel-title { font-weight: bold; }
+.status { display: inline-block; font-size: 0.75rem; font-weight: bold; \
+          text-transform: uppercase; padding: 0.05rem 0.35rem; border-radius: 3px; \
+          background: #dfe1e6; }
+.status.green { background: #e3fcef; color: #006644; }
+.status.red { background: #ffebe6; color: #bf2600; }
+.status.yellow { background: #fffae6; color: #974f0c; }
+.status.blue { background: #deebff; color: #0747a6; }
+.status.purple { background: #eae6ff; color: #403294; }
+";
+
+/// One file of the export.
+struct ExportPage {
+    file: String,
+    title: String,
+    body: String,
+}
+
+/// Render a snapshot's page tree to standalone HTML files (an `index.html` linking every
+/// page, one file per page and a `style.css`) in a ZIP, under a `snapshot-<id>/` folder.
+/// The tree is rendered with the attribute filters and display format and titled with the
+/// title templates of the server's Confluence target, when it has one, so the export
+/// matches a publish. Confluence is not contacted.
+pub async fn export_snapshot(state: &AppState, snapshot: &SnapshotRow) -> Result<Vec<u8>> {
+    let configs = &state.confluence_configs;
+    let config = match configs.fetch(&state.pool, snapshot.akeneo_server_id).await {
+        Ok(config) => Some(config),
+        Err(e) if db::is_not_found(&e) => None,
+        Err(e) => return Err(e.context("Failed to fetch Confluence configuration")),
+    };
+    let render_options = match &config {
+        Some(config) => state.render_options.for_target(config)?,
+        None => state.render_options.as_ref().clone(),
+    };
+    let title_templates = config
+        .as_ref()
+        .map(TitleTemplates::from_config)
+        .unwrap_or_default();
+
+    let tree = state.render_cache.get_or_render(snapshot, &render_options);
+
+    let label = snapshot.label.as_deref().unwrap_or("Unnamed snapshot");
+    let date = snapshot.completed_at.format("%Y-%m-%d").to_string();
+    let time = snapshot.completed_at.format("%H:%M").to_string();
+    let ctx = TitleContext {
+        server: config.as_ref().map_or("", |c| c.server_name.as_str()),
+        label,
+        date: &date,
+        time: &time,
+        ..Default::default()
+    };
+    let pages = export_pages(&tree, &title_templates, &ctx);
+    let title = title_templates.snapshot_title(&ctx);
+    let index = render_index(&title, label, snapshot, &pages);
+
+    let folder = format!("snapshot-{}", snapshot.id);
+    tokio::task::spawn_blocking(move || write_zip(&folder, &title, index, pages))
+        .await
+        .context("Export task panicked")?
+}
+
+/// The pages of a tree with their file names and titles, in index order: the root page,
+/// the analysis pages, the option pages, then the family pages.
+fn export_pages(
+    tree: &SnapshotPageTree,
+    title_templates: &TitleTemplates,
+    ctx: &TitleContext<'_>,
+) -> Vec<ExportPage> {
+    let root_title = title_templates.snapshot_title(ctx);
+    let family_titles: Vec<String> = tree
+        .children
+        .iter()
+        .map(|child| {
+            title_templates.family_title(&TitleContext {
+                label: &child.label,
+                code: &child.code,
+                ..*ctx
+            })
+        })
+        .collect();
+    let root_body = renderer::link_family_titles(
+        &renderer::link_root_title(&tree.root_body, &root_title),
+        tree.children
+            .iter()
+            .zip(&family_titles)
+            .map(|(child, title)| (child.code.as_str(), title.as_str())),
+    );
+    let page = |file: String, title: String, body: &str| ExportPage {
+        file,
+        title,
+        body: storage_to_html(body),
+    };
+
+    let mut pages = vec![
+        page(
+            "overview.html".to_string(),
+            root_title.clone(),
+            &root_body,
+        ),
+        page(
+            "coverage.html".to_string(),
+            format!("{}{}", root_title, COVERAGE_TITLE_SUFFIX),
+            &tree.coverage_body,
+        ),
+        page(
+            "health.html".to_string(),
+            format!("{}{}", root_title, HEALTH_TITLE_SUFFIX),
+            &tree.health_body,
+        ),
+    ];
+    if let Some(body) = &tree.labels_body {
+        pages.push(page(
+            "labels.html".to_string(),
+            format!("{}{}", root_title, LABELS_TITLE_SUFFIX),
+            body,
+        ));
+    }
+    pages.extend(tree.category_trees.iter().map(|category_tree| {
+        page(
+            format!("category-tree-{}.html", file_stem(&category_tree.code)),
+            format!(
+                "{}{}{} ({})",
+                root_title, CATEGORY_TREE_TITLE_SUFFIX, category_tree.label, category_tree.code
+            ),
+            &category_tree.body,
+        )
+    }));
+    pages.extend(tree.option_pages.iter().map(|option_page| {
+        page(
+            format!(
+                "options-{}-{}.html",
+                file_stem(&option_page.attribute),
+                option_page.page
+            ),
+            option_page_title(
+                &root_title,
+                &option_page.attribute,
+                option_page.page,
+                option_page.pages,
+            ),
+            &renderer::link_root_title(&option_page.body, &root_title),
+        )
+    }));
+    pages.extend(tree.children.iter().zip(family_titles).map(|(child, title)| {
+        page(
+            format!("family-{}.html", file_stem(&child.code)),
+            title,
+            &child.body,
+        )
+    }));
+    link_pages(&mut pages);
+    pages
+}
+
+/// Point the links between pages (storage-format `ac:link`s to a page title, such as the
+/// option pages' navigation) at the exported files. A link to a page outside the export
+/// keeps only its text.
+fn link_pages(pages: &mut [ExportPage]) {
+    let files: HashMap<String, String> = pages
+        .iter()
+        .map(|page| (escape_html(&page.title), page.file.clone()))
+        .collect();
+    for page in pages.iter_mut() {
+        let mut out = String::new();
+        let mut rest = page.body.as_str();
+        while let Some(start) = rest.find(PAGE_LINK_OPEN) {
+            let link = &rest[start + PAGE_LINK_OPEN.len()..];
+            let Some(end) = link.find(PAGE_LINK_CLOSE) else {
+                break;
+            };
+            out.push_str(&rest[..start]);
+            let (title, tail) = link[..end].split_once('"').unwrap_or_default();
+            let text = tail
+                .split_once("<ac:link-body>")
+                .and_then(|(_, text)| text.strip_suffix("</ac:link-body>"))
+                .unwrap_or(title);
+            match files.get(title) {
+                Some(file) => out.push_str(&format!("<a href=\"{}\">{}</a>", file, text)),
+                None => out.push_str(text),
+            }
+            rest = &link[end + PAGE_LINK_CLOSE.len()..];
+        }
+        out.push_str(rest);
+        page.body = out;
+    }
+}
+
+/// The index page: the snapshot's details and a link to every exported page.
+fn render_index(title: &str, label: &str, snapshot: &SnapshotRow, pages: &[ExportPage]) -> String {
+    let (families, others): (Vec<_>, Vec<_>) = pages
+        .iter()
+        .partition(|page| page.file.starts_with("family-"));
+    let links = |pages: &[&ExportPage]| -> String {
+        pages
+            .iter()
+            .map(|page| {
+                format!(
+                    "<li><a href=\"{}\">{}</a></li>",
+                    escape_html(&page.file),
+                    escape_html(&page.title)
+                )
+            })
+            .collect()
+    };
+
+    let mut body = format!("<h1>{}</h1>", escape_html(title));
+    body.push_str(&format!(
+        "<p>Snapshot <strong>{}</strong>, completed {}. Exported {}.</p>",
+        escape_html(label),
+        snapshot.completed_at.format("%Y-%m-%d %H:%M UTC"),
+        Utc::now().format("%Y-%m-%d %H:%M UTC"),
+    ));
+    body.push_str(&format!("<h2>Pages</h2><ul>{}</ul>", links(&others)));
+    body.push_str(&format!(
+        "<h2>Families ({})</h2><ul>{}</ul>",
+        families.len(),
+        links(&families)
+    ));
+    body
+}
+
+fn write_zip(folder: &str, title: &str, index: String, pages: Vec<ExportPage>) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut add = |name: &str, content: &str| -> Result<()> {
+        zip.start_file(format!("{}/{}", folder, name), options)
+            .with_context(|| format!("Failed to add {} to the export", name))?;
+        zip.write_all(content.as_bytes())
+            .with_context(|| format!("Failed to write {} to the export", name))
+    };
+    add("style.css", STYLE)?;
+    add("index.html", &html_document(title, &index, false))?;
+    for page in &pages {
+        add(&page.file, &html_document(&page.title, &page.body, true))?;
+    }
+
+    let cursor = zip.finish().context("Failed to finish the export")?;
+    Ok(cursor.into_inner())
+}
+
+/// A standalone HTML document using the export's stylesheet.
+fn html_document(title: &str, body: &str, back_link: bool) -> String {
+    let nav = if back_link {
+        "<nav><a href=\"index.html\">\u{2190} Index</a></nav>\n"
+    } else {
+        ""
+    };
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<link rel=\"stylesheet\" href=\"style.css\">\n</head>\n\
+         <body>\n{}{}\n</body>\n</html>\n",
+        escape_html(title),
+        nav,
+        body
+    )
+}
+
+/// A code as a file name: characters other than letters, digits, `-` and `_` become `_`.
+fn file_stem(code: &str) -> String {
+    code.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Convert Confluence storage format to plain HTML: macros become their closest HTML
+/// equivalent (expands a `<details>`, panels and status lozenges styled `<div>`s and
+/// `<span>`s, code and diagram sources a `<pre>`); other macros keep their body.
+fn storage_to_html(storage: &str) -> String {
+    let mut out = String::new();
+    let mut rest = storage;
+    while let Some(start) = rest.find(MACRO_OPEN) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let Some(tag_end) = after.find('>') else {
+            break;
+        };
+        let tag = &after[..tag_end];
+        let name = attribute(tag, "ac:name").unwrap_or_default();
+        if tag.ends_with('/') {
+            out.push_str(&convert_macro(name, ""));
+            rest = &after[tag_end + 1..];
+            continue;
+        }
+        let content = &after[tag_end + 1..];
+        let Some(len) = macro_content_len(content) else {
+            break;
+        };
+        out.push_str(&convert_macro(name, &content[..len]));
+        rest = &content[len + MACRO_CLOSE.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Length of a macro's content up to its matching closing tag, skipping nested macros
+/// and CDATA sections.
+fn macro_content_len(content: &str) -> Option<usize> {
+    let mut depth = 1;
+    let mut pos = 0;
+    loop {
+        let next = content[pos..].find('<')? + pos;
+        let tail = &content[next..];
+        if tail.starts_with("<![CDATA[") {
+            pos = next + tail.find("]]>")? + 3;
+        } else if tail.starts_with(MACRO_CLOSE) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(next);
+            }
+            pos = next + MACRO_CLOSE.len();
+        } else {
+            if tail.starts_with(MACRO_OPEN) {
+                depth += 1;
+            }
+            pos = next + 1;
+        }
+    }
+}
+
+fn convert_macro(name: &str, content: &str) -> String {
+    let head_len = [RICH_BODY_OPEN, PLAIN_BODY_OPEN]
+        .iter()
+        .filter_map(|open| content.find(open))
+        .min()
+        .unwrap_or(content.len());
+    let head = &content[..head_len];
+    let title = parameter(head, "title");
+
+    let rich = content.find(RICH_BODY_OPEN).and_then(|start| {
+        let start = start + RICH_BODY_OPEN.len();
+        let end = content.rfind(RICH_BODY_CLOSE).filter(|&end| end >= start)?;
+        Some(storage_to_html(&content[start..end]))
+    });
+    let plain = content.find(PLAIN_BODY_OPEN).and_then(|start| {
+        let start = start + PLAIN_BODY_OPEN.len();
+        let end = content
+            .rfind(PLAIN_BODY_CLOSE)
+            .filter(|&end| end >= start)?;
+        Some(escape_html(
+            &content[start..end].replace("]]]]><![CDATA[>", "]]>"),
+        ))
+    });
+
+    match name {
+        "expand" => format!(
+            "<details><summary>{}</summary>{}</details>",
+            title.unwrap_or("Details"),
+            rich.unwrap_or_default()
+        ),
+        "info" | "note" | "tip" | "warning" => format!(
+            "<div class=\"panel {}\">{}{}</div>",
+            name,
+            title.map_or(String::new(), |title| format!(
+                "<p class=\"panel-title\">{}</p>",
+                title
+            )),
+            rich.unwrap_or_default()
+        ),
+        "status" => format!(
+            "<span class=\"status {}\">{}</span>",
+            escape_html(&parameter(head, "colour").unwrap_or_default().to_lowercase()),
+            title.unwrap_or_default()
+        ),
+        _ => match (plain, rich) {
+            (Some(source), _) => format!("<pre><code>{}</code></pre>", source),
+            (None, Some(body)) => {
+                format!("<div class=\"macro {}\">{}</div>", escape_html(name), body)
+            }
+            (None, None) => String::new(),
+        },
+    }
+}
+
+/// The (still escaped) value of a macro parameter, from the part of a macro before its
+/// body.
+fn parameter<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<ac:parameter ac:name=\"{}\">", name);
+    let start = head.find(&open)? + open.len();
+    let end = head[start..].find("</ac:parameter>")? + start;
+    Some(&head[start..end])
+}
+
+/// The value of a double-quoted attribute of a tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("{}=\"", name);
+    let start = tag.find(&open)? + open.len();
+    let end = tag[start..].find('"')? + start;
+    Some(&tag[start..end])
+}
@@ -0,0 +1,307 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::db::{self, AuditRecord, NewAuditEntry};
+use crate::extract::Query;
+use crate::{admin, http_cache, listing, rate_limit};
+use crate::{AppState, ErrorResponse};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// How often entries past the retention period are deleted.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Header a webhook relay sets to name the system it forwards events from.
+const WEBHOOK_SOURCE_HEADER: &str = "x-webhook-source";
+
+/// Settings of the `audit_log` table, which records every publish, promotion, withdrawal
+/// and scheduled run with the identity of whoever triggered it.
+pub struct AuditLog {
+    /// `(name, key)` pairs naming the API keys clients send.
+    key_names: Vec<(String, String)>,
+    /// Days entries are kept; `None` keeps them forever.
+    retention_days: Option<u32>,
+}
+
+impl AuditLog {
+    /// Build the settings from the environment:
+    /// - `API_KEY_NAMES` — comma-separated `name=key` pairs; a request made with one of
+    ///   these keys is recorded under its name
+    /// - `AUDIT_RETENTION_DAYS` — days entries are kept (default 365, `0` keeps them
+    ///   forever)
+    pub fn from_env() -> Self {
+        let key_names = std::env::var("API_KEY_NAMES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| {
+                let (name, key) = pair.split_once('=')?;
+                let (name, key) = (name.trim(), key.trim());
+                if name.is_empty() || key.is_empty() {
+                    warn!("Ignoring malformed API_KEY_NAMES entry (expected name=key)");
+                    return None;
+                }
+                Some((name.to_string(), key.to_string()))
+            })
+            .collect();
+        let retention_days = std::env::var("AUDIT_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(365u32);
+
+        Self {
+            key_names,
+            retention_days: Some(retention_days).filter(|&days| days > 0),
+        }
+    }
+
+    /// The name of a recognised API key: its `API_KEY_NAMES` name, or `admin` for the
+    /// admin token.
+    pub fn key_name(&self, state: &AppState, key: &str) -> Option<String> {
+        let matches = |expected: &str| admin::constant_time_eq(key.as_bytes(), expected.as_bytes());
+        if let Some((name, _)) = self.key_names.iter().find(|(_, k)| matches(k)) {
+            return Some(name.clone());
+        }
+        state.admin_token.as_deref().is_some_and(matches).then(|| "admin".to_string())
+    }
+
+    /// Identify the caller of a request, in order of preference: `webhook:<source>` from
+    /// `X-Webhook-Source` when sent with a recognised key, the name of its API key, the last
+    /// characters of an unnamed API key, or the client IP. Anyone can set the header, so
+    /// without a recognised key the source is only noted after the caller, e.g.
+    /// `ip:1.2.3.4 (webhook:pim)`.
+    fn actor(&self, state: &AppState, headers: &HeaderMap, peer: Option<SocketAddr>) -> String {
+        let key = rate_limit::api_key(headers);
+        let name = key.and_then(|key| self.key_name(state, key));
+        let source = headers
+            .get(WEBHOOK_SOURCE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|s| !s.is_empty());
+        if let Some(name) = name {
+            return match source {
+                Some(source) => format!("webhook:{}", source),
+                None => name,
+            };
+        }
+        let caller = match (key, peer) {
+            // Never store a full key; enough of it to tell keys apart
+            (Some(key), _) => format!(
+                "key:…{}",
+                key.get(key.len().saturating_sub(4)..).unwrap_or_default()
+            ),
+            (None, Some(peer)) => format!("ip:{}", peer.ip()),
+            (None, None) => "unknown".to_string(),
+        };
+        match source {
+            Some(source) => format!("{} (webhook:{})", caller, source),
+            None => caller,
+        }
+    }
+}
+
+/// Who made a request, as recorded in the audit log's `actor` column.
+pub struct Actor(pub String);
+
+impl FromRequestParts<AppState> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Infallible> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+        Ok(Actor(state.audit.actor(state, &parts.headers, peer)))
+    }
+}
+
+/// An operation to record.
+pub struct Entry<'a> {
+    pub actor: &'a str,
+    /// The route, e.g. `GET /api/snapshot/{id}`, or `scheduler:<action>`.
+    pub endpoint: &'a str,
+    /// The snapshot, diff, publication or server the operation was on.
+    pub target_id: Uuid,
+    /// See [`parameters`].
+    pub parameters: serde_json::Value,
+}
+
+/// How an operation ended.
+pub enum Outcome<'a> {
+    /// Done; with the URLs of the Confluence pages it published.
+    Ok(Vec<String>),
+    /// A scheduled run that found nothing to publish.
+    Skipped,
+    Error(&'a anyhow::Error),
+}
+
+/// Request parameters as stored in the `parameters` column, without unset fields.
+pub fn parameters(params: &impl Serialize) -> serde_json::Value {
+    let mut value = serde_json::to_value(params).unwrap_or_default();
+    if let serde_json::Value::Object(map) = &mut value {
+        map.retain(|_, v| !v.is_null());
+    }
+    value
+}
+
+/// Record an operation in the audit log. A failure to record is logged, not returned, so
+/// the caller still reports the outcome of the operation itself.
+pub async fn record(state: &AppState, entry: Entry<'_>, outcome: Outcome<'_>) {
+    let (result, error, page_urls) = match outcome {
+        Outcome::Ok(urls) => ("ok", None, urls),
+        Outcome::Skipped => ("skipped", None, Vec::new()),
+        Outcome::Error(e) => ("error", Some(format!("{:#}", e)), Vec::new()),
+    };
+    let recorded = db::record_audit(
+        &state.pool,
+        NewAuditEntry {
+            actor: entry.actor,
+            endpoint: entry.endpoint,
+            target_id: Some(entry.target_id),
+            parameters: entry.parameters,
+            result,
+            error: error.as_deref(),
+            page_urls: &page_urls,
+        },
+    )
+    .await;
+    if let Err(e) = recorded {
+        error!(
+            "Failed to audit {} on {} by {}: {:#}",
+            entry.endpoint, entry.target_id, entry.actor, e
+        );
+    }
+}
+
+/// Delete entries older than `AUDIT_RETENTION_DAYS` once a day.
+pub fn spawn_retention(pool: PgPool, audit: &AuditLog) {
+    let Some(days) = audit.retention_days else {
+        info!("Audit log retention disabled; entries are kept forever");
+        return;
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let cutoff = Utc::now() - ChronoDuration::days(days.into());
+            match db::prune_audit(&pool, cutoff).await {
+                Ok(0) => {}
+                Ok(deleted) => info!(
+                    "Pruned {} audit log entries older than {} days",
+                    deleted, days
+                ),
+                Err(e) => error!("Audit log retention: {:#}", e),
+            }
+        }
+    });
+}
+
+/// Query parameters of `GET /api/audit`.
+///
+/// `since` / `until` accept an RFC 3339 timestamp or a `YYYY-MM-DD` date; an `until` date
+/// includes that whole day.
+#[derive(Deserialize)]
+pub struct AuditParams {
+    since: Option<String>,
+    until: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// One page of the audit log.
+#[derive(Serialize)]
+struct AuditPage {
+    items: Vec<AuditItem>,
+    limit: i64,
+    offset: i64,
+}
+
+#[derive(Serialize)]
+struct AuditItem {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+    actor: String,
+    endpoint: String,
+    target_id: Option<Uuid>,
+    parameters: serde_json::Value,
+    /// `ok`, `error` or `skipped`.
+    result: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    page_urls: Vec<String>,
+}
+
+impl From<AuditRecord> for AuditItem {
+    fn from(row: AuditRecord) -> Self {
+        Self {
+            id: row.id,
+            created_at: row.created_at,
+            actor: row.actor,
+            endpoint: row.endpoint,
+            target_id: row.target_id,
+            parameters: row.parameters,
+            result: row.result,
+            error: row.error,
+            page_urls: row.page_urls,
+        }
+    }
+}
+
+/// GET /api/audit (admin)
+///
+/// Exports the audit log, oldest first: `?since=2026-01-01&until=2026-02-01`. Page through
+/// a long range with `limit` (default 100, at most 1000) and `offset`.
+pub async fn handle_audit(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<AuditParams>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+
+    let bounds = (
+        params.since.as_deref().map(|v| listing::parse_bound(v, false)).transpose(),
+        params.until.as_deref().map(|v| listing::parse_bound(v, true)).transpose(),
+    );
+    let (since, until) = match bounds {
+        (Ok(since), Ok(until)) => (since, until),
+        (Err(message), _) | (_, Err(message)) => {
+            return (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(message))).into_response();
+        }
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    match db::list_audit(&state.pool, since, until, limit, offset).await {
+        Ok(rows) => http_cache::json(
+            &headers,
+            &AuditPage {
+                items: rows.into_iter().map(AuditItem::from).collect(),
+                limit,
+                offset,
+            },
+        ),
+        Err(e) => {
+            error!("Failed to export audit log: {:#}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
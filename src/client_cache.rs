@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::confluence::{ConfluenceClient, ConfluenceConfig};
+
+/// Targets kept per server: the live target, a draft's staging parent and a few
+/// per-request overrides.
+const MAX_TARGETS_PER_SERVER: usize = 4;
+
+/// In-memory cache of Confluence clients, keyed by Akeneo server ID.
+///
+/// A cached client has its API token resolved (a `vault:` reference costs a Vault request)
+/// and its parent page title resolved to a page ID (otherwise a content search before
+/// every root page is created). A client is only reused for the exact configuration it
+/// was built from, so an edited `confluence_config` row takes effect on the next publish;
+/// a resolved parent ID can go stale when the page is moved or recreated, which the TTL
+/// bounds.
+pub struct ClientCache {
+    entries: Mutex<HashMap<Uuid, Vec<CacheEntry>>>,
+    ttl: Duration,
+}
+
+struct CacheEntry {
+    /// What the client was built from, with the token reference and parent title as
+    /// stored, after the per-request overrides.
+    config: ConfluenceConfig,
+    client: ConfluenceClient,
+    inserted_at: Instant,
+}
+
+/// Point-in-time statistics about the client cache.
+pub struct ClientCacheStats {
+    pub servers: usize,
+    pub entries: usize,
+    pub ttl_secs: u64,
+}
+
+impl ClientCache {
+    /// Build a cache from the environment:
+    /// - `CONFLUENCE_CLIENT_CACHE_TTL_SECS` — how long a client is reused (default 300,
+    ///   `0` disables the cache)
+    pub fn from_env() -> Self {
+        let ttl_secs = std::env::var("CONFLUENCE_CLIENT_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl: Duration::from_secs(ttl_secs),
+        }
+    }
+
+    /// A client built from `config` for this server, unless it has expired.
+    pub fn get(
+        &self,
+        akeneo_server_id: Uuid,
+        config: &ConfluenceConfig,
+    ) -> Option<ConfluenceClient> {
+        let mut entries = self.entries.lock().unwrap();
+        let targets = entries.get_mut(&akeneo_server_id)?;
+        targets.retain(|entry| entry.inserted_at.elapsed() < self.ttl);
+        let found = targets
+            .iter()
+            .find(|entry| entry.config == *config)
+            .map(|entry| entry.client.clone());
+        if targets.is_empty() {
+            entries.remove(&akeneo_server_id);
+        }
+        found
+    }
+
+    /// Store a client built from `config`. Entries of the server built with another URL,
+    /// user or credentials are dropped: its configuration changed.
+    pub fn insert(
+        &self,
+        akeneo_server_id: Uuid,
+        config: ConfluenceConfig,
+        client: ConfluenceClient,
+    ) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let targets = entries.entry(akeneo_server_id).or_default();
+        targets.retain(|entry| {
+            entry.config.base_url == config.base_url
+                && entry.config.email == config.email
+                && entry.config.api_token == config.api_token
+                && entry.config.oauth == config.oauth
+                && entry.config != config
+        });
+        if targets.len() >= MAX_TARGETS_PER_SERVER
+            && let Some(oldest) = targets
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, entry)| entry.inserted_at)
+                .map(|(i, _)| i)
+        {
+            targets.remove(oldest);
+        }
+        targets.push(CacheEntry {
+            config,
+            client,
+            inserted_at: Instant::now(),
+        });
+    }
+
+    /// Forget a server's clients, so its next publish looks its token and parent page up
+    /// again.
+    pub fn invalidate(&self, akeneo_server_id: Uuid) {
+        self.entries.lock().unwrap().remove(&akeneo_server_id);
+    }
+
+    pub fn stats(&self) -> ClientCacheStats {
+        let entries = self.entries.lock().unwrap();
+        ClientCacheStats {
+            servers: entries.len(),
+            entries: entries.values().map(Vec::len).sum(),
+            ttl_secs: self.ttl.as_secs(),
+        }
+    }
+}
@@ -0,0 +1,617 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgConnectOptions;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::render_options::THEMES;
+
+/// Startup configuration: the service's port, database pool, snapshot storage and
+/// retention, Confluence mode and request budget, feature flags and renderer theme.
+///
+/// Loaded once by `load`: defaults, then the TOML file named by `CONFIG_FILE` (optional),
+/// then the environment variables, which win. Finer settings (caches, render options, the
+/// secrets backend, ...) are still read by their own modules.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    /// `PORT` (default 3000).
+    pub port: u16,
+    pub database: DatabaseConfig,
+    pub storage: StorageConfig,
+    pub snapshot_retention: SnapshotRetentionConfig,
+    pub confluence: ConfluenceModeConfig,
+    pub concurrency: ConcurrencyConfig,
+    pub features: FeatureFlags,
+    pub renderer: RendererConfig,
+}
+
+/// The `[database]` table.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// `DATABASE_URL`, required. Never serialized, as it may carry a password.
+    #[serde(skip_serializing)]
+    pub url: Option<String>,
+    /// `DB_MAX_CONNECTIONS` (default 10).
+    pub max_connections: u32,
+    /// `DB_MIN_CONNECTIONS` (default 0).
+    pub min_connections: u32,
+    /// `DB_ACQUIRE_TIMEOUT_SECS` (default 30).
+    pub acquire_timeout_secs: u64,
+    /// `DB_IDLE_TIMEOUT_SECS` (default 600, `0` keeps idle connections forever).
+    pub idle_timeout_secs: u64,
+    /// `DB_STATEMENT_CACHE_CAPACITY`: prepared statements cached per connection (default
+    /// 100).
+    pub statement_cache_capacity: usize,
+}
+
+/// The `[storage]` table: how new snapshots store their `data`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// `SNAPSHOT_CODEC`: `json` (default) or `zstd`.
+    pub snapshot_codec: SnapshotCodec,
+    /// `SNAPSHOT_ZSTD_LEVEL`: zstd compression level, 1 to 22 (default 3).
+    pub zstd_level: i32,
+}
+
+/// How a snapshot's `data` is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotCodec {
+    /// The JSONB `data` column, readable by anything that queries the table.
+    Json,
+    /// zstd-compressed JSON in the `data_zstd` column; `data` is NULL.
+    Zstd,
+}
+
+impl SnapshotCodec {
+    pub fn name(self) -> &'static str {
+        match self {
+            SnapshotCodec::Json => "json",
+            SnapshotCodec::Zstd => "zstd",
+        }
+    }
+}
+
+impl FromStr for SnapshotCodec {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        match value.to_ascii_lowercase().as_str() {
+            "json" => Ok(SnapshotCodec::Json),
+            "zstd" => Ok(SnapshotCodec::Zstd),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The `[snapshot_retention]` table: old snapshots the daily retention job archives or
+/// soft-deletes (see `snapshot_retention.rs`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotRetentionConfig {
+    /// `SNAPSHOT_RETENTION_DAYS`: snapshots completed longer ago are retired (default 0:
+    /// the job does not run).
+    pub days: u32,
+    /// `SNAPSHOT_RETENTION_KEEP_EVERY`: of each server's snapshots, every Nth (the 1st,
+    /// N+1th, ... by completion) is kept however old (default 0: none).
+    pub keep_every: u32,
+    /// `SNAPSHOT_RETENTION_ACTION`: `archive` (default) or `delete`.
+    pub action: RetentionAction,
+}
+
+/// What the retention job does with an old snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    /// Hide it from listings; it still opens and publishes by ID.
+    Archive,
+    /// Soft-delete it: every fetch treats it as gone until it is restored.
+    Delete,
+}
+
+impl FromStr for RetentionAction {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        match value.to_ascii_lowercase().as_str() {
+            "archive" => Ok(RetentionAction::Archive),
+            "delete" => Ok(RetentionAction::Delete),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The `[confluence]` table: where pages are published.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfluenceModeConfig {
+    /// `CONFLUENCE_MODE`: `live` (default) or `mock`.
+    pub mode: ConfluenceMode,
+    /// `CONFLUENCE_MOCK_DIR`: where mock mode keeps its pages (default `confluence-mock`).
+    pub mock_dir: String,
+}
+
+/// What Confluence clients talk to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfluenceMode {
+    /// Each target's Confluence site.
+    Live,
+    /// A file-backed stand-in for the REST API (see `confluence_mock.rs`), for development
+    /// without a Confluence instance.
+    Mock,
+}
+
+impl FromStr for ConfluenceMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        match value.to_ascii_lowercase().as_str() {
+            "live" => Ok(ConfluenceMode::Live),
+            "mock" => Ok(ConfluenceMode::Mock),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The `[concurrency]` table: how hard concurrent publishes may hit one Confluence site
+/// (see `confluence::PublishCoordinator`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConcurrencyConfig {
+    /// `CONFLUENCE_RATE_LIMIT_PER_SECOND`: sustained requests per second per site
+    /// (default 20, `0` sends as fast as possible).
+    pub confluence_requests_per_second: u64,
+    /// `CONFLUENCE_RATE_LIMIT_BURST`: requests a site may get at once (default 50).
+    pub confluence_burst: u64,
+    /// `CONFLUENCE_RATE_LIMIT_RETRIES`: retries of a request answered with 429 (default 3).
+    pub confluence_retries: u64,
+    /// `CONFLUENCE_MAX_CONNECTIONS_PER_HOST`: requests in flight per site, and so
+    /// connections opened to it (default 8, `0` does not cap them).
+    pub confluence_max_connections_per_host: usize,
+    /// `CONFLUENCE_BREAKER_THRESHOLD`: failed requests in a row (errors and 5xx) that open
+    /// a site's circuit breaker (default 5, `0` disables it).
+    pub confluence_breaker_threshold: u64,
+    /// `CONFLUENCE_BREAKER_COOLDOWN_SECS`: how long an open breaker fails requests before
+    /// letting a trial request through (default 30).
+    pub confluence_breaker_cooldown_secs: u64,
+}
+
+/// The `[features]` table. `publishing` and `snapshot_fetch` turn capabilities off for a
+/// deployment, e.g. a read-only preview instance (see `Capability`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureFlags {
+    /// `PUBLISHING_ENABLED`: write to Confluence (publish, republish, promote, withdraw);
+    /// dry runs, previews and exports still work without it (default true).
+    pub publishing: bool,
+    /// `SNAPSHOT_FETCH_ENABLED`: fetch live snapshots from Akeneo (default true).
+    pub snapshot_fetch: bool,
+    /// `SCHEDULER_ENABLED`: run the publish scheduler on this instance (default true).
+    pub scheduler: bool,
+    /// `DIFF_NARRATIVE`: change descriptions above the diff tables (default true).
+    pub diff_narrative: bool,
+    /// `LABEL_COMPLETENESS`: publish the label completeness page (default false).
+    pub label_completeness: bool,
+}
+
+/// The `[renderer]` table.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RendererConfig {
+    /// `THEME`: `default` or `plain`.
+    pub theme: String,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            port: 3000,
+            database: DatabaseConfig::default(),
+            storage: StorageConfig::default(),
+            snapshot_retention: SnapshotRetentionConfig::default(),
+            confluence: ConfluenceModeConfig::default(),
+            concurrency: ConcurrencyConfig::default(),
+            features: FeatureFlags::default(),
+            renderer: RendererConfig::default(),
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            idle_timeout_secs: 600,
+            statement_cache_capacity: 100,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_codec: SnapshotCodec::Json,
+            zstd_level: 3,
+        }
+    }
+}
+
+impl Default for SnapshotRetentionConfig {
+    fn default() -> Self {
+        Self {
+            days: 0,
+            keep_every: 0,
+            action: RetentionAction::Archive,
+        }
+    }
+}
+
+impl Default for ConfluenceModeConfig {
+    fn default() -> Self {
+        Self {
+            mode: ConfluenceMode::Live,
+            mock_dir: "confluence-mock".to_string(),
+        }
+    }
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            confluence_requests_per_second: 20,
+            confluence_burst: 50,
+            confluence_retries: 3,
+            confluence_max_connections_per_host: 8,
+            confluence_breaker_threshold: 5,
+            confluence_breaker_cooldown_secs: 30,
+        }
+    }
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            publishing: true,
+            snapshot_fetch: true,
+            scheduler: true,
+            diff_narrative: true,
+            label_completeness: false,
+        }
+    }
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            theme: "default".to_string(),
+        }
+    }
+}
+
+/// A capability a deployment can turn off with a `[features]` flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Publishing,
+    SnapshotFetch,
+}
+
+impl Capability {
+    fn description(self) -> &'static str {
+        match self {
+            Capability::Publishing => "Publishing to Confluence",
+            Capability::SnapshotFetch => "Fetching live snapshots from Akeneo",
+        }
+    }
+
+    /// The `[features]` key and the environment variable that control it.
+    fn setting(self) -> (&'static str, &'static str) {
+        match self {
+            Capability::Publishing => ("features.publishing", "PUBLISHING_ENABLED"),
+            Capability::SnapshotFetch => ("features.snapshot_fetch", "SNAPSHOT_FETCH_ENABLED"),
+        }
+    }
+}
+
+/// A request for a capability the deployment turned off; handlers answer 403.
+#[derive(Debug)]
+pub struct CapabilityDisabled(pub Capability);
+
+impl std::fmt::Display for CapabilityDisabled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (key, env) = self.0.setting();
+        write!(
+            f,
+            "{} is disabled on this deployment ({} / {}=false)",
+            self.0.description(),
+            key,
+            env
+        )
+    }
+}
+
+impl std::error::Error for CapabilityDisabled {}
+
+/// Whether an error is (or wraps) a disabled capability.
+pub fn is_capability_disabled(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<CapabilityDisabled>().is_some()
+}
+
+impl FeatureFlags {
+    pub fn enabled(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Publishing => self.publishing,
+            Capability::SnapshotFetch => self.snapshot_fetch,
+        }
+    }
+
+    /// Fail with `CapabilityDisabled` unless the capability is on.
+    pub fn ensure(&self, capability: Capability) -> Result<(), CapabilityDisabled> {
+        if self.enabled(capability) {
+            Ok(())
+        } else {
+            Err(CapabilityDisabled(capability))
+        }
+    }
+}
+
+impl AppConfig {
+    /// Load and validate the configuration. Fails with every problem at once: an
+    /// unreadable or malformed `CONFIG_FILE`, unparsable environment variables, or
+    /// settings that fail `validate`.
+    pub fn load() -> Result<Self> {
+        let mut config = match std::env::var("CONFIG_FILE")
+            .ok()
+            .filter(|p| !p.trim().is_empty())
+        {
+            Some(path) => {
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read CONFIG_FILE {}", path))?;
+                let config: AppConfig = toml::from_str(&text)
+                    .with_context(|| format!("Invalid CONFIG_FILE {}", path))?;
+                info!("Loaded configuration from {}", path);
+                config
+            }
+            None => AppConfig::default(),
+        };
+
+        let mut errors = Vec::new();
+        config.apply_env(&mut errors);
+        errors.extend(config.validate());
+        if !errors.is_empty() {
+            bail!("Invalid configuration:\n- {}", errors.join("\n- "));
+        }
+        Ok(config)
+    }
+
+    fn apply_env(&mut self, errors: &mut Vec<String>) {
+        env_value("PORT", &mut self.port, errors);
+
+        let database = &mut self.database;
+        if let Some(url) = env("DATABASE_URL") {
+            database.url = Some(url);
+        }
+        env_value("DB_MAX_CONNECTIONS", &mut database.max_connections, errors);
+        env_value("DB_MIN_CONNECTIONS", &mut database.min_connections, errors);
+        env_value(
+            "DB_ACQUIRE_TIMEOUT_SECS",
+            &mut database.acquire_timeout_secs,
+            errors,
+        );
+        env_value(
+            "DB_IDLE_TIMEOUT_SECS",
+            &mut database.idle_timeout_secs,
+            errors,
+        );
+        env_value(
+            "DB_STATEMENT_CACHE_CAPACITY",
+            &mut database.statement_cache_capacity,
+            errors,
+        );
+
+        env_choice(
+            "SNAPSHOT_CODEC",
+            "json or zstd",
+            &mut self.storage.snapshot_codec,
+            errors,
+        );
+        env_value(
+            "SNAPSHOT_ZSTD_LEVEL",
+            &mut self.storage.zstd_level,
+            errors,
+        );
+
+        let retention = &mut self.snapshot_retention;
+        env_value("SNAPSHOT_RETENTION_DAYS", &mut retention.days, errors);
+        env_value(
+            "SNAPSHOT_RETENTION_KEEP_EVERY",
+            &mut retention.keep_every,
+            errors,
+        );
+        env_choice(
+            "SNAPSHOT_RETENTION_ACTION",
+            "archive or delete",
+            &mut retention.action,
+            errors,
+        );
+
+        env_choice(
+            "CONFLUENCE_MODE",
+            "live or mock",
+            &mut self.confluence.mode,
+            errors,
+        );
+        if let Some(dir) = env("CONFLUENCE_MOCK_DIR") {
+            self.confluence.mock_dir = dir;
+        }
+
+        let concurrency = &mut self.concurrency;
+        env_value(
+            "CONFLUENCE_RATE_LIMIT_PER_SECOND",
+            &mut concurrency.confluence_requests_per_second,
+            errors,
+        );
+        env_value(
+            "CONFLUENCE_RATE_LIMIT_BURST",
+            &mut concurrency.confluence_burst,
+            errors,
+        );
+        env_value(
+            "CONFLUENCE_RATE_LIMIT_RETRIES",
+            &mut concurrency.confluence_retries,
+            errors,
+        );
+        env_value(
+            "CONFLUENCE_MAX_CONNECTIONS_PER_HOST",
+            &mut concurrency.confluence_max_connections_per_host,
+            errors,
+        );
+        env_value(
+            "CONFLUENCE_BREAKER_THRESHOLD",
+            &mut concurrency.confluence_breaker_threshold,
+            errors,
+        );
+        env_value(
+            "CONFLUENCE_BREAKER_COOLDOWN_SECS",
+            &mut concurrency.confluence_breaker_cooldown_secs,
+            errors,
+        );
+
+        env_flag("PUBLISHING_ENABLED", &mut self.features.publishing, errors);
+        env_flag(
+            "SNAPSHOT_FETCH_ENABLED",
+            &mut self.features.snapshot_fetch,
+            errors,
+        );
+        env_flag("SCHEDULER_ENABLED", &mut self.features.scheduler, errors);
+        env_flag("DIFF_NARRATIVE", &mut self.features.diff_narrative, errors);
+        env_flag(
+            "LABEL_COMPLETENESS",
+            &mut self.features.label_completeness,
+            errors,
+        );
+
+        if let Some(theme) = env("THEME") {
+            self.renderer.theme = theme;
+        }
+    }
+
+    /// Problems with the settings, as messages naming the setting.
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.port == 0 {
+            errors.push("port (PORT) must not be 0".to_string());
+        }
+
+        let database = &self.database;
+        match database.url.as_deref() {
+            None => errors.push("database.url (DATABASE_URL) is required".to_string()),
+            Some(url) => {
+                if PgConnectOptions::from_str(url).is_err() {
+                    errors.push(
+                        "database.url (DATABASE_URL) is not a valid PostgreSQL connection string"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+        if database.max_connections == 0 {
+            errors.push(
+                "database.max_connections (DB_MAX_CONNECTIONS) must be at least 1".to_string(),
+            );
+        }
+        if database.min_connections > database.max_connections {
+            errors.push(format!(
+                "database.min_connections (DB_MIN_CONNECTIONS) is {}, above max_connections {}",
+                database.min_connections, database.max_connections
+            ));
+        }
+        if database.acquire_timeout_secs == 0 {
+            errors.push(
+                "database.acquire_timeout_secs (DB_ACQUIRE_TIMEOUT_SECS) must be at least 1"
+                    .to_string(),
+            );
+        }
+
+        if !(1..=22).contains(&self.storage.zstd_level) {
+            errors.push(format!(
+                "storage.zstd_level (SNAPSHOT_ZSTD_LEVEL) is {}, not between 1 and 22",
+                self.storage.zstd_level
+            ));
+        }
+
+        if self.confluence.mode == ConfluenceMode::Mock && self.confluence.mock_dir.is_empty() {
+            errors.push(
+                "confluence.mock_dir (CONFLUENCE_MOCK_DIR) is required in mock mode".to_string(),
+            );
+        }
+
+        if self.concurrency.confluence_burst == 0 {
+            errors.push(
+                "concurrency.confluence_burst (CONFLUENCE_RATE_LIMIT_BURST) must be at least 1"
+                    .to_string(),
+            );
+        }
+        let concurrency = &self.concurrency;
+        if concurrency.confluence_breaker_threshold > 0
+            && concurrency.confluence_breaker_cooldown_secs == 0
+        {
+            errors.push(
+                "concurrency.confluence_breaker_cooldown_secs (CONFLUENCE_BREAKER_COOLDOWN_SECS) \
+                 must be at least 1 while the breaker is on"
+                    .to_string(),
+            );
+        }
+
+        if !THEMES.contains(&self.renderer.theme.as_str()) {
+            errors.push(format!(
+                "renderer.theme (THEME) '{}' is not one of {}",
+                self.renderer.theme,
+                THEMES.join(", ")
+            ));
+        }
+        errors
+    }
+}
+
+/// A set, non-blank environment variable, trimmed.
+fn env(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Override a number from the environment, recording a value that does not parse.
+fn env_value<T: FromStr>(name: &str, target: &mut T, errors: &mut Vec<String>) {
+    let Some(value) = env(name) else { return };
+    match value.parse() {
+        Ok(parsed) => *target = parsed,
+        Err(_) => errors.push(format!("{} '{}' is not a valid number", name, value)),
+    }
+}
+
+/// Override a setting with named values from the environment, recording an unknown one.
+fn env_choice<T: FromStr>(name: &str, choices: &str, target: &mut T, errors: &mut Vec<String>) {
+    let Some(value) = env(name) else { return };
+    match value.parse() {
+        Ok(parsed) => *target = parsed,
+        Err(_) => errors.push(format!("{} '{}' must be {}", name, value, choices)),
+    }
+}
+
+/// Override a flag from the environment: `true` / `false` or `1` / `0`.
+fn env_flag(name: &str, target: &mut bool, errors: &mut Vec<String>) {
+    let Some(value) = env(name) else { return };
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" => *target = true,
+        "false" | "0" => *target = false,
+        _ => errors.push(format!("{} '{}' must be true or false", name, value)),
+    }
+}
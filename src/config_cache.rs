@@ -0,0 +1,94 @@
+use anyhow::Result;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::db::{self, DbConfluenceConfig};
+
+/// In-memory cache of Confluence targets (`db::fetch_confluence_config`, with the stored
+/// secrets already decrypted), keyed by Akeneo server ID.
+///
+/// Every publish reads its target, some of them more than once, so bursts of publishes
+/// would otherwise query the same row again and again. An edited `confluence_config` row
+/// takes effect once its entry expires, or right away after
+/// `POST /api/admin/confluence-config-cache/invalidate` or a Confluence check. Entries
+/// hold credentials, so the cache never logs them.
+pub struct ConfigCache {
+    entries: Mutex<HashMap<Uuid, CacheEntry>>,
+    ttl: Duration,
+}
+
+struct CacheEntry {
+    config: DbConfluenceConfig,
+    inserted_at: Instant,
+}
+
+/// Point-in-time statistics about the config cache.
+pub struct ConfigCacheStats {
+    pub entries: usize,
+    pub ttl_secs: u64,
+}
+
+impl ConfigCache {
+    /// Build a cache from the environment:
+    /// - `CONFLUENCE_CONFIG_CACHE_TTL_SECS` — how long a target is reused (default 60,
+    ///   `0` disables the cache)
+    pub fn from_env() -> Self {
+        let ttl_secs = std::env::var("CONFLUENCE_CONFIG_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl: Duration::from_secs(ttl_secs),
+        }
+    }
+
+    /// The Confluence target of an Akeneo server, from the cache unless it has expired.
+    /// Fails like `db::fetch_confluence_config`; failures are not cached.
+    pub async fn fetch(&self, pool: &PgPool, akeneo_server_id: Uuid) -> Result<DbConfluenceConfig> {
+        if let Some(config) = self.get(akeneo_server_id) {
+            return Ok(config);
+        }
+        let config = db::fetch_confluence_config(pool, akeneo_server_id).await?;
+        if !self.ttl.is_zero() {
+            self.entries.lock().unwrap().insert(
+                akeneo_server_id,
+                CacheEntry {
+                    config: config.clone(),
+                    inserted_at: Instant::now(),
+                },
+            );
+        }
+        Ok(config)
+    }
+
+    fn get(&self, akeneo_server_id: Uuid) -> Option<DbConfluenceConfig> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get(&akeneo_server_id)?;
+        if entry.inserted_at.elapsed() < self.ttl {
+            return Some(entry.config.clone());
+        }
+        entries.remove(&akeneo_server_id);
+        None
+    }
+
+    /// Forget one server's target, or every target; returns how many were cached.
+    pub fn invalidate(&self, akeneo_server_id: Option<Uuid>) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        match akeneo_server_id {
+            Some(id) => usize::from(entries.remove(&id).is_some()),
+            None => std::mem::take(&mut *entries).len(),
+        }
+    }
+
+    pub fn stats(&self) -> ConfigCacheStats {
+        ConfigCacheStats {
+            entries: self.entries.lock().unwrap().len(),
+            ttl_secs: self.ttl.as_secs(),
+        }
+    }
+}
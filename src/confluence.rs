@@ -1,60 +1,1151 @@
 use anyhow::{bail, Context, Result};
-use reqwest::header::{ACCEPT, CONTENT_TYPE};
-use reqwest::Client;
-use serde::Deserialize;
-use tracing::info;
+use futures_util::future::{BoxFuture, FutureExt};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{multipart, Certificate, Client, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use uuid::Uuid;
 
-use crate::db::DbConfluenceConfig;
+use crate::config::ConcurrencyConfig;
+use crate::config_cache::ConfigCache;
+use crate::confluence_mock::MockConfluence;
+use crate::db::{self, DbConfluenceConfig};
+use crate::metrics;
+use crate::secrets::{self, Secrets};
+
+/// Build the shared HTTP client used for all Confluence calls.
+///
+/// The client is created once at startup and shared through `AppState`, so pooled
+/// connections (and their TLS sessions) are reused across requests instead of being
+/// re-established for every publish. HTTP/2 is negotiated via ALPN where the server
+/// supports it, letting many page requests multiplex over a single connection. Targets
+/// with their own TLS settings get a client of their own from [`TlsClients`].
+///
+/// Tunable via environment variables:
+/// - `CONFLUENCE_POOL_IDLE_TIMEOUT_SECS` — how long idle connections are kept (default 90)
+/// - `CONFLUENCE_POOL_MAX_IDLE_PER_HOST` — max idle connections kept per host (default 8)
+/// - `CONFLUENCE_HTTP2_KEEPALIVE_SECS` — HTTP/2 PING interval for idle connections (default 30)
+///
+/// The pool itself does not bound how many connections it opens to a host; that cap is
+/// `CONFLUENCE_MAX_CONNECTIONS_PER_HOST`, enforced per site by [`PublishCoordinator`].
+pub fn build_http_client(tls: &TlsOptions) -> Result<Client> {
+    let idle_timeout = env_u64("CONFLUENCE_POOL_IDLE_TIMEOUT_SECS", 90);
+    let max_idle_per_host = env_u64("CONFLUENCE_POOL_MAX_IDLE_PER_HOST", 8) as usize;
+    let keepalive = env_u64("CONFLUENCE_HTTP2_KEEPALIVE_SECS", 30);
+
+    info!(
+        "Confluence HTTP client: pool_idle_timeout={}s, pool_max_idle_per_host={}, http2_keepalive={}s",
+        idle_timeout, max_idle_per_host, keepalive
+    );
+
+    let mut builder = Client::builder()
+        .pool_idle_timeout(Duration::from_secs(idle_timeout))
+        .pool_max_idle_per_host(max_idle_per_host)
+        .tcp_keepalive(Duration::from_secs(60))
+        .http2_adaptive_window(true)
+        .http2_keep_alive_interval(Duration::from_secs(keepalive))
+        .http2_keep_alive_while_idle(true)
+        .connector_layer(metrics::ConnectionCountLayer);
+    for certificate in tls.root_certificates()? {
+        builder = builder.add_root_certificate(certificate);
+    }
+    if tls.accept_invalid_certs {
+        warn!(
+            "Confluence HTTP client does not verify TLS certificates (danger_accept_invalid_certs)"
+        );
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    builder.build().context("Failed to build Confluence HTTP client")
+}
+
+/// TLS settings of a target, for sites (typically Data Center behind an internal CA)
+/// whose certificate the built-in roots do not cover.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct TlsOptions {
+    /// Extra root certificates: a PEM bundle, or the path of a PEM file.
+    pub ca_certificate: Option<String>,
+    /// Skip certificate verification altogether.
+    pub accept_invalid_certs: bool,
+}
+
+impl TlsOptions {
+    /// The configured root certificates; a path is read on every call.
+    fn root_certificates(&self) -> Result<Vec<Certificate>> {
+        let Some(value) = self.ca_certificate.as_deref().filter(|v| !v.trim().is_empty()) else {
+            return Ok(Vec::new());
+        };
+        let pem = if value.contains("-----BEGIN") {
+            value.as_bytes().to_vec()
+        } else {
+            std::fs::read(value.trim())
+                .with_context(|| format!("Failed to read CA certificate {}", value.trim()))?
+        };
+        let certificates = Certificate::from_pem_bundle(&pem).context("Invalid CA certificate")?;
+        if certificates.is_empty() {
+            bail!("Invalid CA certificate: no PEM certificate found");
+        }
+        Ok(certificates)
+    }
+}
+
+/// HTTP clients for targets with their own TLS settings: one per distinct setting, built
+/// on first use, so their connections are pooled like the shared client's.
+#[derive(Default)]
+pub struct TlsClients {
+    clients: Mutex<HashMap<TlsOptions, Client>>,
+}
+
+impl TlsClients {
+    /// The client for a target's TLS settings: `shared` for the default settings. A CA
+    /// certificate file is read when its client is first built, so replacing the file
+    /// takes a restart.
+    pub fn get(&self, shared: &Client, tls: &TlsOptions) -> Result<Client> {
+        if *tls == TlsOptions::default() {
+            return Ok(shared.clone());
+        }
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get(tls) {
+            return Ok(client.clone());
+        }
+        let client = build_http_client(tls)?;
+        clients.insert(tls.clone(), client.clone());
+        Ok(client)
+    }
+}
+
+/// Read a numeric environment variable, falling back to a default when unset or invalid.
+fn env_u64(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Pause after a 429 without a usable `Retry-After`, and the longest pause honoured.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// Shares each Confluence site's request budget between all concurrent publishes.
+///
+/// Several tenants often publish to the same site at once (e.g. a nightly schedule), and
+/// Confluence rate limits per site. Every request first waits for a token from its site's
+/// bucket, in arrival order across all jobs, so jobs interleave instead of one starving
+/// the others. A 429 pauses the whole site for its `Retry-After`, so every job backs off
+/// together rather than each retrying blindly into the limit. At most
+/// `max_connections_per_host` requests to a site are in flight at once, each holding a
+/// slot until its response body has been read.
+///
+/// Each site also has a circuit breaker: `breaker_threshold` failed requests in a row
+/// (transport errors and 5xx responses) open it, and requests to the site then fail
+/// right away instead of each waiting for its own timeout. After `breaker_cooldown` one
+/// trial request goes through (half-open); its success closes the breaker, its failure
+/// opens it again.
+pub struct PublishCoordinator {
+    sites: Mutex<HashMap<String, Arc<Site>>>,
+    /// `0` disables the token bucket; `Retry-After` pauses still apply.
+    per_second: u64,
+    burst: u64,
+    retries: u64,
+    /// `0` does not cap the requests in flight.
+    max_connections_per_host: usize,
+    /// `0` disables the circuit breaker.
+    breaker_threshold: u64,
+    breaker_cooldown: Duration,
+}
+
+/// One Confluence site's queue and bucket.
+struct Site {
+    /// Held while a request waits for its token; Tokio's mutex is fair, so waiters are
+    /// served in arrival order.
+    queue: tokio::sync::Mutex<()>,
+    bucket: Mutex<SiteBucket>,
+    /// Requests waiting for their turn in the queue or for a token.
+    waiting: AtomicUsize,
+    /// Slots for the requests in flight; `None` when they are not capped.
+    connections: Option<Arc<tokio::sync::Semaphore>>,
+    breaker: Mutex<Breaker>,
+}
+
+/// A site's circuit breaker: closed without `open_until`, open until then, and half-open
+/// once it has passed.
+#[derive(Default)]
+struct Breaker {
+    consecutive_failures: u64,
+    open_until: Option<Instant>,
+    /// The half-open breaker's trial request is in flight.
+    trial: bool,
+}
+
+/// Leave to send one request to a site: its connection slot, if capped, and whether it is
+/// the half-open breaker's trial request. A trial dropped without an outcome (e.g. when
+/// the OAuth refresh failed before sending) lets the next request try instead.
+struct Admission {
+    site: Arc<Site>,
+    _connection: Option<tokio::sync::OwnedSemaphorePermit>,
+    trial: bool,
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        if self.trial {
+            self.site.breaker.lock().unwrap().trial = false;
+        }
+    }
+}
+
+/// Counts a request as waiting for as long as it is held, including when the waiting
+/// request is dropped.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn new(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Point-in-time request budget of one Confluence site.
+pub struct SiteStats {
+    /// The site's base URL, lowercased and without a trailing slash.
+    pub base_url: String,
+    /// When the pause set by a 429's `Retry-After` ends, if one is in effect.
+    pub paused_until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Tokens left in the bucket; `None` when `CONFLUENCE_RATE_LIMIT_PER_SECOND` is `0`.
+    pub tokens: Option<f64>,
+    pub waiting: usize,
+    /// Requests sent and not yet fully read; `None` when they are not capped.
+    pub in_flight: Option<usize>,
+    /// `None` when `CONFLUENCE_BREAKER_THRESHOLD` is `0`.
+    pub breaker: Option<BreakerStats>,
+}
+
+/// Point-in-time state of a site's circuit breaker.
+pub struct BreakerStats {
+    /// `closed`, `open` or `half_open`.
+    pub state: &'static str,
+    pub consecutive_failures: u64,
+    /// When an open breaker lets a trial request through.
+    pub opens_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+struct SiteBucket {
+    tokens: f64,
+    updated_at: Instant,
+    /// Set from a 429's `Retry-After`; no request is sent to the site before then.
+    paused_until: Option<Instant>,
+}
+
+impl PublishCoordinator {
+    /// Build the coordinator from the `[concurrency]` configuration
+    /// (`CONFLUENCE_RATE_LIMIT_*`, `CONFLUENCE_MAX_CONNECTIONS_PER_HOST` and
+    /// `CONFLUENCE_BREAKER_*`, see `config::ConcurrencyConfig`).
+    pub fn new(config: &ConcurrencyConfig) -> Self {
+        Self {
+            sites: Mutex::new(HashMap::new()),
+            per_second: config.confluence_requests_per_second,
+            burst: config.confluence_burst.max(1),
+            retries: config.confluence_retries,
+            max_connections_per_host: config.confluence_max_connections_per_host,
+            breaker_threshold: config.confluence_breaker_threshold,
+            breaker_cooldown: Duration::from_secs(config.confluence_breaker_cooldown_secs),
+        }
+    }
+
+    fn site(&self, base_url: &str) -> Arc<Site> {
+        let key = base_url.trim_end_matches('/').to_ascii_lowercase();
+        let mut sites = self.sites.lock().unwrap();
+        sites
+            .entry(key)
+            .or_insert_with(|| {
+                Arc::new(Site {
+                    queue: tokio::sync::Mutex::new(()),
+                    bucket: Mutex::new(SiteBucket {
+                        tokens: self.burst as f64,
+                        updated_at: Instant::now(),
+                        paused_until: None,
+                    }),
+                    waiting: AtomicUsize::new(0),
+                    connections: (self.max_connections_per_host > 0).then(|| {
+                        Arc::new(tokio::sync::Semaphore::new(self.max_connections_per_host))
+                    }),
+                    breaker: Mutex::new(Breaker::default()),
+                })
+            })
+            .clone()
+    }
+
+    /// Wait until a request may be sent to a site: after any pause, and once its turn in
+    /// the queue has a free connection slot and a token. The slot is held by the returned
+    /// admission until it is dropped. Fails right away while the site's breaker is open.
+    async fn acquire(&self, base_url: &str) -> Result<Admission> {
+        let site = self.site(base_url);
+        let trial = self.admit(base_url, &site)?;
+        let _waiting = Waiting::new(&site.waiting);
+        let _turn = site.queue.lock().await;
+        // The slot comes first, so no token is spent while the request cannot be sent
+        let connection = match &site.connections {
+            Some(connections) => Some(
+                connections
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("the connection semaphore is never closed"),
+            ),
+            None => None,
+        };
+        let mut throttled = false;
+        loop {
+            let wait = {
+                let mut bucket = site.bucket.lock().unwrap();
+                self.take_token(&mut bucket)
+            };
+            match wait {
+                None => {
+                    return Ok(Admission {
+                        site: site.clone(),
+                        _connection: connection,
+                        trial,
+                    });
+                }
+                Some(wait) => {
+                    if !throttled {
+                        metrics::record_confluence_throttled();
+                        throttled = true;
+                    }
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+
+    /// Let a request through the site's breaker; returns whether it is the half-open
+    /// breaker's trial request.
+    fn admit(&self, base_url: &str, site: &Site) -> Result<bool> {
+        if self.breaker_threshold == 0 {
+            return Ok(false);
+        }
+        let mut breaker = site.breaker.lock().unwrap();
+        let Some(until) = breaker.open_until else {
+            return Ok(false);
+        };
+        let now = Instant::now();
+        if until > now || breaker.trial {
+            metrics::record_confluence_breaker_rejected();
+            bail!(
+                "Confluence site {} is unavailable after {} failed requests in a row; retrying \
+                 in {}s",
+                base_url,
+                breaker.consecutive_failures,
+                until.saturating_duration_since(now).as_secs()
+            );
+        }
+        breaker.trial = true;
+        Ok(true)
+    }
+
+    /// Record whether an admitted request reached the site: a transport error or a 5xx
+    /// counts against the breaker, any other response closes it.
+    fn settle(&self, base_url: &str, admission: &Admission, ok: bool) {
+        if self.breaker_threshold == 0 {
+            return;
+        }
+        let mut breaker = admission.site.breaker.lock().unwrap();
+        if admission.trial {
+            breaker.trial = false;
+        }
+        if ok {
+            breaker.consecutive_failures = 0;
+            breaker.open_until = None;
+            return;
+        }
+        breaker.consecutive_failures += 1;
+        if breaker.open_until.is_some() || breaker.consecutive_failures >= self.breaker_threshold {
+            if breaker.open_until.is_none() {
+                warn!(
+                    "Confluence site {} failed {} requests in a row; holding back requests for \
+                     {}s",
+                    base_url,
+                    breaker.consecutive_failures,
+                    self.breaker_cooldown.as_secs()
+                );
+            }
+            breaker.open_until = Some(Instant::now() + self.breaker_cooldown);
+        }
+    }
+
+    /// Take a token from the bucket, or return how long to wait for one.
+    fn take_token(&self, bucket: &mut SiteBucket) -> Option<Duration> {
+        let now = Instant::now();
+        if let Some(until) = bucket.paused_until {
+            if until > now {
+                return Some(until - now);
+            }
+            bucket.paused_until = None;
+        }
+        if self.per_second == 0 {
+            return None;
+        }
+
+        let per_second = self.per_second as f64;
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(self.burst as f64);
+        bucket.updated_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+
+    /// Hold back every request to a site for `wait`, after it answered 429. The bucket is
+    /// emptied so requests resume at the sustained rate rather than in a burst.
+    fn pause(&self, base_url: &str, wait: Duration) {
+        let site = self.site(base_url);
+        let mut bucket = site.bucket.lock().unwrap();
+        let until = Instant::now() + wait;
+        bucket.paused_until = Some(bucket.paused_until.map_or(until, |u| u.max(until)));
+        bucket.tokens = 0.0;
+    }
+
+    /// The request budget of every site requested so far, sorted by base URL. Tokens are
+    /// read as refilled up to now, without taking any.
+    pub fn stats(&self) -> Vec<SiteStats> {
+        let now = Instant::now();
+        let sites: Vec<(String, Arc<Site>)> = self
+            .sites
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(url, site)| (url.clone(), site.clone()))
+            .collect();
+        let mut stats: Vec<SiteStats> = sites
+            .into_iter()
+            .map(|(base_url, site)| {
+                let bucket = site.bucket.lock().unwrap();
+                let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+                let tokens = (self.per_second > 0).then(|| {
+                    (bucket.tokens + elapsed * self.per_second as f64).min(self.burst as f64)
+                });
+                let paused_until = bucket
+                    .paused_until
+                    .filter(|&until| until > now)
+                    .and_then(|until| chrono::Duration::from_std(until - now).ok())
+                    .map(|left| chrono::Utc::now() + left);
+                SiteStats {
+                    base_url,
+                    paused_until,
+                    tokens,
+                    waiting: site.waiting.load(Ordering::Relaxed),
+                    in_flight: site.connections.as_ref().map(|connections| {
+                        self.max_connections_per_host - connections.available_permits()
+                    }),
+                    breaker: (self.breaker_threshold > 0)
+                        .then(|| breaker_stats(&site.breaker.lock().unwrap(), now)),
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.base_url.cmp(&b.base_url));
+        stats
+    }
+}
+
+/// The state of a breaker at `now`.
+fn breaker_stats(breaker: &Breaker, now: Instant) -> BreakerStats {
+    let state = match breaker.open_until {
+        None => "closed",
+        Some(until) if until > now => "open",
+        Some(_) => "half_open",
+    };
+    let opens_at = breaker
+        .open_until
+        .filter(|&until| until > now)
+        .and_then(|until| chrono::Duration::from_std(until - now).ok())
+        .map(|left| chrono::Utc::now() + left);
+    BreakerStats {
+        state,
+        consecutive_failures: breaker.consecutive_failures,
+        opens_at,
+    }
+}
+
+/// A response with its body read into memory, so the connection is free again once it
+/// returns.
+async fn buffered(resp: Response) -> Result<Response> {
+    let mut builder = axum::http::Response::builder()
+        .status(resp.status())
+        .version(resp.version());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = resp.headers().clone();
+    }
+    let body = resp
+        .bytes()
+        .await
+        .context("Failed to read Confluence response")?;
+    let response = builder
+        .body(body)
+        .context("Failed to buffer Confluence response")?;
+    Ok(Response::from(response))
+}
+
+/// How long a 429 response asks to wait: `Retry-After` in seconds or as an HTTP date,
+/// capped at `MAX_RETRY_AFTER`.
+fn retry_after(resp: &Response) -> Duration {
+    let value = resp
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim);
+    let wait = match value {
+        Some(v) => match v.parse::<u64>() {
+            Ok(secs) => Some(Duration::from_secs(secs)),
+            Err(_) => chrono::DateTime::parse_from_rfc2822(v)
+                .ok()
+                .map(|at| at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+                .map(|delta| delta.to_std().unwrap_or_default()),
+        },
+        None => None,
+    };
+    wait.unwrap_or(DEFAULT_RETRY_AFTER).min(MAX_RETRY_AFTER)
+}
+
+/// Default `CONFLUENCE_MAX_BODY_BYTES`: Confluence rejects (or truncates) page bodies
+/// beyond roughly 5 MB of storage format.
+const DEFAULT_MAX_BODY_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Bodies above this share of the limit are logged and counted as nearing it.
+const BODY_WARN_PERCENT: u64 = 80;
+
+/// Default `CONFLUENCE_CONFLICT_RETRIES`.
+const DEFAULT_CONFLICT_RETRIES: u64 = 3;
+
+/// A page update rejected with 409: the page changed after its version was read, usually
+/// because someone edited it mid-publish.
+#[derive(Debug)]
+struct VersionConflict {
+    page_id: String,
+    body: String,
+}
+
+impl std::fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Confluence update of page {} conflicted with a concurrent edit (HTTP 409): {}",
+            self.page_id, self.body
+        )
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+/// The OAuth token endpoint rejected a refresh, e.g. because the refresh token was revoked
+/// or already used.
+#[derive(Debug)]
+struct TokenRefreshFailed {
+    status: StatusCode,
+    body: String,
+}
+
+impl std::fmt::Display for TokenRefreshFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Confluence OAuth token refresh failed (HTTP {}): {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for TokenRefreshFailed {}
+
+/// Whether an error is (or wraps) a rejected OAuth token refresh, which means the target's
+/// OAuth credentials need attention rather than the site.
+pub fn is_token_refresh_failed(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<TokenRefreshFailed>().is_some()
+}
+
+/// A rendered page body too large for Confluence, caught before it is sent.
+#[derive(Debug)]
+pub struct RenderTooLarge {
+    pub title: String,
+    pub size: usize,
+    pub limit: usize,
+}
+
+impl std::fmt::Display for RenderTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Page '{}' renders to {} bytes, over the {} byte Confluence body limit \
+             (raise CONFLUENCE_MAX_BODY_BYTES if your Confluence accepts larger pages)",
+            self.title, self.size, self.limit
+        )
+    }
+}
+
+impl std::error::Error for RenderTooLarge {}
+
+/// Whether an error is (or wraps) an oversized page body, which handlers report as 422
+/// rather than 500.
+pub fn is_render_too_large(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<RenderTooLarge>().is_some()
+}
+
+/// Kind of content to publish: pages nest under a parent page; blog posts are dated
+/// entries of the space's blog and show up in its activity feed.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum ContentType {
+    #[default]
+    #[serde(rename = "page")]
+    Page,
+    #[serde(rename = "blogpost")]
+    BlogPost,
+}
+
+impl ContentType {
+    /// The `type` of the content in the Confluence REST API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContentType::Page => "page",
+            ContentType::BlogPost => "blogpost",
+        }
+    }
+}
+
+/// Order of the family pages under a snapshot's root page, per Confluence target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildPageOrder {
+    /// Leave pages where Confluence put them (creation order).
+    None,
+    /// Alphabetical by family label, case-insensitive.
+    Label,
+    /// Alphabetical by family code.
+    Code,
+    /// Alphabetical by page title, case-insensitive.
+    Title,
+}
+
+impl ChildPageOrder {
+    /// Parse the `confluence_config.child_page_order` column. Unknown values fall back to
+    /// `Label`.
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "none" => ChildPageOrder::None,
+            "code" => ChildPageOrder::Code,
+            "title" => ChildPageOrder::Title,
+            "label" | "" => ChildPageOrder::Label,
+            other => {
+                warn!("Unknown child page order '{}', using 'label'", other);
+                ChildPageOrder::Label
+            }
+        }
+    }
+}
+
+/// Intermediate index pages the family pages of a snapshot tree are nested under, per
+/// Confluence target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FamilyGrouping {
+    /// Family pages directly under the root page.
+    None,
+    /// One page per first letter of the family label.
+    FirstLetter,
+    /// One page per attribute group of the family's `attribute_as_label`.
+    LabelAttributeGroup,
+    /// One page per family code prefix (the part before the first `_`).
+    Prefix,
+}
+
+impl FamilyGrouping {
+    /// Parse the `confluence_config.family_grouping` column. Unknown values fall back to
+    /// `None`.
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "first_letter" => FamilyGrouping::FirstLetter,
+            "label_attribute_group" => FamilyGrouping::LabelAttributeGroup,
+            "prefix" => FamilyGrouping::Prefix,
+            "none" | "" => FamilyGrouping::None,
+            other => {
+                warn!("Unknown family grouping '{}', not grouping", other);
+                FamilyGrouping::None
+            }
+        }
+    }
+}
 
 /// Result of a successful page publish (create or update).
-pub struct PublishResult {
+#[derive(Debug)]
+pub struct PublishOutcome {
     pub page_id: String,
     pub web_url: String,
+    pub operation: PageOperation,
+    /// The page's version number after the publish (1 for a created page).
+    pub version: u64,
+}
+
+/// A page to publish with `ConfluenceClient::publish_tree`, and the pages to publish under
+/// it (in order).
+pub struct PageTree<'a> {
+    pub title: &'a str,
+    pub body: &'a str,
+    pub children: Vec<PageTree<'a>>,
+}
+
+impl PageTree<'_> {
+    /// Number of pages in the tree, this one included.
+    pub fn page_count(&self) -> usize {
+        1 + self.children.iter().map(PageTree::page_count).sum::<usize>()
+    }
+
+    fn pages(&self) -> Vec<&PageTree<'_>> {
+        let mut pages = vec![self];
+        for child in &self.children {
+            pages.extend(child.pages());
+        }
+        pages
+    }
+}
+
+/// What `ConfluenceClient::publish_tree` did with each page of a `PageTree`, in the same
+/// shape.
+pub struct PublishedTree {
+    pub outcome: PublishOutcome,
+    pub children: Vec<PublishedTree>,
+}
+
+impl PublishedTree {
+    /// IDs of every page in the tree, this one included.
+    pub fn page_ids(&self) -> Vec<&str> {
+        let mut ids = vec![self.outcome.page_id.as_str()];
+        for child in &self.children {
+            ids.extend(child.page_ids());
+        }
+        ids
+    }
+}
+
+/// Whether a publish created a page or updated an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageOperation {
+    Created,
+    Updated,
+}
+
+/// A page as a client published it, kept when the client has a publish log (see
+/// `with_publish_log`).
+pub struct PublishedPage {
+    pub page_id: String,
+    pub title: String,
+    pub web_url: String,
+    /// The storage format body that was sent.
+    pub body: String,
+}
+
+/// A write a dry-run client planned instead of sending (see `with_dry_run`).
+#[derive(Serialize)]
+pub struct PlannedPage {
+    pub action: PlannedAction,
+    pub title: String,
+    #[serde(rename = "type")]
+    pub content_type: ContentType,
+    /// The existing page; `None` for a page the publish would create.
+    pub page_id: Option<String>,
+    /// For a created page, the existing page it would go under; `None` at the top level
+    /// of the space or under a page the same publish creates (`parent_title`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// For a created page, the title of its parent when the same publish creates or
+    /// updates that parent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_title: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlannedAction {
+    Create,
+    Update,
+    /// Deleted as a stale page.
+    Prune,
 }
 
+/// Writes planned by a dry-run client and its clones, with the ID each planned page is
+/// known by: its own, or a `PLANNED_PAGE_PREFIX` placeholder for a page it would create.
+#[derive(Default)]
+struct DryRun {
+    pages: Vec<(String, PlannedPage)>,
+    /// Titles of the pages `get_child_pages` listed, to name pruned pages.
+    listed: HashMap<String, String>,
+}
+
+/// Prefix of the IDs a dry-run client hands out for pages it would create.
+const PLANNED_PAGE_PREFIX: &str = "planned-";
+
 /// Minimal info about an existing child page, used for stale page detection.
 pub struct ChildPageInfo {
     pub id: String,
     pub title: String,
 }
 
+/// Atlassian's OAuth 2.0 token endpoint (`CONFLUENCE_OAUTH_TOKEN_URL` overrides it).
+const DEFAULT_OAUTH_TOKEN_URL: &str = "https://auth.atlassian.com/oauth/token";
+
+/// Atlassian's API gateway, which OAuth requests go through as
+/// `{url}/ex/confluence/{cloud_id}/wiki/...` (`CONFLUENCE_OAUTH_API_URL` overrides it).
+const DEFAULT_OAUTH_API_URL: &str = "https://api.atlassian.com";
+
+/// An access token is refreshed when it expires within this margin, so it does not
+/// expire mid-request.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// OAuth 2.0 (3LO) credentials of a target, used instead of email + API token when
+/// `confluence_config.auth_type = 'oauth'`.
+#[derive(Clone)]
+pub struct OAuthConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+    /// Cloud ID of the site, which the API gateway routes requests by.
+    pub cloud_id: String,
+}
+
+/// The refresh token is left out: Atlassian rotates it on every refresh, so the stored one
+/// changes under a cached client that already holds the new one.
+impl PartialEq for OAuthConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.client_id == other.client_id
+            && self.client_secret == other.client_secret
+            && self.cloud_id == other.cloud_id
+    }
+}
+
+/// Where a client writes back the refresh token a token refresh rotated in.
+#[derive(Clone)]
+pub struct RefreshTokenStore {
+    pub pool: PgPool,
+    /// Dropping the target's cached config once the token is saved, so a client built
+    /// later starts from the new token.
+    pub configs: Arc<ConfigCache>,
+    pub akeneo_server_id: Uuid,
+}
+
+impl RefreshTokenStore {
+    /// The store for a target's (unresolved) config; `None` without OAuth, or when the
+    /// refresh token is a secret reference, which cannot be written back.
+    pub fn for_target(
+        config: &ConfluenceConfig,
+        pool: PgPool,
+        configs: Arc<ConfigCache>,
+        akeneo_server_id: Uuid,
+    ) -> Option<Self> {
+        let oauth = config.oauth.as_ref()?;
+        (!secrets::is_reference(&oauth.refresh_token)).then_some(Self {
+            pool,
+            configs,
+            akeneo_server_id,
+        })
+    }
+}
+
 /// Configuration for connecting to Confluence Cloud.
+#[derive(Clone, PartialEq)]
 pub struct ConfluenceConfig {
     pub base_url: String,
     pub email: String,
     pub api_token: String,
+    /// OAuth credentials, which replace `email` / `api_token` when set.
+    pub oauth: Option<OAuthConfig>,
+    pub tls: TlsOptions,
     pub space_key: String,
     pub parent_page: String,
+    /// Parent page ID that takes precedence over `parent_page`: configured, a per-request
+    /// override, or the title resolved by `ConfluenceClient::resolve_parent`.
+    pub parent_page_id: Option<String>,
+    pub child_page_order: ChildPageOrder,
+    pub family_grouping: FamilyGrouping,
+    pub restrictions: PageRestrictions,
 }
 
 impl ConfluenceConfig {
     /// Build config from database configuration.
     pub fn from_db(db_config: DbConfluenceConfig) -> Self {
+        // The table's check constraint guarantees the OAuth columns of an OAuth target
+        let oauth = (db_config.auth_type == "oauth").then(|| OAuthConfig {
+            client_id: db_config.oauth_client_id.unwrap_or_default(),
+            client_secret: db_config.oauth_client_secret.unwrap_or_default(),
+            refresh_token: db_config.oauth_refresh_token.unwrap_or_default(),
+            cloud_id: db_config.cloud_id.unwrap_or_default(),
+        });
         Self {
             base_url: db_config.base_url,
             email: db_config.username,
             api_token: db_config.api_token,
+            oauth,
+            tls: TlsOptions {
+                ca_certificate: db_config.ca_certificate,
+                accept_invalid_certs: db_config.danger_accept_invalid_certs,
+            },
             space_key: db_config.space_key,
             parent_page: db_config.parent_page,
+            parent_page_id: db_config.parent_page_id.filter(|id| !id.is_empty()),
+            child_page_order: ChildPageOrder::parse(&db_config.child_page_order),
+            family_grouping: FamilyGrouping::parse(&db_config.family_grouping),
+            restrictions: PageRestrictions {
+                view_groups: db_config.view_restriction_groups,
+                view_users: db_config.view_restriction_users,
+                edit_groups: db_config.edit_restriction_groups,
+                edit_users: db_config.edit_restriction_users,
+            },
+        }
+    }
+
+    /// Resolve secret references in the credentials: the API token, or the OAuth client
+    /// secret and refresh token.
+    pub async fn resolve_secrets(mut self, secrets: &Secrets) -> Result<Self> {
+        match &mut self.oauth {
+            Some(oauth) => {
+                oauth.client_secret = secrets
+                    .resolve(&oauth.client_secret)
+                    .await
+                    .context("Failed to resolve Confluence OAuth client secret")?;
+                oauth.refresh_token = secrets
+                    .resolve(&oauth.refresh_token)
+                    .await
+                    .context("Failed to resolve Confluence OAuth refresh token")?;
+            }
+            None => {
+                self.api_token = secrets
+                    .resolve(&self.api_token)
+                    .await
+                    .context("Failed to resolve Confluence API token")?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Who requests are made as, for messages: the API token's user or the OAuth app.
+    pub fn account(&self) -> String {
+        match &self.oauth {
+            Some(oauth) => format!("OAuth client {}", oauth.client_id),
+            None => self.email.clone(),
+        }
+    }
+
+    /// Override the configured parent page for a single publish.
+    /// A parent ID wins over a parent title, and either replaces both the configured ID and
+    /// title; `None` keeps the configured parent.
+    pub fn with_parent_override(
+        mut self,
+        parent_page_id: Option<String>,
+        parent_page: Option<String>,
+    ) -> Self {
+        if let Some(id) = parent_page_id.filter(|id| !id.is_empty()) {
+            self.parent_page_id = Some(id);
+        } else if let Some(title) = parent_page.filter(|t| !t.is_empty()) {
+            self.parent_page = title;
+            self.parent_page_id = None;
+        }
+        self
+    }
+
+    /// Publish into another space for a single publish. The configured parent page lives
+    /// in the configured space, so it is dropped unless the request also overrides it.
+    pub fn with_space_override(mut self, space_key: String) -> Self {
+        if space_key != self.space_key {
+            self.space_key = space_key;
+            self.parent_page.clear();
+            self.parent_page_id = None;
         }
+        self
+    }
+}
+
+/// Who may view and who may edit the pages a target publishes (Confluence page
+/// restrictions). An operation without groups and users is not restricted.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct PageRestrictions {
+    /// Group names.
+    pub view_groups: Vec<String>,
+    /// Atlassian account IDs.
+    pub view_users: Vec<String>,
+    pub edit_groups: Vec<String>,
+    pub edit_users: Vec<String>,
+}
+
+impl PageRestrictions {
+    pub fn is_empty(&self) -> bool {
+        self.view_groups.is_empty()
+            && self.view_users.is_empty()
+            && self.edit_groups.is_empty()
+            && self.edit_users.is_empty()
+    }
+
+    /// Body of `PUT /rest/api/content/{id}/restriction`: the restricted operations, each
+    /// also granted to `account_id` (the publishing account) so later publishes can still
+    /// read and update the page.
+    fn request_body(&self, account_id: &str) -> serde_json::Value {
+        let operations = [
+            ("read", &self.view_groups, &self.view_users),
+            ("update", &self.edit_groups, &self.edit_users),
+        ];
+        let restrictions: Vec<serde_json::Value> = operations
+            .into_iter()
+            .filter(|(_, groups, users)| !groups.is_empty() || !users.is_empty())
+            .map(|(operation, groups, users)| {
+                let mut users: Vec<&str> = users.iter().map(String::as_str).collect();
+                if !users.contains(&account_id) {
+                    users.push(account_id);
+                }
+                serde_json::json!({
+                    "operation": operation,
+                    "restrictions": {
+                        "user": users
+                            .iter()
+                            .map(|id| serde_json::json!({ "type": "known", "accountId": id }))
+                            .collect::<Vec<_>>(),
+                        "group": groups
+                            .iter()
+                            .map(|name| serde_json::json!({ "type": "group", "name": name }))
+                            .collect::<Vec<_>>(),
+                    },
+                })
+            })
+            .collect();
+        serde_json::Value::Array(restrictions)
     }
 }
 
 /// Confluence REST API client.
+#[derive(Clone)]
 pub struct ConfluenceClient {
     client: Client,
     config: ConfluenceConfig,
+    /// Content properties written on every page this client creates or updates.
+    page_properties: Vec<(String, serde_json::Value)>,
+    /// Largest page body, in bytes, this client will send (`CONFLUENCE_MAX_BODY_BYTES`).
+    max_body_bytes: usize,
+    /// How often an update is retried after a version conflict (`CONFLUENCE_CONFLICT_RETRIES`).
+    conflict_retries: u64,
+    /// Only retry a conflicting update when the page carries our first page property
+    /// (`CONFLUENCE_CONFLICT_VERIFY_OWNER`), so pages this tool did not publish are not
+    /// overwritten.
+    verify_owner: bool,
+    /// Rate budget shared with every other client publishing to the same site.
+    coordinator: Arc<PublishCoordinator>,
+    /// Access token state of an OAuth target, shared by the clones of a client.
+    oauth: Option<Arc<OAuthSession>>,
+    /// Where a rotated refresh token is saved (see `with_refresh_token_store`).
+    refresh_token_store: Option<RefreshTokenStore>,
+    /// Pages published through this client and its clones (see `with_publish_log`).
+    publish_log: Option<Arc<Mutex<Vec<PublishedPage>>>>,
+    /// Account ID of the user requests are made as, looked up on the first restricted
+    /// page and shared by the clones of a client.
+    account_id: Arc<tokio::sync::OnceCell<String>>,
+    /// Set on a dry-run client, which plans its writes instead of sending them (see
+    /// `with_dry_run`).
+    dry_run: Option<Arc<Mutex<DryRun>>>,
+    /// Set in mock mode: requests are answered by the mock instead of sent (see
+    /// `with_mock`).
+    mock: Option<Arc<MockConfluence>>,
+}
+
+/// OAuth endpoints and tokens of a client.
+struct OAuthSession {
+    token_url: String,
+    api_url: String,
+    /// Held during a refresh, so concurrent requests wait for one refresh instead of each
+    /// spending the (single-use) refresh token.
+    tokens: tokio::sync::Mutex<OAuthTokens>,
+}
+
+struct OAuthTokens {
+    refresh_token: String,
+    /// The access token and when it expires; `None` until the first request.
+    access_token: Option<(String, Instant)>,
+}
+
+/// Response from the OAuth token endpoint.
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default = "default_expires_in")]
+    expires_in: u64,
+    /// The rotated refresh token; the one just used is no longer valid.
+    refresh_token: Option<String>,
+}
+
+fn default_expires_in() -> u64 {
+    3600
+}
+
+/// Response from the content property endpoint.
+#[derive(Deserialize, Debug)]
+struct ContentPropertyResponse {
+    version: VersionInfo,
 }
 
+/// Response from the current user endpoint.
 #[derive(Deserialize, Debug)]
-struct SearchResults {
-    results: Vec<PageResult>,
+struct CurrentUserResponse {
+    #[serde(rename = "accountId")]
+    account_id: String,
+}
+
+/// Page size requested from paginated listing endpoints.
+const PAGE_LIMIT: usize = 50;
+
+/// One page of a paginated v1 listing (content search, descendants).
+#[derive(Deserialize, Debug)]
+struct Paged<T> {
+    results: Vec<T>,
+    #[serde(rename = "_links")]
+    links: Option<PagedLinks>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PagedLinks {
+    /// Link to the next page (relative to `base`); absent on the last page.
+    next: Option<String>,
+    base: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 struct PageResult {
     id: String,
+    title: String,
     version: Option<VersionInfo>,
+    #[serde(default)]
+    ancestors: Vec<AncestorRef>,
+}
+
+#[derive(Deserialize)]
+struct PageBodyResponse {
+    body: PageBody,
+}
+
+#[derive(Deserialize)]
+struct PageBody {
+    storage: StorageBody,
+}
+
+#[derive(Deserialize)]
+struct StorageBody {
+    value: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct AncestorRef {
+    id: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -65,6 +1156,7 @@ struct VersionInfo {
 #[derive(Deserialize, Debug)]
 struct CreatePageResponse {
     id: String,
+    version: Option<VersionInfo>,
     #[serde(rename = "_links")]
     links: Option<PageLinks>,
 }
@@ -75,92 +1167,518 @@ struct PageLinks {
     webui: Option<String>,
 }
 
-/// Response from the descendant/page endpoint (paginated).
-#[derive(Deserialize, Debug)]
-#[allow(dead_code)]
-struct DescendantPagesResponse {
-    results: Vec<DescendantPageResult>,
-    start: Option<u64>,
-    limit: Option<u64>,
-    size: u64,
-}
-
+/// An entry of the descendant/page listing.
 #[derive(Deserialize, Debug)]
 struct DescendantPageResult {
     id: String,
     title: String,
+    version: Option<VersionInfo>,
 }
 
 impl ConfluenceClient {
-    pub fn new(config: ConfluenceConfig) -> Self {
+    /// Create a client for the given config, sharing the pooled HTTP client and the
+    /// per-site rate budget.
+    pub fn new(
+        config: ConfluenceConfig,
+        client: Client,
+        coordinator: Arc<PublishCoordinator>,
+    ) -> Self {
+        let oauth = config.oauth.as_ref().map(|oauth| {
+            Arc::new(OAuthSession {
+                token_url: std::env::var("CONFLUENCE_OAUTH_TOKEN_URL")
+                    .unwrap_or_else(|_| DEFAULT_OAUTH_TOKEN_URL.to_string()),
+                api_url: std::env::var("CONFLUENCE_OAUTH_API_URL")
+                    .unwrap_or_else(|_| DEFAULT_OAUTH_API_URL.to_string()),
+                tokens: tokio::sync::Mutex::new(OAuthTokens {
+                    refresh_token: oauth.refresh_token.clone(),
+                    access_token: None,
+                }),
+            })
+        });
         Self {
-            client: Client::new(),
+            client,
             config,
+            coordinator,
+            oauth,
+            refresh_token_store: None,
+            publish_log: None,
+            account_id: Arc::default(),
+            dry_run: None,
+            mock: None,
+            page_properties: Vec::new(),
+            max_body_bytes: env_u64("CONFLUENCE_MAX_BODY_BYTES", DEFAULT_MAX_BODY_BYTES) as usize,
+            conflict_retries: env_u64("CONFLUENCE_CONFLICT_RETRIES", DEFAULT_CONFLICT_RETRIES),
+            verify_owner: !std::env::var("CONFLUENCE_CONFLICT_VERIFY_OWNER")
+                .is_ok_and(|v| v == "false" || v == "0"),
         }
     }
 
-    /// Search for an existing page by title in the configured space.
-    /// Returns the page ID and current version number if found.
-    async fn find_page(&self, title: &str) -> Result<Option<(String, u64)>> {
-        let url = format!(
-            "{}/wiki/rest/api/content",
-            self.config.base_url.trim_end_matches('/')
-        );
+    /// Fail with `RenderTooLarge` if a page body is over the size limit. Callers publishing
+    /// several pages check them all first so an oversized page fails before anything is
+    /// written.
+    pub fn ensure_body_fits(&self, title: &str, body_storage: &str) -> Result<()> {
+        let size = body_storage.len();
+        if size <= self.max_body_bytes {
+            return Ok(());
+        }
+        metrics::record_page_body_too_large(size);
+        Err(RenderTooLarge {
+            title: title.to_string(),
+            size,
+            limit: self.max_body_bytes,
+        }
+        .into())
+    }
+
+    /// Size check for a body about to be sent: fails when it is over the limit, and logs
+    /// and counts it when it is nearing the limit.
+    fn check_body_size(&self, title: &str, body_storage: &str) -> Result<()> {
+        self.ensure_body_fits(title, body_storage)?;
+        let size = body_storage.len() as u64;
+        let limit = self.max_body_bytes as u64;
+        let near_limit = size * 100 > limit * BODY_WARN_PERCENT;
+        metrics::record_page_body(size, near_limit);
+        if near_limit {
+            warn!(
+                "Page '{}' is {} bytes, {}% of the {} byte body limit",
+                title,
+                size,
+                size * 100 / limit,
+                limit
+            );
+        }
+        Ok(())
+    }
+
+    /// Write a content property on every page published through this client (see
+    /// `set_content_property`).
+    pub fn with_page_property(mut self, key: &str, value: serde_json::Value) -> Self {
+        self.page_properties.push((key.to_string(), value));
+        self
+    }
+
+    /// Save refresh tokens rotated by an OAuth client in `store` (see
+    /// [`RefreshTokenStore::for_target`]). Without a store a rotation is only logged.
+    pub fn with_refresh_token_store(mut self, store: Option<RefreshTokenStore>) -> Self {
+        self.refresh_token_store = store;
+        self
+    }
+
+    /// Keep the title, URL and body of every page published through this client (and its
+    /// clones) from now on, for `take_publish_log`. Starts a new, empty log.
+    pub fn with_publish_log(mut self) -> Self {
+        self.publish_log = Some(Arc::default());
+        self
+    }
+
+    /// The pages published since `with_publish_log`, in publish order; a page published
+    /// twice is listed once, with its last body. Empty without a publish log.
+    pub fn take_publish_log(&self) -> Vec<PublishedPage> {
+        self.publish_log
+            .as_ref()
+            .map(|log| std::mem::take(&mut *log.lock().unwrap()))
+            .unwrap_or_default()
+    }
+
+    /// Send every request to `mock` instead of Confluence (`CONFLUENCE_MODE=mock`); `None`
+    /// keeps the client live.
+    pub fn with_mock(mut self, mock: Option<Arc<MockConfluence>>) -> Self {
+        self.mock = mock;
+        self
+    }
+
+    /// Plan every write through this client (and its clones) from now on instead of sending
+    /// it, for `take_plan`. Page lookups still go to Confluence, so `publish_page` reports
+    /// whether a page would be created or updated; a page it would create gets a
+    /// placeholder ID, which later lookups under it treat as a page without children.
+    /// Content properties, restrictions, attachments and page moves are skipped.
+    pub fn with_dry_run(mut self) -> Self {
+        self.dry_run = Some(Arc::default());
+        self
+    }
+
+    /// The writes planned since `with_dry_run`, in publish order; a page published twice
+    /// is listed once. Empty without a dry run.
+    pub fn take_plan(&self) -> Vec<PlannedPage> {
+        let Some(dry_run) = &self.dry_run else {
+            return Vec::new();
+        };
+        let mut dry_run = dry_run.lock().unwrap();
+        std::mem::take(&mut dry_run.pages)
+            .into_iter()
+            .map(|(_, page)| page)
+            .collect()
+    }
+
+    /// Record a dry-run create or update of the page `find_page` found (`existing`) or did
+    /// not find.
+    fn plan_upsert(
+        dry_run: &Mutex<DryRun>,
+        title: &str,
+        parent_id: Option<&str>,
+        content_type: ContentType,
+        existing: Option<(String, u64)>,
+    ) -> PublishOutcome {
+        let mut dry_run = dry_run.lock().unwrap();
+        let created = existing.is_none();
+        let version = existing.as_ref().map_or(1, |(_id, version)| version + 1);
+        let existing = existing.map(|(id, _version)| id);
+        let id = existing
+            .clone()
+            .unwrap_or_else(|| format!("{}{}", PLANNED_PAGE_PREFIX, dry_run.pages.len()));
+        let (parent_id, parent_title) = match parent_id {
+            Some(parent) if created => {
+                let planned = dry_run.pages.iter().find(|(id, _)| id == parent);
+                let title = planned.map(|(_, page)| page.title.clone());
+                match parent.starts_with(PLANNED_PAGE_PREFIX) {
+                    true => (None, title),
+                    false => (Some(parent.to_string()), title),
+                }
+            }
+            _ => (None, None),
+        };
+        dry_run.pages.retain(|(planned, _)| *planned != id);
+        dry_run.pages.push((
+            id.clone(),
+            PlannedPage {
+                action: match created {
+                    true => PlannedAction::Create,
+                    false => PlannedAction::Update,
+                },
+                title: title.to_string(),
+                content_type,
+                page_id: existing,
+                parent_id,
+                parent_title,
+            },
+        ));
+        PublishOutcome {
+            page_id: id,
+            web_url: String::new(),
+            operation: match created {
+                true => PageOperation::Created,
+                false => PageOperation::Updated,
+            },
+            version,
+        }
+    }
+
+    /// Base of the REST API URLs: the site's `/wiki`, or the site behind the API gateway
+    /// for an OAuth target. Page links (`build_web_url`) always use the site.
+    fn api_base(&self) -> String {
+        match (&self.config.oauth, &self.oauth) {
+            (Some(oauth), Some(session)) => format!(
+                "{}/ex/confluence/{}/wiki",
+                session.api_url.trim_end_matches('/'),
+                oauth.cloud_id
+            ),
+            _ => format!("{}/wiki", self.config.base_url.trim_end_matches('/')),
+        }
+    }
+
+    /// Add the target's credentials to a request: basic auth with the API token, or the
+    /// OAuth access token.
+    async fn authorize(&self, request: RequestBuilder) -> Result<RequestBuilder> {
+        match (&self.config.oauth, &self.oauth) {
+            (Some(oauth), Some(session)) => {
+                Ok(request.bearer_auth(self.access_token(oauth, session).await?))
+            }
+            _ => Ok(request.basic_auth(&self.config.email, Some(&self.config.api_token))),
+        }
+    }
+
+    /// The current OAuth access token, refreshed first when it is missing or about to
+    /// expire. A rotated refresh token is kept for the next refresh and saved.
+    async fn access_token(&self, oauth: &OAuthConfig, session: &OAuthSession) -> Result<String> {
+        let mut tokens = session.tokens.lock().await;
+        if let Some((token, expires_at)) = &tokens.access_token
+            && *expires_at > Instant::now() + TOKEN_REFRESH_MARGIN
+        {
+            return Ok(token.clone());
+        }
 
         let resp = self
             .client
-            .get(&url)
-            .basic_auth(&self.config.email, Some(&self.config.api_token))
-            .header(ACCEPT, "application/json")
-            .query(&[
-                ("title", title),
-                ("spaceKey", &self.config.space_key),
-                ("expand", "version"),
-            ])
+            .post(&session.token_url)
+            .json(&serde_json::json!({
+                "grant_type": "refresh_token",
+                "client_id": oauth.client_id,
+                "client_secret": oauth.client_secret,
+                "refresh_token": tokens.refresh_token,
+            }))
             .send()
             .await
-            .context("Failed to search for existing page")?;
-
-        if resp.status() == reqwest::StatusCode::NOT_FOUND {
-            return Ok(None);
-        }
-
+            .context("Confluence OAuth token refresh failed")?;
         if !resp.status().is_success() {
             let status = resp.status();
             let body = resp.text().await.unwrap_or_default();
-            bail!(
-                "Confluence search request failed (HTTP {}): {}",
-                status,
-                body
+            return Err(TokenRefreshFailed { status, body }.into());
+        }
+        let refreshed: TokenResponse =
+            resp.json().await.context("Failed to parse OAuth token response")?;
+
+        if let Some(rotated) = refreshed.refresh_token.filter(|t| *t != tokens.refresh_token) {
+            self.save_refresh_token(&rotated).await;
+            tokens.refresh_token = rotated;
+        }
+        let expires_at = Instant::now() + Duration::from_secs(refreshed.expires_in);
+        tokens.access_token = Some((refreshed.access_token.clone(), expires_at));
+        info!(
+            "Refreshed Confluence OAuth access token for {} (expires in {}s)",
+            self.config.base_url, refreshed.expires_in
+        );
+        Ok(refreshed.access_token)
+    }
+
+    /// Write a rotated refresh token back to `confluence_config`, so the target can still
+    /// authenticate after a restart. A failure only warns: this client keeps working.
+    async fn save_refresh_token(&self, refresh_token: &str) {
+        let Some(store) = &self.refresh_token_store else {
+            warn!(
+                "Confluence OAuth refresh token for {} was rotated but cannot be saved (it is \
+                 a secret reference); update the secret before this process restarts",
+                self.config.base_url
             );
+            return;
+        };
+        match db::update_oauth_refresh_token(&store.pool, store.akeneo_server_id, refresh_token)
+            .await
+        {
+            Ok(()) => {
+                store.configs.invalidate(Some(store.akeneo_server_id));
+            }
+            Err(e) => warn!("Failed to save rotated Confluence OAuth refresh token: {:#}", e),
+        }
+    }
+
+    /// Send a request once the site's rate budget and connection cap allow it (see
+    /// [`PublishCoordinator`]) and record it in the connection-reuse metrics. The response
+    /// body is read before the connection slot is released, and handed back buffered. A
+    /// 429 pauses the site for its `Retry-After` and the request is retried (unless its
+    /// body cannot be replayed, as with attachment uploads); the last 429 is returned to
+    /// the caller. An OAuth request answered with 401 is retried once with a freshly
+    /// refreshed access token. A mock client answers the request itself.
+    async fn send(&self, mut request: RequestBuilder) -> Result<Response> {
+        if let Some(mock) = &self.mock {
+            return mock.handle(request.build()?);
+        }
+        let base_url = &self.config.base_url;
+        let mut attempt = 0;
+        let mut reauthorized = false;
+        loop {
+            let mut retry = request.try_clone();
+            let admission = self.coordinator.acquire(base_url).await?;
+            let sent = self.authorize(request).await?.send().await;
+            let reached = sent
+                .as_ref()
+                .is_ok_and(|resp| !resp.status().is_server_error());
+            self.coordinator.settle(base_url, &admission, reached);
+            let resp = sent?;
+            metrics::record_confluence_response(resp.version());
+            if resp.status() == StatusCode::UNAUTHORIZED
+                && !reauthorized
+                && let Some(session) = &self.oauth
+                && let Some(next) = retry.take()
+            {
+                // The access token was revoked or expired early
+                session.tokens.lock().await.access_token = None;
+                reauthorized = true;
+                request = next;
+                continue;
+            }
+            if resp.status() != StatusCode::TOO_MANY_REQUESTS {
+                let resp = buffered(resp).await?;
+                drop(admission);
+                return Ok(resp);
+            }
+
+            metrics::record_confluence_rate_limited();
+            let wait = retry_after(&resp);
+            self.coordinator.pause(base_url, wait);
+            match retry {
+                Some(next) if attempt < self.coordinator.retries => {
+                    attempt += 1;
+                    warn!(
+                        "Confluence rate limit hit at {}; pausing the site for {}s ({}/{})",
+                        base_url,
+                        wait.as_secs(),
+                        attempt,
+                        self.coordinator.retries
+                    );
+                    request = next;
+                }
+                _ => return buffered(resp).await,
+            }
+        }
+    }
+
+    /// The configuration this client publishes with.
+    pub fn config(&self) -> &ConfluenceConfig {
+        &self.config
+    }
+
+    /// Authenticated `GET {api base}/rest/api/{path}`, returning the raw response so
+    /// callers can interpret the status themselves (see `preflight.rs`).
+    pub async fn api_get(&self, path: &str, query: &[(&str, &str)]) -> Result<Response> {
+        let url = format!("{}/rest/api/{}", self.api_base(), path);
+        self.send(
+            self.client
+                .get(&url)
+                .query(query)
+                .header(ACCEPT, "application/json"),
+        )
+        .await
+    }
+
+    /// Look the configured parent page title up once, so pages are created under its ID
+    /// without a search each time. Left to be resolved on create (which reports a missing
+    /// parent) when the lookup finds nothing or fails.
+    pub async fn resolve_parent(mut self) -> Self {
+        if self.config.parent_page_id.is_none() && !self.config.parent_page.is_empty() {
+            match self.find_page_id(&self.config.parent_page).await {
+                Ok(Some(id)) => self.config.parent_page_id = Some(id),
+                Ok(None) => {}
+                Err(e) => warn!(
+                    "Failed to resolve parent page '{}': {:#}",
+                    self.config.parent_page, e
+                ),
+            }
+        }
+        self
+    }
+
+    /// ID of the page with this exact title in the configured space, if there is one.
+    pub async fn find_page_id(&self, title: &str) -> Result<Option<String>> {
+        Ok(self.find_page(title, None, ContentType::Page).await?.map(|(id, _)| id))
+    }
+
+    /// Search for an existing page by exact title in the configured space.
+    /// Returns the page ID and current version number if found.
+    ///
+    /// Every result page is read, so a match is found however many pages the title
+    /// search returns. With `ancestor_id`, a match nested under that page is preferred;
+    /// remaining ties go to the oldest page (lowest ID), keeping lookups deterministic.
+    async fn find_page(
+        &self,
+        title: &str,
+        ancestor_id: Option<&str>,
+        content_type: ContentType,
+    ) -> Result<Option<(String, u64)>> {
+        let url = format!("{}/rest/api/content", self.api_base());
+        let query = [
+            ("title", title),
+            ("spaceKey", self.config.space_key.as_str()),
+            ("type", content_type.as_str()),
+            ("status", "current"),
+            ("expand", "version,ancestors"),
+        ];
+
+        let results: Vec<PageResult> = self
+            .get_paginated(&url, &query)
+            .await
+            .context("Failed to search for existing page")?;
+
+        // The title filter is not guaranteed to be exact (e.g. case), so re-check it
+        let mut matches: Vec<PageResult> =
+            results.into_iter().filter(|p| p.title == title).collect();
+        if let Some(ancestor) = ancestor_id
+            && matches.iter().any(|p| p.ancestors.iter().any(|a| a.id == ancestor))
+        {
+            matches.retain(|p| p.ancestors.iter().any(|a| a.id == ancestor));
         }
+        matches.sort_by_key(|p| (p.id.parse::<u64>().unwrap_or(u64::MAX), p.id.clone()));
 
-        let results: SearchResults = resp.json().await.context("Failed to parse search response")?;
+        if matches.len() > 1 {
+            warn!(
+                "{} pages titled \"{}\" found; using the oldest (id={})",
+                matches.len(),
+                title,
+                matches[0].id
+            );
+        }
 
-        if let Some(page) = results.results.first() {
+        Ok(matches.into_iter().next().map(|page| {
             let version = page.version.as_ref().map(|v| v.number).unwrap_or(1);
-            Ok(Some((page.id.clone(), version)))
-        } else {
-            Ok(None)
+            (page.id, version)
+        }))
+    }
+
+    /// Fetch every result of a paginated v1 listing endpoint. Follows the `_links.next`
+    /// cursor when Confluence returns one; otherwise pages with `start` / `limit` until a
+    /// short page.
+    async fn get_paginated<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>> {
+        let mut all = Vec::new();
+        let mut start = 0;
+        let mut next: Option<String> = None;
+
+        loop {
+            let request = match &next {
+                Some(next_url) => self.client.get(next_url),
+                None => self.client.get(url).query(query).query(&[
+                    ("start", start.to_string()),
+                    ("limit", PAGE_LIMIT.to_string()),
+                ]),
+            };
+            let resp = self.send(request.header(ACCEPT, "application/json")).await?;
+
+            // A missing container (e.g. a deleted parent) simply has no results
+            if resp.status() == reqwest::StatusCode::NOT_FOUND {
+                break;
+            }
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let body = resp.text().await.unwrap_or_default();
+                bail!("Confluence request failed (HTTP {}): {}", status, body);
+            }
+
+            let page: Paged<T> = resp.json().await.context("Failed to parse listing response")?;
+            let count = page.results.len();
+            all.extend(page.results);
+
+            match page.links {
+                Some(PagedLinks {
+                    next: Some(path),
+                    base,
+                }) if count > 0 => {
+                    next = Some(if path.starts_with("http") {
+                        path
+                    } else {
+                        // `base` is the site, which does not accept OAuth access tokens
+                        let base = base
+                            .filter(|_| self.oauth.is_none())
+                            .unwrap_or_else(|| self.api_base());
+                        format!("{}{}", base.trim_end_matches('/'), path)
+                    });
+                }
+                // Confluence returns `_links` on every page, so no `next` means the end
+                Some(_) => break,
+                None if count < PAGE_LIMIT => break,
+                None => start += count,
+            }
         }
+
+        Ok(all)
     }
 
     /// Create a new Confluence page using storage (XHTML) representation.
     /// If `parent_id` is provided, the page is nested under that parent.
-    /// Otherwise, falls back to the configured parent page title.
+    /// Otherwise, falls back to the configured parent page ID, then the parent page title.
+    /// Blog posts have no parent.
     async fn create_page(
         &self,
         title: &str,
         body_storage: &str,
         parent_id: Option<&str>,
-    ) -> Result<PublishResult> {
-        let url = format!(
-            "{}/wiki/rest/api/content",
-            self.config.base_url.trim_end_matches('/')
-        );
+        content_type: ContentType,
+    ) -> Result<PublishOutcome> {
+        let url = format!("{}/rest/api/content", self.api_base());
 
         let mut page_json = serde_json::json!({
-            "type": "page",
+            "type": content_type.as_str(),
             "title": title,
             "space": {
                 "key": &self.config.space_key
@@ -173,12 +1691,15 @@ impl ConfluenceClient {
             }
         });
 
-        // Resolve parent: use explicit parent_id if given, otherwise resolve configured parent title
-        if let Some(pid) = parent_id {
+        // Resolve parent: use explicit parent_id if given, otherwise the configured parent
+        // page ID, otherwise resolve the configured parent title
+        if content_type == ContentType::BlogPost {
+            // Blog posts live in the space's blog, outside the page tree
+        } else if let Some(pid) = parent_id.or(self.config.parent_page_id.as_deref()) {
             page_json["ancestors"] = serde_json::json!([{ "id": pid }]);
         } else if !self.config.parent_page.is_empty() {
             let resolved_id = self
-                .find_page(&self.config.parent_page)
+                .find_page(&self.config.parent_page, None, ContentType::Page)
                 .await?
                 .map(|(id, _version)| id)
                 .with_context(|| {
@@ -191,13 +1712,13 @@ impl ConfluenceClient {
         }
 
         let resp = self
-            .client
-            .post(&url)
-            .basic_auth(&self.config.email, Some(&self.config.api_token))
-            .header(CONTENT_TYPE, "application/json")
-            .header(ACCEPT, "application/json")
-            .json(&page_json)
-            .send()
+            .send(
+                self.client
+                    .post(&url)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(ACCEPT, "application/json")
+                    .json(&page_json),
+            )
             .await
             .context("Failed to create Confluence page")?;
 
@@ -212,9 +1733,11 @@ impl ConfluenceClient {
 
         let web_url = self.build_web_url(&result);
         info!("Created new page: {}", web_url);
-        Ok(PublishResult {
+        Ok(PublishOutcome {
             page_id: result.id,
             web_url,
+            operation: PageOperation::Created,
+            version: result.version.map_or(1, |v| v.number),
         })
     }
 
@@ -225,15 +1748,12 @@ impl ConfluenceClient {
         title: &str,
         body_storage: &str,
         current_version: u64,
-    ) -> Result<PublishResult> {
-        let url = format!(
-            "{}/wiki/rest/api/content/{}",
-            self.config.base_url.trim_end_matches('/'),
-            page_id
-        );
+        content_type: ContentType,
+    ) -> Result<PublishOutcome> {
+        let url = format!("{}/rest/api/content/{}", self.api_base(), page_id);
 
         let page_json = serde_json::json!({
-            "type": "page",
+            "type": content_type.as_str(),
             "title": title,
             "version": {
                 "number": current_version + 1
@@ -247,16 +1767,24 @@ impl ConfluenceClient {
         });
 
         let resp = self
-            .client
-            .put(&url)
-            .basic_auth(&self.config.email, Some(&self.config.api_token))
-            .header(CONTENT_TYPE, "application/json")
-            .header(ACCEPT, "application/json")
-            .json(&page_json)
-            .send()
+            .send(
+                self.client
+                    .put(&url)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(ACCEPT, "application/json")
+                    .json(&page_json),
+            )
             .await
             .context("Failed to update Confluence page")?;
 
+        if resp.status() == reqwest::StatusCode::CONFLICT {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(VersionConflict {
+                page_id: page_id.to_string(),
+                body,
+            }
+            .into());
+        }
         if !resp.status().is_success() {
             let status = resp.status();
             let body = resp.text().await.unwrap_or_default();
@@ -272,17 +1800,133 @@ impl ConfluenceClient {
             current_version + 1,
             web_url
         );
-        Ok(PublishResult {
+        Ok(PublishOutcome {
             page_id: result.id,
             web_url,
+            operation: PageOperation::Updated,
+            version: result.version.map_or(current_version + 1, |v| v.number),
         })
     }
 
+    /// Update a page, recovering from version conflicts: on a 409 the current version is
+    /// re-read and the update retried, up to `conflict_retries` times. With `verify_owner`,
+    /// a conflicting page without our first page property is left alone instead.
+    async fn update_page_with_retry(
+        &self,
+        page_id: &str,
+        title: &str,
+        body_storage: &str,
+        mut version: u64,
+        content_type: ContentType,
+    ) -> Result<PublishOutcome> {
+        let mut attempt = 0;
+        loop {
+            match self
+                .update_page(page_id, title, body_storage, version, content_type)
+                .await
+            {
+                Err(e) if e.is::<VersionConflict>() && attempt == self.conflict_retries => {
+                    return Err(e.context(format!(
+                        "Gave up updating '{}' after {} version-conflict retries",
+                        title, attempt
+                    )));
+                }
+                Err(e) if e.is::<VersionConflict>() => {
+                    attempt += 1;
+                    if self.verify_owner
+                        && let Some((key, _)) = self.page_properties.first()
+                        && !self.has_content_property(page_id, key).await?
+                    {
+                        return Err(e.context(format!(
+                            "Page '{}' (id={}) changed while publishing and has no '{}' \
+                             property, so it was not overwritten",
+                            title, page_id, key
+                        )));
+                    }
+                    version = self.get_page_version(page_id).await?;
+                    warn!(
+                        "Version conflict updating '{}' (id={}); retrying on version {} ({}/{})",
+                        title, page_id, version, attempt, self.conflict_retries
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Current version number of a page.
+    async fn get_page_version(&self, page_id: &str) -> Result<u64> {
+        let url = format!("{}/rest/api/content/{}", self.api_base(), page_id);
+        let resp = self
+            .send(
+                self.client
+                    .get(&url)
+                    .query(&[("expand", "version")])
+                    .header(ACCEPT, "application/json"),
+            )
+            .await
+            .context("Failed to fetch Confluence page version")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("Confluence get page failed (HTTP {}): {}", status, body);
+        }
+
+        let page: PageResult = resp.json().await.context("Failed to parse page response")?;
+        Ok(page.version.map(|v| v.number).unwrap_or(1))
+    }
+
+    /// The storage format body of a page, as Confluence currently holds it.
+    pub async fn get_page_body(&self, page_id: &str) -> Result<String> {
+        let url = format!("{}/rest/api/content/{}", self.api_base(), page_id);
+        let resp = self
+            .send(
+                self.client
+                    .get(&url)
+                    .query(&[("expand", "body.storage")])
+                    .header(ACCEPT, "application/json"),
+            )
+            .await
+            .context("Failed to fetch Confluence page body")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("Confluence get page failed (HTTP {}): {}", status, body);
+        }
+
+        let page: PageBodyResponse = resp.json().await.context("Failed to parse page response")?;
+        Ok(page.body.storage.value)
+    }
+
+    /// Whether a page has a content property with this key.
+    async fn has_content_property(&self, page_id: &str, key: &str) -> Result<bool> {
+        let url = format!("{}/rest/api/content/{}/property/{}", self.api_base(), page_id, key);
+        let resp = self
+            .send(self.client.get(&url).header(ACCEPT, "application/json"))
+            .await
+            .context("Failed to fetch content property")?;
+
+        match resp.status() {
+            status if status.is_success() => Ok(true),
+            reqwest::StatusCode::NOT_FOUND => Ok(false),
+            status => {
+                let body = resp.text().await.unwrap_or_default();
+                bail!(
+                    "Confluence get content property failed (HTTP {}): {}",
+                    status,
+                    body
+                );
+            }
+        }
+    }
+
     /// Create or update a Confluence page with the given title and storage format body.
     /// If a page with the same title already exists in the space, it will be updated.
     /// Otherwise, a new page will be created under the configured parent page.
-    pub async fn publish_page(&self, title: &str, body_storage: &str) -> Result<PublishResult> {
-        self.upsert_page(title, body_storage, None).await
+    pub async fn publish_page(&self, title: &str, body_storage: &str) -> Result<PublishOutcome> {
+        self.upsert_page(title, body_storage, None, ContentType::Page).await
     }
 
     /// Create or update a Confluence page under a specific parent page (by ID).
@@ -293,33 +1937,352 @@ impl ConfluenceClient {
         title: &str,
         body_storage: &str,
         parent_id: &str,
-    ) -> Result<PublishResult> {
-        self.upsert_page(title, body_storage, Some(parent_id)).await
+    ) -> Result<PublishOutcome> {
+        self.upsert_page(title, body_storage, Some(parent_id), ContentType::Page)
+            .await
+    }
+
+    /// Create or update a blog post in the configured space, matched by its title like a
+    /// page. Carries the same page properties as pages.
+    pub async fn publish_blog_post(
+        &self,
+        title: &str,
+        body_storage: &str,
+    ) -> Result<PublishOutcome> {
+        self.upsert_page(title, body_storage, None, ContentType::BlogPost)
+            .await
+    }
+
+    /// Publish a page hierarchy: the root (under `parent_id`, else the configured parent),
+    /// then each page's children under it, depth first. Every body is size-checked before
+    /// anything is written, the configured parent is resolved once, and the pages already
+    /// under the root are listed in one request, so only pages new to the tree are searched
+    /// for by title. `progress` is told about every page published or failed.
+    pub async fn publish_tree(
+        &self,
+        tree: &PageTree<'_>,
+        parent_id: Option<&str>,
+        progress: &(dyn Fn(&str, Result<PageOperation, &anyhow::Error>) + Sync),
+    ) -> Result<PublishedTree> {
+        for page in tree.pages() {
+            self.ensure_body_fits(page.title, page.body)?;
+        }
+        let parent_id = match parent_id {
+            Some(id) => Some(id.to_string()),
+            None => self.configured_parent_id().await?,
+        };
+
+        let root = self
+            .publish_tree_page(tree, parent_id.as_deref(), &HashMap::new(), progress)
+            .await?;
+        let existing = self.tree_pages(&root.page_id).await?;
+        let children = self
+            .publish_subtrees(&tree.children, &root.page_id, &existing, progress)
+            .await?;
+        Ok(PublishedTree {
+            outcome: root,
+            children,
+        })
+    }
+
+    /// The pages of `trees` and their descendants, published under `parent_id`.
+    fn publish_subtrees<'s>(
+        &'s self,
+        trees: &'s [PageTree<'s>],
+        parent_id: &'s str,
+        existing: &'s HashMap<String, (String, u64)>,
+        progress: &'s (dyn Fn(&str, Result<PageOperation, &anyhow::Error>) + Sync),
+    ) -> BoxFuture<'s, Result<Vec<PublishedTree>>> {
+        async move {
+            let mut published = Vec::with_capacity(trees.len());
+            for tree in trees {
+                let outcome = self
+                    .publish_tree_page(tree, Some(parent_id), existing, progress)
+                    .await?;
+                let children = self
+                    .publish_subtrees(&tree.children, &outcome.page_id, existing, progress)
+                    .await?;
+                published.push(PublishedTree {
+                    outcome,
+                    children,
+                });
+            }
+            Ok(published)
+        }
+        .boxed()
+    }
+
+    /// Publish one page of a tree, using its entry in `existing` (see `tree_pages`) when
+    /// it has one instead of searching for its title.
+    async fn publish_tree_page(
+        &self,
+        page: &PageTree<'_>,
+        parent_id: Option<&str>,
+        existing: &HashMap<String, (String, u64)>,
+        progress: &(dyn Fn(&str, Result<PageOperation, &anyhow::Error>) + Sync),
+    ) -> Result<PublishOutcome> {
+        let known = existing.get(page.title).cloned();
+        let published = match known {
+            Some(existing) => {
+                self.check_body_size(page.title, page.body)?;
+                let existing = Some(existing);
+                self.write_page(page.title, page.body, parent_id, ContentType::Page, existing)
+                    .await
+            }
+            None => {
+                self.upsert_page(page.title, page.body, parent_id, ContentType::Page)
+                    .await
+            }
+        }
+        .with_context(|| format!("Failed to publish page '{}' to Confluence", page.title));
+        match &published {
+            Ok(outcome) => {
+                info!("Page '{}' published (id={})", page.title, outcome.page_id);
+                progress(page.title, Ok(outcome.operation));
+            }
+            Err(e) => progress(page.title, Err(e)),
+        }
+        published
+    }
+
+    /// ID of the configured parent page, looking its title up when no ID is configured.
+    /// `None` at the top level of the space, or when the title is not found: creating the
+    /// root then reports the missing parent, while an existing root is still updated.
+    async fn configured_parent_id(&self) -> Result<Option<String>> {
+        if let Some(id) = &self.config.parent_page_id {
+            return Ok(Some(id.clone()));
+        }
+        if self.config.parent_page.is_empty() {
+            return Ok(None);
+        }
+        self.find_page_id(&self.config.parent_page).await
+    }
+
+    /// The pages already under a tree's root, by title, with their current version.
+    async fn tree_pages(&self, root_id: &str) -> Result<HashMap<String, (String, u64)>> {
+        if root_id.starts_with(PLANNED_PAGE_PREFIX) {
+            return Ok(HashMap::new());
+        }
+        let url = format!("{}/rest/api/content/{}/descendant/page", self.api_base(), root_id);
+        let results: Vec<DescendantPageResult> = self
+            .get_paginated(&url, &[("expand", "version")])
+            .await
+            .context("Failed to fetch the pages under the root page")?;
+        Ok(results
+            .into_iter()
+            .map(|page| {
+                let version = page.version.map_or(1, |v| v.number);
+                (page.title, (page.id, version))
+            })
+            .collect())
     }
 
-    /// Internal upsert logic shared by publish_page and publish_page_under_id.
+    /// Internal upsert logic shared by publish_page, publish_page_under_id,
+    /// publish_blog_post and publish_tree.
     async fn upsert_page(
         &self,
         title: &str,
         body_storage: &str,
         parent_id: Option<&str>,
-    ) -> Result<PublishResult> {
+        content_type: ContentType,
+    ) -> Result<PublishOutcome> {
+        self.check_body_size(title, body_storage)?;
         info!("Searching for existing page: \"{}\"...", title);
 
-        match self.find_page(title).await? {
+        let existing = self.find_page(title, parent_id, content_type).await?;
+        self.write_page(title, body_storage, parent_id, content_type, existing)
+            .await
+    }
+
+    /// Update the page `find_page` found (`existing`: ID and version) or create it, then
+    /// set its properties and restrictions.
+    async fn write_page(
+        &self,
+        title: &str,
+        body_storage: &str,
+        parent_id: Option<&str>,
+        content_type: ContentType,
+        existing: Option<(String, u64)>,
+    ) -> Result<PublishOutcome> {
+        if let Some(dry_run) = &self.dry_run {
+            // Blog posts have no parent; pages without one go under the configured parent
+            let parent_id = match content_type {
+                ContentType::Page => parent_id.or(self.config.parent_page_id.as_deref()),
+                ContentType::BlogPost => None,
+            };
+            return Ok(Self::plan_upsert(dry_run, title, parent_id, content_type, existing));
+        }
+        let result = match existing {
             Some((page_id, version)) => {
                 info!(
                     "Found existing page (id={}, version={}). Updating...",
                     page_id, version
                 );
-                self.update_page(&page_id, title, body_storage, version)
+                self.update_page_with_retry(&page_id, title, body_storage, version, content_type)
                     .await
             }
             None => {
                 info!("No existing page found. Creating new page...");
-                self.create_page(title, body_storage, parent_id).await
+                self.create_page(title, body_storage, parent_id, content_type)
+                    .await
+            }
+        }?;
+        metrics::record_page_published(result.operation);
+
+        // The page itself is published at this point; a missing property only means
+        // other tools cannot identify it, so don't fail the publish over it
+        for (key, value) in &self.page_properties {
+            if let Err(e) = self.set_content_property(&result.page_id, key, value).await {
+                warn!(
+                    "Failed to set content property '{}' on page {}: {:#}",
+                    key, result.page_id, e
+                );
             }
         }
+
+        // Unlike a missing property, a page left open to edits breaks the target's policy,
+        // so this fails the publish (the page itself is already up to date)
+        self.apply_restrictions(&result.page_id)
+            .await
+            .with_context(|| format!("Failed to restrict page '{}'", title))?;
+
+        if let Some(log) = &self.publish_log {
+            let mut log = log.lock().unwrap();
+            log.retain(|page| page.page_id != result.page_id);
+            log.push(PublishedPage {
+                page_id: result.page_id.clone(),
+                title: title.to_string(),
+                web_url: result.web_url.clone(),
+                body: body_storage.to_string(),
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// Replace a page's restrictions with the target's `PageRestrictions`; nothing to do
+    /// without any. Restrictions set on the page by hand are overwritten.
+    async fn apply_restrictions(&self, page_id: &str) -> Result<()> {
+        let restrictions = &self.config.restrictions;
+        if restrictions.is_empty() {
+            return Ok(());
+        }
+        let account_id = self.current_account_id().await?;
+        let url = format!("{}/rest/api/content/{}/restriction", self.api_base(), page_id);
+        let resp = self
+            .send(
+                self.client
+                    .put(&url)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(ACCEPT, "application/json")
+                    .json(&restrictions.request_body(account_id)),
+            )
+            .await
+            .context("Failed to update page restrictions")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!(
+                "Confluence update page restrictions failed (HTTP {}): {}",
+                status,
+                body
+            );
+        }
+        Ok(())
+    }
+
+    /// The account ID of the user requests are made as (the API token's user, or the user
+    /// who authorized the OAuth app), looked up once per client.
+    async fn current_account_id(&self) -> Result<&str> {
+        let account_id = self
+            .account_id
+            .get_or_try_init(|| async {
+                let url = format!("{}/rest/api/user/current", self.api_base());
+                let resp = self
+                    .send(self.client.get(&url).header(ACCEPT, "application/json"))
+                    .await
+                    .context("Failed to look up the current user")?;
+                let status = resp.status();
+                if !status.is_success() {
+                    let body = resp.text().await.unwrap_or_default();
+                    bail!("Confluence current user lookup failed (HTTP {}): {}", status, body);
+                }
+                let user: CurrentUserResponse = resp
+                    .json()
+                    .await
+                    .context("Failed to parse current user response")?;
+                Ok::<_, anyhow::Error>(user.account_id)
+            })
+            .await?;
+        Ok(account_id)
+    }
+
+    /// Create or replace a content property (a JSON value stored on the page, invisible
+    /// to readers) on a page.
+    pub async fn set_content_property(
+        &self,
+        page_id: &str,
+        key: &str,
+        value: &serde_json::Value,
+    ) -> Result<()> {
+        let base_url = format!("{}/rest/api/content/{}/property", self.api_base(), page_id);
+        let property_url = format!("{}/{}", base_url, key);
+
+        // Updating a property needs its current version, so look it up first
+        let resp = self
+            .send(
+                self.client
+                    .get(&property_url)
+                    .header(ACCEPT, "application/json"),
+            )
+            .await
+            .context("Failed to fetch content property")?;
+
+        let request = if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            self.client.post(&base_url).json(&serde_json::json!({
+                "key": key,
+                "value": value,
+            }))
+        } else if resp.status().is_success() {
+            let existing: ContentPropertyResponse = resp
+                .json()
+                .await
+                .context("Failed to parse content property response")?;
+            self.client.put(&property_url).json(&serde_json::json!({
+                "key": key,
+                "value": value,
+                "version": { "number": existing.version.number + 1 },
+            }))
+        } else {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!(
+                "Confluence get content property failed (HTTP {}): {}",
+                status,
+                body
+            );
+        };
+
+        let resp = self
+            .send(
+                request
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(ACCEPT, "application/json"),
+            )
+            .await
+            .context("Failed to write content property")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!(
+                "Confluence set content property failed (HTTP {}): {}",
+                status,
+                body
+            );
+        }
+
+        Ok(())
     }
 
     /// Build the web URL for a page from its API response.
@@ -348,81 +2311,193 @@ impl ConfluenceClient {
     /// Get all child pages of a given parent page (paginates automatically).
     /// Returns a list of `ChildPageInfo` with id and title for each child.
     pub async fn get_child_pages(&self, parent_id: &str) -> Result<Vec<ChildPageInfo>> {
-        let base_url = format!(
-            "{}/wiki/rest/api/content/{}/descendant/page",
-            self.config.base_url.trim_end_matches('/'),
-            parent_id
-        );
-
-        let mut all_children = Vec::new();
-        let mut start: u64 = 0;
-        let limit: u64 = 25;
+        if parent_id.starts_with(PLANNED_PAGE_PREFIX) {
+            return Ok(Vec::new());
+        }
+        let url = format!("{}/rest/api/content/{}/descendant/page", self.api_base(), parent_id);
 
-        loop {
-            let resp = self
-                .client
-                .get(&base_url)
-                .basic_auth(&self.config.email, Some(&self.config.api_token))
-                .header(ACCEPT, "application/json")
-                .query(&[
-                    ("start", start.to_string()),
-                    ("limit", limit.to_string()),
-                ])
-                .send()
-                .await
-                .context("Failed to fetch child pages")?;
+        let results: Vec<DescendantPageResult> = self
+            .get_paginated(&url, &[])
+            .await
+            .context("Failed to fetch child pages")?;
 
-            if !resp.status().is_success() {
-                let status = resp.status();
-                let body = resp.text().await.unwrap_or_default();
-                bail!(
-                    "Confluence get child pages failed (HTTP {}): {}",
-                    status,
-                    body
-                );
+        if let Some(dry_run) = &self.dry_run {
+            let mut dry_run = dry_run.lock().unwrap();
+            for result in &results {
+                dry_run.listed.insert(result.id.clone(), result.title.clone());
             }
+        }
+        Ok(results
+            .into_iter()
+            .map(|result| ChildPageInfo {
+                id: result.id,
+                title: result.title,
+            })
+            .collect())
+    }
 
-            let page: DescendantPagesResponse = resp
-                .json()
-                .await
-                .context("Failed to parse child pages response")?;
+    /// Make pages children of a parent and, with `reorder`, arrange them in the given order
+    /// (Confluence lists children in page-tree order). Updating a page keeps its parent, so
+    /// pages found elsewhere (e.g. under a group page that is no longer used) are appended
+    /// to the parent first. Only pages that are out of place are moved, and other children
+    /// of the parent are left alone. Returns the number of pages moved.
+    pub async fn arrange_child_pages(
+        &self,
+        parent_id: &str,
+        page_ids: &[String],
+        reorder: bool,
+    ) -> Result<usize> {
+        if self.dry_run.is_some() {
+            return Ok(0);
+        }
+        let url = format!("{}/rest/api/content/{}/child/page", self.api_base(), parent_id);
+        let children: Vec<DescendantPageResult> = self
+            .get_paginated(&url, &[])
+            .await
+            .context("Failed to fetch child pages")?;
 
-            let results_count = page.results.len() as u64;
+        let wanted: HashSet<&str> = page_ids.iter().map(String::as_str).collect();
+        let mut current: Vec<String> = children
+            .into_iter()
+            .map(|child| child.id)
+            .filter(|id| wanted.contains(id.as_str()))
+            .collect();
 
-            for result in page.results {
-                all_children.push(ChildPageInfo {
-                    id: result.id,
-                    title: result.title,
-                });
+        let mut moved = 0;
+        for id in page_ids {
+            if !current.contains(id) {
+                self.move_page(id, "append", parent_id).await?;
+                current.push(id.clone());
+                moved += 1;
             }
+        }
+        if !reorder {
+            return Ok(moved);
+        }
 
-            // If we got fewer results than the limit, we've reached the end
-            if results_count < limit {
-                break;
+        // `current` now holds exactly `page_ids`. Invariant: `current[..i]` matches
+        // `page_ids[..i]`, so each misplaced page goes right after its predecessor (the first
+        // one before the first listed page)
+        for (i, id) in page_ids.iter().enumerate() {
+            if current[i] == *id {
+                continue;
             }
+            match i {
+                0 => self.move_page(id, "before", &current[0]).await?,
+                _ => self.move_page(id, "after", &page_ids[i - 1]).await?,
+            }
+            current.retain(|c| c != id);
+            current.insert(i, id.clone());
+            moved += 1;
+        }
+        Ok(moved)
+    }
+
+    /// Move a page `before` or `after` a sibling, or `append` it to a new parent's children.
+    async fn move_page(&self, page_id: &str, position: &str, target_id: &str) -> Result<()> {
+        let url = format!(
+            "{}/rest/api/content/{}/move/{}/{}",
+            self.api_base(),
+            page_id,
+            position,
+            target_id
+        );
+        let resp = self
+            .send(self.client.put(&url).header(ACCEPT, "application/json"))
+            .await
+            .context("Failed to move Confluence page")?;
 
-            start += results_count;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("Confluence move page failed (HTTP {}): {}", status, body);
+        }
+        Ok(())
+    }
+
+    /// Attach a file to a page, replacing an existing attachment with the same filename
+    /// (Confluence keeps the previous upload as an older attachment version).
+    pub async fn upload_attachment(
+        &self,
+        page_id: &str,
+        filename: &str,
+        content_type: &str,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        if self.dry_run.is_some() {
+            return Ok(());
+        }
+        if let Some(mock) = &self.mock {
+            return mock.save_attachment(page_id, filename, &data);
+        }
+        let url = format!("{}/rest/api/content/{}/child/attachment", self.api_base(), page_id);
+
+        let part = multipart::Part::bytes(data)
+            .file_name(filename.to_string())
+            .mime_str(content_type)
+            .context("Invalid attachment content type")?;
+        let form = multipart::Form::new()
+            .part("file", part)
+            .text("minorEdit", "true");
+
+        // PUT creates the attachment or adds a new version of an existing one
+        let resp = self
+            .send(
+                self.client
+                    .put(&url)
+                    .header("X-Atlassian-Token", "no-check")
+                    .header(ACCEPT, "application/json")
+                    .multipart(form),
+            )
+            .await
+            .with_context(|| format!("Failed to upload attachment '{}'", filename))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!(
+                "Confluence attachment upload of '{}' failed (HTTP {}): {}",
+                filename,
+                status,
+                body
+            );
         }
 
-        Ok(all_children)
+        info!("Attached '{}' to page {}", filename, page_id);
+        Ok(())
     }
 
-    /// Delete a Confluence page (moves it to trash).
+    /// Delete a Confluence page (moves it to trash). A page that no longer exists counts as
+    /// deleted.
     pub async fn delete_page(&self, page_id: &str) -> Result<()> {
-        let url = format!(
-            "{}/wiki/rest/api/content/{}",
-            self.config.base_url.trim_end_matches('/'),
-            page_id
-        );
+        if let Some(dry_run) = &self.dry_run {
+            let mut dry_run = dry_run.lock().unwrap();
+            let title = dry_run.listed.get(page_id).cloned().unwrap_or_default();
+            dry_run.pages.push((
+                page_id.to_string(),
+                PlannedPage {
+                    action: PlannedAction::Prune,
+                    title,
+                    content_type: ContentType::Page,
+                    page_id: Some(page_id.to_string()),
+                    parent_id: None,
+                    parent_title: None,
+                },
+            ));
+            return Ok(());
+        }
+        let url = format!("{}/rest/api/content/{}", self.api_base(), page_id);
 
         let resp = self
-            .client
-            .delete(&url)
-            .basic_auth(&self.config.email, Some(&self.config.api_token))
-            .send()
+            .send(self.client.delete(&url))
             .await
             .context("Failed to delete Confluence page")?;
 
+        // Already deleted (e.g. by hand), which is what the caller wants anyway
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            warn!("Confluence page {} was already deleted", page_id);
+            return Ok(());
+        }
         if !resp.status().is_success() {
             let status = resp.status();
             let body = resp.text().await.unwrap_or_default();
@@ -432,3 +2507,6 @@ impl ConfluenceClient {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests;
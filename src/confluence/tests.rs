@@ -0,0 +1,264 @@
+//! `ConfluenceClient` against a wiremock Confluence (creates, updates, version conflicts,
+//! rate limits) and against the file-backed `MockConfluence` of `CONFLUENCE_MODE=mock`.
+
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use wiremock::matchers::{body_partial_json, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::*;
+
+const TITLE: &str = "Current model";
+
+fn config(base_url: &str) -> ConfluenceConfig {
+    ConfluenceConfig {
+        base_url: base_url.to_string(),
+        email: "publisher@example.com".to_string(),
+        api_token: "token".to_string(),
+        oauth: None,
+        tls: TlsOptions::default(),
+        space_key: "DOC".to_string(),
+        parent_page: String::new(),
+        parent_page_id: None,
+        child_page_order: ChildPageOrder::None,
+        family_grouping: FamilyGrouping::None,
+        restrictions: PageRestrictions::default(),
+    }
+}
+
+/// A live client without a rate budget, retrying 429s up to 3 times.
+fn client(base_url: &str) -> ConfluenceClient {
+    let coordinator = PublishCoordinator::new(&ConcurrencyConfig {
+        confluence_requests_per_second: 0,
+        confluence_burst: 50,
+        confluence_retries: 3,
+        confluence_max_connections_per_host: 0,
+        confluence_breaker_threshold: 0,
+        confluence_breaker_cooldown_secs: 30,
+    });
+    ConfluenceClient::new(config(base_url), Client::new(), Arc::new(coordinator))
+}
+
+fn page(id: &str, version: u64) -> Value {
+    json!({
+        "id": id,
+        "title": TITLE,
+        "version": { "number": version },
+        "_links": { "webui": format!("/spaces/DOC/pages/{}", id) },
+    })
+}
+
+/// Mount the title search for `TITLE`, answering with `results`.
+async fn mount_search(server: &MockServer, results: Value) {
+    Mock::given(method("GET"))
+        .and(path("/wiki/rest/api/content"))
+        .and(query_param("title", TITLE))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "results": results, "_links": {} })),
+        )
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn creates_a_missing_page() {
+    let server = MockServer::start().await;
+    mount_search(&server, json!([])).await;
+    Mock::given(method("POST"))
+        .and(path("/wiki/rest/api/content"))
+        .and(body_partial_json(json!({
+            "type": "page",
+            "title": TITLE,
+            "space": { "key": "DOC" },
+            "body": { "storage": { "value": "<p>v1</p>" } },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page("100", 1)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let outcome = client(&server.uri()).publish_page(TITLE, "<p>v1</p>").await.unwrap();
+    assert_eq!(outcome.page_id, "100");
+    assert_eq!(outcome.operation, PageOperation::Created);
+    assert_eq!(outcome.version, 1);
+    assert_eq!(outcome.web_url, format!("{}/wiki/spaces/DOC/pages/100", server.uri()));
+}
+
+#[tokio::test]
+async fn updates_an_existing_page_with_the_next_version() {
+    let server = MockServer::start().await;
+    mount_search(&server, json!([page("100", 3)])).await;
+    Mock::given(method("PUT"))
+        .and(path("/wiki/rest/api/content/100"))
+        .and(body_partial_json(json!({ "version": { "number": 4 } })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page("100", 4)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let outcome = client(&server.uri()).publish_page(TITLE, "<p>v4</p>").await.unwrap();
+    assert_eq!(outcome.operation, PageOperation::Updated);
+    assert_eq!(outcome.version, 4);
+}
+
+#[tokio::test]
+async fn retries_a_version_conflict_on_the_current_version() {
+    let server = MockServer::start().await;
+    mount_search(&server, json!([page("100", 3)])).await;
+    Mock::given(method("PUT"))
+        .and(path("/wiki/rest/api/content/100"))
+        .and(body_partial_json(json!({ "version": { "number": 4 } })))
+        .respond_with(ResponseTemplate::new(409))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/wiki/rest/api/content/100"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page("100", 5)))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/wiki/rest/api/content/100"))
+        .and(body_partial_json(json!({ "version": { "number": 6 } })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page("100", 6)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let outcome = client(&server.uri()).publish_page(TITLE, "<p>v6</p>").await.unwrap();
+    assert_eq!(outcome.operation, PageOperation::Updated);
+    assert_eq!(outcome.version, 6);
+}
+
+#[tokio::test]
+async fn gives_up_after_the_conflict_retries() {
+    let server = MockServer::start().await;
+    mount_search(&server, json!([page("100", 3)])).await;
+    Mock::given(method("PUT"))
+        .and(path("/wiki/rest/api/content/100"))
+        .respond_with(ResponseTemplate::new(409))
+        .expect(DEFAULT_CONFLICT_RETRIES + 1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/wiki/rest/api/content/100"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page("100", 3)))
+        .mount(&server)
+        .await;
+
+    let err = client(&server.uri()).publish_page(TITLE, "<p>v4</p>").await.unwrap_err();
+    assert!(format!("{:#}", err).contains("version-conflict retries"), "{:#}", err);
+}
+
+#[tokio::test]
+async fn retries_a_rate_limited_request() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/wiki/rest/api/content"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .expect(2)
+        .mount(&server)
+        .await;
+    mount_search(&server, json!([])).await;
+    Mock::given(method("POST"))
+        .and(path("/wiki/rest/api/content"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page("100", 1)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let outcome = client(&server.uri()).publish_page(TITLE, "<p>v1</p>").await.unwrap();
+    assert_eq!(outcome.operation, PageOperation::Created);
+}
+
+#[tokio::test]
+async fn fails_when_the_rate_limit_outlasts_the_retries() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/wiki/rest/api/content"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .expect(4)
+        .mount(&server)
+        .await;
+
+    let err = client(&server.uri()).publish_page(TITLE, "<p>v1</p>").await.unwrap_err();
+    assert!(format!("{:#}", err).contains("HTTP 429"), "{:#}", err);
+}
+
+/// A fresh directory for a `MockConfluence`, removed when dropped.
+struct MockDir(PathBuf);
+
+impl MockDir {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("confluence-mock-{}", Uuid::new_v4())))
+    }
+
+    fn client(&self) -> ConfluenceClient {
+        let mock = MockConfluence::open(self.0.to_str().unwrap()).unwrap();
+        client("https://mock.atlassian.net").with_mock(Some(Arc::new(mock)))
+    }
+}
+
+impl Drop for MockDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[tokio::test]
+async fn mock_mode_keeps_pages_on_disk() {
+    let dir = MockDir::new();
+    let created = dir.client().publish_page(TITLE, "<p>v1</p>").await.unwrap();
+    assert_eq!(created.operation, PageOperation::Created);
+    assert_eq!(created.web_url, "https://mock.atlassian.net/wiki/spaces/DOC/pages/1");
+
+    // A new mock over the same directory finds the page again
+    let updated = dir.client().publish_page(TITLE, "<p>v2</p>").await.unwrap();
+    assert_eq!(updated.page_id, created.page_id);
+    assert_eq!(updated.operation, PageOperation::Updated);
+    assert_eq!(updated.version, 2);
+    let body = std::fs::read_to_string(dir.0.join("pages/1.html")).unwrap();
+    assert_eq!(body, "<p>v2</p>");
+}
+
+#[tokio::test]
+async fn mock_mode_publishes_a_page_tree() {
+    let dir = MockDir::new();
+    let client = dir.client();
+    let tree = PageTree {
+        title: TITLE,
+        body: "<p>root</p>",
+        children: vec![
+            PageTree {
+                title: "Family: Antibodies",
+                body: "<p>antibodies</p>",
+                children: Vec::new(),
+            },
+            PageTree {
+                title: "Family: Reagents",
+                body: "<p>reagents</p>",
+                children: Vec::new(),
+            },
+        ],
+    };
+    let no_progress = |_: &str, _: Result<PageOperation, &anyhow::Error>| {};
+
+    let first = client.publish_tree(&tree, None, &no_progress).await.unwrap();
+    assert_eq!(first.outcome.operation, PageOperation::Created);
+    let children: Vec<String> = client
+        .get_child_pages(&first.outcome.page_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|child| child.title)
+        .collect();
+    assert_eq!(children, ["Family: Antibodies", "Family: Reagents"]);
+
+    let second = client.publish_tree(&tree, None, &no_progress).await.unwrap();
+    assert_eq!(second.page_ids(), first.page_ids());
+    assert!(second.children.iter().all(|child| child.outcome.operation == PageOperation::Updated));
+    assert!(second.children.iter().all(|child| child.outcome.version == 2));
+}
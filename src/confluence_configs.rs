@@ -0,0 +1,643 @@
+use anyhow::Result;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::audit::{self, Actor};
+use crate::db::{self, ConfluenceConfigChanges, ConfluenceConfigRecord};
+use crate::extract::{Json, Path};
+use crate::render_options::{AttributeFilter, DisplayFormat, ItemProperties};
+use crate::{admin, preflight, secrets, servers, AppState, ErrorResponse};
+
+/// A Confluence target as returned by the config endpoints. Secrets are write-only: a
+/// secret reference (`env:` / `vault:`) is shown as stored, any other secret is masked and
+/// an unset one is `null`.
+#[derive(Serialize)]
+struct ConfluenceConfigView {
+    id: Uuid,
+    akeneo_server_id: Uuid,
+    server_name: String,
+    base_url: String,
+    username: String,
+    api_token: Option<String>,
+    space_key: String,
+    parent_page: String,
+    parent_page_id: Option<String>,
+    publish_mode: String,
+    draft_publishing: bool,
+    auth_type: String,
+    oauth_client_id: Option<String>,
+    oauth_client_secret: Option<String>,
+    oauth_refresh_token: Option<String>,
+    cloud_id: Option<String>,
+    snapshot_title_template: Option<String>,
+    family_title_template: Option<String>,
+    diff_title_template: Option<String>,
+    version_title_template: Option<String>,
+    staging_parent_page: Option<String>,
+    allowed_space_keys: Vec<String>,
+    view_restriction_groups: Vec<String>,
+    view_restriction_users: Vec<String>,
+    edit_restriction_groups: Vec<String>,
+    edit_restriction_users: Vec<String>,
+    jira_project_key: Option<String>,
+    jira_issue_type: String,
+    attribute_include: Vec<String>,
+    attribute_exclude: Vec<String>,
+    child_page_order: String,
+    family_grouping: String,
+    archive_bucket: Option<String>,
+    archive_prefix: String,
+    git_mirror_url: Option<String>,
+    git_mirror_branch: String,
+    git_mirror_username: String,
+    git_mirror_token: Option<String>,
+    git_mirror_path: String,
+    git_mirror_format: String,
+    ca_certificate: Option<String>,
+    danger_accept_invalid_certs: bool,
+    display_locale: Option<String>,
+    display_timezone: Option<String>,
+    item_priority_fields: Option<Vec<String>>,
+    item_skip_fields: Option<Vec<String>>,
+    item_columns: serde_json::Value,
+    attribute_table: Option<String>,
+}
+
+impl From<ConfluenceConfigRecord> for ConfluenceConfigView {
+    fn from(record: ConfluenceConfigRecord) -> Self {
+        let secret =
+            |value: Option<String>| value.filter(|v| !v.is_empty()).map(|v| secrets::redact(&v));
+        Self {
+            id: record.id,
+            akeneo_server_id: record.akeneo_server_id,
+            server_name: record.server_name,
+            base_url: record.base_url,
+            username: record.username,
+            api_token: secret(Some(record.api_token)),
+            space_key: record.space_key,
+            parent_page: record.parent_page,
+            parent_page_id: record.parent_page_id,
+            publish_mode: record.publish_mode,
+            draft_publishing: record.draft_publishing,
+            auth_type: record.auth_type,
+            oauth_client_id: record.oauth_client_id,
+            oauth_client_secret: secret(record.oauth_client_secret),
+            oauth_refresh_token: secret(record.oauth_refresh_token),
+            cloud_id: record.cloud_id,
+            snapshot_title_template: record.snapshot_title_template,
+            family_title_template: record.family_title_template,
+            diff_title_template: record.diff_title_template,
+            version_title_template: record.version_title_template,
+            staging_parent_page: record.staging_parent_page,
+            allowed_space_keys: record.allowed_space_keys,
+            view_restriction_groups: record.view_restriction_groups,
+            view_restriction_users: record.view_restriction_users,
+            edit_restriction_groups: record.edit_restriction_groups,
+            edit_restriction_users: record.edit_restriction_users,
+            jira_project_key: record.jira_project_key,
+            jira_issue_type: record.jira_issue_type,
+            attribute_include: record.attribute_include,
+            attribute_exclude: record.attribute_exclude,
+            child_page_order: record.child_page_order,
+            family_grouping: record.family_grouping,
+            archive_bucket: record.archive_bucket,
+            archive_prefix: record.archive_prefix,
+            git_mirror_url: record.git_mirror_url,
+            git_mirror_branch: record.git_mirror_branch,
+            git_mirror_username: record.git_mirror_username,
+            git_mirror_token: secret(record.git_mirror_token),
+            git_mirror_path: record.git_mirror_path,
+            git_mirror_format: record.git_mirror_format,
+            ca_certificate: record.ca_certificate,
+            danger_accept_invalid_certs: record.danger_accept_invalid_certs,
+            display_locale: record.display_locale,
+            display_timezone: record.display_timezone,
+            item_priority_fields: record.item_priority_fields,
+            item_skip_fields: record.item_skip_fields,
+            item_columns: record.item_columns,
+            attribute_table: record.attribute_table,
+        }
+    }
+}
+
+/// Request body of `POST /api/confluence-configs`.
+#[derive(Deserialize, Serialize)]
+pub struct CreateConfluenceConfig {
+    akeneo_server_id: Uuid,
+    #[serde(flatten)]
+    fields: ConfigFields,
+}
+
+/// The settable columns; in `PATCH /api/confluence-configs/{id}` only the given fields
+/// change. An empty string clears a nullable text column (e.g. `parent_page_id`, the OAuth
+/// fields, a title template) and `null` resets `item_priority_fields` / `item_skip_fields`
+/// to the built-in lists.
+#[derive(Deserialize, Serialize)]
+pub struct ConfigFields {
+    base_url: Option<String>,
+    username: Option<String>,
+    /// A token or a secret reference, e.g. `env:CONFLUENCE_TOKEN_ACME`; not audited.
+    #[serde(skip_serializing)]
+    api_token: Option<String>,
+    space_key: Option<String>,
+    parent_page: Option<String>,
+    parent_page_id: Option<String>,
+    /// `overwrite` or `versioned`.
+    publish_mode: Option<String>,
+    draft_publishing: Option<bool>,
+    /// `api_token` or `oauth`.
+    auth_type: Option<String>,
+    oauth_client_id: Option<String>,
+    #[serde(skip_serializing)]
+    oauth_client_secret: Option<String>,
+    #[serde(skip_serializing)]
+    oauth_refresh_token: Option<String>,
+    cloud_id: Option<String>,
+    snapshot_title_template: Option<String>,
+    family_title_template: Option<String>,
+    diff_title_template: Option<String>,
+    version_title_template: Option<String>,
+    staging_parent_page: Option<String>,
+    allowed_space_keys: Option<Vec<String>>,
+    view_restriction_groups: Option<Vec<String>>,
+    view_restriction_users: Option<Vec<String>>,
+    edit_restriction_groups: Option<Vec<String>>,
+    edit_restriction_users: Option<Vec<String>>,
+    jira_project_key: Option<String>,
+    jira_issue_type: Option<String>,
+    /// Attribute filter rules, e.g. `group:marketing` (see `render_options::AttributeFilter`).
+    attribute_include: Option<Vec<String>>,
+    attribute_exclude: Option<Vec<String>>,
+    /// `label`, `code`, `title` or `none`.
+    child_page_order: Option<String>,
+    /// `none`, `first_letter`, `label_attribute_group` or `prefix`.
+    family_grouping: Option<String>,
+    archive_bucket: Option<String>,
+    archive_prefix: Option<String>,
+    git_mirror_url: Option<String>,
+    git_mirror_branch: Option<String>,
+    git_mirror_username: Option<String>,
+    #[serde(skip_serializing)]
+    git_mirror_token: Option<String>,
+    git_mirror_path: Option<String>,
+    /// `json`, `markdown` or `both`.
+    git_mirror_format: Option<String>,
+    /// A PEM bundle or the path of a PEM file.
+    ca_certificate: Option<String>,
+    danger_accept_invalid_certs: Option<bool>,
+    display_locale: Option<String>,
+    display_timezone: Option<String>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    item_priority_fields: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    item_skip_fields: Option<Option<Vec<String>>>,
+    /// Columns per category, e.g. `{"families": ["code", "label"]}`.
+    item_columns: Option<serde_json::Value>,
+    /// `basic` or `extended`.
+    attribute_table: Option<String>,
+}
+
+/// Tell a field set to `null` (`Some(None)`) from a missing one (`None`).
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// JSON response returned after a config is deleted.
+#[derive(Serialize)]
+struct ConfigDeleted {
+    status: &'static str,
+    id: Uuid,
+}
+
+impl ConfigFields {
+    /// The trimmed changes, or a message for the client when a field is invalid.
+    fn changes(&self) -> Result<ConfluenceConfigChanges<'_>, String> {
+        fn trim(value: &Option<String>) -> Option<&str> {
+            value.as_deref().map(str::trim)
+        }
+        let changes = ConfluenceConfigChanges {
+            base_url: trim(&self.base_url).map(|url| url.trim_end_matches('/')),
+            username: trim(&self.username),
+            api_token: trim(&self.api_token),
+            space_key: trim(&self.space_key),
+            parent_page: trim(&self.parent_page),
+            parent_page_id: trim(&self.parent_page_id),
+            publish_mode: trim(&self.publish_mode),
+            draft_publishing: self.draft_publishing,
+            auth_type: trim(&self.auth_type),
+            oauth_client_id: trim(&self.oauth_client_id),
+            oauth_client_secret: trim(&self.oauth_client_secret),
+            oauth_refresh_token: trim(&self.oauth_refresh_token),
+            cloud_id: trim(&self.cloud_id),
+            snapshot_title_template: trim(&self.snapshot_title_template),
+            family_title_template: trim(&self.family_title_template),
+            diff_title_template: trim(&self.diff_title_template),
+            version_title_template: trim(&self.version_title_template),
+            staging_parent_page: trim(&self.staging_parent_page),
+            allowed_space_keys: self.allowed_space_keys.as_deref(),
+            view_restriction_groups: self.view_restriction_groups.as_deref(),
+            view_restriction_users: self.view_restriction_users.as_deref(),
+            edit_restriction_groups: self.edit_restriction_groups.as_deref(),
+            edit_restriction_users: self.edit_restriction_users.as_deref(),
+            jira_project_key: trim(&self.jira_project_key),
+            jira_issue_type: trim(&self.jira_issue_type),
+            attribute_include: self.attribute_include.as_deref(),
+            attribute_exclude: self.attribute_exclude.as_deref(),
+            child_page_order: trim(&self.child_page_order),
+            family_grouping: trim(&self.family_grouping),
+            archive_bucket: trim(&self.archive_bucket),
+            archive_prefix: trim(&self.archive_prefix),
+            git_mirror_url: trim(&self.git_mirror_url),
+            git_mirror_branch: trim(&self.git_mirror_branch),
+            git_mirror_username: trim(&self.git_mirror_username),
+            git_mirror_token: trim(&self.git_mirror_token),
+            git_mirror_path: trim(&self.git_mirror_path),
+            git_mirror_format: trim(&self.git_mirror_format),
+            ca_certificate: trim(&self.ca_certificate),
+            danger_accept_invalid_certs: self.danger_accept_invalid_certs,
+            display_locale: trim(&self.display_locale),
+            display_timezone: trim(&self.display_timezone),
+            item_priority_fields: self.item_priority_fields.as_ref().map(Option::as_deref),
+            item_skip_fields: self.item_skip_fields.as_ref().map(Option::as_deref),
+            item_columns: self.item_columns.as_ref(),
+            attribute_table: trim(&self.attribute_table),
+        };
+
+        let required = [
+            ("base_url", changes.base_url),
+            ("username", changes.username),
+            ("space_key", changes.space_key),
+            ("jira_issue_type", changes.jira_issue_type),
+            ("child_page_order", changes.child_page_order),
+            ("family_grouping", changes.family_grouping),
+            ("git_mirror_branch", changes.git_mirror_branch),
+            ("git_mirror_username", changes.git_mirror_username),
+            ("git_mirror_format", changes.git_mirror_format),
+        ];
+        for (field, value) in required {
+            if value.is_some_and(str::is_empty) {
+                return Err(format!("{} must not be empty", field));
+            }
+        }
+        if let Some(base_url) = changes.base_url.filter(|url| !servers::is_http_url(url)) {
+            return Err(format!("base_url '{}' is not an http(s) URL", base_url));
+        }
+        // The git mirror sends its token with every request, so only over TLS
+        let is_https = |url: &str| {
+            reqwest::Url::parse(url).is_ok_and(|url| url.scheme() == "https" && url.has_host())
+        };
+        if let Some(url) = changes
+            .git_mirror_url
+            .filter(|url| !url.is_empty() && !is_https(url))
+        {
+            return Err(format!("git_mirror_url '{}' is not an https URL", url));
+        }
+        if let Some(id) = changes
+            .parent_page_id
+            .filter(|id| !id.chars().all(|c| c.is_ascii_digit()))
+        {
+            return Err(format!("parent_page_id '{}' is not a page ID", id));
+        }
+        if let Some(mode) = changes
+            .publish_mode
+            .filter(|mode| !matches!(*mode, "overwrite" | "versioned"))
+        {
+            return Err(format!(
+                "Unknown publish_mode '{}' (overwrite or versioned)",
+                mode
+            ));
+        }
+        if let Some(auth_type) = changes
+            .auth_type
+            .filter(|auth_type| !matches!(*auth_type, "api_token" | "oauth"))
+        {
+            return Err(format!(
+                "Unknown auth_type '{}' (api_token or oauth)",
+                auth_type
+            ));
+        }
+        if let Some(table) = changes
+            .attribute_table
+            .filter(|table| !matches!(*table, "" | "basic" | "extended"))
+        {
+            return Err(format!(
+                "Unknown attribute_table '{}' (basic or extended)",
+                table
+            ));
+        }
+        // The rules and formats the pages are rendered with fail the publish when invalid
+        let rules = |rules: Option<&[String]>| rules.unwrap_or_default().to_vec();
+        AttributeFilter::parse(&rules(changes.attribute_include), &rules(changes.attribute_exclude))
+            .map_err(|e| format!("{:#}", e))?;
+        DisplayFormat::parse(changes.display_locale, changes.display_timezone)
+            .map_err(|e| format!("{:#}", e))?;
+        if let Some(item_columns) = changes.item_columns {
+            ItemProperties::parse_columns(item_columns).map_err(|e| format!("{:#}", e))?;
+        }
+        Ok(changes)
+    }
+}
+
+/// Check that a new config has the columns without a default, and an API token unless it
+/// uses OAuth (whose fields the schema checks).
+fn require_new_fields(
+    changes: ConfluenceConfigChanges<'_>,
+) -> Result<ConfluenceConfigChanges<'_>, String> {
+    let uses_api_token = changes.auth_type.unwrap_or("api_token") == "api_token";
+    let missing = [
+        ("base_url", changes.base_url.is_none()),
+        ("username", changes.username.is_none()),
+        ("space_key", changes.space_key.is_none()),
+        (
+            "api_token",
+            uses_api_token && changes.api_token.is_none_or(str::is_empty),
+        ),
+    ];
+    match missing.into_iter().find(|(_, missing)| *missing) {
+        Some((field, _)) => Err(format!("{} is required", field)),
+        None => Ok(changes),
+    }
+}
+
+/// GET /api/confluence-configs (admin)
+///
+/// Lists the Confluence targets, by server name and space.
+pub async fn handle_list_configs(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    match db::fetch_confluence_config_records(&state.pool, None).await {
+        Ok(records) => {
+            let views: Vec<ConfluenceConfigView> = records.into_iter().map(Into::into).collect();
+            (StatusCode::OK, Json(views)).into_response()
+        }
+        Err(e) => internal_error("list Confluence configurations", e),
+    }
+}
+
+/// GET /api/confluence-configs/:id (admin)
+pub async fn handle_get_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(config_id): Path<Uuid>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    config_response(&state, config_id, StatusCode::OK).await
+}
+
+/// POST /api/confluence-configs (admin)
+///
+/// Adds the Confluence target of an Akeneo server; answers 201 with it. 409 when the
+/// server already has one.
+pub async fn handle_create_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Actor(actor): Actor,
+    Json(request): Json<CreateConfluenceConfig>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    let changes = match request.fields.changes().and_then(require_new_fields) {
+        Ok(changes) => changes,
+        Err(message) => return bad_request(message),
+    };
+
+    let server_id = request.akeneo_server_id;
+    match db::fetch_confluence_targets(&state.pool, Some(server_id)).await {
+        Ok(targets) if !targets.is_empty() => {
+            let message = format!(
+                "Akeneo server {} already has a Confluence configuration: {}",
+                server_id, targets[0].id
+            );
+            return (StatusCode::CONFLICT, Json(ErrorResponse::new(message))).into_response();
+        }
+        Ok(_) => {}
+        Err(e) => return internal_error("load Confluence configurations", e),
+    }
+    match db::fetch_akeneo_server_records(&state.pool, Some(server_id)).await {
+        Ok(servers) if servers.is_empty() => {
+            return bad_request(format!("Akeneo server not found: {}", server_id));
+        }
+        Ok(_) => {}
+        Err(e) => return internal_error("load Akeneo server", e),
+    }
+
+    let created = db::insert_confluence_config(&state.pool, server_id, &changes).await;
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "POST /api/confluence-configs",
+        target_id: *created.as_ref().unwrap_or(&Uuid::nil()),
+        parameters: audit::parameters(&request),
+    };
+    let outcome = match &created {
+        Ok(_) => audit::Outcome::Ok(Vec::new()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match created {
+        Ok(config_id) => {
+            info!(
+                "Added Confluence configuration {} for Akeneo server {}",
+                config_id, server_id
+            );
+            forget_target(&state, server_id);
+            config_response(&state, config_id, StatusCode::CREATED).await
+        }
+        Err(e) if db::check_violation(&e).is_some() => constraint_violated(&e),
+        Err(e) => internal_error("create Confluence configuration", e),
+    }
+}
+
+/// PATCH /api/confluence-configs/:id (admin)
+///
+/// Changes the given fields, e.g. `{"api_token": "env:CONFLUENCE_TOKEN_ACME"}`. Switching
+/// to `oauth` needs the OAuth client ID and secret, a refresh token and the cloud ID.
+pub async fn handle_update_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Actor(actor): Actor,
+    Path(config_id): Path<Uuid>,
+    Json(request): Json<ConfigFields>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    let changes = match request.changes() {
+        Ok(changes) => changes,
+        Err(message) => return bad_request(message),
+    };
+    let record = match fetch_record(&state, config_id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+
+    let updated = db::update_confluence_config(&state.pool, config_id, &changes).await;
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "PATCH /api/confluence-configs/{id}",
+        target_id: config_id,
+        parameters: audit::parameters(&request),
+    };
+    let outcome = match &updated {
+        Ok(()) => audit::Outcome::Ok(Vec::new()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match updated {
+        Ok(()) => {
+            info!("Updated Confluence configuration {}", config_id);
+            forget_target(&state, record.akeneo_server_id);
+            config_response(&state, config_id, StatusCode::OK).await
+        }
+        Err(e) if db::is_not_found(&e) => not_found(config_id),
+        Err(e) if db::check_violation(&e).is_some() => constraint_violated(&e),
+        Err(e) => internal_error("update Confluence configuration", e),
+    }
+}
+
+/// DELETE /api/confluence-configs/:id (admin)
+///
+/// Removes a Confluence target; its server cannot publish until it gets a new one.
+pub async fn handle_delete_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Actor(actor): Actor,
+    Path(config_id): Path<Uuid>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    let record = match fetch_record(&state, config_id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+
+    let deleted = db::delete_confluence_config(&state.pool, config_id).await;
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "DELETE /api/confluence-configs/{id}",
+        target_id: config_id,
+        parameters: serde_json::json!({
+            "akeneo_server_id": record.akeneo_server_id,
+            "space_key": record.space_key,
+        }),
+    };
+    let outcome = match &deleted {
+        Ok(()) => audit::Outcome::Ok(Vec::new()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match deleted {
+        Ok(()) => {
+            info!("Deleted Confluence configuration {}", config_id);
+            forget_target(&state, record.akeneo_server_id);
+            (
+                StatusCode::OK,
+                Json(ConfigDeleted {
+                    status: "ok",
+                    id: config_id,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) if db::is_not_found(&e) => not_found(config_id),
+        Err(e) => internal_error("delete Confluence configuration", e),
+    }
+}
+
+/// POST /api/confluence-configs/:id/test (admin)
+///
+/// Runs the pre-flight checklist of `GET /api/server/{id}/confluence/check` against the
+/// target, e.g. right after adding it.
+pub async fn handle_test_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(config_id): Path<Uuid>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    match fetch_record(&state, config_id).await {
+        Ok(record) => preflight::check_response(&state, record.akeneo_server_id).await,
+        Err(response) => response,
+    }
+}
+
+async fn fetch_record(
+    state: &AppState,
+    config_id: Uuid,
+) -> Result<ConfluenceConfigRecord, Response> {
+    match db::fetch_confluence_config_records(&state.pool, Some(config_id)).await {
+        Ok(mut records) if !records.is_empty() => Ok(records.remove(0)),
+        Ok(_) => Err(not_found(config_id)),
+        Err(e) => Err(internal_error("load Confluence configuration", e)),
+    }
+}
+
+/// One config's view, with `status` as the response status; 404 when it does not exist.
+async fn config_response(state: &AppState, config_id: Uuid, status: StatusCode) -> Response {
+    match fetch_record(state, config_id).await {
+        Ok(record) => (status, Json(ConfluenceConfigView::from(record))).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Drop the server's cached target and Confluence clients, so the next publish uses the
+/// stored config.
+fn forget_target(state: &AppState, akeneo_server_id: Uuid) {
+    state.confluence_configs.invalidate(Some(akeneo_server_id));
+    state.clients.invalidate(akeneo_server_id);
+}
+
+/// 400 for a config the schema rejects; the OAuth check is the one the request validation
+/// cannot catch, as it spans stored and given fields.
+fn constraint_violated(e: &anyhow::Error) -> Response {
+    match db::check_violation(e) {
+        Some("confluence_config_oauth_check") => bad_request(
+            "auth_type oauth needs oauth_client_id, oauth_client_secret, oauth_refresh_token \
+             and cloud_id"
+                .to_string(),
+        ),
+        constraint => bad_request(format!(
+            "Invalid Confluence configuration: violates {}",
+            constraint.unwrap_or("a check constraint")
+        )),
+    }
+}
+
+fn bad_request(message: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(message))).into_response()
+}
+
+fn not_found(config_id: Uuid) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new(format!(
+            "Confluence configuration not found: {}",
+            config_id
+        ))),
+    )
+        .into_response()
+}
+
+fn internal_error(action: &str, e: anyhow::Error) -> Response {
+    error!("Failed to {}: {:#}", action, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(format!("{:#}", e))),
+    )
+        .into_response()
+}
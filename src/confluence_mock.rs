@@ -0,0 +1,477 @@
+use anyhow::{bail, Context, Result};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::info;
+
+/// File-backed stand-in for the Confluence REST API, used with `CONFLUENCE_MODE=mock`:
+/// `ConfluenceClient::send` hands it every request instead of sending it, so publishes make
+/// their usual requests against pages kept on disk. Each page is `pages/{id}.json` (title,
+/// space, parent, version, properties) with its storage format body in `pages/{id}.html`;
+/// attachments go to `attachments/{page id}/{filename}`. Pages survive restarts and are
+/// shared by every target, whatever its base URL. Deleted pages stay on disk as `trashed`.
+/// Authentication, rate limits and restrictions are not simulated.
+pub struct MockConfluence {
+    dir: PathBuf,
+    pages: Mutex<BTreeMap<u64, MockPage>>,
+}
+
+/// A page or blog post held by the mock.
+#[derive(Serialize, Deserialize, Clone)]
+struct MockPage {
+    id: u64,
+    title: String,
+    space_key: String,
+    #[serde(rename = "type")]
+    content_type: String,
+    /// `current`, or `trashed` once deleted.
+    status: String,
+    parent_id: Option<u64>,
+    /// Order among its siblings (Confluence's page-tree order).
+    position: u64,
+    version: u64,
+    properties: BTreeMap<String, MockProperty>,
+    /// Kept in `{id}.html`, next to the page.
+    #[serde(skip)]
+    body: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct MockProperty {
+    value: Value,
+    version: u64,
+}
+
+impl MockPage {
+    fn is_current(&self) -> bool {
+        self.status == "current"
+    }
+}
+
+type Pages = BTreeMap<u64, MockPage>;
+
+impl MockConfluence {
+    /// Open the directory holding the mock's pages, creating it if needed, and load them.
+    pub fn open(dir: &str) -> Result<Self> {
+        let dir = PathBuf::from(dir);
+        let pages_dir = dir.join("pages");
+        std::fs::create_dir_all(&pages_dir).with_context(|| {
+            format!("Failed to create the Confluence mock directory {}", pages_dir.display())
+        })?;
+
+        let mut pages = Pages::new();
+        for entry in std::fs::read_dir(&pages_dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let text = std::fs::read_to_string(&path)?;
+            let mut page: MockPage = serde_json::from_str(&text)
+                .with_context(|| format!("Invalid Confluence mock page {}", path.display()))?;
+            page.body = std::fs::read_to_string(path.with_extension("html")).unwrap_or_default();
+            pages.insert(page.id, page);
+        }
+        info!(
+            "Confluence mock mode: {} pages in {}",
+            pages.values().filter(|p| p.is_current()).count(),
+            dir.display()
+        );
+        Ok(Self {
+            dir,
+            pages: Mutex::new(pages),
+        })
+    }
+
+    /// Answer a request the way Confluence Cloud's v1 REST API would.
+    pub fn handle(&self, request: Request) -> Result<Response> {
+        let path = request.url().path().to_string();
+        let Some((_, route)) = path.split_once("/rest/api/") else {
+            return Ok(respond(
+                StatusCode::NOT_FOUND,
+                json!({ "message": "Not a Confluence REST API URL" }),
+            ));
+        };
+        let query: HashMap<String, String> = request.url().query_pairs().into_owned().collect();
+        let body: Value = match request.body().and_then(|b| b.as_bytes()) {
+            Some(bytes) if !bytes.is_empty() => serde_json::from_slice(bytes)
+                .context("Invalid request body for the Confluence mock")?,
+            _ => Value::Null,
+        };
+        let segments: Vec<&str> = route.trim_end_matches('/').split('/').collect();
+
+        let mut pages = self.pages.lock().unwrap();
+        let page_id = segments.get(1).and_then(|id| id.parse::<u64>().ok());
+        let response = match (request.method().as_str(), segments.as_slice(), page_id) {
+            ("GET", ["user", "current"], _) => respond(
+                StatusCode::OK,
+                json!({
+                    "type": "known",
+                    "accountId": "mock-publisher",
+                    "displayName": "Confluence mock",
+                }),
+            ),
+            ("GET", ["space", key], _) => respond(
+                StatusCode::OK,
+                json!({
+                    "key": key,
+                    "name": key,
+                    "operations": [
+                        { "operation": "read", "targetType": "space" },
+                        { "operation": "create", "targetType": "page" },
+                    ],
+                }),
+            ),
+            ("GET", ["content"], _) => search(&pages, &query),
+            ("POST", ["content"], _) => self.create(&mut pages, &body)?,
+            (_, ["content", ..], Some(id)) if !current(&pages, id) => respond(
+                StatusCode::NOT_FOUND,
+                json!({ "message": format!("No content with id {}", id) }),
+            ),
+            ("GET", ["content", _], Some(id)) => respond(StatusCode::OK, page_json(&pages, id)),
+            ("PUT", ["content", _], Some(id)) => self.update(&mut pages, id, &body)?,
+            ("DELETE", ["content", _], Some(id)) => self.delete(&mut pages, id)?,
+            ("GET", ["content", _, "descendant", "page"], Some(id)) => {
+                let mut descendants = Vec::new();
+                collect_descendants(&pages, id, &mut descendants);
+                listing(descendants.into_iter().map(|id| page_json(&pages, id)).collect())
+            }
+            ("GET", ["content", _, "child", "page"], Some(id)) => listing(
+                children(&pages, id)
+                    .into_iter()
+                    .map(|id| page_json(&pages, id))
+                    .collect(),
+            ),
+            ("GET", ["content", _, "property", key], Some(id)) => {
+                match pages[&id].properties.get(*key) {
+                    Some(property) => respond(
+                        StatusCode::OK,
+                        json!({
+                            "key": key,
+                            "value": property.value,
+                            "version": { "number": property.version },
+                        }),
+                    ),
+                    None => respond(
+                        StatusCode::NOT_FOUND,
+                        json!({ "message": format!("No property '{}'", key) }),
+                    ),
+                }
+            }
+            ("POST", ["content", _, "property"], Some(id)) => {
+                let key = body["key"].as_str().unwrap_or_default();
+                self.set_property(&mut pages, id, key, &body, 1)?
+            }
+            ("PUT", ["content", _, "property", key], Some(id)) => {
+                let current = pages[&id].properties.get(*key).map_or(0, |p| p.version);
+                self.set_property(&mut pages, id, key, &body, current + 1)?
+            }
+            ("PUT", ["content", _, "restriction"], Some(_)) => {
+                respond(StatusCode::OK, json!({ "results": [] }))
+            }
+            ("PUT", ["content", _, "move", position, target], Some(id)) => {
+                match target.parse::<u64>().ok().filter(|t| current(&pages, *t)) {
+                    Some(target) => self.move_page(&mut pages, id, position, target)?,
+                    None => respond(
+                        StatusCode::NOT_FOUND,
+                        json!({ "message": format!("No content with id {}", target) }),
+                    ),
+                }
+            }
+            (method, _, _) => {
+                let message =
+                    format!("{} /rest/api/{} is not supported in mock mode", method, route);
+                respond(StatusCode::NOT_FOUND, json!({ "message": message }))
+            }
+        };
+        Ok(response)
+    }
+
+    /// Keep an attachment uploaded to a page (`upload_attachment`, whose multipart body
+    /// `handle` cannot read), replacing an earlier one with the same filename.
+    pub fn save_attachment(&self, page_id: &str, filename: &str, data: &[u8]) -> Result<()> {
+        let known = page_id
+            .parse::<u64>()
+            .is_ok_and(|id| current(&self.pages.lock().unwrap(), id));
+        if !known {
+            bail!("No content with id {} in the Confluence mock", page_id);
+        }
+        let Some(name) = Path::new(filename).file_name() else {
+            bail!("Invalid attachment filename '{}'", filename);
+        };
+        let dir = self.dir.join("attachments").join(page_id);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(name), data)
+            .with_context(|| format!("Failed to write mock attachment '{}'", filename))
+    }
+
+    fn create(&self, pages: &mut Pages, body: &Value) -> Result<Response> {
+        let title = body["title"].as_str().unwrap_or_default();
+        let space_key = body["space"]["key"].as_str().unwrap_or_default();
+        let content_type = body["type"].as_str().unwrap_or("page");
+        let parent_id = body["ancestors"]
+            .as_array()
+            .and_then(|ancestors| ancestors.last())
+            .map(|ancestor| match &ancestor["id"] {
+                Value::String(id) => id.parse::<u64>().ok(),
+                id => id.as_u64(),
+            });
+        if let Some(parent) = parent_id
+            && !parent.is_some_and(|id| current(pages, id))
+        {
+            return Ok(respond(
+                StatusCode::BAD_REQUEST,
+                json!({ "message": "The parent page does not exist" }),
+            ));
+        }
+        let duplicate = pages.values().any(|p| {
+            p.is_current()
+                && p.title == title
+                && p.space_key == space_key
+                && p.content_type == content_type
+        });
+        if duplicate {
+            return Ok(respond(
+                StatusCode::BAD_REQUEST,
+                json!({ "message": "A page with this title already exists" }),
+            ));
+        }
+
+        let id = pages.last_key_value().map_or(1, |(id, _)| id + 1);
+        let page = MockPage {
+            id,
+            title: title.to_string(),
+            space_key: space_key.to_string(),
+            content_type: content_type.to_string(),
+            status: "current".to_string(),
+            parent_id: parent_id.flatten(),
+            position: id,
+            version: 1,
+            properties: BTreeMap::new(),
+            body: storage_value(body),
+        };
+        self.save(&page, true)?;
+        pages.insert(id, page);
+        Ok(respond(StatusCode::OK, page_json(pages, id)))
+    }
+
+    fn update(&self, pages: &mut Pages, id: u64, body: &Value) -> Result<Response> {
+        let page = pages.get_mut(&id).expect("checked by handle");
+        let number = body["version"]["number"].as_u64().unwrap_or_default();
+        if number != page.version + 1 {
+            return Ok(respond(
+                StatusCode::CONFLICT,
+                json!({
+                    "message": format!(
+                        "Version must be incremented on update. Current version is: {}",
+                        page.version
+                    ),
+                }),
+            ));
+        }
+        if let Some(title) = body["title"].as_str() {
+            page.title = title.to_string();
+        }
+        page.body = storage_value(body);
+        page.version = number;
+        self.save(page, true)?;
+        Ok(respond(StatusCode::OK, page_json(pages, id)))
+    }
+
+    /// Trash a page; like Confluence, its children move up to its parent.
+    fn delete(&self, pages: &mut Pages, id: u64) -> Result<Response> {
+        let parent_id = pages[&id].parent_id;
+        for child in children(pages, id) {
+            let child = pages.get_mut(&child).expect("listed by children");
+            child.parent_id = parent_id;
+            self.save(child, false)?;
+        }
+        let page = pages.get_mut(&id).expect("checked by handle");
+        page.status = "trashed".to_string();
+        self.save(page, false)?;
+        Ok(respond(StatusCode::NO_CONTENT, Value::Null))
+    }
+
+    fn set_property(
+        &self,
+        pages: &mut Pages,
+        id: u64,
+        key: &str,
+        body: &Value,
+        version: u64,
+    ) -> Result<Response> {
+        let page = pages.get_mut(&id).expect("checked by handle");
+        let current = page.properties.get(key).map_or(0, |p| p.version);
+        if version != current + 1 || body["version"]["number"].as_u64().unwrap_or(1) != version {
+            return Ok(respond(
+                StatusCode::CONFLICT,
+                json!({ "message": format!("Property '{}' is at version {}", key, current) }),
+            ));
+        }
+        let value = body["value"].clone();
+        page.properties.insert(
+            key.to_string(),
+            MockProperty {
+                value: value.clone(),
+                version,
+            },
+        );
+        self.save(page, false)?;
+        Ok(respond(
+            StatusCode::OK,
+            json!({ "key": key, "value": value, "version": { "number": version } }),
+        ))
+    }
+
+    /// Move a page `before` or `after` a sibling, or `append` it to a new parent.
+    fn move_page(
+        &self,
+        pages: &mut Pages,
+        id: u64,
+        position: &str,
+        target: u64,
+    ) -> Result<Response> {
+        let parent_id = match position {
+            "append" => Some(target),
+            "before" | "after" => pages[&target].parent_id,
+            _ => {
+                return Ok(respond(
+                    StatusCode::BAD_REQUEST,
+                    json!({ "message": format!("Unknown move position '{}'", position) }),
+                ));
+            }
+        };
+
+        let mut siblings: Vec<u64> = match parent_id {
+            Some(parent) => children(pages, parent),
+            None => top_level(pages, &pages[&target].space_key),
+        };
+        siblings.retain(|sibling| *sibling != id);
+        let index = match position {
+            "append" => siblings.len(),
+            _ => {
+                let target_index = siblings.iter().position(|s| *s == target).unwrap_or(0);
+                target_index + usize::from(position == "after")
+            }
+        };
+        siblings.insert(index, id);
+
+        pages.get_mut(&id).expect("checked by handle").parent_id = parent_id;
+        for (position, sibling) in siblings.into_iter().enumerate() {
+            let page = pages.get_mut(&sibling).expect("listed as a sibling");
+            page.position = position as u64;
+            self.save(page, false)?;
+        }
+        Ok(respond(StatusCode::OK, json!({ "pageId": id.to_string() })))
+    }
+
+    /// Write a page's JSON file, and its body with `with_body`.
+    fn save(&self, page: &MockPage, with_body: bool) -> Result<()> {
+        let path = self.dir.join("pages").join(format!("{}.json", page.id));
+        std::fs::write(&path, serde_json::to_string_pretty(page)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        if with_body {
+            std::fs::write(path.with_extension("html"), &page.body)
+                .with_context(|| format!("Failed to write the body of mock page {}", page.id))?;
+        }
+        Ok(())
+    }
+}
+
+fn current(pages: &Pages, id: u64) -> bool {
+    pages.get(&id).is_some_and(MockPage::is_current)
+}
+
+/// The current children of a page, in page-tree order.
+fn children(pages: &Pages, parent_id: u64) -> Vec<u64> {
+    sorted(pages, |p| p.parent_id == Some(parent_id))
+}
+
+/// The current pages of a space that have no parent, in page-tree order.
+fn top_level(pages: &Pages, space_key: &str) -> Vec<u64> {
+    sorted(pages, |p| p.parent_id.is_none() && p.space_key == space_key)
+}
+
+fn sorted(pages: &Pages, filter: impl Fn(&MockPage) -> bool) -> Vec<u64> {
+    let mut matching: Vec<&MockPage> =
+        pages.values().filter(|p| p.is_current() && filter(p)).collect();
+    matching.sort_by_key(|p| (p.position, p.id));
+    matching.into_iter().map(|p| p.id).collect()
+}
+
+/// Every page under a page, depth first in page-tree order.
+fn collect_descendants(pages: &Pages, id: u64, out: &mut Vec<u64>) {
+    for child in children(pages, id) {
+        out.push(child);
+        collect_descendants(pages, child, out);
+    }
+}
+
+/// `GET /rest/api/content?title=..&spaceKey=..&type=..`: the current pages matching the
+/// given filters.
+fn search(pages: &Pages, query: &HashMap<String, String>) -> Response {
+    let param = |key: &str| query.get(key).map(String::as_str);
+    let content_type = param("type").unwrap_or("page");
+    let results = pages
+        .values()
+        .filter(|p| p.is_current() && p.content_type == content_type)
+        .filter(|p| param("title").is_none_or(|title| p.title == title))
+        .filter(|p| param("spaceKey").is_none_or(|key| p.space_key == key))
+        .map(|p| page_json(pages, p.id))
+        .collect();
+    listing(results)
+}
+
+/// A complete (unpaginated) listing: `_links` without `next`.
+fn listing(results: Vec<Value>) -> Response {
+    let size = results.len();
+    respond(
+        StatusCode::OK,
+        json!({ "results": results, "size": size, "_links": {} }),
+    )
+}
+
+/// A page as the content endpoints return it, with every expansion the client asks for.
+fn page_json(pages: &Pages, id: u64) -> Value {
+    let page = &pages[&id];
+    let mut ancestors = Vec::new();
+    let mut parent = page.parent_id;
+    while let Some(parent_id) = parent {
+        ancestors.insert(0, json!({ "id": parent_id.to_string() }));
+        parent = pages.get(&parent_id).and_then(|p| p.parent_id);
+    }
+    json!({
+        "id": page.id.to_string(),
+        "type": page.content_type,
+        "status": page.status,
+        "title": page.title,
+        "space": { "key": page.space_key },
+        "version": { "number": page.version },
+        "ancestors": ancestors,
+        "body": { "storage": { "value": page.body, "representation": "storage" } },
+        "_links": { "webui": format!("/spaces/{}/pages/{}", page.space_key, page.id) },
+    })
+}
+
+fn storage_value(body: &Value) -> String {
+    body["body"]["storage"]["value"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn respond(status: StatusCode, body: Value) -> Response {
+    let body = match body {
+        Value::Null => String::new(),
+        body => body.to_string(),
+    };
+    let response = axum::http::Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body)
+        .expect("a status and a header are a valid response");
+    Response::from(response)
+}
@@ -0,0 +1,180 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tracing::info;
+
+/// Prefix of encrypted values; anything else is treated as legacy plaintext.
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+static CIPHER: OnceLock<Option<TokenCipher>> = OnceLock::new();
+
+/// Envelope encryption for secrets stored in the database (Confluence API tokens).
+///
+/// Each value is encrypted with its own random AES-256-GCM data key; the data key is
+/// encrypted ("wrapped") with the key-encryption key (KEK) from the environment and
+/// stored alongside the ciphertext as
+/// `enc:v1:<kek id>:<base64 nonce+wrapped data key>:<base64 nonce+ciphertext>`.
+/// Rotating the KEK only requires re-wrapping, and retired KEKs can stay configured
+/// for decryption until every value has been re-encrypted.
+pub struct TokenCipher {
+    current_id: String,
+    keys: HashMap<String, Key<Aes256Gcm>>,
+}
+
+impl TokenCipher {
+    /// Load the KEKs from the environment:
+    /// - `TOKEN_ENCRYPTION_KEY` — base64-encoded 32-byte key used to encrypt
+    /// - `TOKEN_ENCRYPTION_KEY_ID` — identifier stored with each value (default `default`)
+    /// - `TOKEN_ENCRYPTION_OLD_KEYS` — retired keys still accepted for decryption,
+    ///   as comma-separated `id:base64key` pairs
+    ///
+    /// Returns `None` when no key is configured (tokens are then expected in plaintext).
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(current) = std::env::var("TOKEN_ENCRYPTION_KEY") else {
+            return Ok(None);
+        };
+        let current_id = std::env::var("TOKEN_ENCRYPTION_KEY_ID")
+            .ok()
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| "default".to_string());
+
+        let mut keys = HashMap::new();
+        if let Ok(old_keys) = std::env::var("TOKEN_ENCRYPTION_OLD_KEYS") {
+            for entry in old_keys.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let (id, key) = entry
+                    .split_once(':')
+                    .context("TOKEN_ENCRYPTION_OLD_KEYS entries must be id:base64key")?;
+                keys.insert(id.to_string(), parse_key(key).with_context(|| {
+                    format!("Invalid key '{}' in TOKEN_ENCRYPTION_OLD_KEYS", id)
+                })?);
+            }
+        }
+        keys.insert(
+            current_id.clone(),
+            parse_key(&current).context("Invalid TOKEN_ENCRYPTION_KEY")?,
+        );
+
+        Ok(Some(Self { current_id, keys }))
+    }
+
+    /// Encrypt a value with a fresh data key wrapped by the current KEK.
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let kek = Aes256Gcm::new(&self.keys[&self.current_id]);
+        let data_key = Aes256Gcm::generate_key(OsRng);
+
+        let wrapped = seal(&kek, data_key.as_slice())?;
+        let ciphertext = seal(&Aes256Gcm::new(&data_key), plaintext.as_bytes())?;
+
+        Ok(format!(
+            "{}{}:{}:{}",
+            PREFIX,
+            self.current_id,
+            BASE64.encode(wrapped),
+            BASE64.encode(ciphertext)
+        ))
+    }
+
+    /// Decrypt a value produced by `encrypt`, with the current or a retired KEK.
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let rest = value
+            .strip_prefix(PREFIX)
+            .context("Value is not encrypted")?;
+        let mut parts = rest.splitn(3, ':');
+        let (Some(key_id), Some(wrapped), Some(ciphertext)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            bail!("Malformed encrypted value");
+        };
+
+        let kek = self
+            .keys
+            .get(key_id)
+            .with_context(|| format!("Unknown encryption key id '{}'", key_id))?;
+        let data_key = open(&Aes256Gcm::new(kek), &BASE64.decode(wrapped)?)
+            .context("Failed to unwrap data key (wrong key?)")?;
+        let data_key = Key::<Aes256Gcm>::from_exact_iter(data_key)
+            .context("Unwrapped data key has the wrong length")?;
+        let plaintext = open(&Aes256Gcm::new(&data_key), &BASE64.decode(ciphertext)?)
+            .context("Failed to decrypt value")?;
+
+        String::from_utf8(plaintext).context("Decrypted value is not UTF-8")
+    }
+
+    /// Whether a value is encrypted with anything other than the current KEK
+    /// (i.e. should be re-encrypted).
+    pub fn needs_reencryption(&self, value: &str) -> bool {
+        match value.strip_prefix(PREFIX) {
+            Some(rest) => rest.split(':').next() != Some(self.current_id.as_str()),
+            None => true,
+        }
+    }
+}
+
+/// Load the process-wide cipher from the environment. Called once at startup.
+pub fn init() -> Result<()> {
+    let cipher = TokenCipher::from_env()?;
+    if let Some(c) = &cipher {
+        info!(
+            "Token encryption enabled (key id '{}', {} key(s) loaded)",
+            c.current_id,
+            c.keys.len()
+        );
+    }
+    CIPHER
+        .set(cipher)
+        .map_err(|_| anyhow!("Token encryption already initialized"))
+}
+
+/// The process-wide cipher, if a key is configured.
+pub fn cipher() -> Option<&'static TokenCipher> {
+    CIPHER.get().and_then(Option::as_ref)
+}
+
+/// Whether a stored value is encrypted (as opposed to legacy plaintext).
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// Turn a stored secret into its plaintext: decrypts encrypted values and passes
+/// legacy plaintext values through unchanged.
+pub fn reveal(stored: &str) -> Result<String> {
+    if !is_encrypted(stored) {
+        return Ok(stored.to_string());
+    }
+    cipher()
+        .context("Value is encrypted but TOKEN_ENCRYPTION_KEY is not set")?
+        .decrypt(stored)
+}
+
+fn parse_key(encoded: &str) -> Result<Key<Aes256Gcm>> {
+    let bytes = BASE64.decode(encoded.trim()).context("Key is not valid base64")?;
+    Key::<Aes256Gcm>::from_exact_iter(bytes).context("Key must be exactly 32 bytes")
+}
+
+/// Encrypt with a random nonce; returns nonce || ciphertext.
+fn seal(cipher: &Aes256Gcm, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let mut out = nonce.to_vec();
+    out.extend(
+        cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow!("Encryption failed"))?,
+    );
+    Ok(out)
+}
+
+/// Decrypt nonce || ciphertext.
+fn open(cipher: &Aes256Gcm, data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < NONCE_LEN {
+        bail!("Encrypted value is truncated");
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Authentication tag mismatch"))
+}
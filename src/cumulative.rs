@@ -0,0 +1,425 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+use crate::db::{self, DiffRow, SnapshotMeta};
+use crate::diff;
+use crate::render_options::IdentityKeys;
+
+/// Why a date range cannot be summarised: too few snapshots, or no chain of stored diffs
+/// from its first snapshot to its last. Handlers report it as 422.
+#[derive(Debug)]
+pub struct NoDiffChain(pub String);
+
+impl std::fmt::Display for NoDiffChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NoDiffChain {}
+
+/// Whether an error is (or wraps) a `NoDiffChain`.
+pub fn is_no_diff_chain(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<NoDiffChain>().is_some()
+}
+
+/// The net change between the first and last snapshot of a date range, composed from the
+/// stored diffs chaining them.
+pub struct CumulativeDiff {
+    pub before: SnapshotMeta,
+    pub after: SnapshotMeta,
+    /// Stored diffs composed, in chain order.
+    pub diff_ids: Vec<Uuid>,
+    /// The composed diff, in the shape of `diff.data`.
+    pub data: Value,
+}
+
+/// Compose the stored diffs from an Akeneo server's first snapshot completed in
+/// `[from, to)` to its last one into a single diff, matching items by `identity`.
+pub async fn load(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    identity: &IdentityKeys,
+) -> Result<CumulativeDiff> {
+    let mut snapshots = db::fetch_snapshots_in_range(pool, akeneo_server_id, from, to).await?;
+    if snapshots.len() < 2 {
+        return Err(NoDiffChain(format!(
+            "The range has {} snapshot(s); a cumulative diff needs at least two",
+            snapshots.len()
+        ))
+        .into());
+    }
+
+    let ids: Vec<Uuid> = snapshots.iter().map(|s| s.id).collect();
+    let diffs = db::fetch_diffs_among(pool, &ids).await?;
+    let chain = resolve_chain(&snapshots, &diffs)?;
+
+    let data = compose(chain.iter().map(|&i| &diffs[i].data), identity);
+    let after = snapshots.pop().expect("at least two snapshots");
+    let before = snapshots.swap_remove(0);
+    Ok(CumulativeDiff {
+        before,
+        after,
+        diff_ids: chain.iter().map(|&i| diffs[i].id).collect(),
+        data,
+    })
+}
+
+/// Pick stored diffs leading from the first snapshot to the last, each from one snapshot to
+/// a later one. Steps are kept as small as possible, so every intermediate snapshot with a
+/// diff on the way is part of the chain. Returns indexes into `diffs`.
+fn resolve_chain(snapshots: &[SnapshotMeta], diffs: &[DiffRow]) -> Result<Vec<usize>> {
+    let position: HashMap<Uuid, usize> =
+        snapshots.iter().enumerate().map(|(i, s)| (s.id, i)).collect();
+    let last = snapshots.len() - 1;
+
+    // Forward diffs out of each snapshot, nearest target first
+    let mut outgoing: Vec<Vec<(usize, usize)>> = vec![Vec::new(); snapshots.len()];
+    for (d, diff) in diffs.iter().enumerate() {
+        let (Some(&from), Some(&to)) = (
+            position.get(&diff.snapshot_before_id),
+            position.get(&diff.snapshot_after_id),
+        ) else {
+            continue;
+        };
+        if from < to {
+            outgoing[from].push((to, d));
+        }
+    }
+    for targets in &mut outgoing {
+        targets.sort_unstable();
+    }
+
+    // Which snapshots the last one can be reached from
+    let mut leads_to_last = vec![false; snapshots.len()];
+    leads_to_last[last] = true;
+    for i in (0..last).rev() {
+        leads_to_last[i] = outgoing[i].iter().any(|&(to, _)| leads_to_last[to]);
+    }
+
+    if !leads_to_last[0] {
+        // Name the latest snapshot the stored diffs do reach, to point at the gap
+        let mut reached = vec![false; snapshots.len()];
+        reached[0] = true;
+        for i in 0..snapshots.len() {
+            if reached[i] {
+                for &(to, _) in &outgoing[i] {
+                    reached[to] = true;
+                }
+            }
+        }
+        let furthest = reached.iter().rposition(|&r| r).unwrap_or(0);
+        return Err(NoDiffChain(format!(
+            "No chain of stored diffs leads from snapshot {} to snapshot {}; they reach {} \
+             at the latest",
+            describe(&snapshots[0]),
+            describe(&snapshots[last]),
+            describe(&snapshots[furthest]),
+        ))
+        .into());
+    }
+
+    let mut chain = Vec::new();
+    let mut current = 0;
+    while current != last {
+        let &(to, d) = outgoing[current]
+            .iter()
+            .find(|&&(to, _)| leads_to_last[to])
+            .expect("a snapshot leading to the last one has such a diff");
+        chain.push(d);
+        current = to;
+    }
+    Ok(chain)
+}
+
+fn describe(snapshot: &SnapshotMeta) -> String {
+    match &snapshot.label {
+        Some(label) => format!("'{}' ({})", label, snapshot.id),
+        None => snapshot.id.to_string(),
+    }
+}
+
+/// An item's net change across the diffs composed so far.
+enum Net {
+    Added(Value),
+    Removed(Value),
+    /// The changed entry, its identity key (and `attribute` for options) plus its `changes`.
+    Changed(Map<String, Value>),
+}
+
+/// Compose diffs (in `diff.data` shape, oldest first) into one diff from the first one's
+/// before-state to the last one's after-state. An item added and later removed drops out;
+/// one removed and re-added becomes a change of the fields that differ; changes of an
+/// added item are applied to it; successive changes of a field keep the first old value
+/// and the last new one, and drop out when they cancel. Items are matched by the first of
+/// their category's `identity` keys they have, as `diff::parse_diff_data` reads them.
+/// Changed entries that cannot be composed (not an object, or without a `changes` object)
+/// are passed through unchanged, so parsing the result reports them.
+pub fn compose<'a>(diffs: impl IntoIterator<Item = &'a Value>, identity: &IdentityKeys) -> Value {
+    let mut categories: BTreeMap<String, BTreeMap<String, Net>> = BTreeMap::new();
+    let mut unreadable: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+
+    for diff in diffs {
+        let Some(diff) = diff.as_object() else {
+            continue;
+        };
+        for (category, value) in diff {
+            let items = categories.entry(category.clone()).or_default();
+            let keys = identity.chain(category);
+            let list = |key: &str| value.get(key).and_then(Value::as_array).cloned();
+
+            for item in list("added").unwrap_or_default() {
+                let key = item_key(&item, &keys);
+                let net = match items.remove(&key) {
+                    Some(Net::Removed(old)) => {
+                        let changes = object_changes(&old, &item);
+                        if changes.is_empty() {
+                            continue;
+                        }
+                        Net::Changed(changed_entry(&item, changes, &keys))
+                    }
+                    _ => Net::Added(item),
+                };
+                items.insert(key, net);
+            }
+
+            for item in list("removed").unwrap_or_default() {
+                let key = item_key(&item, &keys);
+                match items.remove(&key) {
+                    // Added and removed again within the range: no net change
+                    Some(Net::Added(_)) => {}
+                    _ => {
+                        items.insert(key, Net::Removed(item));
+                    }
+                }
+            }
+
+            for entry in list("changed").unwrap_or_default() {
+                let mut entry = match entry {
+                    Value::Object(entry) if entry.get("changes").is_some_and(Value::is_object) => {
+                        entry
+                    }
+                    other => {
+                        unreadable.entry(category.clone()).or_default().push(other);
+                        continue;
+                    }
+                };
+                let key = item_key(&Value::Object(entry.clone()), &keys);
+                let changes = entry["changes"].as_object().cloned().unwrap_or_default();
+                let net = match items.remove(&key) {
+                    None => Net::Changed(entry),
+                    Some(Net::Added(mut item)) => {
+                        apply_changes(&mut item, &changes);
+                        Net::Added(item)
+                    }
+                    Some(Net::Changed(previous)) => {
+                        let Some(Value::Object(before)) = previous.get("changes") else {
+                            continue;
+                        };
+                        let merged = merge_changes(before, &changes);
+                        if merged.is_empty() {
+                            continue;
+                        }
+                        entry.insert("changes".to_string(), Value::Object(merged));
+                        Net::Changed(entry)
+                    }
+                    // A change of a removed item contradicts the removal; keep the removal
+                    Some(removed @ Net::Removed(_)) => removed,
+                };
+                items.insert(key, net);
+            }
+        }
+    }
+
+    let composed: Map<String, Value> = categories
+        .into_iter()
+        .map(|(category, items)| {
+            let (mut added, mut removed, mut changed) = (Vec::new(), Vec::new(), Vec::new());
+            for net in items.into_values() {
+                match net {
+                    Net::Added(item) => added.push(item),
+                    Net::Removed(item) => removed.push(item),
+                    Net::Changed(entry) => changed.push(Value::Object(entry)),
+                }
+            }
+            changed.extend(unreadable.remove(&category).unwrap_or_default());
+            (
+                category,
+                json!({ "added": added, "removed": removed, "changed": changed }),
+            )
+        })
+        .collect();
+    Value::Object(composed)
+}
+
+/// Identity of an item across diffs: its first identity key, qualified by its attribute for
+/// attribute options (changed options may instead carry an `attr/option` code).
+fn item_key(item: &Value, keys: &[&str]) -> String {
+    let code = diff::item_identity(item, keys).unwrap_or_else(|| item.to_string());
+    match item.get("attribute").and_then(Value::as_str) {
+        Some(attribute) if !code.contains('/') => format!("{}/{}", attribute, code),
+        _ => code,
+    }
+}
+
+/// A changed entry for `item` with the given changes, identified like `item`.
+fn changed_entry(item: &Value, changes: Map<String, Value>, keys: &[&str]) -> Map<String, Value> {
+    let mut entry = Map::new();
+    for &field in keys.iter().chain(&["attribute"]) {
+        if let Some(value) = item.get(field) {
+            entry.insert(field.to_string(), value.clone());
+        }
+    }
+    entry.insert("changes".to_string(), Value::Object(changes));
+    entry
+}
+
+fn is_leaf(change: &Map<String, Value>) -> bool {
+    change.contains_key("old") && change.contains_key("new")
+}
+
+fn is_list_change(change: &Map<String, Value>) -> bool {
+    change.get("added").is_some_and(Value::is_array)
+        || change.get("removed").is_some_and(Value::is_array)
+}
+
+fn list(change: &Map<String, Value>, key: &str) -> Vec<Value> {
+    change.get(key).and_then(Value::as_array).cloned().unwrap_or_default()
+}
+
+/// The changes turning `old` into `new`, in the shape `diff::flatten_changes` reads:
+/// objects are compared field by field, lists of plain values as added / removed values,
+/// anything else as an old / new pair.
+fn object_changes(old: &Value, new: &Value) -> Map<String, Value> {
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        return Map::new();
+    };
+    let mut changes = Map::new();
+    let fields = old.keys().chain(new.keys().filter(|k| !old.contains_key(*k)));
+    for field in fields {
+        let (before, after) = (
+            old.get(field).unwrap_or(&Value::Null),
+            new.get(field).unwrap_or(&Value::Null),
+        );
+        if before == after {
+            continue;
+        }
+        let change = match (before, after) {
+            (Value::Object(_), Value::Object(_)) => Value::Object(object_changes(before, after)),
+            (Value::Array(old_items), Value::Array(new_items))
+                if old_items.iter().chain(new_items).all(|v| !v.is_object()) =>
+            {
+                let added: Vec<&Value> =
+                    new_items.iter().filter(|v| !old_items.contains(v)).collect();
+                let removed: Vec<&Value> =
+                    old_items.iter().filter(|v| !new_items.contains(v)).collect();
+                if added.is_empty() && removed.is_empty() {
+                    // Same values in another order
+                    json!({ "old": before, "new": after })
+                } else {
+                    json!({ "added": added, "removed": removed })
+                }
+            }
+            _ => json!({ "old": before, "new": after }),
+        };
+        changes.insert(field.clone(), change);
+    }
+    changes
+}
+
+/// Merge two successive change trees of one item into the change from the first one's
+/// old state to the second one's new state.
+fn merge_changes(first: &Map<String, Value>, second: &Map<String, Value>) -> Map<String, Value> {
+    let mut merged = Map::new();
+    let fields = first.keys().chain(second.keys().filter(|k| !first.contains_key(*k)));
+    for field in fields {
+        let change = match (first.get(field), second.get(field)) {
+            (Some(a), Some(b)) => merge_change(a, b),
+            (Some(change), None) | (None, Some(change)) => Some(change.clone()),
+            (None, None) => None,
+        };
+        if let Some(change) = change {
+            merged.insert(field.clone(), change);
+        }
+    }
+    merged
+}
+
+/// Merge two successive changes of one field; `None` when they cancel out.
+fn merge_change(first: &Value, second: &Value) -> Option<Value> {
+    let (Value::Object(a), Value::Object(b)) = (first, second) else {
+        return Some(second.clone());
+    };
+
+    if is_leaf(a) && is_leaf(b) {
+        return (a["old"] != b["new"]).then(|| json!({ "old": a["old"], "new": b["new"] }));
+    }
+
+    if is_list_change(a) && is_list_change(b) {
+        let (added_a, removed_a) = (list(a, "added"), list(a, "removed"));
+        let (added_b, removed_b) = (list(b, "added"), list(b, "removed"));
+        let mut added: Vec<Value> = Vec::new();
+        for value in added_a.iter().filter(|v| !removed_b.contains(v)).chain(
+            added_b.iter().filter(|v| !removed_a.contains(v)),
+        ) {
+            if !added.contains(value) {
+                added.push(value.clone());
+            }
+        }
+        let mut removed: Vec<Value> = Vec::new();
+        for value in removed_a.iter().filter(|v| !added_b.contains(v)).chain(
+            removed_b.iter().filter(|v| !added_a.contains(v)),
+        ) {
+            if !removed.contains(value) {
+                removed.push(value.clone());
+            }
+        }
+        return (!added.is_empty() || !removed.is_empty())
+            .then(|| json!({ "added": added, "removed": removed }));
+    }
+
+    if !is_leaf(a) && !is_list_change(a) && !is_leaf(b) && !is_list_change(b) {
+        let merged = merge_changes(a, b);
+        return (!merged.is_empty()).then_some(Value::Object(merged));
+    }
+
+    // Changes of different shapes: the later one describes the field best
+    Some(second.clone())
+}
+
+/// Apply a change tree to an added item, so it shows its state at the end of the range.
+fn apply_changes(item: &mut Value, changes: &Map<String, Value>) {
+    let Some(item) = item.as_object_mut() else {
+        return;
+    };
+    for (field, change) in changes {
+        let Some(change) = change.as_object() else {
+            continue;
+        };
+        if is_leaf(change) {
+            item.insert(field.clone(), change["new"].clone());
+        } else if is_list_change(change) {
+            // Only lists the item has; e.g. an attribute's options are not part of it
+            if let Some(Value::Array(values)) = item.get_mut(field) {
+                let removed = list(change, "removed");
+                values.retain(|v| !removed.contains(v));
+                for value in list(change, "added") {
+                    if !values.contains(&value) {
+                        values.push(value);
+                    }
+                }
+            }
+        } else {
+            let nested = item
+                .entry(field.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            apply_changes(nested, change);
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::{self, DiffListRow, ListFilter, ServerSummary, SnapshotListRow};
+use crate::extract::Query;
+use crate::{http_cache, AppState};
+
+/// Snapshots and diffs shown per server.
+const RECENT_LIMIT: i64 = 20;
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #172b4d; }
+nav a { margin-right: 1rem; }
+nav a.current { font-weight: bold; text-decoration: none; color: inherit; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2rem; }
+th, td { text-align: left; padding: 0.4rem 0.6rem; border-bottom: 1px solid #dfe1e6; }
+th { background: #f4f5f7; }
+.kind { font-size: 0.75rem; text-transform: uppercase; padding: 0.1rem 0.3rem; \
+        border-radius: 3px; background: #dfe1e6; }
+.kind.draft { background: #fff0b3; }
+.muted { color: #6b778c; }
+#status { min-height: 1.5rem; }
+#status.error { color: #bf2600; }
+button { margin-right: 0.3rem; }
+";
+
+/// Runs the action buttons against the publishing API. The outcome is kept across the
+/// reload that shows the new publication status.
+const SCRIPT: &str = r#"
+const status = document.getElementById('status');
+function show(message, url, failed) {
+  status.textContent = message;
+  status.className = failed ? 'error' : '';
+  if (url) {
+    const link = document.createElement('a');
+    link.href = url;
+    link.textContent = url;
+    status.append(' ', link);
+  }
+}
+const saved = sessionStorage.getItem('dashboard-status');
+if (saved) {
+  sessionStorage.removeItem('dashboard-status');
+  const { message, url } = JSON.parse(saved);
+  show(message, url, false);
+}
+document.querySelectorAll('button[data-url]').forEach((button) => {
+  button.addEventListener('click', async () => {
+    document.querySelectorAll('button[data-url]').forEach((b) => (b.disabled = true));
+    show(button.dataset.pending + '…');
+    try {
+      const response = await fetch(button.dataset.url, { method: button.dataset.method });
+      const body = await response.json().catch(() => ({}));
+      if (!response.ok) throw new Error(body.message || response.statusText);
+      sessionStorage.setItem('dashboard-status', JSON.stringify({
+        message: button.dataset.done, url: body.page_url,
+      }));
+      location.reload();
+    } catch (e) {
+      show(button.dataset.pending + ' failed: ' + e.message, null, true);
+      document.querySelectorAll('button[data-url]').forEach((b) => (b.disabled = false));
+    }
+  });
+});
+"#;
+
+/// Query parameters of the dashboard.
+#[derive(Deserialize)]
+pub struct DashboardParams {
+    /// Akeneo server to show; the first by name when unset.
+    server: Option<Uuid>,
+}
+
+/// GET /
+///
+/// An HTML dashboard listing an Akeneo server's recent snapshots and diffs with their
+/// publication status, with buttons to preview (publish as a draft), publish and promote
+/// them through the API.
+pub async fn handle_dashboard(
+    State(state): State<AppState>,
+    Query(params): Query<DashboardParams>,
+    headers: HeaderMap,
+) -> Response {
+    match render(&state, params.server).await {
+        Ok(Some(markup)) => http_cache::html(&headers, markup.into_string()),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            layout(html! { p { "Unknown Akeneo server." } }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Dashboard failed: {:#}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                layout(html! { p { "Failed to load the dashboard: " (format!("{:#}", e)) } }),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// The dashboard for a server; `None` when `selected` names no server.
+async fn render(state: &AppState, selected: Option<Uuid>) -> anyhow::Result<Option<Markup>> {
+    let servers = db::list_akeneo_servers(&state.pool).await?;
+    let server = match selected {
+        Some(id) => match servers.iter().find(|s| s.id == id) {
+            Some(server) => server,
+            None => return Ok(None),
+        },
+        None => match servers.first() {
+            Some(server) => server,
+            None => return Ok(Some(layout(html! { p { "No Akeneo servers configured." } }))),
+        },
+    };
+
+    let filter = ListFilter {
+        label: None,
+        from: None,
+        to: None,
+        archived: false,
+        limit: RECENT_LIMIT,
+        offset: 0,
+    };
+    let (snapshots, snapshot_total) = db::list_snapshots(&state.pool, server.id, &filter).await?;
+    let (diffs, diff_total) = db::list_diffs(&state.pool, server.id, &filter).await?;
+
+    Ok(Some(layout(html! {
+        (server_nav(&servers, server.id))
+        p #status {}
+        h2 { "Snapshots " span.muted { (count(snapshots.len(), snapshot_total)) } }
+        (snapshot_table(&snapshots))
+        h2 { "Diffs " span.muted { (count(diffs.len(), diff_total)) } }
+        (diff_table(&diffs))
+    })))
+}
+
+fn layout(content: Markup) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                title { "Akeneo snapshot publisher" }
+                style { (PreEscaped(STYLE)) }
+            }
+            body {
+                h1 { "Akeneo snapshot publisher" }
+                (content)
+                script { (PreEscaped(SCRIPT)) }
+            }
+        }
+    }
+}
+
+fn server_nav(servers: &[ServerSummary], current: Uuid) -> Markup {
+    html! {
+        nav {
+            @for server in servers {
+                a.current[server.id == current] href={ "/?server=" (server.id) } {
+                    (server.name)
+                }
+            }
+        }
+    }
+}
+
+fn snapshot_table(rows: &[SnapshotListRow]) -> Markup {
+    html! {
+        table {
+            tr { th { "Label" } th { "Completed" } th { "Published" } th {} }
+            @for row in rows {
+                tr {
+                    td { (label(&row.label, row.id)) }
+                    td { (timestamp(row.completed_at)) }
+                    td {
+                        (publication(
+                            row.publication_kind.as_deref(),
+                            row.page_title.as_deref(),
+                            row.web_url.as_deref(),
+                            row.published_at,
+                        ))
+                    }
+                    td {
+                        (actions(
+                            &format!("/api/snapshot/{}", row.id),
+                            draft_id(row.publication_kind.as_deref(), row.publication_id),
+                        ))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn diff_table(rows: &[DiffListRow]) -> Markup {
+    html! {
+        table {
+            tr { th { "Diff" } th { "Completed" } th { "Published" } th { "Alerts" } th {} }
+            @for row in rows {
+                tr {
+                    td {
+                        (label(&row.before_label, row.before_id))
+                        " \u{2192} "
+                        (label(&row.after_label, row.after_id))
+                    }
+                    td { (timestamp(row.after_completed_at)) }
+                    td {
+                        (publication(
+                            row.publication_kind.as_deref(),
+                            row.page_title.as_deref(),
+                            row.web_url.as_deref(),
+                            row.published_at,
+                        ))
+                    }
+                    td {
+                        @match row.alerts.as_ref().and_then(|a| a.as_array()) {
+                            Some(alerts) if !alerts.is_empty() => (alerts.len()),
+                            _ => span.muted { "\u{2014}" },
+                        }
+                    }
+                    td {
+                        (actions(
+                            &format!("/api/diff/{}", row.id),
+                            draft_id(row.publication_kind.as_deref(), row.publication_id),
+                        ))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The latest publication as a link to its page, or a placeholder.
+fn publication(
+    kind: Option<&str>,
+    page_title: Option<&str>,
+    web_url: Option<&str>,
+    published_at: Option<DateTime<Utc>>,
+) -> Markup {
+    html! {
+        @match (page_title, web_url, published_at) {
+            (Some(title), Some(url), Some(at)) => {
+                a href=(url) { (title) }
+                " "
+                span.kind.draft[kind == Some("draft")] { (kind.unwrap_or_default()) }
+                br;
+                span.muted { (timestamp(at)) }
+            }
+            _ => span.muted { "Not published" },
+        }
+    }
+}
+
+/// The publication to promote, when the latest one is a draft.
+fn draft_id(kind: Option<&str>, publication_id: Option<Uuid>) -> Option<Uuid> {
+    publication_id.filter(|_| kind == Some("draft"))
+}
+
+fn actions(endpoint: &str, draft: Option<Uuid>) -> Markup {
+    html! {
+        button type="button" data-method="GET" data-url={ (endpoint) "?draft=true" }
+            data-pending="Publishing draft" data-done="Draft published:" { "Preview" }
+        button type="button" data-method="GET" data-url={ (endpoint) "?draft=false" }
+            data-pending="Publishing" data-done="Published:" { "Publish" }
+        @if let Some(id) = draft {
+            button type="button" data-method="POST"
+                data-url={ "/api/publications/" (id) "/promote" }
+                data-pending="Promoting draft" data-done="Promoted:" { "Promote" }
+        }
+    }
+}
+
+/// A snapshot's label, or the start of its ID when it has none.
+fn label(label: &Option<String>, id: Uuid) -> Markup {
+    html! {
+        @match label {
+            Some(label) => (label),
+            None => span.muted { (id.simple().to_string()[..8]) },
+        }
+    }
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+fn count(shown: usize, total: i64) -> String {
+    if shown as i64 == total {
+        format!("({})", total)
+    } else {
+        format!("({} of {})", shown, total)
+    }
+}
@@ -1,11 +1,22 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Utc};
-use sqlx::postgres::PgPool;
-use sqlx::Row;
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use sqlx::FromRow;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::config::{DatabaseConfig, RetentionAction, SnapshotCodec, StorageConfig};
+use crate::crypto::{self, TokenCipher};
+use crate::model::Snapshot;
+use crate::renderer::RENDERER_VERSION;
+use crate::secrets;
+
 /// A row from the `diff` table.
 #[allow(dead_code)]
+#[derive(FromRow)]
 pub struct DiffRow {
     pub id: Uuid,
     pub snapshot_before_id: Uuid,
@@ -15,88 +26,1335 @@ pub struct DiffRow {
 
 /// A row from the `snapshot` table.
 #[allow(dead_code)]
+#[derive(FromRow)]
 pub struct SnapshotRow {
     pub id: Uuid,
     pub akeneo_server_id: Uuid,
     pub label: Option<String>,
     pub started_at: DateTime<Utc>,
     pub completed_at: DateTime<Utc>,
-    pub data: serde_json::Value,
+    #[sqlx(try_from = "serde_json::Value")]
+    pub data: Snapshot,
+}
+
+/// A snapshot's metadata without its `data` blob, for callers that only need its server,
+/// label and completion time.
+#[derive(FromRow)]
+pub struct SnapshotMeta {
+    pub id: Uuid,
+    pub akeneo_server_id: Uuid,
+    pub label: Option<String>,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Akeneo API connection details from the `akeneo_server` table.
+pub struct DbAkeneoServer {
+    pub id: Uuid,
+    pub name: String,
+    pub base_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// An Akeneo server's ID and name, without its credentials.
+pub struct ServerSummary {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// An `akeneo_server` row with its snapshot count, for the server registry.
+pub struct AkeneoServerRecord {
+    pub id: Uuid,
+    pub name: String,
+    pub base_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub username: String,
+    pub password: String,
+    pub webhook_secret: Option<String>,
+    pub snapshots: i64,
+}
+
+/// A `confluence_config` row linked to an Akeneo server, without its credentials.
+pub struct ConfluenceTargetSummary {
+    pub id: Uuid,
+    pub akeneo_server_id: Uuid,
+    pub base_url: String,
+    pub space_key: String,
+}
+
+/// Columns of a new `akeneo_server` row.
+pub struct NewAkeneoServer<'a> {
+    pub name: &'a str,
+    pub base_url: &'a str,
+    pub client_id: &'a str,
+    pub client_secret: &'a str,
+    pub username: &'a str,
+    pub password: &'a str,
+    pub webhook_secret: Option<&'a str>,
+}
+
+/// Changed columns of an `akeneo_server` row; `None` keeps the stored value and
+/// `Some(None)` clears `webhook_secret`.
+#[derive(Default)]
+pub struct AkeneoServerChanges<'a> {
+    pub name: Option<&'a str>,
+    pub base_url: Option<&'a str>,
+    pub client_id: Option<&'a str>,
+    pub client_secret: Option<&'a str>,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a str>,
+    pub webhook_secret: Option<Option<&'a str>>,
+}
+
+/// The connection and placement columns of a `confluence_config` row, as stored: secrets
+/// are not decrypted.
+pub struct ConfluenceConfigRecord {
+    pub id: Uuid,
+    pub akeneo_server_id: Uuid,
+    pub server_name: String,
+    pub base_url: String,
+    pub username: String,
+    pub api_token: String,
+    pub space_key: String,
+    pub parent_page: String,
+    pub parent_page_id: Option<String>,
+    pub publish_mode: String,
+    pub draft_publishing: bool,
+    pub auth_type: String,
+    pub oauth_client_id: Option<String>,
+    pub oauth_client_secret: Option<String>,
+    pub oauth_refresh_token: Option<String>,
+    pub cloud_id: Option<String>,
+    pub snapshot_title_template: Option<String>,
+    pub family_title_template: Option<String>,
+    pub diff_title_template: Option<String>,
+    pub version_title_template: Option<String>,
+    pub staging_parent_page: Option<String>,
+    pub allowed_space_keys: Vec<String>,
+    pub view_restriction_groups: Vec<String>,
+    pub view_restriction_users: Vec<String>,
+    pub edit_restriction_groups: Vec<String>,
+    pub edit_restriction_users: Vec<String>,
+    pub jira_project_key: Option<String>,
+    pub jira_issue_type: String,
+    pub attribute_include: Vec<String>,
+    pub attribute_exclude: Vec<String>,
+    pub child_page_order: String,
+    pub family_grouping: String,
+    pub archive_bucket: Option<String>,
+    pub archive_prefix: String,
+    pub git_mirror_url: Option<String>,
+    pub git_mirror_branch: String,
+    pub git_mirror_username: String,
+    pub git_mirror_token: Option<String>,
+    pub git_mirror_path: String,
+    pub git_mirror_format: String,
+    pub ca_certificate: Option<String>,
+    pub danger_accept_invalid_certs: bool,
+    pub display_locale: Option<String>,
+    pub display_timezone: Option<String>,
+    pub item_priority_fields: Option<Vec<String>>,
+    pub item_skip_fields: Option<Vec<String>>,
+    pub item_columns: serde_json::Value,
+    pub attribute_table: Option<String>,
+}
+
+/// Changed columns of a `confluence_config` row; `None` keeps the stored value and, for
+/// nullable text columns, an empty string clears it (`Some(None)` for the nullable lists).
+/// Secrets are given in plaintext (or as references) and encrypted when
+/// `TOKEN_ENCRYPTION_KEY` is set.
+#[derive(Default)]
+pub struct ConfluenceConfigChanges<'a> {
+    pub base_url: Option<&'a str>,
+    pub username: Option<&'a str>,
+    pub api_token: Option<&'a str>,
+    pub space_key: Option<&'a str>,
+    pub parent_page: Option<&'a str>,
+    pub parent_page_id: Option<&'a str>,
+    pub publish_mode: Option<&'a str>,
+    pub draft_publishing: Option<bool>,
+    pub auth_type: Option<&'a str>,
+    pub oauth_client_id: Option<&'a str>,
+    pub oauth_client_secret: Option<&'a str>,
+    pub oauth_refresh_token: Option<&'a str>,
+    pub cloud_id: Option<&'a str>,
+    pub snapshot_title_template: Option<&'a str>,
+    pub family_title_template: Option<&'a str>,
+    pub diff_title_template: Option<&'a str>,
+    pub version_title_template: Option<&'a str>,
+    pub staging_parent_page: Option<&'a str>,
+    pub allowed_space_keys: Option<&'a [String]>,
+    pub view_restriction_groups: Option<&'a [String]>,
+    pub view_restriction_users: Option<&'a [String]>,
+    pub edit_restriction_groups: Option<&'a [String]>,
+    pub edit_restriction_users: Option<&'a [String]>,
+    pub jira_project_key: Option<&'a str>,
+    pub jira_issue_type: Option<&'a str>,
+    pub attribute_include: Option<&'a [String]>,
+    pub attribute_exclude: Option<&'a [String]>,
+    pub child_page_order: Option<&'a str>,
+    pub family_grouping: Option<&'a str>,
+    pub archive_bucket: Option<&'a str>,
+    pub archive_prefix: Option<&'a str>,
+    pub git_mirror_url: Option<&'a str>,
+    pub git_mirror_branch: Option<&'a str>,
+    pub git_mirror_username: Option<&'a str>,
+    pub git_mirror_token: Option<&'a str>,
+    pub git_mirror_path: Option<&'a str>,
+    pub git_mirror_format: Option<&'a str>,
+    pub ca_certificate: Option<&'a str>,
+    pub danger_accept_invalid_certs: Option<bool>,
+    pub display_locale: Option<&'a str>,
+    pub display_timezone: Option<&'a str>,
+    pub item_priority_fields: Option<Option<&'a [String]>>,
+    pub item_skip_fields: Option<Option<&'a [String]>>,
+    pub item_columns: Option<&'a serde_json::Value>,
+    pub attribute_table: Option<&'a str>,
+}
+
+/// A row from the `publish_schedule` table.
+pub struct ScheduleRow {
+    pub id: Uuid,
+    pub akeneo_server_id: Uuid,
+    pub server_name: String,
+    pub cron: String,
+    /// `snapshot` or `diff`.
+    pub action: String,
+    pub enabled: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
 }
 
 /// Confluence connection configuration from the `confluence_config` table.
+#[derive(FromRow, Clone)]
 pub struct DbConfluenceConfig {
     pub base_url: String,
     pub username: String,
     pub api_token: String,
     pub space_key: String,
     pub parent_page: String,
+    /// ID of the parent page; takes precedence over the `parent_page` title.
+    pub parent_page_id: Option<String>,
+    /// Name of the linked `akeneo_server`, available as `{server}` in title templates.
+    pub server_name: String,
+    pub snapshot_title_template: Option<String>,
+    pub family_title_template: Option<String>,
+    pub diff_title_template: Option<String>,
+    pub version_title_template: Option<String>,
+    /// `overwrite` (default) or `versioned`.
+    pub publish_mode: String,
+    /// Stage every publish as a draft unless the request says otherwise.
+    pub draft_publishing: bool,
+    /// `label` (default), `code`, `title` or `none`.
+    pub child_page_order: String,
+    /// `none` (default), `first_letter`, `label_attribute_group` or `prefix`.
+    pub family_grouping: String,
+    /// Bucket live publishes are archived to (none: not archived), see `object_store.rs`.
+    pub archive_bucket: Option<String>,
+    /// Key prefix of the archived objects within the bucket.
+    pub archive_prefix: String,
+    /// HTTPS URL of the Git repository snapshots are mirrored to (none: not mirrored), see
+    /// `git_mirror.rs`.
+    pub git_mirror_url: Option<String>,
+    pub git_mirror_branch: String,
+    /// User name sent with the token (`x-access-token` for GitHub, `oauth2` for GitLab).
+    pub git_mirror_username: String,
+    pub git_mirror_token: Option<String>,
+    /// Directory of the mirrored files within the repository (default: its root).
+    pub git_mirror_path: String,
+    /// `json` (default), `markdown` or `both`.
+    pub git_mirror_format: String,
+    /// `api_token` (basic auth with `username`) or `oauth` (OAuth 2.0 3LO).
+    pub auth_type: String,
+    pub oauth_client_id: Option<String>,
+    pub oauth_client_secret: Option<String>,
+    pub oauth_refresh_token: Option<String>,
+    /// Atlassian cloud ID of the site, which OAuth requests are routed by.
+    pub cloud_id: Option<String>,
+    /// Extra root certificates for the site: a PEM bundle or the path of a PEM file.
+    pub ca_certificate: Option<String>,
+    pub danger_accept_invalid_certs: bool,
+    /// Title of the page drafts are published under (default: the live parent page).
+    pub staging_parent_page: Option<String>,
+    /// Spaces a request may publish into with `space_key` instead of `space_key`.
+    pub allowed_space_keys: Vec<String>,
+    /// Page restrictions of the generated pages (see `confluence::PageRestrictions`):
+    /// group names and account IDs.
+    pub view_restriction_groups: Vec<String>,
+    pub view_restriction_users: Vec<String>,
+    pub edit_restriction_groups: Vec<String>,
+    pub edit_restriction_users: Vec<String>,
+    /// Jira project that breaking diff changes are reported to (none: not reported).
+    pub jira_project_key: Option<String>,
+    /// Issue type of those reports (default `Task`).
+    pub jira_issue_type: String,
+    /// Attribute filter rules of snapshot pages (see `render_options::AttributeFilter`).
+    pub attribute_include: Vec<String>,
+    pub attribute_exclude: Vec<String>,
+    /// Count and timestamp format of the pages (see `render_options::DisplayFormat`).
+    pub display_locale: Option<String>,
+    pub display_timezone: Option<String>,
+    /// Fields and columns of the diff added / removed tables (see
+    /// `render_options::ItemProperties`).
+    pub item_priority_fields: Option<Vec<String>>,
+    pub item_skip_fields: Option<Vec<String>>,
+    pub item_columns: serde_json::Value,
+    /// `basic` or `extended` attributes tables (none: the `ATTRIBUTE_TABLE` default).
+    pub attribute_table: Option<String>,
 }
 
-/// Create a connection pool from the DATABASE_URL environment variable.
-pub async fn connect() -> Result<PgPool> {
-    let database_url =
-        std::env::var("DATABASE_URL").context("DATABASE_URL environment variable is required")?;
+/// The settings of a Confluence target that a snapshot's page tree is rendered with
+/// (attribute filters, display format and attributes table layout), without the rest of
+/// its config.
+pub struct RenderRules {
+    pub attribute_include: Vec<String>,
+    pub attribute_exclude: Vec<String>,
+    pub display_locale: Option<String>,
+    pub display_timezone: Option<String>,
+    pub attribute_table: Option<String>,
+}
 
-    PgPool::connect(&database_url)
+/// A row from the `publication_history` table, joined with its snapshot.
+pub struct PublicationRow {
+    pub snapshot_label: Option<String>,
+    pub snapshot_completed_at: DateTime<Utc>,
+    pub page_title: String,
+    pub web_url: String,
+    pub published_at: DateTime<Utc>,
+}
+
+/// What a `publication_history` row records.
+#[derive(Clone, Copy)]
+pub enum PublicationKind {
+    /// A dated page created by versioned publishing (listed on the index page).
+    Version,
+    /// The root page updated in place by overwrite-mode publishing.
+    Overwrite,
+    /// A diff page.
+    Diff,
+    /// A staged snapshot tree or diff page awaiting promotion (see `publish::promote_draft`).
+    Draft,
+}
+
+impl PublicationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Version => "version",
+            Self::Overwrite => "overwrite",
+            Self::Diff => "diff",
+            Self::Draft => "draft",
+        }
+    }
+}
+
+/// A publish to record in `publication_history`.
+pub struct NewPublication<'a> {
+    pub akeneo_server_id: Uuid,
+    /// The published snapshot, or a diff's after-snapshot.
+    pub snapshot_id: Uuid,
+    pub diff_id: Option<Uuid>,
+    pub kind: PublicationKind,
+    pub page_id: &'a str,
+    pub page_title: &'a str,
+    pub web_url: &'a str,
+    /// Threshold alerts a diff raised (`diff::DiffAlert`s); an empty array for snapshots.
+    pub alerts: serde_json::Value,
+}
+
+/// A single `publication_history` row.
+pub struct PublicationRecord {
+    pub id: Uuid,
+    pub akeneo_server_id: Uuid,
+    pub snapshot_id: Uuid,
+    pub diff_id: Option<Uuid>,
+    pub kind: String,
+    pub page_id: String,
+    pub page_title: String,
+    pub promoted_at: Option<DateTime<Utc>>,
+    /// The `renderer::RENDERER_VERSION` that produced the pages (0: before versioning).
+    pub renderer_version: i32,
+}
+
+/// Filters and pagination for the snapshot and diff listings.
+pub struct ListFilter {
+    /// Case-insensitive substring match on snapshot labels.
+    pub label: Option<String>,
+    /// Inclusive lower bound on the (after-)snapshot's completion time.
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on the (after-)snapshot's completion time.
+    pub to: Option<DateTime<Utc>>,
+    /// Also list archived snapshots (and diffs with an archived snapshot).
+    pub archived: bool,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// A snapshot listing row with its latest publication, if any.
+pub struct SnapshotListRow {
+    pub id: Uuid,
+    pub label: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    pub archived: bool,
+    pub publication_id: Option<Uuid>,
+    pub publication_kind: Option<String>,
+    pub page_title: Option<String>,
+    pub web_url: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// A diff listing row with both snapshots' labels and its latest publication, if any.
+pub struct DiffListRow {
+    pub id: Uuid,
+    pub before_id: Uuid,
+    pub before_label: Option<String>,
+    pub after_id: Uuid,
+    pub after_label: Option<String>,
+    pub after_completed_at: DateTime<Utc>,
+    pub publication_id: Option<Uuid>,
+    pub publication_kind: Option<String>,
+    pub page_title: Option<String>,
+    pub web_url: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub alerts: Option<serde_json::Value>,
+}
+
+/// One category's item counts in a published diff, for the model activity page. Counted
+/// from the stored diff, so a renamed item counts as one removed and one added.
+pub struct DiffActivityRow {
+    pub diff_id: Uuid,
+    pub after_label: Option<String>,
+    pub after_completed_at: DateTime<Utc>,
+    pub category: String,
+    pub added: i32,
+    pub removed: i32,
+    pub changed: i32,
+}
+
+/// One category's item counts in a live-published diff, for the diff feed. `category` is
+/// `None` (and the counts 0) for a diff without categories.
+pub struct DiffFeedRow {
+    pub publication_id: Uuid,
+    pub server_name: String,
+    pub page_title: String,
+    pub web_url: String,
+    pub published_at: DateTime<Utc>,
+    pub before_label: Option<String>,
+    pub after_label: Option<String>,
+    pub category: Option<String>,
+    pub added: i32,
+    pub removed: i32,
+    pub changed: i32,
+}
+
+/// The check constraint a row broke, when that caused the error.
+pub fn check_violation(err: &anyhow::Error) -> Option<&str> {
+    match err.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(e)) if e.is_check_violation() => e.constraint(),
+        _ => None,
+    }
+}
+
+/// Whether an error was caused by a query that matched no rows.
+pub fn is_not_found(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<sqlx::Error>(),
+        Some(sqlx::Error::RowNotFound)
+    )
+}
+
+/// Create a connection pool from the `[database]` configuration (`DATABASE_URL` and the
+/// `DB_*` pool sizing, see `config::DatabaseConfig`) and run the migrations.
+pub async fn connect(config: &DatabaseConfig) -> Result<PgPool> {
+    let database_url = config.url.as_deref().context("DATABASE_URL is required")?;
+    let max_connections = config.max_connections;
+    let min_connections = config.min_connections;
+    let acquire_timeout = config.acquire_timeout_secs;
+    let idle_timeout = config.idle_timeout_secs;
+    let statement_cache_capacity = config.statement_cache_capacity;
+
+    info!(
+        "Database pool: max_connections={}, min_connections={}, acquire_timeout={}s, idle_timeout={}, statement_cache_capacity={}",
+        max_connections,
+        min_connections,
+        acquire_timeout,
+        if idle_timeout == 0 { "none".to_string() } else { format!("{}s", idle_timeout) },
+        statement_cache_capacity
+    );
+
+    let connect_options = PgConnectOptions::from_str(database_url)
+        .context("DATABASE_URL is not a valid PostgreSQL connection string")?
+        .statement_cache_capacity(statement_cache_capacity);
+
+    let pool = PgPoolOptions::new()
+        .max_connections(max_connections)
+        .min_connections(min_connections)
+        .acquire_timeout(Duration::from_secs(acquire_timeout))
+        .idle_timeout((idle_timeout > 0).then(|| Duration::from_secs(idle_timeout)))
+        .connect_with(connect_options)
         .await
-        .context("Failed to connect to database")
+        .context("Failed to connect to database")?;
+
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .context("Failed to run database migrations")?;
+
+    Ok(pool)
 }
 
-/// Fetch a diff row and both of its related snapshots (before and after).
-pub async fn fetch_diff(pool: &PgPool, diff_id: Uuid) -> Result<(DiffRow, SnapshotRow, SnapshotRow)> {
-    let row = sqlx::query(
-        "SELECT id, snapshot_before_id, snapshot_after_id, data FROM diff WHERE id = $1",
+/// Fetch a diff row. Its snapshots are fetched by `publish::publish_diff`, alongside the
+/// diff parsing. A diff with a soft-deleted snapshot is not found.
+pub async fn fetch_diff(pool: &PgPool, diff_id: Uuid) -> Result<DiffRow> {
+    sqlx::query_as!(
+        DiffRow,
+        "SELECT d.id, d.snapshot_before_id, d.snapshot_after_id, d.data FROM diff d \
+         WHERE d.id = $1 AND NOT EXISTS (SELECT 1 FROM snapshot s \
+             WHERE s.id IN (d.snapshot_before_id, d.snapshot_after_id) \
+             AND s.deleted_at IS NOT NULL)",
+        diff_id
     )
-    .bind(diff_id)
     .fetch_one(pool)
     .await
-    .with_context(|| format!("Diff not found: {}", diff_id))?;
+    .with_context(|| format!("Diff not found: {}", diff_id))
+}
 
-    let diff_row = DiffRow {
-        id: row.get("id"),
-        snapshot_before_id: row.get("snapshot_before_id"),
-        snapshot_after_id: row.get("snapshot_after_id"),
-        data: row.get("data"),
-    };
+/// Stored diffs between two of the given snapshots, in either direction.
+pub async fn fetch_diffs_among(pool: &PgPool, snapshot_ids: &[Uuid]) -> Result<Vec<DiffRow>> {
+    sqlx::query_as!(
+        DiffRow,
+        "SELECT id, snapshot_before_id, snapshot_after_id, data FROM diff \
+         WHERE snapshot_before_id = ANY($1) AND snapshot_after_id = ANY($1)",
+        snapshot_ids
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch diffs")
+}
+
+/// Fetch a single snapshot row by ID. Archived snapshots are found, soft-deleted ones are
+/// not (here and in every other snapshot fetch).
+pub async fn fetch_snapshot(pool: &PgPool, snapshot_id: Uuid) -> Result<SnapshotRow> {
+    let row = sqlx::query!(
+        "SELECT id, akeneo_server_id, label, started_at, completed_at, data, data_zstd \
+         FROM snapshot WHERE id = $1 AND deleted_at IS NULL",
+        snapshot_id
+    )
+    .fetch_one(pool)
+    .await
+    .with_context(|| format!("Snapshot not found: {}", snapshot_id))?;
 
-    let (before, after) = tokio::try_join!(
-        fetch_snapshot(pool, diff_row.snapshot_before_id),
-        fetch_snapshot(pool, diff_row.snapshot_after_id),
-    )?;
+    let data = decode_snapshot_data(row.data, row.data_zstd)
+        .and_then(Snapshot::from_value)
+        .with_context(|| format!("Snapshot {} has invalid data", snapshot_id))?;
 
-    Ok((diff_row, before, after))
+    Ok(SnapshotRow {
+        id: row.id,
+        akeneo_server_id: row.akeneo_server_id,
+        label: row.label,
+        started_at: row.started_at,
+        completed_at: row.completed_at,
+        data,
+    })
 }
 
-/// Fetch a single snapshot row by ID.
-pub async fn fetch_snapshot(pool: &PgPool, snapshot_id: Uuid) -> Result<SnapshotRow> {
-    let row = sqlx::query(
-        "SELECT id, akeneo_server_id, label, started_at, completed_at, data FROM snapshot WHERE id = $1",
+/// An Akeneo server's snapshots completed in `[from, to)`, oldest first, without their
+/// `data`. Includes archived snapshots, so cumulative diffs still span them.
+pub async fn fetch_snapshots_in_range(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<SnapshotMeta>> {
+    sqlx::query_as!(
+        SnapshotMeta,
+        "SELECT id, akeneo_server_id, label, completed_at FROM snapshot \
+         WHERE akeneo_server_id = $1 AND deleted_at IS NULL \
+           AND ($2::timestamptz IS NULL OR completed_at >= $2) \
+           AND ($3::timestamptz IS NULL OR completed_at < $3) \
+         ORDER BY completed_at, id",
+        akeneo_server_id,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch snapshots")
+}
+
+/// Fetch a snapshot's metadata without loading its `data`.
+pub async fn fetch_snapshot_meta(pool: &PgPool, snapshot_id: Uuid) -> Result<SnapshotMeta> {
+    sqlx::query_as!(
+        SnapshotMeta,
+        "SELECT id, akeneo_server_id, label, completed_at FROM snapshot \
+         WHERE id = $1 AND deleted_at IS NULL",
+        snapshot_id
+    )
+    .fetch_one(pool)
+    .await
+    .with_context(|| format!("Snapshot not found: {}", snapshot_id))
+}
+
+/// Number of items in each model list of a snapshot's data (attribute options summed over
+/// their attributes), counted in the database without loading the data. Compressed data
+/// is decompressed and counted here.
+pub async fn fetch_snapshot_counts(
+    pool: &PgPool,
+    snapshot_id: Uuid,
+) -> Result<BTreeMap<String, usize>> {
+    let compressed = sqlx::query_scalar!(
+        "SELECT data_zstd FROM snapshot \
+         WHERE id = $1 AND data_zstd IS NOT NULL AND deleted_at IS NULL",
+        snapshot_id
+    )
+    .fetch_optional(pool)
+    .await
+    .with_context(|| format!("Failed to count the items of snapshot {}", snapshot_id))?
+    .flatten();
+    if let Some(compressed) = compressed {
+        let data = decompress_snapshot_data(&compressed)
+            .with_context(|| format!("Snapshot {} has invalid data", snapshot_id))?;
+        return Ok(count_snapshot_items(&data));
+    }
+
+    let rows = sqlx::query!(
+        r#"SELECT key AS "category!",
+           CASE jsonb_typeof(value)
+               WHEN 'array' THEN jsonb_array_length(value)
+               WHEN 'object' THEN (
+                   SELECT COALESCE(SUM(jsonb_array_length(v)), 0)::int
+                   FROM jsonb_each(value) e(k, v) WHERE jsonb_typeof(v) = 'array'
+               )
+               ELSE 0
+           END AS "count!"
+           FROM snapshot, jsonb_each(CASE jsonb_typeof(data) WHEN 'object' THEN data ELSE '{}' END)
+           WHERE id = $1 AND deleted_at IS NULL"#,
+        snapshot_id
+    )
+    .fetch_all(pool)
+    .await
+    .with_context(|| format!("Failed to count the items of snapshot {}", snapshot_id))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.category, row.count.max(0) as usize))
+        .collect())
+}
+
+/// Fetch a snapshot with only the `families`, `attributes` and `measurement_families` keys
+/// of its data, which is all a family detail page needs. The other model lists are left
+/// empty. Compressed data is decompressed whole and then cut down.
+pub async fn fetch_snapshot_families(pool: &PgPool, snapshot_id: Uuid) -> Result<SnapshotRow> {
+    let row = sqlx::query!(
+        r#"SELECT id, akeneo_server_id, label, started_at, completed_at,
+           CASE WHEN data IS NOT NULL THEN jsonb_build_object(
+               'families', data->'families',
+               'attributes', data->'attributes',
+               'measurement_families', data->'measurement_families'
+           ) END AS data, data_zstd
+           FROM snapshot WHERE id = $1 AND deleted_at IS NULL"#,
+        snapshot_id
     )
-    .bind(snapshot_id)
     .fetch_one(pool)
     .await
     .with_context(|| format!("Snapshot not found: {}", snapshot_id))?;
 
+    let data = decode_snapshot_data(row.data, row.data_zstd)
+        .map(|mut data| {
+            if let serde_json::Value::Object(lists) = &mut data {
+                lists.retain(|key, _| {
+                    matches!(
+                        key.as_str(),
+                        "families" | "attributes" | "measurement_families"
+                    )
+                });
+            }
+            data
+        })
+        .and_then(Snapshot::from_value)
+        .with_context(|| format!("Snapshot {} has invalid data", snapshot_id))?;
+
     Ok(SnapshotRow {
-        id: row.get("id"),
-        akeneo_server_id: row.get("akeneo_server_id"),
-        label: row.get("label"),
-        started_at: row.get("started_at"),
-        completed_at: row.get("completed_at"),
-        data: row.get("data"),
+        id: row.id,
+        akeneo_server_id: row.akeneo_server_id,
+        label: row.label,
+        started_at: row.started_at,
+        completed_at: row.completed_at,
+        data,
     })
 }
 
+/// Store a new snapshot for an Akeneo server, with the configured codec, and return its ID.
+pub async fn insert_snapshot(
+    pool: &PgPool,
+    storage: &StorageConfig,
+    akeneo_server_id: Uuid,
+    label: Option<&str>,
+    started_at: DateTime<Utc>,
+    completed_at: DateTime<Utc>,
+    data: &serde_json::Value,
+) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    let (data, data_zstd) = encode_snapshot_data(data, storage)?;
+    sqlx::query!(
+        "INSERT INTO snapshot (id, akeneo_server_id, label, started_at, completed_at, data, \
+         data_zstd) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        id,
+        akeneo_server_id,
+        label,
+        started_at,
+        completed_at,
+        data,
+        data_zstd
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store snapshot")?;
+
+    Ok(id)
+}
+
+/// Fetch an Akeneo server's API connection details by ID.
+pub async fn fetch_akeneo_server(pool: &PgPool, akeneo_server_id: Uuid) -> Result<DbAkeneoServer> {
+    sqlx::query_as!(
+        DbAkeneoServer,
+        "SELECT id, name, base_url, client_id, client_secret, username, password \
+         FROM akeneo_server WHERE id = $1",
+        akeneo_server_id
+    )
+    .fetch_one(pool)
+    .await
+    .with_context(|| format!("Akeneo server not found: {}", akeneo_server_id))
+}
+
+/// Fetch the secret an Akeneo server's events are signed with; `None` when the server does
+/// not exist or has no `webhook_secret`.
+pub async fn fetch_webhook_secret(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+) -> Result<Option<String>> {
+    let secret = sqlx::query_scalar!(
+        "SELECT webhook_secret FROM akeneo_server WHERE id = $1",
+        akeneo_server_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch Akeneo webhook secret")?;
+    Ok(secret.flatten())
+}
+
+/// Record the nonce of an accepted Akeneo event, dropping nonces received before
+/// `expired_before` first. Returns false when the server already sent this nonce.
+pub async fn record_webhook_nonce(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    nonce: &str,
+    expired_before: DateTime<Utc>,
+) -> Result<bool> {
+    sqlx::query!("DELETE FROM webhook_nonce WHERE received_at < $1", expired_before)
+        .execute(pool)
+        .await
+        .context("Failed to prune webhook nonces")?;
+    let result = sqlx::query!(
+        "INSERT INTO webhook_nonce (akeneo_server_id, nonce) VALUES ($1, $2) \
+         ON CONFLICT DO NOTHING",
+        akeneo_server_id,
+        nonce
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record webhook nonce")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Fetch the ID and name of every Akeneo server, by name.
+pub async fn list_akeneo_servers(pool: &PgPool) -> Result<Vec<ServerSummary>> {
+    sqlx::query_as!(ServerSummary, "SELECT id, name FROM akeneo_server ORDER BY name, id")
+        .fetch_all(pool)
+        .await
+        .context("Failed to list Akeneo servers")
+}
+
+/// Fetch every Akeneo server with its snapshot count, by name; or only `akeneo_server_id`.
+pub async fn fetch_akeneo_server_records(
+    pool: &PgPool,
+    akeneo_server_id: Option<Uuid>,
+) -> Result<Vec<AkeneoServerRecord>> {
+    sqlx::query_as!(
+        AkeneoServerRecord,
+        r#"SELECT a.id, a.name, a.base_url, a.client_id, a.client_secret, a.username,
+                  a.password, a.webhook_secret,
+                  (SELECT COUNT(*) FROM snapshot s
+                   WHERE s.akeneo_server_id = a.id AND s.deleted_at IS NULL)
+                      AS "snapshots!"
+           FROM akeneo_server a
+           WHERE $1::uuid IS NULL OR a.id = $1
+           ORDER BY a.name, a.id"#,
+        akeneo_server_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list Akeneo servers")
+}
+
+/// Fetch the Confluence targets linked to Akeneo servers, by base URL and space; of every
+/// server, or only of `akeneo_server_id`.
+pub async fn fetch_confluence_targets(
+    pool: &PgPool,
+    akeneo_server_id: Option<Uuid>,
+) -> Result<Vec<ConfluenceTargetSummary>> {
+    sqlx::query_as!(
+        ConfluenceTargetSummary,
+        "SELECT id, akeneo_server_id, base_url, space_key FROM confluence_config \
+         WHERE $1::uuid IS NULL OR akeneo_server_id = $1 \
+         ORDER BY base_url, space_key, id",
+        akeneo_server_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list Confluence targets")
+}
+
+/// Insert an Akeneo server; returns its new ID.
+pub async fn insert_akeneo_server(pool: &PgPool, server: &NewAkeneoServer<'_>) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO akeneo_server (id, name, base_url, client_id, client_secret, username, \
+         password, webhook_secret) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        id,
+        server.name,
+        server.base_url,
+        server.client_id,
+        server.client_secret,
+        server.username,
+        server.password,
+        server.webhook_secret
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create Akeneo server")?;
+    Ok(id)
+}
+
+/// Update the given columns of an Akeneo server. Fails (`is_not_found`) when the server
+/// does not exist.
+pub async fn update_akeneo_server(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    changes: &AkeneoServerChanges<'_>,
+) -> Result<()> {
+    let result = sqlx::query!(
+        "UPDATE akeneo_server SET name = COALESCE($2, name), \
+         base_url = COALESCE($3, base_url), client_id = COALESCE($4, client_id), \
+         client_secret = COALESCE($5, client_secret), username = COALESCE($6, username), \
+         password = COALESCE($7, password), \
+         webhook_secret = CASE WHEN $8 THEN $9 ELSE webhook_secret END \
+         WHERE id = $1",
+        akeneo_server_id,
+        changes.name,
+        changes.base_url,
+        changes.client_id,
+        changes.client_secret,
+        changes.username,
+        changes.password,
+        changes.webhook_secret.is_some(),
+        changes.webhook_secret.flatten()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update Akeneo server")?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::Error::new(sqlx::Error::RowNotFound)
+            .context(format!("Akeneo server not found: {}", akeneo_server_id)));
+    }
+    Ok(())
+}
+
+/// Delete an Akeneo server, with its schedules and publication history (cascading). The
+/// caller checks it has no snapshots or Confluence targets left, which the schema does
+/// not cascade. Fails (`is_not_found`) when the server does not exist.
+pub async fn delete_akeneo_server(pool: &PgPool, akeneo_server_id: Uuid) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM akeneo_server WHERE id = $1", akeneo_server_id)
+        .execute(pool)
+        .await
+        .context("Failed to delete Akeneo server")?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::Error::new(sqlx::Error::RowNotFound)
+            .context(format!("Akeneo server not found: {}", akeneo_server_id)));
+    }
+    Ok(())
+}
+
+/// Fetch `confluence_config` rows with their server's name, by server name and space; every
+/// row, or only `config_id`.
+pub async fn fetch_confluence_config_records(
+    pool: &PgPool,
+    config_id: Option<Uuid>,
+) -> Result<Vec<ConfluenceConfigRecord>> {
+    sqlx::query_as!(
+        ConfluenceConfigRecord,
+        "SELECT c.id, c.akeneo_server_id, s.name AS server_name, c.base_url, c.username, \
+         c.api_token, c.space_key, c.parent_page, c.parent_page_id, c.publish_mode, \
+         c.draft_publishing, c.auth_type, c.oauth_client_id, c.oauth_client_secret, \
+         c.oauth_refresh_token, c.cloud_id, c.snapshot_title_template, \
+         c.family_title_template, c.diff_title_template, c.version_title_template, \
+         c.staging_parent_page, c.allowed_space_keys, c.view_restriction_groups, \
+         c.view_restriction_users, c.edit_restriction_groups, c.edit_restriction_users, \
+         c.jira_project_key, c.jira_issue_type, c.attribute_include, c.attribute_exclude, \
+         c.child_page_order, c.family_grouping, c.archive_bucket, c.archive_prefix, \
+         c.git_mirror_url, c.git_mirror_branch, c.git_mirror_username, c.git_mirror_token, \
+         c.git_mirror_path, c.git_mirror_format, c.ca_certificate, \
+         c.danger_accept_invalid_certs, c.display_locale, c.display_timezone, \
+         c.item_priority_fields, c.item_skip_fields, c.item_columns, c.attribute_table \
+         FROM confluence_config c JOIN akeneo_server s ON s.id = c.akeneo_server_id \
+         WHERE $1::uuid IS NULL OR c.id = $1 \
+         ORDER BY s.name, c.space_key, c.id",
+        config_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list Confluence configurations")
+}
+
+/// Insert the Confluence target of an Akeneo server; returns its new ID. `base_url`,
+/// `username` and `space_key` are required, the other columns keep their defaults unless
+/// given.
+pub async fn insert_confluence_config(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    changes: &ConfluenceConfigChanges<'_>,
+) -> Result<Uuid> {
+    let (Some(base_url), Some(username), Some(space_key)) =
+        (changes.base_url, changes.username, changes.space_key)
+    else {
+        bail!("base_url, username and space_key are required");
+    };
+    let mut tx = pool.begin().await?;
+    let config_id = sqlx::query_scalar!(
+        "INSERT INTO confluence_config (akeneo_server_id, base_url, username, api_token, \
+         space_key) VALUES ($1, $2, $3, '', $4) RETURNING id",
+        akeneo_server_id,
+        base_url,
+        username,
+        space_key
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .context("Failed to insert Confluence configuration")?;
+    apply_confluence_config_changes(&mut tx, config_id, changes).await?;
+    tx.commit().await?;
+    Ok(config_id)
+}
+
+/// Update a `confluence_config` row. Fails (`is_not_found`) when it does not exist, and
+/// (`check_violation`) when the result breaks a check constraint, e.g. an `oauth` target
+/// without its OAuth credentials.
+pub async fn update_confluence_config(
+    pool: &PgPool,
+    config_id: Uuid,
+    changes: &ConfluenceConfigChanges<'_>,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    apply_confluence_config_changes(&mut tx, config_id, changes).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Apply the changes in two updates: the connection columns, then the publishing settings.
+async fn apply_confluence_config_changes(
+    conn: &mut sqlx::PgConnection,
+    config_id: Uuid,
+    changes: &ConfluenceConfigChanges<'_>,
+) -> Result<()> {
+    let api_token = changes.api_token.map(store_secret).transpose()?;
+    let oauth_client_secret = changes.oauth_client_secret.map(store_secret).transpose()?;
+    let oauth_refresh_token = changes.oauth_refresh_token.map(store_secret).transpose()?;
+    let git_mirror_token = changes.git_mirror_token.map(store_secret).transpose()?;
+    let result = sqlx::query!(
+        "UPDATE confluence_config SET \
+         base_url = COALESCE($2, base_url), \
+         username = COALESCE($3, username), \
+         api_token = COALESCE($4, api_token), \
+         space_key = COALESCE($5, space_key), \
+         parent_page = COALESCE($6, parent_page), \
+         parent_page_id = CASE WHEN $7::text IS NULL THEN parent_page_id \
+             ELSE NULLIF($7, '') END, \
+         publish_mode = COALESCE($8, publish_mode), \
+         draft_publishing = COALESCE($9, draft_publishing), \
+         auth_type = COALESCE($10, auth_type), \
+         oauth_client_id = CASE WHEN $11::text IS NULL THEN oauth_client_id \
+             ELSE NULLIF($11, '') END, \
+         oauth_client_secret = CASE WHEN $12::text IS NULL THEN oauth_client_secret \
+             ELSE NULLIF($12, '') END, \
+         oauth_refresh_token = CASE WHEN $13::text IS NULL THEN oauth_refresh_token \
+             ELSE NULLIF($13, '') END, \
+         cloud_id = CASE WHEN $14::text IS NULL THEN cloud_id ELSE NULLIF($14, '') END \
+         WHERE id = $1",
+        config_id,
+        changes.base_url,
+        changes.username,
+        api_token,
+        changes.space_key,
+        changes.parent_page,
+        changes.parent_page_id,
+        changes.publish_mode,
+        changes.draft_publishing,
+        changes.auth_type,
+        changes.oauth_client_id,
+        oauth_client_secret,
+        oauth_refresh_token,
+        changes.cloud_id
+    )
+    .execute(&mut *conn)
+    .await
+    .context("Failed to update Confluence configuration")?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::Error::new(sqlx::Error::RowNotFound)
+            .context(format!("Confluence configuration not found: {}", config_id)));
+    }
+
+    sqlx::query!(
+        "UPDATE confluence_config SET \
+         snapshot_title_template = CASE WHEN $2::text IS NULL THEN snapshot_title_template \
+             ELSE NULLIF($2, '') END, \
+         family_title_template = CASE WHEN $3::text IS NULL THEN family_title_template \
+             ELSE NULLIF($3, '') END, \
+         diff_title_template = CASE WHEN $4::text IS NULL THEN diff_title_template \
+             ELSE NULLIF($4, '') END, \
+         version_title_template = CASE WHEN $5::text IS NULL THEN version_title_template \
+             ELSE NULLIF($5, '') END, \
+         staging_parent_page = CASE WHEN $6::text IS NULL THEN staging_parent_page \
+             ELSE NULLIF($6, '') END, \
+         allowed_space_keys = COALESCE($7, allowed_space_keys), \
+         view_restriction_groups = COALESCE($8, view_restriction_groups), \
+         view_restriction_users = COALESCE($9, view_restriction_users), \
+         edit_restriction_groups = COALESCE($10, edit_restriction_groups), \
+         edit_restriction_users = COALESCE($11, edit_restriction_users), \
+         jira_project_key = CASE WHEN $12::text IS NULL THEN jira_project_key \
+             ELSE NULLIF($12, '') END, \
+         jira_issue_type = COALESCE($13, jira_issue_type), \
+         attribute_include = COALESCE($14, attribute_include), \
+         attribute_exclude = COALESCE($15, attribute_exclude), \
+         child_page_order = COALESCE($16, child_page_order), \
+         family_grouping = COALESCE($17, family_grouping), \
+         archive_bucket = CASE WHEN $18::text IS NULL THEN archive_bucket \
+             ELSE NULLIF($18, '') END, \
+         archive_prefix = COALESCE($19, archive_prefix), \
+         git_mirror_url = CASE WHEN $20::text IS NULL THEN git_mirror_url \
+             ELSE NULLIF($20, '') END, \
+         git_mirror_branch = COALESCE($21, git_mirror_branch), \
+         git_mirror_username = COALESCE($22, git_mirror_username), \
+         git_mirror_token = CASE WHEN $23::text IS NULL THEN git_mirror_token \
+             ELSE NULLIF($23, '') END, \
+         git_mirror_path = COALESCE($24, git_mirror_path), \
+         git_mirror_format = COALESCE($25, git_mirror_format), \
+         ca_certificate = CASE WHEN $26::text IS NULL THEN ca_certificate \
+             ELSE NULLIF($26, '') END, \
+         danger_accept_invalid_certs = COALESCE($27, danger_accept_invalid_certs), \
+         display_locale = CASE WHEN $28::text IS NULL THEN display_locale \
+             ELSE NULLIF($28, '') END, \
+         display_timezone = CASE WHEN $29::text IS NULL THEN display_timezone \
+             ELSE NULLIF($29, '') END, \
+         item_priority_fields = CASE WHEN $30 THEN $31 ELSE item_priority_fields END, \
+         item_skip_fields = CASE WHEN $32 THEN $33 ELSE item_skip_fields END, \
+         item_columns = COALESCE($34, item_columns), \
+         attribute_table = CASE WHEN $35::text IS NULL THEN attribute_table \
+             ELSE NULLIF($35, '') END \
+         WHERE id = $1",
+        config_id,
+        changes.snapshot_title_template,
+        changes.family_title_template,
+        changes.diff_title_template,
+        changes.version_title_template,
+        changes.staging_parent_page,
+        changes.allowed_space_keys,
+        changes.view_restriction_groups,
+        changes.view_restriction_users,
+        changes.edit_restriction_groups,
+        changes.edit_restriction_users,
+        changes.jira_project_key,
+        changes.jira_issue_type,
+        changes.attribute_include,
+        changes.attribute_exclude,
+        changes.child_page_order,
+        changes.family_grouping,
+        changes.archive_bucket,
+        changes.archive_prefix,
+        changes.git_mirror_url,
+        changes.git_mirror_branch,
+        changes.git_mirror_username,
+        git_mirror_token,
+        changes.git_mirror_path,
+        changes.git_mirror_format,
+        changes.ca_certificate,
+        changes.danger_accept_invalid_certs,
+        changes.display_locale,
+        changes.display_timezone,
+        changes.item_priority_fields.is_some(),
+        changes.item_priority_fields.flatten(),
+        changes.item_skip_fields.is_some(),
+        changes.item_skip_fields.flatten(),
+        changes.item_columns,
+        changes.attribute_table
+    )
+    .execute(&mut *conn)
+    .await
+    .context("Failed to update Confluence configuration")?;
+    Ok(())
+}
+
+/// Delete a `confluence_config` row. Fails (`is_not_found`) when it does not exist.
+pub async fn delete_confluence_config(pool: &PgPool, config_id: Uuid) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM confluence_config WHERE id = $1", config_id)
+        .execute(pool)
+        .await
+        .context("Failed to delete Confluence configuration")?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::Error::new(sqlx::Error::RowNotFound)
+            .context(format!("Confluence configuration not found: {}", config_id)));
+    }
+    Ok(())
+}
+
+/// A secret as it is stored: encrypted when a cipher is configured, unless it is empty or
+/// a secret reference.
+fn store_secret(value: &str) -> Result<String> {
+    match crypto::cipher() {
+        Some(cipher) if !value.is_empty() && !secrets::is_reference(value) => {
+            cipher.encrypt(value)
+        }
+        _ => Ok(value.to_string()),
+    }
+}
+
+/// Fetch only the raw `data` of a snapshot (decompressed), without parsing it into the
+/// model.
+pub async fn fetch_snapshot_raw_data(pool: &PgPool, snapshot_id: Uuid) -> Result<serde_json::Value> {
+    let row = sqlx::query!(
+        "SELECT data, data_zstd FROM snapshot WHERE id = $1 AND deleted_at IS NULL",
+        snapshot_id
+    )
+    .fetch_one(pool)
+    .await
+    .with_context(|| format!("Snapshot not found: {}", snapshot_id))?;
+
+    decode_snapshot_data(row.data, row.data_zstd)
+        .with_context(|| format!("Snapshot {} has invalid data", snapshot_id))
+}
+
+/// Snapshot data in the columns `codec` stores it in: `(data, data_zstd)`.
+fn encode_snapshot_data(
+    data: &serde_json::Value,
+    storage: &StorageConfig,
+) -> Result<(Option<serde_json::Value>, Option<Vec<u8>>)> {
+    match storage.snapshot_codec {
+        SnapshotCodec::Json => Ok((Some(data.clone()), None)),
+        SnapshotCodec::Zstd => {
+            let json = serde_json::to_vec(data)?;
+            let compressed = zstd::encode_all(json.as_slice(), storage.zstd_level)
+                .context("Failed to compress snapshot data")?;
+            Ok((None, Some(compressed)))
+        }
+    }
+}
+
+/// Snapshot data from whichever of its columns is set.
+fn decode_snapshot_data(
+    data: Option<serde_json::Value>,
+    data_zstd: Option<Vec<u8>>,
+) -> Result<serde_json::Value> {
+    match (data, data_zstd) {
+        (Some(data), _) => Ok(data),
+        (None, Some(compressed)) => decompress_snapshot_data(&compressed),
+        (None, None) => bail!("Snapshot has no data"),
+    }
+}
+
+fn decompress_snapshot_data(compressed: &[u8]) -> Result<serde_json::Value> {
+    let json = zstd::decode_all(compressed).context("Failed to decompress snapshot data")?;
+    serde_json::from_slice(&json).context("Decompressed snapshot data is not JSON")
+}
+
+/// `fetch_snapshot_counts` for data that is not stored as JSONB.
+fn count_snapshot_items(data: &serde_json::Value) -> BTreeMap<String, usize> {
+    let Some(lists) = data.as_object() else {
+        return BTreeMap::new();
+    };
+    lists
+        .iter()
+        .map(|(category, value)| {
+            let count = match value {
+                serde_json::Value::Array(items) => items.len(),
+                serde_json::Value::Object(lists) => lists
+                    .values()
+                    .filter_map(serde_json::Value::as_array)
+                    .map(Vec::len)
+                    .sum(),
+                _ => 0,
+            };
+            (category.clone(), count)
+        })
+        .collect()
+}
+
+/// Outcome of a `migrate_snapshot_storage` run.
+pub struct StorageMigration {
+    /// Snapshots converted to the configured codec.
+    pub migrated: usize,
+    /// Snapshots still stored with the other codec (left by `limit`).
+    pub remaining: i64,
+}
+
+/// Convert stored snapshots to the configured codec, compressing JSONB rows for `zstd` or
+/// decompressing them for `json`; at most `limit` of them, oldest first. Each snapshot is
+/// its own update, so an interrupted run keeps what it converted and a rerun carries on.
+pub async fn migrate_snapshot_storage(
+    pool: &PgPool,
+    storage: &StorageConfig,
+    limit: Option<usize>,
+) -> Result<StorageMigration> {
+    const BATCH: usize = 20;
+    let compress = storage.snapshot_codec == SnapshotCodec::Zstd;
+    let mut migrated = 0;
+    loop {
+        let batch = limit.map_or(BATCH, |limit| BATCH.min(limit - migrated));
+        if batch == 0 {
+            break;
+        }
+        let ids = sqlx::query_scalar!(
+            "SELECT id FROM snapshot \
+             WHERE CASE WHEN $1 THEN data IS NOT NULL ELSE data_zstd IS NOT NULL END \
+             ORDER BY completed_at, id LIMIT $2",
+            compress,
+            batch as i64
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list snapshots to migrate")?;
+        if ids.is_empty() {
+            break;
+        }
+        for id in ids {
+            let row = sqlx::query!(
+                "SELECT data, data_zstd FROM snapshot WHERE id = $1",
+                id
+            )
+            .fetch_one(pool)
+            .await
+            .with_context(|| format!("Snapshot not found: {}", id))?;
+            let data = decode_snapshot_data(row.data, row.data_zstd)
+                .with_context(|| format!("Snapshot {} has invalid data", id))?;
+            let (data, data_zstd) = encode_snapshot_data(&data, storage)?;
+            sqlx::query!(
+                "UPDATE snapshot SET data = $2, data_zstd = $3 WHERE id = $1",
+                id,
+                data,
+                data_zstd
+            )
+            .execute(pool)
+            .await
+            .with_context(|| format!("Failed to store snapshot {}", id))?;
+            migrated += 1;
+        }
+    }
+
+    let remaining = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM snapshot
+           WHERE CASE WHEN $1 THEN data IS NOT NULL ELSE data_zstd IS NOT NULL END"#,
+        compress
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count snapshots to migrate")?;
+    Ok(StorageMigration {
+        migrated,
+        remaining,
+    })
+}
+
+/// Archive or soft-delete the snapshots completed before `cutoff`, returning how many. Each
+/// server's latest live snapshot, every `keep_every`th snapshot of a server (by completion;
+/// 0 keeps none) and snapshots restored by hand are kept.
+pub async fn retire_snapshots(
+    pool: &PgPool,
+    cutoff: DateTime<Utc>,
+    keep_every: u32,
+    action: RetentionAction,
+) -> Result<u64> {
+    let delete = action == RetentionAction::Delete;
+    let result = sqlx::query!(
+        "WITH ranked AS ( \
+             SELECT id, completed_at, \
+                 row_number() OVER (PARTITION BY akeneo_server_id \
+                     ORDER BY completed_at, id) - 1 AS position, \
+                 row_number() OVER (PARTITION BY akeneo_server_id \
+                     ORDER BY completed_at DESC, id DESC) AS recency \
+             FROM snapshot WHERE deleted_at IS NULL) \
+         UPDATE snapshot s SET archived = true, \
+             deleted_at = CASE WHEN $3 THEN now() ELSE NULL END \
+         FROM ranked r \
+         WHERE s.id = r.id AND r.completed_at < $1 AND r.recency > 1 \
+           AND s.restored_at IS NULL AND ($3 OR NOT s.archived) \
+           AND ($2::bigint = 0 OR r.position % $2::bigint <> 0)",
+        cutoff,
+        i64::from(keep_every),
+        delete
+    )
+    .execute(pool)
+    .await
+    .context("Failed to retire old snapshots")?;
+    Ok(result.rows_affected())
+}
+
+/// Bring an archived or soft-deleted snapshot back. It is exempt from retention from then on.
+pub async fn restore_snapshot(pool: &PgPool, snapshot_id: Uuid) -> Result<()> {
+    let result = sqlx::query!(
+        "UPDATE snapshot SET archived = false, deleted_at = NULL, restored_at = now() \
+         WHERE id = $1",
+        snapshot_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to restore snapshot")?;
+    if result.rows_affected() == 0 {
+        return Err(anyhow::Error::new(sqlx::Error::RowNotFound)
+            .context(format!("Snapshot not found: {}", snapshot_id)));
+    }
+    Ok(())
+}
+
 /// Fetch the Confluence configuration for the akeneo_server linked to a snapshot.
 pub async fn fetch_confluence_config(
     pool: &PgPool,
     akeneo_server_id: Uuid,
 ) -> Result<DbConfluenceConfig> {
-    let row = sqlx::query(
-        "SELECT base_url, username, api_token, space_key, parent_page FROM confluence_config WHERE akeneo_server_id = $1",
+    let mut config = sqlx::query_as!(
+        DbConfluenceConfig,
+        "SELECT c.base_url, c.username, c.api_token, c.space_key, c.parent_page, \
+         c.parent_page_id, s.name AS server_name, c.snapshot_title_template, \
+         c.family_title_template, c.diff_title_template, \
+         c.version_title_template, c.publish_mode, c.draft_publishing, c.child_page_order, \
+         c.family_grouping, c.archive_bucket, c.archive_prefix, c.git_mirror_url, \
+         c.git_mirror_branch, c.git_mirror_username, c.git_mirror_token, c.git_mirror_path, \
+         c.git_mirror_format, c.staging_parent_page, \
+         c.allowed_space_keys, c.view_restriction_groups, c.view_restriction_users, \
+         c.edit_restriction_groups, c.edit_restriction_users, c.jira_project_key, \
+         c.jira_issue_type, c.attribute_include, c.attribute_exclude, c.display_locale, \
+         c.display_timezone, c.item_priority_fields, c.item_skip_fields, c.item_columns, \
+         c.attribute_table, c.auth_type, \
+         c.oauth_client_id, c.oauth_client_secret, c.oauth_refresh_token, c.cloud_id, c.ca_certificate, c.danger_accept_invalid_certs \
+         FROM confluence_config c JOIN akeneo_server s ON s.id = c.akeneo_server_id \
+         WHERE c.akeneo_server_id = $1",
+        akeneo_server_id
     )
-    .bind(akeneo_server_id)
     .fetch_one(pool)
     .await
     .with_context(|| {
@@ -106,11 +1364,792 @@ pub async fn fetch_confluence_config(
         )
     })?;
 
-    Ok(DbConfluenceConfig {
-        base_url: row.get("base_url"),
-        username: row.get("username"),
-        api_token: row.get("api_token"),
-        space_key: row.get("space_key"),
-        parent_page: row.get("parent_page"),
-    })
+    // Secrets may be stored encrypted (see `crypto.rs`); legacy plaintext still works
+    let stored_secrets = [
+        ("API token", Some(&mut config.api_token)),
+        ("OAuth client secret", config.oauth_client_secret.as_mut()),
+        ("OAuth refresh token", config.oauth_refresh_token.as_mut()),
+        ("Git mirror token", config.git_mirror_token.as_mut()),
+    ];
+    for (name, value) in stored_secrets {
+        let Some(value) = value.filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        if crypto::cipher().is_some()
+            && !crypto::is_encrypted(value)
+            && !secrets::is_reference(value)
+        {
+            warn!(
+                "Confluence {} for akeneo_server {} is stored in plaintext; run POST /api/admin/reencrypt-tokens",
+                name, akeneo_server_id
+            );
+        }
+        *value = crypto::reveal(value).with_context(|| {
+            format!(
+                "Failed to decrypt Confluence {} for akeneo_server: {}",
+                name, akeneo_server_id
+            )
+        })?;
+    }
+
+    Ok(config)
+}
+
+/// Fetch the render settings of the Confluence target of an Akeneo server; `None` when the
+/// server has no target.
+pub async fn fetch_render_rules(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+) -> Result<Option<RenderRules>> {
+    sqlx::query_as!(
+        RenderRules,
+        "SELECT attribute_include, attribute_exclude, display_locale, display_timezone, \
+         attribute_table FROM confluence_config WHERE akeneo_server_id = $1",
+        akeneo_server_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch render settings")
+}
+
+/// Encrypt every Confluence API token, OAuth secret and Git mirror token that is in
+/// plaintext or encrypted with a retired key, in one transaction. Returns the number of
+/// targets (re-encrypted, already current).
+pub async fn reencrypt_confluence_tokens(
+    pool: &PgPool,
+    cipher: &TokenCipher,
+) -> Result<(usize, usize)> {
+    let mut tx = pool.begin().await.context("Failed to start transaction")?;
+
+    let rows = sqlx::query!(
+        "SELECT id, api_token, oauth_client_secret, oauth_refresh_token, git_mirror_token \
+         FROM confluence_config FOR UPDATE"
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .context("Failed to fetch Confluence API tokens")?;
+
+    let (mut reencrypted, mut current) = (0, 0);
+    for row in rows {
+        let id = row.id;
+        let context = || format!("Failed to decrypt a secret of confluence_config {}", id);
+        let api_token = reencrypt_secret(cipher, &row.api_token).with_context(context)?;
+        let client_secret = match row.oauth_client_secret {
+            Some(stored) => reencrypt_secret(cipher, &stored).with_context(context)?,
+            None => None,
+        };
+        let refresh_token = match row.oauth_refresh_token {
+            Some(stored) => reencrypt_secret(cipher, &stored).with_context(context)?,
+            None => None,
+        };
+        let git_token = match row.git_mirror_token {
+            Some(stored) => reencrypt_secret(cipher, &stored).with_context(context)?,
+            None => None,
+        };
+        if api_token.is_none()
+            && client_secret.is_none()
+            && refresh_token.is_none()
+            && git_token.is_none()
+        {
+            current += 1;
+            continue;
+        }
+
+        sqlx::query!(
+            "UPDATE confluence_config SET api_token = COALESCE($2, api_token), \
+             oauth_client_secret = COALESCE($3, oauth_client_secret), \
+             oauth_refresh_token = COALESCE($4, oauth_refresh_token), \
+             git_mirror_token = COALESCE($5, git_mirror_token) WHERE id = $1",
+            id,
+            api_token,
+            client_secret,
+            refresh_token,
+            git_token
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to store re-encrypted API token")?;
+        reencrypted += 1;
+    }
+
+    tx.commit().await.context("Failed to commit re-encrypted tokens")?;
+    Ok((reencrypted, current))
+}
+
+/// A stored secret encrypted with the current key; `None` when it already is.
+fn reencrypt_secret(cipher: &TokenCipher, stored: &str) -> Result<Option<String>> {
+    // Secret references (`env:...`) are not secrets themselves
+    if !cipher.needs_reencryption(stored) || secrets::is_reference(stored) || stored.is_empty() {
+        return Ok(None);
+    }
+    let plaintext = if crypto::is_encrypted(stored) {
+        cipher.decrypt(stored)?
+    } else {
+        stored.to_string()
+    };
+    cipher.encrypt(&plaintext).map(Some)
+}
+
+/// Store the refresh token an OAuth token refresh rotated in, encrypted when
+/// `TOKEN_ENCRYPTION_KEY` is set.
+pub async fn update_oauth_refresh_token(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    refresh_token: &str,
+) -> Result<()> {
+    let stored = match crypto::cipher() {
+        Some(cipher) => cipher.encrypt(refresh_token)?,
+        None => refresh_token.to_string(),
+    };
+    sqlx::query!(
+        "UPDATE confluence_config SET oauth_refresh_token = $2 \
+         WHERE akeneo_server_id = $1 AND auth_type = 'oauth'",
+        akeneo_server_id,
+        stored
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the rotated OAuth refresh token")?;
+    Ok(())
+}
+
+/// Record a publish by the current renderer and return its ID. Republishing to the same
+/// page refreshes its entry, so in overwrite mode the root page's entry always points at
+/// the most recently published snapshot.
+pub async fn record_publication(pool: &PgPool, publication: NewPublication<'_>) -> Result<Uuid> {
+    sqlx::query_scalar!(
+        "INSERT INTO publication_history \
+         (akeneo_server_id, snapshot_id, diff_id, kind, page_id, page_title, web_url, alerts, \
+          renderer_version) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+         ON CONFLICT (akeneo_server_id, page_id) DO UPDATE \
+         SET snapshot_id = EXCLUDED.snapshot_id, diff_id = EXCLUDED.diff_id, kind = EXCLUDED.kind, \
+         page_title = EXCLUDED.page_title, web_url = EXCLUDED.web_url, alerts = EXCLUDED.alerts, \
+         renderer_version = EXCLUDED.renderer_version, published_at = now(), promoted_at = NULL \
+         RETURNING id",
+        publication.akeneo_server_id,
+        publication.snapshot_id,
+        publication.diff_id,
+        publication.kind.as_str(),
+        publication.page_id,
+        publication.page_title,
+        publication.web_url,
+        publication.alerts,
+        RENDERER_VERSION
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to record publication")
+}
+
+/// Record the object keys a publish was archived to (see `object_store.rs`) on its
+/// publication, identified like in `record_publication` by its root page.
+pub async fn record_archive_keys(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    page_id: &str,
+    keys: &[String],
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE publication_history SET archive_keys = $3 \
+         WHERE akeneo_server_id = $1 AND page_id = $2",
+        akeneo_server_id,
+        page_id,
+        keys
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record archive keys")?;
+    Ok(())
+}
+
+/// Record the content hash (see `publish::content_hash`) of a snapshot publish on its
+/// publication, identified like in `record_publication` by its root page.
+pub async fn record_content_hash(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    page_id: &str,
+    content_hash: &str,
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE publication_history SET content_hash = $3 \
+         WHERE akeneo_server_id = $1 AND page_id = $2",
+        akeneo_server_id,
+        page_id,
+        content_hash
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record the content hash")?;
+    Ok(())
+}
+
+/// The web URL and content hash of a server's most recent publication of a kind, limited
+/// to one root page title when given.
+pub async fn fetch_last_publication_hash(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    kind: PublicationKind,
+    page_title: Option<&str>,
+) -> Result<Option<(String, Option<String>)>> {
+    let row = sqlx::query!(
+        "SELECT web_url, content_hash FROM publication_history \
+         WHERE akeneo_server_id = $1 AND kind = $2 AND ($3::text IS NULL OR page_title = $3) \
+         ORDER BY published_at DESC LIMIT 1",
+        akeneo_server_id,
+        kind.as_str(),
+        page_title
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the last publication")?;
+    Ok(row.map(|row| (row.web_url, row.content_hash)))
+}
+
+/// The snapshot last published to an overwrite-mode root page, identified by its title.
+pub async fn fetch_last_overwrite_snapshot(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    page_title: &str,
+) -> Result<Option<Uuid>> {
+    sqlx::query_scalar!(
+        "SELECT snapshot_id FROM publication_history \
+         WHERE akeneo_server_id = $1 AND kind = 'overwrite' AND page_title = $2 \
+         ORDER BY published_at DESC LIMIT 1",
+        akeneo_server_id,
+        page_title
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the last published snapshot")
+}
+
+/// The URL of the root page a snapshot was last published to (a versioned page or an
+/// overwritten root page, no draft), if that page has not shown another snapshot since.
+pub async fn fetch_published_snapshot_url(
+    pool: &PgPool,
+    snapshot_id: Uuid,
+) -> Result<Option<String>> {
+    sqlx::query_scalar!(
+        "SELECT p.web_url FROM publication_history p \
+         WHERE p.snapshot_id = $1 AND p.kind IN ('version', 'overwrite') \
+         AND NOT EXISTS (SELECT 1 FROM publication_history later \
+             WHERE later.akeneo_server_id = p.akeneo_server_id AND later.page_id = p.page_id \
+             AND later.kind IN ('version', 'overwrite') AND later.snapshot_id <> p.snapshot_id \
+             AND later.published_at > p.published_at) \
+         ORDER BY p.published_at DESC LIMIT 1",
+        snapshot_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the published snapshot page")
+}
+
+/// The URL of the most recently published diff page between two snapshots, if any.
+pub async fn fetch_published_diff_url(
+    pool: &PgPool,
+    before_id: Uuid,
+    after_id: Uuid,
+) -> Result<Option<String>> {
+    sqlx::query_scalar!(
+        "SELECT p.web_url FROM diff d \
+         JOIN publication_history p ON p.diff_id = d.id AND p.kind = 'diff' \
+         WHERE d.snapshot_before_id = $1 AND d.snapshot_after_id = $2 \
+         ORDER BY p.published_at DESC LIMIT 1",
+        before_id,
+        after_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the published diff page")
+}
+
+/// Fetch a single publication by ID.
+pub async fn fetch_publication(pool: &PgPool, publication_id: Uuid) -> Result<PublicationRecord> {
+    sqlx::query_as!(
+        PublicationRecord,
+        "SELECT id, akeneo_server_id, snapshot_id, diff_id, kind, page_id, page_title, \
+         promoted_at, renderer_version FROM publication_history WHERE id = $1",
+        publication_id
+    )
+    .fetch_one(pool)
+    .await
+    .with_context(|| format!("Publication not found: {}", publication_id))
+}
+
+/// Mark a draft publication as promoted.
+pub async fn mark_publication_promoted(pool: &PgPool, publication_id: Uuid) -> Result<()> {
+    sqlx::query!(
+        "UPDATE publication_history SET promoted_at = now() WHERE id = $1",
+        publication_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to mark publication as promoted")?;
+
+    Ok(())
+}
+
+/// The snapshot-tree publications of a snapshot (versioned, overwrite and draft root
+/// pages; diff pages are not included).
+pub async fn fetch_snapshot_publications(
+    pool: &PgPool,
+    snapshot_id: Uuid,
+) -> Result<Vec<PublicationRecord>> {
+    sqlx::query_as!(
+        PublicationRecord,
+        "SELECT id, akeneo_server_id, snapshot_id, diff_id, kind, page_id, page_title, \
+         promoted_at, renderer_version FROM publication_history \
+         WHERE snapshot_id = $1 AND diff_id IS NULL ORDER BY published_at",
+        snapshot_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch snapshot publications")
+}
+
+/// The live publications (drafts are left to promotion) produced by a renderer older than
+/// `renderer_version`, oldest first. Publications of soft-deleted snapshots are skipped, and
+/// so are entries superseded by a later publish of the same kind and title to another page
+/// (the page was recreated): their content is no longer what is published under the title.
+pub async fn fetch_stale_publications(
+    pool: &PgPool,
+    renderer_version: i32,
+) -> Result<Vec<PublicationRecord>> {
+    sqlx::query_as!(
+        PublicationRecord,
+        "SELECT p.id, p.akeneo_server_id, p.snapshot_id, p.diff_id, p.kind, p.page_id, \
+         p.page_title, p.promoted_at, p.renderer_version FROM publication_history p \
+         LEFT JOIN diff d ON d.id = p.diff_id \
+         WHERE p.renderer_version < $1 AND p.kind <> 'draft' \
+           AND NOT EXISTS (SELECT 1 FROM snapshot s \
+               WHERE s.id IN (p.snapshot_id, d.snapshot_before_id) AND s.deleted_at IS NOT NULL) \
+           AND NOT EXISTS (SELECT 1 FROM publication_history n \
+               WHERE n.kind = p.kind \
+                 AND n.akeneo_server_id = p.akeneo_server_id AND n.page_title = p.page_title \
+                 AND n.published_at > p.published_at) \
+         ORDER BY p.published_at, p.id",
+        renderer_version
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch publications to republish")
+}
+
+/// Remove a publication from the history once its pages have been deleted.
+pub async fn delete_publication(pool: &PgPool, publication_id: Uuid) -> Result<()> {
+    sqlx::query!(
+        "DELETE FROM publication_history WHERE id = $1",
+        publication_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete publication")?;
+
+    Ok(())
+}
+
+/// The Jira issue raised for a diff's breaking changes, if any.
+pub async fn fetch_diff_jira_issue(pool: &PgPool, diff_id: Uuid) -> Result<Option<String>> {
+    sqlx::query_scalar!(
+        "SELECT issue_key FROM diff_jira_issue WHERE diff_id = $1",
+        diff_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the diff's Jira issue")
+}
+
+/// Remember the Jira issue raised for a diff.
+pub async fn record_diff_jira_issue(pool: &PgPool, diff_id: Uuid, issue_key: &str) -> Result<()> {
+    sqlx::query!(
+        "INSERT INTO diff_jira_issue (diff_id, issue_key) VALUES ($1, $2) \
+         ON CONFLICT (diff_id) DO NOTHING",
+        diff_id,
+        issue_key
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record the diff's Jira issue")?;
+
+    Ok(())
+}
+
+/// Fetch all versioned publishes for an Akeneo server, newest snapshot first. Publishes of
+/// soft-deleted snapshots are left out.
+pub async fn fetch_publication_history(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+) -> Result<Vec<PublicationRow>> {
+    sqlx::query_as!(
+        PublicationRow,
+        "SELECT s.label AS snapshot_label, s.completed_at AS snapshot_completed_at, \
+         p.page_title, p.web_url, p.published_at \
+         FROM publication_history p JOIN snapshot s ON s.id = p.snapshot_id \
+         WHERE p.akeneo_server_id = $1 AND p.kind = 'version' AND s.deleted_at IS NULL \
+         ORDER BY s.completed_at DESC, p.published_at DESC",
+        akeneo_server_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch publication history")
+}
+
+/// Per-category item counts of an Akeneo server's `limit` most recent live-published diffs
+/// (by after-snapshot completion), oldest diff first.
+pub async fn fetch_diff_activity(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    limit: i64,
+) -> Result<Vec<DiffActivityRow>> {
+    sqlx::query_as!(
+        DiffActivityRow,
+        r#"WITH recent AS (
+             SELECT d.id, d.data, a.label, a.completed_at FROM diff d
+             JOIN snapshot a ON a.id = d.snapshot_after_id
+             WHERE a.akeneo_server_id = $1 AND a.deleted_at IS NULL
+             AND jsonb_typeof(d.data) = 'object'
+             AND EXISTS (SELECT 1 FROM publication_history p
+                         WHERE p.diff_id = d.id AND p.kind = 'diff')
+             ORDER BY a.completed_at DESC LIMIT $2
+           )
+           SELECT r.id AS "diff_id!", r.label AS after_label,
+           r.completed_at AS "after_completed_at!", c.key AS "category!",
+           CASE WHEN jsonb_typeof(c.value->'added') = 'array'
+                THEN jsonb_array_length(c.value->'added') ELSE 0 END AS "added!",
+           CASE WHEN jsonb_typeof(c.value->'removed') = 'array'
+                THEN jsonb_array_length(c.value->'removed') ELSE 0 END AS "removed!",
+           CASE WHEN jsonb_typeof(c.value->'changed') = 'array'
+                THEN jsonb_array_length(c.value->'changed') ELSE 0 END AS "changed!"
+           FROM recent r CROSS JOIN LATERAL jsonb_each(r.data) c
+           ORDER BY r.completed_at, r.id, c.key"#,
+        akeneo_server_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch diff activity")
+}
+
+/// Per-category item counts of the `limit` most recently published live diffs, of one
+/// Akeneo server or of all of them, newest first.
+pub async fn fetch_diff_feed(
+    pool: &PgPool,
+    akeneo_server_id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<DiffFeedRow>> {
+    sqlx::query_as!(
+        DiffFeedRow,
+        r#"WITH recent AS (
+             SELECT p.id, p.page_title, p.web_url, p.published_at, s.name,
+             b.label AS before_label, a.label AS after_label, d.data
+             FROM publication_history p
+             JOIN diff d ON d.id = p.diff_id
+             JOIN snapshot b ON b.id = d.snapshot_before_id
+             JOIN snapshot a ON a.id = d.snapshot_after_id
+             JOIN akeneo_server s ON s.id = p.akeneo_server_id
+             WHERE p.kind = 'diff' AND ($1::uuid IS NULL OR p.akeneo_server_id = $1)
+             AND b.deleted_at IS NULL AND a.deleted_at IS NULL
+             ORDER BY p.published_at DESC, p.id LIMIT $2
+           )
+           SELECT r.id AS "publication_id!", r.name AS "server_name!",
+           r.page_title AS "page_title!", r.web_url AS "web_url!",
+           r.published_at AS "published_at!", r.before_label, r.after_label,
+           c.key AS "category?",
+           CASE WHEN jsonb_typeof(c.value->'added') = 'array'
+                THEN jsonb_array_length(c.value->'added') ELSE 0 END AS "added!",
+           CASE WHEN jsonb_typeof(c.value->'removed') = 'array'
+                THEN jsonb_array_length(c.value->'removed') ELSE 0 END AS "removed!",
+           CASE WHEN jsonb_typeof(c.value->'changed') = 'array'
+                THEN jsonb_array_length(c.value->'changed') ELSE 0 END AS "changed!"
+           FROM recent r LEFT JOIN LATERAL jsonb_each(
+               CASE WHEN jsonb_typeof(r.data) = 'object' THEN r.data ELSE '{}'::jsonb END
+           ) c ON true
+           ORDER BY r.published_at DESC, r.id, c.key"#,
+        akeneo_server_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch diff feed")
+}
+
+/// Fetch one page of an Akeneo server's snapshots, newest first, with the latest
+/// publication of each. Returns the rows and the total number of matching snapshots.
+pub async fn list_snapshots(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    filter: &ListFilter,
+) -> Result<(Vec<SnapshotListRow>, i64)> {
+    let rows = sqlx::query_as!(
+        SnapshotListRow,
+        r#"SELECT s.id, s.label, s.started_at, s.completed_at, s.archived,
+           p.id AS "publication_id?", p.kind AS "publication_kind?", p.page_title AS "page_title?",
+           p.web_url AS "web_url?", p.published_at AS "published_at?"
+           FROM snapshot s
+           LEFT JOIN LATERAL (
+               SELECT id, kind, page_title, web_url, published_at FROM publication_history
+               WHERE snapshot_id = s.id AND diff_id IS NULL
+               ORDER BY published_at DESC LIMIT 1
+           ) p ON true
+           WHERE s.akeneo_server_id = $1 AND s.deleted_at IS NULL AND ($7 OR NOT s.archived)
+             AND ($2::text IS NULL OR s.label ILIKE '%' || $2 || '%')
+             AND ($3::timestamptz IS NULL OR s.completed_at >= $3)
+             AND ($4::timestamptz IS NULL OR s.completed_at < $4)
+           ORDER BY s.completed_at DESC, s.id
+           LIMIT $5 OFFSET $6"#,
+        akeneo_server_id,
+        filter.label,
+        filter.from,
+        filter.to,
+        filter.limit,
+        filter.offset,
+        filter.archived
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list snapshots")?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM snapshot s
+           WHERE s.akeneo_server_id = $1 AND s.deleted_at IS NULL AND ($5 OR NOT s.archived)
+             AND ($2::text IS NULL OR s.label ILIKE '%' || $2 || '%')
+             AND ($3::timestamptz IS NULL OR s.completed_at >= $3)
+             AND ($4::timestamptz IS NULL OR s.completed_at < $4)"#,
+        akeneo_server_id,
+        filter.label,
+        filter.from,
+        filter.to,
+        filter.archived
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count snapshots")?;
+
+    Ok((rows, total))
+}
+
+/// Fetch one page of an Akeneo server's diffs, newest after-snapshot first, with the
+/// latest publication of each. The label filter matches either snapshot's label and the
+/// date range applies to the after-snapshot.
+pub async fn list_diffs(
+    pool: &PgPool,
+    akeneo_server_id: Uuid,
+    filter: &ListFilter,
+) -> Result<(Vec<DiffListRow>, i64)> {
+    let rows = sqlx::query_as!(
+        DiffListRow,
+        r#"SELECT d.id, b.id AS before_id, b.label AS before_label,
+           a.id AS after_id, a.label AS after_label, a.completed_at AS after_completed_at,
+           p.id AS "publication_id?", p.kind AS "publication_kind?", p.page_title AS "page_title?",
+           p.web_url AS "web_url?", p.published_at AS "published_at?", p.alerts AS "alerts?"
+           FROM diff d
+           JOIN snapshot b ON b.id = d.snapshot_before_id
+           JOIN snapshot a ON a.id = d.snapshot_after_id
+           LEFT JOIN LATERAL (
+               SELECT id, kind, page_title, web_url, published_at, alerts FROM publication_history
+               WHERE diff_id = d.id ORDER BY published_at DESC LIMIT 1
+           ) p ON true
+           WHERE a.akeneo_server_id = $1
+             AND b.deleted_at IS NULL AND a.deleted_at IS NULL
+             AND ($7 OR NOT (b.archived OR a.archived))
+             AND ($2::text IS NULL OR b.label ILIKE '%' || $2 || '%' OR a.label ILIKE '%' || $2 || '%')
+             AND ($3::timestamptz IS NULL OR a.completed_at >= $3)
+             AND ($4::timestamptz IS NULL OR a.completed_at < $4)
+           ORDER BY a.completed_at DESC, d.id
+           LIMIT $5 OFFSET $6"#,
+        akeneo_server_id,
+        filter.label,
+        filter.from,
+        filter.to,
+        filter.limit,
+        filter.offset,
+        filter.archived
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list diffs")?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM diff d
+           JOIN snapshot b ON b.id = d.snapshot_before_id
+           JOIN snapshot a ON a.id = d.snapshot_after_id
+           WHERE a.akeneo_server_id = $1
+             AND b.deleted_at IS NULL AND a.deleted_at IS NULL
+             AND ($5 OR NOT (b.archived OR a.archived))
+             AND ($2::text IS NULL OR b.label ILIKE '%' || $2 || '%' OR a.label ILIKE '%' || $2 || '%')
+             AND ($3::timestamptz IS NULL OR a.completed_at >= $3)
+             AND ($4::timestamptz IS NULL OR a.completed_at < $4)"#,
+        akeneo_server_id,
+        filter.label,
+        filter.from,
+        filter.to,
+        filter.archived
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count diffs")?;
+
+    Ok((rows, total))
+}
+
+/// Fetch the ID of the most recently completed (live) snapshot for each Akeneo server.
+pub async fn fetch_latest_snapshot_ids(pool: &PgPool) -> Result<Vec<Uuid>> {
+    sqlx::query_scalar!(
+        "SELECT DISTINCT ON (akeneo_server_id) id FROM snapshot \
+         WHERE deleted_at IS NULL AND NOT archived \
+         ORDER BY akeneo_server_id, completed_at DESC",
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch latest snapshots")
+}
+
+/// Fetch the ID of the most recent diff for an Akeneo server (by after-snapshot completion).
+pub async fn fetch_latest_diff_id(pool: &PgPool, akeneo_server_id: Uuid) -> Result<Option<Uuid>> {
+    sqlx::query_scalar!(
+        "SELECT d.id FROM diff d JOIN snapshot s ON s.id = d.snapshot_after_id \
+         JOIN snapshot b ON b.id = d.snapshot_before_id \
+         WHERE s.akeneo_server_id = $1 AND s.deleted_at IS NULL AND b.deleted_at IS NULL \
+         ORDER BY s.completed_at DESC LIMIT 1",
+        akeneo_server_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch latest diff")
+}
+
+/// Fetch all publish schedules, with the name of their Akeneo server.
+pub async fn fetch_schedules(pool: &PgPool) -> Result<Vec<ScheduleRow>> {
+    sqlx::query_as!(
+        ScheduleRow,
+        "SELECT p.id, p.akeneo_server_id, s.name AS server_name, p.cron, p.action, p.enabled, \
+         p.last_run_at, p.last_status, p.last_error \
+         FROM publish_schedule p JOIN akeneo_server s ON s.id = p.akeneo_server_id \
+         ORDER BY s.name, p.created_at"
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch publish schedules")
+}
+
+/// Enable or disable a publish schedule. Fails with `RowNotFound` for an unknown ID.
+pub async fn set_schedule_enabled(pool: &PgPool, schedule_id: Uuid, enabled: bool) -> Result<()> {
+    let result = sqlx::query!(
+        "UPDATE publish_schedule SET enabled = $2 WHERE id = $1",
+        schedule_id,
+        enabled
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update publish schedule")?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::Error::new(sqlx::Error::RowNotFound)
+            .context(format!("Schedule not found: {}", schedule_id)));
+    }
+
+    Ok(())
+}
+
+/// Record the outcome of a scheduled run.
+pub async fn record_schedule_run(
+    pool: &PgPool,
+    schedule_id: Uuid,
+    ran_at: DateTime<Utc>,
+    status: &str,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE publish_schedule SET last_run_at = $2, last_status = $3, last_error = $4 WHERE id = $1",
+        schedule_id,
+        ran_at,
+        status,
+        error
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record schedule run")?;
+
+    Ok(())
+}
+
+/// An operation to record in `audit_log`.
+pub struct NewAuditEntry<'a> {
+    pub actor: &'a str,
+    pub endpoint: &'a str,
+    pub target_id: Option<Uuid>,
+    pub parameters: serde_json::Value,
+    pub result: &'a str,
+    pub error: Option<&'a str>,
+    pub page_urls: &'a [String],
+}
+
+/// A single `audit_log` row.
+pub struct AuditRecord {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub actor: String,
+    pub endpoint: String,
+    pub target_id: Option<Uuid>,
+    pub parameters: serde_json::Value,
+    pub result: String,
+    pub error: Option<String>,
+    pub page_urls: Vec<String>,
+}
+
+/// Append an entry to the audit log.
+pub async fn record_audit(pool: &PgPool, entry: NewAuditEntry<'_>) -> Result<()> {
+    sqlx::query!(
+        "INSERT INTO audit_log (actor, endpoint, target_id, parameters, result, error, page_urls) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        entry.actor,
+        entry.endpoint,
+        entry.target_id,
+        entry.parameters,
+        entry.result,
+        entry.error,
+        entry.page_urls
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record audit log entry")?;
+
+    Ok(())
+}
+
+/// Audit log entries created in `[since, until)`, oldest first.
+pub async fn list_audit(
+    pool: &PgPool,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<AuditRecord>> {
+    sqlx::query_as!(
+        AuditRecord,
+        "SELECT id, created_at, actor, endpoint, target_id, parameters, result, error, page_urls \
+         FROM audit_log \
+         WHERE ($1::timestamptz IS NULL OR created_at >= $1) \
+           AND ($2::timestamptz IS NULL OR created_at < $2) \
+         ORDER BY created_at, id \
+         LIMIT $3 OFFSET $4",
+        since,
+        until,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list audit log")
+}
+
+/// Delete audit log entries created before `cutoff`. Returns the number deleted.
+pub async fn prune_audit(pool: &PgPool, cutoff: DateTime<Utc>) -> Result<u64> {
+    let result = sqlx::query!("DELETE FROM audit_log WHERE created_at < $1", cutoff)
+        .execute(pool)
+        .await
+        .context("Failed to prune audit log")?;
+
+    Ok(result.rows_affected())
 }
@@ -1,17 +1,136 @@
 use anyhow::{Context, Result};
-use serde_json::Value;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::model::{nullable, AttributeOption, Snapshot};
+use crate::render_options::{AlertKind, AlertThreshold, IdentityKeys, ItemProperties};
 
 /// Represents the entire diff: a map of category names (e.g. "attributes", "families")
-/// to their respective diffs.
-pub type DiffReport = HashMap<String, CategoryDiff>;
+/// to their respective diffs. Ordered, like every collection the renderer walks, so the
+/// same diff always renders to the same bytes.
+pub type DiffReport = BTreeMap<String, CategoryDiff>;
 
 /// A diff for a single category, containing added, removed, and changed items.
+/// Removed/added pairs that look like the same item under a new code are moved out of
+/// `added` / `removed` into `renamed`, categories whose only change is their parent out of
+/// `changed` into `moved`.
 #[derive(Debug)]
 pub struct CategoryDiff {
     pub added: Vec<Value>,
     pub removed: Vec<Value>,
     pub changed: Vec<ChangedItem>,
+    pub renamed: Vec<RenamedItem>,
+    pub moved: Vec<MovedItem>,
+    /// Why changed items that could not be read were left out, one entry per item.
+    pub parse_warnings: Vec<String>,
+    /// Option changes per attribute, sorted by attribute code: the items of the
+    /// `attribute_options` category, or the `options` changes of changed attributes.
+    pub options: Vec<AttributeOptionsDiff>,
+    /// The category's identity keys, in the order `item_code` tries them.
+    identity: Vec<String>,
+}
+
+impl CategoryDiff {
+    /// The code identifying an added or removed item of this category: its first identity
+    /// key holding a string or number, or the whole item when it has none.
+    pub fn item_code(&self, item: &Value) -> String {
+        let keys: Vec<&str> = self.identity.iter().map(String::as_str).collect();
+        item_code(item, &keys)
+    }
+}
+
+/// The option changes of one attribute.
+#[derive(Debug)]
+pub struct AttributeOptionsDiff {
+    pub attribute: String,
+    pub changes: Vec<OptionChange>,
+}
+
+impl AttributeOptionsDiff {
+    /// Whether the options were only moved around: none added, removed or otherwise
+    /// changed. Such attributes render as a compact reordered list instead of a table.
+    pub fn is_pure_reordering(&self) -> bool {
+        !self.changes.is_empty()
+            && self
+                .changes
+                .iter()
+                .all(|c| matches!(c.kind, OptionChangeKind::Reordered { .. }))
+    }
+
+    /// The reordered options as (code, old position, new position), by new position.
+    pub fn reordered(&self) -> Vec<(&str, &str, &str)> {
+        let mut moves: Vec<(&str, &str, &str)> = self
+            .changes
+            .iter()
+            .filter_map(|change| match &change.kind {
+                OptionChangeKind::Reordered { from, to } => {
+                    Some((change.option.code.as_str(), from.as_str(), to.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        // Positions are sort orders or list positions, so compare them as numbers
+        moves.sort_by(|a, b| match (a.2.parse::<i64>(), b.2.parse::<i64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => a.2.cmp(b.2),
+        });
+        moves
+    }
+}
+
+/// A single option change, with whatever the diff carries about the option (at least its
+/// code; labels and sort order when the diff has full option objects).
+#[derive(Debug)]
+pub struct OptionChange {
+    pub option: AttributeOption,
+    pub kind: OptionChangeKind,
+}
+
+#[derive(Debug)]
+pub enum OptionChangeKind {
+    Added,
+    Removed,
+    /// Moved in the option order, from one position (sort order, or 1-based list
+    /// position) to another.
+    Reordered { from: String, to: String },
+    /// Other field changes, such as labels.
+    Changed(Vec<FieldChange>),
+}
+
+/// A removed item and an added item paired as a probable rename (recreated under a new code).
+#[derive(Debug)]
+pub struct RenamedItem {
+    pub old_code: String,
+    pub new_code: String,
+    pub matched_on: RenameMatch,
+}
+
+/// A category that only changed its parent: moved to another place in its tree.
+#[derive(Debug)]
+pub struct MovedItem {
+    pub code: String,
+    /// Parent codes before and after; `None` for a root category.
+    pub old_parent: Option<String>,
+    pub new_parent: Option<String>,
+}
+
+/// Which heuristic paired a rename.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameMatch {
+    /// Identical labels (and identical type/group/parent where present).
+    Labels,
+    /// The only removed and the only added item with the same type and group.
+    TypeAndGroup,
+}
+
+impl RenameMatch {
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Labels => "Same labels",
+            Self::TypeAndGroup => "Same type and group",
+        }
+    }
 }
 
 /// An item that was changed, identified by its code, with a set of field-level changes
@@ -19,12 +138,17 @@ pub struct CategoryDiff {
 #[derive(Debug)]
 pub struct ChangedItem {
     pub code: String,
+    /// The attribute of a changed attribute option, when the diff names it.
+    pub attribute: Option<String>,
     pub changes: Vec<FieldChange>,
     pub nested_diffs: Vec<NestedFieldDiff>,
+    /// An attribute's `options` change, taken out of `nested_diffs` / `changes` and
+    /// moved to `CategoryDiff::options` by `parse_diff_data`.
+    options: Vec<OptionChange>,
 }
 
 /// A single field-level change, with a dotted path (e.g. "labels.en_US"), old value, and new value.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FieldChange {
     pub field_path: String,
     pub old: String,
@@ -40,41 +164,210 @@ pub struct NestedFieldDiff {
     pub removed: Vec<String>,
 }
 
+/// Categories whose removals break downstream consumers: imports, exports and
+/// integrations reference attributes and families by code.
+const BREAKING_CATEGORIES: &[&str] = &["attributes", "families"];
+
+/// Codes that disappeared from a breaking category, either removed outright or renamed
+/// (the old code no longer resolves).
+pub struct BreakingChange {
+    pub category: &'static str,
+    pub removed: Vec<String>,
+    /// (old code, new code) pairs.
+    pub renamed: Vec<(String, String)>,
+}
+
+/// A category whose changes crossed an alert threshold, e.g. 85% of its attributes removed.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffAlert {
+    pub category: String,
+    /// `added`, `removed` or `changed`.
+    pub kind: &'static str,
+    /// Items of that kind in the diff.
+    pub count: usize,
+    /// Items of the category in the before-snapshot.
+    pub total: usize,
+    /// `count` as a percentage of `total`.
+    pub percent: f64,
+    /// The threshold that was crossed, in percent.
+    pub threshold: f64,
+}
+
+/// Changed items of the report left out because they could not be read.
+pub fn skipped_changed_items(report: &DiffReport) -> usize {
+    report.values().map(|diff| diff.parse_warnings.len()).sum()
+}
+
+/// Check every category against the alert thresholds, relative to its item count in the
+/// before-snapshot (`before_counts`). A category-specific threshold replaces the `*`
+/// threshold of the same kind; categories the before-snapshot has no items in never alert.
+pub fn threshold_alerts(
+    report: &DiffReport,
+    before_counts: &BTreeMap<String, usize>,
+    thresholds: &[AlertThreshold],
+) -> Vec<DiffAlert> {
+    let mut alerts = Vec::new();
+
+    for (category, diff) in report {
+        let total = before_counts.get(category).copied().unwrap_or(0);
+        if total == 0 {
+            continue;
+        }
+        for kind in [AlertKind::Added, AlertKind::Removed, AlertKind::Changed] {
+            let applicable = thresholds.iter().filter(|t| t.kind == kind);
+            let Some(threshold) = applicable
+                .clone()
+                .find(|t| t.category == *category)
+                .or_else(|| applicable.clone().find(|t| t.category == "*"))
+            else {
+                continue;
+            };
+            let count = match kind {
+                AlertKind::Added => diff.added.len(),
+                AlertKind::Removed => diff.removed.len(),
+                AlertKind::Changed => diff.changed.len() + diff.moved.len(),
+            };
+            let percent = count as f64 * 100.0 / total as f64;
+            if count > 0 && percent >= threshold.percent {
+                alerts.push(DiffAlert {
+                    category: category.clone(),
+                    kind: kind.as_str(),
+                    count,
+                    total,
+                    percent,
+                    threshold: threshold.percent,
+                });
+            }
+        }
+    }
+
+    alerts
+}
+
+/// How many codes a description lists before summarising the rest as "and N more".
+const DESCRIBED_CODES: usize = 10;
+
+/// Language names for label locales (`en_US` -> "English (US)"); other languages are
+/// shown by their code.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("cs", "Czech"),
+    ("da", "Danish"),
+    ("de", "German"),
+    ("en", "English"),
+    ("es", "Spanish"),
+    ("fi", "Finnish"),
+    ("fr", "French"),
+    ("it", "Italian"),
+    ("ja", "Japanese"),
+    ("nb", "Norwegian"),
+    ("nl", "Dutch"),
+    ("pl", "Polish"),
+    ("pt", "Portuguese"),
+    ("ru", "Russian"),
+    ("sv", "Swedish"),
+    ("zh", "Chinese"),
+];
+
+/// Raw shape of a single category in the diff JSON.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawCategoryDiff {
+    #[serde(deserialize_with = "nullable")]
+    added: Vec<Value>,
+    #[serde(deserialize_with = "nullable")]
+    removed: Vec<Value>,
+    #[serde(deserialize_with = "nullable")]
+    changed: Vec<Value>,
+}
+
+/// Raw shape of a changed item: a tree of changes (the identity key is read separately).
+#[derive(Deserialize)]
+struct RawChangedItem {
+    #[serde(default)]
+    attribute: Option<String>,
+    changes: Map<String, Value>,
+}
+
 /// Parse diff data from a JSON value (typically the `data` JSONB column from the database).
-pub fn parse_diff_data(root: &Value) -> Result<DiffReport> {
+///
+/// A malformed category is left out of the report with a warning (the second element)
+/// rather than failing the whole diff; only a root that is not an object is an error.
+/// Changed items are identified by the first of the category's `identity` keys they have;
+/// those with none are listed by their position (`#3`), unreadable ones are left out and
+/// noted in the category's `parse_warnings`.
+pub fn parse_diff_data(
+    root: &Value,
+    identity: &IdentityKeys,
+) -> Result<(DiffReport, Vec<String>)> {
     let obj = root
         .as_object()
         .context("Diff data root must be an object")?;
 
     let mut report = DiffReport::new();
+    let mut warnings = Vec::new();
 
     for (category_name, category_value) in obj {
-        let cat_obj = category_value
-            .as_object()
-            .with_context(|| format!("Category '{}' must be an object", category_name))?;
-
-        let added = cat_obj
-            .get("added")
-            .and_then(|v| v.as_array())
-            .cloned()
-            .unwrap_or_default();
+        if !category_value.is_object() {
+            warnings.push(format!(
+                "Category '{}' was skipped: expected an object of added, removed and changed \
+                 lists, found {}",
+                category_name,
+                json_type(category_value)
+            ));
+            continue;
+        }
+        let raw: RawCategoryDiff = match serde_path_to_error::deserialize(category_value) {
+            Ok(raw) => raw,
+            Err(e) => {
+                warnings.push(format!("Category '{}' was skipped: {}", category_name, e));
+                continue;
+            }
+        };
 
-        let removed = cat_obj
-            .get("removed")
-            .and_then(|v| v.as_array())
-            .cloned()
-            .unwrap_or_default();
+        let keys = identity.chain(category_name);
+        let mut changed: Vec<ChangedItem> = Vec::new();
+        let mut parse_warnings = Vec::new();
+        for (index, value) in raw.changed.into_iter().enumerate() {
+            match parse_changed_item(value, &keys) {
+                Ok(mut item) => {
+                    if item.code.is_empty() {
+                        item.code = format!("#{}", index + 1);
+                    }
+                    changed.push(item);
+                }
+                Err(e) => {
+                    parse_warnings.push(format!("Changed item #{} skipped: {}", index + 1, e))
+                }
+            }
+        }
 
-        let changed_raw = cat_obj
-            .get("changed")
-            .and_then(|v| v.as_array())
-            .cloned()
-            .unwrap_or_default();
+        // Item order in the stored diff is not meaningful; sort by code for stable pages
+        changed.sort_by(|a, b| a.code.cmp(&b.code));
+        let mut added = raw.added;
+        let mut removed = raw.removed;
+        added.sort_by_cached_key(|item| item_code(item, &keys));
+        removed.sort_by_cached_key(|item| item_code(item, &keys));
+        let renamed = detect_renames(&mut added, &mut removed, &keys);
+        let moved = if category_name == CATEGORIES_CATEGORY {
+            take_moves(&mut changed)
+        } else {
+            Vec::new()
+        };
 
-        let changed = changed_raw
-            .into_iter()
-            .filter_map(|item| parse_changed_item(&item))
-            .collect();
+        let options = if category_name == OPTIONS_CATEGORY {
+            collect_option_changes(&added, &removed, &changed)
+        } else {
+            let mut options: Vec<AttributeOptionsDiff> = changed
+                .iter_mut()
+                .filter(|item| !item.options.is_empty())
+                .map(|item| AttributeOptionsDiff {
+                    attribute: item.code.clone(),
+                    changes: std::mem::take(&mut item.options),
+                })
+                .collect();
+            options.sort_by(|a, b| a.attribute.cmp(&b.attribute));
+            options
+        };
 
         report.insert(
             category_name.clone(),
@@ -82,33 +375,643 @@ pub fn parse_diff_data(root: &Value) -> Result<DiffReport> {
                 added,
                 removed,
                 changed,
+                renamed,
+                moved,
+                options,
+                parse_warnings,
+                identity: keys.iter().map(|key| key.to_string()).collect(),
             },
         );
     }
 
-    Ok(report)
+    Ok((report, warnings))
 }
 
-/// Parse a single changed item from the JSON value.
-fn parse_changed_item(value: &Value) -> Option<ChangedItem> {
-    let obj = value.as_object()?;
-    let code = obj.get("code")?.as_str()?.to_string();
-    let changes_value = obj.get("changes")?;
-    let changes_obj = changes_value.as_object()?;
+/// The categories a diff publish is limited to, from the `include` / `exclude` request
+/// parameters (comma-separated category names, matched case-insensitively).
+#[derive(Debug, Default)]
+pub struct CategoryFilter {
+    /// Only these categories; every category when `None`.
+    include: Option<BTreeSet<String>>,
+    exclude: BTreeSet<String>,
+}
+
+impl CategoryFilter {
+    /// The filter of a request, `None` when neither list names a category.
+    pub fn parse(include: Option<&str>, exclude: Option<&str>) -> Option<Self> {
+        let names = |list: &str| -> BTreeSet<String> {
+            list.split(',')
+                .map(|name| name.trim().to_lowercase())
+                .filter(|name| !name.is_empty())
+                .collect()
+        };
+        let include = include.map(names).filter(|names| !names.is_empty());
+        let exclude = exclude.map(names).unwrap_or_default();
+        if include.is_none() && exclude.is_empty() {
+            return None;
+        }
+        Some(Self { include, exclude })
+    }
+
+    pub fn allows(&self, category: &str) -> bool {
+        let category = category.to_lowercase();
+        self.include.as_ref().is_none_or(|names| names.contains(&category))
+            && !self.exclude.contains(&category)
+    }
+
+    /// Take the categories the filter leaves out of a report, returning them.
+    pub fn split_off(&self, report: &mut DiffReport) -> DiffReport {
+        let (kept, left_out) = std::mem::take(report)
+            .into_iter()
+            .partition(|(category, _)| self.allows(category));
+        *report = kept;
+        left_out
+    }
+}
+
+/// The JSON type of a value, for messages.
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Fields that must be equal (when present on both items) for a label match to count as a
+/// rename: an attribute that changed type is not the same attribute.
+const RENAME_IDENTITY_FIELDS: &[&str] = &["type", "group", "parent", "attribute"];
+
+/// Pair removed and added items that are probably the same item recreated under a new
+/// code, and take the pairs out of `added` / `removed`.
+///
+/// Two heuristics, applied in order, each only when the match is unambiguous (exactly
+/// one removed and one added candidate):
+/// 1. identical non-empty `labels`, with equal type/group/parent where present;
+/// 2. identical `type` and `group`.
+fn detect_renames(
+    added: &mut Vec<Value>,
+    removed: &mut Vec<Value>,
+    keys: &[&str],
+) -> Vec<RenamedItem> {
+    let mut renamed = Vec::new();
+
+    for matched_on in [RenameMatch::Labels, RenameMatch::TypeAndGroup] {
+        let key = |item: &Value| rename_key(item, matched_on);
+
+        let mut removed_keys: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, item) in removed.iter().enumerate() {
+            if let Some(k) = key(item) {
+                removed_keys.entry(k).or_default().push(i);
+            }
+        }
+        let mut added_keys: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, item) in added.iter().enumerate() {
+            if let Some(k) = key(item) {
+                added_keys.entry(k).or_default().push(i);
+            }
+        }
+
+        let mut pairs: Vec<(usize, usize)> = removed_keys
+            .iter()
+            .filter_map(|(k, removed_idx)| match (removed_idx.as_slice(), added_keys.get(k)) {
+                ([r], Some(added_idx)) if added_idx.len() == 1 => Some((*r, added_idx[0])),
+                _ => None,
+            })
+            .collect();
+        if pairs.is_empty() {
+            continue;
+        }
+
+        pairs.sort_unstable();
+        for &(r, a) in &pairs {
+            renamed.push(RenamedItem {
+                old_code: item_code(&removed[r], keys),
+                new_code: item_code(&added[a], keys),
+                matched_on,
+            });
+        }
+
+        // Remove paired items, highest index first so earlier indices stay valid
+        let mut removed_idx: Vec<usize> = pairs.iter().map(|&(r, _)| r).collect();
+        let mut added_idx: Vec<usize> = pairs.iter().map(|&(_, a)| a).collect();
+        removed_idx.sort_unstable_by(|a, b| b.cmp(a));
+        added_idx.sort_unstable_by(|a, b| b.cmp(a));
+        for i in removed_idx {
+            removed.remove(i);
+        }
+        for i in added_idx {
+            added.remove(i);
+        }
+    }
+
+    renamed.sort_by(|a, b| a.old_code.cmp(&b.old_code));
+    renamed
+}
+
+/// The value two items must share to be paired by a heuristic, or `None` if the item
+/// lacks the fields the heuristic needs.
+fn rename_key(item: &Value, matched_on: RenameMatch) -> Option<String> {
+    let obj = item.as_object()?;
+    match matched_on {
+        RenameMatch::Labels => {
+            let labels: BTreeMap<&str, &str> = obj
+                .get("labels")?
+                .as_object()?
+                .iter()
+                .filter_map(|(locale, label)| Some((locale.as_str(), label.as_str()?)))
+                .filter(|(_, label)| !label.is_empty())
+                .collect();
+            if labels.is_empty() {
+                return None;
+            }
+            let identity: Vec<String> = RENAME_IDENTITY_FIELDS
+                .iter()
+                .map(|f| obj.get(*f).map(format_value).unwrap_or_default())
+                .collect();
+            Some(format!("{:?}|{:?}", labels, identity))
+        }
+        RenameMatch::TypeAndGroup => {
+            let item_type = obj.get("type")?.as_str()?;
+            let group = obj.get("group")?.as_str()?;
+            Some(format!("{}|{}", item_type, group))
+        }
+    }
+}
+
+/// The breaking changes of a diff, in `BREAKING_CATEGORIES` order with sorted codes.
+/// Empty when the diff only adds or changes items.
+pub fn breaking_changes(report: &DiffReport) -> Vec<BreakingChange> {
+    BREAKING_CATEGORIES
+        .iter()
+        .filter_map(|&category| {
+            let diff = report.get(category)?;
+            let mut removed: Vec<String> = diff.removed.iter().map(|i| diff.item_code(i)).collect();
+            removed.sort();
+            let mut renamed: Vec<(String, String)> = diff
+                .renamed
+                .iter()
+                .map(|r| (r.old_code.clone(), r.new_code.clone()))
+                .collect();
+            renamed.sort();
+            (!removed.is_empty() || !renamed.is_empty()).then_some(BreakingChange {
+                category,
+                removed,
+                renamed,
+            })
+        })
+        .collect()
+}
+
+/// Readable sentences describing a category's changes, e.g. "3 attributes added to family
+/// shoes: a, b, c" or "English (US) label of attribute sku changed from 'Foo' to 'Bar'".
+///
+/// Additions, removals and renames are each grouped into one sentence; every changed item
+/// gets its own sentences, in item order. Sentences are plain text (not escaped).
+pub fn describe_changes(category: &str, diff: &CategoryDiff) -> Vec<String> {
+    let noun = category.replace('_', " ");
+    let mut out = Vec::new();
+
+    // Option changes read better per attribute ("2 options added to attribute color")
+    if category == OPTIONS_CATEGORY && !diff.options.is_empty() {
+        describe_option_changes(&diff.options, &mut out);
+        return out;
+    }
+
+    for (items, verb) in [(&diff.added, "added"), (&diff.removed, "removed")] {
+        if !items.is_empty() {
+            let codes: Vec<String> = items.iter().map(|i| diff.item_code(i)).collect();
+            out.push(format!(
+                "{} {}: {}",
+                counted(items.len(), &noun),
+                verb,
+                describe_codes(&codes)
+            ));
+        }
+    }
+    if !diff.renamed.is_empty() {
+        let pairs: Vec<String> = diff
+            .renamed
+            .iter()
+            .map(|r| format!("{} \u{2192} {}", r.old_code, r.new_code))
+            .collect();
+        out.push(format!(
+            "{} renamed: {}",
+            counted(diff.renamed.len(), &noun),
+            describe_codes(&pairs)
+        ));
+    }
+
+    if !diff.moved.is_empty() {
+        let parent = |code: &Option<String>| code.clone().unwrap_or_else(|| "root".to_string());
+        let moves: Vec<String> = diff
+            .moved
+            .iter()
+            .map(|m| {
+                format!(
+                    "{} ({} \u{2192} {})",
+                    m.code,
+                    parent(&m.old_parent),
+                    parent(&m.new_parent)
+                )
+            })
+            .collect();
+        out.push(format!(
+            "{} moved: {}",
+            counted(diff.moved.len(), &noun),
+            describe_codes(&moves)
+        ));
+    }
+
+    let subject_noun = singular(&noun);
+    for item in &diff.changed {
+        let subject = format!("{} {}", subject_noun, item.code);
+        for change in &item.changes {
+            let field = describe_field(&change.field_path);
+            out.push(match (is_blank(&change.old), is_blank(&change.new)) {
+                (true, false) => format!("{} of {} set to '{}'", field, subject, change.new),
+                (false, true) => format!("{} of {} cleared (was '{}')", field, subject, change.old),
+                _ => format!(
+                    "{} of {} changed from '{}' to '{}'",
+                    field, subject, change.old, change.new
+                ),
+            });
+        }
+        for nested in &item.nested_diffs {
+            let field = describe_field(&nested.field_path).to_lowercase();
+            for (codes, verb) in [(&nested.added, "added to"), (&nested.removed, "removed from")] {
+                if !codes.is_empty() {
+                    out.push(format!(
+                        "{} {} {}: {}",
+                        counted(codes.len(), &field),
+                        verb,
+                        subject,
+                        describe_codes(codes)
+                    ));
+                }
+            }
+        }
+    }
+    describe_option_changes(&diff.options, &mut out);
+
+    out
+}
+
+/// Per attribute: one sentence each for added, removed, reordered and otherwise changed
+/// options. Reordered options carry their old → new position.
+fn describe_option_changes(options: &[AttributeOptionsDiff], out: &mut Vec<String>) {
+    for attribute in options {
+        let reordered: Vec<String> = attribute
+            .reordered()
+            .into_iter()
+            .map(|(code, from, to)| format!("{} ({} \u{2192} {})", code, from, to))
+            .collect();
+        if attribute.is_pure_reordering() {
+            out.push(format!(
+                "Options of attribute {} reordered: {}",
+                attribute.attribute,
+                describe_codes(&reordered)
+            ));
+            continue;
+        }
+        let mut groups: [(&str, Vec<String>); 4] = [
+            ("added to", Vec::new()),
+            ("removed from", Vec::new()),
+            ("reordered in", reordered),
+            ("changed in", Vec::new()),
+        ];
+        for change in &attribute.changes {
+            let group = match change.kind {
+                OptionChangeKind::Added => 0,
+                OptionChangeKind::Removed => 1,
+                OptionChangeKind::Reordered { .. } => continue,
+                OptionChangeKind::Changed(_) => 3,
+            };
+            groups[group].1.push(change.option.code.clone());
+        }
+        for (verb, codes) in groups {
+            if !codes.is_empty() {
+                out.push(format!(
+                    "{} {} attribute {}: {}",
+                    counted(codes.len(), "options"),
+                    verb,
+                    attribute.attribute,
+                    describe_codes(&codes)
+                ));
+            }
+        }
+    }
+}
+
+/// "1 attribute" / "3 attributes".
+fn counted(count: usize, plural: &str) -> String {
+    if count == 1 {
+        format!("1 {}", singular(plural))
+    } else {
+        format!("{} {}", count, plural)
+    }
+}
+
+/// Singular of an English plural noun phrase ("families" -> "family").
+fn singular(plural: &str) -> String {
+    if let Some(stem) = plural.strip_suffix("ies") {
+        format!("{}y", stem)
+    } else if let Some(stem) = plural.strip_suffix("ses") {
+        format!("{}s", stem)
+    } else {
+        plural.strip_suffix('s').unwrap_or(plural).to_string()
+    }
+}
+
+/// Comma-separated codes, the tail summarised after `DESCRIBED_CODES`.
+fn describe_codes(codes: &[String]) -> String {
+    let mut list = codes
+        .iter()
+        .take(DESCRIBED_CODES)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if codes.len() > DESCRIBED_CODES {
+        list.push_str(&format!(" and {} more", codes.len() - DESCRIBED_CODES));
+    }
+    list
+}
+
+/// A readable name for a dotted field path: `labels.en_US` -> "English (US) label",
+/// `attribute_requirements.ecommerce` -> "Ecommerce attribute requirements",
+/// `validation_rule` -> "Validation rule".
+pub fn describe_field(path: &str) -> String {
+    let name = match path.split_once('.') {
+        Some(("labels", locale)) => format!("{} label", locale_name(locale)),
+        Some((field, key)) => format!("{} {}", key, field.replace(['_', '.'], " ")),
+        None => path.replace('_', " "),
+    };
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+/// "English (US)" for `en_US`; unknown languages keep their code.
+fn locale_name(locale: &str) -> String {
+    let (language, region) = locale.split_once('_').unwrap_or((locale, ""));
+    match LANGUAGES.iter().find(|(code, _)| *code == language) {
+        Some((_, name)) if region.is_empty() => name.to_string(),
+        Some((_, name)) => format!("{} ({})", name, region),
+        None => locale.to_string(),
+    }
+}
+
+fn is_blank(value: &str) -> bool {
+    value.is_empty() || value == "null"
+}
+
+/// The first of `keys` an item has as a string or number, formatted.
+pub fn item_identity(item: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .filter_map(|key| item.get(*key))
+        .find(|value| value.is_string() || value.is_number())
+        .map(format_value)
+}
+
+fn item_code(item: &Value, keys: &[&str]) -> String {
+    item_identity(item, keys).unwrap_or_else(|| item.to_string())
+}
+
+/// Parse a single changed item from the JSON value, identified by the first of `keys` it
+/// has as a string or number (an empty code when it has none).
+fn parse_changed_item(value: Value, keys: &[&str]) -> Result<ChangedItem, serde_json::Error> {
+    let code = item_identity(&value, keys).unwrap_or_default();
+    let mut raw: RawChangedItem = serde_json::from_value(value)?;
+
+    // An attribute's option list gets its own table instead of an opaque nested row
+    let options = match raw.changes.get("options").and_then(parse_option_list_change) {
+        Some(options) => {
+            raw.changes.remove("options");
+            options
+        }
+        None => Vec::new(),
+    };
 
     let mut changes = Vec::new();
     let mut nested_diffs = Vec::new();
-    for (field_name, field_value) in changes_obj {
+    for (field_name, field_value) in &raw.changes {
         flatten_changes(field_name, field_value, &mut changes, &mut nested_diffs);
     }
 
-    Some(ChangedItem {
+    Ok(ChangedItem {
         code,
+        attribute: raw.attribute,
         changes,
         nested_diffs,
+        options,
     })
 }
 
+/// The category that holds option changes of every attribute.
+const OPTIONS_CATEGORY: &str = "attribute_options";
+
+/// The category whose parent-only changes are moves.
+const CATEGORIES_CATEGORY: &str = "categories";
+
+/// Take the changed categories whose only change is `parent` out of `changed`, as moves.
+fn take_moves(changed: &mut Vec<ChangedItem>) -> Vec<MovedItem> {
+    let parent = |value: &str| (!is_blank(value)).then(|| value.to_string());
+    let mut moved = Vec::new();
+    changed.retain(|item| {
+        let [change] = item.changes.as_slice() else {
+            return true;
+        };
+        if change.field_path != "parent" || !item.nested_diffs.is_empty() {
+            return true;
+        }
+        moved.push(MovedItem {
+            code: item.code.clone(),
+            old_parent: parent(&change.old),
+            new_parent: parent(&change.new),
+        });
+        false
+    });
+    moved
+}
+
+/// Group the items of the `attribute_options` category by attribute. Options name their
+/// attribute in an `attribute` field, or with an `attribute/option` code (the key used by
+/// `compare_snapshots`).
+fn collect_option_changes(
+    added: &[Value],
+    removed: &[Value],
+    changed: &[ChangedItem],
+) -> Vec<AttributeOptionsDiff> {
+    let mut by_attribute: BTreeMap<String, Vec<OptionChange>> = BTreeMap::new();
+    let mut push = |attribute: Option<String>, mut option: AttributeOption, kind| {
+        let attribute = match attribute {
+            Some(attribute) => attribute,
+            None => match option.code.split_once('/') {
+                Some((attribute, code)) => {
+                    let attribute = attribute.to_string();
+                    option.code = code.to_string();
+                    attribute
+                }
+                None => "unknown".to_string(),
+            },
+        };
+        by_attribute
+            .entry(attribute)
+            .or_default()
+            .push(OptionChange { option, kind });
+    };
+
+    for value in added {
+        let option = option_entry(value);
+        push(option.attribute.clone(), option, OptionChangeKind::Added);
+    }
+    for value in removed {
+        let option = option_entry(value);
+        push(option.attribute.clone(), option, OptionChangeKind::Removed);
+    }
+    for item in changed {
+        let option = AttributeOption {
+            code: item.code.clone(),
+            ..Default::default()
+        };
+        let (sort_order, others): (Vec<_>, Vec<_>) = item
+            .changes
+            .iter()
+            .partition(|c| c.field_path == "sort_order");
+        if let Some(change) = sort_order.first() {
+            push(
+                item.attribute.clone(),
+                option.clone(),
+                OptionChangeKind::Reordered {
+                    from: change.old.clone(),
+                    to: change.new.clone(),
+                },
+            );
+        }
+        if !others.is_empty() {
+            let changes = others.into_iter().cloned().collect();
+            push(
+                item.attribute.clone(),
+                option,
+                OptionChangeKind::Changed(changes),
+            );
+        }
+    }
+
+    by_attribute
+        .into_iter()
+        .map(|(attribute, changes)| AttributeOptionsDiff { attribute, changes })
+        .collect()
+}
+
+/// Parse an attribute's `options` change: a `{added, removed}` sub-diff, or the whole
+/// list as `{old, new}`, in which case options that kept their place among the common
+/// options are left out and moved ones are reported as reordered. `None` for any other
+/// shape, which is then shown as ordinary field changes.
+fn parse_option_list_change(value: &Value) -> Option<Vec<OptionChange>> {
+    let obj = value.as_object()?;
+    let entries = |key: &str| -> Option<Vec<AttributeOption>> {
+        obj.get(key)
+            .map(|v| v.as_array().map(|arr| arr.iter().map(option_entry).collect()))
+            .unwrap_or(Some(Vec::new()))
+    };
+
+    if obj.contains_key("old") && obj.contains_key("new") {
+        let old = obj["old"].as_array()?;
+        let new = obj["new"].as_array()?;
+        let old: Vec<AttributeOption> = old.iter().map(option_entry).collect();
+        let new: Vec<AttributeOption> = new.iter().map(option_entry).collect();
+        let old_codes: BTreeSet<&str> = old.iter().map(|o| o.code.as_str()).collect();
+        let new_codes: BTreeSet<&str> = new.iter().map(|o| o.code.as_str()).collect();
+
+        // Positions among the options present in both lists, so additions and removals
+        // do not count as moves
+        let common_old: Vec<&str> = old
+            .iter()
+            .map(|o| o.code.as_str())
+            .filter(|c| new_codes.contains(c))
+            .collect();
+        let common_new: Vec<&str> = new
+            .iter()
+            .map(|o| o.code.as_str())
+            .filter(|c| old_codes.contains(c))
+            .collect();
+
+        let mut out = Vec::new();
+        for (position, option) in new.iter().enumerate() {
+            if !old_codes.contains(option.code.as_str()) {
+                out.push(OptionChange {
+                    option: option.clone(),
+                    kind: OptionChangeKind::Added,
+                });
+                continue;
+            }
+            let moved = common_old.iter().position(|c| *c == option.code)
+                != common_new.iter().position(|c| *c == option.code);
+            if moved {
+                let from = old.iter().position(|o| o.code == option.code).unwrap_or(0);
+                out.push(OptionChange {
+                    option: option.clone(),
+                    kind: OptionChangeKind::Reordered {
+                        from: (from + 1).to_string(),
+                        to: (position + 1).to_string(),
+                    },
+                });
+            }
+        }
+        out.extend(
+            old.into_iter()
+                .filter(|o| !new_codes.contains(o.code.as_str()))
+                .map(|option| OptionChange {
+                    option,
+                    kind: OptionChangeKind::Removed,
+                }),
+        );
+        return Some(out);
+    }
+
+    if obj.contains_key("added") || obj.contains_key("removed") {
+        let added = entries("added")?;
+        let removed = entries("removed")?;
+        return Some(
+            added
+                .into_iter()
+                .map(|option| OptionChange {
+                    option,
+                    kind: OptionChangeKind::Added,
+                })
+                .chain(removed.into_iter().map(|option| OptionChange {
+                    option,
+                    kind: OptionChangeKind::Removed,
+                }))
+                .collect(),
+        );
+    }
+
+    None
+}
+
+/// An option from a diff value: a full option object, or just its code.
+fn option_entry(value: &Value) -> AttributeOption {
+    match value {
+        Value::Object(_) => serde_json::from_value(value.clone()).unwrap_or_else(|_| {
+            AttributeOption {
+                code: item_code(value, &["code"]),
+                ..Default::default()
+            }
+        }),
+        other => AttributeOption {
+            code: format_value(other),
+            ..Default::default()
+        },
+    }
+}
+
 /// Recursively flatten nested change objects into a flat list of `FieldChange`,
 /// and collect any nested sub-diffs (added/removed arrays) into `NestedFieldDiff`.
 ///
@@ -181,54 +1084,34 @@ fn format_value(value: &Value) -> String {
     }
 }
 
-/// Extract a human-readable summary of key properties from an added/removed item.
+/// Extract a human-readable summary of key properties from an added/removed item: the
+/// priority fields, the labels, then the other fields `fields` does not skip.
 /// Returns a list of (key, value) pairs for display in a table.
-pub fn extract_item_properties(item: &Value) -> Vec<(String, String)> {
+pub fn extract_item_properties(item: &Value, fields: &ItemProperties) -> Vec<(String, String)> {
     let Some(obj) = item.as_object() else {
         return vec![("value".to_string(), item.to_string())];
     };
+    let skipped = |field: &str| fields.skip_fields.iter().any(|f| f == field);
 
     // Priority fields to show first (in order)
-    let priority_fields = ["code", "type", "group"];
     let mut props = Vec::new();
 
-    for &field in &priority_fields {
-        if let Some(val) = obj.get(field) && !val.is_null() {
-            props.push((field.to_string(), format_value(val)));
+    for field in &fields.priority_fields {
+        if let Some(val) = obj.get(field) && !val.is_null() && !skipped(field) {
+            props.push((field.clone(), format_value(val)));
         }
     }
 
     // Extract labels (flatten the labels object)
-    if let Some(labels) = obj.get("labels").and_then(|v| v.as_object()) {
+    if let Some(labels) = obj.get("labels").and_then(|v| v.as_object()) && !skipped("labels") {
         for (locale, label_val) in labels {
             props.push((format!("label ({})", locale), format_value(label_val)));
         }
     }
 
     // Add other notable non-null, non-default fields
-    let skip_fields = [
-        "code",
-        "type",
-        "group",
-        "labels",
-        "group_labels",
-        "attributes",
-        "decimal_places",
-        "default_value",
-        "display_time",
-        "is_read_only",
-        "max_characters",
-        "max_file_size",
-        "max_items_count",
-        "minimum_input_length",
-        "number_max",
-        "number_min",
-        "reference_data_name",
-        "validation_rule",
-    ];
-
     for (key, val) in obj {
-        if skip_fields.contains(&key.as_str()) {
+        if key == "labels" || skipped(key) || fields.priority_fields.contains(key) {
             continue;
         }
         if val.is_null() {
@@ -249,3 +1132,90 @@ pub fn extract_item_properties(item: &Value) -> Vec<(String, String)> {
 
     props
 }
+
+/// Added / removed / changed item counts of one category in `compare_snapshots`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeCounts {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+impl ChangeCounts {
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0 && self.changed == 0
+    }
+}
+
+/// Quick per-category comparison of two snapshots by item code, without field-level
+/// detail. Used for the "changes since last publish" banner, where a stored diff row may
+/// not exist for the pair of snapshots.
+pub fn compare_snapshots(
+    before: &Snapshot,
+    after: &Snapshot,
+) -> Vec<(&'static str, ChangeCounts)> {
+    fn keyed<'a, T: Serialize + 'a>(
+        items: impl Iterator<Item = (String, &'a T)>,
+    ) -> BTreeMap<String, Value> {
+        items
+            .map(|(key, item)| (key, serde_json::to_value(item).unwrap_or(Value::Null)))
+            .collect()
+    }
+
+    fn count(before: BTreeMap<String, Value>, after: BTreeMap<String, Value>) -> ChangeCounts {
+        let mut counts = ChangeCounts::default();
+        for (key, value) in &after {
+            match before.get(key) {
+                None => counts.added += 1,
+                Some(old) if old != value => counts.changed += 1,
+                Some(_) => {}
+            }
+        }
+        counts.removed = before.keys().filter(|k| !after.contains_key(*k)).count();
+        counts
+    }
+
+    fn by_code<T: Serialize>(items: &[T], code: fn(&T) -> &str) -> BTreeMap<String, Value> {
+        keyed(items.iter().map(|item| (code(item).to_string(), item)))
+    }
+
+    fn options(snapshot: &Snapshot) -> BTreeMap<String, Value> {
+        keyed(snapshot.attribute_options.iter().flat_map(|(attr, options)| {
+            options
+                .iter()
+                .map(move |opt| (format!("{}/{}", attr, opt.code), opt))
+        }))
+    }
+
+    vec![
+        (
+            "channels",
+            count(
+                by_code(&before.channels, |c| &c.code),
+                by_code(&after.channels, |c| &c.code),
+            ),
+        ),
+        (
+            "families",
+            count(
+                by_code(&before.families, |f| &f.code),
+                by_code(&after.families, |f| &f.code),
+            ),
+        ),
+        (
+            "attributes",
+            count(
+                by_code(&before.attributes, |a| &a.code),
+                by_code(&after.attributes, |a| &a.code),
+            ),
+        ),
+        (
+            "categories",
+            count(
+                by_code(&before.categories, |c| &c.code),
+                by_code(&after.categories, |c| &c.code),
+            ),
+        ),
+        ("attribute_options", count(options(before), options(after))),
+    ]
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeSet;
+use tracing::warn;
+
+use crate::model::{Labels, Snapshot};
+
+/// A generated file attached to the snapshot root page.
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// Attachment formats requested for a publish.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttachmentOptions {
+    pub csv: bool,
+}
+
+impl AttachmentOptions {
+    /// Parse the `attachments` option: a comma-separated list of formats, e.g. `csv`.
+    pub fn parse(value: Option<&str>) -> Self {
+        let mut options = Self::default();
+        for format in value
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+        {
+            match format {
+                "csv" => options.csv = true,
+                other => warn!("Ignoring unsupported attachment format '{}'", other),
+            }
+        }
+        options
+    }
+}
+
+/// Build the requested spreadsheet exports of a snapshot's model tables.
+pub fn snapshot_attachments(snapshot: &Snapshot, options: AttachmentOptions) -> Vec<Attachment> {
+    let mut attachments = Vec::new();
+    if options.csv {
+        attachments.push(csv_attachment("attributes.csv", attributes_csv(snapshot)));
+        attachments.push(csv_attachment("families.csv", families_csv(snapshot)));
+        attachments.push(csv_attachment("categories.csv", categories_csv(snapshot)));
+    }
+    attachments
+}
+
+/// Wrap CSV text as an attachment. A UTF-8 byte order mark is prepended so Excel
+/// detects the encoding instead of mangling non-ASCII labels.
+fn csv_attachment(filename: &str, content: String) -> Attachment {
+    Attachment {
+        filename: filename.to_string(),
+        content_type: "text/csv",
+        data: format!("\u{feff}{}", content).into_bytes(),
+    }
+}
+
+/// Attributes: one row per attribute, with a `label-<locale>` column per locale
+/// (the column naming Akeneo's own import/export uses).
+fn attributes_csv(snapshot: &Snapshot) -> String {
+    let locales = label_locales(snapshot.attributes.iter().map(|a| &a.labels));
+
+    let mut header = vec!["code".to_string()];
+    header.extend(locales.iter().map(|l| format!("label-{}", l)));
+    header.extend(
+        ["type", "group", "unique", "scopable", "localizable"]
+            .iter()
+            .map(|h| h.to_string()),
+    );
+
+    let mut out = csv_row(&header);
+    for attr in &snapshot.attributes {
+        let mut row = vec![attr.code.clone()];
+        row.extend(label_cells(&attr.labels, &locales));
+        row.push(attr.attr_type.clone().unwrap_or_default());
+        row.push(attr.group.clone().unwrap_or_default());
+        row.push(bool_cell(attr.unique));
+        row.push(bool_cell(attr.scopable));
+        row.push(bool_cell(attr.localizable));
+        out.push_str(&csv_row(&row));
+    }
+    out
+}
+
+/// Families: one row per family, with the attribute list and a `requirements-<channel>`
+/// column per channel.
+fn families_csv(snapshot: &Snapshot) -> String {
+    let locales = label_locales(snapshot.families.iter().map(|f| &f.labels));
+    let channels: BTreeSet<&String> = snapshot
+        .families
+        .iter()
+        .flat_map(|f| f.attribute_requirements.keys())
+        .collect();
+
+    let mut header = vec!["code".to_string()];
+    header.extend(locales.iter().map(|l| format!("label-{}", l)));
+    header.extend(
+        ["parent", "attribute_as_label", "attribute_as_image", "attributes"]
+            .iter()
+            .map(|h| h.to_string()),
+    );
+    header.extend(channels.iter().map(|c| format!("requirements-{}", c)));
+
+    let mut out = csv_row(&header);
+    for family in &snapshot.families {
+        let mut row = vec![family.code.clone()];
+        row.extend(label_cells(&family.labels, &locales));
+        row.push(family.parent.clone().unwrap_or_default());
+        row.push(family.attribute_as_label.clone().unwrap_or_default());
+        row.push(family.attribute_as_image.clone().unwrap_or_default());
+        row.push(family.attributes.join(","));
+        for channel in &channels {
+            row.push(
+                family
+                    .attribute_requirements
+                    .get(*channel)
+                    .map(|codes| codes.join(","))
+                    .unwrap_or_default(),
+            );
+        }
+        out.push_str(&csv_row(&row));
+    }
+    out
+}
+
+/// Categories: one row per category with its parent and labels.
+fn categories_csv(snapshot: &Snapshot) -> String {
+    let locales = label_locales(snapshot.categories.iter().map(|c| &c.labels));
+
+    let mut header = vec!["code".to_string(), "parent".to_string()];
+    header.extend(locales.iter().map(|l| format!("label-{}", l)));
+    header.push("updated".to_string());
+
+    let mut out = csv_row(&header);
+    for category in &snapshot.categories {
+        let mut row = vec![
+            category.code.clone(),
+            category.parent.clone().unwrap_or_default(),
+        ];
+        row.extend(label_cells(&category.labels, &locales));
+        row.push(category.updated.clone().unwrap_or_default());
+        out.push_str(&csv_row(&row));
+    }
+    out
+}
+
+/// All locales used by any of the given label sets, sorted.
+fn label_locales<'a>(labels: impl Iterator<Item = &'a Labels>) -> Vec<String> {
+    labels
+        .flat_map(|l| l.iter().map(|(locale, _)| locale.clone()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn label_cells(labels: &Labels, locales: &[String]) -> Vec<String> {
+    locales
+        .iter()
+        .map(|l| labels.get(l).unwrap_or("").to_string())
+        .collect()
+}
+
+fn bool_cell(value: bool) -> String {
+    if value { "1" } else { "0" }.to_string()
+}
+
+/// Format one CSV record (RFC 4180): fields containing a comma, quote, or line break
+/// are quoted, with embedded quotes doubled.
+fn csv_row(fields: &[String]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|f| {
+            if f.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
@@ -0,0 +1,106 @@
+use axum::{
+    extract::{rejection::JsonRejection, FromRequest, FromRequestParts, Request},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::ErrorResponse;
+
+/// Longest query parameter (`name=value`, percent-encoded) accepted.
+pub const MAX_QUERY_PARAM_BYTES: usize = 2048;
+
+/// Largest request body the API accepts unless a route sets its own `DefaultBodyLimit`
+/// (see `inline_snapshot::MAX_BODY_BYTES`).
+pub const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Path parameters, e.g. `Path<Uuid>`: axum's `Path` with its rejection answered as an
+/// `ErrorResponse` body instead of plain text. A malformed ID gets `400`.
+pub struct Path<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Response> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Path(value)),
+            Err(rejection) => Err(error(rejection.status(), rejection.body_text())),
+        }
+    }
+}
+
+/// Query parameters: axum's `Query` with its rejection answered as an `ErrorResponse` body
+/// instead of plain text. A parameter longer than `MAX_QUERY_PARAM_BYTES` or one that does not
+/// parse gets `400`.
+pub struct Query<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Response> {
+        let query = parts.uri.query().unwrap_or_default();
+        if let Some(param) = query
+            .split('&')
+            .find(|param| param.len() > MAX_QUERY_PARAM_BYTES)
+        {
+            let name = param.split('=').next().unwrap_or_default();
+            let name: String = name.chars().take(64).collect();
+            let message = format!(
+                "Query parameter '{}' is longer than {} bytes",
+                name, MAX_QUERY_PARAM_BYTES
+            );
+            return Err(error(StatusCode::BAD_REQUEST, message));
+        }
+        match axum::extract::Query::<T>::try_from_uri(&parts.uri) {
+            Ok(axum::extract::Query(value)) => Ok(Query(value)),
+            Err(rejection) => Err(error(rejection.status(), rejection.body_text())),
+        }
+    }
+}
+
+/// A JSON request body, or a JSON response: axum's `Json` with its rejection answered as an
+/// `ErrorResponse` body instead of plain text. A body that is not valid JSON or does not
+/// match `T` gets `400`, one over the route's body limit `413`, one without a JSON content
+/// type `415`.
+pub struct Json<T>(pub T);
+
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Response> {
+        match axum::Json::<T>::from_request(request, state).await {
+            Ok(axum::Json(value)) => Ok(Json(value)),
+            Err(rejection) => {
+                let status = match &rejection {
+                    JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_) => {
+                        StatusCode::BAD_REQUEST
+                    }
+                    _ => rejection.status(),
+                };
+                Err(error(status, rejection.body_text()))
+            }
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(ErrorResponse::new(message))).into_response()
+}
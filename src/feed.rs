@@ -0,0 +1,207 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::{self, DiffFeedRow};
+use crate::extract::Query;
+use crate::renderer::escape_html;
+use crate::{http_cache, AppState, ErrorResponse};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+/// Prefix of the feed's ID, a `tag:` URI that stays the same wherever the service runs.
+const FEED_ID: &str = "tag:akeneo-snapshot-publisher,2026:diffs";
+
+/// Query parameters of the diff feed.
+#[derive(Deserialize)]
+pub struct FeedParams {
+    /// Only this Akeneo server's diffs (default: every server's).
+    server: Option<Uuid>,
+    limit: Option<i64>,
+}
+
+/// A published diff with its per-category counts.
+struct FeedEntry {
+    publication_id: Uuid,
+    server_name: String,
+    page_title: String,
+    web_url: String,
+    published_at: DateTime<Utc>,
+    before_label: Option<String>,
+    after_label: Option<String>,
+    /// Category -> (added, removed, changed)
+    categories: Vec<(String, [i32; 3])>,
+}
+
+/// GET /api/feeds/diffs.atom
+///
+/// Atom feed of the most recently published live diffs, newest first, each linking to its
+/// Confluence page and summarizing its added / removed / changed counts.
+pub async fn handle_diffs_feed(
+    State(state): State<AppState>,
+    Query(params): Query<FeedParams>,
+    headers: HeaderMap,
+) -> Response {
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let server = match params.server {
+        Some(server_id) => match db::fetch_akeneo_server(&state.pool, server_id).await {
+            Ok(server) => Some((server_id, server.name)),
+            Err(e) if db::is_not_found(&e) => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(ErrorResponse::new(format!("{}", e))),
+                )
+                    .into_response()
+            }
+            Err(e) => return internal_error(e),
+        },
+        None => None,
+    };
+
+    match db::fetch_diff_feed(&state.pool, params.server, limit).await {
+        Ok(rows) => http_cache::atom(&headers, render_feed(server, group_entries(rows))),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// Group the feed rows (one per diff category, in feed order) into entries.
+fn group_entries(rows: Vec<DiffFeedRow>) -> Vec<FeedEntry> {
+    let mut entries: Vec<FeedEntry> = Vec::new();
+    for row in rows {
+        let counts = [row.added, row.removed, row.changed];
+        if let Some(entry) = entries
+            .last_mut()
+            .filter(|e| e.publication_id == row.publication_id)
+        {
+            entry.categories.extend(row.category.map(|c| (c, counts)));
+            continue;
+        }
+        entries.push(FeedEntry {
+            publication_id: row.publication_id,
+            server_name: row.server_name,
+            page_title: row.page_title,
+            web_url: row.web_url,
+            published_at: row.published_at,
+            before_label: row.before_label,
+            after_label: row.after_label,
+            categories: row.category.map(|c| (c, counts)).into_iter().collect(),
+        });
+    }
+    entries
+}
+
+fn render_feed(server: Option<(Uuid, String)>, entries: Vec<FeedEntry>) -> String {
+    let (id, title) = match &server {
+        Some((server_id, name)) => (
+            format!("{}/{}", FEED_ID, server_id),
+            format!("Published model diffs: {}", name),
+        ),
+        None => (FEED_ID.to_string(), "Published model diffs".to_string()),
+    };
+    // The newest entry, so the feed (and its ETag) only changes with the entries
+    let updated = entries
+        .first()
+        .map_or(DateTime::UNIX_EPOCH, |entry| entry.published_at);
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    out.push_str(&format!("  <id>{}</id>\n", escape_html(&id)));
+    out.push_str(&format!("  <title>{}</title>\n", escape_html(&title)));
+    out.push_str(&format!("  <updated>{}</updated>\n", timestamp(updated)));
+    out.push_str("  <author><name>Akeneo Snapshot Publisher</name></author>\n");
+    out.push_str(&format!(
+        "  <generator version=\"{}\">akeneo-snapshot-publisher</generator>\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    for entry in &entries {
+        out.push_str("  <entry>\n");
+        out.push_str(&format!("    <id>urn:uuid:{}</id>\n", entry.publication_id));
+        out.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape_html(&entry.page_title)
+        ));
+        out.push_str(&format!(
+            "    <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+            escape_html(&entry.web_url)
+        ));
+        out.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            timestamp(entry.published_at)
+        ));
+        out.push_str(&format!(
+            "    <category term=\"{}\"/>\n",
+            escape_html(&entry.server_name)
+        ));
+        out.push_str(&format!(
+            "    <summary type=\"text\">{}</summary>\n",
+            escape_html(&summary(entry))
+        ));
+        out.push_str("  </entry>\n");
+    }
+    out.push_str("</feed>\n");
+    out
+}
+
+/// e.g. `Week 41 → Week 42 on prod: 3 added, 1 removed, 5 changed (attributes: 2 added,
+/// 5 changed; families: 1 added, 1 removed).` Categories without changes are left out.
+fn summary(entry: &FeedEntry) -> String {
+    let mut totals = [0; 3];
+    let mut categories = Vec::new();
+    for (category, counts) in &entry.categories {
+        for (total, count) in totals.iter_mut().zip(counts) {
+            *total += count;
+        }
+        if counts.iter().any(|&c| c > 0) {
+            categories.push(format!("{}: {}", category, counts_text(counts, false)));
+        }
+    }
+
+    let mut text = format!(
+        "{} \u{2192} {} on {}: {}",
+        entry.before_label.as_deref().unwrap_or("before"),
+        entry.after_label.as_deref().unwrap_or("after"),
+        entry.server_name,
+        counts_text(&totals, true)
+    );
+    if !categories.is_empty() {
+        text.push_str(&format!(" ({})", categories.join("; ")));
+    }
+    text.push('.');
+    text
+}
+
+/// `3 added, 1 removed, 5 changed`, leaving out zero counts unless `all` is set; `no
+/// changes` when every count is zero.
+fn counts_text(counts: &[i32; 3], all: bool) -> String {
+    let parts: Vec<String> = counts
+        .iter()
+        .zip(["added", "removed", "changed"])
+        .filter(|(count, _)| all || **count > 0)
+        .map(|(count, kind)| format!("{} {}", count, kind))
+        .collect();
+    if counts.iter().all(|&c| c == 0) {
+        return "no changes".to_string();
+    }
+    parts.join(", ")
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn internal_error(e: anyhow::Error) -> Response {
+    error!("Diff feed failed: {:#}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(format!("{:#}", e))),
+    )
+        .into_response()
+}
@@ -0,0 +1,299 @@
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::db::{self, DbConfluenceConfig, SnapshotRow};
+use crate::markdown;
+use crate::AppState;
+
+const DEFAULT_AUTHOR_NAME: &str = "Akeneo Snapshot Publisher";
+const DEFAULT_AUTHOR_EMAIL: &str = "snapshot-publisher@localhost";
+
+/// How long a single git command (clone, fetch, push) may take.
+const GIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Characters of a failing git command's output kept in the error.
+const MAX_ERROR_OUTPUT: usize = 300;
+
+/// What is committed for each snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorFormat {
+    /// `snapshot.json`, the snapshot data as stored.
+    Json,
+    /// `snapshot.md`, the model rendered by `markdown::render_snapshot`.
+    Markdown,
+    Both,
+}
+
+impl MirrorFormat {
+    /// Parse the `confluence_config.git_mirror_format` column. Unknown values fall back to
+    /// `Json`.
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "markdown" => MirrorFormat::Markdown,
+            "both" => MirrorFormat::Both,
+            "json" | "" => MirrorFormat::Json,
+            other => {
+                tracing::warn!("Unknown Git mirror format '{}', using 'json'", other);
+                MirrorFormat::Json
+            }
+        }
+    }
+
+    fn json(self) -> bool {
+        self != MirrorFormat::Markdown
+    }
+
+    fn markdown(self) -> bool {
+        self != MirrorFormat::Json
+    }
+}
+
+/// The Git repository a target's snapshots are mirrored to.
+pub struct MirrorTarget {
+    url: String,
+    branch: String,
+    username: String,
+    /// The stored token; may be an `env:` / `vault:` reference.
+    token: Option<String>,
+    /// Directory of the files within the repository, without surrounding slashes.
+    path: String,
+    format: MirrorFormat,
+}
+
+impl MirrorTarget {
+    /// The mirror of a target, `None` when it has no `git_mirror_url`. Fails on a URL that
+    /// is not `https`, since the token goes out with every request, or carries credentials,
+    /// which belong in `git_mirror_token`.
+    pub fn from_config(config: &DbConfluenceConfig) -> Result<Option<Self>> {
+        let Some(url) = config
+            .git_mirror_url
+            .as_deref()
+            .map(str::trim)
+            .filter(|u| !u.is_empty())
+        else {
+            return Ok(None);
+        };
+        let parsed = reqwest::Url::parse(url).context("Invalid git_mirror_url")?;
+        if parsed.scheme() != "https" {
+            bail!("Invalid git_mirror_url: only HTTPS repositories are supported");
+        }
+        if !parsed.username().is_empty() || parsed.password().is_some() {
+            bail!("Invalid git_mirror_url: put the credentials in git_mirror_token");
+        }
+        let path = config.git_mirror_path.trim_matches('/').to_string();
+        if path.split('/').any(|part| part == ".." || part == ".git") {
+            bail!("Invalid git_mirror_path '{}'", config.git_mirror_path);
+        }
+        Ok(Some(Self {
+            url: url.to_string(),
+            branch: config.git_mirror_branch.trim().to_string(),
+            username: config.git_mirror_username.clone(),
+            token: config.git_mirror_token.clone().filter(|t| !t.is_empty()),
+            path,
+            format: MirrorFormat::parse(&config.git_mirror_format),
+        }))
+    }
+}
+
+/// Local working copies of the mirror repositories, one per Akeneo server, kept between
+/// publishes so each mirror only fetches what changed since the last one.
+pub struct GitMirror {
+    dir: PathBuf,
+    author_name: String,
+    author_email: String,
+    /// Held while a server's working copy is in use, so publishes of the same server
+    /// commit one after another.
+    locks: Mutex<HashMap<Uuid, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl GitMirror {
+    /// Configure the working copies from the environment:
+    /// - `GIT_MIRROR_DIR` — where the working copies live (default: `git-mirror` in the
+    ///   system temporary directory)
+    /// - `GIT_MIRROR_AUTHOR_NAME`, `GIT_MIRROR_AUTHOR_EMAIL` — author of the commits
+    pub fn from_env() -> Self {
+        let env = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        Self {
+            dir: env("GIT_MIRROR_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| std::env::temp_dir().join("git-mirror")),
+            author_name: env("GIT_MIRROR_AUTHOR_NAME")
+                .unwrap_or_else(|| DEFAULT_AUTHOR_NAME.to_string()),
+            author_email: env("GIT_MIRROR_AUTHOR_EMAIL")
+                .unwrap_or_else(|| DEFAULT_AUTHOR_EMAIL.to_string()),
+            locks: Mutex::default(),
+        }
+    }
+
+    fn lock(&self, server_id: Uuid) -> Arc<tokio::sync::Mutex<()>> {
+        self.locks
+            .lock()
+            .unwrap()
+            .entry(server_id)
+            .or_default()
+            .clone()
+    }
+}
+
+/// Commit a published snapshot to the target's mirror repository and push it. The
+/// working copy is first reset to the remote branch (or starts the branch when the
+/// remote does not have it yet), so commits made there by others are kept. Nothing is
+/// committed when the files are already up to date.
+pub async fn mirror_snapshot(
+    state: &AppState,
+    target: &MirrorTarget,
+    snapshot: &SnapshotRow,
+    server: &str,
+) -> Result<()> {
+    let mirror = &state.git_mirror;
+    let lock = mirror.lock(snapshot.akeneo_server_id);
+    let _guard = lock.lock().await;
+
+    let token = match &target.token {
+        Some(token) => Some(
+            state
+                .secrets
+                .resolve(token)
+                .await
+                .context("Failed to resolve Git mirror token")?,
+        ),
+        None => None,
+    };
+    let git = Git {
+        dir: mirror.dir.join(snapshot.akeneo_server_id.to_string()),
+        author: (&mirror.author_name, &mirror.author_email),
+        auth: token.map(|token| {
+            let credentials = format!("{}:{}", target.username, token);
+            format!(
+                "Authorization: Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(credentials)
+            )
+        }),
+    };
+
+    tokio::fs::create_dir_all(&git.dir)
+        .await
+        .with_context(|| format!("Failed to create {}", git.dir.display()))?;
+    if !git.dir.join(".git").exists() {
+        git.run(&["init", "-q"]).await?;
+    }
+    let branch_ref = format!("refs/heads/{}", target.branch);
+    let remote = git
+        .run(&["ls-remote", "--heads", &target.url, &branch_ref])
+        .await?;
+    if remote.trim().is_empty() {
+        // A new (or empty) repository: start the branch without history
+        git.run(&["symbolic-ref", "HEAD", &branch_ref]).await?;
+        git.run(&["read-tree", "--empty"]).await?;
+        git.run(&["clean", "-q", "-f", "-d"]).await?;
+    } else {
+        git.run(&["fetch", "-q", &target.url, &branch_ref]).await?;
+        git.run(&["checkout", "-q", "-f", "-B", &target.branch, "FETCH_HEAD"])
+            .await?;
+        git.run(&["clean", "-q", "-f", "-d"]).await?;
+    }
+
+    let folder = match target.path.as_str() {
+        "" => git.dir.clone(),
+        path => git.dir.join(path),
+    };
+    tokio::fs::create_dir_all(&folder)
+        .await
+        .with_context(|| format!("Failed to create {}", folder.display()))?;
+    if target.format.json() {
+        let data = db::fetch_snapshot_raw_data(&state.pool, snapshot.id).await?;
+        let mut json = serde_json::to_string_pretty(&data)?;
+        json.push('\n');
+        write(&folder.join("snapshot.json"), json).await?;
+    }
+    if target.format.markdown() {
+        write(
+            &folder.join("snapshot.md"),
+            markdown::render_snapshot(snapshot, server),
+        )
+        .await?;
+    }
+
+    git.run(&["add", "-A", "--", "."]).await?;
+    if git.run(&["status", "--porcelain"]).await?.trim().is_empty() {
+        info!(
+            "Git mirror of {} already has snapshot {}",
+            server, snapshot.id
+        );
+        return Ok(());
+    }
+    let label = snapshot.label.as_deref().unwrap_or("Unnamed snapshot");
+    let message = format!(
+        "Snapshot {} ({})\n\nServer: {}\nCompleted: {}\nSnapshot-Id: {}\n",
+        label,
+        snapshot.id,
+        server,
+        snapshot.completed_at.to_rfc3339(),
+        snapshot.id
+    );
+    git.run(&["commit", "-q", "-m", &message]).await?;
+    let commit = git.run(&["rev-parse", "HEAD"]).await?.trim().to_string();
+    git.run(&["push", "-q", &target.url, &format!("HEAD:{}", branch_ref)])
+        .await?;
+
+    info!(
+        "Mirrored snapshot {} to {} ({} {})",
+        snapshot.id, target.url, target.branch, commit
+    );
+    Ok(())
+}
+
+async fn write(path: &Path, contents: String) -> Result<()> {
+    tokio::fs::write(path, contents)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// A working copy, the author of its commits and the `Authorization` header its remote
+/// requests carry.
+struct Git<'a> {
+    dir: PathBuf,
+    author: (&'a str, &'a str),
+    auth: Option<String>,
+}
+
+impl Git<'_> {
+    /// Run a git command in the working copy and return its standard output. The header
+    /// is passed through the environment rather than the arguments or `.git/config`, so
+    /// the token is neither visible in the process list nor written to disk.
+    async fn run(&self, args: &[&str]) -> Result<String> {
+        let mut command = Command::new("git");
+        command
+            .args(args)
+            .current_dir(&self.dir)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env("GIT_AUTHOR_NAME", self.author.0)
+            .env("GIT_AUTHOR_EMAIL", self.author.1)
+            .env("GIT_COMMITTER_NAME", self.author.0)
+            .env("GIT_COMMITTER_EMAIL", self.author.1)
+            .kill_on_drop(true);
+        if let Some(auth) = &self.auth {
+            command
+                .env("GIT_CONFIG_COUNT", "1")
+                .env("GIT_CONFIG_KEY_0", "http.extraHeader")
+                .env("GIT_CONFIG_VALUE_0", auth);
+        }
+        let output = tokio::time::timeout(GIT_TIMEOUT, command.output())
+            .await
+            .with_context(|| format!("git {} timed out", args[0]))?
+            .with_context(|| format!("Failed to run git {}", args[0]))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr: String = stderr.trim().chars().take(MAX_ERROR_OUTPUT).collect();
+            bail!("git {} failed ({}): {}", args[0], output.status, stderr);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+
+/// Header carrying the HMAC-SHA256 of the request body, as `sha256=<hex>`.
+const SIGNATURE_HEADER: &str = "x-publisher-signature";
+
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+
+/// Characters of a failing hook's response body kept in the error.
+const MAX_ERROR_BODY: usize = 200;
+
+/// When a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Before any page is written; a failing hook can stop the publish.
+    PrePublish,
+    /// After every page is published, with the root page URL.
+    PostPublish,
+}
+
+impl Stage {
+    fn as_str(self) -> &'static str {
+        match self {
+            Stage::PrePublish => "pre_publish",
+            Stage::PostPublish => "post_publish",
+        }
+    }
+}
+
+/// What a failing hook (error, timeout or non-2xx response) does to the publish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Fail the publish. A pre-publish hook stops it before Confluence is touched; after a
+    /// post-publish hook the pages are live but the publish reports the error.
+    Fail,
+    /// Log the failure and carry on.
+    Ignore,
+}
+
+/// One hook of `PUBLISH_HOOKS`.
+#[derive(Debug, Deserialize)]
+struct Hook {
+    name: String,
+    stage: Stage,
+    url: String,
+    /// Key the payload is signed with; may be an `env:` / `vault:` reference. Unsigned
+    /// when unset.
+    secret: Option<String>,
+    timeout_secs: Option<u64>,
+    /// Defaults to `fail` for pre-publish hooks and `ignore` for post-publish hooks.
+    on_failure: Option<FailurePolicy>,
+}
+
+impl Hook {
+    fn policy(&self) -> FailurePolicy {
+        self.on_failure.unwrap_or(match self.stage {
+            Stage::PrePublish => FailurePolicy::Fail,
+            Stage::PostPublish => FailurePolicy::Ignore,
+        })
+    }
+}
+
+/// A hook that failed under the `fail` policy, which handlers report as 502.
+#[derive(Debug)]
+pub struct HookFailed(pub String);
+
+impl std::fmt::Display for HookFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Publish hook '{}' failed", self.0)
+    }
+}
+
+impl std::error::Error for HookFailed {}
+
+/// Whether an error is (or wraps) a failed publish hook.
+pub fn is_hook_failed(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<HookFailed>().is_some()
+}
+
+/// Ordered pre- and post-publish hooks calling external HTTP endpoints, e.g. to notify
+/// an internal system or stamp a CMDB.
+#[derive(Debug, Default)]
+pub struct PublishHooks {
+    hooks: Vec<Hook>,
+}
+
+impl PublishHooks {
+    /// Load the hooks from the environment:
+    /// - `PUBLISH_HOOKS` — a JSON array of `{name, stage, url, secret, timeout_secs,
+    ///   on_failure}` objects, run in order within each stage
+    ///
+    /// Malformed hooks fail start-up rather than being skipped, since a skipped
+    /// pre-publish hook would let publishes through that it is meant to gate.
+    pub fn from_env() -> Result<Self> {
+        let Some(raw) = std::env::var("PUBLISH_HOOKS").ok().filter(|v| !v.trim().is_empty())
+        else {
+            return Ok(Self::default());
+        };
+        let hooks: Vec<Hook> = serde_json::from_str(&raw).context("Invalid PUBLISH_HOOKS")?;
+        for hook in &hooks {
+            if !hook.url.starts_with("http://") && !hook.url.starts_with("https://") {
+                bail!("Invalid PUBLISH_HOOKS: hook '{}' has no http(s) URL", hook.name);
+            }
+        }
+        info!("Loaded {} publish hook(s)", hooks.len());
+        Ok(Self { hooks })
+    }
+}
+
+/// The publish a hook is called for.
+pub struct PublishEvent<'a> {
+    /// `snapshot`, `diff` or `cumulative_diff`.
+    pub kind: &'static str,
+    pub server_id: Uuid,
+    pub server: &'a str,
+    /// The published snapshot, or a diff's after-snapshot.
+    pub snapshot_id: Uuid,
+    pub diff_id: Option<Uuid>,
+    pub draft: bool,
+    /// Title of the root or diff page.
+    pub title: &'a str,
+}
+
+/// JSON body sent to a hook.
+#[derive(Serialize)]
+struct Payload<'a> {
+    stage: Stage,
+    hook: &'a str,
+    kind: &'static str,
+    server_id: Uuid,
+    server: &'a str,
+    snapshot_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff_id: Option<Uuid>,
+    draft: bool,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page_url: Option<&'a str>,
+    /// Signed with the body, so receivers can reject replayed requests.
+    sent_at: DateTime<Utc>,
+}
+
+/// Run the hooks of a stage in order. `page_url` is the published root page (post-publish
+/// only). Returns a [`HookFailed`] error for the first failing hook with the `fail`
+/// policy; later hooks of the stage are not run.
+pub async fn run(
+    state: &AppState,
+    stage: Stage,
+    event: &PublishEvent<'_>,
+    page_url: Option<&str>,
+) -> Result<()> {
+    for hook in state.hooks.hooks.iter().filter(|hook| hook.stage == stage) {
+        let payload = Payload {
+            stage,
+            hook: &hook.name,
+            kind: event.kind,
+            server_id: event.server_id,
+            server: event.server,
+            snapshot_id: event.snapshot_id,
+            diff_id: event.diff_id,
+            draft: event.draft,
+            title: event.title,
+            page_url,
+            sent_at: Utc::now(),
+        };
+        match call(state, hook, &payload).await {
+            Ok(()) => info!("{} hook '{}' done", stage.as_str(), hook.name),
+            Err(e) if hook.policy() == FailurePolicy::Ignore => {
+                warn!("Ignoring failed {} hook '{}': {:#}", stage.as_str(), hook.name, e)
+            }
+            Err(e) => return Err(e.context(HookFailed(hook.name.clone()))),
+        }
+    }
+    Ok(())
+}
+
+async fn call(state: &AppState, hook: &Hook, payload: &Payload<'_>) -> Result<()> {
+    let body = serde_json::to_vec(payload).context("Failed to serialize hook payload")?;
+    let timeout = Duration::from_secs(hook.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+
+    let mut request = state
+        .http
+        .post(&hook.url)
+        .timeout(timeout)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    if let Some(secret) = &hook.secret {
+        let key = state
+            .secrets
+            .resolve(secret)
+            .await
+            .context("Failed to resolve hook secret")?;
+        request = request.header(SIGNATURE_HEADER, sign(key.as_bytes(), &body));
+    }
+
+    let response = request
+        .body(body)
+        .send()
+        .await
+        .with_context(|| format!("Hook request to {} failed", hook.url))?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        let text: String = text.trim().chars().take(MAX_ERROR_BODY).collect();
+        bail!("Hook returned {}: {}", status, text);
+    }
+    Ok(())
+}
+
+/// `sha256=<hex>` HMAC of a body.
+pub fn sign(key: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256={}", hex)
+}
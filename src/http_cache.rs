@@ -0,0 +1,114 @@
+use axum::{
+    http::{
+        header::{
+            CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Responses may be stored but must be revalidated, since listings change with every
+/// publish.
+const CACHE_CONTROL_VALUE: &str = "no-cache";
+
+/// Respond with `value` as JSON, or `304 Not Modified` when the client already has it.
+pub fn json(request: &HeaderMap, value: &impl Serialize) -> Response {
+    json_last_modified(request, value, None)
+}
+
+/// Like [`json`], also sending `Last-Modified` and honouring `If-Modified-Since`. Only for
+/// content derived from data that never changes after `last_modified`.
+pub fn json_last_modified(
+    request: &HeaderMap,
+    value: &impl Serialize,
+    last_modified: Option<DateTime<Utc>>,
+) -> Response {
+    match serde_json::to_vec(value) {
+        Ok(body) => respond(request, "application/json", body, last_modified),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)).into_response(),
+    }
+}
+
+/// Respond with an HTML document, or `304 Not Modified` when the client already has it.
+pub fn html(request: &HeaderMap, body: String) -> Response {
+    respond(request, "text/html; charset=utf-8", body.into_bytes(), None)
+}
+
+/// Respond with an Atom feed, or `304 Not Modified` when the client already has it.
+pub fn atom(request: &HeaderMap, body: String) -> Response {
+    respond(
+        request,
+        "application/atom+xml; charset=utf-8",
+        body.into_bytes(),
+        None,
+    )
+}
+
+/// Send `body` with a weak ETag of its content (weak because the compression layer may
+/// re-encode it), or an empty `304` when the request's preconditions show the client's
+/// copy is current. `If-None-Match` takes precedence over `If-Modified-Since`.
+fn respond(
+    request: &HeaderMap,
+    content_type: &'static str,
+    body: Vec<u8>,
+    last_modified: Option<DateTime<Utc>>,
+) -> Response {
+    let etag = etag(&body);
+    // HTTP dates have second precision
+    let last_modified = last_modified.map(|at| at.trunc_subsecs(0));
+
+    let not_modified = match request.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        Some(candidates) => matches_etag(candidates, &etag),
+        None => match (last_modified, if_modified_since(request)) {
+            (Some(modified), Some(since)) => modified <= since,
+            _ => false,
+        },
+    };
+
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(ETAG, value);
+    }
+    if let Some(at) = last_modified
+        && let Ok(value) = HeaderValue::from_str(&http_date(at))
+    {
+        headers.insert(LAST_MODIFIED, value);
+    }
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL_VALUE));
+
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    (StatusCode::OK, headers, body).into_response()
+}
+
+fn etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("W/\"{}\"", hex)
+}
+
+/// Whether an `If-None-Match` list names `etag`, by weak comparison.
+fn matches_etag(candidates: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    candidates
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == etag)
+}
+
+fn if_modified_since(request: &HeaderMap) -> Option<DateTime<Utc>> {
+    let value = request.get(IF_MODIFIED_SINCE)?.to_str().ok()?;
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+fn http_date(at: DateTime<Utc>) -> String {
+    at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
@@ -0,0 +1,167 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::audit::{self, Actor};
+use crate::confluence::PageOperation;
+use crate::db::{self, SnapshotRow};
+use crate::extract::{Json, Query};
+use crate::model::Snapshot;
+use crate::publish::{self, PublishParams};
+use crate::{plan_response, publish_error_status, AppState, ErrorResponse};
+
+/// Largest request body `POST /api/render/snapshot` accepts. Model exports are far larger
+/// than axum's 2 MB default.
+pub const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Body of `POST /api/render/snapshot`.
+#[derive(Deserialize, Serialize)]
+pub struct InlineSnapshotRequest {
+    /// Akeneo server whose Confluence target the pages are published to.
+    server_id: Uuid,
+    /// Used in titles as a stored snapshot's label is.
+    label: Option<String>,
+    /// When the export was taken (default: now), for titles and the snapshot info table.
+    completed_at: Option<DateTime<Utc>>,
+    /// The snapshot data, shaped like `snapshot.data`. Left out of the audit log.
+    #[serde(skip_serializing)]
+    snapshot: serde_json::Value,
+}
+
+/// A page the publish wrote.
+#[derive(Serialize)]
+struct InlinePage {
+    title: String,
+    page_id: String,
+    page_url: String,
+}
+
+#[derive(Serialize)]
+struct InlineSnapshotResponse {
+    status: &'static str,
+    /// ID the publish ran under, shown on the root page and in the pages' provenance
+    /// property; no `snapshot` row has it.
+    snapshot_id: Uuid,
+    page_url: String,
+    operation: PageOperation,
+    version: u64,
+    /// Every page published, root first.
+    pages: Vec<InlinePage>,
+    warnings: Vec<String>,
+}
+
+/// POST /api/render/snapshot
+///
+/// Renders the snapshot data in the body and publishes it to the server's Confluence
+/// target without storing it (see `publish::publish_inline_snapshot`). Accepts the
+/// publishing endpoints' query parameters, `dry_run` included. 404 for an unknown server,
+/// 422 for data that does not match the Akeneo model.
+pub async fn handle_render_snapshot(
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+    Query(params): Query<PublishParams>,
+    Json(mut request): Json<InlineSnapshotRequest>,
+) -> Response {
+    let server_id = request.server_id;
+    match db::fetch_akeneo_server(&state.pool, server_id).await {
+        Ok(_) => {}
+        Err(e) if db::is_not_found(&e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!("Akeneo server not found: {}", server_id))),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!("Failed to load Akeneo server {}: {:#}", server_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
+    }
+    let data = match Snapshot::from_value(request.snapshot.take()) {
+        Ok(data) => data,
+        Err(e) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
+    };
+    let completed_at = request.completed_at.unwrap_or_else(Utc::now);
+    let snapshot = SnapshotRow {
+        id: Uuid::new_v4(),
+        akeneo_server_id: server_id,
+        label: request.label.clone(),
+        started_at: completed_at,
+        completed_at,
+        data,
+    };
+    info!(
+        "Processing inline snapshot {} for server {}",
+        snapshot.id, server_id
+    );
+
+    let job = state.jobs.start("snapshot", snapshot.id);
+    // A dry run publishes nothing, so it is not audited
+    if params.is_dry_run() {
+        let plan = publish::plan_inline_snapshot(&state, &snapshot, params, &job).await;
+        return plan_response(plan, &job, &format!("inline snapshot {}", snapshot.id));
+    }
+    let mut parameters = audit::parameters(&params);
+    if let (serde_json::Value::Object(map), serde_json::Value::Object(body)) =
+        (&mut parameters, audit::parameters(&request))
+    {
+        map.extend(body);
+    }
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "POST /api/render/snapshot",
+        target_id: server_id,
+        parameters,
+    };
+
+    let published = publish::publish_inline_snapshot(&state, &snapshot, params, &job).await;
+    let outcome = match &published {
+        Ok((root, _)) => audit::Outcome::Ok(vec![root.web_url.clone()]),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match published {
+        Ok((root, pages)) => Json(InlineSnapshotResponse {
+            status: "ok",
+            snapshot_id: snapshot.id,
+            page_url: root.web_url,
+            operation: root.operation,
+            version: root.version,
+            pages: pages
+                .into_iter()
+                .map(|page| InlinePage {
+                    title: page.title,
+                    page_id: page.page_id,
+                    page_url: page.web_url,
+                })
+                .collect(),
+            warnings: job.warnings(),
+        })
+        .into_response(),
+        Err(e) => {
+            error!("Failed to publish inline snapshot {}: {:#}", snapshot.id, e);
+            job.fail(&e);
+            (
+                publish_error_status(&e),
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
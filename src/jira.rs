@@ -0,0 +1,155 @@
+use anyhow::{bail, Context, Result};
+use reqwest::header::ACCEPT;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::info;
+
+use crate::db::DbConfluenceConfig;
+use crate::diff::BreakingChange;
+use crate::titles::TitleContext;
+
+/// Where breaking diff changes are reported, from the Jira columns of `confluence_config`.
+/// Jira Cloud lives on the same Atlassian site as Confluence and accepts the same
+/// email + API token (OAuth targets keep them for Jira).
+pub struct JiraConfig {
+    pub base_url: String,
+    pub email: String,
+    pub api_token: String,
+    pub project_key: String,
+    pub issue_type: String,
+}
+
+impl JiraConfig {
+    /// Build config from database configuration; `None` when the target has no
+    /// `jira_project_key`, or no API token (an OAuth target without Jira credentials).
+    pub fn from_db(db_config: &DbConfluenceConfig) -> Option<Self> {
+        let project_key = db_config
+            .jira_project_key
+            .as_deref()
+            .filter(|k| !k.is_empty())?;
+        if db_config.api_token.is_empty() {
+            return None;
+        }
+        Some(Self {
+            base_url: db_config.base_url.clone(),
+            email: db_config.username.clone(),
+            api_token: db_config.api_token.clone(),
+            project_key: project_key.to_string(),
+            issue_type: db_config.jira_issue_type.clone(),
+        })
+    }
+}
+
+/// Jira issue to create.
+pub struct NewIssue {
+    pub summary: String,
+    /// Jira wiki markup (REST API v2).
+    pub description: String,
+}
+
+/// Response from the create issue endpoint.
+#[derive(Deserialize, Debug)]
+struct CreateIssueResponse {
+    key: String,
+}
+
+/// Jira Cloud REST API v2 client.
+pub struct JiraClient {
+    client: Client,
+    config: JiraConfig,
+}
+
+impl JiraClient {
+    /// Create a client for the given config, sharing the pooled HTTP client.
+    pub fn new(config: JiraConfig, client: Client) -> Self {
+        Self { client, config }
+    }
+
+    /// Create an issue in the configured project and return its key (e.g. `PIM-42`).
+    pub async fn create_issue(&self, issue: &NewIssue) -> Result<String> {
+        let url = format!(
+            "{}/rest/api/2/issue",
+            self.config.base_url.trim_end_matches('/')
+        );
+        let body = json!({
+            "fields": {
+                "project": { "key": self.config.project_key },
+                "issuetype": { "name": self.config.issue_type },
+                "summary": issue.summary,
+                "description": issue.description,
+            }
+        });
+
+        let resp = self
+            .client
+            .post(&url)
+            .basic_auth(&self.config.email, Some(&self.config.api_token))
+            .header(ACCEPT, "application/json")
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to create Jira issue")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("Jira create issue failed (HTTP {}): {}", status, body);
+        }
+
+        let created: CreateIssueResponse = resp
+            .json()
+            .await
+            .context("Failed to parse Jira create issue response")?;
+        info!(
+            "Created Jira issue {} in project {}",
+            created.key, self.config.project_key
+        );
+        Ok(created.key)
+    }
+}
+
+/// The issue raised for a diff's breaking changes, linking the published diff page.
+pub fn breaking_change_issue(
+    ctx: &TitleContext<'_>,
+    changes: &[BreakingChange],
+    diff_url: &str,
+) -> NewIssue {
+    let mut description = format!(
+        "The Akeneo model of *{}* changed between *{}* and *{}* in ways that can break \
+         imports, exports and integrations:\n\n",
+        ctx.server, ctx.before, ctx.after
+    );
+    for change in changes {
+        if !change.removed.is_empty() {
+            description.push_str(&format!(
+                "* Removed {}: {}\n",
+                change.category,
+                code_list(change.removed.iter().map(String::as_str))
+            ));
+        }
+        for (old_code, new_code) in &change.renamed {
+            description.push_str(&format!(
+                "* Renamed {}: {{{{{}}}}} is now {{{{{}}}}}\n",
+                change.category, old_code, new_code
+            ));
+        }
+    }
+    description.push_str(&format!("\n[View the diff page|{}]", diff_url));
+
+    NewIssue {
+        summary: format!(
+            "Breaking Akeneo model changes in {}: {} \u{2192} {}",
+            ctx.server, ctx.before, ctx.after
+        ),
+        description,
+    }
+}
+
+/// Codes as Jira monospace text, comma-separated.
+fn code_list<'a>(codes: impl Iterator<Item = &'a str>) -> String {
+    codes
+        .map(|code| format!("{{{{{}}}}}", code))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
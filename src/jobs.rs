@@ -0,0 +1,317 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::confluence::PageOperation;
+use crate::extract::Path;
+use crate::{AppState, ErrorResponse};
+
+/// Events buffered per job for slow subscribers; a subscriber that falls further behind
+/// skips ahead to the latest event.
+const EVENT_BUFFER: usize = 256;
+
+/// Registry of publish operations currently in progress.
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<Uuid, Job>>,
+}
+
+struct Job {
+    status: JobStatus,
+    events: broadcast::Sender<JobEvent>,
+    /// Set by `JobHandle::fail`, reported in the final event.
+    error: Option<String>,
+    /// Problems that did not stop the publish, from `JobHandle::warn`.
+    warnings: Vec<String>,
+}
+
+/// Progress snapshot of a single in-flight publish.
+#[derive(Clone, Serialize)]
+pub struct JobStatus {
+    pub id: Uuid,
+    pub kind: &'static str,
+    pub target_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub pages_total: usize,
+    pub pages_done: usize,
+}
+
+impl JobStatus {
+    /// Estimated seconds until every page is published, from the average time per page
+    /// so far. `None` until the first page is done.
+    fn eta_secs(&self) -> Option<u64> {
+        if self.pages_done == 0 {
+            return None;
+        }
+        let elapsed = (Utc::now() - self.started_at).num_milliseconds().max(0) as u64;
+        let remaining = self.pages_total.saturating_sub(self.pages_done) as u64;
+        Some(elapsed * remaining / self.pages_done as u64 / 1000)
+    }
+}
+
+/// How a page of a job ended up.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageOutcome {
+    Created,
+    Updated,
+    Failed,
+}
+
+/// A progress event streamed by `GET /api/jobs/{id}/events`.
+#[derive(Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JobEvent {
+    /// Sent first to every subscriber: where the job is at.
+    Progress {
+        kind: &'static str,
+        target_id: Uuid,
+        pages_done: usize,
+        pages_total: usize,
+        eta_secs: Option<u64>,
+    },
+    /// A page was published, or failed to publish.
+    Page {
+        title: String,
+        outcome: PageOutcome,
+        pages_done: usize,
+        pages_total: usize,
+        eta_secs: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// Something the publish worked around, e.g. colliding page titles.
+    Warning { message: String },
+    /// The job ended; nothing follows. `error` is set when it failed.
+    Finished {
+        pages_done: usize,
+        pages_total: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+    },
+}
+
+impl JobEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            JobEvent::Progress { .. } => "progress",
+            JobEvent::Page { .. } => "page",
+            JobEvent::Warning { .. } => "warning",
+            JobEvent::Finished { .. } => "finished",
+        }
+    }
+}
+
+/// Handle to a registered job. The job is removed from the registry when dropped,
+/// so it disappears on success, error, and early return alike.
+pub struct JobHandle {
+    registry: Arc<JobRegistry>,
+    id: Uuid,
+}
+
+impl JobRegistry {
+    /// Register a new job of the given kind ("snapshot", "diff", ...) for a target ID.
+    pub fn start(self: &Arc<Self>, kind: &'static str, target_id: Uuid) -> JobHandle {
+        let id = Uuid::new_v4();
+        self.jobs.lock().unwrap().insert(
+            id,
+            Job {
+                status: JobStatus {
+                    id,
+                    kind,
+                    target_id,
+                    started_at: Utc::now(),
+                    pages_total: 0,
+                    pages_done: 0,
+                },
+                events: broadcast::channel(EVENT_BUFFER).0,
+                error: None,
+                warnings: Vec::new(),
+            },
+        );
+        JobHandle {
+            registry: self.clone(),
+            id,
+        }
+    }
+
+    /// All jobs currently in progress, oldest first.
+    pub fn active(&self) -> Vec<JobStatus> {
+        let mut jobs: Vec<JobStatus> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|job| job.status.clone())
+            .collect();
+        jobs.sort_by_key(|job| job.started_at);
+        jobs
+    }
+
+    /// Subscribe to a job's events, by job ID or by the ID of the snapshot or diff it is
+    /// publishing (the newest such job). Returns the job's current progress along with
+    /// the receiver.
+    fn subscribe(&self, id: Uuid) -> Option<(JobEvent, broadcast::Receiver<JobEvent>)> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(&id).or_else(|| {
+            jobs.values()
+                .filter(|job| job.status.target_id == id)
+                .max_by_key(|job| job.status.started_at)
+        })?;
+        let status = &job.status;
+        let progress = JobEvent::Progress {
+            kind: status.kind,
+            target_id: status.target_id,
+            pages_done: status.pages_done,
+            pages_total: status.pages_total,
+            eta_secs: status.eta_secs(),
+        };
+        Some((progress, job.events.subscribe()))
+    }
+}
+
+impl JobHandle {
+    /// Set the total number of pages this job will publish.
+    pub fn set_total(&self, pages_total: usize) {
+        if let Some(job) = self.registry.jobs.lock().unwrap().get_mut(&self.id) {
+            job.status.pages_total = pages_total;
+        }
+    }
+
+    /// Record that one more page has been published.
+    pub fn page_done(&self, title: &str, operation: PageOperation) {
+        let outcome = match operation {
+            PageOperation::Created => PageOutcome::Created,
+            PageOperation::Updated => PageOutcome::Updated,
+        };
+        self.page_event(title, outcome, None);
+    }
+
+    /// Record that a page failed to publish.
+    pub fn page_failed(&self, title: &str, error: &anyhow::Error) {
+        self.page_event(title, PageOutcome::Failed, Some(format!("{:#}", error)));
+    }
+
+    /// Mark the job as failed; the error is reported in its final event.
+    pub fn fail(&self, error: &anyhow::Error) {
+        if let Some(job) = self.registry.jobs.lock().unwrap().get_mut(&self.id) {
+            job.error = Some(format!("{:#}", error));
+        }
+    }
+
+    /// Record a problem the publish worked around; it is streamed at once and repeated in
+    /// the final event.
+    pub fn warn(&self, message: String) {
+        if let Some(job) = self.registry.jobs.lock().unwrap().get_mut(&self.id) {
+            let _ = job.events.send(JobEvent::Warning {
+                message: message.clone(),
+            });
+            job.warnings.push(message);
+        }
+    }
+
+    /// The warnings recorded so far, for the publish response.
+    pub fn warnings(&self) -> Vec<String> {
+        self.registry
+            .jobs
+            .lock()
+            .unwrap()
+            .get(&self.id)
+            .map(|job| job.warnings.clone())
+            .unwrap_or_default()
+    }
+
+    fn page_event(&self, title: &str, outcome: PageOutcome, error: Option<String>) {
+        if let Some(job) = self.registry.jobs.lock().unwrap().get_mut(&self.id) {
+            if !matches!(outcome, PageOutcome::Failed) {
+                job.status.pages_done += 1;
+            }
+            // Sending only fails when nobody is subscribed
+            let _ = job.events.send(JobEvent::Page {
+                title: title.to_string(),
+                outcome,
+                pages_done: job.status.pages_done,
+                pages_total: job.status.pages_total,
+                eta_secs: job.status.eta_secs(),
+                error,
+            });
+        }
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        if let Some(job) = self.registry.jobs.lock().unwrap().remove(&self.id) {
+            let _ = job.events.send(JobEvent::Finished {
+                pages_done: job.status.pages_done,
+                pages_total: job.status.pages_total,
+                error: job.error,
+                warnings: job.warnings,
+            });
+        }
+    }
+}
+
+/// GET /api/jobs/:id/events
+///
+/// Streams a publish job's progress as server-sent events: a `progress` event with the
+/// current state, a `page` event per page published (or failed), a `warning` event per
+/// problem worked around, and a final `finished` event. `id` is a job ID or the ID of the
+/// snapshot or diff being published, so a client can subscribe right after starting a
+/// publish.
+pub async fn handle_job_events(State(state): State<AppState>, Path(id): Path<Uuid>) -> Response {
+    let Some((progress, receiver)) = state.jobs.subscribe(id) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(format!("No publish in progress for {}", id))),
+        )
+            .into_response();
+    };
+
+    Sse::new(event_stream(progress, receiver))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// The initial progress event followed by the job's events, ending after `finished` or
+/// when the job is gone.
+fn event_stream(
+    progress: JobEvent,
+    receiver: broadcast::Receiver<JobEvent>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let first = stream::once(async move { progress });
+    let rest = stream::unfold(Some(receiver), |receiver| async move {
+        let mut receiver = receiver?;
+        loop {
+            match receiver.recv().await {
+                Ok(event @ JobEvent::Finished { .. }) => return Some((event, None)),
+                Ok(event) => return Some((event, Some(receiver))),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    first.chain(rest).map(|event| {
+        Ok(Event::default()
+            .event(event.name())
+            .json_data(&event)
+            .unwrap_or_else(|_| Event::default().event(event.name())))
+    })
+}
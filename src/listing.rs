@@ -0,0 +1,261 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::{self, DiffListRow, ListFilter, SnapshotListRow};
+use crate::extract::{Path, Query};
+use crate::{http_cache, AppState, ErrorResponse};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+/// Query parameters of the listing endpoints.
+///
+/// `from` / `to` accept an RFC 3339 timestamp or a `YYYY-MM-DD` date; a `to` date
+/// includes that whole day.
+#[derive(Deserialize)]
+pub struct ListParams {
+    label: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    /// Also list archived snapshots.
+    archived: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// One page of a listing.
+#[derive(Serialize)]
+struct Page<T> {
+    items: Vec<T>,
+    total: i64,
+    limit: i64,
+    offset: i64,
+}
+
+/// The latest publication of a snapshot or diff, from `publication_history`.
+#[derive(Serialize)]
+struct Publication {
+    /// ID to pass to `POST /api/publications/:id/promote` when `kind` is `draft`.
+    id: Uuid,
+    /// `version`, `overwrite`, `diff` or `draft`.
+    kind: String,
+    page_title: String,
+    web_url: String,
+    published_at: DateTime<Utc>,
+    /// Threshold alerts the diff raised when published (diffs only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alerts: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct SnapshotItem {
+    id: Uuid,
+    label: Option<String>,
+    started_at: DateTime<Utc>,
+    completed_at: DateTime<Utc>,
+    /// Retired by the retention job; see `POST /api/admin/snapshots/:id/restore`.
+    archived: bool,
+    /// `null` when the snapshot has not been published.
+    publication: Option<Publication>,
+}
+
+#[derive(Serialize)]
+struct DiffItem {
+    id: Uuid,
+    before_id: Uuid,
+    before_label: Option<String>,
+    after_id: Uuid,
+    after_label: Option<String>,
+    /// Completion time of the after-snapshot.
+    completed_at: DateTime<Utc>,
+    /// `null` when the diff has not been published.
+    publication: Option<Publication>,
+}
+
+impl From<SnapshotListRow> for SnapshotItem {
+    fn from(row: SnapshotListRow) -> Self {
+        let publication = match (
+            row.publication_id,
+            row.page_title,
+            row.web_url,
+            row.published_at,
+        ) {
+            (Some(id), Some(page_title), Some(web_url), Some(published_at)) => Some(Publication {
+                id,
+                kind: row.publication_kind.unwrap_or_default(),
+                page_title,
+                web_url,
+                published_at,
+                alerts: None,
+            }),
+            _ => None,
+        };
+        Self {
+            id: row.id,
+            label: row.label,
+            started_at: row.started_at,
+            completed_at: row.completed_at,
+            archived: row.archived,
+            publication,
+        }
+    }
+}
+
+impl From<DiffListRow> for DiffItem {
+    fn from(row: DiffListRow) -> Self {
+        let publication = match (
+            row.publication_id,
+            row.page_title,
+            row.web_url,
+            row.published_at,
+        ) {
+            (Some(id), Some(page_title), Some(web_url), Some(published_at)) => Some(Publication {
+                id,
+                kind: row.publication_kind.unwrap_or_default(),
+                page_title,
+                web_url,
+                published_at,
+                alerts: row.alerts,
+            }),
+            _ => None,
+        };
+        Self {
+            id: row.id,
+            before_id: row.before_id,
+            before_label: row.before_label,
+            after_id: row.after_id,
+            after_label: row.after_label,
+            completed_at: row.after_completed_at,
+            publication,
+        }
+    }
+}
+
+impl ListParams {
+    /// Validate the parameters into a database filter.
+    fn into_filter(self) -> Result<ListFilter, String> {
+        Ok(ListFilter {
+            label: self.label.filter(|l| !l.is_empty()),
+            from: self.from.as_deref().map(|v| parse_bound(v, false)).transpose()?,
+            to: self.to.as_deref().map(|v| parse_bound(v, true)).transpose()?,
+            archived: self.archived.unwrap_or(false),
+            limit: self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+            offset: self.offset.unwrap_or(0).max(0),
+        })
+    }
+}
+
+/// Parse a date-range bound. A bare `to` date is turned into the start of the next day so
+/// the (exclusive) upper bound covers the whole day.
+pub fn parse_bound(value: &str, end_of_day: bool) -> Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        format!(
+            "Invalid date '{}': expected YYYY-MM-DD or an RFC 3339 timestamp",
+            value
+        )
+    })?;
+    let date = if end_of_day {
+        date.checked_add_days(Days::new(1)).unwrap_or(date)
+    } else {
+        date
+    };
+    Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
+/// GET /api/servers/:id/snapshots
+///
+/// Lists an Akeneo server's snapshots, newest first, with their publication status.
+pub async fn handle_list_snapshots(
+    State(state): State<AppState>,
+    Path(server_id): Path<Uuid>,
+    Query(params): Query<ListParams>,
+    headers: HeaderMap,
+) -> Response {
+    let filter = match check_request(&state, server_id, params).await {
+        Ok(filter) => filter,
+        Err(rejection) => return rejection,
+    };
+
+    match db::list_snapshots(&state.pool, server_id, &filter).await {
+        Ok((rows, total)) => http_cache::json(
+            &headers,
+            &Page {
+                items: rows.into_iter().map(SnapshotItem::from).collect(),
+                total,
+                limit: filter.limit,
+                offset: filter.offset,
+            },
+        ),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// GET /api/servers/:id/diffs
+///
+/// Lists an Akeneo server's diffs, newest first, with their publication status.
+pub async fn handle_list_diffs(
+    State(state): State<AppState>,
+    Path(server_id): Path<Uuid>,
+    Query(params): Query<ListParams>,
+    headers: HeaderMap,
+) -> Response {
+    let filter = match check_request(&state, server_id, params).await {
+        Ok(filter) => filter,
+        Err(rejection) => return rejection,
+    };
+
+    match db::list_diffs(&state.pool, server_id, &filter).await {
+        Ok((rows, total)) => http_cache::json(
+            &headers,
+            &Page {
+                items: rows.into_iter().map(DiffItem::from).collect(),
+                total,
+                limit: filter.limit,
+                offset: filter.offset,
+            },
+        ),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// Validate the query parameters and make sure the server exists, so an unknown ID is a
+/// 404 rather than an empty listing.
+async fn check_request(
+    state: &AppState,
+    server_id: Uuid,
+    params: ListParams,
+) -> Result<ListFilter, Response> {
+    let filter = params.into_filter().map_err(|message| {
+        (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(message))).into_response()
+    })?;
+
+    match db::fetch_akeneo_server(&state.pool, server_id).await {
+        Ok(_) => Ok(filter),
+        Err(e) if db::is_not_found(&e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(format!("{}", e))),
+        )
+            .into_response()),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+fn internal_error(e: anyhow::Error) -> Response {
+    error!("Listing failed: {:#}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(format!("{:#}", e))),
+    )
+        .into_response()
+}
@@ -1,26 +1,102 @@
+mod admin;
+mod akeneo;
+mod akeneo_events;
+mod archive;
+mod audit;
+mod client_cache;
+mod config;
+mod config_cache;
 mod confluence;
+mod confluence_mock;
+mod confluence_configs;
+mod crypto;
+mod cumulative;
+mod dashboard;
 mod db;
 mod diff;
+mod export;
+mod extract;
+mod feed;
+mod git_mirror;
+mod hooks;
+mod http_cache;
+mod inline_snapshot;
+mod jira;
+mod jobs;
+mod listing;
+mod markdown;
+mod matrix;
+mod metrics;
+mod model;
+mod object_store;
+mod preflight;
+mod publish;
+mod rate_limit;
+mod render_cache;
+mod render_options;
 mod renderer;
+mod republish;
+mod sanitize;
+mod scheduler;
+mod secrets;
+mod servers;
+mod snapshot_retention;
+mod titles;
+mod validate;
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{DefaultBodyLimit, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
     response::IntoResponse,
-    routing::get,
-    Json, Router,
+    routing::{any, delete, get, patch, post, MethodRouter},
+    Router,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::collections::HashSet;
-use tower_http::trace::TraceLayer;
-use tracing::{error, info, warn};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower_http::{compression::CompressionLayer, trace::TraceLayer};
+use tracing::{error, info};
 use uuid::Uuid;
 
+use audit::Actor;
+use confluence::PageOperation;
+use extract::{Json, Path, Query};
+
 /// Shared application state passed to all handlers.
 #[derive(Clone)]
 struct AppState {
+    /// Startup configuration, validated before the service starts.
+    config: Arc<config::AppConfig>,
     pool: PgPool,
+    http: reqwest::Client,
+    /// Confluence HTTP clients of targets with a custom CA or relaxed TLS verification.
+    tls_clients: Arc<confluence::TlsClients>,
+    render_cache: Arc<render_cache::RenderCache>,
+    /// Confluence clients with their token and parent page resolved, per Akeneo server.
+    clients: Arc<client_cache::ClientCache>,
+    /// `confluence_config` rows, per Akeneo server.
+    confluence_configs: Arc<config_cache::ConfigCache>,
+    render_options: Arc<render_options::RenderOptions>,
+    jobs: Arc<jobs::JobRegistry>,
+    scheduler: Arc<scheduler::Scheduler>,
+    /// Resolves `env:` / `vault:` references stored in place of Confluence API tokens.
+    secrets: Arc<secrets::Secrets>,
+    /// Bearer token required by admin endpoints; admin endpoints are disabled when unset.
+    admin_token: Option<String>,
+    rate_limiter: Arc<rate_limit::RateLimiter>,
+    /// Per-site Confluence rate budget shared by all concurrent publishes.
+    coordinator: Arc<confluence::PublishCoordinator>,
+    /// Answers every Confluence request in mock mode (`CONFLUENCE_MODE=mock`).
+    confluence_mock: Option<Arc<confluence_mock::MockConfluence>>,
+    audit: Arc<audit::AuditLog>,
+    /// External HTTP endpoints called before and after every publish.
+    hooks: Arc<hooks::PublishHooks>,
+    /// Where live publishes of targets with an `archive_bucket` are archived.
+    object_store: Option<Arc<object_store::ObjectStore>>,
+    /// Working copies of the Git repositories snapshots are mirrored to.
+    git_mirror: Arc<git_mirror::GitMirror>,
 }
 
 /// JSON response returned by both endpoints on success.
@@ -28,6 +104,132 @@ struct AppState {
 struct SuccessResponse {
     status: &'static str,
     page_url: String,
+    /// Whether the page at `page_url` was `created` or `updated`.
+    operation: PageOperation,
+    /// Its version number after the publish.
+    version: u64,
+    /// Key of the Jira issue raised for a diff's breaking changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jira_issue: Option<String>,
+    /// Threshold alerts a published diff raised; always present, possibly empty, for diffs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alerts: Option<Vec<diff::DiffAlert>>,
+    /// Problems worked around: malformed diff categories left off the page, or colliding
+    /// family page titles. Always present, possibly empty, for snapshots and diffs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warnings: Option<Vec<String>>,
+    /// Changed items of a diff left off the page because they could not be read; the
+    /// page's categories list why. Always present for diffs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped_changed_items: Option<usize>,
+    /// The blog post announcing a diff published with `content_type=blogpost`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blog_post_url: Option<String>,
+}
+
+/// JSON response returned by both endpoints for `dry_run=true`.
+#[derive(Serialize)]
+struct PlanResponse {
+    status: &'static str,
+    dry_run: bool,
+    /// What the publish would do, in publish order.
+    pages: Vec<confluence::PlannedPage>,
+    /// Problems the publish would work around, like `SuccessResponse.warnings`.
+    warnings: Vec<String>,
+}
+
+/// The response to a dry run: the plan, or the error the publish would fail with.
+fn plan_response(
+    plan: anyhow::Result<Vec<confluence::PlannedPage>>,
+    job: &jobs::JobHandle,
+    target: &str,
+) -> axum::response::Response {
+    match plan {
+        Ok(pages) => Json(PlanResponse {
+            status: "ok",
+            dry_run: true,
+            pages,
+            warnings: job.warnings(),
+        })
+        .into_response(),
+        Err(e) => {
+            error!("Failed to plan the publish of {}: {:#}", target, e);
+            job.fail(&e);
+            (
+                publish_error_status(&e),
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// JSON response returned by the snapshot endpoint for `if_changed=true` when the pages
+/// would repeat the last publication.
+#[derive(Serialize)]
+struct UnchangedResponse {
+    status: &'static str,
+    /// The last publication's root page, left as it was.
+    page_url: String,
+}
+
+/// JSON response returned by the prerender endpoint.
+#[derive(Serialize)]
+struct PrerenderResponse {
+    status: &'static str,
+    pages: usize,
+}
+
+/// JSON response returned by the family page republish endpoint.
+#[derive(Serialize)]
+struct FamilyPublishResponse {
+    status: &'static str,
+    page_url: String,
+    /// Whether the family page was `created` or `updated`.
+    operation: PageOperation,
+    /// Its version number after the publish.
+    version: u64,
+    /// Whether the family's row of the root page's Families table was refreshed.
+    root_updated: bool,
+    warnings: Vec<String>,
+}
+
+/// JSON response returned by the page withdrawal endpoint.
+#[derive(Serialize)]
+struct WithdrawResponse {
+    status: &'static str,
+    #[serde(flatten)]
+    withdrawal: publish::Withdrawal,
+}
+
+/// JSON response returned by the validate endpoint.
+#[derive(Serialize)]
+struct ValidateResponse {
+    status: &'static str,
+    valid: bool,
+    warnings: Vec<validate::ValidationWarning>,
+}
+
+/// Query parameters for the live snapshot fetch endpoint.
+#[derive(Deserialize)]
+struct FetchSnapshotParams {
+    /// Label stored on the new snapshot.
+    label: Option<String>,
+    /// Publish the new snapshot to Confluence right away.
+    #[serde(default)]
+    publish: bool,
+}
+
+/// JSON response returned by the live snapshot fetch endpoint.
+#[derive(Serialize)]
+struct FetchSnapshotResponse {
+    status: &'static str,
+    snapshot_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page_url: Option<String>,
+    /// Problems the publish worked around (see `SuccessResponse.warnings`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
 }
 
 /// JSON response returned on errors.
@@ -46,6 +248,26 @@ impl ErrorResponse {
     }
 }
 
+/// 403 for a request that needs a capability the deployment turned off.
+fn capability_disabled(capability: config::Capability) -> axum::response::Response {
+    let message = config::CapabilityDisabled(capability).to_string();
+    (StatusCode::FORBIDDEN, Json(ErrorResponse::new(message))).into_response()
+}
+
+/// The route of an endpoint that only exists with a capability; with the capability off,
+/// every request to it gets `capability_disabled`.
+fn gated(
+    features: &config::FeatureFlags,
+    capability: config::Capability,
+    route: MethodRouter<AppState>,
+) -> MethodRouter<AppState> {
+    if features.enabled(capability) {
+        route
+    } else {
+        any(move || async move { capability_disabled(capability) })
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Initialize tracing (respects RUST_LOG env var, defaults to info)
@@ -56,23 +278,166 @@ async fn main() -> anyhow::Result<()> {
         )
         .init();
 
-    let pool = db::connect().await?;
-    let state = AppState { pool };
+    let config = Arc::new(config::AppConfig::load()?);
+    crypto::init()?;
+    let pool = db::connect(&config.database).await?;
+    let http = confluence::build_http_client(&confluence::TlsOptions::default())?;
+    let render_cache = Arc::new(render_cache::RenderCache::from_env());
+    let render_options = Arc::new(render_options::RenderOptions::from_env(&config));
+    render_cache::spawn_nightly_prerender(
+        pool.clone(),
+        render_cache.clone(),
+        render_options.clone(),
+    )?;
+    let admin_token = std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
+    let secrets = Arc::new(secrets::Secrets::from_env(http.clone()));
+    let audit = Arc::new(audit::AuditLog::from_env());
+    audit::spawn_retention(pool.clone(), &audit);
+    let confluence_mock = match config.confluence.mode {
+        config::ConfluenceMode::Live => None,
+        config::ConfluenceMode::Mock => Some(Arc::new(confluence_mock::MockConfluence::open(
+            &config.confluence.mock_dir,
+        )?)),
+    };
+    let state = AppState {
+        config: config.clone(),
+        pool,
+        http,
+        tls_clients: Arc::default(),
+        render_cache,
+        clients: Arc::new(client_cache::ClientCache::from_env()),
+        confluence_configs: Arc::new(config_cache::ConfigCache::from_env()),
+        render_options,
+        jobs: Arc::new(jobs::JobRegistry::default()),
+        scheduler: Arc::new(scheduler::Scheduler::default()),
+        secrets,
+        admin_token,
+        rate_limiter: Arc::new(rate_limit::RateLimiter::from_env()),
+        coordinator: Arc::new(confluence::PublishCoordinator::new(&config.concurrency)),
+        confluence_mock,
+        audit,
+        hooks: Arc::new(hooks::PublishHooks::from_env()?),
+        object_store: object_store::ObjectStore::from_env()?.map(Arc::new),
+        git_mirror: Arc::new(git_mirror::GitMirror::from_env()),
+    };
+    scheduler::spawn(state.clone());
+    snapshot_retention::spawn(state.clone());
 
+    let features = &config.features;
+    let publishing = config::Capability::Publishing;
     let app = Router::new()
+        .route("/", get(dashboard::handle_dashboard))
         .route("/api/snapshot/{id}", get(handle_snapshot))
+        .route(
+            "/api/snapshot/{id}/pages",
+            gated(features, publishing, delete(handle_withdraw_pages)),
+        )
+        .route("/api/snapshot/{id}/prerender", post(handle_prerender))
+        .route(
+            "/api/snapshot/{id}/families/{code}/publish",
+            gated(features, publishing, post(handle_publish_family)),
+        )
+        .route("/api/snapshot/{id}/validate", get(handle_validate))
+        .route("/api/snapshot/{id}/export.zip", get(handle_export_zip))
         .route("/api/diff/{id}", get(handle_diff))
+        .route(
+            "/api/publications/{id}/promote",
+            gated(features, publishing, post(handle_promote)),
+        )
+        .route(
+            "/api/republish",
+            gated(features, publishing, post(republish::handle_republish)),
+        )
+        .route(
+            "/api/server/{id}/snapshot",
+            gated(
+                features,
+                config::Capability::SnapshotFetch,
+                post(handle_fetch_snapshot),
+            ),
+        )
+        .route(
+            "/api/webhooks/akeneo/{id}",
+            gated(
+                features,
+                config::Capability::SnapshotFetch,
+                post(akeneo_events::handle_akeneo_event),
+            ),
+        )
+        .route(
+            "/api/server/{id}/confluence/check",
+            get(preflight::handle_confluence_check),
+        )
+        .route(
+            "/api/confluence-configs",
+            get(confluence_configs::handle_list_configs)
+                .post(confluence_configs::handle_create_config),
+        )
+        .route(
+            "/api/confluence-configs/{id}",
+            get(confluence_configs::handle_get_config)
+                .patch(confluence_configs::handle_update_config)
+                .delete(confluence_configs::handle_delete_config),
+        )
+        .route(
+            "/api/confluence-configs/{id}/test",
+            post(confluence_configs::handle_test_config),
+        )
+        .route(
+            "/api/servers",
+            get(servers::handle_list_servers).post(servers::handle_create_server),
+        )
+        .route(
+            "/api/servers/{id}",
+            get(servers::handle_get_server)
+                .patch(servers::handle_update_server)
+                .delete(servers::handle_delete_server),
+        )
+        .route("/api/servers/{id}/snapshots", get(listing::handle_list_snapshots))
+        .route("/api/servers/{id}/diffs", get(listing::handle_list_diffs))
+        .route("/api/feeds/diffs.atom", get(feed::handle_diffs_feed))
+        .route("/api/servers/{id}/diffs/cumulative", get(handle_cumulative_diff))
+        .route("/api/compare/matrix", post(handle_matrix))
+        .route(
+            "/api/render/snapshot",
+            post(inline_snapshot::handle_render_snapshot)
+                .layer(DefaultBodyLimit::max(inline_snapshot::MAX_BODY_BYTES)),
+        )
+        .route("/api/jobs/{id}/events", get(jobs::handle_job_events))
+        .route("/api/schedules", get(scheduler::handle_list_schedules))
+        .route("/api/schedules/{id}", patch(scheduler::handle_update_schedule))
+        .route("/api/audit", get(audit::handle_audit))
+        .route("/api/debug/state", get(admin::handle_debug_state))
+        .route("/api/admin/reencrypt-tokens", post(admin::handle_reencrypt_tokens))
+        .route(
+            "/api/admin/migrate-snapshot-storage",
+            post(admin::handle_migrate_snapshot_storage),
+        )
+        .route(
+            "/api/admin/snapshots/{id}/restore",
+            post(snapshot_retention::handle_restore_snapshot),
+        )
+        .route(
+            "/api/admin/confluence-config-cache/invalidate",
+            post(admin::handle_invalidate_config_cache),
+        )
+        .route("/metrics", get(handle_metrics))
+        .layer(DefaultBodyLimit::max(extract::MAX_BODY_BYTES))
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit::limit))
+        // gzip or brotli as the client accepts; the SSE job events stream is left alone
+        .layer(CompressionLayer::new())
         .layer(TraceLayer::new_for_http())
         .with_state(state);
 
-    let port: u16 = std::env::var("PORT")
-        .ok()
-        .and_then(|p| p.parse().ok())
-        .unwrap_or(3000);
-
+    let port = config.port;
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
     info!("Listening on 0.0.0.0:{}", port);
-    axum::serve(listener, app).await?;
+    // Peer addresses identify clients that send no API key to the rate limiter
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -84,13 +449,15 @@ async fn main() -> anyhow::Result<()> {
 async fn handle_snapshot(
     State(state): State<AppState>,
     Path(snapshot_id): Path<Uuid>,
+    Actor(actor): Actor,
+    Query(params): Query<publish::PublishParams>,
 ) -> impl IntoResponse {
     info!("Processing snapshot: {}", snapshot_id);
 
     // 1. Fetch snapshot from DB
     let snapshot = match db::fetch_snapshot(&state.pool, snapshot_id).await {
         Ok(s) => s,
-        Err(e) => {
+        Err(e) if db::is_not_found(&e) => {
             error!("Failed to fetch snapshot {}: {:#}", snapshot_id, e);
             return (
                 StatusCode::NOT_FOUND,
@@ -101,137 +468,409 @@ async fn handle_snapshot(
             )
                 .into_response();
         }
+        Err(e) => {
+            error!("Failed to load snapshot {}: {:#}", snapshot_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
     };
 
-    // 2. Render multi-page snapshot tree
-    let page_tree = renderer::render_snapshot_pages(snapshot.label.as_deref(), &snapshot.data);
+    let job = state.jobs.start("snapshot", snapshot_id);
+    // A dry run publishes nothing, so it is not audited
+    if params.is_dry_run() {
+        let plan = publish::plan_snapshot(&state, &snapshot, params, &job).await;
+        return plan_response(plan, &job, &format!("snapshot {}", snapshot_id));
+    }
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "GET /api/snapshot/{id}",
+        target_id: snapshot_id,
+        parameters: audit::parameters(&params),
+    };
 
-    // 3. Get Confluence config and build client
-    let confluence_config =
-        match db::fetch_confluence_config(&state.pool, snapshot.akeneo_server_id).await {
-            Ok(c) => c,
-            Err(e) => {
-                error!(
-                    "Failed to fetch Confluence config for server {}: {:#}",
-                    snapshot.akeneo_server_id, e
-                );
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse::new(format!(
-                        "Failed to fetch Confluence configuration: {}",
-                        e
-                    ))),
-                )
-                    .into_response();
-            }
-        };
+    // 2. Render and publish the page tree, unless it would repeat the last publication
+    let published = match publish::unchanged_publication(&state, &snapshot, &params).await {
+        Ok(Some(page_url)) => {
+            info!("Snapshot {} is unchanged since its last publication", snapshot_id);
+            let response = UnchangedResponse {
+                status: "unchanged",
+                page_url,
+            };
+            return (StatusCode::OK, Json(response)).into_response();
+        }
+        Ok(None) => publish::publish_snapshot(&state, &snapshot, params, &job).await,
+        Err(e) => Err(e),
+    };
+    let outcome = match &published {
+        Ok(result) => audit::Outcome::Ok(vec![result.web_url.clone()]),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match published {
+        Ok(result) => (
+            StatusCode::OK,
+            Json(SuccessResponse {
+                status: "ok",
+                page_url: result.web_url,
+                operation: result.operation,
+                version: result.version,
+                jira_issue: None,
+                alerts: None,
+                warnings: Some(job.warnings()),
+                skipped_changed_items: None,
+                blog_post_url: None,
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to publish snapshot {}: {:#}", snapshot_id, e);
+            job.fail(&e);
+            (
+                publish_error_status(&e),
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
 
-    let config = confluence::ConfluenceConfig::from_db(confluence_config);
-    let client = confluence::ConfluenceClient::new(config);
+/// Status code for a failed publish: 403 for a disallowed space override, 422 for a page
+/// too large for Confluence, 500 otherwise.
+fn publish_error_status(e: &anyhow::Error) -> StatusCode {
+    if publish::is_space_not_allowed(e) || config::is_capability_disabled(e) {
+        StatusCode::FORBIDDEN
+    } else if confluence::is_render_too_large(e) {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else if hooks::is_hook_failed(e) {
+        StatusCode::BAD_GATEWAY
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
 
-    // 4. Publish root page
-    let root_result = match client
-        .publish_page(&page_tree.root_title, &page_tree.root_body)
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            error!("Failed to publish root page: {:#}", e);
+/// POST /api/snapshot/:id/families/:code/publish
+///
+/// Republishes one family's child page of a published snapshot (see
+/// `publish::publish_family`), loading only the families, attributes and measurement
+/// families of the snapshot. Accepts the publishing endpoints' query parameters. 404 when
+/// the snapshot or the family does not exist.
+async fn handle_publish_family(
+    State(state): State<AppState>,
+    Path((snapshot_id, family_code)): Path<(Uuid, String)>,
+    Actor(actor): Actor,
+    Query(params): Query<publish::PublishParams>,
+) -> impl IntoResponse {
+    info!("Processing family {} of snapshot {}", family_code, snapshot_id);
+
+    let snapshot = match db::fetch_snapshot_families(&state.pool, snapshot_id).await {
+        Ok(s) => s,
+        Err(e) if db::is_not_found(&e) => {
             return (
-                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::NOT_FOUND,
                 Json(ErrorResponse::new(format!(
-                    "Failed to publish root page to Confluence: {}",
-                    e
+                    "Snapshot not found: {}",
+                    snapshot_id
                 ))),
             )
                 .into_response();
         }
+        Err(e) => {
+            error!("Failed to load snapshot {}: {:#}", snapshot_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
     };
+    if !snapshot.data.families.iter().any(|f| f.code == family_code) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(format!(
+                "Family {} not found in snapshot {}",
+                family_code, snapshot_id
+            ))),
+        )
+            .into_response();
+    }
 
-    info!(
-        "Root page '{}' published (id={})",
-        page_tree.root_title, root_result.page_id
-    );
+    let job = state.jobs.start("family", snapshot_id);
+    let mut parameters = audit::parameters(&params);
+    if let serde_json::Value::Object(map) = &mut parameters {
+        map.insert("family".to_string(), family_code.clone().into());
+    }
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "POST /api/snapshot/{id}/families/{code}/publish",
+        target_id: snapshot_id,
+        parameters,
+    };
 
-    // 5. Publish each child page under the root page, tracking all published page IDs
-    let mut published_ids = HashSet::new();
-    published_ids.insert(root_result.page_id.clone());
-
-    for child in &page_tree.children {
-        match client
-            .publish_page_under_id(&child.title, &child.body, &root_result.page_id)
-            .await
-        {
-            Ok(child_result) => {
-                info!(
-                    "Child page '{}' published (id={})",
-                    child.title, child_result.page_id
-                );
-                published_ids.insert(child_result.page_id);
-            }
-            Err(e) => {
-                error!("Failed to publish child page '{}': {:#}", child.title, e);
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse::new(format!(
-                        "Failed to publish child page '{}' to Confluence: {}",
-                        child.title, e
-                    ))),
-                )
-                    .into_response();
-            }
+    let published = publish::publish_family(&state, &snapshot, &family_code, params, &job).await;
+    let outcome = match &published {
+        Ok(result) => audit::Outcome::Ok(vec![result.page.web_url.clone()]),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match published {
+        Ok(result) => Json(FamilyPublishResponse {
+            status: "ok",
+            page_url: result.page.web_url,
+            operation: result.page.operation,
+            version: result.page.version,
+            root_updated: result.root_updated,
+            warnings: job.warnings(),
+        })
+        .into_response(),
+        Err(e) => {
+            error!(
+                "Failed to publish family {} of snapshot {}: {:#}",
+                family_code, snapshot_id, e
+            );
+            job.fail(&e);
+            (
+                publish_error_status(&e),
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
         }
     }
+}
 
-    // 6. Clean up stale child pages that no longer exist in the snapshot
-    match client.get_child_pages(&root_result.page_id).await {
-        Ok(existing_children) => {
-            let stale_children: Vec<_> = existing_children
-                .into_iter()
-                .filter(|child| !published_ids.contains(&child.id))
-                .collect();
-
-            if !stale_children.is_empty() {
-                info!(
-                    "Found {} stale child page(s) to remove",
-                    stale_children.len()
-                );
-            }
+/// POST /api/snapshot/:id/prerender
+///
+/// Renders a snapshot's page tree into the render cache without publishing, so a
+/// later publish only spends time on Confluence API calls.
+async fn handle_prerender(
+    State(state): State<AppState>,
+    Path(snapshot_id): Path<Uuid>,
+) -> impl IntoResponse {
+    info!("Pre-rendering snapshot: {}", snapshot_id);
 
-            for stale in &stale_children {
-                match client.delete_page(&stale.id).await {
-                    Ok(()) => {
-                        info!(
-                            "Deleted stale child page '{}' (id={})",
-                            stale.title, stale.id
-                        );
-                    }
-                    Err(e) => {
-                        warn!(
-                            "Failed to delete stale child page '{}' (id={}): {:#}",
-                            stale.title, stale.id, e
-                        );
-                    }
-                }
-            }
+    match render_cache::prerender_snapshot(
+        &state.pool,
+        &state.render_cache,
+        &state.render_options,
+        snapshot_id,
+    )
+    .await
+    {
+        Ok(pages) => {
+            info!("Pre-rendered snapshot {} ({} pages)", snapshot_id, pages);
+            (
+                StatusCode::OK,
+                Json(PrerenderResponse {
+                    status: "ok",
+                    pages,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) if db::is_not_found(&e) => {
+            error!("Failed to pre-render snapshot {}: {:#}", snapshot_id, e);
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!(
+                    "Snapshot not found: {}",
+                    snapshot_id
+                ))),
+            )
+                .into_response()
         }
         Err(e) => {
-            warn!(
-                "Failed to fetch existing child pages for stale cleanup: {:#}",
-                e
-            );
+            error!("Failed to pre-render snapshot {}: {:#}", snapshot_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
         }
     }
+}
 
-    // 7. Return the root page URL
-    (
-        StatusCode::OK,
-        Json(SuccessResponse {
+/// DELETE /api/snapshot/:id/pages
+///
+/// Withdraws a snapshot: deletes (trashes) every page tree published for it in
+/// Confluence and removes those publications from the history.
+async fn handle_withdraw_pages(
+    State(state): State<AppState>,
+    Path(snapshot_id): Path<Uuid>,
+    Actor(actor): Actor,
+) -> impl IntoResponse {
+    info!("Withdrawing pages of snapshot: {}", snapshot_id);
+
+    let snapshot = match db::fetch_snapshot_meta(&state.pool, snapshot_id).await {
+        Ok(s) => s,
+        Err(e) if db::is_not_found(&e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!("{}", e))),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!("Failed to load snapshot {}: {:#}", snapshot_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
+    };
+
+    let withdrawn = publish::withdraw_snapshot(&state, &snapshot).await;
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "DELETE /api/snapshot/{id}/pages",
+        target_id: snapshot_id,
+        parameters: serde_json::json!({}),
+    };
+    let outcome = match &withdrawn {
+        Ok(_) => audit::Outcome::Ok(Vec::new()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match withdrawn {
+        Ok(withdrawal) => (
+            StatusCode::OK,
+            Json(WithdrawResponse {
+                status: "ok",
+                withdrawal,
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to withdraw snapshot {}: {:#}", snapshot_id, e);
+            (
+                publish_error_status(&e),
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// GET /api/snapshot/:id/validate
+///
+/// Checks a snapshot's data against the expected Akeneo model shape and returns
+/// any structural warnings, without rendering or contacting Confluence. Snapshot data never
+/// changes, so the report is sent with `Last-Modified` set to the snapshot's completion.
+async fn handle_validate(
+    State(state): State<AppState>,
+    Path(snapshot_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    info!("Validating snapshot: {}", snapshot_id);
+
+    let data = match db::fetch_snapshot_raw_data(&state.pool, snapshot_id).await {
+        Ok(d) => d,
+        Err(e) if !db::is_not_found(&e) => {
+            error!("Failed to load snapshot {}: {:#}", snapshot_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!("Failed to fetch snapshot {}: {:#}", snapshot_id, e);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!(
+                    "Snapshot not found: {}",
+                    snapshot_id
+                ))),
+            )
+                .into_response();
+        }
+    };
+
+    let completed_at = match db::fetch_snapshot_meta(&state.pool, snapshot_id).await {
+        Ok(meta) => Some(meta.completed_at),
+        Err(e) => {
+            error!("Failed to load snapshot {} metadata: {:#}", snapshot_id, e);
+            None
+        }
+    };
+
+    let warnings = validate::validate_snapshot(&data);
+    info!(
+        "Snapshot {} validated with {} warning(s)",
+        snapshot_id,
+        warnings.len()
+    );
+
+    http_cache::json_last_modified(
+        &headers,
+        &ValidateResponse {
             status: "ok",
-            page_url: root_result.web_url,
-        }),
+            valid: warnings.is_empty(),
+            warnings,
+        },
+        completed_at,
     )
-        .into_response()
+}
+
+/// GET /api/snapshot/:id/export.zip
+///
+/// Renders a snapshot's page tree to standalone HTML files with an index and a stylesheet
+/// and returns them as a ZIP, for offline review or archival. Confluence is not contacted.
+async fn handle_export_zip(
+    State(state): State<AppState>,
+    Path(snapshot_id): Path<Uuid>,
+) -> impl IntoResponse {
+    info!("Exporting snapshot: {}", snapshot_id);
+
+    let snapshot = match db::fetch_snapshot(&state.pool, snapshot_id).await {
+        Ok(s) => s,
+        Err(e) if db::is_not_found(&e) => {
+            error!("Failed to fetch snapshot {}: {:#}", snapshot_id, e);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!(
+                    "Snapshot not found: {}",
+                    snapshot_id
+                ))),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!("Failed to load snapshot {}: {:#}", snapshot_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
+    };
+
+    match archive::export_snapshot(&state, &snapshot).await {
+        Ok(zip) => {
+            info!("Exported snapshot {} ({} bytes)", snapshot_id, zip.len());
+            (
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"snapshot-{}.zip\"", snapshot_id),
+                    ),
+                ],
+                zip,
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("Failed to export snapshot {}: {:#}", snapshot_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
 }
 
 /// GET /api/diff/:id
@@ -241,105 +880,541 @@ async fn handle_snapshot(
 async fn handle_diff(
     State(state): State<AppState>,
     Path(diff_id): Path<Uuid>,
+    Actor(actor): Actor,
+    Query(params): Query<publish::PublishParams>,
 ) -> impl IntoResponse {
     info!("Processing diff: {}", diff_id);
+    let job = state.jobs.start("diff", diff_id);
 
-    // 1. Fetch diff and both snapshots
-    let (diff_row, before_snapshot, after_snapshot) =
-        match db::fetch_diff(&state.pool, diff_id).await {
-            Ok(data) => data,
-            Err(e) => {
-                error!("Failed to fetch diff {}: {:#}", diff_id, e);
-                return (
-                    StatusCode::NOT_FOUND,
-                    Json(ErrorResponse::new(format!("Diff not found: {}", diff_id))),
-                )
-                    .into_response();
-            }
-        };
-
-    // 2. Parse the diff data
-    let report = match diff::parse_diff_data(&diff_row.data) {
-        Ok(r) => r,
+    // 1. Fetch the diff
+    let diff_row = match db::fetch_diff(&state.pool, diff_id).await {
+        Ok(row) => row,
+        Err(e) if db::is_not_found(&e) => {
+            error!("Failed to fetch diff {}: {:#}", diff_id, e);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!("Diff not found: {}", diff_id))),
+            )
+                .into_response();
+        }
         Err(e) => {
-            error!("Failed to parse diff data for {}: {:#}", diff_id, e);
+            error!("Failed to load diff {}: {:#}", diff_id, e);
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new(format!(
-                    "Failed to parse diff data: {}",
-                    e
-                ))),
+                Json(ErrorResponse::new(format!("{:#}", e))),
             )
                 .into_response();
         }
     };
 
-    // Log summary
-    for (category, cat_diff) in &report {
-        info!(
-            "  {}: {} added, {} removed, {} changed",
-            category,
-            cat_diff.added.len(),
-            cat_diff.removed.len(),
-            cat_diff.changed.len()
-        );
+    if params.is_dry_run() {
+        let plan = publish::plan_diff(&state, diff_row, params, &job).await;
+        return plan_response(plan, &job, &format!("diff {}", diff_id));
+    }
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "GET /api/diff/{id}",
+        target_id: diff_id,
+        parameters: audit::parameters(&params),
+    };
+
+    // 2. Fetch both snapshots and the target while parsing, then render and publish
+    let published = publish::publish_diff(&state, diff_row, params, &job).await;
+    let outcome = match &published {
+        Ok(result) => audit::Outcome::Ok(result.urls()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match published {
+        Ok(result) => (
+            StatusCode::OK,
+            Json(SuccessResponse {
+                status: "ok",
+                page_url: result.page.web_url,
+                operation: result.page.operation,
+                version: result.page.version,
+                jira_issue: result.jira_issue,
+                alerts: Some(result.alerts),
+                warnings: Some(result.warnings),
+                skipped_changed_items: Some(result.skipped_changed_items),
+                blog_post_url: result.blog_post.map(|post| post.web_url),
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to publish diff {}: {:#}", diff_id, e);
+            job.fail(&e);
+            (
+                publish_error_status(&e),
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Date range of `GET /api/servers/{id}/diffs/cumulative`: `YYYY-MM-DD` dates or RFC 3339
+/// timestamps; a `to` date includes that whole day. Either end may be left open.
+#[derive(Deserialize, Serialize)]
+struct CumulativeRange {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// GET /api/servers/:id/diffs/cumulative
+///
+/// Composes the stored diffs between an Akeneo server's first and last snapshot completed
+/// in `?from=&to=` into one diff, and publishes it as a single page. Accepts the same query
+/// parameters as the other publishing endpoints (except `draft`). 422 when the range has
+/// fewer than two snapshots or no chain of diffs connects them.
+async fn handle_cumulative_diff(
+    State(state): State<AppState>,
+    Path(server_id): Path<Uuid>,
+    Actor(actor): Actor,
+    Query(range): Query<CumulativeRange>,
+    Query(params): Query<publish::PublishParams>,
+) -> impl IntoResponse {
+    let bounds = (
+        range.from.as_deref().map(|v| listing::parse_bound(v, false)).transpose(),
+        range.to.as_deref().map(|v| listing::parse_bound(v, true)).transpose(),
+    );
+    let (from, to) = match bounds {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(message), _) | (_, Err(message)) => {
+            return (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(message))).into_response();
+        }
+    };
+    match db::fetch_akeneo_server(&state.pool, server_id).await {
+        Ok(_) => {}
+        Err(e) if db::is_not_found(&e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!("{}", e))),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!("Failed to load Akeneo server {}: {:#}", server_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
     }
 
-    // 3. Render the diff page
-    let (title, body) = renderer::render_diff_page(
-        before_snapshot.label.as_deref(),
-        after_snapshot.label.as_deref(),
-        &report,
+    info!(
+        "Processing cumulative diff for server {} ({} to {})",
+        server_id,
+        range.from.as_deref().unwrap_or("the first snapshot"),
+        range.to.as_deref().unwrap_or("the last snapshot")
     );
+    let job = state.jobs.start("cumulative_diff", server_id);
+    let mut parameters = audit::parameters(&params);
+    if let (serde_json::Value::Object(map), serde_json::Value::Object(bounds)) =
+        (&mut parameters, audit::parameters(&range))
+    {
+        map.extend(bounds);
+    }
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "GET /api/servers/{id}/diffs/cumulative",
+        target_id: server_id,
+        parameters,
+    };
 
-    // 4. Get Confluence config and build client
-    let confluence_config =
-        match db::fetch_confluence_config(&state.pool, after_snapshot.akeneo_server_id).await {
-            Ok(c) => c,
-            Err(e) => {
-                error!(
-                    "Failed to fetch Confluence config for server {}: {:#}",
-                    after_snapshot.akeneo_server_id, e
-                );
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse::new(format!(
-                        "Failed to fetch Confluence configuration: {}",
-                        e
-                    ))),
-                )
-                    .into_response();
-            }
+    let identity = &state.render_options.diff.identity_keys;
+    let published = match cumulative::load(&state.pool, server_id, from, to, identity).await {
+        Ok(cumulative) => publish::publish_cumulative_diff(&state, cumulative, params, &job).await,
+        Err(e) => Err(e),
+    };
+    let outcome = match &published {
+        Ok(result) => audit::Outcome::Ok(result.urls()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match published {
+        Ok(result) => (
+            StatusCode::OK,
+            Json(SuccessResponse {
+                status: "ok",
+                page_url: result.page.web_url,
+                operation: result.page.operation,
+                version: result.page.version,
+                jira_issue: None,
+                alerts: Some(result.alerts),
+                warnings: Some(result.warnings),
+                skipped_changed_items: Some(result.skipped_changed_items),
+                blog_post_url: result.blog_post.map(|post| post.web_url),
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to publish cumulative diff for server {}: {:#}", server_id, e);
+            job.fail(&e);
+            let status = if cumulative::is_no_diff_chain(&e) {
+                StatusCode::UNPROCESSABLE_ENTITY
+            } else {
+                publish_error_status(&e)
+            };
+            (status, Json(ErrorResponse::new(format!("{:#}", e)))).into_response()
+        }
+    }
+}
+
+/// JSON response of the comparison matrix endpoint.
+#[derive(Serialize)]
+struct MatrixResponse {
+    status: &'static str,
+    #[serde(flatten)]
+    matrix: matrix::Matrix,
+    /// The published matrix page, with `"publish": true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page_url: Option<String>,
+}
+
+/// POST /api/compare/matrix
+///
+/// Compares the presence and configuration of entities (`matrix::Entity`) across
+/// snapshots, e.g. of the servers of several brands, and returns the matrix. With
+/// `"publish": true` it is also published as a page to the Confluence target of
+/// `server_id` (default: the first snapshot's server), accepting the parent and space
+/// query parameters of the other publishing endpoints. 400 on an invalid request, 404
+/// when a snapshot or the server does not exist.
+async fn handle_matrix(
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+    Query(params): Query<publish::PublishParams>,
+    Json(request): Json<matrix::MatrixRequest>,
+) -> impl IntoResponse {
+    if request.publish && !state.config.features.publishing {
+        return capability_disabled(config::Capability::Publishing);
+    }
+    let entities = match request.entities() {
+        Ok(entities) => entities,
+        Err(message) => {
+            return (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(message))).into_response();
+        }
+    };
+    let matrix = match matrix::build(&state.pool, &request.snapshots, &entities).await {
+        Ok(matrix) => matrix,
+        Err(e) if db::is_not_found(&e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!("{}", e))),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!("Failed to build comparison matrix: {:#}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
+    };
+    if !request.publish {
+        let response = MatrixResponse {
+            status: "ok",
+            matrix,
+            page_url: None,
         };
+        return (StatusCode::OK, Json(response)).into_response();
+    }
+
+    let server_id = request
+        .server_id
+        .unwrap_or(matrix.snapshots[0].server_id);
+    match db::fetch_akeneo_server(&state.pool, server_id).await {
+        Ok(_) => {}
+        Err(e) if db::is_not_found(&e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!("{}", e))),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!("Failed to load Akeneo server {}: {:#}", server_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
+    }
+
+    info!(
+        "Publishing comparison matrix of {} entities across {} snapshots",
+        matrix.rows.len(),
+        matrix.snapshots.len()
+    );
+    let job = state.jobs.start("matrix", server_id);
+    let mut parameters = audit::parameters(&params);
+    if let (serde_json::Value::Object(map), serde_json::Value::Object(body)) =
+        (&mut parameters, audit::parameters(&request))
+    {
+        map.extend(body);
+    }
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "POST /api/compare/matrix",
+        target_id: server_id,
+        parameters,
+    };
 
-    let config = confluence::ConfluenceConfig::from_db(confluence_config);
-    let client = confluence::ConfluenceClient::new(config);
+    let title = request.title.as_deref();
+    let published = publish::publish_matrix(&state, server_id, &matrix, title, params, &job).await;
+    let outcome = match &published {
+        Ok(page) => audit::Outcome::Ok(vec![page.web_url.clone()]),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match published {
+        Ok(page) => {
+            let response = MatrixResponse {
+                status: "ok",
+                matrix,
+                page_url: Some(page.web_url),
+            };
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to publish comparison matrix: {:#}", e);
+            job.fail(&e);
+            (publish_error_status(&e), Json(ErrorResponse::new(format!("{:#}", e))))
+                .into_response()
+        }
+    }
+}
 
-    // 5. Publish the diff page
-    let result = match client.publish_page(&title, &body).await {
-        Ok(r) => r,
+/// POST /api/publications/:id/promote
+///
+/// Publishes a reviewed draft to its live location and removes the staged draft pages.
+/// Accepts the same query parameters as the publishing endpoints (except `draft`).
+async fn handle_promote(
+    State(state): State<AppState>,
+    Path(publication_id): Path<Uuid>,
+    Actor(actor): Actor,
+    Query(params): Query<publish::PublishParams>,
+) -> impl IntoResponse {
+    info!("Promoting draft publication: {}", publication_id);
+
+    let draft = match db::fetch_publication(&state.pool, publication_id).await {
+        Ok(p) => p,
+        Err(e) if db::is_not_found(&e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!("{}", e))),
+            )
+                .into_response();
+        }
         Err(e) => {
-            error!("Failed to publish diff page: {:#}", e);
+            error!("Failed to load publication {}: {:#}", publication_id, e);
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new(format!(
-                    "Failed to publish diff page to Confluence: {}",
-                    e
-                ))),
+                Json(ErrorResponse::new(format!("{:#}", e))),
             )
                 .into_response();
         }
     };
 
-    info!("Diff page '{}' published (id={})", title, result.page_id);
+    if draft.kind != db::PublicationKind::Draft.as_str() {
+        return (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new(format!(
+                "Publication {} is not a draft (kind '{}')",
+                publication_id, draft.kind
+            ))),
+        )
+            .into_response();
+    }
+    if let Some(promoted_at) = draft.promoted_at {
+        return (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new(format!(
+                "Publication {} was already promoted at {}",
+                publication_id,
+                promoted_at.to_rfc3339()
+            ))),
+        )
+            .into_response();
+    }
+
+    let job = match draft.diff_id {
+        Some(diff_id) => state.jobs.start("diff", diff_id),
+        None => state.jobs.start("snapshot", draft.snapshot_id),
+    };
+
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "POST /api/publications/{id}/promote",
+        target_id: publication_id,
+        parameters: audit::parameters(&params),
+    };
+    let promoted = publish::promote_draft(&state, &draft, params, &job).await;
+    let outcome = match &promoted {
+        Ok(result) => audit::Outcome::Ok(vec![result.web_url.clone()]),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match promoted {
+        Ok(result) => (
+            StatusCode::OK,
+            Json(SuccessResponse {
+                status: "ok",
+                page_url: result.web_url,
+                operation: result.operation,
+                version: result.version,
+                jira_issue: None,
+                alerts: None,
+                warnings: None,
+                skipped_changed_items: None,
+                blog_post_url: None,
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to promote publication {}: {:#}", publication_id, e);
+            job.fail(&e);
+            (
+                publish_error_status(&e),
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /api/server/:id/snapshot
+///
+/// Pulls the live data model from an Akeneo server, stores it as a new snapshot,
+/// and (with `?publish=true`) publishes it to Confluence immediately.
+async fn handle_fetch_snapshot(
+    State(state): State<AppState>,
+    Path(server_id): Path<Uuid>,
+    Actor(actor): Actor,
+    Query(params): Query<FetchSnapshotParams>,
+    Query(publish_params): Query<publish::PublishParams>,
+) -> impl IntoResponse {
+    info!("Fetching live snapshot for Akeneo server: {}", server_id);
+    if params.publish && !state.config.features.publishing {
+        return capability_disabled(config::Capability::Publishing);
+    }
+
+    // 1. Fetch the live model and store it
+    let snapshot_id = {
+        let _job = state.jobs.start("fetch", server_id);
+        match akeneo::fetch_and_store_snapshot(&state, server_id, params.label.as_deref()).await {
+            Ok(id) => id,
+            Err(e) if db::is_not_found(&e) => {
+                error!("Failed to fetch Akeneo server {}: {:#}", server_id, e);
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(ErrorResponse::new(format!(
+                        "Akeneo server not found: {}",
+                        server_id
+                    ))),
+                )
+                    .into_response();
+            }
+            Err(e) => {
+                error!("Failed to fetch live snapshot for {}: {:#}", server_id, e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse::new(format!("{:#}", e))),
+                )
+                    .into_response();
+            }
+        }
+    };
+
+    // 2. Optionally publish it
+    let mut page_url = None;
+    let mut warnings = Vec::new();
+    if params.publish {
+        let job = state.jobs.start("snapshot", snapshot_id);
+        let mut parameters = audit::parameters(&publish_params);
+        parameters["server_id"] = serde_json::json!(server_id);
+        if let Some(label) = &params.label {
+            parameters["label"] = serde_json::json!(label);
+        }
+        let audit_entry = audit::Entry {
+            actor: &actor,
+            endpoint: "POST /api/server/{id}/snapshot",
+            target_id: snapshot_id,
+            parameters,
+        };
+        let snapshot = match db::fetch_snapshot(&state.pool, snapshot_id).await {
+            Ok(snapshot) => publish::unchanged_publication(&state, &snapshot, &publish_params)
+                .await
+                .map(|unchanged| (snapshot, unchanged)),
+            Err(e) => Err(e),
+        };
+        if let Ok((_, Some(page_url))) = snapshot {
+            info!("Snapshot {} is unchanged since its last publication", snapshot_id);
+            let response = FetchSnapshotResponse {
+                status: "unchanged",
+                snapshot_id,
+                page_url: Some(page_url),
+                warnings,
+            };
+            return (StatusCode::OK, Json(response)).into_response();
+        }
+        let published = match snapshot {
+            Ok((snapshot, _)) => {
+                publish::publish_snapshot(&state, &snapshot, publish_params, &job).await
+            }
+            Err(e) => Err(e),
+        };
+        let outcome = match &published {
+            Ok(result) => audit::Outcome::Ok(vec![result.web_url.clone()]),
+            Err(e) => audit::Outcome::Error(e),
+        };
+        audit::record(&state, audit_entry, outcome).await;
+        match published {
+            Ok(result) => {
+                page_url = Some(result.web_url);
+                warnings = job.warnings();
+            }
+            Err(e) => {
+                error!("Failed to publish snapshot {}: {:#}", snapshot_id, e);
+                job.fail(&e);
+                return (
+                    publish_error_status(&e),
+                    Json(ErrorResponse::new(format!(
+                        "Snapshot {} was stored but publishing failed: {:#}",
+                        snapshot_id, e
+                    ))),
+                )
+                    .into_response();
+            }
+        }
+    }
 
-    // 6. Return the page URL
     (
         StatusCode::OK,
-        Json(SuccessResponse {
+        Json(FetchSnapshotResponse {
             status: "ok",
-            page_url: result.web_url,
+            snapshot_id,
+            page_url,
+            warnings,
         }),
     )
         .into_response()
 }
+
+/// GET /metrics
+///
+/// Exposes service metrics (including Confluence connection reuse) in the
+/// Prometheus text format.
+async fn handle_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        metrics::render_prometheus(&state.render_cache.stats()),
+    )
+}
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+
+use crate::db::SnapshotRow;
+use crate::model::{Category, Labels};
+
+/// Render a snapshot's model as one Markdown document, e.g. for the Git mirror
+/// (`git_mirror.rs`). Everything is sorted by code so the document of the next snapshot
+/// diffs line by line against this one.
+pub fn render_snapshot(snapshot: &SnapshotRow, server: &str) -> String {
+    let data = &snapshot.data;
+    let mut out = format!(
+        "# {}\n\nSnapshot `{}` of {}, completed {}.\n",
+        escape(snapshot.label.as_deref().unwrap_or("Unnamed snapshot")),
+        snapshot.id,
+        escape(server),
+        snapshot.completed_at.format("%Y-%m-%d %H:%M UTC")
+    );
+
+    let mut channels: Vec<_> = data.channels.iter().collect();
+    channels.sort_by(|a, b| a.code.cmp(&b.code));
+    section(&mut out, "Channels", channels.len());
+    table(
+        &mut out,
+        &["Code", "Label", "Locales", "Currencies", "Category tree"],
+        channels.iter().map(|c| {
+            vec![
+                code(&c.code),
+                label(&c.labels),
+                c.locales.join(", "),
+                c.currencies.join(", "),
+                c.category_tree.as_deref().map(code).unwrap_or_default(),
+            ]
+        }),
+    );
+
+    let mut attributes: Vec<_> = data.attributes.iter().collect();
+    attributes.sort_by(|a, b| a.code.cmp(&b.code));
+    section(&mut out, "Attributes", attributes.len());
+    table(
+        &mut out,
+        &[
+            "Code",
+            "Label",
+            "Type",
+            "Group",
+            "Unique",
+            "Scopable",
+            "Localizable",
+        ],
+        attributes.iter().map(|a| {
+            vec![
+                code(&a.code),
+                label(&a.labels),
+                a.attr_type.as_deref().map(code).unwrap_or_default(),
+                a.group.as_deref().map(code).unwrap_or_default(),
+                yes_no(a.unique),
+                yes_no(a.scopable),
+                yes_no(a.localizable),
+            ]
+        }),
+    );
+
+    let mut families: Vec<_> = data.families.iter().collect();
+    families.sort_by(|a, b| a.code.cmp(&b.code));
+    section(&mut out, "Families", families.len());
+    for family in families {
+        out.push_str(&format!(
+            "\n### {} ({})\n\n",
+            label(&family.labels),
+            code(&family.code)
+        ));
+        let details = [
+            ("Parent", family.parent.as_deref()),
+            ("Attribute as label", family.attribute_as_label.as_deref()),
+            ("Attribute as image", family.attribute_as_image.as_deref()),
+        ];
+        for (name, value) in details {
+            if let Some(value) = value {
+                out.push_str(&format!("- {}: {}\n", name, code(value)));
+            }
+        }
+        // Attribute -> channels it is required in
+        let mut required: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (channel, codes) in &family.attribute_requirements {
+            for attribute in codes {
+                required.entry(attribute).or_default().push(channel);
+            }
+        }
+        let mut codes: Vec<_> = family.attributes.iter().collect();
+        codes.sort();
+        table(
+            &mut out,
+            &["Attribute", "Required in"],
+            codes.iter().map(|attribute| {
+                let channels = required
+                    .get(attribute.as_str())
+                    .cloned()
+                    .unwrap_or_default();
+                vec![code(attribute), channels.join(", ")]
+            }),
+        );
+    }
+
+    section(&mut out, "Categories", data.categories.len());
+    if !data.categories.is_empty() {
+        out.push('\n');
+    }
+    let mut children: BTreeMap<Option<&str>, Vec<&Category>> = BTreeMap::new();
+    for category in &data.categories {
+        children
+            .entry(category.parent.as_deref())
+            .or_default()
+            .push(category);
+    }
+    for list in children.values_mut() {
+        list.sort_by(|a, b| a.code.cmp(&b.code));
+    }
+    // Roots, plus categories whose parent is not in the snapshot
+    let known: std::collections::HashSet<&str> =
+        data.categories.iter().map(|c| c.code.as_str()).collect();
+    let mut roots: Vec<&Category> = data
+        .categories
+        .iter()
+        .filter(|c| c.parent.as_deref().is_none_or(|p| !known.contains(p)))
+        .collect();
+    roots.sort_by(|a, b| a.code.cmp(&b.code));
+    for root in roots {
+        category_tree(&mut out, root, &children, 0);
+    }
+
+    let mut measurements: Vec<_> = data.measurement_families.iter().collect();
+    measurements.sort_by(|a, b| a.code.cmp(&b.code));
+    section(&mut out, "Measurement families", measurements.len());
+    table(
+        &mut out,
+        &["Code", "Label", "Standard unit", "Units"],
+        measurements.iter().map(|m| {
+            vec![
+                code(&m.code),
+                label(&m.labels),
+                m.standard_unit_code
+                    .as_deref()
+                    .map(code)
+                    .unwrap_or_default(),
+                m.units
+                    .keys()
+                    .map(|u| code(u))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ]
+        }),
+    );
+
+    section(
+        &mut out,
+        "Attribute options",
+        data.attribute_options_count(),
+    );
+    for (attribute, options) in &data.attribute_options {
+        out.push_str(&format!("\n### {}\n\n", code(attribute)));
+        let mut options: Vec<_> = options.iter().collect();
+        options.sort_by(|a, b| a.code.cmp(&b.code));
+        table(
+            &mut out,
+            &["Code", "Label", "Sort order"],
+            options.iter().map(|o| {
+                vec![
+                    code(&o.code),
+                    label(&o.labels),
+                    o.sort_order.map(|s| s.to_string()).unwrap_or_default(),
+                ]
+            }),
+        );
+    }
+
+    out
+}
+
+fn section(out: &mut String, title: &str, count: usize) {
+    out.push_str(&format!("\n## {} ({})\n", title, count));
+}
+
+/// A table, or nothing without rows. Starts with a blank line.
+fn table(out: &mut String, header: &[&str], rows: impl Iterator<Item = Vec<String>>) {
+    let mut rows = rows.peekable();
+    if rows.peek().is_none() {
+        return;
+    }
+    out.push_str(&format!("\n| {} |\n", header.join(" | ")));
+    out.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+    for row in rows {
+        out.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+}
+
+fn category_tree(
+    out: &mut String,
+    category: &Category,
+    children: &BTreeMap<Option<&str>, Vec<&Category>>,
+    depth: usize,
+) {
+    out.push_str(&format!(
+        "{}- {} ({})\n",
+        "  ".repeat(depth),
+        label(&category.labels),
+        code(&category.code)
+    ));
+    for child in children
+        .get(&Some(category.code.as_str()))
+        .into_iter()
+        .flatten()
+    {
+        category_tree(out, child, children, depth + 1);
+    }
+}
+
+/// The first label, escaped; empty without labels.
+fn label(labels: &Labels) -> String {
+    labels.first().map(escape).unwrap_or_default()
+}
+
+fn code(value: &str) -> String {
+    format!("`{}`", value.replace('`', "'"))
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "Yes" } else { "No" }.to_string()
+}
+
+/// Escape text for a table cell or heading: pipes, Markdown emphasis and line breaks.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '|' | '*' | '`' | '[' | ']' | '<' | '>' | '#' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' | '\r' => out.push(' '),
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,287 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::db;
+use crate::model::Snapshot;
+
+/// Most snapshots (columns) one matrix compares.
+pub const MAX_SNAPSHOTS: usize = 20;
+/// Most entities (rows) of one matrix.
+pub const MAX_ENTITIES: usize = 500;
+
+/// Body of `POST /api/compare/matrix`.
+#[derive(Deserialize, Serialize)]
+pub struct MatrixRequest {
+    /// The snapshots to compare, one column each, in this order.
+    pub snapshots: Vec<Uuid>,
+    /// The rows, as `kind:code` (see `Entity::parse`), e.g. `attribute:sku`.
+    pub entities: Vec<String>,
+    /// Also publish the matrix as a Confluence page.
+    #[serde(default)]
+    pub publish: bool,
+    /// Akeneo server whose Confluence target the page is published to (default: the first
+    /// snapshot's).
+    pub server_id: Option<Uuid>,
+    /// Page title (default: the target's snapshot title plus `MATRIX_TITLE_SUFFIX`).
+    pub title: Option<String>,
+}
+
+impl MatrixRequest {
+    /// Check the request's size and parse its entities, with a message for the client.
+    pub fn entities(&self) -> Result<Vec<Entity>, String> {
+        if self.snapshots.is_empty() {
+            return Err("snapshots must list at least one snapshot".to_string());
+        }
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            return Err(format!(
+                "At most {} snapshots can be compared",
+                MAX_SNAPSHOTS
+            ));
+        }
+        if self.entities.is_empty() {
+            return Err("entities must list at least one entity".to_string());
+        }
+        if self.entities.len() > MAX_ENTITIES {
+            return Err(format!("At most {} entities can be compared", MAX_ENTITIES));
+        }
+        self.entities
+            .iter()
+            .map(|entity| Entity::parse(entity).map_err(|e| format!("{:#}", e)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityKind {
+    Attribute,
+    Family,
+    Channel,
+    Category,
+    MeasurementFamily,
+    AttributeOption,
+}
+
+impl EntityKind {
+    /// Singular or plural, e.g. `attribute` or `attributes`.
+    fn parse(kind: &str) -> Option<Self> {
+        match kind.trim().to_lowercase().as_str() {
+            "attribute" | "attributes" => Some(EntityKind::Attribute),
+            "family" | "families" => Some(EntityKind::Family),
+            "channel" | "channels" => Some(EntityKind::Channel),
+            "category" | "categories" => Some(EntityKind::Category),
+            "measurement_family" | "measurement_families" => Some(EntityKind::MeasurementFamily),
+            "attribute_option" | "attribute_options" => Some(EntityKind::AttributeOption),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EntityKind::Attribute => "attribute",
+            EntityKind::Family => "family",
+            EntityKind::Channel => "channel",
+            EntityKind::Category => "category",
+            EntityKind::MeasurementFamily => "measurement_family",
+            EntityKind::AttributeOption => "attribute_option",
+        }
+    }
+}
+
+/// The entity of a matrix row.
+pub struct Entity {
+    pub kind: EntityKind,
+    /// For attribute options, `<attribute>.<option>`.
+    pub code: String,
+}
+
+impl Entity {
+    /// Parse `kind:code`, e.g. `attribute:sku`, `channel:ecommerce` or (attribute options)
+    /// `attribute_option:color.red`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let Some((kind, code)) = value.split_once(':') else {
+            bail!(
+                "Invalid entity '{}': expected kind:code, e.g. attribute:sku",
+                value
+            );
+        };
+        let kind = EntityKind::parse(kind).with_context(|| {
+            format!(
+                "Invalid entity '{}': unknown kind (attribute, family, channel, category, \
+                 measurement_family or attribute_option)",
+                value
+            )
+        })?;
+        let code = code.trim();
+        if code.is_empty() {
+            bail!("Invalid entity '{}': missing code", value);
+        }
+        if kind == EntityKind::AttributeOption && !code.contains('.') {
+            bail!(
+                "Invalid entity '{}': expected attribute_option:<attribute>.<option>",
+                value
+            );
+        }
+        Ok(Self {
+            kind,
+            code: code.to_string(),
+        })
+    }
+
+    /// How a snapshot configures the entity, e.g. `pim_catalog_text, group marketing,
+    /// scopable`; `None` when the snapshot does not have it.
+    fn configuration(&self, snapshot: &Snapshot) -> Option<String> {
+        let code = self.code.as_str();
+        let mut parts: Vec<String> = Vec::new();
+        match self.kind {
+            EntityKind::Attribute => {
+                let attribute = snapshot.attributes.iter().find(|a| a.code == code)?;
+                parts.extend(attribute.attr_type.clone());
+                parts.extend(attribute.group.as_ref().map(|g| format!("group {}", g)));
+                for (flag, set) in [
+                    ("unique", attribute.unique),
+                    ("scopable", attribute.scopable),
+                    ("localizable", attribute.localizable),
+                ] {
+                    if set {
+                        parts.push(flag.to_string());
+                    }
+                }
+            }
+            EntityKind::Family => {
+                let family = snapshot.families.iter().find(|f| f.code == code)?;
+                parts.push(format!("{} attributes", family.attributes.len()));
+                parts.extend(family.parent.as_ref().map(|p| format!("parent {}", p)));
+                parts.extend(
+                    family
+                        .attribute_as_label
+                        .as_ref()
+                        .map(|a| format!("label {}", a)),
+                );
+                for (channel, required) in &family.attribute_requirements {
+                    parts.push(format!("{} required in {}", required.len(), channel));
+                }
+            }
+            EntityKind::Channel => {
+                let channel = snapshot.channels.iter().find(|c| c.code == code)?;
+                parts.push(format!("locales {}", channel.locales.join(" ")));
+                parts.push(format!("currencies {}", channel.currencies.join(" ")));
+                parts.extend(
+                    channel
+                        .category_tree
+                        .as_ref()
+                        .map(|t| format!("tree {}", t)),
+                );
+            }
+            EntityKind::Category => {
+                let category = snapshot.categories.iter().find(|c| c.code == code)?;
+                parts.push(match &category.parent {
+                    Some(parent) => format!("parent {}", parent),
+                    None => "root".to_string(),
+                });
+            }
+            EntityKind::MeasurementFamily => {
+                let family = snapshot
+                    .measurement_families
+                    .iter()
+                    .find(|m| m.code == code)?;
+                parts.extend(
+                    family
+                        .standard_unit_code
+                        .as_ref()
+                        .map(|u| format!("standard unit {}", u)),
+                );
+                parts.push(format!("{} units", family.units.len()));
+            }
+            EntityKind::AttributeOption => {
+                let (attribute, option) = code.split_once('.')?;
+                let option = snapshot
+                    .attribute_options
+                    .get(attribute)?
+                    .iter()
+                    .find(|o| o.code == option)?;
+                parts.extend(option.sort_order.map(|s| format!("sort order {}", s)));
+            }
+        }
+        Some(parts.join(", "))
+    }
+}
+
+/// Presence and configuration of each requested entity in each requested snapshot.
+#[derive(Serialize)]
+pub struct Matrix {
+    pub snapshots: Vec<MatrixSnapshot>,
+    pub rows: Vec<MatrixRow>,
+}
+
+/// A column of the matrix.
+#[derive(Serialize)]
+pub struct MatrixSnapshot {
+    pub id: Uuid,
+    pub server_id: Uuid,
+    pub server: String,
+    pub label: Option<String>,
+    pub completed_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct MatrixRow {
+    /// `kind:code`, with the singular kind, e.g. `attribute:sku`.
+    pub entity: String,
+    pub kind: &'static str,
+    pub code: String,
+    /// Per snapshot, in column order: the entity's configuration (`Entity::configuration`),
+    /// or `None` when the snapshot does not have it.
+    pub cells: Vec<Option<String>>,
+    /// Whether the snapshots agree: all have the entity, configured the same way, or none
+    /// has it.
+    pub consistent: bool,
+}
+
+/// Load the snapshots and fill in the matrix. Fails (`db::is_not_found`) when a snapshot
+/// does not exist.
+pub async fn build(pool: &PgPool, snapshot_ids: &[Uuid], entities: &[Entity]) -> Result<Matrix> {
+    let snapshots = futures_util::future::try_join_all(
+        snapshot_ids.iter().map(|&id| db::fetch_snapshot(pool, id)),
+    )
+    .await?;
+    let mut servers: HashMap<Uuid, String> = HashMap::new();
+    for snapshot in &snapshots {
+        if !servers.contains_key(&snapshot.akeneo_server_id) {
+            let server = db::fetch_akeneo_server(pool, snapshot.akeneo_server_id).await?;
+            servers.insert(server.id, server.name);
+        }
+    }
+
+    let rows = entities
+        .iter()
+        .map(|entity| {
+            let cells: Vec<Option<String>> = snapshots
+                .iter()
+                .map(|snapshot| entity.configuration(&snapshot.data))
+                .collect();
+            let consistent = cells.iter().all(|cell| *cell == cells[0]);
+            MatrixRow {
+                entity: format!("{}:{}", entity.kind.name(), entity.code),
+                kind: entity.kind.name(),
+                code: entity.code.clone(),
+                cells,
+                consistent,
+            }
+        })
+        .collect();
+    let snapshots = snapshots
+        .into_iter()
+        .map(|snapshot| MatrixSnapshot {
+            id: snapshot.id,
+            server_id: snapshot.akeneo_server_id,
+            server: servers[&snapshot.akeneo_server_id].clone(),
+            label: snapshot.label,
+            completed_at: snapshot.completed_at,
+        })
+        .collect();
+    Ok(Matrix { snapshots, rows })
+}
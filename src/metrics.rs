@@ -0,0 +1,263 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+
+use tower::{Layer, Service};
+
+use crate::confluence::PageOperation;
+use crate::render_cache::RenderCacheStats;
+
+/// Total number of HTTP requests sent to Confluence.
+static CONFLUENCE_REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of new TCP/TLS connections opened to Confluence.
+/// Compared against `CONFLUENCE_REQUESTS`, this shows how often pooled
+/// connections are reused instead of paying for a fresh handshake.
+static CONFLUENCE_CONNECTIONS_OPENED: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of Confluence responses received over HTTP/2.
+static CONFLUENCE_HTTP2_RESPONSES: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of pages (and blog posts) created in Confluence.
+static CONFLUENCE_PAGES_CREATED: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of existing pages (and blog posts) updated in Confluence.
+static CONFLUENCE_PAGES_UPDATED: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of page bodies above the warning share of the Confluence body limit.
+static PAGE_BODIES_NEAR_LIMIT: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of page bodies refused for exceeding the Confluence body limit.
+static PAGE_BODIES_TOO_LARGE: AtomicU64 = AtomicU64::new(0);
+
+/// Largest page body, in bytes, checked since startup.
+static LARGEST_PAGE_BODY_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of API requests rejected by the rate limiter.
+static RATE_LIMITED_REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of Confluence responses that were 429 Too Many Requests.
+static CONFLUENCE_RATE_LIMITED: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of Confluence requests that waited for their site's rate budget.
+static CONFLUENCE_THROTTLED: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of Confluence requests failed fast by their site's open circuit breaker.
+static CONFLUENCE_BREAKER_REJECTED: AtomicU64 = AtomicU64::new(0);
+
+/// Record an API request rejected with 429.
+pub fn record_rate_limited() {
+    RATE_LIMITED_REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record a 429 response from Confluence.
+pub fn record_confluence_rate_limited() {
+    CONFLUENCE_RATE_LIMITED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record a Confluence request held back by the publish coordinator.
+pub fn record_confluence_throttled() {
+    CONFLUENCE_THROTTLED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record a Confluence request refused by an open circuit breaker.
+pub fn record_confluence_breaker_rejected() {
+    CONFLUENCE_BREAKER_REJECTED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record a page published to Confluence (dry runs are not counted).
+pub fn record_page_published(operation: PageOperation) {
+    let counter = match operation {
+        PageOperation::Created => &CONFLUENCE_PAGES_CREATED,
+        PageOperation::Updated => &CONFLUENCE_PAGES_UPDATED,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record the size of a page body about to be published.
+pub fn record_page_body(size: u64, near_limit: bool) {
+    LARGEST_PAGE_BODY_BYTES.fetch_max(size, Ordering::Relaxed);
+    if near_limit {
+        PAGE_BODIES_NEAR_LIMIT.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Record a page body refused for exceeding the Confluence body limit.
+pub fn record_page_body_too_large(size: usize) {
+    LARGEST_PAGE_BODY_BYTES.fetch_max(size as u64, Ordering::Relaxed);
+    PAGE_BODIES_TOO_LARGE.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record a request sent to Confluence, and whether its response came back over HTTP/2.
+pub fn record_confluence_response(version: reqwest::Version) {
+    CONFLUENCE_REQUESTS.fetch_add(1, Ordering::Relaxed);
+    if version == reqwest::Version::HTTP_2 {
+        CONFLUENCE_HTTP2_RESPONSES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Current values of the Confluence HTTP counters.
+pub struct ConfluenceCounters {
+    pub requests: u64,
+    pub connections_opened: u64,
+    pub http2_responses: u64,
+}
+
+pub fn confluence_counters() -> ConfluenceCounters {
+    ConfluenceCounters {
+        requests: CONFLUENCE_REQUESTS.load(Ordering::Relaxed),
+        connections_opened: CONFLUENCE_CONNECTIONS_OPENED.load(Ordering::Relaxed),
+        http2_responses: CONFLUENCE_HTTP2_RESPONSES.load(Ordering::Relaxed),
+    }
+}
+
+/// Render all metrics in the Prometheus text exposition format.
+pub fn render_prometheus(render_cache: &RenderCacheStats) -> String {
+    let confluence = confluence_counters();
+
+    let mut out = String::new();
+    push_counter(
+        &mut out,
+        "confluence_requests_total",
+        "HTTP requests sent to Confluence.",
+        confluence.requests,
+    );
+    push_counter(
+        &mut out,
+        "confluence_connections_opened_total",
+        "New connections opened to Confluence.",
+        confluence.connections_opened,
+    );
+    push_counter(
+        &mut out,
+        "confluence_http2_responses_total",
+        "Confluence responses received over HTTP/2.",
+        confluence.http2_responses,
+    );
+    push_counter(
+        &mut out,
+        "confluence_rate_limited_total",
+        "Confluence responses that were 429 Too Many Requests.",
+        CONFLUENCE_RATE_LIMITED.load(Ordering::Relaxed),
+    );
+    push_counter(
+        &mut out,
+        "confluence_throttled_requests_total",
+        "Confluence requests that waited for their site's shared rate budget.",
+        CONFLUENCE_THROTTLED.load(Ordering::Relaxed),
+    );
+    push_counter(
+        &mut out,
+        "confluence_breaker_rejected_total",
+        "Confluence requests failed fast because their site's circuit breaker was open.",
+        CONFLUENCE_BREAKER_REJECTED.load(Ordering::Relaxed),
+    );
+    push_counter(
+        &mut out,
+        "confluence_pages_created_total",
+        "Pages and blog posts created in Confluence.",
+        CONFLUENCE_PAGES_CREATED.load(Ordering::Relaxed),
+    );
+    push_counter(
+        &mut out,
+        "confluence_pages_updated_total",
+        "Existing pages and blog posts updated in Confluence.",
+        CONFLUENCE_PAGES_UPDATED.load(Ordering::Relaxed),
+    );
+    push_counter(
+        &mut out,
+        "page_bodies_near_limit_total",
+        "Page bodies published while above 80% of the Confluence body limit.",
+        PAGE_BODIES_NEAR_LIMIT.load(Ordering::Relaxed),
+    );
+    push_counter(
+        &mut out,
+        "page_bodies_too_large_total",
+        "Page bodies refused for exceeding the Confluence body limit.",
+        PAGE_BODIES_TOO_LARGE.load(Ordering::Relaxed),
+    );
+    push_gauge(
+        &mut out,
+        "page_body_max_bytes",
+        "Largest page body checked since startup, in bytes.",
+        LARGEST_PAGE_BODY_BYTES.load(Ordering::Relaxed),
+    );
+    push_counter(
+        &mut out,
+        "rate_limited_requests_total",
+        "API requests rejected with 429 by the rate limiter.",
+        RATE_LIMITED_REQUESTS.load(Ordering::Relaxed),
+    );
+    push_counter(
+        &mut out,
+        "render_cache_hits_total",
+        "Snapshot publishes served from a pre-rendered page tree.",
+        render_cache.hits,
+    );
+    push_counter(
+        &mut out,
+        "render_cache_misses_total",
+        "Snapshot publishes that had to render on demand.",
+        render_cache.misses,
+    );
+    push_gauge(
+        &mut out,
+        "render_cache_entries",
+        "Page trees currently held in the render cache.",
+        render_cache.entries as u64,
+    );
+    push_gauge(
+        &mut out,
+        "render_cache_capacity",
+        "Maximum page trees the render cache will hold.",
+        render_cache.max_entries as u64,
+    );
+    out
+}
+
+fn push_counter(out: &mut String, name: &str, help: &str, value: u64) {
+    out.push_str(&format!("# HELP {} {}\n", name, help));
+    out.push_str(&format!("# TYPE {} counter\n", name));
+    out.push_str(&format!("{} {}\n", name, value));
+}
+
+fn push_gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    out.push_str(&format!("# HELP {} {}\n", name, help));
+    out.push_str(&format!("# TYPE {} gauge\n", name));
+    out.push_str(&format!("{} {}\n", name, value));
+}
+
+/// Tower layer for the reqwest connector that counts every new connection opened.
+#[derive(Clone)]
+pub struct ConnectionCountLayer;
+
+impl<S> Layer<S> for ConnectionCountLayer {
+    type Service = ConnectionCount<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectionCount { inner }
+    }
+}
+
+/// Connector service wrapper produced by `ConnectionCountLayer`.
+#[derive(Clone)]
+pub struct ConnectionCount<S> {
+    inner: S,
+}
+
+impl<S, R> Service<R> for ConnectionCount<S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        CONFLUENCE_CONNECTIONS_OPENED.fetch_add(1, Ordering::Relaxed);
+        self.inner.call(req)
+    }
+}
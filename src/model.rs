@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Typed view of the `snapshot.data` JSONB column.
+///
+/// Every field defaults when missing, and explicit `null`s are treated the same as
+/// missing, so partial exports still parse. Keys the model does not know about are
+/// kept in `extra` rather than silently dropped.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Snapshot {
+    #[serde(deserialize_with = "nullable")]
+    pub channels: Vec<Channel>,
+    #[serde(deserialize_with = "nullable")]
+    pub families: Vec<Family>,
+    #[serde(deserialize_with = "nullable")]
+    pub attributes: Vec<Attribute>,
+    #[serde(deserialize_with = "nullable")]
+    pub categories: Vec<Category>,
+    /// Attribute code -> options of that attribute. Unlike the other keys this is a
+    /// dictionary, not an array.
+    #[serde(deserialize_with = "nullable")]
+    pub attribute_options: BTreeMap<String, Vec<AttributeOption>>,
+    /// Measurement families metric attributes refer to; empty in snapshots taken
+    /// without them.
+    #[serde(deserialize_with = "nullable")]
+    pub measurement_families: Vec<MeasurementFamily>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Channel {
+    pub code: String,
+    pub labels: Labels,
+    #[serde(deserialize_with = "nullable")]
+    pub locales: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub currencies: Vec<String>,
+    pub category_tree: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Family {
+    pub code: String,
+    pub labels: Labels,
+    pub parent: Option<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub attributes: Vec<String>,
+    pub attribute_as_label: Option<String>,
+    pub attribute_as_image: Option<String>,
+    /// Channel code -> attribute codes required on that channel.
+    #[serde(deserialize_with = "nullable")]
+    pub attribute_requirements: BTreeMap<String, Vec<String>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Product usage statistics some snapshot exports include per family, read from the
+/// family's extra keys: `products_count`, `variants_count` and `completeness` (channel
+/// code -> percentage, or channel code -> locale -> percentage). Values of an unexpected
+/// shape are ignored.
+#[derive(Debug, Default)]
+pub struct FamilyUsage {
+    pub products: Option<u64>,
+    pub variants: Option<u64>,
+    pub completeness: BTreeMap<String, ChannelCompleteness>,
+}
+
+/// Completeness of a family's products on one channel, in percent.
+#[derive(Debug)]
+pub enum ChannelCompleteness {
+    Overall(f64),
+    PerLocale(BTreeMap<String, f64>),
+}
+
+impl Family {
+    /// The family's usage statistics, or `None` when the snapshot has none for it.
+    pub fn usage(&self) -> Option<FamilyUsage> {
+        let count = |key: &str| self.extra.get(key).and_then(Value::as_u64);
+        let completeness = self
+            .extra
+            .get("completeness")
+            .and_then(Value::as_object)
+            .map(|channels| {
+                channels
+                    .iter()
+                    .filter_map(|(channel, value)| {
+                        let completeness = match value {
+                            Value::Object(locales) => ChannelCompleteness::PerLocale(
+                                locales
+                                    .iter()
+                                    .filter_map(|(locale, v)| Some((locale.clone(), v.as_f64()?)))
+                                    .collect(),
+                            ),
+                            _ => ChannelCompleteness::Overall(value.as_f64()?),
+                        };
+                        Some((channel.clone(), completeness))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let usage = FamilyUsage {
+            products: count("products_count"),
+            variants: count("variants_count"),
+            completeness,
+        };
+        if usage.products.is_none() && usage.variants.is_none() && usage.completeness.is_empty()
+        {
+            return None;
+        }
+        Some(usage)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Attribute {
+    pub code: String,
+    #[serde(rename = "type")]
+    pub attr_type: Option<String>,
+    pub group: Option<String>,
+    pub labels: Labels,
+    pub group_labels: Labels,
+    #[serde(deserialize_with = "nullable")]
+    pub unique: bool,
+    #[serde(deserialize_with = "nullable")]
+    pub scopable: bool,
+    #[serde(deserialize_with = "nullable")]
+    pub localizable: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Category {
+    pub code: String,
+    pub labels: Labels,
+    pub parent: Option<String>,
+    pub updated: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AttributeOption {
+    pub code: String,
+    pub labels: Labels,
+    pub attribute: Option<String>,
+    pub sort_order: Option<i64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MeasurementFamily {
+    pub code: String,
+    pub labels: Labels,
+    pub standard_unit_code: Option<String>,
+    /// Unit code -> unit. Like `attribute_options` this is a dictionary.
+    #[serde(deserialize_with = "nullable")]
+    pub units: BTreeMap<String, MeasurementUnit>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MeasurementUnit {
+    pub code: String,
+    pub labels: Labels,
+    pub symbol: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Locale -> label map. Null labels are dropped, and an empty JSON array (how PHP
+/// serializes an empty map) is accepted as "no labels".
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Labels(pub BTreeMap<String, String>);
+
+impl Labels {
+    /// The first available label, in locale order.
+    pub fn first(&self) -> Option<&str> {
+        self.0.values().next().map(|s| s.as_str())
+    }
+
+    /// The label for a specific locale.
+    pub fn get(&self, locale: &str) -> Option<&str> {
+        self.0.get(locale).map(|s| s.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'de> Deserialize<'de> for Labels {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawLabels {
+            Map(BTreeMap<String, Option<String>>),
+            List(Vec<Value>),
+        }
+
+        match Option::<RawLabels>::deserialize(deserializer)? {
+            Some(RawLabels::Map(map)) => Ok(Labels(
+                map.into_iter()
+                    .filter_map(|(locale, label)| label.map(|l| (locale, l)))
+                    .collect(),
+            )),
+            Some(RawLabels::List(list)) if list.is_empty() => Ok(Labels::default()),
+            Some(RawLabels::List(_)) => Err(serde::de::Error::custom(
+                "labels must be an object mapping locales to strings",
+            )),
+            None => Ok(Labels::default()),
+        }
+    }
+}
+
+/// Deserialize a field, treating an explicit `null` as the type's default.
+pub(crate) fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+impl Snapshot {
+    /// Parse snapshot data from a JSON value. Errors name the offending path
+    /// (e.g. `attributes[12].scopable`) so schema drift is easy to locate.
+    pub fn from_value(value: Value) -> Result<Self> {
+        serde_path_to_error::deserialize(value).context("Snapshot data does not match the Akeneo model")
+    }
+
+    /// Total number of attribute options across all attributes.
+    pub fn attribute_options_count(&self) -> usize {
+        self.attribute_options.values().map(|o| o.len()).sum()
+    }
+}
+
+/// Lets `#[sqlx(try_from = "serde_json::Value")]` decode a JSONB column into a snapshot.
+impl TryFrom<Value> for Snapshot {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> Result<Self> {
+        Self::from_value(value)
+    }
+}
@@ -0,0 +1,348 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::confluence::PublishedPage;
+use crate::db::{self, DbConfluenceConfig};
+use crate::AppState;
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Characters of a failed upload's response body kept in the error.
+const MAX_ERROR_BODY: usize = 200;
+
+/// An S3-compatible object store (AWS S3, MinIO, Ceph, ...) that live publishes are
+/// archived to, so exactly what was published can be produced later.
+#[derive(Debug)]
+pub struct ObjectStore {
+    endpoint: String,
+    region: String,
+    access_key_id: String,
+    /// May be an `env:` / `vault:` reference, resolved on every archive.
+    secret_access_key: String,
+}
+
+impl ObjectStore {
+    /// Load the object store from the environment; `None` when archival is not set up:
+    /// - `ARCHIVE_S3_ENDPOINT` — base URL of the store, e.g.
+    ///   `https://s3.eu-west-1.amazonaws.com`; buckets are addressed path-style
+    ///   (`{endpoint}/{bucket}/{key}`)
+    /// - `ARCHIVE_S3_REGION` — signing region (default `us-east-1`)
+    /// - `ARCHIVE_S3_ACCESS_KEY_ID`, `ARCHIVE_S3_SECRET_ACCESS_KEY` — credentials; the
+    ///   secret may be an `env:` / `vault:` reference
+    ///
+    /// Which targets are archived, and where, is configured per target
+    /// (`confluence_config.archive_bucket` / `archive_prefix`).
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(endpoint) = env("ARCHIVE_S3_ENDPOINT") else {
+            return Ok(None);
+        };
+        if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+            bail!("Invalid ARCHIVE_S3_ENDPOINT: not an http(s) URL");
+        }
+        let (Some(access_key_id), Some(secret_access_key)) = (
+            env("ARCHIVE_S3_ACCESS_KEY_ID"),
+            env("ARCHIVE_S3_SECRET_ACCESS_KEY"),
+        ) else {
+            bail!(
+                "Publish archival needs ARCHIVE_S3_ACCESS_KEY_ID and ARCHIVE_S3_SECRET_ACCESS_KEY"
+            );
+        };
+        let store = Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region: env("ARCHIVE_S3_REGION").unwrap_or_else(|| DEFAULT_REGION.to_string()),
+            access_key_id,
+            secret_access_key,
+        };
+        info!(
+            "Publish archival: endpoint={}, region={}",
+            store.endpoint, store.region
+        );
+        Ok(Some(store))
+    }
+
+    /// Upload an object with a SigV4-signed `PUT`, replacing any object with its key.
+    async fn put_object(
+        &self,
+        http: &reqwest::Client,
+        secret_access_key: &str,
+        bucket: &str,
+        key: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<()> {
+        let path = format!("/{}/{}", uri_encode(bucket, true), uri_encode(key, false));
+        let url = reqwest::Url::parse(&format!("{}{}", self.endpoint, path))
+            .context("Invalid object URL")?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => bail!("ARCHIVE_S3_ENDPOINT has no host"),
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex(&Sha256::digest(&body));
+        let canonical_request = format!(
+            "PUT\n{}\n\ncontent-type:{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            url.path(),
+            content_type,
+            host,
+            payload_hash,
+            amz_date,
+            SIGNED_HEADERS,
+            payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac(
+                format!("AWS4{}", secret_access_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac(&key, part.as_bytes()),
+        );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id,
+            scope,
+            SIGNED_HEADERS,
+            hex(&hmac(&signing_key, string_to_sign.as_bytes()))
+        );
+
+        let response = http
+            .put(url)
+            .timeout(UPLOAD_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("Upload of '{}' failed", key))?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            let text: String = text.trim().chars().take(MAX_ERROR_BODY).collect();
+            bail!("Upload of '{}' failed (HTTP {}): {}", key, status, text);
+        }
+        Ok(())
+    }
+}
+
+/// Headers covered by the upload signature, in canonical order.
+const SIGNED_HEADERS: &str = "content-type;host;x-amz-content-sha256;x-amz-date";
+
+/// Where a target's publishes are archived.
+pub struct ArchiveTarget {
+    bucket: String,
+    /// Without leading or trailing slashes; may be empty.
+    prefix: String,
+}
+
+impl ArchiveTarget {
+    /// The archive of a target, `None` when it has no `archive_bucket`. Fails when it has
+    /// one but no object store is configured, so a target that must be archived is never
+    /// published without it.
+    pub fn from_config(state: &AppState, config: &DbConfluenceConfig) -> Result<Option<Self>> {
+        let Some(bucket) = config
+            .archive_bucket
+            .as_deref()
+            .filter(|b| !b.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        if state.object_store.is_none() {
+            bail!(
+                "Target of {} is archived to bucket '{}' but ARCHIVE_S3_ENDPOINT is not set",
+                config.server_name,
+                bucket
+            );
+        }
+        Ok(Some(Self {
+            bucket: bucket.trim().to_string(),
+            prefix: config.archive_prefix.trim_matches('/').to_string(),
+        }))
+    }
+}
+
+/// The publish being archived.
+pub struct ArchivedPublish<'a> {
+    /// `snapshot`, `diff` or `cumulative_diff`.
+    pub kind: &'static str,
+    pub server_id: Uuid,
+    pub server: &'a str,
+    /// The published snapshot, or a diff's after-snapshot.
+    pub snapshot_id: Uuid,
+    pub diff_id: Option<Uuid>,
+    pub published_at: DateTime<Utc>,
+    /// The root or diff page, whose publication the keys are recorded on.
+    pub page_id: &'a str,
+    pub page_url: &'a str,
+    /// Problems the publish worked around, as in the publish response.
+    pub warnings: &'a [String],
+}
+
+/// `report.json`: what was published, with the key of each archived page body.
+#[derive(Serialize)]
+struct Report<'a> {
+    kind: &'static str,
+    server_id: Uuid,
+    server: &'a str,
+    snapshot_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff_id: Option<Uuid>,
+    published_at: DateTime<Utc>,
+    tool_version: &'static str,
+    page_url: &'a str,
+    pages: Vec<ReportPage<'a>>,
+    warnings: &'a [String],
+}
+
+#[derive(Serialize)]
+struct ReportPage<'a> {
+    page_id: &'a str,
+    title: &'a str,
+    web_url: &'a str,
+    key: String,
+}
+
+/// Upload the published page bodies and a `report.json` describing the publish under
+/// `{prefix}/{kind}/{snapshot or diff id}/{timestamp}/`, and record the keys (the
+/// report's last) on the publication.
+///
+/// An upload failure fails the archive (the pages are live by then); failing to record
+/// the keys only logs, since the report in the bucket lists them too.
+pub async fn archive(
+    state: &AppState,
+    target: &ArchiveTarget,
+    publish: &ArchivedPublish<'_>,
+    pages: &[PublishedPage],
+) -> Result<()> {
+    let store = state
+        .object_store
+        .as_ref()
+        .context("Publish archival is not configured")?;
+    let secret_access_key = state
+        .secrets
+        .resolve(&store.secret_access_key)
+        .await
+        .context("Failed to resolve ARCHIVE_S3_SECRET_ACCESS_KEY")?;
+
+    let folder = format!(
+        "{}/{}/{}",
+        publish.kind,
+        publish.diff_id.unwrap_or(publish.snapshot_id),
+        publish.published_at.format("%Y%m%dT%H%M%SZ")
+    );
+    let folder = match target.prefix.as_str() {
+        "" => folder,
+        prefix => format!("{}/{}", prefix, folder),
+    };
+
+    let mut keys = Vec::with_capacity(pages.len() + 1);
+    let mut report_pages = Vec::with_capacity(pages.len());
+    for page in pages {
+        let key = format!("{}/pages/{}.xhtml", folder, page.page_id);
+        store
+            .put_object(
+                &state.http,
+                &secret_access_key,
+                &target.bucket,
+                &key,
+                "application/xhtml+xml",
+                page.body.clone().into_bytes(),
+            )
+            .await?;
+        report_pages.push(ReportPage {
+            page_id: &page.page_id,
+            title: &page.title,
+            web_url: &page.web_url,
+            key: key.clone(),
+        });
+        keys.push(key);
+    }
+
+    let report = Report {
+        kind: publish.kind,
+        server_id: publish.server_id,
+        server: publish.server,
+        snapshot_id: publish.snapshot_id,
+        diff_id: publish.diff_id,
+        published_at: publish.published_at,
+        tool_version: env!("CARGO_PKG_VERSION"),
+        page_url: publish.page_url,
+        pages: report_pages,
+        warnings: publish.warnings,
+    };
+    let key = format!("{}/report.json", folder);
+    let body = serde_json::to_vec_pretty(&report).context("Failed to serialize publish report")?;
+    store
+        .put_object(
+            &state.http,
+            &secret_access_key,
+            &target.bucket,
+            &key,
+            "application/json",
+            body,
+        )
+        .await?;
+    keys.push(key);
+    info!(
+        "Archived {} page(s) of the publish to s3://{}/{}",
+        pages.len(),
+        target.bucket,
+        folder
+    );
+
+    if let Err(e) =
+        db::record_archive_keys(&state.pool, publish.server_id, publish.page_id, &keys).await
+    {
+        warn!("{:#}", e);
+    }
+    Ok(())
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Percent-encode a URI path the way SigV4 canonical URIs expect; `/` is kept unless
+/// `encode_slash` is set.
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
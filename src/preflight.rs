@@ -0,0 +1,430 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::confluence::{self, ConfluenceClient, ConfluenceConfig, RefreshTokenStore};
+use crate::db;
+use crate::extract::Path;
+use crate::{AppState, ErrorResponse};
+
+/// Result of `GET /api/server/{id}/confluence/check`.
+#[derive(Serialize)]
+struct CheckReport {
+    /// `ok` when no check failed, else `failed`.
+    status: &'static str,
+    base_url: String,
+    space_key: String,
+    parent_page: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_page_id: Option<String>,
+    checks: Vec<Check>,
+}
+
+/// One item of the checklist.
+#[derive(Serialize)]
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+}
+
+#[derive(Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Pass,
+    Fail,
+    Warning,
+    /// Not run because an earlier check failed, or not applicable.
+    Skipped,
+}
+
+/// `GET /wiki/rest/api/user/current`
+#[derive(Deserialize)]
+struct CurrentUser {
+    #[serde(rename = "displayName", default)]
+    display_name: String,
+    /// `anonymous` when the credentials were not accepted.
+    #[serde(rename = "type", default)]
+    user_type: String,
+}
+
+/// `GET /wiki/rest/api/space/{key}?expand=operations`
+#[derive(Deserialize)]
+struct Space {
+    name: String,
+    /// What the current user may do in the space.
+    operations: Option<Vec<SpaceOperation>>,
+}
+
+/// `GET /wiki/rest/api/content/{id}?expand=space`
+#[derive(Deserialize)]
+struct Page {
+    title: String,
+    space: Option<SpaceRef>,
+}
+
+#[derive(Deserialize)]
+struct SpaceRef {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct SpaceOperation {
+    operation: String,
+    #[serde(rename = "targetType")]
+    target_type: String,
+}
+
+/// The checks in the order they run; each one needs the ones before it to pass.
+const CHECKS: &[&str] = &[
+    "base_url",
+    "authentication",
+    "space",
+    "parent_page",
+    "write_permission",
+];
+
+impl CheckReport {
+    fn push(&mut self, name: &'static str, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name,
+            status,
+            detail: detail.into(),
+        });
+    }
+
+    /// Mark every check not run yet as skipped and settle the overall status.
+    /// Fail authentication of an OAuth target that got no access token; the site could
+    /// not be reached either.
+    fn no_access_token(mut self, detail: &str) -> Self {
+        self.push("base_url", CheckStatus::Skipped, "Not checked: no OAuth access token");
+        self.push("authentication", CheckStatus::Fail, detail);
+        self.finish()
+    }
+
+    fn finish(mut self) -> Self {
+        for &name in &CHECKS[self.checks.len()..] {
+            self.push(name, CheckStatus::Skipped, "Not checked: an earlier check failed");
+        }
+        if self.checks.iter().any(|c| c.status == CheckStatus::Fail) {
+            self.status = "failed";
+        }
+        self
+    }
+}
+
+/// GET /api/server/:id/confluence/check
+///
+/// Verifies an Akeneo server's Confluence target before anything is published: that the
+/// base URL is a Confluence site, the credentials are accepted, the space and parent page
+/// (by `parent_page_id` when configured) exist, and the user may create pages in the
+/// space. Always 200 with a checklist; `status` is `failed` when any check failed. Drops
+/// the server's cached Confluence clients.
+pub async fn handle_confluence_check(
+    State(state): State<AppState>,
+    Path(server_id): Path<Uuid>,
+) -> Response {
+    check_response(&state, server_id).await
+}
+
+/// Run the checklist against an Akeneo server's Confluence target, as
+/// `GET /api/server/{id}/confluence/check` does; 404 when the server has no target.
+pub async fn check_response(state: &AppState, server_id: Uuid) -> Response {
+    let db_config = match db::fetch_confluence_config(&state.pool, server_id).await {
+        Ok(config) => config,
+        Err(e) if db::is_not_found(&e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(format!("{}", e))),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!("Failed to fetch Confluence config for {}: {:#}", server_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
+    };
+
+    // Operators run the check after fixing a target; publish with fresh lookups after it
+    state.clients.invalidate(server_id);
+    state.confluence_configs.invalidate(Some(server_id));
+
+    let config = ConfluenceConfig::from_db(db_config);
+    let store = RefreshTokenStore::for_target(
+        &config,
+        state.pool.clone(),
+        state.confluence_configs.clone(),
+        server_id,
+    );
+    let (config, token_error) = match config.clone().resolve_secrets(&state.secrets).await {
+        Ok(resolved) => (resolved, None),
+        Err(e) => (config, Some(format!("{:#}", e))),
+    };
+    let (http, tls_error) = match state.tls_clients.get(&state.http, &config.tls) {
+        Ok(http) => (http, None),
+        Err(e) => (state.http.clone(), Some(format!("{:#}", e))),
+    };
+    let client = ConfluenceClient::new(config, http, state.coordinator.clone())
+        .with_refresh_token_store(store)
+        .with_mock(state.confluence_mock.clone());
+
+    let report = run_checks(&client, tls_error, token_error).await;
+    info!(
+        "Confluence check for server {}: {}",
+        server_id, report.status
+    );
+    Json(report).into_response()
+}
+
+async fn run_checks(
+    client: &ConfluenceClient,
+    tls_error: Option<String>,
+    token_error: Option<String>,
+) -> CheckReport {
+    let config = client.config();
+    let mut report = CheckReport {
+        status: "ok",
+        base_url: config.base_url.clone(),
+        space_key: config.space_key.clone(),
+        parent_page: config.parent_page.clone(),
+        parent_page_id: config.parent_page_id.clone(),
+        checks: Vec::new(),
+    };
+
+    // Base URL and authentication
+    if let Some(tls_error) = tls_error {
+        report.push("base_url", CheckStatus::Fail, format!("Invalid TLS settings: {}", tls_error));
+        return report.finish();
+    }
+    // OAuth requests cannot reach the site without an access token
+    if config.oauth.is_some()
+        && let Some(token_error) = &token_error
+    {
+        return report.no_access_token(token_error);
+    }
+    let user = match client.api_get("user/current", &[]).await {
+        Err(e) if confluence::is_token_refresh_failed(&e) => {
+            return report.no_access_token(&format!("{:#}", e));
+        }
+        Err(e) => {
+            report.push("base_url", CheckStatus::Fail, format!("Request failed: {:#}", e));
+            return report.finish();
+        }
+        Ok(resp) if resp.status() == StatusCode::NOT_FOUND => {
+            report.push(
+                "base_url",
+                CheckStatus::Fail,
+                format!(
+                    "{} has no Confluence REST API; use the site root, e.g. \
+                     https://your-domain.atlassian.net",
+                    config.base_url
+                ),
+            );
+            return report.finish();
+        }
+        Ok(resp) => {
+            report.push("base_url", CheckStatus::Pass, "Confluence REST API reachable");
+            resp
+        }
+    };
+
+    if let Some(token_error) = token_error {
+        report.push("authentication", CheckStatus::Fail, token_error);
+        return report.finish();
+    }
+    let status = user.status();
+    if !status.is_success() {
+        report.push(
+            "authentication",
+            CheckStatus::Fail,
+            format!(
+                "Confluence rejected the credentials of {} (HTTP {})",
+                config.account(),
+                status
+            ),
+        );
+        return report.finish();
+    }
+    let user_name = match user.json::<CurrentUser>().await {
+        Ok(user) if user.user_type != "anonymous" => user.display_name,
+        Ok(_) => {
+            report.push(
+                "authentication",
+                CheckStatus::Fail,
+                format!(
+                    "The credentials of {} were not accepted (anonymous user)",
+                    config.account()
+                ),
+            );
+            return report.finish();
+        }
+        Err(e) => {
+            report.push("authentication", CheckStatus::Fail, format!("Unexpected response: {}", e));
+            return report.finish();
+        }
+    };
+    report.push("authentication", CheckStatus::Pass, format!("Authenticated as {}", user_name));
+
+    // Space
+    let space = match client
+        .api_get(&format!("space/{}", config.space_key), &[("expand", "operations")])
+        .await
+    {
+        Ok(resp) if resp.status().is_success() => resp.json::<Space>().await.ok(),
+        Ok(resp) if resp.status() == StatusCode::NOT_FOUND => None,
+        Ok(resp) => {
+            report.push(
+                "space",
+                CheckStatus::Fail,
+                format!("Space lookup failed (HTTP {})", resp.status()),
+            );
+            return report.finish();
+        }
+        Err(e) => {
+            report.push("space", CheckStatus::Fail, format!("Space lookup failed: {:#}", e));
+            return report.finish();
+        }
+    };
+    let Some(space) = space else {
+        report.push(
+            "space",
+            CheckStatus::Fail,
+            format!(
+                "Space '{}' does not exist or {} cannot view it",
+                config.space_key, user_name
+            ),
+        );
+        return report.finish();
+    };
+    report.push(
+        "space",
+        CheckStatus::Pass,
+        format!("Space '{}' ({}) found", config.space_key, space.name),
+    );
+
+    // Parent page, by its configured ID or else its title
+    if let Some(id) = &config.parent_page_id {
+        match client.api_get(&format!("content/{}", id), &[("expand", "space")]).await {
+            Ok(resp) if resp.status().is_success() => match resp.json::<Page>().await {
+                Ok(page) if page.space.as_ref().is_some_and(|s| s.key != config.space_key) => {
+                    report.push(
+                        "parent_page",
+                        CheckStatus::Fail,
+                        format!(
+                            "Page {} ('{}') is in space '{}', not '{}'",
+                            id,
+                            page.title,
+                            page.space.map(|s| s.key).unwrap_or_default(),
+                            config.space_key
+                        ),
+                    );
+                    return report.finish();
+                }
+                Ok(page) => report.push(
+                    "parent_page",
+                    CheckStatus::Pass,
+                    format!("Page {} ('{}') found", id, page.title),
+                ),
+                Err(e) => {
+                    let detail = format!("Unexpected response: {}", e);
+                    report.push("parent_page", CheckStatus::Fail, detail);
+                    return report.finish();
+                }
+            },
+            Ok(resp) if resp.status() == StatusCode::NOT_FOUND => {
+                report.push(
+                    "parent_page",
+                    CheckStatus::Fail,
+                    format!("No page with ID {} (or {} cannot view it)", id, user_name),
+                );
+                return report.finish();
+            }
+            Ok(resp) => {
+                report.push(
+                    "parent_page",
+                    CheckStatus::Fail,
+                    format!("Parent page lookup failed (HTTP {})", resp.status()),
+                );
+                return report.finish();
+            }
+            Err(e) => {
+                report.push(
+                    "parent_page",
+                    CheckStatus::Fail,
+                    format!("Parent page lookup failed: {:#}", e),
+                );
+                return report.finish();
+            }
+        }
+    } else if config.parent_page.is_empty() {
+        report.push(
+            "parent_page",
+            CheckStatus::Skipped,
+            "No parent page configured; pages are created at the top level of the space",
+        );
+    } else {
+        match client.find_page_id(&config.parent_page).await {
+            Ok(Some(id)) => report.push(
+                "parent_page",
+                CheckStatus::Pass,
+                format!("'{}' found (id={})", config.parent_page, id),
+            ),
+            Ok(None) => {
+                report.push(
+                    "parent_page",
+                    CheckStatus::Fail,
+                    format!(
+                        "No page titled '{}' in space '{}'",
+                        config.parent_page, config.space_key
+                    ),
+                );
+                return report.finish();
+            }
+            Err(e) => {
+                report.push("parent_page", CheckStatus::Fail, format!("{:#}", e));
+                return report.finish();
+            }
+        }
+    }
+
+    // Write permission
+    match space.operations {
+        Some(operations)
+            if operations
+                .iter()
+                .any(|op| op.operation == "create" && op.target_type == "page") =>
+        {
+            report.push(
+                "write_permission",
+                CheckStatus::Pass,
+                format!("{} can create pages in '{}'", user_name, config.space_key),
+            );
+        }
+        Some(_) => report.push(
+            "write_permission",
+            CheckStatus::Fail,
+            format!(
+                "{} cannot create pages in '{}'; grant the user \"Add page\" in the space \
+                 permissions",
+                user_name, config.space_key
+            ),
+        ),
+        None => report.push(
+            "write_permission",
+            CheckStatus::Warning,
+            "Confluence did not report the user's space permissions",
+        ),
+    }
+
+    report.finish()
+}
@@ -0,0 +1,2221 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::Capability;
+use crate::confluence::{
+    self, ChildPageOrder, ConfluenceClient, ContentType, FamilyGrouping, PageOperation,
+    PageTree, PlannedPage, PublishOutcome, PublishedPage, RefreshTokenStore,
+};
+use crate::cumulative::CumulativeDiff;
+use crate::db::{
+    self, DbConfluenceConfig, DiffRow, NewPublication, PublicationKind, SnapshotMeta, SnapshotRow,
+};
+use crate::diff;
+use crate::export::{self, AttachmentOptions};
+use crate::git_mirror::{self, MirrorTarget};
+use crate::hooks::{self, PublishEvent, Stage};
+use crate::jira::{self, JiraClient, JiraConfig};
+use crate::jobs::JobHandle;
+use crate::matrix::Matrix;
+use crate::object_store::{self, ArchiveTarget, ArchivedPublish};
+use crate::render_options::{ChangedGrouping, DisplayFormat, ItemProperties, RenderOptions};
+use crate::renderer::{self, SnapshotChildPage, SnapshotInfo, SnapshotPageTree};
+use crate::titles::{
+    apply_template, option_page_title, TitleContext, TitleTemplates, ACTIVITY_TITLE_SUFFIX,
+    BLOG_POST_TITLE, CATEGORY_TREE_TITLE_SUFFIX, COVERAGE_TITLE_SUFFIX, CUMULATIVE_TITLE_SUFFIX,
+    FAMILY_GROUP_TITLE_SUFFIX, HEALTH_TITLE_SUFFIX, LABELS_TITLE_SUFFIX, MATRIX_TITLE_SUFFIX,
+};
+use crate::AppState;
+
+/// Query parameters accepted by the publishing endpoints.
+///
+/// `parent_page_id` (or `parent_page`, a page title) overrides the configured parent page
+/// for this request only, e.g. to publish into a scratch area for review.
+///
+/// `attachments` (e.g. `csv`) attaches spreadsheet exports of the model tables to the
+/// snapshot root page.
+///
+/// `draft` stages the publish for review (see `promote_draft`) or, with `draft=false`,
+/// publishes live even when the target has `draft_publishing` enabled.
+///
+/// `space_key` publishes into another space, which must be listed in the target's
+/// `allowed_space_keys`. Without a parent override the pages go to the top level of that
+/// space.
+///
+/// `columns` (e.g. `code,label,type,unique`) and `sort` (e.g. `group` or `code:desc`)
+/// override the configured columns and row order of the attributes table, or of a diff's
+/// added / removed tables.
+///
+/// `debug=true` follows added / removed items and family pages with their raw JSON.
+///
+/// `include` / `exclude` (e.g. `attributes,families`) limit a diff page to some categories
+/// (see `diff::CategoryFilter`); snapshots ignore them.
+///
+/// `group_changes=field` (or `item`) overrides `DIFF_CHANGED_GROUPING` for a diff page's
+/// Changed tables (see `render_options::ChangedGrouping`); snapshots ignore it.
+///
+/// `content_type=blogpost` also announces a live diff in a blog post (see
+/// `publish_announcement`); snapshots ignore it.
+///
+/// `dry_run=true` makes the snapshot and diff endpoints return what the publish would do
+/// (`plan_snapshot` / `plan_diff`) instead of publishing; the publish functions ignore it.
+///
+/// `if_changed=true` makes the snapshot endpoints skip the publish when the rendered pages
+/// match the last publication's (`unchanged_publication`); the publish functions ignore it.
+#[derive(Deserialize, Serialize, Default)]
+pub struct PublishParams {
+    pub parent_page_id: Option<String>,
+    pub parent_page: Option<String>,
+    pub attachments: Option<String>,
+    pub draft: Option<bool>,
+    pub space_key: Option<String>,
+    pub columns: Option<String>,
+    pub sort: Option<String>,
+    pub content_type: Option<ContentType>,
+    pub debug: Option<bool>,
+    pub include: Option<String>,
+    pub exclude: Option<String>,
+    pub dry_run: Option<bool>,
+    pub if_changed: Option<bool>,
+    pub group_changes: Option<ChangedGrouping>,
+}
+
+impl PublishParams {
+    /// Render options with this request's table overrides and debug output, or `None` to
+    /// use the defaults.
+    fn render_options(&self, defaults: &RenderOptions) -> Option<RenderOptions> {
+        let options = defaults.with_table_overrides(self.columns.as_deref(), self.sort.as_deref());
+        if self.debug != Some(true) {
+            return options;
+        }
+        let mut options = options.unwrap_or_else(|| defaults.clone());
+        options.tables.raw_json = true;
+        Some(options)
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run == Some(true)
+    }
+
+    fn category_filter(&self) -> Option<diff::CategoryFilter> {
+        diff::CategoryFilter::parse(self.include.as_deref(), self.exclude.as_deref())
+    }
+
+    /// Apply the space and parent overrides (if any) to a Confluence config. Fails with
+    /// `SpaceNotAllowed` when the requested space is not in `allowed_space_keys`.
+    pub fn apply(
+        self,
+        mut config: confluence::ConfluenceConfig,
+        allowed_space_keys: &[String],
+    ) -> Result<confluence::ConfluenceConfig> {
+        if let Some(requested) = self.space_key.filter(|k| !k.is_empty()) {
+            // Space keys are case-insensitive; publish with the configured spelling
+            let space_key = std::iter::once(&config.space_key)
+                .chain(allowed_space_keys)
+                .find(|k| k.eq_ignore_ascii_case(&requested))
+                .cloned()
+                .ok_or(SpaceNotAllowed(requested))?;
+            info!("Overriding space for this publish (space_key={})", space_key);
+            config = config.with_space_override(space_key);
+        }
+        if self.parent_page_id.is_some() || self.parent_page.is_some() {
+            info!(
+                "Overriding parent page for this publish (id={:?}, title={:?})",
+                self.parent_page_id, self.parent_page
+            );
+        }
+        Ok(config.with_parent_override(self.parent_page_id, self.parent_page))
+    }
+}
+
+/// A `space_key` override naming a space the target does not allow.
+#[derive(Debug)]
+pub struct SpaceNotAllowed(pub String);
+
+impl std::fmt::Display for SpaceNotAllowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Publishing into space '{}' is not allowed for this Confluence target",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for SpaceNotAllowed {}
+
+/// Whether an error is (or wraps) a rejected space override, which handlers report as
+/// 403 rather than 500.
+pub fn is_space_not_allowed(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<SpaceNotAllowed>().is_some()
+}
+
+/// How snapshot publishes treat earlier publishes, per Confluence target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishMode {
+    /// Update the same root page ("Current model") on every publish.
+    Overwrite,
+    /// Create a new dated page per snapshot under an index page that links all of them.
+    Versioned,
+}
+
+impl PublishMode {
+    /// Parse the `confluence_config.publish_mode` column. Unknown values fall back to
+    /// `Overwrite` so a typo never creates unexpected pages.
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "versioned" => PublishMode::Versioned,
+            "overwrite" | "" => PublishMode::Overwrite,
+            other => {
+                warn!("Unknown publish mode '{}', using 'overwrite'", other);
+                PublishMode::Overwrite
+            }
+        }
+    }
+}
+
+/// Key of the content property written on every published page.
+const PROVENANCE_PROPERTY: &str = "akeneo-snapshot-publisher";
+
+/// Provenance metadata stored as the `akeneo-snapshot-publisher` content property on each
+/// published page, so generated pages can be identified (and safely pruned) by other tools.
+#[derive(Serialize)]
+struct Provenance {
+    server_id: Uuid,
+    /// The published snapshot, or a diff's after-snapshot.
+    snapshot_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff_id: Option<Uuid>,
+    tool_version: &'static str,
+    renderer_version: i32,
+    published_at: DateTime<Utc>,
+}
+
+impl Provenance {
+    fn new(server_id: Uuid, snapshot_id: Uuid, diff_id: Option<Uuid>) -> Self {
+        Self {
+            server_id,
+            snapshot_id,
+            diff_id,
+            tool_version: env!("CARGO_PKG_VERSION"),
+            renderer_version: renderer::RENDERER_VERSION,
+            published_at: Utc::now(),
+        }
+    }
+}
+
+/// How a snapshot's target titles its pages, shared by `publish_snapshot`, `plan_snapshot`
+/// and `publish_family`.
+struct SnapshotTitles {
+    draft: bool,
+    title_templates: TitleTemplates,
+    mode: PublishMode,
+    server_name: String,
+    date: String,
+    time: String,
+    /// The target's display format, for the snapshot info panel and the index page.
+    format: DisplayFormat,
+}
+
+impl SnapshotTitles {
+    /// Decide whether the publish is a draft (see `is_draft`, which may move the target's
+    /// parent) and load the target's title templates.
+    fn new(
+        snapshot: &SnapshotRow,
+        params: &PublishParams,
+        config: &mut DbConfluenceConfig,
+        format: DisplayFormat,
+    ) -> Self {
+        let draft = is_draft(params, config);
+        let mut title_templates = TitleTemplates::from_config(config);
+        if draft {
+            title_templates = title_templates.into_draft();
+        }
+        Self {
+            draft,
+            title_templates,
+            mode: PublishMode::parse(&config.publish_mode),
+            server_name: config.server_name.clone(),
+            date: snapshot.completed_at.format("%Y-%m-%d").to_string(),
+            time: snapshot.completed_at.format("%H:%M").to_string(),
+            format,
+        }
+    }
+
+    /// The versioned-mode index page, listing `history`.
+    fn history_index(&self, state: &AppState, history: &[db::PublicationRow]) -> String {
+        let options = state.render_options.with_display_format(self.format.clone());
+        renderer::render_history_index(history, &options)
+    }
+
+    fn ctx<'a>(&'a self, snapshot: &'a SnapshotRow) -> TitleContext<'a> {
+        TitleContext {
+            server: &self.server_name,
+            label: snapshot.label.as_deref().unwrap_or("Unnamed snapshot"),
+            date: &self.date,
+            time: &self.time,
+            ..Default::default()
+        }
+    }
+
+    fn root_title(&self, ctx: &TitleContext<'_>) -> String {
+        match self.mode {
+            PublishMode::Overwrite => self.title_templates.snapshot_title(ctx),
+            PublishMode::Versioned => self.title_templates.version_title(ctx),
+        }
+    }
+}
+
+/// Fetch the Confluence target of a snapshot's Akeneo server and render (or, with `cache`,
+/// reuse a cached render of) the snapshot's page tree for it.
+async fn prepare_snapshot(
+    state: &AppState,
+    snapshot: &SnapshotRow,
+    params: &PublishParams,
+    cache: bool,
+) -> Result<(DbConfluenceConfig, Arc<SnapshotPageTree>, SnapshotTitles)> {
+    // Get Confluence config, whose attribute filters and display format apply to the render
+    let mut confluence_config = state
+        .confluence_configs
+        .fetch(&state.pool, snapshot.akeneo_server_id)
+        .await
+        .context("Failed to fetch Confluence configuration")?;
+    let render_options = state.render_options.for_target(&confluence_config)?;
+
+    // Render multi-page snapshot tree (or reuse a pre-rendered one). Table overrides and
+    // debug output render a one-off tree that is not cached.
+    let page_tree = match params.render_options(&render_options) {
+        None if cache => state.render_cache.get_or_render(snapshot, &render_options),
+        options => Arc::new(renderer::render_snapshot_pages(
+            snapshot.label.as_deref(),
+            &snapshot.data,
+            options.as_ref().unwrap_or(&render_options),
+        )),
+    };
+
+    let format = render_options.theme.format.clone();
+    let titles = SnapshotTitles::new(snapshot, params, &mut confluence_config, format);
+    Ok((confluence_config, page_tree, titles))
+}
+
+/// SHA-256 (hex) of what a snapshot publish writes: the rendered page tree, which is
+/// deterministic, and the parent and space overrides. Page titles and the root page's
+/// snapshot info are left out, so another snapshot with the same model hashes the same.
+fn content_hash(page_tree: &SnapshotPageTree, params: &PublishParams) -> String {
+    let mut hasher = Sha256::new();
+    let mut field = |value: &str| {
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(value.as_bytes());
+    };
+    for value in [&params.space_key, &params.parent_page_id, &params.parent_page] {
+        field(value.as_deref().unwrap_or_default());
+    }
+    field(&page_tree.root_body);
+    field(&page_tree.coverage_body);
+    field(&page_tree.health_body);
+    field(page_tree.labels_body.as_deref().unwrap_or_default());
+    for page in &page_tree.option_pages {
+        field(&page.attribute);
+        field(&page.page.to_string());
+        field(&page.body);
+    }
+    for page in page_tree.children.iter().chain(&page_tree.category_trees) {
+        field(&page.code);
+        field(&page.label);
+        field(page.label_group.as_deref().unwrap_or_default());
+        field(&page.body);
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// With `if_changed=true`, the URL of the publication a snapshot publish would repeat: the
+/// target's last publication of the same kind (the same overwrite or draft root page; the
+/// latest version in versioned mode) when it has the content hash of this snapshot's
+/// render. Renders (or reuses the cached tree) but makes no Confluence calls. `None`
+/// without `if_changed`.
+pub async fn unchanged_publication(
+    state: &AppState,
+    snapshot: &SnapshotRow,
+    params: &PublishParams,
+) -> Result<Option<String>> {
+    if params.if_changed != Some(true) {
+        return Ok(None);
+    }
+    state.config.features.ensure(Capability::Publishing)?;
+    let (_, page_tree, titles) = prepare_snapshot(state, snapshot, params, true).await?;
+    let root_title = titles.root_title(&titles.ctx(snapshot));
+    let (kind, page_title) = match titles.mode {
+        _ if titles.draft => (PublicationKind::Draft, Some(root_title.as_str())),
+        PublishMode::Overwrite => (PublicationKind::Overwrite, Some(root_title.as_str())),
+        PublishMode::Versioned => (PublicationKind::Version, None),
+    };
+    let last =
+        db::fetch_last_publication_hash(&state.pool, snapshot.akeneo_server_id, kind, page_title)
+            .await?;
+    let hash = content_hash(&page_tree, params);
+    Ok(last
+        .filter(|(_, last_hash)| last_hash.as_deref() == Some(hash.as_str()))
+        .map(|(web_url, _)| web_url))
+}
+
+/// Render (or reuse a cached render of) a snapshot and publish its page tree to the
+/// Confluence target configured for its Akeneo server. Returns the published root page.
+pub async fn publish_snapshot(
+    state: &AppState,
+    snapshot: &SnapshotRow,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<PublishOutcome> {
+    state.config.features.ensure(Capability::Publishing)?;
+    let (confluence_config, page_tree, titles) =
+        prepare_snapshot(state, snapshot, &params, true).await?;
+    let SnapshotTitles {
+        draft,
+        title_templates,
+        mode,
+        server_name,
+        ..
+    } = &titles;
+    let (draft, mode) = (*draft, *mode);
+    let ctx = titles.ctx(snapshot);
+    let root_title = titles.root_title(&ctx);
+    let event = PublishEvent {
+        kind: "snapshot",
+        server_id: snapshot.akeneo_server_id,
+        server: server_name,
+        snapshot_id: snapshot.id,
+        diff_id: None,
+        draft,
+        title: &root_title,
+    };
+    let archive = archive_target(state, &confluence_config, draft)?;
+    let git_mirror = match draft {
+        false => MirrorTarget::from_config(&confluence_config),
+        true => Ok(None),
+    };
+    hooks::run(state, Stage::PrePublish, &event, None).await?;
+
+    let content_hash = content_hash(&page_tree, &params);
+    let attachments = AttachmentOptions::parse(params.attachments.as_deref());
+    let provenance = Provenance::new(snapshot.akeneo_server_id, snapshot.id, None);
+    let client = build_client(state, confluence_config, params, &provenance).await?;
+    let client = match archive {
+        Some(_) => client.with_publish_log(),
+        None => client,
+    };
+
+    let result = match mode {
+        // A draft is the tree the live publish would produce, staged as a standalone
+        // tree so it can be reviewed before the index or root page changes
+        _ if draft => {
+            let info = snapshot_info(snapshot, &titles, provenance.published_at);
+            let root = RootPage {
+                title: &root_title,
+                info: &info,
+                banner: None,
+                parent_id: None,
+            };
+            let result =
+                publish_tree(&client, &page_tree, root, title_templates, &ctx, job).await?;
+            let publication_id = db::record_publication(
+                &state.pool,
+                NewPublication {
+                    akeneo_server_id: snapshot.akeneo_server_id,
+                    snapshot_id: snapshot.id,
+                    diff_id: None,
+                    kind: PublicationKind::Draft,
+                    page_id: &result.page_id,
+                    page_title: &root_title,
+                    web_url: &result.web_url,
+                    alerts: serde_json::json!([]),
+                },
+            )
+            .await?;
+            info!(
+                "Snapshot {} staged as draft publication {}",
+                snapshot.id, publication_id
+            );
+            Ok(result)
+        }
+        PublishMode::Overwrite => {
+            // The banner is only a courtesy, so it never blocks a publish
+            let banner = changes_banner(state, snapshot, &titles, &root_title)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Skipping the changes banner: {:#}", e);
+                    None
+                });
+            let info = snapshot_info(snapshot, &titles, provenance.published_at);
+            let root = RootPage {
+                title: &root_title,
+                info: &info,
+                banner: banner.as_deref(),
+                parent_id: None,
+            };
+            let result =
+                publish_tree(&client, &page_tree, root, title_templates, &ctx, job).await?;
+            // Only used for listings, so a failure here does not fail the publish
+            if let Err(e) = db::record_publication(
+                &state.pool,
+                NewPublication {
+                    akeneo_server_id: snapshot.akeneo_server_id,
+                    snapshot_id: snapshot.id,
+                    diff_id: None,
+                    kind: PublicationKind::Overwrite,
+                    page_id: &result.page_id,
+                    page_title: &root_title,
+                    web_url: &result.web_url,
+                    alerts: serde_json::json!([]),
+                },
+            )
+            .await
+            {
+                warn!("{:#}", e);
+            }
+            Ok(result)
+        }
+        PublishMode::Versioned => {
+            publish_versioned(state, &client, snapshot, &page_tree, &titles, &ctx, job).await
+        }
+    }?;
+
+    // Attach spreadsheet exports of the model tables to the root page
+    for attachment in export::snapshot_attachments(&snapshot.data, attachments) {
+        client
+            .upload_attachment(
+                &result.page_id,
+                &attachment.filename,
+                attachment.content_type,
+                attachment.data,
+            )
+            .await
+            .context("Failed to attach exports to the root page")?;
+    }
+    // Without the hash the next `if_changed` publish only repeats this one
+    let recorded = db::record_content_hash(
+        &state.pool,
+        snapshot.akeneo_server_id,
+        &result.page_id,
+        &content_hash,
+    );
+    if let Err(e) = recorded.await {
+        warn!("{:#}", e);
+    }
+
+    if let Some(archive) = &archive {
+        let warnings = job.warnings();
+        archive_publish(state, archive, &client, &event, &provenance, &result, &warnings).await?;
+    }
+    update_git_mirror(state, git_mirror, snapshot, server_name, job).await;
+
+    hooks::run(state, Stage::PostPublish, &event, Some(&result.web_url)).await?;
+    Ok(result)
+}
+
+/// Dry run of `publish_snapshot`: the pages publishing the snapshot would create, update
+/// or prune, found by the same lookups without writing to Confluence (see
+/// `ConfluenceClient::with_dry_run`). Hooks, the publication history, attachments, the
+/// archive and the Git mirror are left alone.
+pub async fn plan_snapshot(
+    state: &AppState,
+    snapshot: &SnapshotRow,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<Vec<PlannedPage>> {
+    let (confluence_config, page_tree, titles) =
+        prepare_snapshot(state, snapshot, &params, true).await?;
+    let ctx = titles.ctx(snapshot);
+    let root_title = titles.root_title(&ctx);
+    let provenance = Provenance::new(snapshot.akeneo_server_id, snapshot.id, None);
+    let client = build_client(state, confluence_config, params, &provenance)
+        .await?
+        .with_dry_run();
+
+    // A live versioned publish nests the new version under the index page
+    let index = match titles.mode {
+        PublishMode::Versioned if !titles.draft => {
+            let history =
+                db::fetch_publication_history(&state.pool, snapshot.akeneo_server_id).await?;
+            let body = titles.history_index(state, &history);
+            let index_title = titles.title_templates.snapshot_title(&ctx);
+            Some(client.publish_page(&index_title, &body).await?)
+        }
+        _ => None,
+    };
+    let info = snapshot_info(snapshot, &titles, provenance.published_at);
+    let root = RootPage {
+        title: &root_title,
+        info: &info,
+        banner: None,
+        parent_id: index.as_ref().map(|index| index.page_id.as_str()),
+    };
+    publish_tree(&client, &page_tree, root, &titles.title_templates, &ctx, job).await?;
+    Ok(client.take_plan())
+}
+
+/// Publish a snapshot that has no `snapshot` row (`POST /api/render/snapshot`) to the
+/// Confluence target of its Akeneo server, as an overwrite publish would: the root page and
+/// its children under the target's snapshot title (a versioned target's version title,
+/// outside its index), with stale pages removed. Hooks and attachments run as usual, but
+/// nothing is stored: no publication history, changes banner, archive or Git mirror, and
+/// the render is not cached. Returns the root page and every page published.
+pub async fn publish_inline_snapshot(
+    state: &AppState,
+    snapshot: &SnapshotRow,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<(PublishOutcome, Vec<PublishedPage>)> {
+    state.config.features.ensure(Capability::Publishing)?;
+    let (confluence_config, page_tree, titles) =
+        prepare_snapshot(state, snapshot, &params, false).await?;
+    let ctx = titles.ctx(snapshot);
+    let root_title = titles.root_title(&ctx);
+    let event = PublishEvent {
+        kind: "snapshot",
+        server_id: snapshot.akeneo_server_id,
+        server: &titles.server_name,
+        snapshot_id: snapshot.id,
+        diff_id: None,
+        draft: titles.draft,
+        title: &root_title,
+    };
+    hooks::run(state, Stage::PrePublish, &event, None).await?;
+
+    let attachments = AttachmentOptions::parse(params.attachments.as_deref());
+    let provenance = Provenance::new(snapshot.akeneo_server_id, snapshot.id, None);
+    let client = build_client(state, confluence_config, params, &provenance)
+        .await?
+        .with_publish_log();
+    let info = snapshot_info(snapshot, &titles, provenance.published_at);
+    let root = RootPage {
+        title: &root_title,
+        info: &info,
+        banner: None,
+        parent_id: None,
+    };
+    let result =
+        publish_tree(&client, &page_tree, root, &titles.title_templates, &ctx, job).await?;
+
+    for attachment in export::snapshot_attachments(&snapshot.data, attachments) {
+        client
+            .upload_attachment(
+                &result.page_id,
+                &attachment.filename,
+                attachment.content_type,
+                attachment.data,
+            )
+            .await
+            .context("Failed to attach exports to the root page")?;
+    }
+
+    hooks::run(state, Stage::PostPublish, &event, Some(&result.web_url)).await?;
+    Ok((result, client.take_publish_log()))
+}
+
+/// Dry run of `publish_inline_snapshot`.
+pub async fn plan_inline_snapshot(
+    state: &AppState,
+    snapshot: &SnapshotRow,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<Vec<PlannedPage>> {
+    let (confluence_config, page_tree, titles) =
+        prepare_snapshot(state, snapshot, &params, false).await?;
+    let ctx = titles.ctx(snapshot);
+    let root_title = titles.root_title(&ctx);
+    let provenance = Provenance::new(snapshot.akeneo_server_id, snapshot.id, None);
+    let client = build_client(state, confluence_config, params, &provenance)
+        .await?
+        .with_dry_run();
+    let info = snapshot_info(snapshot, &titles, provenance.published_at);
+    let root = RootPage {
+        title: &root_title,
+        info: &info,
+        banner: None,
+        parent_id: None,
+    };
+    publish_tree(&client, &page_tree, root, &titles.title_templates, &ctx, job).await?;
+    Ok(client.take_plan())
+}
+
+/// A family page republished on its own by `publish_family`.
+pub struct FamilyPublication {
+    pub page: PublishOutcome,
+    /// Whether the family's row of the root page's Families table was out of date and has
+    /// been refreshed.
+    pub root_updated: bool,
+}
+
+/// Render one family's child page and publish it into the page tree an earlier publish of
+/// the snapshot left, without touching the other pages. `snapshot` only needs the data
+/// `db::fetch_snapshot_families` loads.
+///
+/// The page is titled and placed like `publish_tree` does: the root page (and the family's
+/// group page, when the target groups families) must already exist, and the family page
+/// is found by its title under them, or created there. The family's row of the root page's
+/// Families table is swapped for a fresh render when it changed; the rest of the root page
+/// is left as it was published.
+pub async fn publish_family(
+    state: &AppState,
+    snapshot: &SnapshotRow,
+    family_code: &str,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<FamilyPublication> {
+    state.config.features.ensure(Capability::Publishing)?;
+    job.set_total(1);
+    let families = &snapshot.data.families;
+    let index = families
+        .iter()
+        .position(|family| family.code == family_code)
+        .with_context(|| format!("Family not found in snapshot: {}", family_code))?;
+
+    let mut confluence_config = state
+        .confluence_configs
+        .fetch(&state.pool, snapshot.akeneo_server_id)
+        .await
+        .context("Failed to fetch Confluence configuration")?;
+    let render_options = state.render_options.for_target(&confluence_config)?;
+    let options = params.render_options(&render_options).unwrap_or(render_options);
+
+    // Every family is titled and grouped, so colliding titles get the suffixes they get
+    // in a full publish, but only this family's page is rendered
+    let children: Vec<SnapshotChildPage> = families
+        .iter()
+        .enumerate()
+        .map(|(i, family)| match i == index {
+            true => renderer::render_family_child_page(&snapshot.data, family, &options),
+            false => renderer::family_child_page(&snapshot.data, family),
+        })
+        .collect();
+
+    let format = options.theme.format.clone();
+    let titles = SnapshotTitles::new(snapshot, &params, &mut confluence_config, format);
+    let ctx = titles.ctx(snapshot);
+    let root_title = titles.root_title(&ctx);
+    let mut title = family_titles(&children, &titles.title_templates, &ctx, job).swap_remove(index);
+    if titles.mode == PublishMode::Versioned && !titles.draft {
+        title = format!("{} [{}]", title, root_title);
+    }
+
+    let provenance = Provenance::new(snapshot.akeneo_server_id, snapshot.id, None);
+    let client = build_client(state, confluence_config, params, &provenance).await?;
+    let root_id = client.find_page_id(&root_title).await?.with_context(|| {
+        format!(
+            "Root page '{}' not found; publish the whole snapshot first",
+            root_title
+        )
+    })?;
+    let group = family_groups(client.config().family_grouping, &children)
+        .into_iter()
+        .find(|(_, indices)| indices.contains(&index));
+    let parent_id = match group {
+        Some((name, _)) => {
+            let group_title = format!("{}{}{}", root_title, FAMILY_GROUP_TITLE_SUFFIX, name);
+            client.find_page_id(&group_title).await?.with_context(|| {
+                format!(
+                    "Family group page '{}' not found; publish the whole snapshot first",
+                    group_title
+                )
+            })?
+        }
+        None => root_id.clone(),
+    };
+
+    let child = &children[index];
+    let page = client
+        .publish_page_under_id(&title, &child.body, &parent_id)
+        .await
+        .inspect_err(|e| job.page_failed(&title, e))
+        .with_context(|| format!("Failed to publish child page '{}' to Confluence", title))?;
+    info!("Child page '{}' published (id={})", title, page.page_id);
+    job.page_done(&title, page.operation);
+
+    let root_body = client.get_page_body(&root_id).await?;
+    let family = &families[index];
+    let row = renderer::replace_family_row(&root_body, family, &title, &options.theme);
+    let root_updated = match row {
+        Some(body) if body != root_body => {
+            client
+                .publish_page(&root_title, &body)
+                .await
+                .context("Failed to update the root page's Families table")?;
+            info!("Root page '{}' row of family {} refreshed", root_title, family_code);
+            true
+        }
+        Some(_) => false,
+        None => {
+            let message = format!(
+                "Root page '{}' has no row for family {}; publish the whole snapshot to add it",
+                root_title, family_code
+            );
+            warn!("{}", message);
+            job.warn(message);
+            false
+        }
+    };
+    Ok(FamilyPublication { page, root_updated })
+}
+
+/// Reports with more items than this are rendered on a blocking thread rather than on the
+/// async executor.
+const BLOCKING_RENDER_ITEMS: usize = 500;
+
+/// A diff's rendered page and what its target needs to title it, shared by `publish_diff`
+/// and `plan_diff`.
+struct PreparedDiff {
+    diff_id: Uuid,
+    /// The categories on the page; `left_out` holds the ones `include` / `exclude` left out.
+    report: diff::DiffReport,
+    left_out: diff::DiffReport,
+    alerts: Vec<diff::DiffAlert>,
+    warnings: Vec<String>,
+    body: String,
+    options: Arc<RenderOptions>,
+    draft: bool,
+    after_snapshot: SnapshotMeta,
+    title_templates: TitleTemplates,
+    labels: DiffLabels,
+}
+
+/// The strings a diff's `TitleContext` borrows.
+struct DiffLabels {
+    server: String,
+    date: String,
+    before: String,
+    after: String,
+}
+
+impl DiffLabels {
+    fn ctx(&self) -> TitleContext<'_> {
+        TitleContext {
+            server: &self.server,
+            label: &self.after,
+            date: &self.date,
+            before: &self.before,
+            after: &self.after,
+            ..Default::default()
+        }
+    }
+}
+
+/// Parse and render a diff for the Confluence target configured for the after-snapshot's
+/// Akeneo server.
+///
+/// The diff is parsed on a blocking thread while both snapshots and the target's
+/// configuration are fetched, so the database round trips overlap the parsing.
+async fn prepare_diff(
+    state: &AppState,
+    diff_row: DiffRow,
+    params: &PublishParams,
+) -> Result<(DbConfluenceConfig, PreparedDiff)> {
+    let DiffRow {
+        id: diff_id,
+        snapshot_before_id,
+        snapshot_after_id,
+        data,
+    } = diff_row;
+
+    let identity = state.render_options.diff.identity_keys.clone();
+    let parse = async {
+        tokio::task::spawn_blocking(move || diff::parse_diff_data(&data, &identity))
+            .await
+            .context("Diff parsing task panicked")?
+            .context("Failed to parse diff data")
+    };
+    let after = async {
+        let after = db::fetch_snapshot_meta(&state.pool, snapshot_after_id).await?;
+        let confluence_config = state
+            .confluence_configs
+            .fetch(&state.pool, after.akeneo_server_id)
+            .await
+            .context("Failed to fetch Confluence configuration")?;
+        Ok::<_, anyhow::Error>((after, confluence_config))
+    };
+    let (parsed, before_snapshot, (after_snapshot, mut confluence_config), before_counts) =
+        tokio::try_join!(
+            parse,
+            db::fetch_snapshot_meta(&state.pool, snapshot_before_id),
+            after,
+            alert_base(state, snapshot_before_id),
+        )?;
+    let (mut report, warnings) = parsed;
+
+    // Log summary
+    for warning in &warnings {
+        warn!("Diff {}: {}", diff_id, warning);
+    }
+    for (category, cat_diff) in &report {
+        info!(
+            "  {}: {} added, {} removed, {} changed, {} renamed, {} moved",
+            category,
+            cat_diff.added.len(),
+            cat_diff.removed.len(),
+            cat_diff.changed.len(),
+            cat_diff.renamed.len(),
+            cat_diff.moved.len()
+        );
+        for warning in &cat_diff.parse_warnings {
+            warn!("Diff {}, {}: {}", diff_id, category, warning);
+        }
+    }
+    let filter = params.category_filter();
+    let left_out = match &filter {
+        Some(filter) => filter.split_off(&mut report),
+        None => diff::DiffReport::new(),
+    };
+    if filter.is_some() {
+        info!(
+            "Diff {} is limited to {:?}",
+            diff_id,
+            report.keys().collect::<Vec<_>>()
+        );
+    }
+    let thresholds = &state.render_options.diff.alert_thresholds;
+    let alerts = diff::threshold_alerts(&report, &before_counts, thresholds);
+    for alert in &alerts {
+        warn!(
+            "Diff {} alert: {} of {} {} {} ({:.0}%, threshold {}%)",
+            diff_id,
+            alert.count,
+            alert.total,
+            alert.category,
+            alert.kind,
+            alert.percent,
+            alert.threshold
+        );
+    }
+
+    // Render the diff page
+    let options = diff_options(state, params, &confluence_config)?;
+    let (before_side, after_side) = tokio::join!(
+        diff_snapshot(state, &before_snapshot),
+        diff_snapshot(state, &after_snapshot)
+    );
+    let (report, body) = render_diff(
+        &options,
+        report,
+        &alerts,
+        &warnings,
+        filter.map(|_| left_out.keys().cloned().collect()),
+        before_side,
+        after_side,
+    )
+    .await?;
+
+    let draft = is_draft(params, &mut confluence_config);
+
+    // Apply the target's diff title template
+    let mut title_templates = TitleTemplates::from_config(&confluence_config);
+    if draft {
+        title_templates = title_templates.into_draft();
+    }
+    let labels = DiffLabels {
+        server: confluence_config.server_name.clone(),
+        date: after_snapshot.completed_at.format("%Y-%m-%d").to_string(),
+        before: before_snapshot.label.unwrap_or_else(|| "before".to_string()),
+        after: after_snapshot.label.clone().unwrap_or_else(|| "after".to_string()),
+    };
+    let prepared = PreparedDiff {
+        diff_id,
+        report,
+        left_out,
+        alerts,
+        warnings,
+        body,
+        options,
+        draft,
+        after_snapshot,
+        title_templates,
+        labels,
+    };
+    Ok((confluence_config, prepared))
+}
+
+/// Parse and render a diff between two snapshots and publish it as a single page to the
+/// Confluence target configured for the after-snapshot's Akeneo server.
+pub async fn publish_diff(
+    state: &AppState,
+    diff_row: DiffRow,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<DiffPublication> {
+    state.config.features.ensure(Capability::Publishing)?;
+    job.set_total(1);
+    let (confluence_config, prepared) = prepare_diff(state, diff_row, &params).await?;
+    let PreparedDiff {
+        diff_id,
+        mut report,
+        left_out,
+        alerts,
+        warnings,
+        body,
+        options,
+        draft,
+        after_snapshot,
+        title_templates,
+        labels,
+    } = prepared;
+    let announce = params.content_type == Some(ContentType::BlogPost);
+    if announce && draft {
+        info!("Diff {} is a draft; it is announced when the draft is promoted", diff_id);
+    }
+    let activity = !draft && state.render_options.diff.activity_diffs > 0;
+    job.set_total(1 + usize::from(announce && !draft) + usize::from(activity));
+
+    let ctx = labels.ctx();
+    let title = title_templates.diff_title(&ctx);
+    let jira_config = JiraConfig::from_db(&confluence_config);
+    let event = PublishEvent {
+        kind: "diff",
+        server_id: after_snapshot.akeneo_server_id,
+        server: &labels.server,
+        snapshot_id: after_snapshot.id,
+        diff_id: Some(diff_id),
+        draft,
+        title: &title,
+    };
+    let archive = archive_target(state, &confluence_config, draft)?;
+    hooks::run(state, Stage::PrePublish, &event, None).await?;
+
+    let provenance = Provenance::new(
+        after_snapshot.akeneo_server_id,
+        after_snapshot.id,
+        Some(diff_id),
+    );
+    let client = build_client(state, confluence_config, params, &provenance).await?;
+    let client = match archive {
+        Some(_) => client.with_publish_log(),
+        None => client,
+    };
+
+    // Publish the diff page
+    let result = client
+        .publish_page(&title, &body)
+        .await
+        .inspect_err(|e| job.page_failed(&title, e))
+        .context("Failed to publish diff page to Confluence")?;
+
+    info!("Diff page '{}' published (id={})", title, result.page_id);
+    job.page_done(&title, result.operation);
+
+    let publication = NewPublication {
+        akeneo_server_id: after_snapshot.akeneo_server_id,
+        snapshot_id: after_snapshot.id,
+        diff_id: Some(diff_id),
+        kind: if draft {
+            PublicationKind::Draft
+        } else {
+            PublicationKind::Diff
+        },
+        page_id: &result.page_id,
+        page_title: &title,
+        web_url: &result.web_url,
+        alerts: serde_json::json!(alerts),
+    };
+    // A draft cannot be promoted without its record; live diffs only need it for listings
+    match db::record_publication(&state.pool, publication).await {
+        Ok(publication_id) if draft => {
+            info!("Diff {} staged as draft publication {}", diff_id, publication_id)
+        }
+        Ok(_) => {}
+        Err(e) if draft => return Err(e),
+        Err(e) => warn!("{:#}", e),
+    }
+
+    let blog_post = if announce && !draft {
+        let post = publish_announcement(&client, &ctx, &report, &alerts, &result, &options, job);
+        Some(post.await?)
+    } else {
+        None
+    };
+
+    // The activity page only summarizes the diff pages, so failing to refresh it does not
+    // fail the publish
+    if activity {
+        let server_id = after_snapshot.akeneo_server_id;
+        let update = publish_activity(state, &client, server_id, &title_templates, &ctx, job);
+        if let Err(e) = update.await {
+            warn!("Failed to update the model activity page: {:#}", e);
+        }
+    }
+
+    // Breaking changes are raised for the whole diff: the issue is raised once, so it must
+    // not depend on the categories of the publish that happened to raise it
+    let skipped_changed_items = diff::skipped_changed_items(&report);
+    report.extend(left_out);
+
+    // Drafts are raised when they are promoted; the page is already live, so a Jira
+    // failure does not fail the publish
+    let jira_issue = match jira_config {
+        Some(jira_config) if !draft => {
+            raise_jira_issue(state, jira_config, diff_id, &ctx, &report, &result.web_url)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to raise a Jira issue for diff {}: {:#}", diff_id, e);
+                    None
+                })
+        }
+        _ => None,
+    };
+
+    if let Some(archive) = &archive {
+        archive_publish(state, archive, &client, &event, &provenance, &result, &warnings).await?;
+    }
+
+    hooks::run(state, Stage::PostPublish, &event, Some(&result.web_url)).await?;
+    Ok(DiffPublication {
+        page: result,
+        jira_issue,
+        alerts,
+        warnings,
+        skipped_changed_items,
+        blog_post,
+    })
+}
+
+/// Dry run of `publish_diff`: whether publishing the diff would create or update its page
+/// and, when requested, its blog post and the model activity page, without writing to
+/// Confluence (see `ConfluenceClient::with_dry_run`). Hooks, the publication history, Jira
+/// and the archive are left alone.
+pub async fn plan_diff(
+    state: &AppState,
+    diff_row: DiffRow,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<Vec<PlannedPage>> {
+    let (confluence_config, prepared) = prepare_diff(state, diff_row, &params).await?;
+    for warning in &prepared.warnings {
+        job.warn(warning.clone());
+    }
+    let announce = params.content_type == Some(ContentType::BlogPost) && !prepared.draft;
+    let activity = !prepared.draft && state.render_options.diff.activity_diffs > 0;
+    job.set_total(1 + usize::from(announce) + usize::from(activity));
+
+    let ctx = prepared.labels.ctx();
+    let title = prepared.title_templates.diff_title(&ctx);
+    let provenance = Provenance::new(
+        prepared.after_snapshot.akeneo_server_id,
+        prepared.after_snapshot.id,
+        Some(prepared.diff_id),
+    );
+    let client = build_client(state, confluence_config, params, &provenance)
+        .await?
+        .with_dry_run();
+
+    let result = client.publish_page(&title, &prepared.body).await?;
+    job.page_done(&title, result.operation);
+    if announce {
+        let PreparedDiff {
+            report,
+            alerts,
+            options,
+            ..
+        } = &prepared;
+        publish_announcement(&client, &ctx, report, alerts, &result, options, job).await?;
+    }
+    if activity {
+        let server_id = prepared.after_snapshot.akeneo_server_id;
+        let templates = &prepared.title_templates;
+        publish_activity(state, &client, server_id, templates, &ctx, job).await?;
+    }
+    Ok(client.take_plan())
+}
+
+/// Publish a cumulative diff (see `cumulative::load`) as one diff page, titled by the
+/// target's diff title template with the range's first and last snapshot plus
+/// `CUMULATIVE_TITLE_SUFFIX`. Unlike a stored
+/// diff it is always published live, is not recorded in `publication_history` and raises
+/// no Jira issue.
+pub async fn publish_cumulative_diff(
+    state: &AppState,
+    cumulative: CumulativeDiff,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<DiffPublication> {
+    state.config.features.ensure(Capability::Publishing)?;
+    let announce = params.content_type == Some(ContentType::BlogPost);
+    job.set_total(if announce { 2 } else { 1 });
+    let CumulativeDiff {
+        before,
+        after,
+        diff_ids,
+        data,
+    } = cumulative;
+
+    let identity = state.render_options.diff.identity_keys.clone();
+    let parse = async {
+        tokio::task::spawn_blocking(move || diff::parse_diff_data(&data, &identity))
+            .await
+            .context("Diff parsing task panicked")?
+            .context("Failed to parse the composed diff")
+    };
+    let confluence_config = async {
+        state
+            .confluence_configs
+            .fetch(&state.pool, after.akeneo_server_id)
+            .await
+            .context("Failed to fetch Confluence configuration")
+    };
+    let ((mut report, warnings), confluence_config, before_counts) =
+        tokio::try_join!(parse, confluence_config, alert_base(state, before.id))?;
+    for warning in &warnings {
+        warn!("Cumulative diff of server {}: {}", after.akeneo_server_id, warning);
+    }
+    for (category, cat_diff) in &report {
+        for warning in &cat_diff.parse_warnings {
+            warn!(
+                "Cumulative diff of server {}, {}: {}",
+                after.akeneo_server_id, category, warning
+            );
+        }
+    }
+    let filter = params.category_filter();
+    let left_out = filter
+        .as_ref()
+        .map(|filter| filter.split_off(&mut report).into_keys().collect());
+    let skipped_changed_items = diff::skipped_changed_items(&report);
+
+    let alerts = diff::threshold_alerts(
+        &report,
+        &before_counts,
+        &state.render_options.diff.alert_thresholds,
+    );
+    let options = diff_options(state, &params, &confluence_config)?;
+    let (before_side, after_side) =
+        tokio::join!(diff_snapshot(state, &before), diff_snapshot(state, &after));
+    let (report, body) = render_diff(
+        &options,
+        report,
+        &alerts,
+        &warnings,
+        left_out,
+        before_side,
+        after_side,
+    )
+    .await?;
+    let body = format!(
+        "{}{}",
+        renderer::render_cumulative_note(&before, &after, diff_ids.len(), &options.theme.format),
+        body
+    );
+
+    let title_templates = TitleTemplates::from_config(&confluence_config);
+    let server_name = confluence_config.server_name.clone();
+    let diff_date = after.completed_at.format("%Y-%m-%d").to_string();
+    let after_label = after.label.as_deref().unwrap_or("after");
+    let ctx = TitleContext {
+        server: &server_name,
+        label: after_label,
+        date: &diff_date,
+        before: before.label.as_deref().unwrap_or("before"),
+        after: after_label,
+        ..Default::default()
+    };
+    let title = title_templates.diff_title(&ctx) + CUMULATIVE_TITLE_SUFFIX;
+    let event = PublishEvent {
+        kind: "cumulative_diff",
+        server_id: after.akeneo_server_id,
+        server: &server_name,
+        snapshot_id: after.id,
+        diff_id: None,
+        draft: false,
+        title: &title,
+    };
+    let archive = archive_target(state, &confluence_config, false)?;
+    hooks::run(state, Stage::PrePublish, &event, None).await?;
+
+    let provenance = Provenance::new(after.akeneo_server_id, after.id, None);
+    let client = build_client(state, confluence_config, params, &provenance).await?;
+    let client = match archive {
+        Some(_) => client.with_publish_log(),
+        None => client,
+    };
+    let result = client
+        .publish_page(&title, &body)
+        .await
+        .inspect_err(|e| job.page_failed(&title, e))
+        .context("Failed to publish cumulative diff page to Confluence")?;
+
+    info!(
+        "Cumulative diff page '{}' published from {} diffs (id={})",
+        title,
+        diff_ids.len(),
+        result.page_id
+    );
+    job.page_done(&title, result.operation);
+
+    let blog_post = if announce {
+        let post = publish_announcement(&client, &ctx, &report, &alerts, &result, &options, job);
+        Some(post.await?)
+    } else {
+        None
+    };
+
+    if let Some(archive) = &archive {
+        archive_publish(state, archive, &client, &event, &provenance, &result, &warnings).await?;
+    }
+
+    hooks::run(state, Stage::PostPublish, &event, Some(&result.web_url)).await?;
+    Ok(DiffPublication {
+        page: result,
+        jira_issue: None,
+        alerts,
+        warnings,
+        skipped_changed_items,
+        blog_post,
+    })
+}
+
+/// Item counts of a diff's before-snapshot, which alert thresholds are relative to. Empty
+/// without thresholds, saving the query.
+async fn alert_base(state: &AppState, before_id: Uuid) -> Result<BTreeMap<String, usize>> {
+    if state.render_options.diff.alert_thresholds.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    db::fetch_snapshot_counts(&state.pool, before_id).await
+}
+
+/// Options a diff page is rendered with: the target's display format and item fields and
+/// the request's table overrides, changed-item grouping and debug output.
+fn diff_options(
+    state: &AppState,
+    params: &PublishParams,
+    config: &DbConfluenceConfig,
+) -> Result<Arc<RenderOptions>> {
+    let mut options = state
+        .render_options
+        .with_display_format(DisplayFormat::from_db(config)?)
+        .with_item_properties(ItemProperties::from_db(config)?);
+    if let Some(grouping) = params.group_changes {
+        options.diff.changed_grouping = grouping;
+    }
+    Ok(Arc::new(params.render_options(&options).unwrap_or(options)))
+}
+
+/// A side of a diff for the page header, linking to the snapshot's published root page.
+/// A failed lookup only drops the link.
+async fn diff_snapshot(state: &AppState, snapshot: &SnapshotMeta) -> renderer::DiffSnapshot {
+    let page_url = db::fetch_published_snapshot_url(&state.pool, snapshot.id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to look up the page of snapshot {}: {:#}", snapshot.id, e);
+            None
+        });
+    renderer::DiffSnapshot {
+        id: snapshot.id,
+        label: snapshot.label.clone(),
+        completed_at: snapshot.completed_at,
+        page_url,
+    }
+}
+
+/// Render a diff page, on a blocking thread when the report is large enough to stall the
+/// executor. The report is handed back for the steps after publishing.
+async fn render_diff(
+    options: &Arc<RenderOptions>,
+    report: diff::DiffReport,
+    alerts: &[diff::DiffAlert],
+    warnings: &[String],
+    left_out: Option<Vec<String>>,
+    before: renderer::DiffSnapshot,
+    after: renderer::DiffSnapshot,
+) -> Result<(diff::DiffReport, String)> {
+    let alerts = alerts.to_vec();
+    let warnings = warnings.to_vec();
+    let render = move |report: diff::DiffReport, options: &RenderOptions| {
+        let body = renderer::render_diff_page(
+            &before,
+            &after,
+            &report,
+            &alerts,
+            &warnings,
+            left_out.as_deref(),
+            options,
+        );
+        (report, body)
+    };
+
+    let items: usize = report
+        .values()
+        .map(|d| {
+            d.added.len() + d.removed.len() + d.changed.len() + d.renamed.len() + d.moved.len()
+        })
+        .sum();
+    if items <= BLOCKING_RENDER_ITEMS {
+        return Ok(render(report, options));
+    }
+
+    info!("Rendering {} diff items on a blocking thread", items);
+    let options = options.clone();
+    tokio::task::spawn_blocking(move || render(report, &options))
+        .await
+        .context("Render task panicked")
+}
+
+/// A published diff page, the Jira issue raised for its breaking changes, if any, the
+/// threshold alerts it raised, the malformed categories it left out and the blog post
+/// announcing it, if one was requested.
+pub struct DiffPublication {
+    pub page: PublishOutcome,
+    pub jira_issue: Option<String>,
+    pub alerts: Vec<diff::DiffAlert>,
+    /// `diff::parse_diff_data` warnings, also shown on the page.
+    pub warnings: Vec<String>,
+    /// Changed items left out because they could not be read (`CategoryDiff::parse_warnings`),
+    /// in the categories on the page.
+    pub skipped_changed_items: usize,
+    pub blog_post: Option<PublishOutcome>,
+}
+
+impl DiffPublication {
+    /// URLs of the diff page and the blog post, as recorded in the audit log.
+    pub fn urls(&self) -> Vec<String> {
+        std::iter::once(&self.page)
+            .chain(&self.blog_post)
+            .map(|page| page.web_url.clone())
+            .collect()
+    }
+}
+
+/// Announce a live diff in a blog post titled by `BLOG_POST_TITLE`, with the diff's
+/// summary and change descriptions and a link to its page. Republishing a diff updates
+/// the same post.
+async fn publish_announcement(
+    client: &ConfluenceClient,
+    ctx: &TitleContext<'_>,
+    report: &diff::DiffReport,
+    alerts: &[diff::DiffAlert],
+    diff_page: &PublishOutcome,
+    options: &RenderOptions,
+    job: &JobHandle,
+) -> Result<PublishOutcome> {
+    let title = apply_template(BLOG_POST_TITLE, ctx);
+    let body = renderer::render_diff_announcement(
+        ctx.before,
+        ctx.after,
+        report,
+        alerts,
+        &diff_page.web_url,
+        &options.theme,
+    );
+    let post = client
+        .publish_blog_post(&title, &body)
+        .await
+        .inspect_err(|e| job.page_failed(&title, e))
+        .context("Failed to publish the diff announcement blog post")?;
+    info!("Blog post '{}' published (id={})", title, post.page_id);
+    job.page_done(&title, post.operation);
+    Ok(post)
+}
+
+/// Regenerate the server's model activity page (`renderer::render_activity_page`) from its
+/// last `MODEL_ACTIVITY_DIFFS` live diff publishes, next to the diff pages.
+async fn publish_activity(
+    state: &AppState,
+    client: &ConfluenceClient,
+    server_id: Uuid,
+    title_templates: &TitleTemplates,
+    ctx: &TitleContext<'_>,
+    job: &JobHandle,
+) -> Result<()> {
+    let limit = state.render_options.diff.activity_diffs as i64;
+    let rows = db::fetch_diff_activity(&state.pool, server_id, limit).await?;
+    let snapshot_title = title_templates.snapshot_title(&TitleContext {
+        server: ctx.server,
+        ..Default::default()
+    });
+    let title = format!("{}{}", snapshot_title, ACTIVITY_TITLE_SUFFIX);
+    let body = renderer::render_activity_page(&rows, &state.render_options);
+    let page = client
+        .publish_page(&title, &body)
+        .await
+        .inspect_err(|e| job.page_failed(&title, e))
+        .context("Failed to publish the model activity page")?;
+    info!("Model activity page '{}' updated (id={})", title, page.page_id);
+    job.page_done(&title, page.operation);
+    Ok(())
+}
+
+/// Publish a comparison matrix (`matrix::build`) as a single page to the Confluence target
+/// of `server_id`, titled `title` or, by default, the target's snapshot title plus
+/// `MATRIX_TITLE_SUFFIX`. Like a cumulative diff it is always published live and is not
+/// recorded in `publication_history`.
+pub async fn publish_matrix(
+    state: &AppState,
+    server_id: Uuid,
+    matrix: &Matrix,
+    title: Option<&str>,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<PublishOutcome> {
+    state.config.features.ensure(Capability::Publishing)?;
+    job.set_total(1);
+    let confluence_config = state
+        .confluence_configs
+        .fetch(&state.pool, server_id)
+        .await
+        .context("Failed to fetch Confluence configuration")?;
+    let title = match title.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(title) => title.to_string(),
+        None => {
+            let snapshot_title =
+                TitleTemplates::from_config(&confluence_config).snapshot_title(&TitleContext {
+                    server: &confluence_config.server_name,
+                    ..Default::default()
+                });
+            format!("{}{}", snapshot_title, MATRIX_TITLE_SUFFIX)
+        }
+    };
+    let body = renderer::render_matrix_page(matrix, &state.render_options.theme);
+
+    let snapshot_id = matrix.snapshots.first().map(|s| s.id).unwrap_or_default();
+    let provenance = Provenance::new(server_id, snapshot_id, None);
+    let client = build_client(state, confluence_config, params, &provenance).await?;
+    let page = client
+        .publish_page(&title, &body)
+        .await
+        .inspect_err(|e| job.page_failed(&title, e))
+        .context("Failed to publish the comparison matrix page")?;
+    info!(
+        "Comparison matrix '{}' published for {} snapshots (id={})",
+        title,
+        matrix.snapshots.len(),
+        page.page_id
+    );
+    job.page_done(&title, page.operation);
+    Ok(page)
+}
+
+/// Raise a Jira issue when a diff has breaking changes (`diff::breaking_changes`), once
+/// per diff: republishing returns the issue raised the first time.
+async fn raise_jira_issue(
+    state: &AppState,
+    mut jira_config: JiraConfig,
+    diff_id: Uuid,
+    ctx: &TitleContext<'_>,
+    report: &diff::DiffReport,
+    diff_url: &str,
+) -> Result<Option<String>> {
+    let changes = diff::breaking_changes(report);
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    if let Some(issue_key) = db::fetch_diff_jira_issue(&state.pool, diff_id).await? {
+        info!("Diff {} already has Jira issue {}", diff_id, issue_key);
+        return Ok(Some(issue_key));
+    }
+
+    jira_config.api_token = state
+        .secrets
+        .resolve(&jira_config.api_token)
+        .await
+        .context("Failed to resolve Jira API token")?;
+    let issue = jira::breaking_change_issue(ctx, &changes, diff_url);
+    let issue_key = JiraClient::new(jira_config, state.http.clone())
+        .create_issue(&issue)
+        .await?;
+    db::record_diff_jira_issue(&state.pool, diff_id, &issue_key).await?;
+
+    Ok(Some(issue_key))
+}
+
+/// Promote a draft publication: publish the same snapshot or diff to the live location,
+/// then delete the staged draft pages and mark the draft as promoted.
+///
+/// The live pages are rendered afresh rather than moved, so promotion produces exactly
+/// what a live publish would (titles, index page, publication history) and the drafts'
+/// `[Draft]` titles never reach the live tree.
+pub async fn promote_draft(
+    state: &AppState,
+    draft: &db::PublicationRecord,
+    params: PublishParams,
+    job: &JobHandle,
+) -> Result<PublishOutcome> {
+    let params = PublishParams {
+        draft: Some(false),
+        ..params
+    };
+    let result = match draft.diff_id {
+        Some(diff_id) => {
+            let diff_row = db::fetch_diff(&state.pool, diff_id).await?;
+            publish_diff(state, diff_row, params, job).await?.page
+        }
+        None => {
+            let snapshot = db::fetch_snapshot(&state.pool, draft.snapshot_id).await?;
+            publish_snapshot(state, &snapshot, params, job).await?
+        }
+    };
+
+    // The live pages are in place, so failing to clean up the drafts only leaves
+    // stale pages behind
+    let confluence_config = state
+        .confluence_configs
+        .fetch(&state.pool, draft.akeneo_server_id)
+        .await
+        .context("Failed to fetch Confluence configuration")?;
+    let provenance = Provenance::new(draft.akeneo_server_id, draft.snapshot_id, draft.diff_id);
+    let client =
+        build_client(state, confluence_config, PublishParams::default(), &provenance).await?;
+    if let Err(e) = delete_page_tree(&client, &draft.page_id).await {
+        warn!(
+            "Failed to delete draft page '{}' (id={}): {:#}",
+            draft.page_title, draft.page_id, e
+        );
+    }
+
+    db::mark_publication_promoted(&state.pool, draft.id).await?;
+    info!("Draft publication {} promoted", draft.id);
+
+    Ok(result)
+}
+
+/// What `withdraw_snapshot` removed.
+#[derive(Serialize)]
+pub struct Withdrawal {
+    /// Publications removed from the history.
+    pub publications: usize,
+    /// Confluence pages deleted (moved to the trash), including child pages.
+    pub pages: usize,
+}
+
+/// Delete every page tree published for a snapshot (versioned, overwrite and draft
+/// publications; diff pages are left alone) and remove them from the publication
+/// history. The versioned-mode index is regenerated so it no longer links to the
+/// withdrawn pages.
+pub async fn withdraw_snapshot(state: &AppState, snapshot: &SnapshotMeta) -> Result<Withdrawal> {
+    state.config.features.ensure(Capability::Publishing)?;
+    let publications = db::fetch_snapshot_publications(&state.pool, snapshot.id).await?;
+    if publications.is_empty() {
+        return Ok(Withdrawal {
+            publications: 0,
+            pages: 0,
+        });
+    }
+
+    let confluence_config = state
+        .confluence_configs
+        .fetch(&state.pool, snapshot.akeneo_server_id)
+        .await
+        .context("Failed to fetch Confluence configuration")?;
+    let title_templates = TitleTemplates::from_config(&confluence_config);
+    let server_name = confluence_config.server_name.clone();
+    let format = DisplayFormat::from_db(&confluence_config)?;
+    let provenance = Provenance::new(snapshot.akeneo_server_id, snapshot.id, None);
+    let client =
+        build_client(state, confluence_config, PublishParams::default(), &provenance).await?;
+
+    let mut pages = 0;
+    for publication in &publications {
+        pages += delete_page_tree(&client, &publication.page_id)
+            .await
+            .with_context(|| format!("Failed to delete '{}'", publication.page_title))?;
+        db::delete_publication(&state.pool, publication.id).await?;
+        info!(
+            "Withdrew {} publication '{}' (id={}) of snapshot {}",
+            publication.kind, publication.page_title, publication.page_id, snapshot.id
+        );
+    }
+
+    if publications
+        .iter()
+        .any(|p| p.kind == PublicationKind::Version.as_str())
+    {
+        let date = snapshot.completed_at.format("%Y-%m-%d").to_string();
+        let time = snapshot.completed_at.format("%H:%M").to_string();
+        let ctx = TitleContext {
+            server: &server_name,
+            label: snapshot.label.as_deref().unwrap_or("Unnamed snapshot"),
+            date: &date,
+            time: &time,
+            ..Default::default()
+        };
+        let index_title = title_templates.snapshot_title(&ctx);
+        let history = db::fetch_publication_history(&state.pool, snapshot.akeneo_server_id).await?;
+        let options = state.render_options.with_display_format(format);
+        client
+            .publish_page(&index_title, &renderer::render_history_index(&history, &options))
+            .await
+            .context("Failed to update index page in Confluence")?;
+        info!(
+            "Index page '{}' updated with {} publish(es)",
+            index_title,
+            history.len()
+        );
+    }
+
+    Ok(Withdrawal {
+        publications: publications.len(),
+        pages,
+    })
+}
+
+/// Delete a page and all of its descendants, deepest first. Returns the number of pages
+/// deleted; fails after trying every page if any of them could not be deleted.
+async fn delete_page_tree(client: &ConfluenceClient, page_id: &str) -> Result<usize> {
+    let descendants = client.get_child_pages(page_id).await?;
+
+    let mut deleted = 0;
+    let mut failed = 0;
+    for page in descendants.iter().rev() {
+        match client.delete_page(&page.id).await {
+            Ok(()) => deleted += 1,
+            Err(e) => {
+                warn!(
+                    "Failed to delete page '{}' (id={}): {:#}",
+                    page.title, page.id, e
+                );
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} child page(s) of page {} could not be deleted", failed, page_id);
+    }
+
+    client.delete_page(page_id).await?;
+    info!("Deleted page {} and {} child page(s)", page_id, deleted);
+    Ok(deleted + 1)
+}
+
+/// Where a publish is archived: the target's `archive_bucket`, unless the publish is a
+/// draft (its promotion is archived instead). Checked before publishing, so a target that
+/// must be archived fails untouched when archival is not configured.
+fn archive_target(
+    state: &AppState,
+    config: &DbConfluenceConfig,
+    draft: bool,
+) -> Result<Option<ArchiveTarget>> {
+    if draft {
+        return Ok(None);
+    }
+    ArchiveTarget::from_config(state, config)
+}
+
+/// Archive the pages a publish wrote (the client's publish log) with its report. The pages
+/// are live by then, so a failed archive fails the publish rather than going unnoticed.
+async fn archive_publish(
+    state: &AppState,
+    archive: &ArchiveTarget,
+    client: &ConfluenceClient,
+    event: &PublishEvent<'_>,
+    provenance: &Provenance,
+    result: &PublishOutcome,
+    warnings: &[String],
+) -> Result<()> {
+    let publish = ArchivedPublish {
+        kind: event.kind,
+        server_id: event.server_id,
+        server: event.server,
+        snapshot_id: event.snapshot_id,
+        diff_id: event.diff_id,
+        published_at: provenance.published_at,
+        page_id: &result.page_id,
+        page_url: &result.web_url,
+        warnings,
+    };
+    object_store::archive(state, archive, &publish, &client.take_publish_log())
+        .await
+        .context("Failed to archive the publish")?;
+    Ok(())
+}
+
+/// Commit a live snapshot publish to the target's Git mirror, if it has one. The mirror
+/// only follows the model history, so a failure (including an invalid mirror
+/// configuration) is logged and reported as a warning rather than failing the publish.
+async fn update_git_mirror(
+    state: &AppState,
+    target: Result<Option<MirrorTarget>>,
+    snapshot: &SnapshotRow,
+    server: &str,
+    job: &JobHandle,
+) {
+    let result = match target {
+        Ok(Some(target)) => git_mirror::mirror_snapshot(state, &target, snapshot, server).await,
+        Ok(None) => return,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        warn!("Failed to update the Git mirror of snapshot {}: {:#}", snapshot.id, e);
+        job.warn(format!("The Git mirror was not updated: {:#}", e));
+    }
+}
+
+/// Whether a publish is a draft: the request's `draft` parameter, else the target's
+/// `draft_publishing` setting. Drafts are published under the staging parent page (a title,
+/// replacing a configured parent ID) when one is configured; a per-request parent override
+/// still wins over it.
+fn is_draft(params: &PublishParams, config: &mut DbConfluenceConfig) -> bool {
+    let draft = params.draft.unwrap_or(config.draft_publishing);
+    if draft
+        && let Some(staging) = config.staging_parent_page.take().filter(|s| !s.is_empty())
+    {
+        config.parent_page = staging;
+        config.parent_page_id = None;
+    }
+    draft
+}
+
+/// Build a Confluence client for a target, resolving secret references in its credentials,
+/// applying the per-request overrides, and tagging every page it publishes with the
+/// provenance property. The client (with its resolved token and parent page ID) is reused
+/// from `state.clients` while the target's configuration is unchanged.
+async fn build_client(
+    state: &AppState,
+    mut db_config: DbConfluenceConfig,
+    params: PublishParams,
+    provenance: &Provenance,
+) -> Result<ConfluenceClient> {
+    let allowed_space_keys = std::mem::take(&mut db_config.allowed_space_keys);
+    let config = params.apply(
+        confluence::ConfluenceConfig::from_db(db_config),
+        &allowed_space_keys,
+    )?;
+    let client = match state.clients.get(provenance.server_id, &config) {
+        Some(client) => client,
+        None => {
+            let resolved = config.clone().resolve_secrets(&state.secrets).await?;
+            let http = state.tls_clients.get(&state.http, &config.tls)?;
+            let store = RefreshTokenStore::for_target(
+                &config,
+                state.pool.clone(),
+                state.confluence_configs.clone(),
+                provenance.server_id,
+            );
+            let client = ConfluenceClient::new(resolved, http, state.coordinator.clone())
+                .with_refresh_token_store(store)
+                .with_mock(state.confluence_mock.clone())
+                    .resolve_parent()
+                    .await;
+            state.clients.insert(provenance.server_id, config, client.clone());
+            client
+        }
+    };
+    Ok(client.with_page_property(PROVENANCE_PROPERTY, serde_json::to_value(provenance)?))
+}
+
+/// Versioned mode: publish the snapshot as a new page under the index page, record it
+/// in the publication history, then regenerate the index from that history.
+async fn publish_versioned(
+    state: &AppState,
+    client: &ConfluenceClient,
+    snapshot: &SnapshotRow,
+    page_tree: &SnapshotPageTree,
+    titles: &SnapshotTitles,
+    ctx: &TitleContext<'_>,
+    job: &JobHandle,
+) -> Result<PublishOutcome> {
+    let title_templates = &titles.title_templates;
+    let server_id = snapshot.akeneo_server_id;
+    let index_title = title_templates.snapshot_title(ctx);
+    let version_title = title_templates.version_title(ctx);
+
+    // Make sure the index page exists so the new version can be nested under it
+    let history = db::fetch_publication_history(&state.pool, server_id).await?;
+    let index = client
+        .publish_page(&index_title, &titles.history_index(state, &history))
+        .await
+        .context("Failed to publish index page to Confluence")?;
+    info!("Index page '{}' ready (id={})", index_title, index.page_id);
+
+    let info = snapshot_info(snapshot, titles, Utc::now());
+    let root = RootPage {
+        title: &version_title,
+        info: &info,
+        banner: None,
+        parent_id: Some(&index.page_id),
+    };
+    let result = publish_tree(client, page_tree, root, title_templates, ctx, job).await?;
+
+    db::record_publication(
+        &state.pool,
+        NewPublication {
+            akeneo_server_id: server_id,
+            snapshot_id: snapshot.id,
+            diff_id: None,
+            kind: PublicationKind::Version,
+            page_id: &result.page_id,
+            page_title: &version_title,
+            web_url: &result.web_url,
+            alerts: serde_json::json!([]),
+        },
+    )
+    .await?;
+
+    let history = db::fetch_publication_history(&state.pool, server_id).await?;
+    client
+        .publish_page(&index_title, &titles.history_index(state, &history))
+        .await
+        .context("Failed to update index page in Confluence")?;
+    info!(
+        "Index page '{}' updated with {} publish(es)",
+        index_title,
+        history.len()
+    );
+
+    Ok(result)
+}
+
+/// The root page of a tree passed to `publish_tree`.
+struct RootPage<'a> {
+    title: &'a str,
+    /// The snapshot metadata panel, placed at the top of the page.
+    info: &'a str,
+    /// Content placed between the metadata panel and the rendered root body, such as the
+    /// changes banner.
+    banner: Option<&'a str>,
+    /// Publish under this page instead of the configured parent.
+    parent_id: Option<&'a str>,
+}
+
+/// The metadata panel for a snapshot's root page.
+fn snapshot_info(
+    snapshot: &SnapshotRow,
+    titles: &SnapshotTitles,
+    published_at: DateTime<Utc>,
+) -> String {
+    renderer::render_snapshot_info(&SnapshotInfo {
+        id: snapshot.id,
+        label: snapshot.label.as_deref(),
+        server_name: &titles.server_name,
+        started_at: snapshot.started_at,
+        completed_at: snapshot.completed_at,
+        published_at,
+        format: &titles.format,
+    })
+}
+
+/// Overwrite mode: the "changes since last publish" info panel for a root page, comparing
+/// the snapshot with the one last published to the same page. `None` on a first publish
+/// or when republishing the same snapshot.
+async fn changes_banner(
+    state: &AppState,
+    snapshot: &SnapshotRow,
+    titles: &SnapshotTitles,
+    root_title: &str,
+) -> Result<Option<String>> {
+    let Some(previous_id) =
+        db::fetch_last_overwrite_snapshot(&state.pool, snapshot.akeneo_server_id, root_title)
+            .await?
+    else {
+        return Ok(None);
+    };
+    if previous_id == snapshot.id {
+        return Ok(None);
+    }
+
+    let previous = db::fetch_snapshot(&state.pool, previous_id).await?;
+    let changes = diff::compare_snapshots(&previous.data, &snapshot.data);
+    let diff_url = db::fetch_published_diff_url(&state.pool, previous_id, snapshot.id).await?;
+
+    Ok(Some(renderer::render_changes_banner(
+        previous.label.as_deref(),
+        &changes,
+        diff_url.as_deref(),
+        &state.render_options.with_display_format(titles.format.clone()),
+    )))
+}
+
+/// The family pages' titles from the target's template. Families whose titles collide
+/// (Confluence titles are unique per space, ignoring case), e.g. two families sharing a
+/// label under a template without `{code}`, get their code appended so neither page
+/// overwrites the other; each collision is reported as a job warning.
+fn family_titles(
+    children: &[SnapshotChildPage],
+    title_templates: &TitleTemplates,
+    ctx: &TitleContext<'_>,
+    job: &JobHandle,
+) -> Vec<String> {
+    let mut titles: Vec<String> = children
+        .iter()
+        .map(|child| {
+            title_templates.family_title(&TitleContext {
+                label: &child.label,
+                code: &child.code,
+                ..*ctx
+            })
+        })
+        .collect();
+
+    let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, title) in titles.iter().enumerate() {
+        groups.entry(title.to_lowercase()).or_default().push(i);
+    }
+    for indices in groups.values().filter(|indices| indices.len() > 1) {
+        let codes: Vec<&str> = indices.iter().map(|&i| children[i].code.as_str()).collect();
+        let message = format!(
+            "Families {} share the page title '{}'; their codes were appended to tell the \
+             pages apart",
+            codes.join(", "),
+            titles[indices[0]]
+        );
+        warn!("{}", message);
+        job.warn(message);
+        for &i in indices {
+            titles[i] = format!("{} ({})", titles[i], children[i].code);
+        }
+    }
+    titles
+}
+
+/// The family groups of the target's `FamilyGrouping`, sorted by name (case-insensitive),
+/// each with the indices of its families in `children`; empty when families are not
+/// grouped.
+fn family_groups(
+    grouping: FamilyGrouping,
+    children: &[SnapshotChildPage],
+) -> Vec<(String, Vec<usize>)> {
+    let mut groups: BTreeMap<String, (String, Vec<usize>)> = BTreeMap::new();
+    for (i, child) in children.iter().enumerate() {
+        let name = match grouping {
+            FamilyGrouping::None => return Vec::new(),
+            FamilyGrouping::FirstLetter => match child.label.chars().next() {
+                Some(c) if c.is_alphabetic() => c.to_uppercase().collect(),
+                _ => "#".to_string(),
+            },
+            FamilyGrouping::LabelAttributeGroup => {
+                child.label_group.clone().unwrap_or_else(|| "Other".to_string())
+            }
+            FamilyGrouping::Prefix => child.code.split('_').next().unwrap_or_default().to_string(),
+        };
+        groups
+            .entry(name.to_lowercase())
+            .or_insert_with(|| (name, Vec::new()))
+            .1
+            .push(i);
+    }
+    groups.into_values().collect()
+}
+
+/// Move the family pages under `parent_id` (a root or family group page) if they are not
+/// there yet, in the target's `ChildPageOrder`; new pages are otherwise appended in
+/// creation order. Arranging is cosmetic, so a failure only warns.
+async fn arrange_family_pages(
+    client: &ConfluenceClient,
+    parent_id: &str,
+    mut pages: Vec<(&String, &SnapshotChildPage, String)>,
+) {
+    let order = client.config().child_page_order;
+    match order {
+        ChildPageOrder::None => {}
+        ChildPageOrder::Label => pages.sort_by_cached_key(|(_, child, _)| {
+            (child.label.to_lowercase(), child.code.clone())
+        }),
+        ChildPageOrder::Code => pages.sort_by(|(_, a, _), (_, b, _)| a.code.cmp(&b.code)),
+        ChildPageOrder::Title => pages.sort_by_cached_key(|(title, _, _)| title.to_lowercase()),
+    }
+    let ids: Vec<String> = pages.into_iter().map(|(_, _, id)| id).collect();
+    arrange_pages(client, parent_id, &ids, order != ChildPageOrder::None).await;
+}
+
+/// Log the outcome of `ConfluenceClient::arrange_child_pages`, which only warns on failure.
+async fn arrange_pages(client: &ConfluenceClient, parent_id: &str, ids: &[String], reorder: bool) {
+    match client.arrange_child_pages(parent_id, ids, reorder).await {
+        Ok(0) => {}
+        Ok(moved) => info!("Moved {} page(s) into place under page {}", moved, parent_id),
+        Err(e) => warn!("Failed to arrange the pages under page {}: {:#}", parent_id, e),
+    }
+}
+
+/// Publish a rendered page tree: the root page (under `parent_id` if given, else under
+/// the configured parent), each family child page (nested under a family group page per
+/// the target's `FamilyGrouping`) and the requirements coverage, model health, category
+/// tree and option pages under it, then remove stale children.
+async fn publish_tree(
+    client: &ConfluenceClient,
+    page_tree: &SnapshotPageTree,
+    root: RootPage<'_>,
+    title_templates: &TitleTemplates,
+    ctx: &TitleContext<'_>,
+    job: &JobHandle,
+) -> Result<PublishOutcome> {
+    let RootPage {
+        title: root_title,
+        info,
+        banner,
+        parent_id,
+    } = root;
+    let groups = family_groups(client.config().family_grouping, &page_tree.children);
+
+    let titles = family_titles(&page_tree.children, title_templates, ctx, job);
+    let children: Vec<_> = titles
+        .into_iter()
+        .zip(&page_tree.children)
+        .map(|(title, child)| {
+            // Versioned pages live side by side in one space, so their children need
+            // titles that are unique per version
+            let title = match parent_id {
+                Some(_) => format!("{} [{}]", title, root_title),
+                None => title,
+            };
+            (title, child)
+        })
+        .collect();
+    // The Families table links each family to its page
+    let root_body = renderer::link_family_titles(
+        &renderer::link_root_title(&page_tree.root_body, root_title),
+        children.iter().map(|(title, child)| (child.code.as_str(), title.as_str())),
+    );
+    let root_body = format!("{}{}{}", info, banner.unwrap_or_default(), root_body);
+    // Like the analysis pages' titles, group titles follow the root page's, so they are
+    // unique per version
+    let group_pages: Vec<_> = groups
+        .iter()
+        .map(|(name, indices)| {
+            (
+                format!("{}{}{}", root_title, FAMILY_GROUP_TITLE_SUFFIX, name),
+                renderer::render_family_group_page(name, indices.len()),
+                indices,
+            )
+        })
+        .collect();
+    // The analysis pages' titles follow the root page's, so they are already unique per
+    // version
+    let mut analysis_pages = vec![
+        (
+            format!("{}{}", root_title, COVERAGE_TITLE_SUFFIX),
+            &page_tree.coverage_body,
+        ),
+        (
+            format!("{}{}", root_title, HEALTH_TITLE_SUFFIX),
+            &page_tree.health_body,
+        ),
+    ];
+    if let Some(body) = &page_tree.labels_body {
+        analysis_pages.push((
+            format!("{}{}", root_title, LABELS_TITLE_SUFFIX),
+            body,
+        ));
+    }
+    analysis_pages.extend(page_tree.category_trees.iter().map(|tree| {
+        (
+            format!(
+                "{}{}{} ({})",
+                root_title, CATEGORY_TREE_TITLE_SUFFIX, tree.label, tree.code
+            ),
+            &tree.body,
+        )
+    }));
+    let option_pages: Vec<(String, String)> = page_tree
+        .option_pages
+        .iter()
+        .map(|page| {
+            (
+                option_page_title(root_title, &page.attribute, page.page, page.pages),
+                renderer::link_root_title(&page.body, root_title),
+            )
+        })
+        .collect();
+    analysis_pages.extend(option_pages.iter().map(|(title, body)| (title.clone(), body)));
+
+    // Groups, when families are grouped, hold every family page; the analysis pages
+    // follow the families under the root
+    let family_page = |i: usize| PageTree {
+        title: &children[i].0,
+        body: &children[i].1.body,
+        children: Vec::new(),
+    };
+    let mut subtrees: Vec<PageTree> = match group_pages.is_empty() {
+        true => (0..children.len()).map(family_page).collect(),
+        false => group_pages
+            .iter()
+            .map(|(title, body, indices)| PageTree {
+                title,
+                body,
+                children: indices.iter().map(|&i| family_page(i)).collect(),
+            })
+            .collect(),
+    };
+    subtrees.extend(analysis_pages.iter().map(|(title, body)| PageTree {
+        title,
+        body,
+        children: Vec::new(),
+    }));
+    let tree = PageTree {
+        title: root_title,
+        body: &root_body,
+        children: subtrees,
+    };
+    job.set_total(tree.page_count());
+
+    // Every body is size-checked before the first page is written, so an oversized page
+    // fails the publish rather than leaving a half-updated tree
+    let progress = |title: &str, result: Result<PageOperation, &anyhow::Error>| match result {
+        Ok(operation) => job.page_done(title, operation),
+        Err(e) => job.page_failed(title, e),
+    };
+    let published = client.publish_tree(&tree, parent_id, &progress).await?;
+    let root_result = &published.outcome;
+    let published_ids: HashSet<&str> = published.page_ids().into_iter().collect();
+
+    let family_subtrees = &published.children[..tree.children.len() - analysis_pages.len()];
+    if group_pages.is_empty() {
+        let pages = children
+            .iter()
+            .zip(family_subtrees)
+            .map(|((title, child), page)| (title, *child, page.outcome.page_id.clone()))
+            .collect();
+        arrange_family_pages(client, &root_result.page_id, pages).await;
+    } else {
+        let group_ids: Vec<String> =
+            family_subtrees.iter().map(|group| group.outcome.page_id.clone()).collect();
+        let reorder = client.config().child_page_order != ChildPageOrder::None;
+        arrange_pages(client, &root_result.page_id, &group_ids, reorder).await;
+        for ((_, _, indices), group) in group_pages.iter().zip(family_subtrees) {
+            let pages = indices
+                .iter()
+                .zip(&group.children)
+                .map(|(&i, page)| (&children[i].0, children[i].1, page.outcome.page_id.clone()))
+                .collect();
+            arrange_family_pages(client, &group.outcome.page_id, pages).await;
+        }
+    }
+
+    // Clean up stale child pages that no longer exist in the snapshot
+    match client.get_child_pages(&root_result.page_id).await {
+        Ok(existing_children) => {
+            let stale_children: Vec<_> = existing_children
+                .into_iter()
+                .filter(|child| !published_ids.contains(child.id.as_str()))
+                .collect();
+
+            if !stale_children.is_empty() {
+                info!(
+                    "Found {} stale child page(s) to remove",
+                    stale_children.len()
+                );
+            }
+
+            for stale in &stale_children {
+                match client.delete_page(&stale.id).await {
+                    Ok(()) => {
+                        info!(
+                            "Deleted stale child page '{}' (id={})",
+                            stale.title, stale.id
+                        );
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to delete stale child page '{}' (id={}): {:#}",
+                            stale.title, stale.id, e
+                        );
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            warn!(
+                "Failed to fetch existing child pages for stale cleanup: {:#}",
+                e
+            );
+        }
+    }
+
+    Ok(published.outcome)
+}
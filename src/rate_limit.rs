@@ -0,0 +1,156 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{
+        header::{AUTHORIZATION, RETRY_AFTER},
+        HeaderMap, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::metrics;
+use crate::{AppState, ErrorResponse};
+
+/// Number of tracked clients above which fully refilled buckets are dropped.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Paths that are never rate limited (Prometheus scrapes).
+const EXEMPT_PATHS: &[&str] = &["/metrics"];
+
+/// Per-client token-bucket rate limiter for the HTTP API.
+///
+/// Clients are identified by their API key (the `Authorization: Bearer` token or
+/// `X-Api-Key` header) when it is a recognised one (named in `API_KEY_NAMES`, or the admin
+/// token), otherwise by their IP address, so made-up keys do not buy fresh buckets. Each
+/// client may make `burst` requests at once, refilled at `per_minute` requests per minute.
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    per_minute: u32,
+    burst: u32,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    /// Build a limiter from the environment:
+    /// - `RATE_LIMIT_PER_MINUTE` — sustained requests per minute per client (default 120,
+    ///   `0` disables rate limiting)
+    /// - `RATE_LIMIT_BURST` — requests a client may make at once (default 20)
+    pub fn from_env() -> Self {
+        let per_minute = std::env::var("RATE_LIMIT_PER_MINUTE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(120);
+        let burst = std::env::var("RATE_LIMIT_BURST")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(20u32)
+            .max(1);
+
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            per_minute,
+            burst,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.per_minute > 0
+    }
+
+    /// Take a token from a client's bucket. Returns how long to wait when it is empty.
+    fn acquire(&self, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let per_second = f64::from(self.per_minute) / 60.0;
+        let burst = f64::from(self.burst);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // A bucket idle long enough to have refilled is the same as no bucket
+            let refill = Duration::from_secs_f64(burst / per_second);
+            buckets.retain(|_, b| now.duration_since(b.updated_at) < refill);
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(burst);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+}
+
+/// Middleware rejecting requests over the client's quota with 429 and `Retry-After`.
+pub async fn limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let limiter = &state.rate_limiter;
+    if !limiter.is_enabled() || EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let key_name = api_key(request.headers()).and_then(|key| state.audit.key_name(&state, key));
+    let client = match key_name {
+        Some(name) => format!("key:{}", name),
+        None => match peer {
+            Some(ip) => format!("ip:{}", ip),
+            None => "ip:unknown".to_string(),
+        },
+    };
+
+    match limiter.acquire(&client) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            metrics::record_rate_limited();
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            warn!(
+                "Rate limited {} {} from {} (retry after {}s)",
+                request.method(),
+                request.uri().path(),
+                match peer {
+                    Some(ip) => ip.to_string(),
+                    None => "unknown client".to_string(),
+                },
+                retry_after
+            );
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.to_string())],
+                Json(ErrorResponse::new(format!(
+                    "Rate limit of {} requests per minute exceeded; retry in {}s",
+                    limiter.per_minute, retry_after
+                ))),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// The API key a request is made with, if any.
+pub fn api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .filter(|key| !key.is_empty())
+}
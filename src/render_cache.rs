@@ -0,0 +1,243 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, NaiveTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::db::{self, SnapshotRow};
+use crate::render_options::{AttributeFilter, DisplayFormat, RenderOptions};
+use crate::renderer::{self, SnapshotPageTree};
+
+/// In-memory cache of rendered snapshot page trees, keyed by snapshot ID.
+///
+/// Snapshots are immutable once captured, so a rendered tree stays valid until it
+/// expires or is evicted, or its server's attribute filters, display format or attributes
+/// table layout change.
+/// Pre-rendering fills the cache ahead of a publish window, leaving only the Confluence
+/// API calls on the critical path.
+pub struct RenderCache {
+    entries: Mutex<HashMap<Uuid, CacheEntry>>,
+    max_entries: usize,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    next_prerender_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+struct CacheEntry {
+    tree: Arc<SnapshotPageTree>,
+    /// The attribute filters, display format and attributes table layout the tree was
+    /// rendered with.
+    filter: AttributeFilter,
+    format: DisplayFormat,
+    extended_attributes: bool,
+    inserted_at: Instant,
+}
+
+/// Point-in-time statistics about the render cache.
+pub struct RenderCacheStats {
+    pub entries: usize,
+    pub max_entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl RenderCache {
+    /// Build a cache from the environment:
+    /// - `RENDER_CACHE_MAX_ENTRIES` — maximum cached trees (default 16)
+    /// - `RENDER_CACHE_TTL_SECS` — how long a rendered tree is kept (default 86400)
+    pub fn from_env() -> Self {
+        let max_entries = std::env::var("RENDER_CACHE_MAX_ENTRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(16);
+        let ttl_secs = std::env::var("RENDER_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86_400);
+
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            max_entries,
+            ttl: Duration::from_secs(ttl_secs),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            next_prerender_at: Mutex::new(None),
+        }
+    }
+
+    /// Look up a tree rendered with the attribute filter, display format and attributes
+    /// table layout of `options`, discarding it if it has expired or was rendered with
+    /// other settings.
+    pub fn get(&self, snapshot_id: Uuid, options: &RenderOptions) -> Option<Arc<SnapshotPageTree>> {
+        let mut entries = self.entries.lock().unwrap();
+        let found = match entries.get(&snapshot_id) {
+            Some(entry)
+                if entry.inserted_at.elapsed() < self.ttl
+                    && entry.filter == options.attributes
+                    && entry.format == options.theme.format
+                    && entry.extended_attributes == options.tables.extended_attributes =>
+            {
+                Some(entry.tree.clone())
+            }
+            Some(_) => {
+                entries.remove(&snapshot_id);
+                None
+            }
+            None => None,
+        };
+
+        if found.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
+
+    /// The cached tree of a snapshot rendered with `options` (keyed by its attribute
+    /// filter, display format and attributes table layout), rendering and storing it on a
+    /// miss.
+    pub fn get_or_render(
+        &self,
+        snapshot: &SnapshotRow,
+        options: &RenderOptions,
+    ) -> Arc<SnapshotPageTree> {
+        if let Some(tree) = self.get(snapshot.id, options) {
+            info!("Using pre-rendered page tree for snapshot {}", snapshot.id);
+            return tree;
+        }
+        let tree = Arc::new(renderer::render_snapshot_pages(
+            snapshot.label.as_deref(),
+            &snapshot.data,
+            options,
+        ));
+        self.insert(snapshot.id, options, tree.clone());
+        tree
+    }
+
+    /// Store a tree rendered with `options`, evicting the oldest entry when the cache is
+    /// full.
+    pub fn insert(&self, snapshot_id: Uuid, options: &RenderOptions, tree: Arc<SnapshotPageTree>) {
+        if self.max_entries == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(&snapshot_id) && entries.len() >= self.max_entries {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.inserted_at)
+                .map(|(id, _)| *id);
+            if let Some(id) = oldest {
+                entries.remove(&id);
+            }
+        }
+
+        entries.insert(
+            snapshot_id,
+            CacheEntry {
+                tree,
+                filter: options.attributes.clone(),
+                format: options.theme.format.clone(),
+                extended_attributes: options.tables.extended_attributes,
+                inserted_at: Instant::now(),
+            },
+        );
+    }
+
+    /// When the nightly pre-render task will next run, if it is enabled.
+    pub fn next_prerender_at(&self) -> Option<DateTime<Utc>> {
+        *self.next_prerender_at.lock().unwrap()
+    }
+
+    pub fn stats(&self) -> RenderCacheStats {
+        RenderCacheStats {
+            entries: self.entries.lock().unwrap().len(),
+            max_entries: self.max_entries,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Fetch a snapshot, render its page tree with its server's attribute filters, display
+/// format and attributes table layout off the async executor, and store it in the cache.
+/// Returns the number of pages in the rendered tree.
+pub async fn prerender_snapshot(
+    pool: &PgPool,
+    cache: &RenderCache,
+    options: &Arc<RenderOptions>,
+    snapshot_id: Uuid,
+) -> Result<usize> {
+    let snapshot = db::fetch_snapshot(pool, snapshot_id).await?;
+    let options = match db::fetch_render_rules(pool, snapshot.akeneo_server_id).await? {
+        Some(rules) => options
+            .with_attribute_filter(AttributeFilter::from_rules(&rules)?)
+            .with_display_format(DisplayFormat::from_rules(&rules)?)
+            .with_attribute_table(rules.attribute_table.as_deref()),
+        None => options.as_ref().clone(),
+    };
+
+    let render_options = options.clone();
+    let tree = tokio::task::spawn_blocking(move || {
+        renderer::render_snapshot_pages(snapshot.label.as_deref(), &snapshot.data, &render_options)
+    })
+    .await
+    .context("Render task panicked")?;
+
+    let page_count = tree.page_count();
+    cache.insert(snapshot_id, &options, Arc::new(tree));
+    Ok(page_count)
+}
+
+/// Spawn a background task that pre-renders the latest snapshot of every Akeneo
+/// server once a day at `PRERENDER_AT` (UTC, `HH:MM`), ahead of the nightly publishes.
+/// Does nothing when `PRERENDER_AT` is unset.
+pub fn spawn_nightly_prerender(
+    pool: PgPool,
+    cache: Arc<RenderCache>,
+    options: Arc<RenderOptions>,
+) -> Result<()> {
+    let Ok(at) = std::env::var("PRERENDER_AT") else {
+        return Ok(());
+    };
+    let at = NaiveTime::parse_from_str(&at, "%H:%M")
+        .with_context(|| format!("PRERENDER_AT must be HH:MM, got '{}'", at))?;
+
+    info!("Nightly pre-render enabled at {} UTC", at.format("%H:%M"));
+
+    tokio::spawn(async move {
+        loop {
+            let now = Utc::now();
+            let mut next = now.date_naive().and_time(at).and_utc();
+            if next <= now {
+                next += ChronoDuration::days(1);
+            }
+            *cache.next_prerender_at.lock().unwrap() = Some(next);
+            let wait = (next - now).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+
+            let ids = match db::fetch_latest_snapshot_ids(&pool).await {
+                Ok(ids) => ids,
+                Err(e) => {
+                    error!("Pre-render: failed to list latest snapshots: {:#}", e);
+                    continue;
+                }
+            };
+
+            for id in ids {
+                match prerender_snapshot(&pool, &cache, &options, id).await {
+                    Ok(pages) => info!("Pre-rendered snapshot {} ({} pages)", id, pages),
+                    Err(e) => error!("Pre-render of snapshot {} failed: {:#}", id, e),
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
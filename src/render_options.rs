@@ -0,0 +1,1086 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
+
+use crate::config::AppConfig;
+use crate::db::{DbConfluenceConfig, RenderRules};
+
+/// Options that control how snapshot pages are rendered.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    pub expand: ExpandOptions,
+    pub theme: Theme,
+    pub diff: DiffOptions,
+    pub tables: TableOptions,
+    pub category_diagrams: CategoryDiagrams,
+    /// Publish the label completeness page (missing labels per entity type and locale).
+    pub label_completeness: bool,
+    /// The Confluence target's attribute filters; never set from the environment.
+    pub attributes: AttributeFilter,
+}
+
+/// Diff page configuration.
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// List readable change descriptions (`diff::describe_changes`) above each category's
+    /// tables.
+    pub narrative: bool,
+    /// Change shares above which a diff raises an alert (`diff::threshold_alerts`).
+    pub alert_thresholds: Vec<AlertThreshold>,
+    /// Published diffs charted on the model activity page; `0` publishes no such page.
+    pub activity_diffs: usize,
+    /// How the Changed tables list changes.
+    pub changed_grouping: ChangedGrouping,
+    /// Fields that identify changed items (`diff::parse_diff_data`).
+    pub identity_keys: IdentityKeys,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            narrative: true,
+            alert_thresholds: vec![AlertThreshold {
+                category: "*".to_string(),
+                kind: AlertKind::Removed,
+                percent: DEFAULT_ALERT_PERCENT,
+            }],
+            activity_diffs: DEFAULT_ACTIVITY_DIFFS,
+            changed_grouping: ChangedGrouping::default(),
+            identity_keys: IdentityKeys::default(),
+        }
+    }
+}
+
+/// Fields a changed diff item is identified by, tried in order after a category's own
+/// `DIFF_IDENTITY_KEYS`.
+pub const DEFAULT_IDENTITY_KEYS: &[&str] = &["code", "identifier", "uuid"];
+
+/// Per-category identity keys of changed diff items, e.g. `locale` for locales or
+/// `uuid` for products. A category's keys are tried before `DEFAULT_IDENTITY_KEYS`.
+#[derive(Debug, Clone, Default)]
+pub struct IdentityKeys {
+    categories: HashMap<String, Vec<String>>,
+}
+
+impl IdentityKeys {
+    /// Parse `category=key|key,...`, e.g. `products=uuid|identifier,locales=locale`.
+    fn parse(value: &str) -> Self {
+        let mut categories = HashMap::new();
+        for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((category, keys)) = entry.split_once('=') else {
+                warn!("Ignoring malformed DIFF_IDENTITY_KEYS entry '{}'", entry);
+                continue;
+            };
+            let keys: Vec<String> = keys
+                .split('|')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .map(str::to_string)
+                .collect();
+            if keys.is_empty() {
+                warn!("Ignoring DIFF_IDENTITY_KEYS entry '{}' without keys", entry);
+                continue;
+            }
+            categories.insert(category.trim().to_lowercase(), keys);
+        }
+        Self { categories }
+    }
+
+    /// The keys of a category's changed items, in the order they are tried.
+    pub fn chain(&self, category: &str) -> Vec<&str> {
+        let mut chain: Vec<&str> = self
+            .categories
+            .get(&category.to_lowercase())
+            .map(|keys| keys.iter().map(String::as_str).collect())
+            .unwrap_or_default();
+        for key in DEFAULT_IDENTITY_KEYS {
+            if !chain.contains(key) {
+                chain.push(key);
+            }
+        }
+        chain
+    }
+}
+
+/// How a diff page's Changed tables list changes: `DIFF_CHANGED_GROUPING`, or a request's
+/// `group_changes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangedGrouping {
+    /// One row per changed field of each item.
+    #[default]
+    Item,
+    /// One row per field path and the number of items it changed on, with their old and
+    /// new values in a collapsed expand macro: a label corrected on 200 attributes takes
+    /// one row.
+    Field,
+}
+
+impl ChangedGrouping {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "item" => Some(Self::Item),
+            "field" => Some(Self::Field),
+            _ => None,
+        }
+    }
+}
+
+/// Share of a category's items removed above which a diff raises an alert unless
+/// `DIFF_ALERT_THRESHOLDS` says otherwise: removing most of a category usually means a
+/// broken export, not a real model change.
+pub const DEFAULT_ALERT_PERCENT: f64 = 80.0;
+
+/// Published diffs the model activity page charts unless `MODEL_ACTIVITY_DIFFS` says
+/// otherwise.
+pub const DEFAULT_ACTIVITY_DIFFS: usize = 10;
+
+/// Which items of a category an alert threshold counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl AlertKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertKind::Added => "added",
+            AlertKind::Removed => "removed",
+            AlertKind::Changed => "changed",
+        }
+    }
+}
+
+/// Alert when `kind` items make up at least `percent` of the category's items in the
+/// before-snapshot.
+#[derive(Debug, Clone)]
+pub struct AlertThreshold {
+    /// Diff category (`attributes`, `families`, ...), or `*` for every category without a
+    /// threshold of its own for `kind`.
+    pub category: String,
+    pub kind: AlertKind,
+    pub percent: f64,
+}
+
+impl AlertThreshold {
+    /// Parse `category[.kind]=percent`, e.g. `attributes.removed=50` or `*=80` (the kind
+    /// defaults to `removed`).
+    fn parse(entry: &str) -> Option<Self> {
+        let (target, percent) = entry.split_once('=')?;
+        let (category, kind) = target.trim().split_once('.').unwrap_or((target.trim(), "removed"));
+        let kind = match kind {
+            "added" => AlertKind::Added,
+            "removed" => AlertKind::Removed,
+            "changed" => AlertKind::Changed,
+            _ => return None,
+        };
+        let percent: f64 = percent.trim().trim_end_matches('%').parse().ok()?;
+        if category.is_empty() || !percent.is_finite() || percent <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            category: category.to_string(),
+            kind,
+            percent,
+        })
+    }
+}
+
+/// Which attributes snapshot pages document, from the `attribute_include` /
+/// `attribute_exclude` rules of a server's `confluence_config`. An attribute is shown when
+/// it matches an include rule (or there are none) and no exclude rule. Applies to the
+/// attributes section, family attribute tables and attribute options.
+#[derive(Debug, Clone, Default)]
+pub struct AttributeFilter {
+    include: Vec<AttributeRule>,
+    exclude: Vec<AttributeRule>,
+}
+
+/// One filter rule, kept with the text it was parsed from.
+#[derive(Debug, Clone)]
+struct AttributeRule {
+    source: String,
+    matcher: RuleMatcher,
+}
+
+#[derive(Debug, Clone)]
+enum RuleMatcher {
+    Group(String),
+    Prefix(String),
+    Regex(Regex),
+}
+
+/// Filters are equal when their rules are, so a cached render can be reused for them.
+impl PartialEq for AttributeFilter {
+    fn eq(&self, other: &Self) -> bool {
+        let same = |a: &[AttributeRule], b: &[AttributeRule]| {
+            a.iter().map(|r| &r.source).eq(b.iter().map(|r| &r.source))
+        };
+        same(&self.include, &other.include) && same(&self.exclude, &other.exclude)
+    }
+}
+
+impl AttributeFilter {
+    /// The target's filters. Fails on a malformed rule rather than documenting the
+    /// attributes it was meant to hide.
+    pub fn from_db(config: &DbConfluenceConfig) -> Result<Self> {
+        Self::parse(&config.attribute_include, &config.attribute_exclude)
+    }
+
+    pub fn from_rules(rules: &RenderRules) -> Result<Self> {
+        Self::parse(&rules.attribute_include, &rules.attribute_exclude)
+    }
+
+    pub fn parse(include: &[String], exclude: &[String]) -> Result<Self> {
+        let parse_all = |rules: &[String]| {
+            rules
+                .iter()
+                .map(|rule| AttributeRule::parse(rule))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: parse_all(include).context("Invalid attribute_include rule")?,
+            exclude: parse_all(exclude).context("Invalid attribute_exclude rule")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether an attribute is documented. `group` is `None` for codes the snapshot has no
+    /// attribute for, which only code rules can match.
+    pub fn keeps(&self, code: &str, group: Option<&str>) -> bool {
+        let matches = |rule: &AttributeRule| match &rule.matcher {
+            RuleMatcher::Group(wanted) => group == Some(wanted.as_str()),
+            RuleMatcher::Prefix(prefix) => code.starts_with(prefix.as_str()),
+            RuleMatcher::Regex(regex) => regex.is_match(code),
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+impl AttributeRule {
+    /// Parse `group:<code>`, `prefix:<prefix>` or `regex:<pattern>`.
+    fn parse(rule: &str) -> Result<Self> {
+        let (kind, value) = rule.split_once(':').unwrap_or((rule, ""));
+        let value = value.trim();
+        if value.is_empty() {
+            bail!("'{}': expected group:<code>, prefix:<prefix> or regex:<pattern>", rule);
+        }
+        let matcher = match kind.trim() {
+            "group" => RuleMatcher::Group(value.to_string()),
+            "prefix" => RuleMatcher::Prefix(value.to_string()),
+            "regex" => RuleMatcher::Regex(
+                Regex::new(value).with_context(|| format!("'{}': invalid pattern", rule))?,
+            ),
+            other => bail!(
+                "'{}': unknown rule '{}', expected group, prefix or regex",
+                rule,
+                other
+            ),
+        };
+        Ok(Self {
+            source: rule.to_string(),
+            matcher,
+        })
+    }
+}
+
+/// Locales `display_locale` accepts (case-insensitive, `_` for `-` too).
+pub const DISPLAY_LOCALES: &[&str] = &["en-US", "en-GB", "de-DE", "fr-FR", "nl-NL", "sv-SE"];
+
+/// How counts and timestamps read on rendered pages, from the `display_locale` /
+/// `display_timezone` columns of a server's `confluence_config`. Without either, pages show
+/// ungrouped counts and `2026-10-14 08:00 UTC` timestamps; a locale adds digit grouping and
+/// its date and time order, a time zone (IANA, e.g. `Europe/Berlin`) converts timestamps
+/// and names the zone by its abbreviation. Page titles keep their ISO `{date}` / `{time}`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayFormat {
+    locale: Option<DisplayLocale>,
+    timezone: Option<Tz>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DisplayLocale {
+    EnUs,
+    EnGb,
+    DeDe,
+    FrFr,
+    NlNl,
+    SvSe,
+}
+
+impl DisplayLocale {
+    fn parse(value: &str) -> Option<Self> {
+        let locales = [Self::EnUs, Self::EnGb, Self::DeDe, Self::FrFr, Self::NlNl, Self::SvSe];
+        let value = value.replace('_', "-");
+        DISPLAY_LOCALES
+            .iter()
+            .position(|locale| locale.eq_ignore_ascii_case(&value))
+            .map(|i| locales[i])
+    }
+
+    fn group_separator(self) -> &'static str {
+        match self {
+            Self::EnUs | Self::EnGb => ",",
+            Self::DeDe | Self::NlNl => ".",
+            // Narrow and regular no-break spaces, so a count never wraps
+            Self::FrFr => "\u{202F}",
+            Self::SvSe => "\u{A0}",
+        }
+    }
+
+    fn date_pattern(self) -> &'static str {
+        match self {
+            Self::EnUs => "%m/%d/%Y",
+            Self::EnGb | Self::FrFr => "%d/%m/%Y",
+            Self::DeDe => "%d.%m.%Y",
+            Self::NlNl => "%d-%m-%Y",
+            Self::SvSe => "%Y-%m-%d",
+        }
+    }
+
+    fn time_pattern(self, seconds: bool) -> &'static str {
+        match (self, seconds) {
+            (Self::EnUs, false) => "%-I:%M %p",
+            (Self::EnUs, true) => "%-I:%M:%S %p",
+            (_, false) => "%H:%M",
+            (_, true) => "%H:%M:%S",
+        }
+    }
+}
+
+impl DisplayFormat {
+    /// The target's format. Fails on an unknown locale or time zone rather than guessing.
+    pub fn from_db(config: &DbConfluenceConfig) -> Result<Self> {
+        Self::parse(config.display_locale.as_deref(), config.display_timezone.as_deref())
+    }
+
+    pub fn from_rules(rules: &RenderRules) -> Result<Self> {
+        Self::parse(rules.display_locale.as_deref(), rules.display_timezone.as_deref())
+    }
+
+    pub fn parse(locale: Option<&str>, timezone: Option<&str>) -> Result<Self> {
+        fn set(value: Option<&str>) -> Option<&str> {
+            value.map(str::trim).filter(|v| !v.is_empty())
+        }
+        let locale = match set(locale) {
+            Some(locale) => Some(DisplayLocale::parse(locale).with_context(|| {
+                format!(
+                    "Invalid display_locale '{}': expected one of {}",
+                    locale,
+                    DISPLAY_LOCALES.join(", ")
+                )
+            })?),
+            None => None,
+        };
+        let timezone = match set(timezone) {
+            Some(timezone) => Some(timezone.parse::<Tz>().map_err(|_| {
+                anyhow!(
+                    "Invalid display_timezone '{}': expected an IANA time zone, e.g. Europe/Berlin",
+                    timezone
+                )
+            })?),
+            None => None,
+        };
+        Ok(Self { locale, timezone })
+    }
+
+    /// A count with the locale's digit grouping, e.g. `12.345` for `de-DE`.
+    pub fn count(&self, count: usize) -> String {
+        let digits = count.to_string();
+        let Some(locale) = self.locale else {
+            return digits;
+        };
+        let mut out = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                out.push_str(locale.group_separator());
+            }
+            out.push(digit);
+        }
+        out
+    }
+
+    /// The date of a timestamp in the target's time zone.
+    pub fn date(&self, at: DateTime<Utc>) -> String {
+        self.format(at, self.locale.map_or("%Y-%m-%d", DisplayLocale::date_pattern))
+    }
+
+    /// Date, time and zone, e.g. `10/14/2026 10:00 AM CEST`; `seconds` adds the seconds.
+    pub fn date_time(&self, at: DateTime<Utc>, seconds: bool) -> String {
+        let time = match self.locale {
+            Some(locale) => locale.time_pattern(seconds),
+            None if seconds => "%H:%M:%S",
+            None => "%H:%M",
+        };
+        let date = self.locale.map_or("%Y-%m-%d", DisplayLocale::date_pattern);
+        self.format(at, &format!("{} {} %Z", date, time))
+    }
+
+    /// An RFC 3339 timestamp from the snapshot data (e.g. a category's `updated`) as
+    /// `date_time`; left as it is without a locale or time zone, or when it does not parse.
+    pub fn timestamp(&self, value: &str) -> String {
+        if *self == Self::default() {
+            return value.to_string();
+        }
+        match DateTime::parse_from_rfc3339(value) {
+            Ok(at) => self.date_time(at.with_timezone(&Utc), false),
+            Err(_) => value.to_string(),
+        }
+    }
+
+    fn format(&self, at: DateTime<Utc>, pattern: &str) -> String {
+        match self.timezone {
+            Some(timezone) => at.with_timezone(&timezone).format(pattern).to_string(),
+            None => at.format(pattern).to_string(),
+        }
+    }
+}
+
+/// Columns of the attributes table unless `ATTRIBUTE_COLUMNS` says otherwise.
+pub const DEFAULT_ATTRIBUTE_COLUMNS: &[&str] =
+    &["code", "label", "type", "group", "scopable", "localizable"];
+
+/// Cell length above which values (long option labels, validation regexes, ...) are cut
+/// off unless `CELL_MAX_LENGTH` says otherwise.
+pub const DEFAULT_MAX_CELL_LENGTH: usize = 120;
+
+/// Options of an attribute above which they move from the root page onto option pages
+/// unless `OPTION_PAGE_THRESHOLD` says otherwise.
+pub const DEFAULT_OPTION_PAGE_THRESHOLD: usize = 1000;
+
+/// Options listed per option page unless `OPTION_PAGE_SIZE` says otherwise.
+pub const DEFAULT_OPTION_PAGE_SIZE: usize = 500;
+
+/// Columns and row order of the snapshot attributes table and the diff added / removed
+/// tables, and how long a text cell of any snapshot or diff table may get.
+#[derive(Debug, Clone)]
+pub struct TableOptions {
+    /// Attributes table columns, in order: `code`, `label`, `type`, `group`, `scopable`,
+    /// `localizable`, `unique`, `constraints` (a summary of the validation settings), or any
+    /// other attribute field (e.g. `useable_as_grid_filter`, `allowed_extensions`).
+    pub attribute_columns: Vec<String>,
+    /// Extended attribute tables: the attributes table gets a `constraints` column when
+    /// its columns leave it out, and family pages list each attribute's constraints.
+    pub extended_attributes: bool,
+    /// Item fields shown in diff added / removed tables, in order (`label` is the first
+    /// label). Empty shows every notable field.
+    pub item_columns: Vec<String>,
+    /// The Confluence target's field lists and per-category columns of those tables; never
+    /// set from the environment.
+    pub item_properties: ItemProperties,
+    /// Row order of those tables; `None` keeps the order of the snapshot or diff.
+    pub sort: Option<SortOrder>,
+    /// Characters of a text cell shown before it is cut off, with the full value in an
+    /// expand macro below; `None` never cuts.
+    pub max_cell_length: Option<usize>,
+    /// Follow each added / removed item, family page and family attribute row with its
+    /// raw JSON in a collapsed expand macro, for debugging the rendering.
+    pub raw_json: bool,
+    /// Options of an attribute above which its options table moves onto child pages of
+    /// `option_page_size` rows each, linked from the root page; `0` keeps every table on
+    /// the root page.
+    pub option_page_threshold: usize,
+    pub option_page_size: usize,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            attribute_columns: DEFAULT_ATTRIBUTE_COLUMNS.iter().map(|c| c.to_string()).collect(),
+            extended_attributes: false,
+            item_columns: Vec::new(),
+            item_properties: ItemProperties::default(),
+            sort: None,
+            max_cell_length: Some(DEFAULT_MAX_CELL_LENGTH),
+            raw_json: false,
+            option_page_threshold: DEFAULT_OPTION_PAGE_THRESHOLD,
+            option_page_size: DEFAULT_OPTION_PAGE_SIZE,
+        }
+    }
+}
+
+/// Fields shown first in the diff added / removed tables without configured columns,
+/// unless a target's `item_priority_fields` says otherwise.
+pub const DEFAULT_ITEM_PRIORITY_FIELDS: &[&str] = &["code", "type", "group"];
+
+/// Fields those tables leave out, unless a target's `item_skip_fields` says otherwise:
+/// nested data and attribute settings that rarely tell added items apart.
+pub const DEFAULT_ITEM_SKIP_FIELDS: &[&str] = &[
+    "group_labels",
+    "attributes",
+    "decimal_places",
+    "default_value",
+    "display_time",
+    "is_read_only",
+    "max_characters",
+    "max_file_size",
+    "max_items_count",
+    "minimum_input_length",
+    "number_max",
+    "number_min",
+    "reference_data_name",
+    "validation_rule",
+];
+
+/// Which fields of an added / removed item `diff::extract_item_properties` shows, and the
+/// columns of single categories' tables, from a target's `item_priority_fields`,
+/// `item_skip_fields` and `item_columns`.
+#[derive(Debug, Clone)]
+pub struct ItemProperties {
+    /// Shown first, in order; `labels` follow as one `label (<locale>)` column per locale.
+    pub priority_fields: Vec<String>,
+    /// Never shown; `labels` here leaves the label columns out.
+    pub skip_fields: Vec<String>,
+    /// Columns of one category's tables (keyed by lowercase category name), in place of
+    /// `TableOptions.item_columns`.
+    pub category_columns: HashMap<String, Vec<String>>,
+}
+
+impl Default for ItemProperties {
+    fn default() -> Self {
+        let owned = |fields: &[&str]| fields.iter().map(|f| f.to_string()).collect();
+        Self {
+            priority_fields: owned(DEFAULT_ITEM_PRIORITY_FIELDS),
+            skip_fields: owned(DEFAULT_ITEM_SKIP_FIELDS),
+            category_columns: HashMap::new(),
+        }
+    }
+}
+
+impl ItemProperties {
+    /// The target's lists. Fails on an `item_columns` that is not an object of column
+    /// lists.
+    pub fn from_db(config: &DbConfluenceConfig) -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            priority_fields: config
+                .item_priority_fields
+                .clone()
+                .unwrap_or(defaults.priority_fields),
+            skip_fields: config.item_skip_fields.clone().unwrap_or(defaults.skip_fields),
+            category_columns: Self::parse_columns(&config.item_columns)?,
+        })
+    }
+
+    /// The per-category columns of an `item_columns` value, keyed by lowercase category
+    /// name. Fails when it is not an object of column lists.
+    pub fn parse_columns(
+        item_columns: &serde_json::Value,
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let columns = item_columns
+            .as_object()
+            .ok_or_else(|| anyhow!("Invalid item_columns: expected an object of column lists"))?;
+        let mut category_columns = HashMap::new();
+        for (category, list) in columns {
+            let list: Option<Vec<&str>> = list
+                .as_array()
+                .and_then(|list| list.iter().map(serde_json::Value::as_str).collect());
+            let Some(list) = list else {
+                bail!("Invalid item_columns for '{}': expected a list of columns", category);
+            };
+            let list: Vec<String> = list
+                .into_iter()
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect();
+            if !list.is_empty() {
+                category_columns.insert(category.to_lowercase(), list);
+            }
+        }
+        Ok(category_columns)
+    }
+
+    /// The columns of a category's tables: its own, else `item_columns`.
+    pub fn columns<'a>(&'a self, category: &str, item_columns: &'a [String]) -> &'a [String] {
+        self.category_columns
+            .get(&category.to_lowercase())
+            .map_or(item_columns, Vec::as_slice)
+    }
+}
+
+/// Sort table rows by one column.
+#[derive(Debug, Clone)]
+pub struct SortOrder {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl SortOrder {
+    /// Parse `column`, `column:asc` or `column:desc`. `None` when empty.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (column, direction) = value.split_once(':').unwrap_or((value, "asc"));
+        let column = column.trim();
+        if column.is_empty() {
+            return None;
+        }
+        let descending = match direction.trim() {
+            "desc" => true,
+            "asc" | "" => false,
+            other => {
+                warn!("Unknown sort direction '{}', sorting ascending", other);
+                false
+            }
+        };
+        Some(Self {
+            column: column.to_string(),
+            descending,
+        })
+    }
+}
+
+/// Split a comma-separated column list.
+fn parse_columns(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Node count above which a category tree is listed as a table instead of drawn, unless
+/// `CATEGORY_DIAGRAM_MAX_NODES` says otherwise: larger diagrams become unreadable.
+pub const DEFAULT_DIAGRAM_MAX_NODES: usize = 150;
+
+/// Diagram language of the category tree pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagramFormat {
+    /// A Mermaid flowchart.
+    Mermaid,
+    /// A PlantUML work breakdown structure.
+    PlantUml,
+}
+
+impl DiagramFormat {
+    /// Name of the Confluence macro the diagram source goes into unless
+    /// `CATEGORY_DIAGRAM_MACRO` says otherwise.
+    fn default_macro(self) -> &'static str {
+        match self {
+            DiagramFormat::Mermaid => "mermaid",
+            DiagramFormat::PlantUml => "plantuml",
+        }
+    }
+}
+
+/// Per-tree category diagram pages. Confluence Cloud has no diagram macros of its own, so
+/// these need a Mermaid or PlantUML app installed in the site.
+#[derive(Debug, Clone)]
+pub struct CategoryDiagrams {
+    /// `None` publishes no category tree pages.
+    pub format: Option<DiagramFormat>,
+    /// Macro the diagram source is placed in, e.g. `mermaid-cloud` for apps that name it
+    /// differently.
+    pub macro_name: String,
+    /// Trees with more categories are listed as a table instead.
+    pub max_nodes: usize,
+}
+
+impl Default for CategoryDiagrams {
+    fn default() -> Self {
+        Self {
+            format: None,
+            macro_name: String::new(),
+            max_nodes: DEFAULT_DIAGRAM_MAX_NODES,
+        }
+    }
+}
+
+/// Theme presets, by `Theme::named`.
+pub const THEMES: &[&str] = &["default", "plain"];
+
+/// Lozenge colours supported by Confluence's status macro.
+const LOZENGE_COLORS: &[&str] = &["Grey", "Red", "Yellow", "Green", "Blue", "Purple"];
+
+/// How boolean cells are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanStyle {
+    /// ✅ / ❌ emoji.
+    Emoji,
+    /// Plain `Yes` / `No`, for corporate styles and PDF exports that drop emoji.
+    Text,
+}
+
+/// Visual style of rendered pages (snapshot, diff and index pages alike).
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub booleans: BooleanStyle,
+    /// Show an emoji icon above each summary card count.
+    pub card_icons: bool,
+    /// Lozenge colour replacements, keyed by the built-in colour (e.g. `Green` -> `Blue`).
+    pub palette: HashMap<String, String>,
+    /// The Confluence target's count and timestamp format; never set from the environment.
+    pub format: DisplayFormat,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            booleans: BooleanStyle::Emoji,
+            card_icons: true,
+            palette: HashMap::new(),
+            format: DisplayFormat::default(),
+        }
+    }
+}
+
+impl Theme {
+    /// A preset of `THEMES` by name.
+    fn named(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            "plain" => Some(Self::plain()),
+            _ => None,
+        }
+    }
+
+    /// Emoji-free preset: text booleans and no card icons.
+    fn plain() -> Self {
+        Self {
+            booleans: BooleanStyle::Text,
+            card_icons: false,
+            palette: HashMap::new(),
+            format: DisplayFormat::default(),
+        }
+    }
+
+    /// The lozenge colour to use in place of a built-in one.
+    pub fn color<'a>(&'a self, color: &'a str) -> &'a str {
+        self.palette.get(color).map(String::as_str).unwrap_or(color)
+    }
+}
+
+/// When a category section's table is wrapped in a collapsed Confluence expand macro.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpandMode {
+    /// Collapse only when the section has more rows than the threshold.
+    Auto,
+    /// Always collapse the section.
+    Always,
+    /// Never collapse the section.
+    Never,
+}
+
+/// Per-section expand configuration for the snapshot root page.
+#[derive(Debug, Clone)]
+pub struct ExpandOptions {
+    /// Row count above which `Auto` sections are collapsed.
+    pub threshold: usize,
+    /// Section key (`channels`, `families`, `attributes`, `categories`,
+    /// `attribute_options`) -> mode. Sections not listed use `Auto`.
+    pub sections: HashMap<String, ExpandMode>,
+}
+
+impl Default for ExpandOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 50,
+            sections: HashMap::new(),
+        }
+    }
+}
+
+impl ExpandOptions {
+    /// Whether a section with `rows` rows should be collapsed.
+    pub fn should_collapse(&self, section: &str, rows: usize) -> bool {
+        match self.sections.get(section).copied().unwrap_or(ExpandMode::Auto) {
+            ExpandMode::Auto => rows > self.threshold,
+            ExpandMode::Always => true,
+            ExpandMode::Never => false,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Load render options from the startup configuration (`THEME`, `DIFF_NARRATIVE` and
+    /// `LABEL_COMPLETENESS`, see `config::AppConfig`) and the environment:
+    /// - `EXPAND_THRESHOLD` — rows above which sections collapse (default 50)
+    /// - `EXPAND_SECTIONS` — per-section overrides, e.g. `attributes=always,channels=never`
+    /// - `THEME_BOOLEANS` — `emoji` or `text`, overriding the theme
+    /// - `THEME_CARD_ICONS` — `true` or `false`, overriding the theme
+    /// - `THEME_LOZENGE_COLORS` — colour replacements, e.g. `Green=Blue,Yellow=Grey`
+    /// - `DIFF_ALERT_THRESHOLDS` — alert thresholds, e.g. `attributes.removed=50,*=80`
+    ///   (default `*.removed=80`, empty disables alerts)
+    /// - `MODEL_ACTIVITY_DIFFS` — published diffs charted on the model activity page
+    ///   (default 10, `0` disables the page)
+    /// - `DIFF_CHANGED_GROUPING` — `item` (default) or `field`, see `ChangedGrouping`
+    /// - `DIFF_IDENTITY_KEYS` — per-category keys of changed items, e.g.
+    ///   `products=uuid|identifier` (default `code`, then `identifier`, then `uuid`)
+    /// - `ATTRIBUTE_COLUMNS` — attributes table columns, e.g. `code,label,type,unique`
+    /// - `ATTRIBUTE_TABLE` — `extended` to add attribute constraints to the attributes
+    ///   table and family pages of targets without an `attribute_table` (default `basic`)
+    /// - `ITEM_COLUMNS` — diff added / removed table columns (default: every notable field)
+    /// - `TABLE_SORT` — row order of those tables, e.g. `group` or `code:desc`
+    /// - `CELL_MAX_LENGTH` — characters of a table cell shown before it is cut off
+    ///   (default 120, `0` never cuts)
+    /// - `OPTION_PAGE_THRESHOLD` — options of an attribute above which they are listed on
+    ///   option pages instead of the root page (default 1000, `0` never)
+    /// - `OPTION_PAGE_SIZE` — options per option page (default 500)
+    /// - `CATEGORY_DIAGRAMS` — `mermaid` or `plantuml` to publish a diagram page per
+    ///   category tree (default: none)
+    /// - `CATEGORY_DIAGRAM_MACRO` — macro the diagram goes into (default `mermaid` /
+    ///   `plantuml`)
+    /// - `CATEGORY_DIAGRAM_MAX_NODES` — categories above which a tree is listed as a table
+    ///   instead (default 150)
+    pub fn from_env(config: &AppConfig) -> Self {
+        let mut expand = ExpandOptions::default();
+
+        if let Some(threshold) = std::env::var("EXPAND_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            expand.threshold = threshold;
+        }
+
+        if let Ok(sections) = std::env::var("EXPAND_SECTIONS") {
+            for entry in sections.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let Some((section, mode)) = entry.split_once('=') else {
+                    warn!("Ignoring malformed EXPAND_SECTIONS entry '{}'", entry);
+                    continue;
+                };
+                let mode = match mode.trim() {
+                    "auto" => ExpandMode::Auto,
+                    "always" => ExpandMode::Always,
+                    "never" => ExpandMode::Never,
+                    other => {
+                        warn!("Ignoring unknown expand mode '{}' for '{}'", other, section);
+                        continue;
+                    }
+                };
+                expand.sections.insert(section.trim().to_string(), mode);
+            }
+        }
+
+        let mut diff = DiffOptions {
+            narrative: config.features.diff_narrative,
+            ..DiffOptions::default()
+        };
+        if let Some(diffs) = std::env::var("MODEL_ACTIVITY_DIFFS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            diff.activity_diffs = diffs;
+        }
+        if let Ok(grouping) = std::env::var("DIFF_CHANGED_GROUPING") {
+            match ChangedGrouping::parse(&grouping) {
+                Some(grouping) => diff.changed_grouping = grouping,
+                None => warn!("Ignoring unknown DIFF_CHANGED_GROUPING '{}'", grouping),
+            }
+        }
+        if let Ok(keys) = std::env::var("DIFF_IDENTITY_KEYS") {
+            diff.identity_keys = IdentityKeys::parse(&keys);
+        }
+        if let Ok(thresholds) = std::env::var("DIFF_ALERT_THRESHOLDS") {
+            diff.alert_thresholds = thresholds
+                .split(',')
+                .map(str::trim)
+                .filter(|e| !e.is_empty())
+                .filter_map(|entry| {
+                    let threshold = AlertThreshold::parse(entry);
+                    if threshold.is_none() {
+                        warn!("Ignoring malformed DIFF_ALERT_THRESHOLDS entry '{}'", entry);
+                    }
+                    threshold
+                })
+                .collect();
+        }
+
+        let mut tables = TableOptions::default();
+        if let Ok(columns) = std::env::var("ATTRIBUTE_COLUMNS") {
+            let columns = parse_columns(&columns);
+            if !columns.is_empty() {
+                tables.attribute_columns = columns;
+            }
+        }
+        match std::env::var("ATTRIBUTE_TABLE").as_deref().map(str::trim) {
+            Ok("extended") => tables.extended_attributes = true,
+            Ok("basic") | Ok("") | Err(_) => {}
+            Ok(other) => warn!("Ignoring unknown ATTRIBUTE_TABLE '{}'", other),
+        }
+        if let Ok(columns) = std::env::var("ITEM_COLUMNS") {
+            tables.item_columns = parse_columns(&columns);
+        }
+        if let Ok(sort) = std::env::var("TABLE_SORT") {
+            tables.sort = SortOrder::parse(&sort);
+        }
+        if let Some(max) = std::env::var("CELL_MAX_LENGTH")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+        {
+            tables.max_cell_length = Some(max).filter(|&max| max > 0);
+        }
+        if let Some(threshold) = std::env::var("OPTION_PAGE_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            tables.option_page_threshold = threshold;
+        }
+        if let Some(size) = std::env::var("OPTION_PAGE_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&size| size > 0)
+        {
+            tables.option_page_size = size;
+        }
+
+        Self {
+            expand,
+            theme: theme_from_env(&config.renderer.theme),
+            diff,
+            tables,
+            category_diagrams: category_diagrams_from_env(),
+            label_completeness: config.features.label_completeness,
+            attributes: AttributeFilter::default(),
+        }
+    }
+
+    /// These options with a publish request's table overrides: `columns` replaces the
+    /// attributes table's and the diff item tables' columns, per-category ones included (a
+    /// request publishes one or the other), `sort` the row order. `None` when the request
+    /// overrides nothing.
+    pub fn with_table_overrides(&self, columns: Option<&str>, sort: Option<&str>) -> Option<Self> {
+        let columns = columns.map(parse_columns).filter(|c| !c.is_empty());
+        let sort = sort.and_then(SortOrder::parse);
+        if columns.is_none() && sort.is_none() {
+            return None;
+        }
+
+        let mut options = self.clone();
+        if let Some(columns) = columns {
+            options.tables.attribute_columns = columns.clone();
+            options.tables.item_columns = columns;
+            options.tables.item_properties.category_columns.clear();
+        }
+        if sort.is_some() {
+            options.tables.sort = sort;
+        }
+        Some(options)
+    }
+
+    /// These options with a Confluence target's attribute filters.
+    pub fn with_attribute_filter(&self, attributes: AttributeFilter) -> Self {
+        Self {
+            attributes,
+            ..self.clone()
+        }
+    }
+
+    /// These options with a Confluence target's count and timestamp format.
+    pub fn with_display_format(&self, format: DisplayFormat) -> Self {
+        let mut options = self.clone();
+        options.theme.format = format;
+        options
+    }
+
+    /// These options with a Confluence target's diff item fields and columns.
+    pub fn with_item_properties(&self, properties: ItemProperties) -> Self {
+        let mut options = self.clone();
+        options.tables.item_properties = properties;
+        options
+    }
+
+    /// These options with a Confluence target's attributes table layout (`basic` or
+    /// `extended`); `None` keeps the `ATTRIBUTE_TABLE` default.
+    pub fn with_attribute_table(&self, table: Option<&str>) -> Self {
+        let mut options = self.clone();
+        if let Some(table) = table {
+            options.tables.extended_attributes = table == "extended";
+        }
+        options
+    }
+
+    /// These options with a Confluence target's attribute filters, display format and
+    /// attributes table layout.
+    pub fn for_target(&self, config: &DbConfluenceConfig) -> Result<Self> {
+        let options = self
+            .with_attribute_filter(AttributeFilter::from_db(config)?)
+            .with_attribute_table(config.attribute_table.as_deref());
+        Ok(options.with_display_format(DisplayFormat::from_db(config)?))
+    }
+}
+
+fn category_diagrams_from_env() -> CategoryDiagrams {
+    let format = match std::env::var("CATEGORY_DIAGRAMS").as_deref().map(str::trim) {
+        Ok("mermaid") => Some(DiagramFormat::Mermaid),
+        Ok("plantuml") => Some(DiagramFormat::PlantUml),
+        Ok("") | Ok("none") | Err(_) => None,
+        Ok(other) => {
+            warn!("Ignoring unknown CATEGORY_DIAGRAMS '{}'", other);
+            None
+        }
+    };
+    let macro_name = std::env::var("CATEGORY_DIAGRAM_MACRO")
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .or_else(|| format.map(|f| f.default_macro().to_string()))
+        .unwrap_or_default();
+    let max_nodes = std::env::var("CATEGORY_DIAGRAM_MAX_NODES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_DIAGRAM_MAX_NODES);
+    CategoryDiagrams {
+        format,
+        macro_name,
+        max_nodes,
+    }
+}
+
+/// The named preset (validated by `config::AppConfig::load`), with the `THEME_*`
+/// overrides.
+fn theme_from_env(name: &str) -> Theme {
+    let mut theme = Theme::named(name).unwrap_or_default();
+
+    match std::env::var("THEME_BOOLEANS").as_deref().map(str::trim) {
+        Ok("emoji") => theme.booleans = BooleanStyle::Emoji,
+        Ok("text") => theme.booleans = BooleanStyle::Text,
+        Ok("") | Err(_) => {}
+        Ok(other) => warn!("Ignoring unknown THEME_BOOLEANS '{}'", other),
+    }
+
+    match std::env::var("THEME_CARD_ICONS").as_deref().map(str::trim) {
+        Ok("true") => theme.card_icons = true,
+        Ok("false") => theme.card_icons = false,
+        Ok("") | Err(_) => {}
+        Ok(other) => warn!("Ignoring unknown THEME_CARD_ICONS '{}'", other),
+    }
+
+    if let Ok(colors) = std::env::var("THEME_LOZENGE_COLORS") {
+        for entry in colors.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((from, to)) = entry.split_once('=') else {
+                warn!("Ignoring malformed THEME_LOZENGE_COLORS entry '{}'", entry);
+                continue;
+            };
+            let (Some(from), Some(to)) = (lozenge_color(from), lozenge_color(to)) else {
+                warn!(
+                    "Ignoring THEME_LOZENGE_COLORS entry '{}': colours must be one of {}",
+                    entry,
+                    LOZENGE_COLORS.join(", ")
+                );
+                continue;
+            };
+            theme.palette.insert(from.to_string(), to.to_string());
+        }
+    }
+
+    theme
+}
+
+/// Match a lozenge colour case-insensitively, returning its canonical spelling.
+fn lozenge_color(name: &str) -> Option<&'static str> {
+    let name = name.trim();
+    LOZENGE_COLORS
+        .iter()
+        .copied()
+        .find(|c| c.eq_ignore_ascii_case(name))
+}
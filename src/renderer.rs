@@ -1,69 +1,301 @@
-use crate::diff::{extract_item_properties, CategoryDiff, DiffReport};
+use crate::db::{DiffActivityRow, PublicationRow, SnapshotMeta};
+use crate::diff::{
+    describe_changes, describe_field, extract_item_properties, AttributeOptionsDiff, CategoryDiff,
+    ChangeCounts, DiffAlert, DiffReport, MovedItem, OptionChange, OptionChangeKind, RenamedItem,
+};
+use crate::matrix::Matrix;
+use crate::model::{
+    Attribute, AttributeOption, Category, Channel, ChannelCompleteness, Family, FamilyUsage, Labels,
+    MeasurementFamily, Snapshot,
+};
+use crate::render_options::{
+    AttributeFilter, BooleanStyle, CategoryDiagrams, ChangedGrouping, DiagramFormat, DisplayFormat,
+    ExpandOptions, RenderOptions, SortOrder, TableOptions, Theme,
+};
+use crate::sanitize;
+use crate::titles::option_page_title;
+use chrono::{DateTime, TimeDelta, Utc};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use uuid::Uuid;
+
+/// Version of the rendered output, recorded on every publication and page. Bump it with a
+/// change that alters what already published pages would render to, then refresh them with
+/// `POST /api/republish?renderer_older_than=<new version>`.
+pub const RENDERER_VERSION: i32 = 6;
 
 // =============================================================================
 // Diff rendering
 // =============================================================================
 
-/// Render a diff page in Confluence storage format (XHTML).
-/// Returns (page_title, page_body).
+/// One side of a diff as the header panel of its page shows it.
+pub struct DiffSnapshot {
+    pub id: Uuid,
+    pub label: Option<String>,
+    pub completed_at: DateTime<Utc>,
+    /// The snapshot's published root page, when it still shows the snapshot
+    /// (`db::fetch_published_snapshot_url`).
+    pub page_url: Option<String>,
+}
+
+/// Render a diff page body in Confluence storage format (XHTML).
+/// The page title comes from the target's title templates (see `titles.rs`).
+///
+/// `left_out` is set when the publish was limited to some categories (`CategoryFilter`):
+/// the changed categories it left out of `report`, noted in the header panel.
 pub fn render_diff_page(
-    before_label: Option<&str>,
-    after_label: Option<&str>,
+    before: &DiffSnapshot,
+    after: &DiffSnapshot,
     report: &DiffReport,
-) -> (String, String) {
-    let before = before_label.unwrap_or("before");
-    let after = after_label.unwrap_or("after");
-    let title = format!("Diff: {} \u{2192} {}", before, after);
-
+    alerts: &[DiffAlert],
+    warnings: &[String],
+    left_out: Option<&[String]>,
+    options: &RenderOptions,
+) -> String {
+    let theme = &options.theme;
     let mut body = String::new();
 
+    // Threshold alerts come first: such a diff usually means a broken export
+    if !alerts.is_empty() {
+        body.push_str(&render_alerts_panel(alerts));
+    }
+    if !warnings.is_empty() {
+        body.push_str(&render_warnings_panel(warnings));
+    }
+
     // Header info panel
-    body.push_str(&render_diff_header(before, after));
+    body.push_str(&render_diff_header(before, after, left_out, &theme.format));
 
     // Summary table
-    body.push_str(&render_summary_table(report));
+    body.push_str(&render_summary_table(report, theme));
 
     // Per-category sections (sorted alphabetically)
     let mut categories: Vec<_> = report.iter().collect();
     categories.sort_by_key(|(name, _)| name.to_lowercase());
 
     for (category_name, diff) in &categories {
-        body.push_str(&render_category(category_name, diff));
+        body.push_str(&render_category(category_name, diff, options));
     }
 
-    (title, body)
+    body
 }
 
-fn render_diff_header(before: &str, after: &str) -> String {
-    let mut out = String::new();
-    out.push_str(&info_panel(&format!(
-        "<strong>Before:</strong> {}<br/><strong>After:</strong> {}",
+/// Render the blog post announcing a published diff: its alerts, the summary table and
+/// every category's change descriptions, linking to the diff page for the details.
+pub fn render_diff_announcement(
+    before: &str,
+    after: &str,
+    report: &DiffReport,
+    alerts: &[DiffAlert],
+    diff_url: &str,
+    theme: &Theme,
+) -> String {
+    let mut body = String::new();
+    if !alerts.is_empty() {
+        body.push_str(&render_alerts_panel(alerts));
+    }
+    body.push_str(&format!(
+        "<p>The product model changed from <strong>{}</strong> to <strong>{}</strong>. \
+         <a href=\"{}\">View the full diff</a></p>",
         escape_html(before),
         escape_html(after),
+        escape_html(diff_url),
+    ));
+    body.push_str(&render_summary_table(report, theme));
+
+    let mut categories: Vec<_> = report.iter().collect();
+    categories.sort_by_key(|(name, _)| name.to_lowercase());
+    for (name, diff) in categories {
+        let sentences = describe_changes(name, diff);
+        if !sentences.is_empty() {
+            body.push_str(&format!("<h3>{}</h3>", capitalize(&escape_html(name))));
+            body.push_str(&render_change_descriptions(&sentences));
+        }
+    }
+    body
+}
+
+/// Render the note panel listing the diff categories that could not be parsed and are
+/// missing from the page.
+fn render_warnings_panel(warnings: &[String]) -> String {
+    let items: String = warnings
+        .iter()
+        .map(|warning| format!("<li>{}</li>", escape_html(warning)))
+        .collect();
+    format!(
+        "<ac:structured-macro ac:name=\"note\">\
+         <ac:parameter ac:name=\"title\">Incomplete diff</ac:parameter>\
+         <ac:rich-text-body><p>Parts of the stored diff could not be read and are not shown:</p>\
+         <ul>{}</ul></ac:rich-text-body>\
+         </ac:structured-macro>",
+        items
+    )
+}
+
+/// Changed items of a category shown at most in its skipped-items panel; the rest are
+/// counted.
+const SHOWN_PARSE_WARNINGS: usize = 10;
+
+/// Render the warning panel under a category heading listing the changed items that could
+/// not be read and are missing from its tables.
+fn render_parse_warnings(warnings: &[String]) -> String {
+    let mut items: String = warnings
+        .iter()
+        .take(SHOWN_PARSE_WARNINGS)
+        .map(|warning| format!("<li>{}</li>", escape_html(warning)))
+        .collect();
+    if warnings.len() > SHOWN_PARSE_WARNINGS {
+        items.push_str(&format!(
+            "<li>and {} more</li>",
+            warnings.len() - SHOWN_PARSE_WARNINGS
+        ));
+    }
+    let count = match warnings.len() {
+        1 => "1 changed item".to_string(),
+        n => format!("{} changed items", n),
+    };
+    format!(
+        "<ac:structured-macro ac:name=\"warning\">\
+         <ac:parameter ac:name=\"title\">Unreadable changes</ac:parameter>\
+         <ac:rich-text-body><p>{} could not be read and {} not shown:</p>\
+         <ul>{}</ul></ac:rich-text-body>\
+         </ac:structured-macro>",
+        count,
+        if warnings.len() == 1 { "is" } else { "are" },
+        items
+    )
+}
+
+/// Render the red warning panel listing the categories whose changes crossed an alert
+/// threshold.
+fn render_alerts_panel(alerts: &[DiffAlert]) -> String {
+    let items: String = alerts
+        .iter()
+        .map(|alert| {
+            format!(
+                "<li><strong>{} of {} {} {}</strong> ({:.0}%, alert threshold {}%)</li>",
+                alert.count,
+                alert.total,
+                escape_html(&alert.category),
+                alert.kind,
+                alert.percent,
+                alert.threshold,
+            )
+        })
+        .collect();
+    format!(
+        "<ac:structured-macro ac:name=\"warning\">\
+         <ac:parameter ac:name=\"title\">Unusually large diff</ac:parameter>\
+         <ac:rich-text-body><p>This diff changes far more of the model than usual, which \
+         often means a broken or partial export. Check it before acting on it.</p>\
+         <ul>{}</ul></ac:rich-text-body>\
+         </ac:structured-macro>",
+        items,
+    )
+}
+
+/// Render the info panel opening a cumulative diff page: the range it covers and how
+/// many stored diffs it was composed from.
+pub fn render_cumulative_note(
+    before: &SnapshotMeta,
+    after: &SnapshotMeta,
+    diffs: usize,
+    format: &DisplayFormat,
+) -> String {
+    let snapshot = |meta: &SnapshotMeta| {
+        let name = match &meta.label {
+            Some(label) => format!("<strong>{}</strong>", escape_html(label)),
+            None => format!("<code>{}</code>", meta.id),
+        };
+        format!("{} ({})", name, format.date(meta.completed_at))
+    };
+    info_panel(&format!(
+        "Cumulative diff: the net change from {} to {}, composed from {} stored diff{}.",
+        snapshot(before),
+        snapshot(after),
+        diffs,
+        if diffs == 1 { "" } else { "s" },
+    ))
+}
+
+/// The header panel: each side's label (linking to its published root page), ID and
+/// capture time, and the categories a filtered publish left out.
+fn render_diff_header(
+    before: &DiffSnapshot,
+    after: &DiffSnapshot,
+    left_out: Option<&[String]>,
+    format: &DisplayFormat,
+) -> String {
+    let side = |snapshot: &DiffSnapshot, fallback: &str| {
+        let label = escape_html(snapshot.label.as_deref().unwrap_or(fallback));
+        let label = match &snapshot.page_url {
+            Some(url) => format!("<a href=\"{}\">{}</a>", escape_html(url), label),
+            None => label,
+        };
+        format!(
+            "{} (<code>{}</code>, captured {})",
+            label,
+            snapshot.id,
+            format.date_time(snapshot.completed_at, false)
+        )
+    };
+    let mut out = String::new();
+    let filtered = match left_out {
+        None => String::new(),
+        Some([]) => "<br/><strong>Filtered:</strong> limited to some categories; no other \
+                     category changed."
+            .to_string(),
+        Some(categories) => format!(
+            "<br/><strong>Filtered:</strong> limited to some categories; changes to {} are \
+             not shown.",
+            categories
+                .iter()
+                .map(|c| format!("<em>{}</em>", escape_html(c)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    out.push_str(&info_panel(&format!(
+        "<strong>Before:</strong> {}<br/><strong>After:</strong> {}{}",
+        side(before, "before"),
+        side(after, "after"),
+        filtered,
     )));
     out.push_str("<hr/>");
     out
 }
 
-fn render_summary_table(report: &DiffReport) -> String {
+/// The per-category counts; the Moved column only shows when some category has moves.
+fn render_summary_table(report: &DiffReport, theme: &Theme) -> String {
     let mut out = String::new();
     out.push_str("<h2>Summary</h2>");
+    let moves = report.values().any(|diff| !diff.moved.is_empty());
 
     out.push_str("<table data-layout=\"full-width\"><tbody>");
-    out.push_str("<tr><th>Category</th><th>Added</th><th>Removed</th><th>Changed</th></tr>");
+    out.push_str(&format!(
+        "<tr><th>Category</th><th>Added</th><th>Removed</th><th>Changed</th>\
+         <th>Renamed</th>{}</tr>",
+        if moves { "<th>Moved</th>" } else { "" },
+    ));
 
     let mut categories: Vec<_> = report.iter().collect();
     categories.sort_by_key(|(name, _)| name.to_lowercase());
 
     for (name, diff) in &categories {
+        let moved = if moves {
+            format!("<td>{}</td>", status_badge(theme, "Moved", diff.moved.len(), "Purple"))
+        } else {
+            String::new()
+        };
         out.push_str(&format!(
-            "<tr><td><strong>{}</strong></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            "<tr><td><strong>{}</strong></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>{}</tr>",
             capitalize(&escape_html(name)),
-            status_badge("Added", diff.added.len(), "Green"),
-            status_badge("Removed", diff.removed.len(), "Red"),
-            status_badge("Changed", diff.changed.len(), "Yellow"),
+            status_badge(theme, "Added", diff.added.len(), "Green"),
+            status_badge(theme, "Removed", diff.removed.len(), "Red"),
+            status_badge(theme, "Changed", diff.changed.len(), "Yellow"),
+            status_badge(theme, "Renamed", diff.renamed.len(), "Blue"),
+            moved,
         ));
     }
 
@@ -71,25 +303,232 @@ fn render_summary_table(report: &DiffReport) -> String {
     out
 }
 
-fn render_category(name: &str, diff: &CategoryDiff) -> String {
+fn render_category(name: &str, diff: &CategoryDiff, options: &RenderOptions) -> String {
+    let theme = &options.theme;
     let mut out = String::new();
     let display_name = capitalize(&escape_html(name));
 
     out.push_str(&format!("<h2>{}</h2>", display_name));
+    if !diff.parse_warnings.is_empty() {
+        out.push_str(&render_parse_warnings(&diff.parse_warnings));
+    }
+
+    if options.diff.narrative {
+        out.push_str(&render_change_descriptions(&describe_changes(name, diff)));
+    }
+
+    // Attribute options get per-attribute tables instead of the generic item tables
+    if name == "attribute_options" && !diff.options.is_empty() {
+        out.push_str(&render_option_changes(&diff.options, &options.tables, theme));
+        if !diff.renamed.is_empty() {
+            out.push_str(&render_renamed_section(&diff.renamed, theme));
+        }
+        return out;
+    }
+
+    out.push_str(&render_added_section(name, &diff.added, &options.tables, theme));
+    out.push_str(&render_removed_section(name, &diff.removed, &options.tables, theme));
+    out.push_str(&render_changed_section(
+        &diff.changed,
+        options.diff.changed_grouping,
+        &options.tables,
+        theme,
+    ));
+    if !diff.renamed.is_empty() {
+        out.push_str(&render_renamed_section(&diff.renamed, theme));
+    }
+    if !diff.moved.is_empty() {
+        out.push_str(&render_moved_section(&diff.moved, theme));
+    }
+    if !diff.options.is_empty() {
+        out.push_str(&render_option_changes(&diff.options, &options.tables, theme));
+    }
+
+    out
+}
+
+/// Per attribute, a table of its added, removed and changed options, followed by the
+/// reordered ones as a compact old → new position list (alone for a pure reordering).
+fn render_option_changes(
+    options: &[AttributeOptionsDiff],
+    tables: &TableOptions,
+    theme: &Theme,
+) -> String {
+    let max_length = tables.max_cell_length;
+    let mut out = String::new();
+
+    for attribute in options {
+        let (mut added, mut removed, mut reordered, mut changed) = (0, 0, 0, 0);
+        for change in &attribute.changes {
+            match change.kind {
+                OptionChangeKind::Added => added += 1,
+                OptionChangeKind::Removed => removed += 1,
+                OptionChangeKind::Reordered { .. } => reordered += 1,
+                OptionChangeKind::Changed(_) => changed += 1,
+            }
+        }
+        out.push_str(&format!(
+            "<h3>Options of <code>{}</code> {} {} {} {}</h3>",
+            escape_html(&attribute.attribute),
+            status_badge(theme, "Added", added, "Green"),
+            status_badge(theme, "Removed", removed, "Red"),
+            status_badge(theme, "Reordered", reordered, "Blue"),
+            status_badge(theme, "Changed", changed, "Yellow"),
+        ));
+
+        if reordered < attribute.changes.len() {
+            out.push_str(&render_option_change_table(&attribute.changes, max_length, theme));
+        }
+        if reordered > 0 {
+            let moves: Vec<String> = attribute
+                .reordered()
+                .into_iter()
+                .map(|(code, from, to)| {
+                    format!(
+                        "<code>{}</code> {} \u{2192} {}",
+                        escape_html(code),
+                        escape_html(from),
+                        escape_html(to)
+                    )
+                })
+                .collect();
+            out.push_str(&format!(
+                "<p>{} (old position \u{2192} new position): {}</p>",
+                status_text(theme, "Reordered", "Blue"),
+                moves.join(", ")
+            ));
+        }
+    }
+
+    out
+}
+
+/// The table of an attribute's added, removed and changed options; reorders are left out.
+fn render_option_change_table(
+    changes: &[OptionChange],
+    max_length: Option<usize>,
+    theme: &Theme,
+) -> String {
+    let mut out = String::from("<table data-layout=\"full-width\"><tbody>");
+    out.push_str("<tr><th>Change</th><th>Code</th><th>Labels</th><th>Details</th></tr>");
+    for change in changes {
+        let (status, color, details) = match &change.kind {
+            OptionChangeKind::Added => ("Added", "Green", String::new()),
+            OptionChangeKind::Removed => ("Removed", "Red", String::new()),
+            OptionChangeKind::Reordered { .. } => continue,
+            OptionChangeKind::Changed(fields) => (
+                "Changed",
+                "Yellow",
+                fields
+                    .iter()
+                    .map(|f| {
+                        let (old, old_full) = truncate_cell(&f.old, max_length);
+                        let (new, new_full) = truncate_cell(&f.new, max_length);
+                        format!(
+                            "{}: <span style=\"color: red;\">{}</span>{} \u{2192} \
+                             <span style=\"color: green;\">{}</span>{}",
+                            escape_html(&describe_field(&f.field_path)),
+                            old,
+                            old_full,
+                            new,
+                            new_full,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("<br/>"),
+            ),
+        };
+        out.push_str(&format!(
+            "<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+            status_text(theme, status, color),
+            escape_html(&change.option.code),
+            render_labels_inline(&change.option.labels, max_length),
+            details,
+        ));
+    }
+    out.push_str("</tbody></table>");
+    out
+}
+
+/// Readable summary of a category's changes, listed above its tables.
+fn render_change_descriptions(sentences: &[String]) -> String {
+    if sentences.is_empty() {
+        return String::new();
+    }
+    let items: String = sentences
+        .iter()
+        .map(|sentence| format!("<li>{}</li>", escape_html(sentence)))
+        .collect();
+    format!("<ul>{}</ul>", items)
+}
+
+/// Removed/added pairs detected as renames, shown as old → new code.
+fn render_renamed_section(items: &[RenamedItem], theme: &Theme) -> String {
+    let mut out = String::new();
+
+    out.push_str(&format!(
+        "<h3>{} Renamed</h3>",
+        status_lozenge(theme, items.len(), "Blue"),
+    ));
+    out.push_str(
+        "<p><em>Detected from items removed and re-added under a new code; \
+         verify before relying on it.</em></p>",
+    );
+
+    out.push_str("<table data-layout=\"full-width\"><tbody>");
+    out.push_str("<tr><th>Old Code</th><th>New Code</th><th>Matched On</th></tr>");
+    for item in items {
+        out.push_str(&format!(
+            "<tr><td><code><span style=\"color: red;\">{}</span></code></td>\
+             <td><code><span style=\"color: green;\">{}</span></code></td><td>{}</td></tr>",
+            escape_html(&item.old_code),
+            escape_html(&item.new_code),
+            item.matched_on.description(),
+        ));
+    }
+    out.push_str("</tbody></table>");
+    out
+}
+
+/// Categories that only changed their parent, shown as old parent → new parent.
+fn render_moved_section(items: &[MovedItem], theme: &Theme) -> String {
+    let mut out = String::new();
 
-    out.push_str(&render_added_section(&diff.added));
-    out.push_str(&render_removed_section(&diff.removed));
-    out.push_str(&render_changed_section(&diff.changed));
+    out.push_str(&format!(
+        "<h3>{} Moved</h3>",
+        status_lozenge(theme, items.len(), "Purple"),
+    ));
 
+    let parent = |code: &Option<String>| match code {
+        Some(code) => format!("<code>{}</code>", escape_html(code)),
+        None => "<em>root</em>".to_string(),
+    };
+    out.push_str("<table data-layout=\"full-width\"><tbody>");
+    out.push_str("<tr><th>Code</th><th>Old Parent</th><th>New Parent</th></tr>");
+    for item in items {
+        out.push_str(&format!(
+            "<tr><td><code>{}</code></td><td><span style=\"color: red;\">{}</span></td>\
+             <td><span style=\"color: green;\">{}</span></td></tr>",
+            escape_html(&item.code),
+            parent(&item.old_parent),
+            parent(&item.new_parent),
+        ));
+    }
+    out.push_str("</tbody></table>");
     out
 }
 
-fn render_added_section(items: &[Value]) -> String {
+fn render_added_section(
+    category: &str,
+    items: &[Value],
+    tables: &TableOptions,
+    theme: &Theme,
+) -> String {
     let mut out = String::new();
 
     out.push_str(&format!(
         "<h3>{} Added</h3>",
-        status_lozenge(items.len(), "Green"),
+        status_lozenge(theme, items.len(), "Green"),
     ));
 
     if items.is_empty() {
@@ -97,16 +536,21 @@ fn render_added_section(items: &[Value]) -> String {
         return out;
     }
 
-    out.push_str(&render_item_table(items));
+    out.push_str(&render_item_table(category, items, tables, theme));
     out
 }
 
-fn render_removed_section(items: &[Value]) -> String {
+fn render_removed_section(
+    category: &str,
+    items: &[Value],
+    tables: &TableOptions,
+    theme: &Theme,
+) -> String {
     let mut out = String::new();
 
     out.push_str(&format!(
         "<h3>{} Removed</h3>",
-        status_lozenge(items.len(), "Red"),
+        status_lozenge(theme, items.len(), "Red"),
     ));
 
     if items.is_empty() {
@@ -114,22 +558,31 @@ fn render_removed_section(items: &[Value]) -> String {
         return out;
     }
 
-    out.push_str(&render_item_table(items));
+    out.push_str(&render_item_table(category, items, tables, theme));
     out
 }
 
-fn render_changed_section(items: &[crate::diff::ChangedItem]) -> String {
+fn render_changed_section(
+    items: &[crate::diff::ChangedItem],
+    grouping: ChangedGrouping,
+    tables: &TableOptions,
+    theme: &Theme,
+) -> String {
     let mut out = String::new();
 
     out.push_str(&format!(
         "<h3>{} Changed</h3>",
-        status_lozenge(items.len(), "Yellow"),
+        status_lozenge(theme, items.len(), "Yellow"),
     ));
 
     if items.is_empty() {
         out.push_str("<p><em>No changes.</em></p>");
         return out;
     }
+    if grouping == ChangedGrouping::Field {
+        out.push_str(&render_changes_by_field(items, tables, theme));
+        return out;
+    }
 
     out.push_str("<table data-layout=\"full-width\"><tbody>");
     out.push_str("<tr><th>Code</th><th>Field</th><th>Old Value</th><th>New Value</th></tr>");
@@ -137,14 +590,18 @@ fn render_changed_section(items: &[crate::diff::ChangedItem]) -> String {
     for item in items {
         // Render flat field-level changes (old → new)
         for change in &item.changes {
+            let (old, old_full) = truncate_cell(&change.old, tables.max_cell_length);
+            let (new, new_full) = truncate_cell(&change.new, tables.max_cell_length);
             out.push_str(&format!(
                 "<tr><td><code>{}</code></td><td><code>{}</code></td>\
-                 <td><span style=\"color: red;\">{}</span></td>\
-                 <td><span style=\"color: green;\">{}</span></td></tr>",
+                 <td><span style=\"color: red;\">{}</span>{}</td>\
+                 <td><span style=\"color: green;\">{}</span>{}</td></tr>",
                 escape_html(&item.code),
                 escape_html(&change.field_path),
-                escape_html(&change.old),
-                escape_html(&change.new),
+                old,
+                old_full,
+                new,
+                new_full,
             ));
         }
 
@@ -189,20 +646,156 @@ fn render_changed_section(items: &[crate::diff::ChangedItem]) -> String {
     out
 }
 
-/// Render a table of added/removed items using their extracted properties.
-fn render_item_table(items: &[Value]) -> String {
-    let all_props: Vec<Vec<(String, String)>> =
-        items.iter().map(extract_item_properties).collect();
+/// Changed items grouped by field path, in path order: one row per field with the number
+/// of items it changed on, and each item's old and new value in a collapsed expand macro.
+/// Nested sub-diffs are grouped as `<field>.added` / `<field>.removed`.
+fn render_changes_by_field(
+    items: &[crate::diff::ChangedItem],
+    tables: &TableOptions,
+    theme: &Theme,
+) -> String {
+    // Code, old and new value cells of each field's items, in item order
+    let mut fields: BTreeMap<String, Vec<(&str, String, String)>> = BTreeMap::new();
+    let colored = |color: &str, html: &str| {
+        format!("<span style=\"color: {};\">{}</span>", color, html)
+    };
+    for item in items {
+        for change in &item.changes {
+            let (old, old_full) = truncate_cell(&change.old, tables.max_cell_length);
+            let (new, new_full) = truncate_cell(&change.new, tables.max_cell_length);
+            fields.entry(change.field_path.clone()).or_default().push((
+                &item.code,
+                colored("red", &old) + &old_full,
+                colored("green", &new) + &new_full,
+            ));
+        }
+        for nested in &item.nested_diffs {
+            let list = |values: &[String]| {
+                values.iter().map(|v| escape_html(v)).collect::<Vec<_>>().join(", ")
+            };
+            if !nested.added.is_empty() {
+                let field = format!("{}.added", nested.field_path);
+                let new = colored("green", &list(&nested.added));
+                fields.entry(field).or_default().push((&item.code, String::new(), new));
+            }
+            if !nested.removed.is_empty() {
+                let field = format!("{}.removed", nested.field_path);
+                let old = colored("red", &list(&nested.removed));
+                fields.entry(field).or_default().push((&item.code, old, String::new()));
+            }
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str("<table data-layout=\"full-width\"><tbody>");
+    out.push_str("<tr><th>Field</th><th>Changes</th></tr>");
+    for (field, rows) in &fields {
+        let mut list = String::from(
+            "<table><tbody><tr><th>Code</th><th>Old Value</th><th>New Value</th></tr>",
+        );
+        for (code, old, new) in rows {
+            list.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+                escape_html(code),
+                old,
+                new,
+            ));
+        }
+        list.push_str("</tbody></table>");
+        let count = match rows.len() {
+            1 => "1 item".to_string(),
+            n => format!("{} items", theme.format.count(n)),
+        };
+        out.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>Changed on {}{}</td></tr>",
+            escape_html(field),
+            count,
+            expand_macro(&format!("Show {}", count), &list),
+        ));
+    }
+    out.push_str("</tbody></table>");
+    out
+}
+
+/// Render a table of added/removed items: the configured columns of the category (else
+/// `ITEM_COLUMNS`) that any of the items has, else every property `extract_item_properties`
+/// finds, in the configured row order.
+fn render_item_table(
+    category: &str,
+    items: &[Value],
+    tables: &TableOptions,
+    theme: &Theme,
+) -> String {
+    let configured: Vec<&String> = tables
+        .item_properties
+        .columns(category, &tables.item_columns)
+        .iter()
+        .filter(|col| items.iter().any(|item| item_field(item, col).is_some()))
+        .collect();
 
-    // Determine unique column names, preserving insertion order
-    let mut columns: Vec<String> = Vec::new();
-    for props in &all_props {
-        for (key, _) in props {
-            if !columns.contains(key) {
-                columns.push(key.clone());
+    let (columns, mut rows): (Vec<String>, Vec<Vec<Cell>>) = if configured.is_empty() {
+        let all_props: Vec<Vec<(String, String)>> =
+            items
+                .iter()
+                .map(|item| extract_item_properties(item, &tables.item_properties))
+                .collect();
+
+        // Determine unique column names, preserving insertion order
+        let mut columns: Vec<String> = Vec::new();
+        for props in &all_props {
+            for (key, _) in props {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
             }
         }
+
+        let rows = all_props
+            .iter()
+            .map(|props| {
+                let prop_map: BTreeMap<&str, &str> = props
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str()))
+                    .collect();
+                columns
+                    .iter()
+                    .map(|col| match prop_map.get(col.as_str()) {
+                        Some(val) if col == "code" => Cell::code(val),
+                        Some(val) => Cell::truncated(val, tables.max_cell_length),
+                        None => Cell::dash(),
+                    })
+                    .collect()
+            })
+            .collect();
+        (columns, rows)
+    } else {
+        let rows = items
+            .iter()
+            .map(|item| {
+                configured
+                    .iter()
+                    .map(|col| match item_field(item, col) {
+                        Some(val) if col.as_str() == "code" => {
+                            Cell::code(val.as_str().unwrap_or_default())
+                        }
+                        val => Cell::json(val, tables.max_cell_length, theme),
+                    })
+                    .collect()
+            })
+            .collect();
+        (configured.into_iter().cloned().collect(), rows)
+    };
+    // The raw JSON rides along as a cell past the last column, so it follows its row
+    // through the sort
+    if tables.raw_json {
+        for (row, item) in rows.iter_mut().zip(items) {
+            row.push(Cell {
+                html: raw_json_expand(item),
+                text: String::new(),
+            });
+        }
     }
+    sort_rows(&mut rows, &columns, tables.sort.as_ref());
 
     let mut out = String::new();
 
@@ -216,28 +809,53 @@ fn render_item_table(items: &[Value]) -> String {
     out.push_str("</tr>");
 
     // Data rows
-    for props in &all_props {
-        let prop_map: HashMap<&str, &str> = props
-            .iter()
-            .map(|(k, v)| (k.as_str(), v.as_str()))
-            .collect();
-
+    for row in &rows {
+        let (cells, raw) = row.split_at(columns.len().min(row.len()));
         out.push_str("<tr>");
-        for col in &columns {
-            let val = prop_map.get(col.as_str()).unwrap_or(&"\u{2014}");
-            if col == "code" {
-                out.push_str(&format!("<td><code>{}</code></td>", escape_html(val)));
-            } else {
-                out.push_str(&format!("<td>{}</td>", escape_html(val)));
-            }
+        for cell in cells {
+            out.push_str(&format!("<td>{}</td>", cell.html));
         }
         out.push_str("</tr>");
+        if let Some(raw) = raw.first() {
+            out.push_str(&format!(
+                "<tr><td colspan=\"{}\">{}</td></tr>",
+                columns.len(),
+                raw.html
+            ));
+        }
     }
 
     out.push_str("</tbody></table>");
     out
 }
 
+/// A collapsed "Raw JSON" expand macro with the pretty-printed value in a code macro.
+fn raw_json_expand(value: &impl serde::Serialize) -> String {
+    let json = serde_json::to_string_pretty(value).unwrap_or_default();
+    expand_macro(
+        "Raw JSON",
+        &format!(
+            "<ac:structured-macro ac:name=\"code\">\
+             <ac:parameter ac:name=\"language\">json</ac:parameter>\
+             <ac:plain-text-body><![CDATA[{}]]></ac:plain-text-body>\
+             </ac:structured-macro>",
+            json.replace("]]>", "]]]]><![CDATA[>"),
+        ),
+    )
+}
+
+/// A field of an added/removed item; `label` is its first label.
+fn item_field<'a>(item: &'a Value, field: &str) -> Option<&'a Value> {
+    match field {
+        "label" => item
+            .get("labels")
+            .and_then(Value::as_object)
+            .and_then(|labels| labels.values().next()),
+        _ => item.get(field),
+    }
+    .filter(|v| !v.is_null())
+}
+
 // =============================================================================
 // Snapshot rendering (multi-page)
 // =============================================================================
@@ -245,16 +863,71 @@ fn render_item_table(items: &[Value]) -> String {
 /// A tree of pages representing a snapshot.
 /// The root page contains the full overview (summary cards + all category tables),
 /// and each family gets its own child page with detailed configuration.
+/// Page titles are not part of the tree; they are applied from the target's title
+/// templates at publish time, so a rendered tree can be cached independently of them.
 pub struct SnapshotPageTree {
-    pub root_title: String,
     pub root_body: String,
     pub children: Vec<SnapshotChildPage>,
+    /// The requirements coverage analysis page, published under the root page next to the
+    /// family pages.
+    pub coverage_body: String,
+    /// The model health page (orphans and broken references), published next to the
+    /// coverage page.
+    pub health_body: String,
+    /// The label completeness page (missing labels per locale), when
+    /// `RenderOptions.label_completeness` is set.
+    pub labels_body: Option<String>,
+    /// One diagram page per category tree, identified by its root category; empty unless
+    /// `RenderOptions.category_diagrams` names a format.
+    pub category_trees: Vec<SnapshotChildPage>,
+    /// The options tables of attributes with more than `TableOptions.option_page_threshold`
+    /// options, split into pages, in attribute order.
+    pub option_pages: Vec<SnapshotOptionPage>,
+}
+
+impl SnapshotPageTree {
+    /// Number of pages in the tree (root, family pages, the coverage and health pages and
+    /// the optional label completeness, category tree and option pages).
+    pub fn page_count(&self) -> usize {
+        self.children.len()
+            + 3
+            + usize::from(self.labels_body.is_some())
+            + self.category_trees.len()
+            + self.option_pages.len()
+    }
+}
+
+/// One page of an attribute's options table, titled by `titles::option_page_title`. Its
+/// body and the root page's link to it refer to the root page title through
+/// `ROOT_TITLE_MARKER`; fill it in with `link_root_title` before publishing.
+pub struct SnapshotOptionPage {
+    pub attribute: String,
+    /// 1-based.
+    pub page: usize,
+    pub pages: usize,
+    pub body: String,
 }
 
-/// A single child page (one per family in the snapshot).
+/// Stands in for the root page title in the links between the root page and the option
+/// pages, since titles are applied after rendering. Rendered values are escaped, so no
+/// snapshot data can contain it.
+const ROOT_TITLE_MARKER: &str = "<root-title/>";
+
+/// A rendered body with the links to the option pages (and back to the root page) pointed
+/// at the root page titled `root_title`.
+pub fn link_root_title(body: &str, root_title: &str) -> String {
+    body.replace(ROOT_TITLE_MARKER, &escape_html(root_title))
+}
+
+/// A single child page (one per family in the snapshot, or per category tree),
+/// identified by the family's or root category's code and display label.
 pub struct SnapshotChildPage {
-    pub title: String,
     pub body: String,
+    pub code: String,
+    pub label: String,
+    /// Label of the attribute group of a family's `attribute_as_label`, for
+    /// `FamilyGrouping::LabelAttributeGroup`; `None` for category trees.
+    pub label_group: Option<String>,
 }
 
 /// Render a snapshot as a multi-page tree in Confluence storage format (XHTML).
@@ -263,50 +936,12 @@ pub struct SnapshotChildPage {
 /// - A root "Akeneo Model Snapshot" page containing summary cards and all category tables
 /// - One child page per family with detailed configuration, attribute requirements, and
 ///   enriched attribute tables cross-referenced against the snapshot's attribute data
-pub fn render_snapshot_pages(label: Option<&str>, data: &Value) -> SnapshotPageTree {
+pub fn render_snapshot_pages(
+    label: Option<&str>,
+    snapshot: &Snapshot,
+    options: &RenderOptions,
+) -> SnapshotPageTree {
     let _display_label = label.unwrap_or("Unnamed snapshot");
-    let root_title = "Current model".to_string();
-
-    let Some(obj) = data.as_object() else {
-        return SnapshotPageTree {
-            root_title,
-            root_body: "<p><em>No data available.</em></p>".to_string(),
-            children: Vec::new(),
-        };
-    };
-
-    let channels = obj
-        .get("channels")
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_default();
-    let families = obj
-        .get("families")
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_default();
-    let attributes = obj
-        .get("attributes")
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_default();
-    let categories = obj
-        .get("categories")
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_default();
-    let attribute_options = obj.get("attribute_options");
-
-    // Count attribute options (it's a dict of attribute_code -> [options])
-    let attr_options_count: usize = attribute_options
-        .and_then(|v| v.as_object())
-        .map(|o| {
-            o.values()
-                .filter_map(|v| v.as_array())
-                .map(|a| a.len())
-                .sum()
-        })
-        .unwrap_or(0);
 
     // ── Root page body ──────────────────────────────────────────────────
     let mut body = String::new();
@@ -316,64 +951,249 @@ pub fn render_snapshot_pages(label: Option<&str>, data: &Value) -> SnapshotPageT
     body.push_str("<p>Overview of the PIM data model configuration \u{2014} channels, families, attributes, categories, and attribute options.</p>");
     body.push_str("<hr/>");
 
+    // Attributes and attribute options the target's filters leave out
+    let filter = &options.attributes;
+    let groups: BTreeMap<&str, Option<&str>> = snapshot
+        .attributes
+        .iter()
+        .map(|a| (a.code.as_str(), a.group.as_deref()))
+        .collect();
+    let kept = |code: &str| filter.keeps(code, groups.get(code).copied().flatten());
+    let (attributes, attribute_options): (Cow<[Attribute]>, Cow<BTreeMap<_, _>>) =
+        if filter.is_empty() {
+            (
+                Cow::Borrowed(&snapshot.attributes),
+                Cow::Borrowed(&snapshot.attribute_options),
+            )
+        } else {
+            (
+                snapshot.attributes.iter().filter(|a| kept(&a.code)).cloned().collect(),
+                Cow::Owned(
+                    snapshot
+                        .attribute_options
+                        .iter()
+                        .filter(|(code, _)| kept(code))
+                        .map(|(code, options)| (code.clone(), options.clone()))
+                        .collect(),
+                ),
+            )
+        };
+    let option_count: usize = attribute_options.values().map(Vec::len).sum();
+    let hidden_attributes = snapshot.attributes.len() - attributes.len();
+    let hidden_options = snapshot.attribute_options_count() - option_count;
+
     // Summary cards (rendered as a table)
+    let theme = &options.theme;
     body.push_str(&render_summary_cards(
-        channels.len(),
-        families.len(),
+        theme,
+        snapshot.channels.len(),
+        snapshot.families.len(),
         attributes.len(),
-        categories.len(),
-        attr_options_count,
+        snapshot.categories.len(),
+        option_count,
     ));
 
     // Category sections
-    body.push_str(&render_channels_section(&channels));
-    body.push_str(&render_families_section(&families));
-    body.push_str(&render_attributes_section(&attributes));
-    body.push_str(&render_categories_section(&categories));
-    body.push_str(&render_attribute_options_sections(attribute_options));
+    let expand = &options.expand;
+    body.push_str(&render_channels_section(&snapshot.channels, expand, theme));
+    body.push_str(&render_families_section(&snapshot.families, expand, theme));
+    body.push_str(&render_attributes_section(
+        &attributes,
+        hidden_attributes,
+        &snapshot.measurement_families,
+        &options.tables,
+        expand,
+        theme,
+    ));
+    body.push_str(&render_categories_section(
+        &snapshot.categories,
+        &options.tables,
+        expand,
+        theme,
+    ));
+    let (options_section, option_pages) = render_attribute_options_sections(
+        &attribute_options,
+        hidden_options,
+        &options.tables,
+        expand,
+        theme,
+    );
+    body.push_str(&options_section);
 
     // ── Child pages (one per family) ────────────────────────────────────
-    let children: Vec<SnapshotChildPage> = families
+    let children: Vec<SnapshotChildPage> = snapshot
+        .families
         .iter()
-        .map(|family| {
-            let code = family
-                .get("code")
-                .and_then(|v| v.as_str())
-                .unwrap_or("unknown");
-            let label = get_label(family).unwrap_or_else(|| code.to_string());
-            let page_title = format!("Family: {} ({})", label, code);
-            let page_body = render_family_detail_page(family, &attributes);
-            SnapshotChildPage {
-                title: page_title,
-                body: page_body,
-            }
-        })
+        .map(|family| render_family_child_page(snapshot, family, options))
         .collect();
 
     SnapshotPageTree {
-        root_title,
         root_body: body,
         children,
+        coverage_body: render_coverage_page(snapshot, theme),
+        health_body: render_health_page(snapshot, theme),
+        labels_body: options
+            .label_completeness
+            .then(|| render_label_completeness_page(snapshot, options)),
+        category_trees: render_category_tree_pages(&snapshot.categories, options),
+        option_pages,
     }
 }
 
-// =============================================================================
-// Overview page sections
-// =============================================================================
+/// A family's child page as `render_snapshot_pages` renders it. Only needs the snapshot's
+/// attributes and measurement families besides the family itself.
+pub fn render_family_child_page(
+    snapshot: &Snapshot,
+    family: &Family,
+    options: &RenderOptions,
+) -> SnapshotChildPage {
+    SnapshotChildPage {
+        body: render_family_detail_page(
+            family,
+            &snapshot.attributes,
+            &snapshot.measurement_families,
+            &options.attributes,
+            &options.tables,
+            &options.theme,
+        ),
+        ..family_child_page(snapshot, family)
+    }
+}
 
-/// Render the summary cards as a 5-column table with large counts and labels.
-fn render_summary_cards(
-    channels: usize,
-    families: usize,
-    attributes: usize,
-    categories: usize,
-    attr_options: usize,
+/// A family's child page without its body: just what titles and groups it.
+pub fn family_child_page(snapshot: &Snapshot, family: &Family) -> SnapshotChildPage {
+    let label_group = family
+        .attribute_as_label
+        .as_deref()
+        .and_then(|code| snapshot.attributes.iter().find(|a| a.code == code))
+        .and_then(|attr| attr.group_labels.first().or(attr.group.as_deref()))
+        .map(str::to_string);
+    SnapshotChildPage {
+        body: String::new(),
+        code: family.code.clone(),
+        label: family.labels.first().unwrap_or(&family.code).to_string(),
+        label_group,
+    }
+}
+
+/// Render the "changes since last publish" info panel placed above a republished root
+/// page: per-category counts against the previously published snapshot, with a link to
+/// the full diff page when one has been published.
+pub fn render_changes_banner(
+    previous_label: Option<&str>,
+    changes: &[(&str, ChangeCounts)],
+    diff_url: Option<&str>,
+    options: &RenderOptions,
 ) -> String {
-    let mut out = String::new();
-    out.push_str("<table data-layout=\"full-width\"><tbody><tr>");
+    let theme = &options.theme;
+    let previous = escape_html(previous_label.unwrap_or("Unnamed snapshot"));
 
-    let cards = [
-        ("\u{1F4E1}", channels, "Channels"),
+    let changed: Vec<String> = changes
+        .iter()
+        .filter(|(_, counts)| !counts.is_empty())
+        .map(|(category, counts)| {
+            let mut badges = Vec::new();
+            if counts.added > 0 {
+                badges.push(status_badge(theme, "Added", counts.added, "Green"));
+            }
+            if counts.removed > 0 {
+                badges.push(status_badge(theme, "Removed", counts.removed, "Red"));
+            }
+            if counts.changed > 0 {
+                badges.push(status_badge(theme, "Changed", counts.changed, "Yellow"));
+            }
+            format!(
+                "<strong>{}</strong> {}",
+                capitalize(&escape_html(&category.replace('_', " "))),
+                badges.join(" "),
+            )
+        })
+        .collect();
+
+    let mut html = if changed.is_empty() {
+        format!("<strong>No changes since the last publish</strong> ({}).", previous)
+    } else {
+        format!(
+            "<strong>Changes since the last publish</strong> ({}):<br/>{}",
+            previous,
+            changed.join("<br/>"),
+        )
+    };
+    if let Some(url) = diff_url {
+        html.push_str(&format!(
+            "<br/><a href=\"{}\">View the full diff</a>",
+            escape_html(url)
+        ));
+    }
+
+    info_panel(&html)
+}
+
+/// What the metadata panel at the top of a snapshot root page shows. The panel is
+/// rendered at publish time, outside the cached page tree, since it includes the publish
+/// time.
+pub struct SnapshotInfo<'a> {
+    pub id: Uuid,
+    pub label: Option<&'a str>,
+    pub server_name: &'a str,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    pub published_at: DateTime<Utc>,
+    /// The target's display format, for the times.
+    pub format: &'a DisplayFormat,
+}
+
+/// Render the snapshot metadata info panel: label and ID, the Akeneo server it was
+/// captured from, the capture window and its duration, and when it was published.
+pub fn render_snapshot_info(info: &SnapshotInfo<'_>) -> String {
+    let rows = [
+        (
+            "Snapshot",
+            format!(
+                "{} (<code>{}</code>)",
+                escape_html(info.label.unwrap_or("Unnamed snapshot")),
+                info.id
+            ),
+        ),
+        ("Akeneo server", escape_html(info.server_name)),
+        (
+            "Captured",
+            format!(
+                "{} \u{2013} {}",
+                info.format.date_time(info.started_at, true),
+                info.format.date_time(info.completed_at, true)
+            ),
+        ),
+        ("Duration", format_duration(info.completed_at - info.started_at)),
+        ("Published", info.format.date_time(info.published_at, true)),
+    ];
+
+    let html: Vec<String> = rows
+        .iter()
+        .map(|(name, value)| format!("<strong>{}:</strong> {}", name, value))
+        .collect();
+    info_panel(&html.join("<br/>"))
+}
+
+// =============================================================================
+// Overview page sections
+// =============================================================================
+
+/// Render the summary cards as a 5-column table with large counts and labels, each
+/// topped with an emoji icon unless the theme turns card icons off.
+fn render_summary_cards(
+    theme: &Theme,
+    channels: usize,
+    families: usize,
+    attributes: usize,
+    categories: usize,
+    attr_options: usize,
+) -> String {
+    let mut out = String::new();
+    out.push_str("<table data-layout=\"full-width\"><tbody><tr>");
+
+    let cards = [
+        ("\u{1F4E1}", channels, "Channels"),
         ("\u{1F4DA}", families, "Families"),
         ("\u{2699}\u{FE0F}", attributes, "Attributes"),
         ("\u{1F4C2}", categories, "Categories"),
@@ -381,9 +1201,16 @@ fn render_summary_cards(
     ];
 
     for (icon, count, label) in &cards {
+        let icon = if theme.card_icons {
+            format!("<p>{}</p>", icon)
+        } else {
+            String::new()
+        };
         out.push_str(&format!(
-            "<td><p>{}</p><p><strong style=\"font-size: 24px;\">{}</strong></p><p><em>{}</em></p></td>",
-            icon, count, label,
+            "<td>{}<p><strong style=\"font-size: 24px;\">{}</strong></p><p><em>{}</em></p></td>",
+            icon,
+            theme.format.count(*count),
+            label,
         ));
     }
 
@@ -392,165 +1219,1627 @@ fn render_summary_cards(
 }
 
 /// Render the Channels section with a structured table.
-fn render_channels_section(channels: &[Value]) -> String {
+fn render_channels_section(channels: &[Channel], expand: &ExpandOptions, theme: &Theme) -> String {
     let mut out = String::new();
-    out.push_str(&section_heading("Channels", channels.len(), "Green"));
+    out.push_str(&section_heading(theme, "Channels", channels.len(), "Green"));
 
     if channels.is_empty() {
         out.push_str("<p><em>No channels.</em></p>");
         return out;
     }
 
-    out.push_str("<table data-layout=\"full-width\"><tbody>");
-    out.push_str("<tr><th>Code</th><th>Label</th><th>Locales</th><th>Currencies</th><th>Category Tree</th></tr>");
+    let mut table = String::new();
+    table.push_str("<table data-layout=\"full-width\"><tbody>");
+    table.push_str("<tr><th>Code</th><th>Label</th><th>Locales</th><th>Currencies</th><th>Category Tree</th></tr>");
 
     for ch in channels {
-        let code = get_code(ch);
-        let label = get_label(ch).unwrap_or_else(|| "\u{2014}".to_string());
-        let locales = get_string_array(ch, "locales").join(", ");
-        let currencies = get_string_array(ch, "currencies").join(", ");
-        let tree = ch
-            .get("category_tree")
-            .and_then(|v| v.as_str())
-            .unwrap_or("\u{2014}");
-
-        out.push_str(&format!(
+        table.push_str(&format!(
             "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
-            escape_html(code),
-            escape_html(&label),
-            escape_html(&locales),
-            escape_html(&currencies),
-            escape_html(tree),
+            escape_html(&ch.code),
+            escape_html(ch.labels.first().unwrap_or("\u{2014}")),
+            escape_html(&ch.locales.join(", ")),
+            escape_html(&ch.currencies.join(", ")),
+            escape_html(or_dash(&ch.category_tree)),
         ));
     }
 
-    out.push_str("</tbody></table>");
+    table.push_str("</tbody></table>");
+
+    out.push_str(&collapsible(expand, "channels", channels.len(), &table));
     out
 }
 
 /// Render the Families section with a structured table.
-fn render_families_section(families: &[Value]) -> String {
+fn render_families_section(families: &[Family], expand: &ExpandOptions, theme: &Theme) -> String {
     let mut out = String::new();
-    out.push_str(&section_heading("Families", families.len(), "Yellow"));
+    out.push_str(&section_heading(theme, "Families", families.len(), "Yellow"));
 
     if families.is_empty() {
         out.push_str("<p><em>No families.</em></p>");
         return out;
     }
 
-    out.push_str("<table data-layout=\"full-width\"><tbody>");
-    out.push_str("<tr><th>Code</th><th>Label</th><th>Attributes</th><th>Label Attr</th><th>Image Attr</th></tr>");
+    let mut table = String::new();
+    table.push_str("<table data-layout=\"full-width\"><tbody>");
+    table.push_str(FAMILIES_TABLE_HEADER);
+
+    for fam in families {
+        let title = format!("{}{}\"/>", FAMILY_TITLE_MARKER, escape_html(&fam.code));
+        table.push_str(&render_family_row(fam, theme, &title));
+    }
+
+    table.push_str("</tbody></table>");
+
+    out.push_str(&collapsible(expand, "families", families.len(), &table));
+    out
+}
+
+/// Header row of the root page's Families table, which `replace_family_row` looks for.
+const FAMILIES_TABLE_HEADER: &str =
+    "<tr><th>Code</th><th>Label</th><th>Attributes</th><th>Label Attr</th><th>Image Attr</th></tr>";
+
+/// Opens the stand-in for a family page's title (`<family-title code="…"/>`) in the root
+/// page's Families table, like `ROOT_TITLE_MARKER`; `link_family_titles` fills it in.
+const FAMILY_TITLE_MARKER: &str = "<family-title code=\"";
+
+/// A Families table row, its label linking to the family page titled `page_title` (escaped).
+fn render_family_row(fam: &Family, theme: &Theme, page_title: &str) -> String {
+    format!(
+        "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td><code>{}</code></td><td><code>{}</code></td></tr>",
+        escape_html(&fam.code),
+        page_link(page_title, fam.labels.first().unwrap_or("\u{2014}")),
+        status_lozenge(theme, fam.attributes.len(), "Blue"),
+        escape_html(or_dash(&fam.attribute_as_label)),
+        escape_html(or_dash(&fam.attribute_as_image)),
+    )
+}
+
+/// A rendered root page body with the Families table's labels linking to the family pages,
+/// given each family's code and page title.
+pub fn link_family_titles<'a>(
+    body: &str,
+    titles: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> String {
+    let titles: HashMap<String, String> = titles
+        .into_iter()
+        .map(|(code, title)| (escape_html(code), escape_html(title)))
+        .collect();
+    let mut out = String::new();
+    let mut rest = body;
+    while let Some(start) = rest.find(FAMILY_TITLE_MARKER) {
+        let marker = &rest[start + FAMILY_TITLE_MARKER.len()..];
+        let Some(end) = marker.find("\"/>") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let code = &marker[..end];
+        out.push_str(titles.get(code).map_or(code, String::as_str));
+        rest = &marker[end + "\"/>".len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A published root page body with the family's row of its Families table re-rendered,
+/// linking to the family page titled `page_title`; `None` when the table has no row for
+/// the family (or the page has no Families table).
+pub fn replace_family_row(
+    root_body: &str,
+    family: &Family,
+    page_title: &str,
+    theme: &Theme,
+) -> Option<String> {
+    let table_start = root_body.find(FAMILIES_TABLE_HEADER)? + FAMILIES_TABLE_HEADER.len();
+    let table_len = root_body[table_start..].find("</tbody></table>")?;
+    let table = &root_body[table_start..table_start + table_len];
+    let prefix = format!("<tr><td><code>{}</code></td>", escape_html(&family.code));
+    let row_start = table_start + table.find(&prefix)?;
+    let row_len = root_body[row_start..].find("</tr>")? + "</tr>".len();
+    Some(format!(
+        "{}{}{}",
+        &root_body[..row_start],
+        render_family_row(family, theme, &escape_html(page_title)),
+        &root_body[row_start + row_len..]
+    ))
+}
+
+/// Render the Attributes section as a table of the configured columns and row order.
+/// `hidden` attributes were left out by the attribute filters.
+fn render_attributes_section(
+    attributes: &[Attribute],
+    hidden: usize,
+    measurement_families: &[MeasurementFamily],
+    tables: &TableOptions,
+    expand: &ExpandOptions,
+    theme: &Theme,
+) -> String {
+    let mut out = String::new();
+    out.push_str(&section_heading(theme, "Attributes", attributes.len(), "Purple"));
+    out.push_str(&filtered_note(hidden, "attribute", "attributes"));
+
+    if attributes.is_empty() {
+        out.push_str("<p><em>No attributes.</em></p>");
+        return out;
+    }
+
+    let mut columns = tables.attribute_columns.clone();
+    if tables.extended_attributes && !columns.iter().any(|col| col == "constraints") {
+        columns.push("constraints".to_string());
+    }
+    let columns = &columns;
+    let mut rows: Vec<Vec<Cell>> = attributes
+        .iter()
+        .map(|attr| {
+            columns
+                .iter()
+                .map(|col| {
+                    attribute_cell(attr, col, measurement_families, tables.max_cell_length, theme)
+                })
+                .collect()
+        })
+        .collect();
+    sort_rows(&mut rows, columns, tables.sort.as_ref());
+
+    let mut table = String::new();
+    table.push_str("<table data-layout=\"full-width\"><tbody><tr>");
+    for col in columns {
+        table.push_str(&format!("<th>{}</th>", attribute_column_header(col)));
+    }
+    table.push_str("</tr>");
+
+    for row in &rows {
+        table.push_str("<tr>");
+        for cell in row {
+            table.push_str(&format!("<td>{}</td>", cell.html));
+        }
+        table.push_str("</tr>");
+    }
+
+    table.push_str("</tbody></table>");
+
+    out.push_str(&collapsible(expand, "attributes", attributes.len(), &table));
+    out
+}
+
+/// One cell of an attributes table column: a built-in column, else the attribute field of
+/// that name. Metric attributes' type cells also name their unit, see [`metric_unit`].
+fn attribute_cell(
+    attr: &Attribute,
+    column: &str,
+    measurement_families: &[MeasurementFamily],
+    max_length: Option<usize>,
+    theme: &Theme,
+) -> Cell {
+    let text = |value: &str| Cell::truncated(value, max_length);
+    match column {
+        "code" => Cell::code(&attr.code),
+        "label" => attr.labels.first().map_or_else(Cell::dash, text),
+        "type" => match attr.attr_type.as_deref() {
+            Some(attr_type) => {
+                let mut cell = Cell::code(attr_type);
+                if let Some(unit) = metric_unit(attr, measurement_families) {
+                    cell.html.push_str(&format!("<br/>{}", unit));
+                }
+                cell
+            }
+            None => Cell::dash(),
+        },
+        "group" => attr.group.as_deref().map_or_else(Cell::dash, text),
+        "scopable" => Cell::check(theme, attr.scopable),
+        "localizable" => Cell::check(theme, attr.localizable),
+        "unique" => Cell::check(theme, attr.unique),
+        "constraints" => constraints_cell(attr, max_length),
+        field => Cell::json(attr.extra.get(field), max_length, theme),
+    }
+}
+
+/// A metric attribute's metric family and default unit, for auditing unit configuration:
+/// labelled from the snapshot's measurement families when it has them, which also flags a
+/// family or unit they do not define. `None` for other attribute types.
+fn metric_unit(attr: &Attribute, measurement_families: &[MeasurementFamily]) -> Option<String> {
+    if attr.attr_type.as_deref() != Some("pim_catalog_metric") {
+        return None;
+    }
+    let field = |name: &str| {
+        attr.extra
+            .get(name)
+            .and_then(Value::as_str)
+            .filter(|v| !v.is_empty())
+    };
+    let Some(family_code) = field("metric_family") else {
+        return Some("<em>No metric family</em>".to_string());
+    };
+    let family = measurement_families.iter().find(|f| f.code == family_code);
+
+    let family_html = match family {
+        Some(family) => match family.labels.first() {
+            Some(label) => format!(
+                "{} (<code>{}</code>)",
+                escape_html(label),
+                escape_html(family_code)
+            ),
+            None => format!("<code>{}</code>", escape_html(family_code)),
+        },
+        None if measurement_families.is_empty() => {
+            format!("<code>{}</code>", escape_html(family_code))
+        }
+        None => format!(
+            "<code>{}</code> <em>(unknown measurement family)</em>",
+            escape_html(family_code)
+        ),
+    };
+
+    let unit_html = match (field("default_metric_unit"), family) {
+        (None, _) => "<em>no default unit</em>".to_string(),
+        (Some(unit_code), Some(family)) => match family.units.get(unit_code) {
+            Some(unit) => {
+                let label = unit.labels.first().unwrap_or(unit_code);
+                match unit.symbol.as_deref().filter(|s| !s.is_empty()) {
+                    Some(symbol) => format!(
+                        "{} (<code>{}</code>, {})",
+                        escape_html(label),
+                        escape_html(unit_code),
+                        escape_html(symbol)
+                    ),
+                    None => format!(
+                        "{} (<code>{}</code>)",
+                        escape_html(label),
+                        escape_html(unit_code)
+                    ),
+                }
+            }
+            None => format!(
+                "<code>{}</code> <em>(not a unit of this family)</em>",
+                escape_html(unit_code)
+            ),
+        },
+        (Some(unit_code), None) => format!("<code>{}</code>", escape_html(unit_code)),
+    };
+
+    Some(format!("{}, default {}", family_html, unit_html))
+}
+
+/// An attribute's constraints one per line, see [`attribute_constraints`].
+fn constraints_cell(attr: &Attribute, max_length: Option<usize>) -> Cell {
+    let constraints = attribute_constraints(attr);
+    if constraints.is_empty() {
+        return Cell::dash();
+    }
+    let html = constraints
+        .iter()
+        .map(|constraint| {
+            let (text, full) = truncate_cell(constraint, max_length);
+            text + &full
+        })
+        .collect::<Vec<_>>()
+        .join("<br/>");
+    Cell {
+        html,
+        text: constraints.join("; "),
+    }
+}
+
+/// A plain-text summary of an attribute's validation settings: uniqueness, validation
+/// rule, number and date bounds, text length, file size and allowed extensions. Empty
+/// when the attribute constrains nothing.
+fn attribute_constraints(attr: &Attribute) -> Vec<String> {
+    let field = |name: &str| attr.extra.get(name).and_then(constraint_value);
+    let mut constraints = Vec::new();
+
+    if attr.unique {
+        constraints.push("Unique".to_string());
+    }
+    match field("validation_rule").as_deref() {
+        Some("regexp") => match field("validation_regexp") {
+            Some(regexp) => constraints.push(format!("Matches {}", regexp)),
+            None => constraints.push("Validation: regexp".to_string()),
+        },
+        Some(rule) => constraints.push(format!("Validation: {}", rule)),
+        None => {}
+    }
+    for (kind, min, max) in [
+        ("Number", field("number_min"), field("number_max")),
+        ("Date", field("date_min"), field("date_max")),
+    ] {
+        match (min, max) {
+            (Some(min), Some(max)) => {
+                constraints.push(format!("{} between {} and {}", kind, min, max))
+            }
+            (Some(min), None) => constraints.push(format!("{} at least {}", kind, min)),
+            (None, Some(max)) => constraints.push(format!("{} at most {}", kind, max)),
+            (None, None) => {}
+        }
+    }
+    if let Some(max) = field("max_characters") {
+        constraints.push(format!("Up to {} characters", max));
+    }
+    if let Some(max) = field("max_file_size") {
+        constraints.push(format!("Up to {} MB", max));
+    }
+    let extensions: Vec<&str> = attr
+        .extra
+        .get("allowed_extensions")
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    if !extensions.is_empty() {
+        constraints.push(format!("Extensions: {}", extensions.join(", ")));
+    }
+
+    constraints
+}
+
+/// A constraint setting as text; `None` when unset. Akeneo sends decimals as strings
+/// like `"10.0000"`, so trailing zeros are dropped.
+fn constraint_value(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => return None,
+    };
+    if text.is_empty() {
+        return None;
+    }
+    if text.contains('.') && text.parse::<f64>().is_ok() {
+        return Some(text.trim_end_matches('0').trim_end_matches('.').to_string());
+    }
+    Some(text)
+}
+
+fn attribute_column_header(column: &str) -> String {
+    match column {
+        "code" => "Code".to_string(),
+        "label" => "Label".to_string(),
+        "type" => "Type".to_string(),
+        "group" => "Group".to_string(),
+        "scopable" => "Scopable".to_string(),
+        "localizable" => "Localizable".to_string(),
+        "unique" => "Unique".to_string(),
+        "constraints" => "Constraints".to_string(),
+        field => capitalize(&escape_html(&field.replace('_', " "))),
+    }
+}
+
+/// Render the Categories section with a structured table.
+fn render_categories_section(
+    categories: &[Category],
+    tables: &TableOptions,
+    expand: &ExpandOptions,
+    theme: &Theme,
+) -> String {
+    let mut out = String::new();
+    out.push_str(&section_heading(theme, "Categories", categories.len(), "Blue"));
+
+    if categories.is_empty() {
+        out.push_str("<p><em>No categories.</em></p>");
+        return out;
+    }
+
+    let mut table = String::new();
+    table.push_str("<table data-layout=\"full-width\"><tbody>");
+    table.push_str("<tr><th>Code</th><th>Labels</th><th>Parent</th><th>Updated</th></tr>");
+
+    for cat in categories {
+        let updated = cat.updated.as_deref().map(|updated| theme.format.timestamp(updated));
+        table.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&cat.code),
+            render_labels_inline(&cat.labels, tables.max_cell_length),
+            escape_html(or_dash(&cat.parent)),
+            escape_html(or_dash(&updated)),
+        ));
+    }
+
+    table.push_str("</tbody></table>");
+
+    out.push_str(&collapsible(expand, "categories", categories.len(), &table));
+    out
+}
+
+/// Render the label completeness page for translators: a matrix of entity types by locale
+/// with the number of items missing a label, then per locale the codes missing one. The
+/// locales are the channels' (every label locale when no channel lists any); a blank label
+/// counts as missing.
+fn render_label_completeness_page(snapshot: &Snapshot, options: &RenderOptions) -> String {
+    let theme = &options.theme;
+    let mut out = String::new();
+
+    let mut locales: BTreeSet<&str> = snapshot
+        .channels
+        .iter()
+        .flat_map(|ch| ch.locales.iter().map(String::as_str))
+        .collect();
+
+    // (entity type, code, labels) of everything that has labels
+    let option_codes: Vec<(String, &Labels)> = snapshot
+        .attribute_options
+        .iter()
+        .flat_map(|(attribute, options)| {
+            options
+                .iter()
+                .map(move |o| (format!("{}/{}", attribute, o.code), &o.labels))
+        })
+        .collect();
+    let types: Vec<(&str, Vec<(&str, &Labels)>)> = vec![
+        (
+            "Channels",
+            snapshot.channels.iter().map(|c| (c.code.as_str(), &c.labels)).collect(),
+        ),
+        (
+            "Families",
+            snapshot.families.iter().map(|f| (f.code.as_str(), &f.labels)).collect(),
+        ),
+        (
+            "Attributes",
+            snapshot.attributes.iter().map(|a| (a.code.as_str(), &a.labels)).collect(),
+        ),
+        (
+            "Categories",
+            snapshot.categories.iter().map(|c| (c.code.as_str(), &c.labels)).collect(),
+        ),
+        (
+            "Attribute Options",
+            option_codes.iter().map(|(code, labels)| (code.as_str(), *labels)).collect(),
+        ),
+    ];
+    if locales.is_empty() {
+        locales = types
+            .iter()
+            .flat_map(|(_, items)| items.iter().flat_map(|(_, labels)| labels.0.keys()))
+            .map(String::as_str)
+            .collect();
+    }
+
+    // Entity type -> locale -> codes missing a label in that locale
+    let missing: Vec<BTreeMap<&str, Vec<&str>>> = types
+        .iter()
+        .map(|(_, items)| {
+            locales
+                .iter()
+                .map(|&locale| {
+                    let codes = items
+                        .iter()
+                        .filter(|(_, labels)| {
+                            labels.get(locale).is_none_or(|label| label.trim().is_empty())
+                        })
+                        .map(|(code, _)| *code)
+                        .collect();
+                    (locale, codes)
+                })
+                .collect()
+        })
+        .collect();
+
+    out.push_str("<h1>Label Completeness</h1>");
+    out.push_str(
+        "<p>Items without a label in each locale of the channels, for translators.</p>",
+    );
+    out.push_str("<hr/>");
+
+    if locales.is_empty() {
+        out.push_str("<p><em>No locales found: no channel lists locales and no item has \
+                      labels.</em></p>");
+        return out;
+    }
+
+    // ── Matrix ──────────────────────────────────────────────────────────
+    let total_missing: usize = missing
+        .iter()
+        .flat_map(|by_locale| by_locale.values().map(Vec::len))
+        .sum();
+    out.push_str(&section_heading(
+        theme,
+        "Missing Labels",
+        total_missing,
+        if total_missing == 0 { "Green" } else { "Yellow" },
+    ));
+    out.push_str("<table data-layout=\"full-width\"><tbody><tr><th>Type</th>");
+    for locale in &locales {
+        out.push_str(&format!("<th><code>{}</code></th>", escape_html(locale)));
+    }
+    out.push_str("</tr>");
+    for ((name, items), by_locale) in types.iter().zip(&missing) {
+        out.push_str(&format!("<tr><td><strong>{}</strong> ({})</td>", name, items.len()));
+        for locale in &locales {
+            let count = by_locale[locale].len();
+            let color = if count == 0 { "Green" } else { "Yellow" };
+            out.push_str(&format!("<td>{}</td>", status_lozenge(theme, count, color)));
+        }
+        out.push_str("</tr>");
+    }
+    out.push_str("</tbody></table>");
+
+    // ── Drill-down per locale ───────────────────────────────────────────
+    for locale in &locales {
+        let rows: Vec<(&str, &Vec<&str>)> = types
+            .iter()
+            .zip(&missing)
+            .map(|((name, _), by_locale)| (*name, &by_locale[locale]))
+            .filter(|(_, codes)| !codes.is_empty())
+            .collect();
+        let count: usize = rows.iter().map(|(_, codes)| codes.len()).sum();
+        out.push_str(&section_heading(
+            theme,
+            &format!("Missing in {}", locale),
+            count,
+            if count == 0 { "Green" } else { "Yellow" },
+        ));
+        if rows.is_empty() {
+            out.push_str("<p><em>Every item has a label in this locale.</em></p>");
+            continue;
+        }
+        let mut table = String::from("<table data-layout=\"full-width\"><tbody>");
+        table.push_str("<tr><th>Type</th><th>Missing</th><th>Codes</th></tr>");
+        for (name, codes) in &rows {
+            table.push_str(&format!(
+                "<tr><td><strong>{}</strong></td><td>{}</td><td>{}</td></tr>",
+                name,
+                codes.len(),
+                code_list(codes),
+            ));
+        }
+        table.push_str("</tbody></table>");
+        out.push_str(&collapsible(&options.expand, "label_completeness", count, &table));
+    }
+
+    out
+}
+
+/// Render one page per category tree (a root category and its descendants) with the tree
+/// drawn as a diagram, or listed as a table when it has more than
+/// `CategoryDiagrams.max_nodes` categories. Categories whose parent is missing are in no
+/// tree; the model health page lists them.
+fn render_category_tree_pages(
+    categories: &[Category],
+    options: &RenderOptions,
+) -> Vec<SnapshotChildPage> {
+    let diagrams = &options.category_diagrams;
+    let Some(format) = diagrams.format else {
+        return Vec::new();
+    };
+
+    let mut children: BTreeMap<&str, Vec<&Category>> = BTreeMap::new();
+    for cat in categories {
+        if let Some(parent) = &cat.parent {
+            children.entry(parent.as_str()).or_default().push(cat);
+        }
+    }
+
+    categories
+        .iter()
+        .filter(|cat| cat.parent.is_none())
+        .map(|root| {
+            let nodes = category_tree_nodes(root, &children);
+            let label = root.labels.first().unwrap_or(&root.code);
+            SnapshotChildPage {
+                body: render_category_tree_page(label, &nodes, format, diagrams, options),
+                code: root.code.clone(),
+                label: label.to_string(),
+                label_group: None,
+            }
+        })
+        .collect()
+}
+
+/// The categories of a tree in depth-first order, each with its depth (the root is 0).
+/// A category is visited once, so duplicate codes cannot loop.
+fn category_tree_nodes<'a>(
+    root: &'a Category,
+    children: &BTreeMap<&str, Vec<&'a Category>>,
+) -> Vec<(&'a Category, usize)> {
+    let mut nodes = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut stack = vec![(root, 0)];
+    while let Some((cat, depth)) = stack.pop() {
+        if !seen.insert(cat.code.as_str()) {
+            continue;
+        }
+        nodes.push((cat, depth));
+        if let Some(kids) = children.get(cat.code.as_str()) {
+            stack.extend(kids.iter().rev().map(|kid| (*kid, depth + 1)));
+        }
+    }
+    nodes
+}
+
+fn render_category_tree_page(
+    label: &str,
+    nodes: &[(&Category, usize)],
+    format: DiagramFormat,
+    diagrams: &CategoryDiagrams,
+    options: &RenderOptions,
+) -> String {
+    let mut out = String::new();
+    let (root, _) = nodes[0];
+    let depth = nodes.iter().map(|(_, depth)| depth + 1).max().unwrap_or(1);
+
+    out.push_str(&format!("<h1>Category Tree: {}</h1>", escape_html(label)));
+    out.push_str(&format!(
+        "<p>The <code>{}</code> category tree \u{2014} {} {}, {} {} deep.</p>",
+        escape_html(&root.code),
+        nodes.len(),
+        if nodes.len() == 1 { "category" } else { "categories" },
+        depth,
+        if depth == 1 { "level" } else { "levels" },
+    ));
+    out.push_str("<hr/>");
+
+    if nodes.len() <= diagrams.max_nodes {
+        let source = match format {
+            DiagramFormat::Mermaid => mermaid_tree(nodes),
+            DiagramFormat::PlantUml => plantuml_tree(nodes),
+        };
+        out.push_str(&format!(
+            "<ac:structured-macro ac:name=\"{}\">\
+             <ac:plain-text-body><![CDATA[{}]]></ac:plain-text-body>\
+             </ac:structured-macro>",
+            escape_html(&diagrams.macro_name),
+            source.replace("]]>", "]]]]><![CDATA[>"),
+        ));
+        return out;
+    }
+
+    out.push_str(&info_panel(&format!(
+        "This tree has more than {} categories, too many to draw, so it is listed instead.",
+        diagrams.max_nodes
+    )));
+    out.push_str(&section_heading(&options.theme, "Categories", nodes.len(), "Blue"));
+    out.push_str("<table data-layout=\"full-width\"><tbody>");
+    out.push_str("<tr><th>Code</th><th>Labels</th><th>Level</th><th>Parent</th></tr>");
+    for (cat, depth) in nodes {
+        out.push_str(&format!(
+            "<tr><td>{}<code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            "\u{00a0}\u{00a0}\u{00a0}\u{00a0}".repeat(*depth),
+            escape_html(&cat.code),
+            render_labels_inline(&cat.labels, options.tables.max_cell_length),
+            depth,
+            escape_html(or_dash(&cat.parent)),
+        ));
+    }
+    out.push_str("</tbody></table>");
+    out
+}
+
+/// A category's diagram node text: its first label, on one line.
+fn diagram_label(cat: &Category) -> String {
+    let label = cat.labels.first().unwrap_or(&cat.code);
+    label.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A left-to-right Mermaid flowchart of a category tree. Nodes are numbered so codes never
+/// need escaping.
+fn mermaid_tree(nodes: &[(&Category, usize)]) -> String {
+    let ids: BTreeMap<&str, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (cat, _))| (cat.code.as_str(), i))
+        .collect();
+    let mut out = String::from("graph LR\n");
+    for (i, (cat, _)) in nodes.iter().enumerate() {
+        out.push_str(&format!(
+            "  n{}[\"{}\"]\n",
+            i,
+            diagram_label(cat).replace('"', "#quot;")
+        ));
+        if let Some(parent) = cat.parent.as_deref().and_then(|p| ids.get(p)) {
+            out.push_str(&format!("  n{} --> n{}\n", parent, i));
+        }
+    }
+    out
+}
+
+/// A PlantUML work breakdown structure of a category tree, one `*` per level.
+fn plantuml_tree(nodes: &[(&Category, usize)]) -> String {
+    let mut out = String::from("@startwbs\n");
+    for (cat, depth) in nodes {
+        out.push_str(&format!("{} {}\n", "*".repeat(depth + 1), diagram_label(cat)));
+    }
+    out.push_str("@endwbs\n");
+    out
+}
+
+/// Render the Attribute Options section, grouped by parent attribute code. `hidden`
+/// options belong to attributes left out by the attribute filters. Attributes with more
+/// than `TableOptions.option_page_threshold` options get links to their option pages
+/// instead of a table; the pages are returned next to the section.
+fn render_attribute_options_sections(
+    options: &BTreeMap<String, Vec<AttributeOption>>,
+    hidden: usize,
+    tables: &TableOptions,
+    expand: &ExpandOptions,
+    theme: &Theme,
+) -> (String, Vec<SnapshotOptionPage>) {
+    let mut out = String::new();
+    let mut pages = Vec::new();
+
+    if options.is_empty() {
+        out.push_str(&section_heading(theme, "Attribute Options", 0, "Grey"));
+        out.push_str(&filtered_note(hidden, "attribute option", "attribute options"));
+        out.push_str("<p><em>No attribute options.</em></p>");
+        return (out, pages);
+    }
+
+    let total: usize = options.values().map(|o| o.len()).sum();
+    out.push_str(&section_heading(theme, "Attribute Options", total, "Yellow"));
+    out.push_str(&filtered_note(hidden, "attribute option", "attribute options"));
+
+    let mut sections = String::new();
+    for (attr_code, attr_options) in options {
+        sections.push_str(&format!(
+            "<h3>Attribute: <code>{}</code> {}</h3>",
+            escape_html(attr_code),
+            status_lozenge(theme, attr_options.len(), "Grey"),
+        ));
+
+        if attr_options.is_empty() {
+            sections.push_str("<p><em>No options.</em></p>");
+            continue;
+        }
+
+        let threshold = tables.option_page_threshold;
+        if threshold > 0 && attr_options.len() > threshold {
+            let attr_pages = render_option_pages(attr_code, attr_options, tables, theme);
+            let size = tables.option_page_size.max(1);
+            let links: Vec<String> = attr_pages
+                .iter()
+                .map(|page| {
+                    let first = (page.page - 1) * size + 1;
+                    let last = (page.page * size).min(attr_options.len());
+                    let range = option_range(theme, first, last);
+                    option_page_link(attr_code, page.page, page.pages, &range)
+                })
+                .collect();
+            sections.push_str(&format!(
+                "<p><em>Listed on {} pages:</em> {}</p>",
+                attr_pages.len(),
+                links.join(" \u{00B7} "),
+            ));
+            pages.extend(attr_pages);
+            continue;
+        }
+
+        sections.push_str(&option_table(attr_options, tables));
+    }
+
+    out.push_str(&collapsible(expand, "attribute_options", total, &sections));
+    (out, pages)
+}
+
+/// The options table of an attribute (or one page of it).
+fn option_table(attr_options: &[AttributeOption], tables: &TableOptions) -> String {
+    let mut out = String::new();
+    out.push_str("<table data-layout=\"full-width\"><tbody>");
+    out.push_str("<tr><th>Code</th><th>Label</th><th>Sort Order</th></tr>");
+
+    for opt in attr_options {
+        let sort_order = opt
+            .sort_order
+            .map(|n| n.to_string())
+            .unwrap_or_else(|| "\u{2014}".to_string());
+
+        let (label, label_full) =
+            truncate_cell(opt.labels.first().unwrap_or("\u{2014}"), tables.max_cell_length);
+        out.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>{}{}</td><td>{}</td></tr>",
+            escape_html(&opt.code),
+            label,
+            label_full,
+            escape_html(&sort_order),
+        ));
+    }
+
+    out.push_str("</tbody></table>");
+    out
+}
+
+/// Split an attribute's options into pages of `TableOptions.option_page_size` rows, each
+/// with links to the previous and next page and back to the root page.
+fn render_option_pages(
+    attr_code: &str,
+    attr_options: &[AttributeOption],
+    tables: &TableOptions,
+    theme: &Theme,
+) -> Vec<SnapshotOptionPage> {
+    let size = tables.option_page_size.max(1);
+    let pages = attr_options.len().div_ceil(size);
+    attr_options
+        .chunks(size)
+        .enumerate()
+        .map(|(i, rows)| {
+            let page = i + 1;
+            let mut nav = Vec::new();
+            if page > 1 {
+                nav.push(option_page_link(attr_code, page - 1, pages, "\u{2190} Previous"));
+            }
+            nav.push(format!("Page {} of {}", page, pages));
+            if page < pages {
+                nav.push(option_page_link(attr_code, page + 1, pages, "Next \u{2192}"));
+            }
+            let nav = format!("<p>{}</p>", nav.join(" \u{00B7} "));
+
+            let mut body = String::new();
+            body.push_str(&format!(
+                "<h1>Attribute Options: <code>{}</code></h1>",
+                escape_html(attr_code)
+            ));
+            body.push_str(&format!(
+                "<p>Options {} of {}, listed here since the attribute has too many for the \
+                 {}.</p>",
+                option_range(theme, i * size + 1, i * size + rows.len()),
+                theme.format.count(attr_options.len()),
+                page_link(ROOT_TITLE_MARKER, "snapshot overview"),
+            ));
+            body.push_str(&nav);
+            body.push_str(&option_table(rows, tables));
+            body.push_str(&nav);
+            SnapshotOptionPage {
+                attribute: attr_code.to_string(),
+                page,
+                pages,
+                body,
+            }
+        })
+        .collect()
+}
+
+/// `first`–`last` (1-based option positions), or just `first` for one option.
+fn option_range(theme: &Theme, first: usize, last: usize) -> String {
+    match first == last {
+        true => theme.format.count(first),
+        false => format!("{}\u{2013}{}", theme.format.count(first), theme.format.count(last)),
+    }
+}
+
+/// A link to an attribute's option page, titled after the root page.
+fn option_page_link(attr_code: &str, page: usize, pages: usize, text: &str) -> String {
+    let title = format!(
+        "{}{}",
+        ROOT_TITLE_MARKER,
+        escape_html(&option_page_title("", attr_code, page, pages))
+    );
+    page_link(&title, text)
+}
+
+/// A link to the page titled `title` (already escaped) in the same space.
+fn page_link(title: &str, text: &str) -> String {
+    format!(
+        "<ac:link><ri:page ri:content-title=\"{}\"/><ac:link-body>{}</ac:link-body></ac:link>",
+        title,
+        escape_html(text)
+    )
+}
+
+// =============================================================================
+// Family detail child pages
+// =============================================================================
+
+/// Render a detailed family page with configuration metadata, attribute requirements,
+/// and an enriched attributes table cross-referenced against the snapshot's attribute data.
+/// The attributes table leaves out the attributes `filter` hides, and lists constraints
+/// with `TableOptions.extended_attributes`.
+fn render_family_detail_page(
+    family: &Family,
+    all_attributes: &[Attribute],
+    measurement_families: &[MeasurementFamily],
+    filter: &AttributeFilter,
+    tables: &TableOptions,
+    theme: &Theme,
+) -> String {
+    let mut out = String::new();
+
+    let code = family.code.as_str();
+    let label = family.labels.first().unwrap_or(code);
+
+    // Build an attribute lookup map for cross-referencing
+    let attr_map: BTreeMap<&str, &Attribute> = all_attributes
+        .iter()
+        .map(|a| (a.code.as_str(), a))
+        .collect();
+
+    // ── Title ────────────────────────────────────────────────────────────
+    out.push_str(&format!("<h1>{}</h1>", escape_html(label),));
+    out.push_str(&format!(
+        "<p><code>{}</code> \u{2014} Family configuration and associated attributes from the Akeneo PIM snapshot.</p>",
+        escape_html(code),
+    ));
+    out.push_str("<hr/>");
+
+    // ── Family Configuration ────────────────────────────────────────────
+    out.push_str("<h2>Family Configuration</h2>");
+
+    let parent = family.parent.as_deref().unwrap_or("\u{2014} No parent");
+    let total_attrs = family.attributes.len();
+
+    // Render as a 3-column x 2-row metadata table
+    out.push_str("<table data-layout=\"full-width\"><tbody>");
+    out.push_str("<tr>");
+    out.push_str(&format!(
+        "<td><strong>Family Code</strong><br/><code>{}</code></td>",
+        escape_html(code),
+    ));
+    out.push_str(&format!(
+        "<td><strong>Label</strong><br/>{}</td>",
+        escape_html(label),
+    ));
+    out.push_str(&format!(
+        "<td><strong>Parent</strong><br/>{}</td>",
+        escape_html(parent),
+    ));
+    out.push_str("</tr><tr>");
+    out.push_str(&format!(
+        "<td><strong>Attribute as Label</strong><br/><code>{}</code></td>",
+        escape_html(or_dash(&family.attribute_as_label)),
+    ));
+    out.push_str(&format!(
+        "<td><strong>Attribute as Image</strong><br/><code>{}</code></td>",
+        escape_html(or_dash(&family.attribute_as_image)),
+    ));
+    out.push_str(&format!(
+        "<td><strong>Total Attributes</strong><br/><strong style=\"font-size: 24px;\">{}</strong></td>",
+        total_attrs,
+    ));
+    out.push_str("</tr></tbody></table>");
+    if tables.raw_json {
+        out.push_str(&raw_json_expand(family));
+    }
+
+    // ── Usage (only when the snapshot has product statistics) ───────────
+    if let Some(usage) = family.usage() {
+        out.push_str(&render_family_usage(&usage, theme));
+    }
+
+    // ── Attribute Requirements ───────────────────────────────────────────
+    out.push_str("<h2>Attribute Requirements</h2>");
+
+    let requirements = &family.attribute_requirements;
+
+    if requirements.is_empty() {
+        out.push_str("<p><em>No attribute requirements defined.</em></p>");
+    } else {
+        out.push_str("<table data-layout=\"full-width\"><tbody>");
+        out.push_str("<tr><th>Channel</th><th>Required Attributes</th></tr>");
+
+        let mut channels: Vec<_> = requirements.iter().collect();
+        channels.sort_by_key(|(name, _)| name.to_lowercase());
+
+        for (channel, attrs) in channels {
+            let attrs = attrs
+                .iter()
+                .map(|s| format!("<code>{}</code>", escape_html(s)))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            out.push_str(&format!(
+                "<tr><td><strong>{}</strong></td><td>{}</td></tr>",
+                escape_html(channel),
+                attrs,
+            ));
+        }
+
+        out.push_str("</tbody></table>");
+    }
+
+    // ── Family Attributes (enriched) ────────────────────────────────────
+    let shown: Vec<&String> = family
+        .attributes
+        .iter()
+        .filter(|code| {
+            let group = attr_map.get(code.as_str()).and_then(|a| a.group.as_deref());
+            filter.keeps(code, group)
+        })
+        .collect();
+    out.push_str(&format!(
+        "<h2>Family Attributes {}</h2>",
+        status_lozenge(theme, shown.len(), "Purple"),
+    ));
+    out.push_str(&filtered_note(total_attrs - shown.len(), "attribute", "attributes"));
+
+    if family.attributes.is_empty() {
+        out.push_str("<p><em>No attributes in this family.</em></p>");
+        return out;
+    }
+    if shown.is_empty() {
+        return out;
+    }
+
+    out.push_str("<table data-layout=\"full-width\"><tbody>");
+    out.push_str("<tr><th>Attribute Code</th><th>Type</th><th>Group</th><th>Scopable</th><th>Localizable</th><th>Required</th>");
+    if tables.extended_attributes {
+        out.push_str("<th>Constraints</th>");
+    }
+    out.push_str("</tr>");
+
+    for attr_code in shown {
+        // Cross-reference with the snapshot's attributes data
+        let (attr_type, group, scopable, localizable) = match attr_map.get(attr_code.as_str()) {
+            Some(attr) => (
+                or_dash(&attr.attr_type),
+                or_dash(&attr.group),
+                attr.scopable,
+                attr.localizable,
+            ),
+            None => ("\u{2014}", "\u{2014}", false, false),
+        };
+        let unit = attr_map
+            .get(attr_code.as_str())
+            .and_then(|attr| metric_unit(attr, measurement_families))
+            .map(|unit| format!("<br/>{}", unit))
+            .unwrap_or_default();
+
+        // Determine which channels require this attribute
+        let required_channels: Vec<&str> = requirements
+            .iter()
+            .filter(|(_, req_attrs)| req_attrs.contains(attr_code))
+            .map(|(ch, _)| ch.as_str())
+            .collect();
+
+        let required_display = if required_channels.is_empty() {
+            "\u{2014}".to_string()
+        } else {
+            required_channels
+                .iter()
+                .map(|ch| escape_html(ch))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        out.push_str(&format!(
+            "<tr><td><code>{}</code></td><td><code>{}</code>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+            escape_html(attr_code),
+            escape_html(attr_type),
+            unit,
+            escape_html(group),
+            check_icon(theme, scopable),
+            check_icon(theme, localizable),
+            required_display,
+        ));
+        if tables.extended_attributes {
+            let constraints = match attr_map.get(attr_code.as_str()) {
+                Some(attr) => constraints_cell(attr, tables.max_cell_length),
+                None => Cell::dash(),
+            };
+            out.push_str(&format!("<td>{}</td>", constraints.html));
+        }
+        out.push_str("</tr>");
+        if tables.raw_json
+            && let Some(attr) = attr_map.get(attr_code.as_str())
+        {
+            let columns = if tables.extended_attributes { 7 } else { 6 };
+            out.push_str(&format!(
+                "<tr><td colspan=\"{}\">{}</td></tr>",
+                columns,
+                raw_json_expand(attr)
+            ));
+        }
+    }
+
+    out.push_str("</tbody></table>");
+    out
+}
+
+/// Render a family's "Usage" card: product and variant counts, then completeness per
+/// channel (per locale when the snapshot breaks it down).
+fn render_family_usage(usage: &FamilyUsage, theme: &Theme) -> String {
+    let mut out = String::from("<h2>Usage</h2>");
+
+    if usage.products.is_some() || usage.variants.is_some() {
+        out.push_str("<table data-layout=\"full-width\"><tbody><tr>");
+        for (label, count) in [("Products", usage.products), ("Variants", usage.variants)] {
+            out.push_str(&format!(
+                "<td><strong>{}</strong><br/><strong style=\"font-size: 24px;\">{}</strong></td>",
+                label,
+                count.map_or_else(|| "\u{2014}".to_string(), |c| c.to_string()),
+            ));
+        }
+        out.push_str("</tr></tbody></table>");
+    }
+
+    if !usage.completeness.is_empty() {
+        out.push_str("<table data-layout=\"full-width\"><tbody>");
+        out.push_str("<tr><th>Channel</th><th>Completeness</th></tr>");
+        for (channel, completeness) in &usage.completeness {
+            let cell = match completeness {
+                ChannelCompleteness::Overall(percent) => completeness_lozenge(theme, *percent),
+                ChannelCompleteness::PerLocale(locales) if locales.is_empty() => {
+                    "\u{2014}".to_string()
+                }
+                ChannelCompleteness::PerLocale(locales) => locales
+                    .iter()
+                    .map(|(locale, percent)| {
+                        format!(
+                            "<code>{}</code> {}",
+                            escape_html(locale),
+                            completeness_lozenge(theme, *percent)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            };
+            out.push_str(&format!(
+                "<tr><td><strong>{}</strong></td><td>{}</td></tr>",
+                escape_html(channel),
+                cell,
+            ));
+        }
+        out.push_str("</tbody></table>");
+    }
+
+    out
+}
+
+/// A completeness percentage as a lozenge: green from 90%, yellow from 60%, else red.
+fn completeness_lozenge(theme: &Theme, percent: f64) -> String {
+    let color = if percent >= 90.0 {
+        "Green"
+    } else if percent >= 60.0 {
+        "Yellow"
+    } else {
+        "Red"
+    };
+    status_text(theme, &format!("{:.0}%", percent), color)
+}
+
+// =============================================================================
+// Requirements coverage child page
+// =============================================================================
+
+/// Render the requirements coverage page for governance reviews: per channel, how many
+/// attributes families require; which attributes every family requires on every channel;
+/// and which families require nothing at all.
+fn render_coverage_page(snapshot: &Snapshot, theme: &Theme) -> String {
+    let mut out = String::new();
+    let families = &snapshot.families;
+
+    let attr_map: BTreeMap<&str, &Attribute> = snapshot
+        .attributes
+        .iter()
+        .map(|a| (a.code.as_str(), a))
+        .collect();
+
+    out.push_str("<h1>Requirements Coverage</h1>");
+    out.push_str("<p>Attribute requirements of every family, summarized per channel.</p>");
+    out.push_str("<hr/>");
+
+    // Channels in snapshot order, then any channel only referenced by requirements
+    let mut channels: Vec<(&str, Option<&Channel>)> = snapshot
+        .channels
+        .iter()
+        .map(|ch| (ch.code.as_str(), Some(ch)))
+        .collect();
+    let referenced: BTreeSet<&str> = families
+        .iter()
+        .flat_map(|f| f.attribute_requirements.keys().map(String::as_str))
+        .collect();
+    for code in referenced {
+        if !channels.iter().any(|(c, _)| *c == code) {
+            channels.push((code, None));
+        }
+    }
+
+    // ── Per-channel summary ─────────────────────────────────────────────
+    out.push_str(&section_heading(theme, "Channels", channels.len(), "Green"));
+
+    if channels.is_empty() {
+        out.push_str("<p><em>No channels.</em></p>");
+    } else {
+        out.push_str("<table data-layout=\"full-width\"><tbody>");
+        out.push_str("<tr><th>Channel</th><th>Families with Requirements</th><th>Required Attributes</th><th>Total Requirements</th><th>Required in Every Family</th></tr>");
+
+        for (code, channel) in &channels {
+            let lists: Vec<&Vec<String>> = families
+                .iter()
+                .filter_map(|f| f.attribute_requirements.get(*code))
+                .filter(|attrs| !attrs.is_empty())
+                .collect();
+            let distinct: BTreeSet<&str> = lists
+                .iter()
+                .flat_map(|attrs| attrs.iter().map(String::as_str))
+                .collect();
+            let total: usize = lists.iter().map(|attrs| attrs.len()).sum();
+
+            // Only possible when every family has requirements on this channel
+            let every_family_has_requirements =
+                !families.is_empty() && lists.len() == families.len();
+            let in_every_family: Vec<&str> = if every_family_has_requirements {
+                distinct
+                    .iter()
+                    .copied()
+                    .filter(|attr| lists.iter().all(|attrs| attrs.iter().any(|a| a == attr)))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            let name = match channel.and_then(|ch| ch.labels.first()) {
+                Some(label) => format!(
+                    "<strong>{}</strong> <code>{}</code>",
+                    escape_html(label),
+                    escape_html(code),
+                ),
+                None => format!("<code>{}</code>", escape_html(code)),
+            };
+            let name = if channel.is_none() {
+                format!("{} <em>(not in snapshot)</em>", name)
+            } else {
+                name
+            };
+
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{} / {}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                name,
+                lists.len(),
+                families.len(),
+                status_lozenge(theme, distinct.len(), "Purple"),
+                total,
+                code_list(&in_every_family),
+            ));
+        }
+
+        out.push_str("</tbody></table>");
+    }
+
+    // ── Attributes required everywhere ──────────────────────────────────
+    let everywhere: Vec<&str> = match families.first() {
+        Some(first) if !channels.is_empty() => {
+            let candidates: BTreeSet<&str> = first
+                .attribute_requirements
+                .values()
+                .flatten()
+                .map(String::as_str)
+                .collect();
+            candidates
+                .into_iter()
+                .filter(|attr| {
+                    families.iter().all(|f| {
+                        channels.iter().all(|(code, _)| {
+                            f.attribute_requirements
+                                .get(*code)
+                                .is_some_and(|attrs| attrs.iter().any(|a| a == attr))
+                        })
+                    })
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+
+    out.push_str(&section_heading(theme, "Required Everywhere", everywhere.len(), "Blue"));
+    out.push_str("<p>Attributes every family requires on every channel.</p>");
+
+    if everywhere.is_empty() {
+        out.push_str("<p><em>No attribute is required everywhere.</em></p>");
+    } else {
+        out.push_str("<table data-layout=\"full-width\"><tbody>");
+        out.push_str("<tr><th>Code</th><th>Label</th><th>Type</th></tr>");
+        for code in &everywhere {
+            let attr = attr_map.get(code);
+            out.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td><code>{}</code></td></tr>",
+                escape_html(code),
+                escape_html(attr.and_then(|a| a.labels.first()).unwrap_or("\u{2014}")),
+                escape_html(attr.map(|a| or_dash(&a.attr_type)).unwrap_or("\u{2014}")),
+            ));
+        }
+        out.push_str("</tbody></table>");
+    }
+
+    // ── Families without requirements ───────────────────────────────────
+    let unrequired: Vec<&Family> = families
+        .iter()
+        .filter(|f| f.attribute_requirements.values().all(Vec::is_empty))
+        .collect();
+
+    out.push_str(&section_heading(
+        theme,
+        "Families Without Requirements",
+        unrequired.len(),
+        if unrequired.is_empty() { "Grey" } else { "Red" },
+    ));
+
+    if unrequired.is_empty() {
+        out.push_str("<p><em>Every family requires at least one attribute.</em></p>");
+    } else {
+        out.push_str("<table data-layout=\"full-width\"><tbody>");
+        out.push_str("<tr><th>Code</th><th>Label</th><th>Attributes</th></tr>");
+        for fam in &unrequired {
+            out.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+                escape_html(&fam.code),
+                escape_html(fam.labels.first().unwrap_or("\u{2014}")),
+                status_lozenge(theme, fam.attributes.len(), "Blue"),
+            ));
+        }
+        out.push_str("</tbody></table>");
+    }
+
+    out
+}
+
+// =============================================================================
+// Model health page
+// =============================================================================
+
+/// Render the model health page: inconsistencies found in the snapshot itself — attributes
+/// in no family, attributes families reference that the snapshot does not define, families
+/// labelled by an attribute they do not have, and categories whose parent is missing.
+fn render_health_page(snapshot: &Snapshot, theme: &Theme) -> String {
+    let mut out = String::new();
+    let families = &snapshot.families;
+
+    out.push_str("<h1>Model Health</h1>");
+    out.push_str(
+        "<p>Orphans and broken references in the data model, derived from the snapshot \
+         alone.</p>",
+    );
+    out.push_str("<hr/>");
+
+    // ── Orphan attributes ───────────────────────────────────────────────
+    let in_families: BTreeSet<&str> = families
+        .iter()
+        .flat_map(|f| f.attributes.iter().map(String::as_str))
+        .collect();
+    let orphans: Vec<&Attribute> = snapshot
+        .attributes
+        .iter()
+        .filter(|a| !in_families.contains(a.code.as_str()))
+        .collect();
+
+    out.push_str(&section_heading(
+        theme,
+        "Orphan Attributes",
+        orphans.len(),
+        if orphans.is_empty() { "Grey" } else { "Yellow" },
+    ));
+    out.push_str("<p>Attributes no family uses.</p>");
+
+    if orphans.is_empty() {
+        out.push_str("<p><em>Every attribute belongs to at least one family.</em></p>");
+    } else {
+        out.push_str("<table data-layout=\"full-width\"><tbody>");
+        out.push_str("<tr><th>Code</th><th>Label</th><th>Type</th><th>Group</th></tr>");
+        for attr in &orphans {
+            out.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td><code>{}</code></td><td>{}</td></tr>",
+                escape_html(&attr.code),
+                escape_html(attr.labels.first().unwrap_or("\u{2014}")),
+                escape_html(or_dash(&attr.attr_type)),
+                escape_html(or_dash(&attr.group)),
+            ));
+        }
+        out.push_str("</tbody></table>");
+    }
+
+    // ── Undefined attributes ────────────────────────────────────────────
+    let defined: BTreeSet<&str> = snapshot.attributes.iter().map(|a| a.code.as_str()).collect();
+    let mut undefined: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for fam in families {
+        let referenced = fam
+            .attributes
+            .iter()
+            .chain(fam.attribute_requirements.values().flatten())
+            .chain(&fam.attribute_as_label)
+            .chain(&fam.attribute_as_image);
+        for code in referenced.filter(|code| !defined.contains(code.as_str())) {
+            undefined.entry(code).or_default().insert(&fam.code);
+        }
+    }
+
+    out.push_str(&section_heading(
+        theme,
+        "Undefined Attributes",
+        undefined.len(),
+        if undefined.is_empty() { "Grey" } else { "Red" },
+    ));
+    out.push_str(
+        "<p>Attributes families reference (as a family attribute, requirement, label or \
+         image) that are missing from the snapshot's attributes.</p>",
+    );
+
+    if undefined.is_empty() {
+        out.push_str("<p><em>Every attribute families reference is defined.</em></p>");
+    } else {
+        out.push_str("<table data-layout=\"full-width\"><tbody>");
+        out.push_str("<tr><th>Code</th><th>Referenced By</th></tr>");
+        for (code, referencing) in &undefined {
+            let referencing: Vec<&str> = referencing.iter().copied().collect();
+            out.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td></tr>",
+                escape_html(code),
+                code_list(&referencing),
+            ));
+        }
+        out.push_str("</tbody></table>");
+    }
+
+    // ── Label attributes outside the family ─────────────────────────────
+    let mislabelled: Vec<(&Family, &str)> = families
+        .iter()
+        .filter_map(|f| {
+            let label_attr = f.attribute_as_label.as_deref()?;
+            (!f.attributes.iter().any(|a| a == label_attr)).then_some((f, label_attr))
+        })
+        .collect();
+
+    out.push_str(&section_heading(
+        theme,
+        "Label Attribute Not in Family",
+        mislabelled.len(),
+        if mislabelled.is_empty() { "Grey" } else { "Red" },
+    ));
+    out.push_str(
+        "<p>Families whose <code>attribute_as_label</code> is not one of their attributes.</p>",
+    );
+
+    if mislabelled.is_empty() {
+        out.push_str("<p><em>Every family is labelled by one of its own attributes.</em></p>");
+    } else {
+        out.push_str("<table data-layout=\"full-width\"><tbody>");
+        out.push_str("<tr><th>Family</th><th>Label</th><th>Attribute as Label</th></tr>");
+        for (fam, label_attr) in &mislabelled {
+            out.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td><code>{}</code></td></tr>",
+                escape_html(&fam.code),
+                escape_html(fam.labels.first().unwrap_or("\u{2014}")),
+                escape_html(label_attr),
+            ));
+        }
+        out.push_str("</tbody></table>");
+    }
+
+    // ── Categories with a missing parent ────────────────────────────────
+    let category_codes: BTreeSet<&str> =
+        snapshot.categories.iter().map(|c| c.code.as_str()).collect();
+    let detached: Vec<(&Category, &str)> = snapshot
+        .categories
+        .iter()
+        .filter_map(|c| {
+            let parent = c.parent.as_deref()?;
+            (!category_codes.contains(parent)).then_some((c, parent))
+        })
+        .collect();
 
-    for fam in families {
-        let code = get_code(fam);
-        let label = get_label(fam).unwrap_or_else(|| "\u{2014}".to_string());
-        let attr_count = fam
-            .get("attributes")
-            .and_then(|v| v.as_array())
-            .map(|a| a.len())
-            .unwrap_or(0);
-        let label_attr = fam
-            .get("attribute_as_label")
-            .and_then(|v| v.as_str())
-            .unwrap_or("\u{2014}");
-        let image_attr = fam
-            .get("attribute_as_image")
-            .and_then(|v| v.as_str())
-            .unwrap_or("\u{2014}");
+    out.push_str(&section_heading(
+        theme,
+        "Categories With Missing Parent",
+        detached.len(),
+        if detached.is_empty() { "Grey" } else { "Red" },
+    ));
+    out.push_str("<p>Categories whose parent category is not in the snapshot.</p>");
 
-        out.push_str(&format!(
-            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td><code>{}</code></td><td><code>{}</code></td></tr>",
-            escape_html(code),
-            escape_html(&label),
-            status_lozenge(attr_count, "Blue"),
-            escape_html(label_attr),
-            escape_html(image_attr),
-        ));
+    if detached.is_empty() {
+        out.push_str("<p><em>Every parent category is in the snapshot.</em></p>");
+    } else {
+        out.push_str("<table data-layout=\"full-width\"><tbody>");
+        out.push_str("<tr><th>Code</th><th>Labels</th><th>Missing Parent</th></tr>");
+        for (cat, parent) in &detached {
+            out.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td><code>{}</code></td></tr>",
+                escape_html(&cat.code),
+                render_labels_inline(&cat.labels, None),
+                escape_html(parent),
+            ));
+        }
+        out.push_str("</tbody></table>");
     }
 
-    out.push_str("</tbody></table>");
     out
 }
 
-/// Render the Attributes section with a structured table.
-fn render_attributes_section(attributes: &[Value]) -> String {
-    let mut out = String::new();
-    out.push_str(&section_heading("Attributes", attributes.len(), "Purple"));
+// =============================================================================
+// Family group pages
+// =============================================================================
 
-    if attributes.is_empty() {
-        out.push_str("<p><em>No attributes.</em></p>");
-        return out;
-    }
+/// Render a family group page, the index page its family pages are nested under (see
+/// `FamilyGrouping`). The family pages are listed by Confluence's `children` macro, so the
+/// list follows the page tree.
+pub fn render_family_group_page(group: &str, families: usize) -> String {
+    format!(
+        "<h1>Families: {}</h1>\
+         <p>{} family page(s) in this group; this page is regenerated on every publish.</p>\
+         <hr/>\
+         <ac:structured-macro ac:name=\"children\">\
+         <ac:parameter ac:name=\"all\">false</ac:parameter>\
+         </ac:structured-macro>",
+        escape_html(group),
+        families
+    )
+}
 
-    out.push_str("<table data-layout=\"full-width\"><tbody>");
-    out.push_str("<tr><th>Code</th><th>Label</th><th>Type</th><th>Group</th><th>Scopable</th><th>Localizable</th></tr>");
-
-    for attr in attributes {
-        let code = get_code(attr);
-        let label = get_label(attr).unwrap_or_else(|| "\u{2014}".to_string());
-        let attr_type = attr
-            .get("type")
-            .and_then(|v| v.as_str())
-            .unwrap_or("\u{2014}");
-        let group = attr
-            .get("group")
-            .and_then(|v| v.as_str())
-            .unwrap_or("\u{2014}");
-        let scopable = attr
-            .get("scopable")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-        let localizable = attr
-            .get("localizable")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
+// =============================================================================
+// Comparison matrix page
+// =============================================================================
+
+/// Render a comparison matrix (`matrix::build`) as one table: a row per entity, a column
+/// per snapshot, each cell the entity's configuration in that snapshot. Rows whose
+/// entity is missing from some snapshots or configured differently are flagged.
+pub fn render_matrix_page(matrix: &Matrix, theme: &Theme) -> String {
+    let differing = matrix.rows.iter().filter(|row| !row.consistent).count();
+    let mut out = String::new();
+    out.push_str("<h1>Comparison Matrix</h1>");
+    out.push_str(&format!(
+        "<p>{} of {} compared entities differ between these {} snapshots.</p>",
+        differing,
+        matrix.rows.len(),
+        matrix.snapshots.len(),
+    ));
+    out.push_str("<hr/>");
 
+    out.push_str("<table data-layout=\"full-width\"><tbody><tr><th>Entity</th>");
+    for snapshot in &matrix.snapshots {
+        let label = match &snapshot.label {
+            Some(label) => escape_html(label),
+            None => format!("<code>{}</code>", snapshot.id),
+        };
         out.push_str(&format!(
-            "<tr><td><code>{}</code></td><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
-            escape_html(code),
-            escape_html(&label),
-            escape_html(attr_type),
-            escape_html(group),
-            check_icon(scopable),
-            check_icon(localizable),
+            "<th>{}<br/>{} ({})</th>",
+            label,
+            escape_html(&snapshot.server),
+            theme.format.date(snapshot.completed_at),
         ));
     }
-
+    out.push_str("</tr>");
+    for row in &matrix.rows {
+        let flag = if row.consistent {
+            String::new()
+        } else {
+            format!(" {}", status_text(theme, "Differs", "Yellow"))
+        };
+        out.push_str(&format!(
+            "<tr><td>{} <code>{}</code>{}</td>",
+            escape_html(&row.kind.replace('_', " ")),
+            escape_html(&row.code),
+            flag,
+        ));
+        for cell in &row.cells {
+            let cell = match cell {
+                Some(configuration) if configuration.is_empty() => {
+                    check_icon(theme, true).to_string()
+                }
+                Some(configuration) => format!(
+                    "{} {}",
+                    check_icon(theme, true),
+                    escape_html(configuration)
+                ),
+                None => check_icon(theme, false).to_string(),
+            };
+            out.push_str(&format!("<td>{}</td>", cell));
+        }
+        out.push_str("</tr>");
+    }
     out.push_str("</tbody></table>");
     out
 }
 
-/// Render the Categories section with a structured table.
-fn render_categories_section(categories: &[Value]) -> String {
+// =============================================================================
+// Versioned publishing index page
+// =============================================================================
+
+/// Render the index page for versioned publishing: a table linking every published
+/// snapshot page, newest first.
+pub fn render_history_index(history: &[PublicationRow], options: &RenderOptions) -> String {
+    let theme = &options.theme;
     let mut out = String::new();
-    out.push_str(&section_heading("Categories", categories.len(), "Blue"));
+    out.push_str("<h1>Akeneo Model Snapshots</h1>");
+    out.push_str("<p>Every published snapshot of the PIM data model. Each snapshot is kept as a separate page; this index is regenerated on every publish.</p>");
+    out.push_str("<hr/>");
+    out.push_str(&section_heading(theme, "Published snapshots", history.len(), "Blue"));
 
-    if categories.is_empty() {
-        out.push_str("<p><em>No categories.</em></p>");
+    if history.is_empty() {
+        out.push_str("<p><em>No snapshots published yet.</em></p>");
         return out;
     }
 
     out.push_str("<table data-layout=\"full-width\"><tbody>");
-    out.push_str("<tr><th>Code</th><th>Labels</th><th>Parent</th><th>Updated</th></tr>");
-
-    for cat in categories {
-        let code = get_code(cat);
-        let labels = render_labels_inline(cat);
-        let parent = cat
-            .get("parent")
-            .and_then(|v| v.as_str())
-            .unwrap_or("\u{2014}");
-        let updated = cat
-            .get("updated")
-            .and_then(|v| v.as_str())
-            .unwrap_or("\u{2014}");
+    out.push_str("<tr><th>Page</th><th>Snapshot</th><th>Completed</th><th>Published</th></tr>");
 
+    for entry in history {
         out.push_str(&format!(
-            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
-            escape_html(code),
-            labels,
-            escape_html(parent),
-            escape_html(updated),
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&entry.web_url),
+            escape_html(&entry.page_title),
+            escape_html(or_dash(&entry.snapshot_label)),
+            theme.format.date_time(entry.snapshot_completed_at, false),
+            theme.format.date_time(entry.published_at, false),
         ));
     }
 
@@ -558,279 +2847,114 @@ fn render_categories_section(categories: &[Value]) -> String {
     out
 }
 
-/// Render the Attribute Options section, grouped by parent attribute.
-/// The `options_value` is expected to be a JSON object mapping attribute codes
-/// to arrays of option objects.
-fn render_attribute_options_sections(options_value: Option<&Value>) -> String {
-    let mut out = String::new();
-
-    let Some(obj) = options_value.and_then(|v| v.as_object()) else {
-        out.push_str(&section_heading("Attribute Options", 0, "Grey"));
-        out.push_str("<p><em>No attribute options.</em></p>");
-        return out;
-    };
-
-    let total: usize = obj
-        .values()
-        .filter_map(|v| v.as_array())
-        .map(|a| a.len())
-        .sum();
-
-    out.push_str(&section_heading("Attribute Options", total, "Yellow"));
+/// Series colours of the activity charts: added, removed, changed.
+const ACTIVITY_COLORS: &str = "#36B37E,#FF5630,#FFAB00";
 
-    let mut attr_codes: Vec<&String> = obj.keys().collect();
-    attr_codes.sort();
-
-    for attr_code in attr_codes {
-        let options = match obj.get(attr_code).and_then(|v| v.as_array()) {
-            Some(arr) => arr,
-            None => continue,
-        };
-
-        out.push_str(&format!(
-            "<h3>Attribute: <code>{}</code> {}</h3>",
-            escape_html(attr_code),
-            status_lozenge(options.len(), "Grey"),
-        ));
-
-        if options.is_empty() {
-            out.push_str("<p><em>No options.</em></p>");
-            continue;
-        }
-
-        out.push_str("<table data-layout=\"full-width\"><tbody>");
-        out.push_str("<tr><th>Code</th><th>Label</th><th>Sort Order</th></tr>");
-
-        for opt in options {
-            let code = get_code(opt);
-            let label = get_label(opt).unwrap_or_else(|| "\u{2014}".to_string());
-            let sort_order = opt
-                .get("sort_order")
-                .map(|v| match v {
-                    Value::Number(n) => n.to_string(),
-                    _ => v.to_string(),
-                })
-                .unwrap_or_else(|| "\u{2014}".to_string());
+/// Render the model activity page: per category, a stacked bar chart (Confluence `chart`
+/// macro) of the items each of the charted diffs added, removed and changed. `rows` come
+/// from `db::fetch_diff_activity`, oldest diff first.
+pub fn render_activity_page(rows: &[DiffActivityRow], options: &RenderOptions) -> String {
+    let theme = &options.theme;
+    let mut out = String::new();
 
-            out.push_str(&format!(
-                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
-                escape_html(code),
-                escape_html(&label),
-                escape_html(&sort_order),
-            ));
+    // Diffs in order, named by their after-snapshot (with the time when labels repeat,
+    // since the chart merges equal names)
+    let mut firsts: Vec<&DiffActivityRow> = Vec::new();
+    for row in rows {
+        if firsts.last().map(|r| r.diff_id) != Some(row.diff_id) {
+            firsts.push(row);
         }
-
-        out.push_str("</tbody></table>");
     }
-
-    out
-}
-
-// =============================================================================
-// Family detail child pages
-// =============================================================================
-
-/// Render a detailed family page with configuration metadata, attribute requirements,
-/// and an enriched attributes table cross-referenced against the snapshot's attribute data.
-fn render_family_detail_page(family: &Value, all_attributes: &[Value]) -> String {
-    let mut out = String::new();
-
-    let code = get_code(family);
-    let label = get_label(family).unwrap_or_else(|| code.to_string());
-
-    // Build an attribute lookup map for cross-referencing
-    let attr_map: HashMap<&str, &Value> = all_attributes
+    let label = |row: &DiffActivityRow| match &row.after_label {
+        Some(label) => label.clone(),
+        None => row.after_completed_at.format("%Y-%m-%d").to_string(),
+    };
+    let diffs: Vec<(Uuid, String)> = firsts
         .iter()
-        .filter_map(|a| a.get("code").and_then(|c| c.as_str()).map(|c| (c, a)))
+        .map(|row| {
+            let name = label(row);
+            if firsts.iter().filter(|other| label(other) == name).count() > 1 {
+                let at = row.after_completed_at.format("%Y-%m-%d %H:%M");
+                (row.diff_id, format!("{} ({})", name, at))
+            } else {
+                (row.diff_id, name)
+            }
+        })
         .collect();
 
-    // ── Title ────────────────────────────────────────────────────────────
-    out.push_str(&format!("<h1>{}</h1>", escape_html(&label),));
+    out.push_str("<h1>Model Activity</h1>");
     out.push_str(&format!(
-        "<p><code>{}</code> \u{2014} Family configuration and associated attributes from the Akeneo PIM snapshot.</p>",
-        escape_html(code),
+        "<p>Items added, removed and changed per category by the last {} published {}. \
+         This page is regenerated on every diff publish.</p>",
+        diffs.len(),
+        if diffs.len() == 1 { "diff" } else { "diffs" },
     ));
     out.push_str("<hr/>");
 
-    // ── Family Configuration ────────────────────────────────────────────
-    out.push_str("<h2>Family Configuration</h2>");
-
-    let parent = family
-        .get("parent")
-        .and_then(|v| v.as_str())
-        .unwrap_or("\u{2014} No parent");
-    let label_attr = family
-        .get("attribute_as_label")
-        .and_then(|v| v.as_str())
-        .unwrap_or("\u{2014}");
-    let image_attr = family
-        .get("attribute_as_image")
-        .and_then(|v| v.as_str())
-        .unwrap_or("\u{2014}");
-    let family_attrs = family.get("attributes").and_then(|v| v.as_array());
-    let total_attrs = family_attrs.map(|a| a.len()).unwrap_or(0);
-
-    // Render as a 3-column x 2-row metadata table
-    out.push_str("<table data-layout=\"full-width\"><tbody>");
-    out.push_str("<tr>");
-    out.push_str(&format!(
-        "<td><strong>Family Code</strong><br/><code>{}</code></td>",
-        escape_html(code),
-    ));
-    out.push_str(&format!(
-        "<td><strong>Label</strong><br/>{}</td>",
-        escape_html(&label),
-    ));
-    out.push_str(&format!(
-        "<td><strong>Parent</strong><br/>{}</td>",
-        escape_html(parent),
-    ));
-    out.push_str("</tr><tr>");
-    out.push_str(&format!(
-        "<td><strong>Attribute as Label</strong><br/><code>{}</code></td>",
-        escape_html(label_attr),
-    ));
-    out.push_str(&format!(
-        "<td><strong>Attribute as Image</strong><br/><code>{}</code></td>",
-        escape_html(image_attr),
-    ));
-    out.push_str(&format!(
-        "<td><strong>Total Attributes</strong><br/><strong style=\"font-size: 24px;\">{}</strong></td>",
-        total_attrs,
-    ));
-    out.push_str("</tr></tbody></table>");
-
-    // ── Attribute Requirements ───────────────────────────────────────────
-    out.push_str("<h2>Attribute Requirements</h2>");
-
-    let requirements = family
-        .get("attribute_requirements")
-        .and_then(|v| v.as_object());
-
-    match requirements {
-        Some(reqs) if !reqs.is_empty() => {
-            out.push_str("<table data-layout=\"full-width\"><tbody>");
-            out.push_str("<tr><th>Channel</th><th>Required Attributes</th></tr>");
-
-            let mut channels: Vec<_> = reqs.iter().collect();
-            channels.sort_by_key(|(name, _)| name.to_lowercase());
-
-            for (channel, attrs_val) in channels {
-                let attrs = attrs_val
-                    .as_array()
-                    .map(|arr| {
-                        arr.iter()
-                            .filter_map(|v| v.as_str())
-                            .map(|s| format!("<code>{}</code>", escape_html(s)))
-                            .collect::<Vec<_>>()
-                            .join(", ")
-                    })
-                    .unwrap_or_else(|| "\u{2014}".to_string());
-
-                out.push_str(&format!(
-                    "<tr><td><strong>{}</strong></td><td>{}</td></tr>",
-                    escape_html(channel),
-                    attrs,
-                ));
-            }
-
-            out.push_str("</tbody></table>");
-        }
-        _ => {
-            out.push_str("<p><em>No attribute requirements defined.</em></p>");
-        }
+    if diffs.is_empty() {
+        out.push_str("<p><em>No diffs published yet.</em></p>");
+        return out;
     }
 
-    // ── Family Attributes (enriched) ────────────────────────────────────
-    out.push_str(&format!(
-        "<h2>Family Attributes {}</h2>",
-        status_lozenge(total_attrs, "Purple"),
-    ));
-
-    match family_attrs {
-        Some(attrs) if !attrs.is_empty() => {
-            // Build a set of required attributes per channel for this family
-            let required_map: HashMap<&str, Vec<&str>> = requirements
-                .map(|reqs| {
-                    reqs.iter()
-                        .filter_map(|(ch, arr)| {
-                            arr.as_array().map(|a| {
-                                (
-                                    ch.as_str(),
-                                    a.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>(),
-                                )
-                            })
-                        })
-                        .collect()
-                })
-                .unwrap_or_default();
-
-            out.push_str("<table data-layout=\"full-width\"><tbody>");
-            out.push_str("<tr><th>Attribute Code</th><th>Type</th><th>Group</th><th>Scopable</th><th>Localizable</th><th>Required</th></tr>");
-
-            for attr_val in attrs {
-                let attr_code = attr_val.as_str().unwrap_or("unknown");
-
-                // Cross-reference with the snapshot's attributes data
-                let (attr_type, group, scopable, localizable) =
-                    if let Some(attr_data) = attr_map.get(attr_code) {
-                        (
-                            attr_data
-                                .get("type")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("\u{2014}"),
-                            attr_data
-                                .get("group")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("\u{2014}"),
-                            attr_data
-                                .get("scopable")
-                                .and_then(|v| v.as_bool())
-                                .unwrap_or(false),
-                            attr_data
-                                .get("localizable")
-                                .and_then(|v| v.as_bool())
-                                .unwrap_or(false),
-                        )
-                    } else {
-                        ("\u{2014}", "\u{2014}", false, false)
-                    };
-
-                // Determine which channels require this attribute
-                let required_channels: Vec<&str> = required_map
-                    .iter()
-                    .filter(|(_, req_attrs)| req_attrs.contains(&attr_code))
-                    .map(|(ch, _)| *ch)
-                    .collect();
-
-                let required_display = if required_channels.is_empty() {
-                    "\u{2014}".to_string()
-                } else {
-                    required_channels
-                        .iter()
-                        .map(|ch| escape_html(ch))
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                };
+    let mut categories: BTreeMap<&str, BTreeMap<Uuid, &DiffActivityRow>> = BTreeMap::new();
+    for row in rows {
+        categories
+            .entry(row.category.as_str())
+            .or_default()
+            .insert(row.diff_id, row);
+    }
 
-                out.push_str(&format!(
-                    "<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
-                    escape_html(attr_code),
-                    escape_html(attr_type),
-                    escape_html(group),
-                    check_icon(scopable),
-                    check_icon(localizable),
-                    required_display,
-                ));
-            }
+    let mut unchanged = Vec::new();
+    for (category, by_diff) in &categories {
+        let total: i32 = by_diff.values().map(|r| r.added + r.removed + r.changed).sum();
+        if total == 0 {
+            unchanged.push(*category);
+            continue;
+        }
+        let title = capitalize(&category.replace('_', " "));
+        out.push_str(&section_heading(theme, &title, total as usize, "Blue"));
 
-            out.push_str("</tbody></table>");
+        let mut table = String::from("<table><tbody><tr><th>Change</th>");
+        for (_, name) in &diffs {
+            table.push_str(&format!("<th>{}</th>", escape_html(name)));
         }
-        _ => {
-            out.push_str("<p><em>No attributes in this family.</em></p>");
+        table.push_str("</tr>");
+        for kind in ["Added", "Removed", "Changed"] {
+            table.push_str(&format!("<tr><td>{}</td>", kind));
+            for (id, _) in &diffs {
+                let count = by_diff.get(id).map_or(0, |r| match kind {
+                    "Added" => r.added,
+                    "Removed" => r.removed,
+                    _ => r.changed,
+                });
+                table.push_str(&format!("<td>{}</td>", count));
+            }
+            table.push_str("</tr>");
         }
+        table.push_str("</tbody></table>");
+
+        out.push_str(&format!(
+            "<ac:structured-macro ac:name=\"chart\">\
+             <ac:parameter ac:name=\"type\">bar</ac:parameter>\
+             <ac:parameter ac:name=\"stacked\">true</ac:parameter>\
+             <ac:parameter ac:name=\"dataOrientation\">horizontal</ac:parameter>\
+             <ac:parameter ac:name=\"colors\">{}</ac:parameter>\
+             <ac:parameter ac:name=\"width\">800</ac:parameter>\
+             <ac:parameter ac:name=\"height\">300</ac:parameter>\
+             <ac:parameter ac:name=\"yLabel\">Items</ac:parameter>\
+             <ac:rich-text-body>{}</ac:rich-text-body>\
+             </ac:structured-macro>",
+            ACTIVITY_COLORS, table,
+        ));
     }
 
+    if !unchanged.is_empty() {
+        let unchanged: Vec<String> = unchanged.iter().map(|c| c.replace('_', " ")).collect();
+        out.push_str(&format!(
+            "<p><em>No changes to {} in these diffs.</em></p>",
+            escape_html(&unchanged.join(", "))
+        ));
+    }
     out
 }
 
@@ -839,12 +2963,13 @@ fn render_family_detail_page(family: &Value, all_attributes: &[Value]) -> String
 // =============================================================================
 
 /// Render a Confluence status macro (lozenge badge) in storage format.
-fn status_badge(label: &str, count: usize, color: &str) -> String {
+fn status_badge(theme: &Theme, label: &str, count: usize, color: &str) -> String {
     let (title, colour) = if count == 0 {
         (format!("{}: 0", label), "Grey")
     } else {
-        (format!("{}: {}", label, count), color)
+        (format!("{}: {}", label, theme.format.count(count)), color)
     };
+    let colour = theme.color(colour);
     format!(
         "<ac:structured-macro ac:name=\"status\">\
          <ac:parameter ac:name=\"title\">{}</ac:parameter>\
@@ -855,17 +2980,52 @@ fn status_badge(label: &str, count: usize, color: &str) -> String {
     )
 }
 
+/// A status lozenge with a text title.
+fn status_text(theme: &Theme, title: &str, color: &str) -> String {
+    format!(
+        "<ac:structured-macro ac:name=\"status\">\
+         <ac:parameter ac:name=\"title\">{}</ac:parameter>\
+         <ac:parameter ac:name=\"colour\">{}</ac:parameter>\
+         </ac:structured-macro>",
+        escape_html(title),
+        theme.color(color),
+    )
+}
+
 /// Render a Confluence status macro (count-only lozenge) in storage format.
-fn status_lozenge(count: usize, color: &str) -> String {
+fn status_lozenge(theme: &Theme, count: usize, color: &str) -> String {
     format!(
         "<ac:structured-macro ac:name=\"status\">\
          <ac:parameter ac:name=\"title\">{}</ac:parameter>\
          <ac:parameter ac:name=\"colour\">{}</ac:parameter>\
          </ac:structured-macro>",
-        count, color,
+        theme.format.count(count),
+        theme.color(color),
     )
 }
 
+/// Render a Confluence expand macro (collapsed by default) around a block of content.
+fn expand_macro(title: &str, body_html: &str) -> String {
+    format!(
+        "<ac:structured-macro ac:name=\"expand\">\
+         <ac:parameter ac:name=\"title\">{}</ac:parameter>\
+         <ac:rich-text-body>{}</ac:rich-text-body>\
+         </ac:structured-macro>",
+        escape_html(title),
+        body_html,
+    )
+}
+
+/// The note under a section heading saying how many items the attribute filters left
+/// out; empty when none were.
+fn filtered_note(hidden: usize, singular: &str, plural: &str) -> String {
+    match hidden {
+        0 => String::new(),
+        1 => format!("<p><em>1 {} hidden by the attribute filters.</em></p>", singular),
+        n => format!("<p><em>{} {} hidden by the attribute filters.</em></p>", n, plural),
+    }
+}
+
 /// Render a Confluence info panel in storage format.
 fn info_panel(body_html: &str) -> String {
     format!(
@@ -876,6 +3036,16 @@ fn info_panel(body_html: &str) -> String {
     )
 }
 
+/// Format a duration as e.g. `45s`, `2m 14s` or `1h 5m`.
+fn format_duration(duration: TimeDelta) -> String {
+    let secs = duration.num_seconds().max(0);
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
+}
+
 fn capitalize(s: &str) -> String {
     let mut chars = s.chars();
     match chars.next() {
@@ -885,7 +3055,7 @@ fn capitalize(s: &str) -> String {
 }
 
 /// Escape characters that have special meaning in HTML/XHTML.
-fn escape_html(s: &str) -> String {
+pub fn escape_html(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -897,68 +3067,216 @@ fn escape_html(s: &str) -> String {
 // =============================================================================
 
 /// Render a section heading with an uppercase label and a count lozenge.
-fn section_heading(label: &str, count: usize, color: &str) -> String {
+fn section_heading(theme: &Theme, label: &str, count: usize, color: &str) -> String {
     format!(
         "<h2>{} {}</h2>",
         escape_html(&label.to_uppercase()),
-        status_lozenge(count, color),
+        status_lozenge(theme, count, color),
     )
 }
 
-/// Render a checkmark or X icon for boolean values.
-fn check_icon(val: bool) -> &'static str {
-    if val {
-        "\u{2705}" // green checkmark emoji
+/// Emit a section body as-is, or inside a collapsed expand macro when the section's
+/// expand policy says so. The section heading (with its count lozenge) stays outside.
+fn collapsible(expand: &ExpandOptions, section: &str, rows: usize, body_html: &str) -> String {
+    if expand.should_collapse(section, rows) {
+        expand_macro(&format!("Show all {} rows", rows), body_html)
     } else {
-        "\u{274C}" // red X emoji
+        body_html.to_string()
     }
 }
 
-/// Extract the "code" field from a JSON object.
-fn get_code(item: &Value) -> &str {
-    item.get("code")
-        .and_then(|v| v.as_str())
-        .unwrap_or("unknown")
+/// Render a boolean as a checkmark / X icon, or as Yes / No with the text theme.
+fn check_icon(theme: &Theme, val: bool) -> &'static str {
+    match (theme.booleans, val) {
+        (BooleanStyle::Emoji, true) => "\u{2705}", // green checkmark emoji
+        (BooleanStyle::Emoji, false) => "\u{274C}", // red X emoji
+        (BooleanStyle::Text, true) => "Yes",
+        (BooleanStyle::Text, false) => "No",
+    }
 }
 
-/// Extract the first available label from a JSON object's "labels" field.
-fn get_label(item: &Value) -> Option<String> {
-    item.get("labels")
-        .and_then(|v| v.as_object())
-        .and_then(|labels| labels.values().next())
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string())
+/// Render codes as comma-separated `<code>` tags, or an em dash when there are none.
+fn code_list(codes: &[&str]) -> String {
+    if codes.is_empty() {
+        return "\u{2014}".to_string();
+    }
+    codes
+        .iter()
+        .map(|c| format!("<code>{}</code>", escape_html(c)))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-/// Extract an array of strings from a JSON object field.
-fn get_string_array(item: &Value, field: &str) -> Vec<String> {
-    item.get(field)
-        .and_then(|v| v.as_array())
-        .map(|arr| {
-            arr.iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .collect()
-        })
-        .unwrap_or_default()
+/// A cell of a configurable table: its HTML and the plain text rows are sorted by.
+struct Cell {
+    html: String,
+    text: String,
 }
 
-/// Render labels as inline locale-tagged text (e.g., "en_GB: Label, de_AT: Label").
-fn render_labels_inline(item: &Value) -> String {
-    item.get("labels")
-        .and_then(|v| v.as_object())
-        .map(|labels| {
-            labels
-                .iter()
-                .map(|(locale, val)| {
-                    let text = val.as_str().unwrap_or("\u{2014}");
-                    format!(
-                        "<strong>{}</strong>: {}",
-                        escape_html(locale),
-                        escape_html(text),
-                    )
-                })
-                .collect::<Vec<_>>()
-                .join(", ")
+impl Cell {
+    /// Text cut off at `max_length` characters, see [`truncate_cell`]. Rich text (a value
+    /// with HTML tags) is rendered sanitized instead, see [`Cell::rich`].
+    fn truncated(value: &str, max_length: Option<usize>) -> Self {
+        if sanitize::looks_like_html(value) {
+            return Self::rich(value, max_length);
+        }
+        let (html, full) = truncate_cell(value, max_length);
+        Self {
+            html: html + &full,
+            text: value.to_string(),
+        }
+    }
+
+    /// Rich text rendered through the HTML sanitizer. When its text is longer than
+    /// `max_length` characters the cell shows the start of the text, with the formatted
+    /// value in an expand macro below. Sorts by its text.
+    fn rich(value: &str, max_length: Option<usize>) -> Self {
+        let html = sanitize::sanitize_html(value);
+        let text = sanitize::html_to_text(value);
+        let cut = max_length.and_then(|max| text.char_indices().nth(max));
+        let html = match cut {
+            Some((end, _)) => format!(
+                "{}\u{2026}{}",
+                escape_html(&text[..end]),
+                expand_macro(&format!("Show all {} characters", text.chars().count()), &html),
+            ),
+            None => html,
+        };
+        Self { html, text }
+    }
+
+    fn code(value: &str) -> Self {
+        Self {
+            html: format!("<code>{}</code>", escape_html(value)),
+            text: value.to_string(),
+        }
+    }
+
+    fn dash() -> Self {
+        Self {
+            html: "\u{2014}".to_string(),
+            text: String::new(),
+        }
+    }
+
+    fn check(theme: &Theme, value: bool) -> Self {
+        Self {
+            html: check_icon(theme, value).to_string(),
+            text: value.to_string(),
+        }
+    }
+
+    /// A raw JSON field: booleans as check icons, arrays as code lists, maps of text (e.g.
+    /// per-locale guidelines) one locale per line, other values as text cut off at
+    /// `max_length`.
+    fn json(value: Option<&Value>, max_length: Option<usize>, theme: &Theme) -> Self {
+        match value {
+            None | Some(Value::Null) => Self::dash(),
+            Some(Value::Bool(b)) => Self::check(theme, *b),
+            Some(Value::String(s)) => Self::truncated(s, max_length),
+            Some(Value::Object(map))
+                if !map.is_empty() && map.values().all(|v| v.is_string() || v.is_null()) =>
+            {
+                let entries: Vec<(&String, Self)> = map
+                    .iter()
+                    .filter_map(|(key, v)| Some((key, Self::truncated(v.as_str()?, max_length))))
+                    .collect();
+                if entries.is_empty() {
+                    return Self::dash();
+                }
+                Self {
+                    html: entries
+                        .iter()
+                        .map(|(key, cell)| {
+                            format!("<code>{}</code>: {}", escape_html(key), cell.html)
+                        })
+                        .collect::<Vec<_>>()
+                        .join("<br/>"),
+                    text: entries
+                        .iter()
+                        .map(|(key, cell)| format!("{}: {}", key, cell.text))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                }
+            }
+            Some(Value::Array(items)) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect();
+                let codes: Vec<&str> = items.iter().map(String::as_str).collect();
+                Self {
+                    html: code_list(&codes),
+                    text: items.join(", "),
+                }
+            }
+            Some(other) => Self::truncated(&other.to_string(), max_length),
+        }
+    }
+}
+
+/// Escape a cell value, cut off after `max_length` characters with an ellipsis. Returns
+/// the visible text and, for a cut value, a collapsed expand macro with the full value
+/// (empty otherwise), to place after it in the same cell.
+fn truncate_cell(value: &str, max_length: Option<usize>) -> (String, String) {
+    let cut = max_length.and_then(|max| value.char_indices().nth(max));
+    let Some((end, _)) = cut else {
+        return (escape_html(value), String::new());
+    };
+    (
+        format!("{}\u{2026}", escape_html(&value[..end])),
+        expand_macro(
+            &format!("Show all {} characters", value.chars().count()),
+            &format!("<p>{}</p>", escape_html(value)),
+        ),
+    )
+}
+
+/// Sort table rows by the `sort` column, numerically when both values are numbers, else
+/// case-insensitively. Rows stay in their original order when the column is not shown.
+fn sort_rows(rows: &mut [Vec<Cell>], columns: &[String], sort: Option<&SortOrder>) {
+    let Some(sort) = sort else {
+        return;
+    };
+    let Some(index) = columns.iter().position(|col| *col == sort.column) else {
+        return;
+    };
+
+    rows.sort_by(|a, b| {
+        let (a, b) = (&a[index].text, &b[index].text);
+        let ordering = match (a.parse::<f64>(), b.parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.total_cmp(&y),
+            _ => a.to_lowercase().cmp(&b.to_lowercase()),
+        };
+        if sort.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+/// Borrow an optional string field, falling back to an em dash when absent.
+fn or_dash(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("\u{2014}")
+}
+
+/// Render labels as inline locale-tagged text (e.g., "en_GB: Label, de_AT: Label"), each
+/// cut off at `max_length` characters.
+fn render_labels_inline(labels: &Labels, max_length: Option<usize>) -> String {
+    if labels.is_empty() {
+        return "\u{2014}".to_string();
+    }
+
+    labels
+        .iter()
+        .map(|(locale, text)| {
+            let (text, full) = truncate_cell(text, max_length);
+            format!("<strong>{}</strong>: {}{}", escape_html(locale), text, full)
         })
-        .unwrap_or_else(|| "\u{2014}".to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
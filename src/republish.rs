@@ -0,0 +1,184 @@
+use anyhow::{bail, Context, Result};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::audit::{self, Actor};
+use crate::db::{self, PublicationKind, PublicationRecord};
+use crate::extract::Query;
+use crate::jobs::JobHandle;
+use crate::publish::{self, PublishMode, PublishParams};
+use crate::renderer::RENDERER_VERSION;
+use crate::{admin, AppState, ErrorResponse};
+
+#[derive(Deserialize, Serialize)]
+pub struct RepublishParams {
+    /// Republish the publications produced by a renderer version below this one (default:
+    /// the current `RENDERER_VERSION`).
+    renderer_older_than: Option<i32>,
+}
+
+/// A republished publication.
+#[derive(Serialize)]
+struct Republished {
+    publication_id: Uuid,
+    kind: String,
+    snapshot_id: Uuid,
+    diff_id: Option<Uuid>,
+    /// The renderer version that produced the pages before.
+    renderer_version: i32,
+    page_url: String,
+}
+
+/// A publication that could not be republished; it keeps its renderer version, so a
+/// later call retries it.
+#[derive(Serialize)]
+struct RepublishFailure {
+    publication_id: Uuid,
+    kind: String,
+    snapshot_id: Uuid,
+    diff_id: Option<Uuid>,
+    error: String,
+}
+
+#[derive(Serialize)]
+struct RepublishResponse {
+    status: &'static str,
+    renderer_version: i32,
+    republished: Vec<Republished>,
+    failed: Vec<RepublishFailure>,
+}
+
+/// POST /api/republish?renderer_older_than=X (admin)
+///
+/// Re-renders and republishes, one after the other, every live publication recorded by a
+/// renderer older than `X`. Each publication is audited; a failure is reported and the
+/// next one still runs.
+pub async fn handle_republish(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Actor(actor): Actor,
+    Query(params): Query<RepublishParams>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    let older_than = params.renderer_older_than.unwrap_or(RENDERER_VERSION);
+
+    let publications = match db::fetch_stale_publications(&state.pool, older_than).await {
+        Ok(publications) => publications,
+        Err(e) => {
+            error!("Failed to list publications to republish: {:#}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response();
+        }
+    };
+    info!(
+        "Republishing {} publication(s) rendered before renderer version {}",
+        publications.len(),
+        older_than
+    );
+
+    let mut republished = Vec::new();
+    let mut failed = Vec::new();
+    for publication in publications {
+        let job = match publication.diff_id {
+            Some(diff_id) => state.jobs.start("diff", diff_id),
+            None => state.jobs.start("snapshot", publication.snapshot_id),
+        };
+        let audit_entry = audit::Entry {
+            actor: &actor,
+            endpoint: "POST /api/republish",
+            target_id: publication.id,
+            parameters: audit::parameters(&params),
+        };
+        let result = republish(&state, &publication, &job).await;
+        let outcome = match &result {
+            Ok(page_url) => audit::Outcome::Ok(vec![page_url.clone()]),
+            Err(e) => audit::Outcome::Error(e),
+        };
+        audit::record(&state, audit_entry, outcome).await;
+        match result {
+            Ok(page_url) => republished.push(Republished {
+                publication_id: publication.id,
+                kind: publication.kind,
+                snapshot_id: publication.snapshot_id,
+                diff_id: publication.diff_id,
+                renderer_version: publication.renderer_version,
+                page_url,
+            }),
+            Err(e) => {
+                error!("Failed to republish publication {}: {:#}", publication.id, e);
+                job.fail(&e);
+                failed.push(RepublishFailure {
+                    publication_id: publication.id,
+                    kind: publication.kind,
+                    snapshot_id: publication.snapshot_id,
+                    diff_id: publication.diff_id,
+                    error: format!("{:#}", e),
+                });
+            }
+        }
+    }
+
+    let status = match failed.is_empty() {
+        true => "ok",
+        false => "error",
+    };
+    Json(RepublishResponse {
+        status,
+        renderer_version: RENDERER_VERSION,
+        republished,
+        failed,
+    })
+    .into_response()
+}
+
+/// Publish a recorded publication's snapshot or diff live again with the current renderer.
+/// Titles do not depend on the publish time, so the pages are updated in place. A snapshot
+/// publication whose kind no longer matches its target's publish mode is refused: in the
+/// other mode it would publish an old snapshot over the current pages.
+async fn republish(
+    state: &AppState,
+    publication: &PublicationRecord,
+    job: &JobHandle,
+) -> Result<String> {
+    let params = PublishParams {
+        draft: Some(false),
+        ..Default::default()
+    };
+    if let Some(diff_id) = publication.diff_id {
+        let diff_row = db::fetch_diff(&state.pool, diff_id).await?;
+        let published = publish::publish_diff(state, diff_row, params, job).await?;
+        return Ok(published.page.web_url);
+    }
+
+    let confluence_config = state
+        .confluence_configs
+        .fetch(&state.pool, publication.akeneo_server_id)
+        .await
+        .context("Failed to fetch Confluence configuration")?;
+    let (mode, expected) = match PublishMode::parse(&confluence_config.publish_mode) {
+        PublishMode::Overwrite => ("overwrite", PublicationKind::Overwrite),
+        PublishMode::Versioned => ("versioned", PublicationKind::Version),
+    };
+    if publication.kind != expected.as_str() {
+        bail!(
+            "The target now publishes in {} mode, so the '{}' publication is left alone",
+            mode,
+            publication.kind
+        );
+    }
+    let snapshot = db::fetch_snapshot(&state.pool, publication.snapshot_id).await?;
+    let published = publish::publish_snapshot(state, &snapshot, params, job).await?;
+    Ok(published.web_url)
+}
@@ -0,0 +1,318 @@
+/// Tags kept (without attributes, except a safe `href` on links).
+const ALLOWED_TAGS: &[&str] = &[
+    "a", "b", "blockquote", "br", "code", "em", "i", "li", "ol", "p", "pre", "s", "strong",
+    "sub", "sup", "u", "ul",
+];
+
+/// Tags that never have content of their own.
+const VOID_TAGS: &[&str] = &["br", "hr", "img"];
+
+/// Tags dropped together with their content.
+const DROPPED_TAGS: &[&str] = &["iframe", "noscript", "object", "script", "style", "template"];
+
+/// Tags that are dropped but whose content is kept. Together with the lists above these
+/// are the tags that make a value count as HTML (see [`looks_like_html`]).
+const UNWRAPPED_TAGS: &[&str] = &[
+    "article", "div", "font", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "img", "section",
+    "span", "table", "tbody", "td", "th", "thead", "tr",
+];
+
+/// Tags that separate words, for [`html_to_text`].
+const BLOCK_TAGS: &[&str] = &[
+    "blockquote", "br", "div", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "li", "p", "pre", "td",
+    "th", "tr",
+];
+
+/// Link schemes an `href` may use.
+const ALLOWED_SCHEMES: &[&str] = &["http://", "https://", "mailto:"];
+
+/// Whether a text value contains HTML tags worth rendering rather than escaping. Plain
+/// text with the odd `<` (e.g. `size < 10`) does not count.
+pub fn looks_like_html(value: &str) -> bool {
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(tag) = parse_tag(rest)
+            && is_known(&tag.name)
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// Sanitize a rich-text value from Akeneo (attribute guidelines, descriptions, ...) so it
+/// can be embedded in Confluence storage format as formatted text.
+///
+/// The result is well-formed XHTML using only [`ALLOWED_TAGS`]: other tags are
+/// unwrapped (or dropped with their content, for scripts and styles), attributes are
+/// removed, stray closing tags are dropped and open ones closed, and text is re-escaped.
+pub fn sanitize_html(value: &str) -> String {
+    let mut out = String::new();
+    let mut open: Vec<String> = Vec::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find('<') {
+        push_text(&mut out, &rest[..start]);
+        let after = &rest[start + 1..];
+
+        if let Some(comment) = after.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(tag) = parse_tag(after) else {
+            out.push_str("&lt;");
+            rest = after;
+            continue;
+        };
+        rest = &after[tag.len..];
+
+        if DROPPED_TAGS.contains(&tag.name.as_str()) {
+            if !tag.closing && !tag.self_closing {
+                rest = skip_element(rest, &tag.name);
+            }
+            continue;
+        }
+        if !ALLOWED_TAGS.contains(&tag.name.as_str()) {
+            continue;
+        }
+
+        if VOID_TAGS.contains(&tag.name.as_str()) {
+            if !tag.closing {
+                out.push_str(&format!("<{}/>", tag.name));
+            }
+        } else if tag.closing {
+            // Close up to the matching open tag; a closing tag that matches nothing is
+            // dropped
+            if let Some(index) = open.iter().rposition(|name| *name == tag.name) {
+                for name in open.drain(index..).rev() {
+                    out.push_str(&format!("</{}>", name));
+                }
+            }
+        } else if tag.self_closing {
+            out.push_str(&format!("<{0}></{0}>", tag.name));
+        } else {
+            match tag.href.as_deref().filter(|href| is_safe_href(href)) {
+                Some(href) if tag.name == "a" => {
+                    out.push_str(&format!("<a href=\"{}\">", escape(href)));
+                }
+                _ => out.push_str(&format!("<{}>", tag.name)),
+            }
+            open.push(tag.name);
+        }
+    }
+    push_text(&mut out, rest);
+
+    for name in open.into_iter().rev() {
+        out.push_str(&format!("</{}>", name));
+    }
+    out
+}
+
+/// The text of an HTML value without its tags and with entities decoded, e.g. to
+/// measure or cut it.
+pub fn html_to_text(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&decode_entities(&rest[..start]));
+        let after = &rest[start + 1..];
+        match parse_tag(after) {
+            Some(tag) => {
+                rest = &after[tag.len..];
+                if DROPPED_TAGS.contains(&tag.name.as_str()) && !tag.closing {
+                    rest = skip_element(rest, &tag.name);
+                } else if BLOCK_TAGS.contains(&tag.name.as_str()) && !out.ends_with(' ') {
+                    out.push(' ');
+                }
+            }
+            None => {
+                out.push('<');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(&decode_entities(rest));
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A parsed tag: its lowercase name, whether it is `</closing>` or `<self-closing/>`,
+/// the `href` attribute and how many bytes after the `<` it spans.
+struct Tag {
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    href: Option<String>,
+    len: usize,
+}
+
+/// Parse a tag from the text following a `<`; `None` when it is not one.
+fn parse_tag(after: &str) -> Option<Tag> {
+    let (closing, body) = match after.strip_prefix('/') {
+        Some(body) => (true, body),
+        None => (false, after),
+    };
+    let name_len = body
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(body.len());
+    if name_len == 0 || !body.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let end = find_tag_end(&body[name_len..])? + name_len;
+    let next = body[name_len..].chars().next()?;
+    if !(next.is_whitespace() || next == '>' || next == '/') {
+        return None;
+    }
+
+    let attributes = body[name_len..end].trim();
+    let self_closing = attributes.ends_with('/');
+    Some(Tag {
+        name: body[..name_len].to_ascii_lowercase(),
+        closing,
+        self_closing,
+        href: attribute(attributes.trim_end_matches('/'), "href"),
+        len: usize::from(closing) + end + 1,
+    })
+}
+
+/// Index of the `>` ending a tag, skipping any inside quoted attribute values.
+fn find_tag_end(attributes: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in attributes.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            (None, '<') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The decoded value of a quoted or bare attribute.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let key_len = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_len];
+        rest = rest[key_len..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, remainder) = match after.chars().next() {
+                    Some(q @ ('"' | '\'')) => {
+                        let inner = &after[1..];
+                        let end = inner.find(q).unwrap_or(inner.len());
+                        (&inner[..end], inner.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    }
+                };
+                rest = remainder;
+                Some(value)
+            }
+            None => None,
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return value.map(decode_entities);
+        }
+    }
+}
+
+/// The text after an element's closing tag (all of it when it is never closed).
+fn skip_element<'a>(rest: &'a str, name: &str) -> &'a str {
+    let lower = rest.to_ascii_lowercase();
+    match lower.find(&format!("</{}", name)) {
+        Some(start) => match rest[start..].find('>') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        },
+        None => "",
+    }
+}
+
+fn is_known(name: &str) -> bool {
+    [ALLOWED_TAGS, DROPPED_TAGS, UNWRAPPED_TAGS]
+        .iter()
+        .any(|tags| tags.contains(&name))
+}
+
+fn is_safe_href(href: &str) -> bool {
+    let href = href.trim().to_ascii_lowercase();
+    ALLOWED_SCHEMES.iter().any(|scheme| href.starts_with(scheme))
+}
+
+/// Append text with its entities decoded and then re-escaped, so valid entities survive
+/// and bare `&` or `>` cannot break the XHTML.
+fn push_text(out: &mut String, text: &str) {
+    out.push_str(&escape(&decode_entities(text)));
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Decode numeric entities and the common named ones; anything else is left as text. A
+/// numeric entity of a character XML does not allow (e.g. `&#0;`, `&#x1F;`, `&#xFFFE;`)
+/// becomes U+FFFD, which keeps the page body well-formed.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => match entity.strip_prefix('#') {
+                    Some(hex) if hex.starts_with(['x', 'X']) => {
+                        u32::from_str_radix(&hex[1..], 16).ok().and_then(char::from_u32)
+                    }
+                    Some(dec) => dec.parse().ok().and_then(char::from_u32),
+                    None => None,
+                }
+                .map(|c| if is_xml_char(c) { c } else { char::REPLACEMENT_CHARACTER }),
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whether XML 1.0 allows a character in a document (`char` already excludes surrogates).
+fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | '\u{20}'..='\u{fffd}' | '\u{10000}'..)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,23 @@
+//! Entity decoding of the sanitizer: characters XML does not allow never reach a page body.
+
+use super::*;
+
+#[test]
+fn decodes_named_and_numeric_entities() {
+    assert_eq!(decode_entities("a &lt; b &amp;&#65;&#x42;&nbsp;"), "a < b &AB\u{a0}");
+    assert_eq!(decode_entities("&#9;&#xA;&#13;"), "\t\n\r");
+    assert_eq!(decode_entities("&unknown; & &#xZZ;"), "&unknown; & &#xZZ;");
+}
+
+#[test]
+fn replaces_entities_of_characters_xml_does_not_allow() {
+    for entity in ["&#0;", "&#x0;", "&#x1;", "&#8;", "&#x1F;", "&#xFFFE;", "&#xffff;"] {
+        assert_eq!(decode_entities(entity), "\u{fffd}", "{}", entity);
+    }
+    // Surrogates are not characters at all and stay text
+    assert_eq!(decode_entities("&#xD800;"), "&#xD800;");
+    assert_eq!(decode_entities("&#x20;&#xFFFD;&#x10000;"), " \u{fffd}\u{10000}");
+
+    assert_eq!(sanitize_html("<p>a&#0;b&#x1;</p>"), "<p>a\u{fffd}b\u{fffd}</p>");
+    assert_eq!(html_to_text("a&#xFFFE;b"), "a\u{fffd}b");
+}
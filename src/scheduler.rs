@@ -0,0 +1,351 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::config::Capability;
+use crate::db::{self, ScheduleRow};
+use crate::extract::{Json, Path};
+use crate::{admin, akeneo, audit, publish, AppState, ErrorResponse};
+
+/// Runs the cron schedules stored in `publish_schedule`.
+///
+/// Schedules are re-read from the database on every tick, so rows added, edited, or
+/// toggled take effect without a restart. A schedule never overlaps itself: a tick that
+/// falls due while the previous run is still going is skipped.
+#[derive(Default)]
+pub struct Scheduler {
+    running: Mutex<HashSet<Uuid>>,
+}
+
+/// Inspectable state of one schedule, returned by `GET /api/schedules`.
+#[derive(Serialize)]
+pub struct ScheduleStatus {
+    pub id: Uuid,
+    pub akeneo_server_id: Uuid,
+    pub server_name: String,
+    pub cron: String,
+    pub action: String,
+    pub enabled: bool,
+    pub running: bool,
+    /// `None` when the schedule is disabled or its cron expression is invalid.
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cron_error: Option<String>,
+}
+
+/// Request body of `PATCH /api/schedules/{id}`.
+#[derive(Deserialize)]
+pub struct UpdateSchedule {
+    enabled: bool,
+}
+
+/// JSON response returned after a schedule is toggled.
+#[derive(Serialize)]
+struct ScheduleUpdated {
+    status: &'static str,
+    id: Uuid,
+    enabled: bool,
+}
+
+/// Parse a cron expression. Standard 5-field expressions (`min hour dom month dow`) are
+/// accepted alongside the 6/7-field form with seconds; all times are UTC.
+pub fn parse_cron(expr: &str) -> Result<Schedule> {
+    let expr = expr.trim();
+    let expr = if expr.split_whitespace().count() == 5 {
+        format!("0 {}", expr)
+    } else {
+        expr.to_string()
+    };
+    Schedule::from_str(&expr).with_context(|| format!("Invalid cron expression '{}'", expr))
+}
+
+impl Scheduler {
+    fn is_running(&self, schedule_id: Uuid) -> bool {
+        self.running.lock().unwrap().contains(&schedule_id)
+    }
+
+    /// Mark a schedule as running; returns false if it already was.
+    fn try_start(&self, schedule_id: Uuid) -> bool {
+        self.running.lock().unwrap().insert(schedule_id)
+    }
+
+    fn finish(&self, schedule_id: Uuid) {
+        self.running.lock().unwrap().remove(&schedule_id);
+    }
+
+    /// Current state of every schedule, with the next fire time of enabled ones.
+    pub async fn statuses(&self, state: &AppState) -> Result<Vec<ScheduleStatus>> {
+        let now = Utc::now();
+        let rows = db::fetch_schedules(&state.pool).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let (next_run_at, cron_error) = match parse_cron(&row.cron) {
+                    Ok(schedule) => (
+                        schedule.after(&now).next().filter(|_| row.enabled),
+                        None,
+                    ),
+                    Err(e) => (None, Some(format!("{:#}", e))),
+                };
+                ScheduleStatus {
+                    running: self.is_running(row.id),
+                    id: row.id,
+                    akeneo_server_id: row.akeneo_server_id,
+                    server_name: row.server_name,
+                    cron: row.cron,
+                    action: row.action,
+                    enabled: row.enabled,
+                    next_run_at,
+                    last_run_at: row.last_run_at,
+                    last_status: row.last_status,
+                    last_error: row.last_error,
+                    cron_error,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Start the scheduler loop. `SCHEDULER_TICK_SECS` (default 30) sets how often schedules
+/// are checked; the `features.scheduler` flag (`SCHEDULER_ENABLED=false`) turns the
+/// scheduler off for this instance.
+pub fn spawn(state: AppState) {
+    if !state.config.features.scheduler {
+        info!("Scheduler disabled (features.scheduler / SCHEDULER_ENABLED)");
+        return;
+    }
+    // Every scheduled run publishes
+    if !state.config.features.publishing {
+        info!("Scheduler disabled: publishing is disabled on this deployment");
+        return;
+    }
+    let tick = std::env::var("SCHEDULER_TICK_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&secs| secs > 0)
+        .unwrap_or(30);
+
+    info!("Scheduler enabled (tick={}s)", tick);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(tick));
+        let mut last_tick = Utc::now();
+
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+
+            let schedules = match db::fetch_schedules(&state.pool).await {
+                Ok(rows) => rows,
+                Err(e) => {
+                    error!("Scheduler: failed to load schedules: {:#}", e);
+                    continue;
+                }
+            };
+
+            for row in schedules.into_iter().filter(|row| row.enabled) {
+                let schedule = match parse_cron(&row.cron) {
+                    Ok(schedule) => schedule,
+                    Err(e) => {
+                        // Reported by GET /api/schedules; avoid logging it on every tick
+                        debug!("Scheduler: skipping schedule {}: {:#}", row.id, e);
+                        continue;
+                    }
+                };
+                // Fire once if any occurrence fell in (last_tick, now]
+                let due = schedule
+                    .after(&last_tick)
+                    .next()
+                    .is_some_and(|at| at <= now);
+                if !due {
+                    continue;
+                }
+                if !state.scheduler.try_start(row.id) {
+                    warn!(
+                        "Scheduler: schedule {} is still running, skipping this occurrence",
+                        row.id
+                    );
+                    continue;
+                }
+
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let schedule_id = row.id;
+                    run_schedule(&state, row).await;
+                    state.scheduler.finish(schedule_id);
+                });
+            }
+
+            last_tick = now;
+        }
+    });
+}
+
+/// Run a schedule's action once and record the outcome on its row.
+async fn run_schedule(state: &AppState, row: ScheduleRow) {
+    let started_at = Utc::now();
+    info!(
+        "Scheduler: running {} schedule {} for '{}'",
+        row.action, row.id, row.server_name
+    );
+
+    let outcome = match row.action.as_str() {
+        "snapshot" => run_snapshot(state, &row, started_at).await,
+        "diff" => run_diff(state, &row).await,
+        other => Err(anyhow::anyhow!("Unknown schedule action '{}'", other)),
+    };
+
+    let endpoint = format!("scheduler:{}", row.action);
+    let audit_entry = audit::Entry {
+        actor: "scheduler",
+        endpoint: &endpoint,
+        target_id: row.akeneo_server_id,
+        parameters: serde_json::json!({ "schedule_id": row.id }),
+    };
+    let audit_outcome = match &outcome {
+        Ok(Some(url)) => audit::Outcome::Ok(vec![url.clone()]),
+        Ok(None) => audit::Outcome::Skipped,
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(state, audit_entry, audit_outcome).await;
+
+    let (status, error) = match &outcome {
+        Ok(Some(url)) => {
+            info!("Scheduler: schedule {} published {}", row.id, url);
+            ("ok", None)
+        }
+        Ok(None) => ("skipped", None),
+        Err(e) => {
+            error!("Scheduler: schedule {} failed: {:#}", row.id, e);
+            ("error", Some(format!("{:#}", e)))
+        }
+    };
+
+    if let Err(e) =
+        db::record_schedule_run(&state.pool, row.id, started_at, status, error.as_deref()).await
+    {
+        error!("Scheduler: {:#}", e);
+    }
+}
+
+/// Fetch the live model, store it as a snapshot, and publish it. Returns the page URL.
+async fn run_snapshot(
+    state: &AppState,
+    row: &ScheduleRow,
+    started_at: DateTime<Utc>,
+) -> Result<Option<String>> {
+    state.config.features.ensure(Capability::SnapshotFetch)?;
+    let label = format!("Scheduled {}", started_at.format("%Y-%m-%d"));
+    let snapshot_id = {
+        let _job = state.jobs.start("fetch", row.akeneo_server_id);
+        akeneo::fetch_and_store_snapshot(state, row.akeneo_server_id, Some(&label)).await?
+    };
+
+    let snapshot = db::fetch_snapshot(&state.pool, snapshot_id).await?;
+    let job = state.jobs.start("snapshot", snapshot_id);
+    let result =
+        publish::publish_snapshot(state, &snapshot, publish::PublishParams::default(), &job)
+            .await
+            .inspect_err(|e| job.fail(e))?;
+    Ok(Some(result.web_url))
+}
+
+/// Publish the server's most recent diff. Returns `None` if the server has no diffs.
+async fn run_diff(state: &AppState, row: &ScheduleRow) -> Result<Option<String>> {
+    let Some(diff_id) = db::fetch_latest_diff_id(&state.pool, row.akeneo_server_id).await? else {
+        info!(
+            "Scheduler: no diff exists yet for '{}', skipping",
+            row.server_name
+        );
+        return Ok(None);
+    };
+
+    let job = state.jobs.start("diff", diff_id);
+    let diff_row = db::fetch_diff(&state.pool, diff_id).await?;
+    let result = publish::publish_diff(state, diff_row, publish::PublishParams::default(), &job)
+        .await
+        .inspect_err(|e| job.fail(e))?;
+    Ok(Some(result.page.web_url))
+}
+
+/// GET /api/schedules
+///
+/// Lists every publish schedule with its next run time and last outcome.
+pub async fn handle_list_schedules(State(state): State<AppState>) -> impl IntoResponse {
+    match state.scheduler.statuses(&state).await {
+        Ok(statuses) => (StatusCode::OK, Json(statuses)).into_response(),
+        Err(e) => {
+            error!("Failed to list schedules: {:#}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// PATCH /api/schedules/:id (admin)
+///
+/// Enables or disables a schedule: `{"enabled": false}`.
+pub async fn handle_update_schedule(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(schedule_id): Path<Uuid>,
+    Json(update): Json<UpdateSchedule>,
+) -> impl IntoResponse {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+
+    match db::set_schedule_enabled(&state.pool, schedule_id, update.enabled).await {
+        Ok(()) => {
+            info!(
+                "Schedule {} {}",
+                schedule_id,
+                if update.enabled { "enabled" } else { "disabled" }
+            );
+            (
+                StatusCode::OK,
+                Json(ScheduleUpdated {
+                    status: "ok",
+                    id: schedule_id,
+                    enabled: update.enabled,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) if db::is_not_found(&e) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(format!(
+                "Schedule not found: {}",
+                schedule_id
+            ))),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to update schedule {}: {:#}", schedule_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
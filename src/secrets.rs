@@ -0,0 +1,170 @@
+use anyhow::{bail, Context, Result};
+use std::future::Future;
+use std::pin::Pin;
+use tracing::info;
+
+/// Future returned by `SecretResolver::resolve`.
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
+
+/// Schemes recognised as secret references even when no resolver is registered for
+/// them, so a misconfigured reference fails loudly instead of being sent as a token.
+const KNOWN_SCHEMES: &[&str] = &["env", "vault"];
+
+/// A source of secrets addressed by `<scheme>:<path>` references.
+pub trait SecretResolver: Send + Sync {
+    /// The reference prefix handled by this resolver, without the colon (e.g. `env`).
+    fn scheme(&self) -> &'static str;
+
+    /// Resolve the part of the reference after `<scheme>:`.
+    fn resolve<'a>(&'a self, path: &'a str) -> ResolveFuture<'a>;
+}
+
+/// Resolves secret references stored in place of literal secrets, e.g.
+/// `confluence_config.api_token = 'env:CONFLUENCE_TOKEN_ACME'`.
+///
+/// Values without a registered or known scheme are returned unchanged, so literal
+/// (or decrypted) tokens keep working.
+pub struct Secrets {
+    resolvers: Vec<Box<dyn SecretResolver>>,
+}
+
+impl Secrets {
+    /// Register the built-in resolvers: `env:` always, `vault:` when `VAULT_ADDR` is set.
+    pub fn from_env(http: reqwest::Client) -> Self {
+        let mut resolvers: Vec<Box<dyn SecretResolver>> = vec![Box::new(EnvResolver)];
+        if let Some(vault) = VaultResolver::from_env(http) {
+            info!("Vault secret references enabled ({})", vault.addr);
+            resolvers.push(Box::new(vault));
+        }
+        Self { resolvers }
+    }
+
+    /// Resolve a stored value: secret references are looked up, anything else is
+    /// returned as-is.
+    pub async fn resolve(&self, value: &str) -> Result<String> {
+        let Some((scheme, path)) = value.split_once(':') else {
+            return Ok(value.to_string());
+        };
+        match self.resolvers.iter().find(|r| r.scheme() == scheme) {
+            Some(resolver) => resolver
+                .resolve(path)
+                .await
+                .with_context(|| format!("Failed to resolve secret reference '{}'", value)),
+            None if KNOWN_SCHEMES.contains(&scheme) => bail!(
+                "Secret reference '{}' uses '{}:' but that resolver is not configured",
+                value,
+                scheme
+            ),
+            None => Ok(value.to_string()),
+        }
+    }
+}
+
+/// Whether a stored value is a secret reference rather than a secret.
+pub fn is_reference(value: &str) -> bool {
+    value
+        .split_once(':')
+        .is_some_and(|(scheme, _)| KNOWN_SCHEMES.contains(&scheme))
+}
+
+/// How an API shows a stored secret: a reference as stored, a secret as `********`.
+pub fn redact(value: &str) -> String {
+    if is_reference(value) {
+        value.to_string()
+    } else {
+        "********".to_string()
+    }
+}
+
+/// `env:NAME` — reads the environment variable `NAME`.
+struct EnvResolver;
+
+impl SecretResolver for EnvResolver {
+    fn scheme(&self) -> &'static str {
+        "env"
+    }
+
+    fn resolve<'a>(&'a self, path: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            match std::env::var(path) {
+                Ok(value) if !value.is_empty() => Ok(value),
+                _ => bail!("Environment variable {} is not set", path),
+            }
+        })
+    }
+}
+
+/// `vault:<mount>/<path>#<field>` — reads a field from a HashiCorp Vault KV secret.
+///
+/// Configured with `VAULT_ADDR`, `VAULT_TOKEN`, optional `VAULT_NAMESPACE`, and
+/// `VAULT_KV_VERSION` (`2` by default, `1` for the legacy KV engine). The field defaults
+/// to `token` when the reference has no `#field` suffix.
+struct VaultResolver {
+    http: reqwest::Client,
+    addr: String,
+    token: String,
+    namespace: Option<String>,
+    kv_version: u8,
+}
+
+impl VaultResolver {
+    fn from_env(http: reqwest::Client) -> Option<Self> {
+        let addr = std::env::var("VAULT_ADDR").ok().filter(|a| !a.is_empty())?;
+        Some(Self {
+            http,
+            addr: addr.trim_end_matches('/').to_string(),
+            token: std::env::var("VAULT_TOKEN").unwrap_or_default(),
+            namespace: std::env::var("VAULT_NAMESPACE").ok().filter(|n| !n.is_empty()),
+            kv_version: match std::env::var("VAULT_KV_VERSION").as_deref() {
+                Ok("1") => 1,
+                _ => 2,
+            },
+        })
+    }
+
+    /// API URL of a secret. KV v2 inserts `data/` after the mount.
+    fn secret_url(&self, path: &str) -> Result<String> {
+        let (mount, rest) = path
+            .split_once('/')
+            .with_context(|| format!("Vault path '{}' must be <mount>/<path>", path))?;
+        Ok(match self.kv_version {
+            1 => format!("{}/v1/{}/{}", self.addr, mount, rest),
+            _ => format!("{}/v1/{}/data/{}", self.addr, mount, rest),
+        })
+    }
+}
+
+impl SecretResolver for VaultResolver {
+    fn scheme(&self) -> &'static str {
+        "vault"
+    }
+
+    fn resolve<'a>(&'a self, path: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let (path, field) = path.split_once('#').unwrap_or((path, "token"));
+            let url = self.secret_url(path)?;
+
+            let mut request = self.http.get(&url).header("X-Vault-Token", &self.token);
+            if let Some(namespace) = &self.namespace {
+                request = request.header("X-Vault-Namespace", namespace);
+            }
+            let response = request.send().await.context("Vault request failed")?;
+            let status = response.status();
+            if !status.is_success() {
+                bail!("Vault returned {} for {}", status, path);
+            }
+            let body: serde_json::Value =
+                response.json().await.context("Failed to parse Vault response")?;
+
+            // KV v2 nests the secret's key/value pairs one level deeper
+            let data = match self.kv_version {
+                1 => &body["data"],
+                _ => &body["data"]["data"],
+            };
+            data[field]
+                .as_str()
+                .map(str::to_string)
+                .with_context(|| format!("Vault secret {} has no string field '{}'", path, field))
+        })
+    }
+}
@@ -0,0 +1,392 @@
+use anyhow::Result;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::audit::{self, Actor};
+use crate::db::{self, AkeneoServerChanges, AkeneoServerRecord, NewAkeneoServer};
+use crate::extract::{Json, Path};
+use crate::{admin, confluence_configs, secrets, AppState, ErrorResponse};
+
+/// An Akeneo server as returned by the registry endpoints. Secrets are write-only: a
+/// secret reference (`env:` / `vault:`) is shown as stored, a literal secret is masked.
+#[derive(Serialize)]
+struct ServerView {
+    id: Uuid,
+    name: String,
+    base_url: String,
+    client_id: String,
+    client_secret: String,
+    username: String,
+    password: String,
+    /// Signs requests to the Akeneo event receiver; `None` when the receiver is off.
+    webhook_secret: Option<String>,
+    /// Snapshots stored for the server.
+    snapshots: i64,
+    confluence_targets: Vec<ConfluenceTarget>,
+}
+
+/// A `confluence_config` row linked to the server.
+#[derive(Serialize)]
+struct ConfluenceTarget {
+    id: Uuid,
+    base_url: String,
+    space_key: String,
+}
+
+/// Request body of `POST /api/servers`.
+#[derive(Deserialize, Serialize)]
+pub struct CreateServer {
+    name: String,
+    base_url: String,
+    client_id: String,
+    /// A literal secret or a secret reference, e.g. `env:AKENEO_SECRET`; not audited.
+    #[serde(skip_serializing)]
+    client_secret: String,
+    username: String,
+    #[serde(skip_serializing)]
+    password: String,
+    /// Turns on the Akeneo event receiver for the server; not audited.
+    #[serde(default, skip_serializing)]
+    webhook_secret: Option<String>,
+}
+
+/// Request body of `PATCH /api/servers/{id}`: only the given fields change.
+#[derive(Deserialize, Serialize)]
+pub struct UpdateServer {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
+    #[serde(skip_serializing)]
+    client_secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+    #[serde(skip_serializing)]
+    password: Option<String>,
+    /// `null` or an empty string turns the event receiver off.
+    #[serde(
+        default,
+        deserialize_with = "confluence_configs::nullable",
+        skip_serializing
+    )]
+    webhook_secret: Option<Option<String>>,
+}
+
+/// JSON response returned after a server is deleted.
+#[derive(Serialize)]
+struct ServerDeleted {
+    status: &'static str,
+    id: Uuid,
+}
+
+impl CreateServer {
+    fn changes(&self) -> AkeneoServerChanges<'_> {
+        AkeneoServerChanges {
+            name: Some(&self.name),
+            base_url: Some(&self.base_url),
+            client_id: Some(&self.client_id),
+            client_secret: Some(&self.client_secret),
+            username: Some(&self.username),
+            password: Some(&self.password),
+            webhook_secret: Some(self.webhook_secret.as_deref()),
+        }
+    }
+}
+
+impl UpdateServer {
+    fn changes(&self) -> AkeneoServerChanges<'_> {
+        AkeneoServerChanges {
+            name: self.name.as_deref(),
+            base_url: self.base_url.as_deref(),
+            client_id: self.client_id.as_deref(),
+            client_secret: self.client_secret.as_deref(),
+            username: self.username.as_deref(),
+            password: self.password.as_deref(),
+            webhook_secret: self.webhook_secret.as_ref().map(Option::as_deref),
+        }
+    }
+}
+
+/// Check the given fields, with a message for the client: none may be blank (except
+/// `webhook_secret`, which a blank value clears) and the base URL must be an http(s) URL.
+fn validate(changes: &AkeneoServerChanges<'_>) -> Result<(), String> {
+    let fields = [
+        ("name", changes.name),
+        ("base_url", changes.base_url),
+        ("client_id", changes.client_id),
+        ("client_secret", changes.client_secret),
+        ("username", changes.username),
+        ("password", changes.password),
+    ];
+    for (field, value) in fields {
+        if value.is_some_and(|v| v.trim().is_empty()) {
+            return Err(format!("{} must not be empty", field));
+        }
+    }
+    if let Some(base_url) = changes.base_url.filter(|url| !is_http_url(url)) {
+        return Err(format!("base_url '{}' is not an http(s) URL", base_url));
+    }
+    Ok(())
+}
+
+/// Whether a base URL is an absolute http(s) URL.
+pub fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url.trim())
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+}
+
+/// Trim the fields, and the base URL's trailing slashes; a blank `webhook_secret` becomes
+/// `Some(None)`.
+fn trimmed(changes: AkeneoServerChanges<'_>) -> AkeneoServerChanges<'_> {
+    AkeneoServerChanges {
+        name: changes.name.map(str::trim),
+        base_url: changes.base_url.map(|url| url.trim().trim_end_matches('/')),
+        client_id: changes.client_id.map(str::trim),
+        client_secret: changes.client_secret.map(str::trim),
+        username: changes.username.map(str::trim),
+        password: changes.password,
+        webhook_secret: changes
+            .webhook_secret
+            .map(|secret| secret.map(str::trim).filter(|secret| !secret.is_empty())),
+    }
+}
+
+/// The servers with their Confluence targets; every server, or only `server_id`.
+async fn fetch_views(state: &AppState, server_id: Option<Uuid>) -> Result<Vec<ServerView>> {
+    let (records, targets) = tokio::try_join!(
+        db::fetch_akeneo_server_records(&state.pool, server_id),
+        db::fetch_confluence_targets(&state.pool, server_id),
+    )?;
+    Ok(records
+        .into_iter()
+        .map(|record: AkeneoServerRecord| ServerView {
+            id: record.id,
+            name: record.name,
+            base_url: record.base_url,
+            client_id: record.client_id,
+            client_secret: secrets::redact(&record.client_secret),
+            username: record.username,
+            password: secrets::redact(&record.password),
+            webhook_secret: record.webhook_secret.as_deref().map(secrets::redact),
+            snapshots: record.snapshots,
+            confluence_targets: targets
+                .iter()
+                .filter(|target| target.akeneo_server_id == record.id)
+                .map(|target| ConfluenceTarget {
+                    id: target.id,
+                    base_url: target.base_url.clone(),
+                    space_key: target.space_key.clone(),
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+/// One server's view, with `status` as the response status; 404 when it does not exist.
+async fn server_response(state: &AppState, server_id: Uuid, status: StatusCode) -> Response {
+    match fetch_views(state, Some(server_id)).await {
+        Ok(mut views) if !views.is_empty() => (status, Json(views.remove(0))).into_response(),
+        Ok(_) => not_found(server_id),
+        Err(e) => internal_error("load Akeneo server", e),
+    }
+}
+
+/// GET /api/servers (admin)
+///
+/// Lists the Akeneo servers, by name, with their Confluence targets and snapshot counts.
+pub async fn handle_list_servers(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    match fetch_views(&state, None).await {
+        Ok(views) => (StatusCode::OK, Json(views)).into_response(),
+        Err(e) => internal_error("list Akeneo servers", e),
+    }
+}
+
+/// GET /api/servers/:id (admin)
+pub async fn handle_get_server(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(server_id): Path<Uuid>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    server_response(&state, server_id, StatusCode::OK).await
+}
+
+/// POST /api/servers (admin)
+///
+/// Registers an Akeneo server; answers 201 with the new server.
+pub async fn handle_create_server(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Actor(actor): Actor,
+    Json(request): Json<CreateServer>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    let changes = request.changes();
+    if let Err(message) = validate(&changes) {
+        return (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(message))).into_response();
+    }
+    let changes = trimmed(changes);
+    let server = NewAkeneoServer {
+        name: changes.name.unwrap_or_default(),
+        base_url: changes.base_url.unwrap_or_default(),
+        client_id: changes.client_id.unwrap_or_default(),
+        client_secret: changes.client_secret.unwrap_or_default(),
+        username: changes.username.unwrap_or_default(),
+        password: changes.password.unwrap_or_default(),
+        webhook_secret: changes.webhook_secret.flatten(),
+    };
+
+    let created = db::insert_akeneo_server(&state.pool, &server).await;
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "POST /api/servers",
+        target_id: *created.as_ref().unwrap_or(&Uuid::nil()),
+        parameters: audit::parameters(&request),
+    };
+    let outcome = match &created {
+        Ok(_) => audit::Outcome::Ok(Vec::new()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match created {
+        Ok(server_id) => {
+            info!("Registered Akeneo server {} ({})", server.name, server_id);
+            server_response(&state, server_id, StatusCode::CREATED).await
+        }
+        Err(e) => internal_error("create Akeneo server", e),
+    }
+}
+
+/// PATCH /api/servers/:id (admin)
+///
+/// Changes the given fields of a server, e.g. `{"base_url": "https://pim.example.com"}`.
+pub async fn handle_update_server(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Actor(actor): Actor,
+    Path(server_id): Path<Uuid>,
+    Json(request): Json<UpdateServer>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    let changes = request.changes();
+    if let Err(message) = validate(&changes) {
+        return (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(message))).into_response();
+    }
+
+    let updated = db::update_akeneo_server(&state.pool, server_id, &trimmed(changes)).await;
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "PATCH /api/servers/{id}",
+        target_id: server_id,
+        parameters: audit::parameters(&request),
+    };
+    let outcome = match &updated {
+        Ok(()) => audit::Outcome::Ok(Vec::new()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match updated {
+        Ok(()) => {
+            // Cached Confluence targets carry the server name (`{server}` in titles)
+            state.confluence_configs.invalidate(Some(server_id));
+            info!("Updated Akeneo server {}", server_id);
+            server_response(&state, server_id, StatusCode::OK).await
+        }
+        Err(e) if db::is_not_found(&e) => not_found(server_id),
+        Err(e) => internal_error("update Akeneo server", e),
+    }
+}
+
+/// DELETE /api/servers/:id (admin)
+///
+/// Deletes a server with its schedules and publication history. 409 while it still has
+/// snapshots or Confluence targets, which have to be removed first.
+pub async fn handle_delete_server(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Actor(actor): Actor,
+    Path(server_id): Path<Uuid>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    let view = match fetch_views(&state, Some(server_id)).await {
+        Ok(mut views) if !views.is_empty() => views.remove(0),
+        Ok(_) => return not_found(server_id),
+        Err(e) => return internal_error("load Akeneo server", e),
+    };
+    if view.snapshots > 0 || !view.confluence_targets.is_empty() {
+        let message = format!(
+            "Akeneo server {} still has {} snapshot(s) and {} Confluence target(s)",
+            server_id,
+            view.snapshots,
+            view.confluence_targets.len()
+        );
+        return (StatusCode::CONFLICT, Json(ErrorResponse::new(message))).into_response();
+    }
+
+    let deleted = db::delete_akeneo_server(&state.pool, server_id).await;
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "DELETE /api/servers/{id}",
+        target_id: server_id,
+        parameters: serde_json::json!({ "name": view.name }),
+    };
+    let outcome = match &deleted {
+        Ok(()) => audit::Outcome::Ok(Vec::new()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match deleted {
+        Ok(()) => {
+            info!("Deleted Akeneo server {} ({})", view.name, server_id);
+            (
+                StatusCode::OK,
+                Json(ServerDeleted {
+                    status: "ok",
+                    id: server_id,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) if db::is_not_found(&e) => not_found(server_id),
+        Err(e) => internal_error("delete Akeneo server", e),
+    }
+}
+
+fn not_found(server_id: Uuid) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new(format!(
+            "Akeneo server not found: {}",
+            server_id
+        ))),
+    )
+        .into_response()
+}
+
+fn internal_error(action: &str, e: anyhow::Error) -> Response {
+    error!("Failed to {}: {:#}", action, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(format!("{:#}", e))),
+    )
+        .into_response()
+}
@@ -0,0 +1,118 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration as ChronoDuration, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::audit::{self, Actor};
+use crate::config::RetentionAction;
+use crate::db;
+use crate::extract::Path;
+use crate::{admin, AppState, ErrorResponse};
+
+/// How often snapshots past the retention period are retired.
+const RETIRE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize)]
+struct SnapshotRestored {
+    status: &'static str,
+    id: Uuid,
+}
+
+/// Start the daily job that archives or soft-deletes snapshots older than
+/// `snapshot_retention.days`; nothing runs when it is 0. The first pass runs at startup.
+pub fn spawn(state: AppState) {
+    let retention = state.config.snapshot_retention.clone();
+    if retention.days == 0 {
+        info!("Snapshot retention disabled; snapshots are kept forever");
+        return;
+    }
+    let verb = match retention.action {
+        RetentionAction::Archive => "Archived",
+        RetentionAction::Delete => "Soft-deleted",
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETIRE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let cutoff = Utc::now() - ChronoDuration::days(retention.days.into());
+            let retired = db::retire_snapshots(
+                &state.pool,
+                cutoff,
+                retention.keep_every,
+                retention.action,
+            )
+            .await;
+            match retired {
+                Ok(0) => {}
+                Ok(count) => info!(
+                    "{} {} snapshots older than {} days",
+                    verb, count, retention.days
+                ),
+                Err(e) => error!("Snapshot retention: {:#}", e),
+            }
+        }
+    });
+}
+
+/// `POST /api/admin/snapshots/:id/restore`: un-archive or undelete a snapshot. A restored
+/// snapshot is never retired again by the retention job.
+pub async fn handle_restore_snapshot(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Actor(actor): Actor,
+    Path(snapshot_id): Path<Uuid>,
+) -> Response {
+    if let Err(rejection) = admin::require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+
+    let restored = db::restore_snapshot(&state.pool, snapshot_id).await;
+    let audit_entry = audit::Entry {
+        actor: &actor,
+        endpoint: "POST /api/admin/snapshots/{id}/restore",
+        target_id: snapshot_id,
+        parameters: serde_json::json!({}),
+    };
+    let outcome = match &restored {
+        Ok(()) => audit::Outcome::Ok(Vec::new()),
+        Err(e) => audit::Outcome::Error(e),
+    };
+    audit::record(&state, audit_entry, outcome).await;
+    match restored {
+        Ok(()) => {
+            info!("Restored snapshot {}", snapshot_id);
+            (
+                StatusCode::OK,
+                Json(SnapshotRestored {
+                    status: "ok",
+                    id: snapshot_id,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) if db::is_not_found(&e) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(format!(
+                "Snapshot not found: {}",
+                snapshot_id
+            ))),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to restore snapshot {}: {:#}", snapshot_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(format!("{:#}", e))),
+            )
+                .into_response()
+        }
+    }
+}
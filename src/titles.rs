@@ -0,0 +1,168 @@
+use crate::db::DbConfluenceConfig;
+
+/// Default title of the snapshot root page.
+pub const DEFAULT_SNAPSHOT_TITLE: &str = "Current model";
+/// Default title of a family child page.
+pub const DEFAULT_FAMILY_TITLE: &str = "Family: {label} ({code})";
+/// Default title of a diff page.
+pub const DEFAULT_DIFF_TITLE: &str = "Diff: {before} \u{2192} {after}";
+/// Default title of a snapshot page in versioned publish mode.
+pub const DEFAULT_VERSION_TITLE: &str = "{label} ({date} {time})";
+/// Appended to the root page title to title the requirements coverage page, which keeps
+/// it unique per snapshot tree without a template of its own.
+pub const COVERAGE_TITLE_SUFFIX: &str = " \u{2014} Requirements coverage";
+/// Appended to the root page title to title the model health page, like
+/// `COVERAGE_TITLE_SUFFIX`.
+pub const HEALTH_TITLE_SUFFIX: &str = " \u{2014} Model health";
+/// Appended to the root page title to title the label completeness page, like
+/// `COVERAGE_TITLE_SUFFIX`.
+pub const LABELS_TITLE_SUFFIX: &str = " \u{2014} Label completeness";
+/// Appended to the root page title, followed by the root category's label and code, to
+/// title a category tree page.
+pub const CATEGORY_TREE_TITLE_SUFFIX: &str = " \u{2014} Category tree: ";
+/// Appended to the root page title, followed by the group's name, to title a family group
+/// page.
+pub const FAMILY_GROUP_TITLE_SUFFIX: &str = " \u{2014} Families: ";
+/// Appended to the root page title, followed by the attribute code and the page number, to
+/// title a page of a long attribute option list (see `option_page_title`).
+pub const OPTIONS_TITLE_SUFFIX: &str = " \u{2014} Options: ";
+/// Appended to the snapshot title (with only `{server}` filled in) to title the model
+/// activity page, a sibling of the diff pages it charts.
+pub const ACTIVITY_TITLE_SUFFIX: &str = " \u{2014} Model activity";
+/// Appended to the snapshot title (with only `{server}` filled in) to title a comparison
+/// matrix published without a title of its own.
+pub const MATRIX_TITLE_SUFFIX: &str = " \u{2014} Comparison matrix";
+/// Appended to the diff title of a cumulative diff, so it does not replace the page of a
+/// stored diff between the same two snapshots.
+pub const CUMULATIVE_TITLE_SUFFIX: &str = " (cumulative)";
+/// Title of the blog post announcing a diff (`content_type=blogpost`), dated by the
+/// after-snapshot. Blog posts sit outside the page tree, so it needs no target template.
+pub const BLOG_POST_TITLE: &str = "Model changes {date}: {before} \u{2192} {after}";
+/// Prefix of every staged draft page, keeping drafts from colliding with the live pages.
+pub const DRAFT_TITLE_PREFIX: &str = "[Draft] ";
+
+/// Page-title templates for one Confluence target, with placeholders
+/// `{server}`, `{label}`, `{code}`, `{date}`, `{time}`, `{before}` and `{after}`.
+///
+/// Templates let several Akeneo servers publish into the same space without their
+/// pages colliding (e.g. `"{server}: Current model"`).
+pub struct TitleTemplates {
+    pub snapshot: String,
+    pub family: String,
+    pub diff: String,
+    /// Title of each dated snapshot page in versioned mode (the index page uses `snapshot`).
+    pub version: String,
+}
+
+/// Values substituted into a title template. Unset placeholders become empty strings.
+#[derive(Default, Clone, Copy)]
+pub struct TitleContext<'a> {
+    pub server: &'a str,
+    pub label: &'a str,
+    pub code: &'a str,
+    pub date: &'a str,
+    pub time: &'a str,
+    pub before: &'a str,
+    pub after: &'a str,
+}
+
+impl Default for TitleTemplates {
+    fn default() -> Self {
+        Self {
+            snapshot: DEFAULT_SNAPSHOT_TITLE.to_string(),
+            family: DEFAULT_FAMILY_TITLE.to_string(),
+            diff: DEFAULT_DIFF_TITLE.to_string(),
+            version: DEFAULT_VERSION_TITLE.to_string(),
+        }
+    }
+}
+
+impl TitleTemplates {
+    /// Take the templates configured for a Confluence target, falling back to the
+    /// built-in titles for any that are unset or blank.
+    pub fn from_config(config: &DbConfluenceConfig) -> Self {
+        fn pick(template: &Option<String>, default: &str) -> String {
+            template
+                .as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .unwrap_or(default)
+                .to_string()
+        }
+
+        Self {
+            snapshot: pick(&config.snapshot_title_template, DEFAULT_SNAPSHOT_TITLE),
+            family: pick(&config.family_title_template, DEFAULT_FAMILY_TITLE),
+            diff: pick(&config.diff_title_template, DEFAULT_DIFF_TITLE),
+            version: pick(&config.version_title_template, DEFAULT_VERSION_TITLE),
+        }
+    }
+
+    /// The same templates with `DRAFT_TITLE_PREFIX` in front, for staged drafts.
+    pub fn into_draft(self) -> Self {
+        let draft = |template: String| format!("{}{}", DRAFT_TITLE_PREFIX, template);
+        Self {
+            snapshot: draft(self.snapshot),
+            family: draft(self.family),
+            diff: draft(self.diff),
+            version: draft(self.version),
+        }
+    }
+
+    pub fn snapshot_title(&self, ctx: &TitleContext) -> String {
+        apply_template(&self.snapshot, ctx)
+    }
+
+    pub fn family_title(&self, ctx: &TitleContext) -> String {
+        apply_template(&self.family, ctx)
+    }
+
+    pub fn diff_title(&self, ctx: &TitleContext) -> String {
+        apply_template(&self.diff, ctx)
+    }
+
+    pub fn version_title(&self, ctx: &TitleContext) -> String {
+        apply_template(&self.version, ctx)
+    }
+}
+
+/// Substitute placeholders in a title template in a single pass, so braces inside
+/// substituted values are never expanded again. Unknown placeholders are kept verbatim.
+pub fn apply_template(template: &str, ctx: &TitleContext) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after_brace = &rest[start..];
+        let Some(end) = after_brace.find('}') else {
+            rest = after_brace;
+            break;
+        };
+
+        let value = match &after_brace[1..end] {
+            "server" => Some(ctx.server),
+            "label" => Some(ctx.label),
+            "code" => Some(ctx.code),
+            "date" => Some(ctx.date),
+            "time" => Some(ctx.time),
+            "before" => Some(ctx.before),
+            "after" => Some(ctx.after),
+            _ => None,
+        };
+        match value {
+            Some(v) => out.push_str(v),
+            None => out.push_str(&after_brace[..=end]),
+        }
+        rest = &after_brace[end + 1..];
+    }
+    out.push_str(rest);
+
+    out.trim().to_string()
+}
+
+/// Title of page `page` (1-based) of the `pages` option pages of an attribute, under the
+/// root page titled `root_title`.
+pub fn option_page_title(root_title: &str, attribute: &str, page: usize, pages: usize) -> String {
+    format!("{}{}{} ({}/{})", root_title, OPTIONS_TITLE_SUFFIX, attribute, page, pages)
+}
@@ -0,0 +1,199 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::model::{Attribute, AttributeOption, Category, Channel, Family, Labels};
+
+/// A single structural problem found in a snapshot payload.
+#[derive(Serialize)]
+pub struct ValidationWarning {
+    /// JSON path of the offending value, e.g. `attributes[12].scopable`.
+    pub path: String,
+    /// One of `missing_key`, `wrong_type`, `empty_labels`, `missing_code`.
+    pub kind: &'static str,
+    pub message: String,
+}
+
+/// Entity types that carry a code and labels, shared by the per-entity checks.
+trait Entity: DeserializeOwned {
+    fn code(&self) -> &str;
+    fn labels(&self) -> &Labels;
+}
+
+macro_rules! impl_entity {
+    ($($ty:ty),*) => {
+        $(impl Entity for $ty {
+            fn code(&self) -> &str {
+                &self.code
+            }
+            fn labels(&self) -> &Labels {
+                &self.labels
+            }
+        })*
+    };
+}
+
+impl_entity!(Channel, Family, Attribute, Category, AttributeOption);
+
+/// Check raw snapshot data against the expected Akeneo model shape.
+///
+/// Unlike `Snapshot::from_value`, which stops at the first error, this walks every
+/// entity and reports all problems it finds. An empty result means the payload is sound.
+pub fn validate_snapshot(data: &Value) -> Vec<ValidationWarning> {
+    let mut warnings = Vec::new();
+
+    let Some(obj) = data.as_object() else {
+        warnings.push(warning("", "wrong_type", "snapshot data must be an object"));
+        return warnings;
+    };
+
+    let array_sections: [(&str, &[&str], EntityCheck); 4] = [
+        (
+            "channels",
+            &["code", "labels", "locales", "currencies", "category_tree"],
+            check_entity::<Channel>,
+        ),
+        (
+            "families",
+            &["code", "labels", "attributes", "attribute_as_label", "attribute_requirements"],
+            check_entity::<Family>,
+        ),
+        (
+            "attributes",
+            &["code", "type", "group", "labels", "scopable", "localizable"],
+            check_entity::<Attribute>,
+        ),
+        (
+            "categories",
+            &["code", "labels", "parent"],
+            check_entity::<Category>,
+        ),
+    ];
+
+    for (key, required, check) in array_sections {
+        match obj.get(key) {
+            None => warnings.push(warning(key, "missing_key", "key is missing")),
+            Some(Value::Array(items)) => {
+                for (i, item) in items.iter().enumerate() {
+                    check(&format!("{}[{}]", key, i), item, required, &mut warnings);
+                }
+            }
+            Some(other) => warnings.push(warning(
+                key,
+                "wrong_type",
+                &format!("expected an array, found {}", type_name(other)),
+            )),
+        }
+    }
+
+    match obj.get("attribute_options") {
+        None => warnings.push(warning("attribute_options", "missing_key", "key is missing")),
+        Some(Value::Object(by_attribute)) => {
+            for (attr_code, options) in by_attribute {
+                let path = format!("attribute_options.{}", attr_code);
+                match options {
+                    Value::Array(items) => {
+                        for (i, item) in items.iter().enumerate() {
+                            check_entity::<AttributeOption>(
+                                &format!("{}[{}]", path, i),
+                                item,
+                                &["code", "labels", "sort_order"],
+                                &mut warnings,
+                            );
+                        }
+                    }
+                    other => warnings.push(warning(
+                        &path,
+                        "wrong_type",
+                        &format!("expected an array, found {}", type_name(other)),
+                    )),
+                }
+            }
+        }
+        Some(other) => warnings.push(warning(
+            "attribute_options",
+            "wrong_type",
+            &format!("expected an object, found {}", type_name(other)),
+        )),
+    }
+
+    warnings
+}
+
+type EntityCheck = fn(&str, &Value, &[&str], &mut Vec<ValidationWarning>);
+
+/// Validate one entity: required keys present, value parses into the typed model,
+/// and it has a code and at least one label.
+fn check_entity<T: Entity>(
+    path: &str,
+    item: &Value,
+    required: &[&str],
+    warnings: &mut Vec<ValidationWarning>,
+) {
+    let Some(fields) = item.as_object() else {
+        warnings.push(warning(
+            path,
+            "wrong_type",
+            &format!("expected an object, found {}", type_name(item)),
+        ));
+        return;
+    };
+
+    for key in required {
+        if !fields.contains_key(*key) {
+            warnings.push(warning(
+                &format!("{}.{}", path, key),
+                "missing_key",
+                "key is missing",
+            ));
+        }
+    }
+
+    let entity: T = match serde_path_to_error::deserialize(item) {
+        Ok(entity) => entity,
+        Err(e) => {
+            let inner_path = e.path().to_string();
+            let full_path = if inner_path == "." {
+                path.to_string()
+            } else {
+                format!("{}.{}", path, inner_path)
+            };
+            warnings.push(warning(&full_path, "wrong_type", &e.inner().to_string()));
+            return;
+        }
+    };
+
+    if entity.code().is_empty() {
+        warnings.push(warning(
+            &format!("{}.code", path),
+            "missing_code",
+            "entity has no code",
+        ));
+    }
+    if entity.labels().is_empty() {
+        warnings.push(warning(
+            &format!("{}.labels", path),
+            "empty_labels",
+            &format!("'{}' has no labels", entity.code()),
+        ));
+    }
+}
+
+fn warning(path: &str, kind: &'static str, message: &str) -> ValidationWarning {
+    ValidationWarning {
+        path: path.to_string(),
+        kind,
+        message: message.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}